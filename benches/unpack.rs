@@ -0,0 +1,132 @@
+// Allows share test vectors between unit and integration tests
+pub(crate) use didcomm;
+
+#[allow(unused_imports, dead_code)]
+#[path = "../src/test_vectors/mod.rs"]
+mod test_vectors;
+
+use criterion::{async_executor::FuturesExecutor, criterion_group, criterion_main, Criterion};
+
+use didcomm::{
+    did::resolvers::ExampleDIDResolver, secrets::resolvers::ExampleSecretsResolver, Message,
+    UnpackOptions,
+};
+
+use test_vectors::{
+    ALICE_DID_DOC, BOB_DID_DOC, BOB_SECRETS, ENCRYPTED_MSG_ANON_XC20P_1, ENCRYPTED_MSG_AUTH_P256,
+    ENCRYPTED_MSG_AUTH_X25519, SIGNED_MSG_ALICE_KEY_1, SIGNED_MSG_ALICE_KEY_2,
+    SIGNED_MSG_ALICE_KEY_3,
+};
+
+// Here we have an async function to benchmark
+async fn unpack(
+    msg: &str,
+    did_resolver: &ExampleDIDResolver,
+    secrets_resolver: &ExampleSecretsResolver,
+    opts: &UnpackOptions,
+) {
+    Message::unpack(msg, did_resolver, secrets_resolver, opts)
+        .await
+        .expect("Unable unpack");
+}
+
+fn benchmarks(c: &mut Criterion) {
+    // Bob has secrets for all 3 recipient keys, so the first candidate key decrypts the message
+    {
+        let msg = ENCRYPTED_MSG_ANON_XC20P_1;
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+        let opts = UnpackOptions::default();
+
+        c.bench_function("unpack_anoncrypt_xc20p_x25519_3keys", move |b| {
+            b.to_async(FuturesExecutor)
+                .iter(|| unpack(msg, &did_resolver, &secrets_resolver, &opts));
+        });
+    }
+
+    // All 3 candidate keys are tried
+    {
+        let msg = ENCRYPTED_MSG_ANON_XC20P_1;
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let opts = UnpackOptions {
+            expect_decrypt_by_all_keys: true,
+            ..UnpackOptions::default()
+        };
+
+        c.bench_function(
+            "unpack_anoncrypt_xc20p_x25519_3keys_decrypt_by_all_keys",
+            move |b| {
+                b.to_async(FuturesExecutor)
+                    .iter(|| unpack(msg, &did_resolver, &secrets_resolver, &opts));
+            },
+        );
+    }
+
+    {
+        let msg = ENCRYPTED_MSG_AUTH_X25519;
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+        let opts = UnpackOptions::default();
+
+        c.bench_function("unpack_authcrypt_x25519_3keys", move |b| {
+            b.to_async(FuturesExecutor)
+                .iter(|| unpack(msg, &did_resolver, &secrets_resolver, &opts));
+        });
+    }
+
+    {
+        let msg = ENCRYPTED_MSG_AUTH_X25519;
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let opts = UnpackOptions {
+            expect_decrypt_by_all_keys: true,
+            ..UnpackOptions::default()
+        };
+
+        c.bench_function(
+            "unpack_authcrypt_x25519_3keys_decrypt_by_all_keys",
+            move |b| {
+                b.to_async(FuturesExecutor)
+                    .iter(|| unpack(msg, &did_resolver, &secrets_resolver, &opts));
+            },
+        );
+    }
+
+    {
+        let msg = ENCRYPTED_MSG_AUTH_P256;
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+        let opts = UnpackOptions::default();
+
+        c.bench_function("unpack_authcrypt_p256_sign_ed25519", move |b| {
+            b.to_async(FuturesExecutor)
+                .iter(|| unpack(msg, &did_resolver, &secrets_resolver, &opts));
+        });
+    }
+
+    for (name, msg) in [
+        ("unpack_signed_ed25519_key_1", SIGNED_MSG_ALICE_KEY_1),
+        ("unpack_signed_p256_key_2", SIGNED_MSG_ALICE_KEY_2),
+        ("unpack_signed_k256_key_3", SIGNED_MSG_ALICE_KEY_3),
+    ] {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+        let opts = UnpackOptions::default();
+
+        c.bench_function(name, move |b| {
+            b.to_async(FuturesExecutor)
+                .iter(|| unpack(msg, &did_resolver, &secrets_resolver, &opts));
+        });
+    }
+}
+
+criterion_group!(benches, benchmarks);
+criterion_main!(benches);
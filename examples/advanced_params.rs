@@ -50,8 +50,22 @@ async fn main() {
                     "expires_time".to_string(),
                     json!(99999),
                 )])),
+                forward_from: None,
                 messaging_service: Some("did:example:bob#didcomm-1".to_string()),
                 enc_alg_auth: Default::default(),
+                sign_outside: false,
+                from_prior: None,
+                resolver_timeout: None,
+                strict_did_urls: false,
+                aad: None,
+                reject_key_reuse: false,
+                preserve_recipient_kids_order: false,
+                recipient_kid_prefix: None,
+                content_hash: false,
+                flattened_jwe: false,
+                nonce_source: None,
+                session_key_provider: None,
+                algorithms: Default::default(),
             },
         )
         .await
@@ -0,0 +1,562 @@
+use std::{marker::PhantomData, sync::Arc};
+
+use askar_crypto::{
+    alg::{
+        aes::{A128CbcHs256, A128Gcm, A256CbcHs512, A256Gcm, A256Kw, AesKey},
+        chacha20::{Chacha20Key, XC20P},
+        p256::P256KeyPair,
+        x25519::X25519KeyPair,
+    },
+    kdf::{ecdh_1pu::Ecdh1PU, ecdh_es::EcdhEs, KeyExchange},
+    repr::KeyGen,
+};
+
+use crate::{
+    algorithms::{AnonCryptAlg, AuthCryptAlg},
+    did::VerificationMethod,
+    error::{err_msg, ErrorKind, Result, ResultContext},
+    jwe::{self, envelope::JWE, NonceSource, SessionKeyProvider},
+    jwk::{FromJwkValue, ToJwkValue},
+    secrets::Secret,
+    utils::crypto::{AsKnownKeyPair, JoseKDF, KnownKeyAlg},
+};
+
+/// Key agreement algorithm of DIDComm encrypted messages for keys of a particular curve
+/// identified by JWK `crv`. Allows to support additional curves without forking the library
+/// (see `AlgorithmRegistry::register_key_agreement_algorithm`).
+/// Built-in X25519 and P-256 key agreements are implementations of this trait too.
+/// Anonymous encryption (`ECDH-ES+A256KW`) is required, authenticated encryption
+/// (`ECDH-1PU+A256KW`) is optional and unsupported unless `encrypt_authenticated`
+/// and `decrypt_authenticated` are implemented.
+pub trait KeyAgreementAlgorithm: Send + Sync {
+    /// JWK `crv` of keys the algorithm works with (for ex. `X25519`).
+    fn crv(&self) -> &str;
+
+    /// Whether the recipient key agreement verification method can be used for encryption
+    /// with this algorithm.
+    fn supports_method(&self, method: &VerificationMethod) -> bool;
+
+    /// Whether the recipient secret can be used for decryption with this algorithm.
+    fn supports_secret(&self, secret: &Secret) -> bool;
+
+    /// Encrypts the message for the recipient keys (all supported by this algorithm).
+    /// Returns JWE in general JSON serialization with `ECDH-ES+A256KW` alg, `epk` of this curve,
+    /// recipient `kid` headers and `apv` as DIDComm requires (see `derive_apu_apv`).
+    /// `nonce_source`, `session_key_provider` and `aad` are the ones of `PackEncryptedOptions`.
+    fn encrypt(
+        &self,
+        msg: &[u8],
+        to_keys: &[&VerificationMethod],
+        enc_alg: &AnonCryptAlg,
+        nonce_source: Option<&dyn NonceSource>,
+        session_key_provider: Option<&dyn SessionKeyProvider>,
+        aad: Option<&[u8]>,
+    ) -> Result<String>;
+
+    /// Decrypts JWE produced by `encrypt` with the recipient secret. Returns plaintext.
+    /// JWE structure (recipients, `apv`, `iv` length) is already verified by the caller.
+    fn decrypt(&self, jwe: &str, secret: &Secret) -> Result<Vec<u8>>;
+
+    /// Encrypts the message from the sender secret for the recipient keys (all supported
+    /// by this algorithm). Returns JWE in general JSON serialization with `ECDH-1PU+A256KW` alg,
+    /// sender `skid` and `apu` in addition to what `encrypt` produces.
+    fn encrypt_authenticated(
+        &self,
+        _msg: &[u8],
+        _from_secret: &Secret,
+        _to_keys: &[&VerificationMethod],
+        _enc_alg: &AuthCryptAlg,
+        _nonce_source: Option<&dyn NonceSource>,
+        _aad: Option<&[u8]>,
+    ) -> Result<String> {
+        Err(err_msg(
+            ErrorKind::Unsupported,
+            "Authenticated encryption is unsupported by key agreement algorithm",
+        ))
+    }
+
+    /// Decrypts JWE produced by `encrypt_authenticated` with the recipient secret verifying
+    /// the sender key. Returns plaintext. JWE structure and content encryption
+    /// (AES-CBC with HMAC) are already verified by the caller.
+    fn decrypt_authenticated(
+        &self,
+        _jwe: &str,
+        _from_key: &VerificationMethod,
+        _secret: &Secret,
+    ) -> Result<Vec<u8>> {
+        Err(err_msg(
+            ErrorKind::Unsupported,
+            "Authenticated encryption is unsupported by key agreement algorithm",
+        ))
+    }
+}
+
+/// Built-in key agreement algorithms in order of preference.
+pub(crate) fn builtin_key_agreement_algorithms(
+) -> impl Iterator<Item = Arc<dyn KeyAgreementAlgorithm>> {
+    let algorithms: Vec<Arc<dyn KeyAgreementAlgorithm>> = vec![
+        Arc::new(EcdhKeyAgreement::<X25519KeyPair>::new()),
+        Arc::new(EcdhKeyAgreement::<P256KeyPair>::new()),
+    ];
+
+    algorithms.into_iter()
+}
+
+/// Key pair of a curve supported by built-in key agreements.
+trait EcdhKeyPair: KeyExchange + KeyGen + ToJwkValue + FromJwkValue + Sized {
+    const CRV: &'static str;
+    const KEY_ALG: KnownKeyAlg;
+
+    fn from_key<T: AsKnownKeyPair>(key: &T) -> Result<Self>;
+}
+
+impl EcdhKeyPair for X25519KeyPair {
+    const CRV: &'static str = "X25519";
+    const KEY_ALG: KnownKeyAlg = KnownKeyAlg::X25519;
+
+    fn from_key<T: AsKnownKeyPair>(key: &T) -> Result<Self> {
+        key.as_x25519()
+    }
+}
+
+impl EcdhKeyPair for P256KeyPair {
+    const CRV: &'static str = "P-256";
+    const KEY_ALG: KnownKeyAlg = KnownKeyAlg::P256;
+
+    fn from_key<T: AsKnownKeyPair>(key: &T) -> Result<Self> {
+        key.as_p256()
+    }
+}
+
+/// Built-in ECDH-ES and ECDH-1PU key agreements with keys of curve `K`.
+struct EcdhKeyAgreement<K>(PhantomData<fn() -> K>);
+
+impl<K> EcdhKeyAgreement<K> {
+    fn new() -> Self {
+        EcdhKeyAgreement(PhantomData)
+    }
+}
+
+impl<K: EcdhKeyPair> KeyAgreementAlgorithm for EcdhKeyAgreement<K> {
+    fn crv(&self) -> &str {
+        K::CRV
+    }
+
+    fn supports_method(&self, method: &VerificationMethod) -> bool {
+        method.key_alg() == K::KEY_ALG
+    }
+
+    fn supports_secret(&self, secret: &Secret) -> bool {
+        secret.key_alg() == K::KEY_ALG
+    }
+
+    fn encrypt(
+        &self,
+        msg: &[u8],
+        to_keys: &[&VerificationMethod],
+        enc_alg: &AnonCryptAlg,
+        nonce_source: Option<&dyn NonceSource>,
+        session_key_provider: Option<&dyn SessionKeyProvider>,
+        aad: Option<&[u8]>,
+    ) -> Result<String> {
+        let enc = match enc_alg {
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw => jwe::EncAlgorithm::A256cbcHs512,
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw => jwe::EncAlgorithm::A128cbcHs256,
+            AnonCryptAlg::Xc20pEcdhEsA256kw => jwe::EncAlgorithm::Xc20P,
+            AnonCryptAlg::A256gcmEcdhEsA256kw => jwe::EncAlgorithm::A256Gcm,
+            AnonCryptAlg::A128gcmEcdhEsA256kw => jwe::EncAlgorithm::A128Gcm,
+        };
+
+        _encrypt::<EcdhEs<'_, K>, K>(
+            msg,
+            jwe::Algorithm::EcdhEsA256kw,
+            enc,
+            None,
+            to_keys,
+            nonce_source,
+            session_key_provider,
+            aad,
+        )
+        .context("Unable produce anoncrypt envelope")
+    }
+
+    fn decrypt(&self, jwe: &str, secret: &Secret) -> Result<Vec<u8>> {
+        _decrypt::<EcdhEs<'_, K>, K>(jwe, None, secret)
+    }
+
+    fn encrypt_authenticated(
+        &self,
+        msg: &[u8],
+        from_secret: &Secret,
+        to_keys: &[&VerificationMethod],
+        enc_alg: &AuthCryptAlg,
+        nonce_source: Option<&dyn NonceSource>,
+        aad: Option<&[u8]>,
+    ) -> Result<String> {
+        let enc = match enc_alg {
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw => jwe::EncAlgorithm::A256cbcHs512,
+            AuthCryptAlg::A128cbcHs256Ecdh1puA256kw => jwe::EncAlgorithm::A128cbcHs256,
+        };
+
+        let from_key = K::from_key(from_secret)?;
+
+        _encrypt::<Ecdh1PU<'_, K>, K>(
+            msg,
+            jwe::Algorithm::Ecdh1puA256kw,
+            enc,
+            Some((&from_secret.id, &from_key)),
+            to_keys,
+            nonce_source,
+            None,
+            aad,
+        )
+        .context("Unable produce authcrypt envelope")
+    }
+
+    fn decrypt_authenticated(
+        &self,
+        jwe: &str,
+        from_key: &VerificationMethod,
+        secret: &Secret,
+    ) -> Result<Vec<u8>> {
+        let sender = K::from_key(from_key)?;
+        _decrypt::<Ecdh1PU<'_, K>, K>(jwe, Some((&from_key.id, &sender)), secret)
+    }
+}
+
+/// Encrypts the message with content encryption `enc` for recipient keys of curve `K`.
+/// Key agreement `KDF` is ECDH-ES or ECDH-1PU (requires `sender`) depending on `alg`.
+fn _encrypt<KDF, K>(
+    msg: &[u8],
+    alg: jwe::Algorithm,
+    enc: jwe::EncAlgorithm,
+    sender: Option<(&str, &K)>,
+    to_keys: &[&VerificationMethod],
+    nonce_source: Option<&dyn NonceSource>,
+    session_key_provider: Option<&dyn SessionKeyProvider>,
+    aad: Option<&[u8]>,
+) -> Result<String>
+where
+    KDF: JoseKDF<K, AesKey<A256Kw>>,
+    K: EcdhKeyPair,
+{
+    let _to_keys = to_keys
+        .iter()
+        .map(|vm| K::from_key(*vm).map(|k| (&vm.id, k)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let to_keys: Vec<_> = _to_keys
+        .iter()
+        .map(|(id, key)| (id.as_str(), key))
+        .collect();
+
+    match enc {
+        jwe::EncAlgorithm::A256cbcHs512 => {
+            jwe::encrypt::<AesKey<A256CbcHs512>, KDF, K, AesKey<A256Kw>>(
+                msg,
+                alg,
+                enc,
+                sender,
+                &to_keys,
+                nonce_source,
+                session_key_provider,
+                aad,
+            )
+        }
+        jwe::EncAlgorithm::A128cbcHs256 => {
+            jwe::encrypt::<AesKey<A128CbcHs256>, KDF, K, AesKey<A256Kw>>(
+                msg,
+                alg,
+                enc,
+                sender,
+                &to_keys,
+                nonce_source,
+                session_key_provider,
+                aad,
+            )
+        }
+        jwe::EncAlgorithm::Xc20P => jwe::encrypt::<Chacha20Key<XC20P>, KDF, K, AesKey<A256Kw>>(
+            msg,
+            alg,
+            enc,
+            sender,
+            &to_keys,
+            nonce_source,
+            session_key_provider,
+            aad,
+        ),
+        jwe::EncAlgorithm::A256Gcm => jwe::encrypt::<AesKey<A256Gcm>, KDF, K, AesKey<A256Kw>>(
+            msg,
+            alg,
+            enc,
+            sender,
+            &to_keys,
+            nonce_source,
+            session_key_provider,
+            aad,
+        ),
+        jwe::EncAlgorithm::A128Gcm => jwe::encrypt::<AesKey<A128Gcm>, KDF, K, AesKey<A256Kw>>(
+            msg,
+            alg,
+            enc,
+            sender,
+            &to_keys,
+            nonce_source,
+            session_key_provider,
+            aad,
+        ),
+        jwe::EncAlgorithm::Other(_) => Err(err_msg(
+            ErrorKind::Unsupported,
+            "Unsupported content encryption algorithm",
+        )),
+    }
+}
+
+/// Decrypts JWE with the recipient secret of curve `K` by content encryption of its `enc`.
+/// Key agreement `KDF` is ECDH-ES or ECDH-1PU (requires `sender`).
+fn _decrypt<KDF, K>(jwe: &str, sender: Option<(&str, &K)>, secret: &Secret) -> Result<Vec<u8>>
+where
+    KDF: JoseKDF<K, AesKey<A256Kw>>,
+    K: EcdhKeyPair,
+{
+    let to_kid = secret.id.as_str();
+    let to_key = K::from_key(secret)?;
+
+    // Base64 strictness is checked by the caller
+    let mut buf = vec![];
+    let parsed_jwe = JWE::from_str(jwe)?.parse_with(&mut buf, true)?;
+    let recipient = (to_kid, &to_key);
+
+    match parsed_jwe.protected.enc {
+        jwe::EncAlgorithm::A256cbcHs512 => {
+            parsed_jwe.decrypt::<AesKey<A256CbcHs512>, KDF, K, AesKey<A256Kw>>(sender, recipient)
+        }
+        jwe::EncAlgorithm::A128cbcHs256 => {
+            parsed_jwe.decrypt::<AesKey<A128CbcHs256>, KDF, K, AesKey<A256Kw>>(sender, recipient)
+        }
+        jwe::EncAlgorithm::Xc20P => {
+            parsed_jwe.decrypt::<Chacha20Key<XC20P>, KDF, K, AesKey<A256Kw>>(sender, recipient)
+        }
+        jwe::EncAlgorithm::A256Gcm => {
+            parsed_jwe.decrypt::<AesKey<A256Gcm>, KDF, K, AesKey<A256Kw>>(sender, recipient)
+        }
+        jwe::EncAlgorithm::A128Gcm => {
+            parsed_jwe.decrypt::<AesKey<A128Gcm>, KDF, K, AesKey<A256Kw>>(sender, recipient)
+        }
+        jwe::EncAlgorithm::Other(_) => Err(err_msg(
+            ErrorKind::Unsupported,
+            "Unsupported recipient key agreement method",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::{
+        algorithms::AlgorithmRegistry,
+        did::resolvers::ExampleDIDResolver,
+        secrets::resolvers::ExampleSecretsResolver,
+        test_vectors::{
+            ALICE_DID, ALICE_DID_DOC, ALICE_SECRETS, BOB_DID, BOB_DID_DOC, BOB_SECRETS,
+            MESSAGE_SIMPLE,
+        },
+        Message, PackEncryptedOptions, UnpackOptions,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn register_key_agreement_algorithm_works() {
+        let algorithm = Arc::new(CountingX25519::default());
+
+        let mut algorithms = AlgorithmRegistry::new();
+        algorithms.register_key_agreement_algorithm(algorithm.clone());
+
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let (msg, metadata) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                None,
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    algorithms: algorithms.clone(),
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("pack is ok.");
+
+        assert_eq!(algorithm.encrypted.load(Ordering::SeqCst), 1);
+        assert_eq!(algorithm.decrypted.load(Ordering::SeqCst), 0);
+        assert_eq!(metadata.to_kids.len(), 3);
+
+        let (unpacked, metadata) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                algorithms: algorithms.clone(),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&unpacked, &*MESSAGE_SIMPLE);
+        assert!(metadata.anonymous_sender);
+        assert_eq!(algorithm.encrypted.load(Ordering::SeqCst), 1);
+        assert_eq!(algorithm.decrypted.load(Ordering::SeqCst), 1);
+
+        algorithms.unregister_key_agreement_algorithm("X25519");
+
+        let (unpacked, _) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                algorithms,
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&unpacked, &*MESSAGE_SIMPLE);
+        assert_eq!(algorithm.decrypted.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn register_key_agreement_algorithm_works_authcrypt() {
+        let algorithm = Arc::new(CountingX25519::default());
+
+        let mut algorithms = AlgorithmRegistry::new();
+        algorithms.register_key_agreement_algorithm(algorithm.clone());
+
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let (msg, metadata) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                Some(ALICE_DID),
+                None,
+                &did_resolver,
+                &alice_secrets_resolver,
+                &PackEncryptedOptions {
+                    protect_sender: true,
+                    algorithms: algorithms.clone(),
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("pack is ok.");
+
+        assert_eq!(algorithm.encrypted.load(Ordering::SeqCst), 1);
+        assert_eq!(algorithm.encrypted_authenticated.load(Ordering::SeqCst), 1);
+        assert_eq!(metadata.to_kids.len(), 3);
+
+        let (unpacked, metadata) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &bob_secrets_resolver,
+            &UnpackOptions {
+                algorithms,
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&unpacked, &*MESSAGE_SIMPLE);
+        assert!(metadata.authenticated);
+        assert!(metadata.anonymous_sender);
+        assert_eq!(algorithm.decrypted.load(Ordering::SeqCst), 1);
+        assert_eq!(algorithm.decrypted_authenticated.load(Ordering::SeqCst), 1);
+    }
+
+    /// Delegates to built-in X25519 key agreement counting calls.
+    #[derive(Default)]
+    struct CountingX25519 {
+        encrypted: AtomicUsize,
+        decrypted: AtomicUsize,
+        encrypted_authenticated: AtomicUsize,
+        decrypted_authenticated: AtomicUsize,
+    }
+
+    fn x25519() -> EcdhKeyAgreement<X25519KeyPair> {
+        EcdhKeyAgreement::new()
+    }
+
+    impl KeyAgreementAlgorithm for CountingX25519 {
+        fn crv(&self) -> &str {
+            "X25519"
+        }
+
+        fn supports_method(&self, method: &VerificationMethod) -> bool {
+            x25519().supports_method(method)
+        }
+
+        fn supports_secret(&self, secret: &Secret) -> bool {
+            x25519().supports_secret(secret)
+        }
+
+        fn encrypt(
+            &self,
+            msg: &[u8],
+            to_keys: &[&VerificationMethod],
+            enc_alg: &AnonCryptAlg,
+            nonce_source: Option<&dyn NonceSource>,
+            session_key_provider: Option<&dyn SessionKeyProvider>,
+            aad: Option<&[u8]>,
+        ) -> Result<String> {
+            self.encrypted.fetch_add(1, Ordering::SeqCst);
+
+            x25519().encrypt(
+                msg,
+                to_keys,
+                enc_alg,
+                nonce_source,
+                session_key_provider,
+                aad,
+            )
+        }
+
+        fn decrypt(&self, jwe: &str, secret: &Secret) -> Result<Vec<u8>> {
+            self.decrypted.fetch_add(1, Ordering::SeqCst);
+            x25519().decrypt(jwe, secret)
+        }
+
+        fn encrypt_authenticated(
+            &self,
+            msg: &[u8],
+            from_secret: &Secret,
+            to_keys: &[&VerificationMethod],
+            enc_alg: &AuthCryptAlg,
+            nonce_source: Option<&dyn NonceSource>,
+            aad: Option<&[u8]>,
+        ) -> Result<String> {
+            self.encrypted_authenticated.fetch_add(1, Ordering::SeqCst);
+
+            x25519().encrypt_authenticated(msg, from_secret, to_keys, enc_alg, nonce_source, aad)
+        }
+
+        fn decrypt_authenticated(
+            &self,
+            jwe: &str,
+            from_key: &VerificationMethod,
+            secret: &Secret,
+        ) -> Result<Vec<u8>> {
+            self.decrypted_authenticated.fetch_add(1, Ordering::SeqCst);
+            x25519().decrypt_authenticated(jwe, from_key, secret)
+        }
+    }
+}
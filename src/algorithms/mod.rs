@@ -0,0 +1,359 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{err_msg, Error, ErrorKind};
+
+mod key_agreement;
+mod registry;
+mod signature;
+
+pub use key_agreement::KeyAgreementAlgorithm;
+pub use registry::AlgorithmRegistry;
+pub use signature::SignatureAlgorithm;
+
+/// Algorithms for anonymous encryption.
+///
+/// Only A256KW key wrapping is supported. Messages using other key management
+/// algorithms (for ex. `ECDH-ES+A128KW` or direct `ECDH-ES` key agreement)
+/// are rejected on unpack with `Unsupported` error.
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
+pub enum AnonCryptAlg {
+    /// AES256-CBC + HMAC-SHA512 with a 512 bit key content encryption,
+    /// ECDH-ES key agreement with A256KW key wrapping
+    A256cbcHs512EcdhEsA256kw,
+
+    /// XChaCha20Poly1305 with a 256 bit key content encryption,
+    /// ECDH-ES key agreement with A256KW key wrapping
+    Xc20pEcdhEsA256kw,
+
+    /// A256GCM_ECDH_ES_A256KW: XChaCha20Poly1305 with a 256 bit key content encryption,
+    /// ECDH-ES key agreement with A256KW key wrapping
+    A256gcmEcdhEsA256kw,
+
+    /// AES128-CBC + HMAC-SHA256 with a 256 bit key content encryption,
+    /// ECDH-ES key agreement with A256KW key wrapping
+    A128cbcHs256EcdhEsA256kw,
+
+    /// AES128-GCM with a 128 bit key content encryption,
+    /// ECDH-ES key agreement with A256KW key wrapping
+    A128gcmEcdhEsA256kw,
+}
+
+impl Default for AnonCryptAlg {
+    fn default() -> Self {
+        AnonCryptAlg::Xc20pEcdhEsA256kw
+    }
+}
+
+impl AnonCryptAlg {
+    /// JWA names of content encryption and key management algorithms joined with `+`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw => "A256CBC-HS512+ECDH-ES+A256KW",
+            AnonCryptAlg::Xc20pEcdhEsA256kw => "XC20P+ECDH-ES+A256KW",
+            AnonCryptAlg::A256gcmEcdhEsA256kw => "A256GCM+ECDH-ES+A256KW",
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw => "A128CBC-HS256+ECDH-ES+A256KW",
+            AnonCryptAlg::A128gcmEcdhEsA256kw => "A128GCM+ECDH-ES+A256KW",
+        }
+    }
+}
+
+impl fmt::Display for AnonCryptAlg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AnonCryptAlg {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "A256CBC-HS512+ECDH-ES+A256KW" => Ok(AnonCryptAlg::A256cbcHs512EcdhEsA256kw),
+            "XC20P+ECDH-ES+A256KW" => Ok(AnonCryptAlg::Xc20pEcdhEsA256kw),
+            "A256GCM+ECDH-ES+A256KW" => Ok(AnonCryptAlg::A256gcmEcdhEsA256kw),
+            "A128CBC-HS256+ECDH-ES+A256KW" => Ok(AnonCryptAlg::A128cbcHs256EcdhEsA256kw),
+            "A128GCM+ECDH-ES+A256KW" => Ok(AnonCryptAlg::A128gcmEcdhEsA256kw),
+            _ => Err(err_msg(
+                ErrorKind::IllegalArgument,
+                format!("Unknown anoncrypt alg `{}`", s),
+            )),
+        }
+    }
+}
+
+/// Algorithms for authenticated encryption.
+///
+/// Only A256KW key wrapping is supported. Messages using other key management
+/// algorithms (for ex. `ECDH-1PU+A128KW`) are rejected on unpack with `Unsupported` error.
+/// ECDH-1PU requires AES-CBC + HMAC-SHA2 content encryption, so there are no GCM variants.
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
+pub enum AuthCryptAlg {
+    /// AES256-CBC + HMAC-SHA512 with a 512 bit key content encryption,
+    /// ECDH-1PU key agreement with A256KW key wrapping
+    A256cbcHs512Ecdh1puA256kw,
+
+    /// AES128-CBC + HMAC-SHA256 with a 256 bit key content encryption,
+    /// ECDH-1PU key agreement with A256KW key wrapping
+    A128cbcHs256Ecdh1puA256kw,
+}
+
+impl Default for AuthCryptAlg {
+    fn default() -> Self {
+        AuthCryptAlg::A256cbcHs512Ecdh1puA256kw
+    }
+}
+
+impl AuthCryptAlg {
+    /// JWA names of content encryption and key management algorithms joined with `+`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw => "A256CBC-HS512+ECDH-1PU+A256KW",
+            AuthCryptAlg::A128cbcHs256Ecdh1puA256kw => "A128CBC-HS256+ECDH-1PU+A256KW",
+        }
+    }
+}
+
+impl fmt::Display for AuthCryptAlg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AuthCryptAlg {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "A256CBC-HS512+ECDH-1PU+A256KW" => Ok(AuthCryptAlg::A256cbcHs512Ecdh1puA256kw),
+            "A128CBC-HS256+ECDH-1PU+A256KW" => Ok(AuthCryptAlg::A128cbcHs256Ecdh1puA256kw),
+            _ => Err(err_msg(
+                ErrorKind::IllegalArgument,
+                format!("Unknown authcrypt alg `{}`", s),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
+pub enum SignAlg {
+    EdDSA,
+    ES256,
+    ES256K,
+}
+
+impl SignAlg {
+    /// JWA name of signature algorithm
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SignAlg::EdDSA => "EdDSA",
+            SignAlg::ES256 => "ES256",
+            SignAlg::ES256K => "ES256K",
+        }
+    }
+}
+
+impl fmt::Display for SignAlg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SignAlg {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "EdDSA" => Ok(SignAlg::EdDSA),
+            "ES256" => Ok(SignAlg::ES256),
+            "ES256K" => Ok(SignAlg::ES256K),
+            _ => Err(err_msg(
+                ErrorKind::IllegalArgument,
+                format!("Unknown sign alg `{}`", s),
+            )),
+        }
+    }
+}
+
+/// Signature algorithms this build is able to produce and verify.
+pub fn supported_sign_algs() -> Vec<SignAlg> {
+    vec![SignAlg::EdDSA, SignAlg::ES256, SignAlg::ES256K]
+}
+
+/// Anonymous encryption algorithms this build is able to pack and unpack.
+pub fn supported_anoncrypt_algs() -> Vec<AnonCryptAlg> {
+    vec![
+        AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+        AnonCryptAlg::Xc20pEcdhEsA256kw,
+        AnonCryptAlg::A256gcmEcdhEsA256kw,
+        AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+        AnonCryptAlg::A128gcmEcdhEsA256kw,
+    ]
+}
+
+/// Authenticated encryption algorithms this build is able to pack and unpack.
+pub fn supported_authcrypt_algs() -> Vec<AuthCryptAlg> {
+    vec![
+        AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+        AuthCryptAlg::A128cbcHs256Ecdh1puA256kw,
+    ]
+}
+
+/// Computes `apu` and `apv` JWE protected header values exactly as they are produced on packing
+/// (for ex. to compare them with protected header produced by another implementation):
+/// - `apu` is base64url encoded sender key ID for authenticated encryption, `None` for anonymous one;
+/// - `apv` is base64url encoded SHA-256 of recipient key IDs sorted and joined with `.`.
+///
+/// # Params
+/// - `skid` sender key ID (DID URL) for authenticated encryption.
+/// - `kids` recipient key IDs (DID URLs) in any order.
+///
+/// # Returns
+/// Tuple `(apu, apv)`.
+pub fn derive_apu_apv(skid: Option<&str>, kids: &[String]) -> (Option<String>, String) {
+    let kids = kids.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let apu = skid.map(|skid| base64::encode_config(skid, base64::URL_SAFE_NO_PAD));
+    let apv = base64::encode_config(apv_digest(&kids), base64::URL_SAFE_NO_PAD);
+
+    (apu, apv)
+}
+
+/// Raw `apv` value: SHA-256 of recipient key IDs sorted and joined with `.`.
+pub(crate) fn apv_digest(kids: &[&str]) -> Vec<u8> {
+    let mut kids = kids.to_vec();
+    kids.sort();
+    Sha256::digest(kids.join(".").as_bytes()).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::test_vectors::{ENCRYPTED_MSG_ANON_XC20P_1, ENCRYPTED_MSG_AUTH_X25519};
+
+    use super::*;
+
+    #[test]
+    fn anoncrypt_alg_from_str_works() {
+        for alg in [
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+            AnonCryptAlg::Xc20pEcdhEsA256kw,
+            AnonCryptAlg::A256gcmEcdhEsA256kw,
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+            AnonCryptAlg::A128gcmEcdhEsA256kw,
+        ] {
+            assert_eq!(
+                alg,
+                AnonCryptAlg::from_str(&alg.to_string()).expect("parse is ok.")
+            );
+        }
+
+        assert_eq!(
+            AnonCryptAlg::from_str("A256CBC-HS512+ECDH-ES+A256KW").expect("parse is ok."),
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw
+        );
+    }
+
+    #[test]
+    fn authcrypt_alg_from_str_works() {
+        for alg in [
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            AuthCryptAlg::A128cbcHs256Ecdh1puA256kw,
+        ] {
+            assert_eq!(
+                alg,
+                AuthCryptAlg::from_str(&alg.to_string()).expect("parse is ok.")
+            );
+        }
+    }
+
+    #[test]
+    fn sign_alg_from_str_works() {
+        for alg in [SignAlg::EdDSA, SignAlg::ES256, SignAlg::ES256K] {
+            assert_eq!(
+                alg,
+                SignAlg::from_str(&alg.to_string()).expect("parse is ok.")
+            );
+        }
+    }
+
+    #[test]
+    fn supported_algs_works() {
+        let sign_algs = supported_sign_algs();
+        assert!(sign_algs.contains(&SignAlg::EdDSA));
+        assert!(sign_algs.contains(&SignAlg::ES256));
+        assert!(sign_algs.contains(&SignAlg::ES256K));
+
+        assert!(supported_anoncrypt_algs().contains(&AnonCryptAlg::default()));
+        assert!(supported_authcrypt_algs().contains(&AuthCryptAlg::default()));
+    }
+
+    #[test]
+    fn alg_from_str_works_unknown() {
+        let err = AnonCryptAlg::from_str("A256CBC-HS512+ECDH-1PU+A256KW").expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: Unknown anoncrypt alg `A256CBC-HS512+ECDH-1PU+A256KW`"
+        );
+
+        let err = AuthCryptAlg::from_str("unknown").expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        let err = SignAlg::from_str("RS256").expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+    }
+
+    #[test]
+    fn derive_apu_apv_works() {
+        _derive_apu_apv_works(ENCRYPTED_MSG_ANON_XC20P_1);
+        _derive_apu_apv_works(ENCRYPTED_MSG_AUTH_X25519);
+
+        fn _derive_apu_apv_works(msg: &str) {
+            let msg: Value = serde_json::from_str(msg).expect("Unable parse msg");
+
+            let protected = base64::decode_config(
+                msg["protected"].as_str().expect("No protected header"),
+                base64::URL_SAFE_NO_PAD,
+            )
+            .expect("Unable decode protected header");
+
+            let protected: Value =
+                serde_json::from_slice(&protected).expect("Unable parse protected header");
+
+            let mut kids = msg["recipients"]
+                .as_array()
+                .expect("No recipients")
+                .iter()
+                .map(|r| r["header"]["kid"].as_str().expect("No kid").to_owned())
+                .collect::<Vec<_>>();
+
+            // Order of recipient key IDs doesn't matter
+            kids.reverse();
+
+            let (apu, apv) = derive_apu_apv(protected["skid"].as_str(), &kids);
+
+            assert_eq!(apu.as_deref(), protected["apu"].as_str());
+            assert_eq!(Some(apv.as_str()), protected["apv"].as_str());
+        }
+    }
+
+    #[test]
+    fn derive_apu_apv_works_known_values() {
+        let (apu, apv) = derive_apu_apv(
+            None,
+            &[
+                "did:example:bob#key-x25519-1".to_owned(),
+                "did:example:bob#key-x25519-2".to_owned(),
+                "did:example:bob#key-x25519-3".to_owned(),
+            ],
+        );
+
+        assert_eq!(apu, None);
+        assert_eq!(apv, "NcsuAnrRfPK69A-rkZ0L9XWUG4jMvNC3Zg74BPz53PA");
+    }
+}
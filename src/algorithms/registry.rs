@@ -0,0 +1,168 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    algorithms::{
+        key_agreement::{builtin_key_agreement_algorithms, KeyAgreementAlgorithm},
+        signature::{builtin_signature_algorithm, builtin_signature_algorithm_for_secret},
+        SignatureAlgorithm,
+    },
+    did::VerificationMethod,
+    error::{err_msg, ErrorKind, Result},
+    secrets::Secret,
+    utils::identity::ByIdentity,
+};
+
+/// Algorithms registered by application and consulted before built-in ones on packing
+/// and unpacking. Passed through options of pack and unpack methods
+/// (see `SignOptions::algorithms`, `PackEncryptedOptions::algorithms`, `UnpackOptions::algorithms`),
+/// so different parts of application can use different sets of algorithms.
+/// Default value has no registered algorithms, so only built-in ones are used.
+#[derive(Clone, Default)]
+pub struct AlgorithmRegistry {
+    signature_algorithms: Vec<Arc<dyn SignatureAlgorithm>>,
+    key_agreement_algorithms: Vec<Arc<dyn KeyAgreementAlgorithm>>,
+}
+
+impl AlgorithmRegistry {
+    /// Creates registry without registered algorithms.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers signature algorithm. Algorithm with the same `alg` identifier registered
+    /// earlier is replaced. Built-in algorithms can be overridden the same way.
+    ///
+    /// # Params
+    /// - `algorithm` signature algorithm implementation
+    pub fn register_signature_algorithm(&mut self, algorithm: Arc<dyn SignatureAlgorithm>) {
+        self.signature_algorithms
+            .retain(|a| a.alg() != algorithm.alg());
+
+        self.signature_algorithms.push(algorithm);
+    }
+
+    /// Removes signature algorithm registered for the given `alg` identifier.
+    /// Built-in algorithms can't be removed, but can be overridden by `register_signature_algorithm`.
+    pub fn unregister_signature_algorithm(&mut self, alg: &str) {
+        self.signature_algorithms.retain(|a| a.alg() != alg);
+    }
+
+    /// Registers key agreement algorithm. Algorithm with the same `crv` identifier registered
+    /// earlier is replaced. Built-in algorithms can be overridden the same way.
+    ///
+    /// # Params
+    /// - `algorithm` key agreement algorithm implementation
+    pub fn register_key_agreement_algorithm(&mut self, algorithm: Arc<dyn KeyAgreementAlgorithm>) {
+        self.key_agreement_algorithms
+            .retain(|a| a.crv() != algorithm.crv());
+
+        self.key_agreement_algorithms.push(algorithm);
+    }
+
+    /// Removes key agreement algorithm registered for the given `crv` identifier.
+    /// Built-in algorithms can't be removed, but can be overridden by `register_key_agreement_algorithm`.
+    pub fn unregister_key_agreement_algorithm(&mut self, crv: &str) {
+        self.key_agreement_algorithms.retain(|a| a.crv() != crv);
+    }
+
+    /// Returns signature algorithm for the given `alg` identifier:
+    /// registered one if any, otherwise built-in.
+    pub(crate) fn signature_algorithm(&self, alg: &str) -> Result<Arc<dyn SignatureAlgorithm>> {
+        match self.signature_algorithms.iter().find(|a| a.alg() == alg) {
+            Some(algorithm) => Ok(algorithm.clone()),
+            None => builtin_signature_algorithm(alg),
+        }
+    }
+
+    /// Returns signature algorithm to sign with the given secret: the last registered one
+    /// supporting the secret if any, otherwise built-in one for the secret key type.
+    pub(crate) fn signature_algorithm_for_secret(
+        &self,
+        secret: &Secret,
+    ) -> Result<Arc<dyn SignatureAlgorithm>> {
+        match self
+            .signature_algorithms
+            .iter()
+            .rev()
+            .find(|a| a.supports_secret(secret))
+        {
+            Some(algorithm) => Ok(algorithm.clone()),
+            None => builtin_signature_algorithm_for_secret(secret),
+        }
+    }
+
+    /// Returns key agreement algorithm to encrypt for the given recipient key: the last registered
+    /// one supporting the key if any, otherwise built-in one for the key curve.
+    pub(crate) fn key_agreement_algorithm_for_method(
+        &self,
+        method: &VerificationMethod,
+    ) -> Option<Arc<dyn KeyAgreementAlgorithm>> {
+        self.key_agreement_algorithms
+            .iter()
+            .rev()
+            .cloned()
+            .chain(builtin_key_agreement_algorithms())
+            .find(|a| a.supports_method(method))
+    }
+
+    /// Returns key agreement algorithm to decrypt with the given recipient secret: the last
+    /// registered one supporting the secret if any, otherwise built-in one for the secret curve.
+    pub(crate) fn key_agreement_algorithm_for_secret(
+        &self,
+        secret: &Secret,
+    ) -> Result<Arc<dyn KeyAgreementAlgorithm>> {
+        self.key_agreement_algorithms
+            .iter()
+            .rev()
+            .cloned()
+            .chain(builtin_key_agreement_algorithms())
+            .find(|a| a.supports_secret(secret))
+            .ok_or_else(|| {
+                err_msg(
+                    ErrorKind::Unsupported,
+                    "Unsupported recipient key agreement method",
+                )
+            })
+    }
+}
+
+impl fmt::Debug for AlgorithmRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AlgorithmRegistry")
+            .field(
+                "signature_algorithms",
+                &self
+                    .signature_algorithms
+                    .iter()
+                    .map(|a| a.alg())
+                    .collect::<Vec<_>>(),
+            )
+            .field(
+                "key_agreement_algorithms",
+                &self
+                    .key_agreement_algorithms
+                    .iter()
+                    .map(|a| a.crv())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl PartialEq for AlgorithmRegistry {
+    fn eq(&self, other: &Self) -> bool {
+        _eq_by_identity(&self.signature_algorithms, &other.signature_algorithms)
+            && _eq_by_identity(
+                &self.key_agreement_algorithms,
+                &other.key_agreement_algorithms,
+            )
+    }
+}
+
+impl Eq for AlgorithmRegistry {}
+
+fn _eq_by_identity<T: ?Sized>(a: &[Arc<T>], b: &[Arc<T>]) -> bool {
+    a.iter()
+        .map(|a| ByIdentity(&**a))
+        .eq(b.iter().map(|b| ByIdentity(&**b)))
+}
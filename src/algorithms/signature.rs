@@ -0,0 +1,453 @@
+use std::sync::Arc;
+
+use askar_crypto::sign::{KeySigVerify, KeySign, SignatureType};
+
+use crate::{
+    algorithms::SignAlg,
+    did::VerificationMethod,
+    error::{err_msg, ErrorKind, Result, ResultContext, ResultExt},
+    secrets::Secret,
+    utils::crypto::{AsKnownKeyPair, KnownKeyAlg, KnownKeyPair},
+};
+
+/// Signature algorithm of DIDComm signed messages (JWS) identified by JWS `alg` header.
+/// Allows to support additional curves without forking the library
+/// (see `AlgorithmRegistry::register_signature_algorithm`).
+/// Built-in `EdDSA` (Ed25519), `ES256` (P-256) and `ES256K` (secp256k1) are implementations
+/// of this trait too.
+pub trait SignatureAlgorithm: Send + Sync {
+    /// JWS `alg` header value identifying the algorithm (for ex. `EdDSA`).
+    fn alg(&self) -> &str;
+
+    /// Algorithm reported in `UnpackMetadata::sign_alg`.
+    /// None for algorithms not known to the library.
+    fn sign_alg(&self) -> Option<SignAlg>;
+
+    /// Whether the secret can be used for signing with this algorithm.
+    fn supports_secret(&self, secret: &Secret) -> bool;
+
+    /// Signs JWS signing input with the secret. Returns raw signature bytes.
+    fn sign(&self, secret: &Secret, input: &[u8]) -> Result<Vec<u8>>;
+
+    /// Verifies raw signature bytes over JWS signing input with the signer verification method.
+    fn verify(&self, method: &VerificationMethod, input: &[u8], signature: &[u8]) -> Result<bool>;
+}
+
+/// Returns built-in algorithm for the given `alg` identifier.
+pub(crate) fn builtin_signature_algorithm(alg: &str) -> Result<Arc<dyn SignatureAlgorithm>> {
+    let algorithm: Arc<dyn SignatureAlgorithm> = match alg {
+        "EdDSA" => Arc::new(EdDSA),
+        "ES256" => Arc::new(ES256),
+        "ES256K" => Arc::new(ES256K),
+        _ => Err(err_msg(
+            ErrorKind::Unsupported,
+            "Unsupported signature algorithm",
+        ))?,
+    };
+
+    Ok(algorithm)
+}
+
+/// Returns built-in algorithm to sign with the given secret based on the secret key type.
+pub(crate) fn builtin_signature_algorithm_for_secret(
+    secret: &Secret,
+) -> Result<Arc<dyn SignatureAlgorithm>> {
+    let alg = match secret
+        .as_key_pair()
+        .context("Unable instantiate sign key")?
+    {
+        KnownKeyPair::Ed25519(_) => "EdDSA",
+        KnownKeyPair::P256(_) => "ES256",
+        KnownKeyPair::K256(_) => "ES256K",
+        _ => Err(err_msg(ErrorKind::Unsupported, "Unsupported signature alg"))?,
+    };
+
+    builtin_signature_algorithm(alg)
+}
+
+/// Built-in EdDSA signature with Ed25519 keys.
+struct EdDSA;
+
+impl SignatureAlgorithm for EdDSA {
+    fn alg(&self) -> &str {
+        "EdDSA"
+    }
+
+    fn sign_alg(&self) -> Option<SignAlg> {
+        Some(SignAlg::EdDSA)
+    }
+
+    fn supports_secret(&self, secret: &Secret) -> bool {
+        secret.key_alg() == KnownKeyAlg::Ed25519
+    }
+
+    fn sign(&self, secret: &Secret, input: &[u8]) -> Result<Vec<u8>> {
+        _sign(&secret.as_ed25519()?, input, SignatureType::EdDSA)
+    }
+
+    fn verify(&self, method: &VerificationMethod, input: &[u8], signature: &[u8]) -> Result<bool> {
+        let key = method
+            .as_ed25519()
+            .context("Unable instantiate signer key")?;
+
+        _verify(&key, input, signature, SignatureType::EdDSA)
+    }
+}
+
+/// Built-in ECDSA signature with P-256 keys and SHA-256.
+struct ES256;
+
+impl SignatureAlgorithm for ES256 {
+    fn alg(&self) -> &str {
+        "ES256"
+    }
+
+    fn sign_alg(&self) -> Option<SignAlg> {
+        Some(SignAlg::ES256)
+    }
+
+    fn supports_secret(&self, secret: &Secret) -> bool {
+        secret.key_alg() == KnownKeyAlg::P256
+    }
+
+    fn sign(&self, secret: &Secret, input: &[u8]) -> Result<Vec<u8>> {
+        _sign(&secret.as_p256()?, input, SignatureType::ES256)
+    }
+
+    fn verify(&self, method: &VerificationMethod, input: &[u8], signature: &[u8]) -> Result<bool> {
+        let key = method.as_p256().context("Unable instantiate signer key")?;
+
+        _verify(&key, input, signature, SignatureType::ES256)
+    }
+}
+
+/// Built-in ECDSA signature with secp256k1 keys and SHA-256.
+struct ES256K;
+
+impl SignatureAlgorithm for ES256K {
+    fn alg(&self) -> &str {
+        "ES256K"
+    }
+
+    fn sign_alg(&self) -> Option<SignAlg> {
+        Some(SignAlg::ES256K)
+    }
+
+    fn supports_secret(&self, secret: &Secret) -> bool {
+        secret.key_alg() == KnownKeyAlg::K256
+    }
+
+    fn sign(&self, secret: &Secret, input: &[u8]) -> Result<Vec<u8>> {
+        _sign(&secret.as_k256()?, input, SignatureType::ES256K)
+    }
+
+    fn verify(&self, method: &VerificationMethod, input: &[u8], signature: &[u8]) -> Result<bool> {
+        let key = method.as_k256().context("Unable instantiate signer key")?;
+
+        _verify(&key, input, signature, SignatureType::ES256K)
+    }
+}
+
+fn _sign<Key: KeySign>(key: &Key, input: &[u8], sig_type: SignatureType) -> Result<Vec<u8>> {
+    let signature = key
+        .create_signature(input, Some(sig_type))
+        .kind(ErrorKind::InvalidState, "Unable create signature")?;
+
+    Ok(signature.as_ref().to_vec())
+}
+
+fn _verify<Key: KeySigVerify>(
+    key: &Key,
+    input: &[u8],
+    signature: &[u8],
+    sig_type: SignatureType,
+) -> Result<bool> {
+    key.verify_signature(input, signature, Some(sig_type))
+        .kind(ErrorKind::Malformed, "Unable verify signature")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use serde_json::{json, Value};
+    use sha2::{Digest, Sha256};
+
+    use crate::{
+        algorithms::AlgorithmRegistry,
+        did::{
+            resolvers::ExampleDIDResolver, DIDDoc, VerificationMaterial, VerificationMethod,
+            VerificationMethodType,
+        },
+        error::{ErrorKind, Result},
+        secrets::{resolvers::ExampleSecretsResolver, Secret, SecretMaterial, SecretType},
+        FromPriorSpec, Message, PackPlaintextOptions, SignOptions, UnpackOptions,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn register_signature_algorithm_works() {
+        let algorithm = Arc::new(DummyAlgorithm::default());
+
+        let mut algorithms = AlgorithmRegistry::new();
+        algorithms.register_signature_algorithm(algorithm.clone());
+
+        let did_resolver = ExampleDIDResolver::new(vec![_dummy_did_doc()]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![_dummy_secret()]);
+
+        let (msg, metadata) = _dummy_message()
+            .pack_signed_with_options(
+                DUMMY_DID,
+                &did_resolver,
+                &secrets_resolver,
+                &SignOptions {
+                    algorithms: algorithms.clone(),
+                    ..SignOptions::default()
+                },
+            )
+            .await
+            .expect("pack is ok.");
+
+        assert_eq!(metadata.sign_by_kid, DUMMY_KID);
+        assert_eq!(algorithm.signed.load(Ordering::SeqCst), 1);
+
+        let jws: Value = serde_json::from_str(&msg).expect("jws is ok.");
+
+        let protected = jws["signatures"][0]["protected"]
+            .as_str()
+            .expect("protected is ok.");
+
+        let protected =
+            base64::decode_config(protected, base64::URL_SAFE_NO_PAD).expect("protected is ok.");
+
+        let protected: Value = serde_json::from_slice(&protected).expect("protected is ok.");
+        assert_eq!(protected["alg"], "DUMMY");
+
+        let (unpacked, metadata) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                algorithms: algorithms.clone(),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(unpacked, _dummy_message());
+        assert_eq!(algorithm.verified.load(Ordering::SeqCst), 1);
+        assert!(metadata.non_repudiation);
+        assert_eq!(metadata.sign_from.as_deref(), Some(DUMMY_KID));
+        assert_eq!(metadata.sign_alg, None);
+
+        let err = Message::unpack(
+            &msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+
+        algorithms.unregister_signature_algorithm("DUMMY");
+
+        let err = Message::unpack(
+            &msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                algorithms,
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+        assert_eq!(algorithm.verified.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn register_signature_algorithm_works_not_registered() {
+        let did_resolver = ExampleDIDResolver::new(vec![_dummy_did_doc()]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![_dummy_secret()]);
+
+        let err = _dummy_message()
+            .pack_signed(DUMMY_DID, &did_resolver, &secrets_resolver)
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+    }
+
+    #[tokio::test]
+    async fn register_signature_algorithm_works_from_prior() {
+        let algorithm = Arc::new(DummyAlgorithm::default());
+
+        let mut algorithms = AlgorithmRegistry::new();
+        algorithms.register_signature_algorithm(algorithm.clone());
+
+        let did_resolver = ExampleDIDResolver::new(vec![_dummy_did_doc()]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![_dummy_secret()]);
+
+        let msg = Message {
+            from: Some("did:example:alice".to_owned()),
+            .._dummy_message()
+        };
+
+        let options = PackPlaintextOptions {
+            from_prior: Some(FromPriorSpec {
+                iss: DUMMY_DID.to_owned(),
+                issuer_kid: None,
+            }),
+            algorithms: algorithms.clone(),
+        };
+
+        let packed = msg
+            .pack_plaintext_with_options(&did_resolver, &secrets_resolver, &options)
+            .await
+            .expect("pack is ok.");
+
+        assert_eq!(algorithm.signed.load(Ordering::SeqCst), 1);
+
+        let (_, metadata) = Message::unpack(
+            &packed,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                algorithms,
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert!(algorithm.verified.load(Ordering::SeqCst) >= 1);
+        assert_eq!(metadata.from_prior_issuer_kid.as_deref(), Some(DUMMY_KID));
+
+        let from_prior = metadata.from_prior.expect("from_prior is ok.");
+        assert_eq!(from_prior.iss, DUMMY_DID);
+        assert_eq!(from_prior.sub, "did:example:alice");
+
+        let err = Message::unpack(
+            &packed,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn signature_algorithm_works_builtin() {
+        let algorithms = AlgorithmRegistry::default();
+
+        for &alg in ["EdDSA", "ES256", "ES256K"].iter() {
+            let algorithm = algorithms
+                .signature_algorithm(alg)
+                .expect("algorithm is ok.");
+            assert_eq!(algorithm.alg(), alg);
+            assert!(algorithm.sign_alg().is_some());
+        }
+
+        let err = algorithms
+            .signature_algorithm("UNKNOWN")
+            .map(|_| ())
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+
+        assert_eq!(
+            format!("{}", err),
+            "Unsupported crypto or method: Unsupported signature algorithm"
+        );
+    }
+
+    /// Signs with SHA-256 digest of signing input, so any change of JWS breaks the signature.
+    #[derive(Default)]
+    struct DummyAlgorithm {
+        signed: AtomicUsize,
+        verified: AtomicUsize,
+    }
+
+    impl SignatureAlgorithm for DummyAlgorithm {
+        fn alg(&self) -> &str {
+            "DUMMY"
+        }
+
+        fn sign_alg(&self) -> Option<SignAlg> {
+            None
+        }
+
+        fn supports_secret(&self, secret: &Secret) -> bool {
+            secret.id == DUMMY_KID
+        }
+
+        fn sign(&self, _secret: &Secret, input: &[u8]) -> Result<Vec<u8>> {
+            self.signed.fetch_add(1, Ordering::SeqCst);
+            Ok(Sha256::digest(input).to_vec())
+        }
+
+        fn verify(
+            &self,
+            method: &VerificationMethod,
+            input: &[u8],
+            signature: &[u8],
+        ) -> Result<bool> {
+            self.verified.fetch_add(1, Ordering::SeqCst);
+            Ok(method.id == DUMMY_KID && Sha256::digest(input).as_slice() == signature)
+        }
+    }
+
+    const DUMMY_DID: &str = "did:example:dummy";
+    const DUMMY_KID: &str = "did:example:dummy#key-1";
+
+    fn _dummy_did_doc() -> DIDDoc {
+        DIDDoc {
+            did: DUMMY_DID.to_owned(),
+            key_agreements: vec![],
+            authentications: vec![DUMMY_KID.to_owned()],
+            verification_methods: vec![VerificationMethod {
+                id: DUMMY_KID.to_owned(),
+                type_: VerificationMethodType::Other,
+                controller: DUMMY_DID.to_owned(),
+                verification_material: VerificationMaterial::Other {
+                    value: json!({ "kty": "DUMMY", "x": "dummy" }),
+                },
+            }],
+            services: vec![],
+            also_known_as: vec![],
+        }
+    }
+
+    fn _dummy_secret() -> Secret {
+        Secret {
+            id: DUMMY_KID.to_owned(),
+            type_: SecretType::Other,
+            secret_material: SecretMaterial::Other {
+                value: json!({ "kty": "DUMMY", "x": "dummy", "d": "dummy" }),
+            },
+        }
+    }
+
+    fn _dummy_message() -> Message {
+        Message::build(
+            "1234567890".to_owned(),
+            "http://example.com/protocols/lets_do_lunch/1.0/proposal".to_owned(),
+            json!({"messagespecificattribute": "and its value"}),
+        )
+        .from(DUMMY_DID.to_owned())
+        .to("did:example:bob".to_owned())
+        .finalize()
+    }
+}
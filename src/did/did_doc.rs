@@ -24,6 +24,11 @@ pub struct DIDDoc {
 
     /// All services (https://www.w3.org/TR/did-core/#services)
     pub services: Vec<Service>,
+
+    /// Other identifiers (usually DIDs) of the DID subject
+    /// (https://www.w3.org/TR/did-core/#also-known-as).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub also_known_as: Vec<String>,
 }
 
 /// Represents verification method record in DID Document
@@ -0,0 +1,137 @@
+//! Key IDs of raw public keys based on `did:jwk` DIDs (https://github.com/quartzjer/did-jwk/blob/main/spec.md)
+
+use serde_json::Value;
+
+use crate::{
+    did::{VerificationMaterial, VerificationMethod, VerificationMethodType},
+    error::{err_msg, ErrorKind, Result, ResultContext, ResultExt},
+    utils::crypto::{AsKnownKeyPair, KnownKeyAlg},
+};
+
+/// Builds key ID (`did:jwk` DID URL) for the given public key JWK.
+/// It allows to address raw public keys that have no resolvable DID
+/// (for ex. keys embedded in out-of-band invitations) as other DIDComm keys.
+/// The recipient must use the same key ID for the matching secret to unpack messages.
+///
+/// # Params
+/// - `jwk` public key agreement key as JWK.
+///
+/// # Returns
+/// `did:jwk:<base64url(jwk)>#0` DID URL.
+///
+/// # Errors
+/// - `IllegalArgument` `jwk` is not a JSON object or contains private key.
+/// - `Unsupported` `jwk` isn't X25519 or P-256 key.
+/// - `Malformed` `jwk` is invalid.
+pub fn build_did_jwk_kid(jwk: &Value) -> Result<String> {
+    Ok(did_jwk_key_agreement(jwk)?.id)
+}
+
+/// Key agreement verification method of `did:jwk` DID built for the given public key JWK.
+pub(crate) fn did_jwk_key_agreement(jwk: &Value) -> Result<VerificationMethod> {
+    let props = jwk
+        .as_object()
+        .ok_or_else(|| err_msg(ErrorKind::IllegalArgument, "JWK is not an object"))?;
+
+    if props.contains_key("d") {
+        Err(err_msg(
+            ErrorKind::IllegalArgument,
+            "JWK must not contain private key",
+        ))?
+    }
+
+    let jwk_str =
+        serde_json::to_string(jwk).kind(ErrorKind::InvalidState, "Unable serialize jwk")?;
+
+    let did = format!(
+        "did:jwk:{}",
+        base64::encode_config(jwk_str, base64::URL_SAFE_NO_PAD)
+    );
+
+    let vm = VerificationMethod {
+        id: format!("{}#0", did),
+        type_: VerificationMethodType::JsonWebKey2020,
+        controller: did,
+        verification_material: VerificationMaterial::JWK { value: jwk.clone() },
+    };
+
+    match vm.key_alg() {
+        KnownKeyAlg::X25519 | KnownKeyAlg::P256 => {}
+        _ => Err(err_msg(
+            ErrorKind::Unsupported,
+            "Only X25519 and P-256 keys are supported for key agreement",
+        ))?,
+    }
+
+    vm.as_key_pair().context("Unable instantiate key")?;
+
+    Ok(vm)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        did::VerificationMaterial,
+        error::ErrorKind,
+        secrets::SecretMaterial,
+        test_vectors::{BOB_SECRETS, BOB_VERIFICATION_METHOD_KEY_AGREEM_X25519_1},
+    };
+
+    use super::build_did_jwk_kid;
+
+    #[test]
+    fn build_did_jwk_kid_works() {
+        let jwk = match BOB_VERIFICATION_METHOD_KEY_AGREEM_X25519_1.verification_material {
+            VerificationMaterial::JWK { ref value } => value.clone(),
+            _ => panic!("Unexpected verification material"),
+        };
+
+        let kid = build_did_jwk_kid(&jwk).expect("build_did_jwk_kid is ok.");
+
+        let encoded = kid
+            .strip_prefix("did:jwk:")
+            .and_then(|kid| kid.strip_suffix("#0"))
+            .expect("Unexpected kid format");
+
+        let decoded =
+            base64::decode_config(encoded, base64::URL_SAFE_NO_PAD).expect("Unable decode kid");
+
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&decoded).unwrap(),
+            jwk
+        );
+    }
+
+    #[test]
+    fn build_did_jwk_kid_works_unsupported_key() {
+        let err = build_did_jwk_kid(&json!({
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "x": "G-boxFB6vOZBu-wXkm-9Lh79I8nf9Z50cILaOgKKGww",
+        }))
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn build_did_jwk_kid_works_private_key() {
+        let secret = BOB_SECRETS
+            .iter()
+            .find(|s| s.id == BOB_VERIFICATION_METHOD_KEY_AGREEM_X25519_1.id)
+            .expect("Secret not found");
+
+        let jwk = match secret.secret_material {
+            SecretMaterial::JWK { ref value } => value.clone(),
+            _ => panic!("Unexpected secret material"),
+        };
+
+        let err = build_did_jwk_kid(&jwk).expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        let err = build_did_jwk_kid(&json!("not an object")).expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+    }
+}
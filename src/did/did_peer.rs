@@ -0,0 +1,213 @@
+//! Generation of `did:peer` DIDs (https://identity.foundation/peer-did-method-spec/)
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    did::{DIDCommMessagingService, VerificationMaterial, VerificationMethod},
+    error::{err_msg, ErrorKind, Result, ResultExt, ToResult},
+    utils::{
+        crypto::{AsKnownKeyPair, KnownKeyAlg},
+        did::{_to_multicodec, Codec},
+    },
+};
+
+/// Builds `did:peer` DID with numalgo 2 that encodes the given keys and DIDComm service
+/// (https://identity.foundation/peer-did-method-spec/#method-2-multiple-inception-key-without-doc).
+///
+/// # Params
+/// - `keys` public keys of the agent. X25519 keys are encoded as key agreement keys
+///   and Ed25519 keys as authentication keys. Identifiers of verification methods are ignored.
+/// - `service` optional DIDComm service of the agent.
+///
+/// # Returns
+/// `did:peer:2` DID that can be resolved with `DIDPeerResolver`.
+///
+/// # Errors
+/// - `Unsupported` key type or verification material isn't supported.
+/// - `Malformed` verification material is invalid.
+pub fn build_did_peer_2(
+    keys: &[VerificationMethod],
+    service: Option<&DIDCommMessagingService>,
+) -> Result<String> {
+    let mut did = "did:peer:2".to_owned();
+
+    for key in keys {
+        let (purpose, codec) = match key.key_alg() {
+            KnownKeyAlg::X25519 => ('E', Codec::X25519Pub),
+            KnownKeyAlg::Ed25519 => ('V', Codec::Ed25519Pub),
+            _ => Err(err_msg(
+                ErrorKind::Unsupported,
+                "Only X25519 and Ed25519 keys are supported for did:peer:2",
+            ))?,
+        };
+
+        did.push('.');
+        did.push(purpose);
+        did.push_str(&_multibase(&key.verification_material, codec)?);
+    }
+
+    if let Some(service) = service {
+        let mut abbreviated = Map::new();
+        abbreviated.insert("t".into(), json!("dm"));
+        abbreviated.insert("s".into(), json!(service.service_endpoint));
+
+        if !service.routing_keys.is_empty() {
+            abbreviated.insert("r".into(), json!(service.routing_keys));
+        }
+
+        if !service.accept.is_empty() {
+            abbreviated.insert("a".into(), json!(service.accept));
+        }
+
+        let service = serde_json::to_string(&Value::Object(abbreviated))
+            .kind(ErrorKind::InvalidState, "Unable serialize service")?;
+
+        did.push_str(".S");
+        did.push_str(&base64::encode_config(service, base64::URL_SAFE_NO_PAD));
+    }
+
+    Ok(did)
+}
+
+fn _multibase(material: &VerificationMaterial, codec: Codec) -> Result<String> {
+    let value = match material {
+        VerificationMaterial::Multibase { ref value } => return Ok(value.clone()),
+        VerificationMaterial::Base58 { ref value } => bs58::decode(value)
+            .into_vec()
+            .to_didcomm("Wrong base58 value in verification material")?,
+        VerificationMaterial::JWK { ref value } => {
+            let x = value["x"].as_str().ok_or_else(|| {
+                err_msg(
+                    ErrorKind::Malformed,
+                    "No `x` value in verification material",
+                )
+            })?;
+
+            base64::decode_config(x, base64::URL_SAFE_NO_PAD)
+                .kind(ErrorKind::Malformed, "Unable decode `x` value")?
+        }
+        _ => Err(err_msg(
+            ErrorKind::Unsupported,
+            "Unsupported verification material",
+        ))?,
+    };
+
+    Ok(format!(
+        "z{}",
+        bs58::encode(_to_multicodec(codec, &value)?).into_string()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        did::{
+            resolvers::DIDPeerResolver, DIDCommMessagingService, DIDResolver, ServiceKind,
+            VerificationMaterial, VerificationMethod, VerificationMethodType,
+        },
+        error::ErrorKind,
+        test_vectors::{
+            ALICE_AUTH_METHOD_25519, ALICE_AUTH_METHOD_P256,
+            ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
+        },
+        utils::crypto::AsKnownKeyPair,
+    };
+
+    use super::build_did_peer_2;
+
+    #[tokio::test]
+    async fn build_did_peer_2_works() {
+        let service = DIDCommMessagingService {
+            service_endpoint: "https://example.com/endpoint".into(),
+            accept: vec!["didcomm/v2".into()],
+            routing_keys: vec!["did:example:somemediator#somekey".into()],
+        };
+
+        let did = build_did_peer_2(
+            &[
+                ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.clone(),
+                ALICE_AUTH_METHOD_25519.clone(),
+            ],
+            Some(&service),
+        )
+        .expect("build is ok.");
+
+        assert!(did.starts_with("did:peer:2.Ez6LS"));
+
+        let did_doc = DIDPeerResolver::new()
+            .resolve(&did)
+            .await
+            .expect("resolve is ok.")
+            .expect("did doc is found.");
+
+        assert_eq!(did_doc.did, did);
+        assert_eq!(did_doc.key_agreements.len(), 1);
+        assert_eq!(did_doc.authentications.len(), 1);
+        assert_eq!(did_doc.verification_methods.len(), 2);
+
+        assert_eq!(
+            format!(
+                "{:?}",
+                did_doc.verification_methods[0]
+                    .as_key_pair()
+                    .expect("key is ok.")
+            ),
+            format!(
+                "{:?}",
+                ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519
+                    .as_key_pair()
+                    .expect("key is ok.")
+            )
+        );
+
+        assert_eq!(
+            format!(
+                "{:?}",
+                did_doc.verification_methods[1]
+                    .as_key_pair()
+                    .expect("key is ok.")
+            ),
+            format!(
+                "{:?}",
+                ALICE_AUTH_METHOD_25519.as_key_pair().expect("key is ok.")
+            )
+        );
+
+        match did_doc.services[0].kind {
+            ServiceKind::DIDCommMessaging { ref value } => {
+                assert_eq!(value.service_endpoint, service.service_endpoint);
+                assert_eq!(value.accept, service.accept);
+                assert_eq!(value.routing_keys, service.routing_keys);
+            }
+            _ => panic!("DIDCommMessaging service expected"),
+        }
+    }
+
+    #[test]
+    fn build_did_peer_2_works_multibase() {
+        let did = build_did_peer_2(
+            &[VerificationMethod {
+                id: "#key-1".into(),
+                type_: VerificationMethodType::X25519KeyAgreementKey2020,
+                controller: "#key-1".into(),
+                verification_material: VerificationMaterial::Multibase {
+                    value: "z6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc".into(),
+                },
+            }],
+            None,
+        )
+        .expect("build is ok.");
+
+        assert_eq!(
+            did,
+            "did:peer:2.Ez6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc"
+        );
+    }
+
+    #[test]
+    fn build_did_peer_2_works_unsupported_key() {
+        let err = build_did_peer_2(&[ALICE_AUTH_METHOD_P256.clone()], None).expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+    }
+}
@@ -0,0 +1,100 @@
+//! Verification relationships (https://www.w3.org/TR/did-core/#verification-relationships)
+//! of keys in the resolved DID Doc.
+
+use serde::{Deserialize, Serialize};
+
+use crate::did::DIDDoc;
+
+/// Verification relationship of the key in DID Doc.
+/// Only relationships used by DIDComm and kept in `DIDDoc` are supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Purpose {
+    /// Key can be used to sign messages (`authentication`).
+    Authentication,
+
+    /// Key can be used to encrypt messages (`keyAgreement`).
+    KeyAgreement,
+}
+
+/// Returns verification relationships of DID Doc referencing the given key.
+///
+/// # Params
+/// - `did_doc` resolved DID Doc.
+/// - `kid` key ID (DID URL). Relative references in DID Doc (for ex. `#key-1`)
+///   are resolved against DID of the DID Doc.
+///
+/// # Returns
+/// Relationships in `authentication`, `keyAgreement` order. Empty if the key
+/// isn't referenced by any relationship.
+pub fn key_purposes(did_doc: &DIDDoc, kid: &str) -> Vec<Purpose> {
+    let references = |refs: &[String]| refs.iter().any(|r| _is_reference_to(did_doc, r, kid));
+
+    let mut purposes = vec![];
+
+    if references(&did_doc.authentications) {
+        purposes.push(Purpose::Authentication);
+    }
+
+    if references(&did_doc.key_agreements) {
+        purposes.push(Purpose::KeyAgreement);
+    }
+
+    purposes
+}
+
+fn _is_reference_to(did_doc: &DIDDoc, reference: &str, kid: &str) -> bool {
+    if reference.starts_with('#') {
+        kid.len() == did_doc.did.len() + reference.len()
+            && kid.starts_with(&did_doc.did)
+            && kid.ends_with(reference)
+    } else {
+        reference == kid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{did::DIDDoc, test_vectors::ALICE_DID_DOC};
+
+    use super::{key_purposes, Purpose};
+
+    #[test]
+    fn key_purposes_works() {
+        let did_doc = DIDDoc {
+            key_agreements: vec![
+                "did:example:alice#key-x25519-1".into(),
+                "did:example:alice#key-1".into(),
+                "#key-2".into(),
+            ],
+            ..ALICE_DID_DOC.clone()
+        };
+
+        assert_eq!(
+            key_purposes(&did_doc, "did:example:alice#key-1"),
+            vec![Purpose::Authentication, Purpose::KeyAgreement]
+        );
+
+        assert_eq!(
+            key_purposes(&did_doc, "did:example:alice#key-2"),
+            vec![Purpose::Authentication, Purpose::KeyAgreement]
+        );
+
+        assert_eq!(
+            key_purposes(&did_doc, "did:example:alice#key-3"),
+            vec![Purpose::Authentication]
+        );
+
+        assert_eq!(
+            key_purposes(&did_doc, "did:example:alice#key-x25519-1"),
+            vec![Purpose::KeyAgreement]
+        );
+
+        assert_eq!(
+            key_purposes(&did_doc, "did:example:alice#key-p256-1"),
+            vec![]
+        );
+        assert_eq!(key_purposes(&did_doc, "did:example:bob#key-2"), vec![]);
+        assert_eq!(key_purposes(&did_doc, "#key-2"), vec![]);
+    }
+}
@@ -1,8 +1,28 @@
 //! DID Resolver (https://www.w3.org/TR/did-core/#dfn-did-resolvers) interfaces
 
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
 use async_trait::async_trait;
+use futures::future::{self, Either, FutureExt, Shared};
+use futures_timer::Delay;
 
-use crate::{did::did_doc::DIDDoc, error::Result};
+use crate::{
+    algorithms::AlgorithmRegistry,
+    did::{
+        did_doc::{DIDDoc, VerificationMaterial, VerificationMethod},
+        did_purpose::{key_purposes, Purpose},
+    },
+    error::{err_msg, Error, ErrorKind, Result},
+    utils::{
+        crypto::{AsKnownKeyPair, KnownKeyAlg},
+        did::did_or_url,
+    },
+};
 
 /// Represents DID Doc resolver (https://www.w3.org/TR/did-core/#did-resolution).
 #[cfg(feature = "uniffi")]
@@ -20,6 +40,49 @@ pub trait DIDResolver: Sync {
     /// - `IoError` IO error during resolving
     /// - `InvalidState` indicates a bug in resolver code
     async fn resolve(&self, did: &str) -> Result<Option<DIDDoc>>;
+
+    /// Resolves a verification method by the given key ID.
+    /// Default implementation resolves DID Doc of the key and looks for
+    /// the verification method in it. Resolvers able to look up keys directly
+    /// (for ex. from key-indexed storage) can override it to avoid resolving of full DID Doc.
+    ///
+    /// # Params
+    /// - `kid` a key ID (in form of DID URL) to be resolved.
+    /// - `purpose` verification relationship the key must be declared in by the DID Doc
+    ///   (for ex. `Authentication` for signer keys). None if any key of the DID Doc fits.
+    ///
+    /// # Returns
+    /// An instance of resolved verification method or None if DID or key is not found
+    /// or the key isn't declared in the given verification relationship.
+    ///
+    /// # Errors
+    /// - `IoError` IO error during resolving
+    /// - `InvalidState` indicates a bug in resolver code
+    async fn resolve_verification_method(
+        &self,
+        kid: &str,
+        purpose: Option<Purpose>,
+    ) -> Result<Option<VerificationMethod>> {
+        let (did, _) = did_or_url(kid);
+
+        let vm = self
+            .resolve(did)
+            .await?
+            .and_then(|did_doc| did_doc_verification_method(&did_doc, kid, purpose));
+
+        Ok(vm)
+    }
+
+    /// Whether `resolve_verification_method` looks up keys directly without resolving
+    /// full DID Doc. If `true`, keys referenced by key ID in unpacked messages (signer,
+    /// sender and `from_prior` issuer keys) are looked up with `resolve_verification_method`
+    /// before resolving of DID Doc, and DID Doc isn't resolved if the key is found.
+    /// So the resolver is trusted to return only keys of the current DID Doc version
+    /// declared in the requested verification relationship (`purpose`).
+    /// Default implementation returns `false`.
+    fn resolves_keys_directly(&self) -> bool {
+        false
+    }
 }
 
 /// Represents DID Doc resolver (https://www.w3.org/TR/did-core/#did-resolution).
@@ -38,4 +101,760 @@ pub trait DIDResolver {
     /// - `IoError` IO error during resolving
     /// - `InvalidState` indicates a bug in resolver code
     async fn resolve(&self, did: &str) -> Result<Option<DIDDoc>>;
+
+    /// Resolves a verification method by the given key ID.
+    /// Default implementation resolves DID Doc of the key and looks for
+    /// the verification method in it. Resolvers able to look up keys directly
+    /// (for ex. from key-indexed storage) can override it to avoid resolving of full DID Doc.
+    ///
+    /// # Params
+    /// - `kid` a key ID (in form of DID URL) to be resolved.
+    /// - `purpose` verification relationship the key must be declared in by the DID Doc
+    ///   (for ex. `Authentication` for signer keys). None if any key of the DID Doc fits.
+    ///
+    /// # Returns
+    /// An instance of resolved verification method or None if DID or key is not found
+    /// or the key isn't declared in the given verification relationship.
+    ///
+    /// # Errors
+    /// - `IoError` IO error during resolving
+    /// - `InvalidState` indicates a bug in resolver code
+    async fn resolve_verification_method(
+        &self,
+        kid: &str,
+        purpose: Option<Purpose>,
+    ) -> Result<Option<VerificationMethod>> {
+        let (did, _) = did_or_url(kid);
+
+        let vm = self
+            .resolve(did)
+            .await?
+            .and_then(|did_doc| did_doc_verification_method(&did_doc, kid, purpose));
+
+        Ok(vm)
+    }
+
+    /// Whether `resolve_verification_method` looks up keys directly without resolving
+    /// full DID Doc. If `true`, keys referenced by key ID in unpacked messages (signer,
+    /// sender and `from_prior` issuer keys) are looked up with `resolve_verification_method`
+    /// before resolving of DID Doc, and DID Doc isn't resolved if the key is found.
+    /// So the resolver is trusted to return only keys of the current DID Doc version
+    /// declared in the requested verification relationship (`purpose`).
+    /// Default implementation returns `false`.
+    fn resolves_keys_directly(&self) -> bool {
+        false
+    }
+}
+
+/// Represents DID Doc resolver able to resolve DID Doc versions that were valid in the past.
+/// Allows verifying messages signed by keys that were rotated out of the DID Doc since then.
+#[cfg(feature = "uniffi")]
+#[async_trait]
+pub trait VersionedDIDResolver: Sync {
+    /// Resolves a version of DID document that was valid at the given time.
+    ///
+    /// # Params
+    /// - `did` a DID to be resolved.
+    /// - `time` UTC Epoch Seconds the DID Doc version must be valid at.
+    ///   None means the current version.
+    ///
+    /// # Returns
+    /// An instance of resolved DID DOC or None if DID is not found.
+    ///
+    /// # Errors
+    /// - `IoError` IO error during resolving
+    /// - `InvalidState` indicates a bug in resolver code
+    async fn resolve_at(&self, did: &str, time: Option<u64>) -> Result<Option<DIDDoc>>;
+}
+
+/// Represents DID Doc resolver able to resolve DID Doc versions that were valid in the past.
+/// Allows verifying messages signed by keys that were rotated out of the DID Doc since then.
+#[cfg(not(feature = "uniffi"))]
+#[async_trait(?Send)]
+pub trait VersionedDIDResolver {
+    /// Resolves a version of DID document that was valid at the given time.
+    ///
+    /// # Params
+    /// - `did` a DID to be resolved.
+    /// - `time` UTC Epoch Seconds the DID Doc version must be valid at.
+    ///   None means the current version.
+    ///
+    /// # Returns
+    /// An instance of resolved DID DOC or None if DID is not found.
+    ///
+    /// # Errors
+    /// - `IoError` IO error during resolving
+    /// - `InvalidState` indicates a bug in resolver code
+    async fn resolve_at(&self, did: &str, time: Option<u64>) -> Result<Option<DIDDoc>>;
+}
+
+/// Adapts `VersionedDIDResolver` to `DIDResolver` resolving DID Doc versions valid at fixed time.
+pub(crate) struct AtTimeDIDResolver<'dr> {
+    resolver: &'dr (dyn VersionedDIDResolver + 'dr),
+    time: Option<u64>,
+}
+
+impl<'dr> AtTimeDIDResolver<'dr> {
+    pub(crate) fn new(resolver: &'dr (dyn VersionedDIDResolver + 'dr), time: Option<u64>) -> Self {
+        AtTimeDIDResolver { resolver, time }
+    }
+}
+
+#[cfg_attr(feature = "uniffi", async_trait)]
+#[cfg_attr(not(feature = "uniffi"), async_trait(?Send))]
+impl<'dr> DIDResolver for AtTimeDIDResolver<'dr> {
+    async fn resolve(&self, did: &str) -> Result<Option<DIDDoc>> {
+        self.resolver.resolve_at(did, self.time).await
+    }
+}
+
+/// Bounds calls of the wrapped `DIDResolver` with the given timeout.
+/// Calls that don't complete in time fail with `IOError`. If timeout is None calls aren't bounded.
+pub(crate) struct TimeoutDIDResolver<'dr> {
+    resolver: &'dr (dyn DIDResolver + 'dr),
+    timeout: Option<Duration>,
+}
+
+impl<'dr> TimeoutDIDResolver<'dr> {
+    pub(crate) fn new(resolver: &'dr (dyn DIDResolver + 'dr), timeout: Option<Duration>) -> Self {
+        TimeoutDIDResolver { resolver, timeout }
+    }
+
+    async fn _with_timeout<T>(&self, fut: impl Future<Output = Result<T>>) -> Result<T> {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return fut.await,
+        };
+
+        futures::pin_mut!(fut);
+
+        match future::select(fut, Delay::new(timeout)).await {
+            Either::Left((res, _)) => res,
+            Either::Right(_) => Err(err_msg(ErrorKind::IoError, "DID resolver call timed out")),
+        }
+    }
+}
+
+#[cfg_attr(feature = "uniffi", async_trait)]
+#[cfg_attr(not(feature = "uniffi"), async_trait(?Send))]
+impl<'dr> DIDResolver for TimeoutDIDResolver<'dr> {
+    async fn resolve(&self, did: &str) -> Result<Option<DIDDoc>> {
+        self._with_timeout(self.resolver.resolve(did)).await
+    }
+
+    async fn resolve_verification_method(
+        &self,
+        kid: &str,
+        purpose: Option<Purpose>,
+    ) -> Result<Option<VerificationMethod>> {
+        self._with_timeout(self.resolver.resolve_verification_method(kid, purpose))
+            .await
+    }
+
+    fn resolves_keys_directly(&self) -> bool {
+        self.resolver.resolves_keys_directly()
+    }
+}
+
+/// Expands relative DID URLs (fragment-only, for ex. `#key-1`) of verification methods
+/// in DID Docs resolved by the wrapped `DIDResolver` to absolute ones against the DID.
+/// If `strict` is set, DID Docs with relative DID URLs are rejected with `Malformed` error instead.
+pub(crate) struct RelativeDIDUrlsResolver<'dr> {
+    resolver: &'dr (dyn DIDResolver + 'dr),
+    strict: bool,
+}
+
+impl<'dr> RelativeDIDUrlsResolver<'dr> {
+    pub(crate) fn new(resolver: &'dr (dyn DIDResolver + 'dr), strict: bool) -> Self {
+        RelativeDIDUrlsResolver { resolver, strict }
+    }
+
+    fn _expand(&self, did: &str, url: &mut String) -> Result<()> {
+        if !url.starts_with('#') {
+            return Ok(());
+        }
+
+        if self.strict {
+            Err(err_msg(
+                ErrorKind::Malformed,
+                format!("Relative DID URL `{}` isn't allowed", url),
+            ))?
+        }
+
+        url.insert_str(0, did);
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "uniffi", async_trait)]
+#[cfg_attr(not(feature = "uniffi"), async_trait(?Send))]
+impl<'dr> DIDResolver for RelativeDIDUrlsResolver<'dr> {
+    async fn resolve(&self, did: &str) -> Result<Option<DIDDoc>> {
+        let mut ddoc = match self.resolver.resolve(did).await? {
+            Some(ddoc) => ddoc,
+            None => return Ok(None),
+        };
+
+        let did = ddoc.did.clone();
+
+        for url in ddoc
+            .key_agreements
+            .iter_mut()
+            .chain(ddoc.authentications.iter_mut())
+            .chain(ddoc.verification_methods.iter_mut().map(|vm| &mut vm.id))
+        {
+            self._expand(&did, url)?;
+        }
+
+        Ok(Some(ddoc))
+    }
+
+    async fn resolve_verification_method(
+        &self,
+        kid: &str,
+        purpose: Option<Purpose>,
+    ) -> Result<Option<VerificationMethod>> {
+        let mut vm = match self
+            .resolver
+            .resolve_verification_method(kid, purpose)
+            .await?
+        {
+            Some(vm) => vm,
+            None => return Ok(None),
+        };
+
+        let (did, _) = did_or_url(kid);
+        self._expand(did, &mut vm.id)?;
+
+        Ok(Some(vm))
+    }
+
+    fn resolves_keys_directly(&self) -> bool {
+        self.resolver.resolves_keys_directly()
+    }
+}
+
+/// Shared resolution of a DID awaited by all concurrent requests of the DID.
+#[cfg(feature = "uniffi")]
+type SharedResolve<'dr> =
+    Shared<future::BoxFuture<'dr, std::result::Result<Option<DIDDoc>, Arc<Error>>>>;
+
+/// Shared resolution of a DID awaited by all concurrent requests of the DID.
+#[cfg(not(feature = "uniffi"))]
+type SharedResolve<'dr> =
+    Shared<future::LocalBoxFuture<'dr, std::result::Result<Option<DIDDoc>, Arc<Error>>>>;
+
+/// Wraps DID resolver and caches resolution results for the lifetime of the wrapper.
+/// Concurrent requests of the same DID share one in-flight resolution, so the wrapped
+/// resolver is asked once per DID. Failed resolutions aren't cached and are retried
+/// by the next request.
+pub(crate) struct CachingDIDResolver<'dr> {
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    cache: Mutex<HashMap<String, SharedResolve<'dr>>>,
+}
+
+impl<'dr> CachingDIDResolver<'dr> {
+    pub(crate) fn new(did_resolver: &'dr (dyn DIDResolver + 'dr)) -> Self {
+        CachingDIDResolver {
+            did_resolver,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn _resolve(&self, did: &str) -> SharedResolve<'dr> {
+        let did_resolver = self.did_resolver;
+        let did = did.to_owned();
+
+        let resolve = async move { did_resolver.resolve(&did).await.map_err(Arc::new) };
+
+        #[cfg(feature = "uniffi")]
+        let resolve = resolve.boxed();
+
+        #[cfg(not(feature = "uniffi"))]
+        let resolve = resolve.boxed_local();
+
+        resolve.shared()
+    }
+}
+
+#[cfg_attr(feature = "uniffi", async_trait)]
+#[cfg_attr(not(feature = "uniffi"), async_trait(?Send))]
+impl<'dr> DIDResolver for CachingDIDResolver<'dr> {
+    async fn resolve(&self, did: &str) -> Result<Option<DIDDoc>> {
+        let resolve = self
+            .cache
+            .lock()
+            .map_err(|_| err_msg(ErrorKind::InvalidState, "Unable lock did cache"))?
+            .entry(did.to_owned())
+            .or_insert_with(|| self._resolve(did))
+            .clone();
+
+        let res = resolve.await;
+
+        if res.is_err() {
+            let mut cache = self
+                .cache
+                .lock()
+                .map_err(|_| err_msg(ErrorKind::InvalidState, "Unable lock did cache"))?;
+
+            // Entry can be already replaced by the retry of other request
+            if let Some(Err(_)) = cache.get(did).and_then(|resolve| resolve.peek()) {
+                cache.remove(did);
+            }
+        }
+
+        res.map_err(|err| err_msg(err.kind(), format!("{:#}", err.source)))
+    }
+
+    async fn resolve_verification_method(
+        &self,
+        kid: &str,
+        purpose: Option<Purpose>,
+    ) -> Result<Option<VerificationMethod>> {
+        if self.did_resolver.resolves_keys_directly() {
+            return self
+                .did_resolver
+                .resolve_verification_method(kid, purpose)
+                .await;
+        }
+
+        let (did, _) = did_or_url(kid);
+
+        let vm = self
+            .resolve(did)
+            .await?
+            .and_then(|did_doc| did_doc_verification_method(&did_doc, kid, purpose));
+
+        Ok(vm)
+    }
+
+    fn resolves_keys_directly(&self) -> bool {
+        self.did_resolver.resolves_keys_directly()
+    }
+}
+
+/// Looks up the verification method with the given key ID directly without resolving
+/// of DID Doc if the resolver is able to (see `DIDResolver::resolves_keys_directly`).
+/// The key must be declared in the given verification relationship.
+/// Returns None if it isn't able to or the key isn't found,
+/// so the caller falls back to resolving of DID Doc.
+pub(crate) async fn resolve_key_directly<'dr>(
+    kid: &str,
+    purpose: Purpose,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+) -> Result<Option<VerificationMethod>> {
+    if !did_resolver.resolves_keys_directly() {
+        return Ok(None);
+    }
+
+    did_resolver
+        .resolve_verification_method(kid, Some(purpose))
+        .await
+}
+
+/// Looks for the verification method with the given key ID in the DID Doc.
+/// If `purpose` is set, the key must be declared in this verification relationship.
+pub(crate) fn did_doc_verification_method(
+    did_doc: &DIDDoc,
+    kid: &str,
+    purpose: Option<Purpose>,
+) -> Option<VerificationMethod> {
+    if let Some(purpose) = purpose {
+        if !key_purposes(did_doc, kid).contains(&purpose) {
+            return None;
+        }
+    }
+
+    did_doc
+        .verification_methods
+        .iter()
+        .find(|vm| vm.id == kid)
+        .cloned()
+}
+
+/// Looks for the verification method with the given key ID in the DID Doc.
+/// If it isn't embedded in the DID Doc (for ex. DID Doc only references the key),
+/// it's resolved with `DIDResolver::resolve_verification_method`.
+pub(crate) async fn find_verification_method<'dr>(
+    did_doc: &DIDDoc,
+    kid: &str,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+) -> Result<Option<VerificationMethod>> {
+    if let Some(vm) = did_doc.verification_methods.iter().find(|vm| vm.id == kid) {
+        return Ok(Some(vm.clone()));
+    }
+
+    did_resolver.resolve_verification_method(kid, None).await
+}
+
+/// Checks whether the given key IDs refer to the same key material (for ex. the same P-256 key
+/// declared for both authentication and key agreement). Unresolvable keys and keys
+/// of unknown types are considered different.
+pub(crate) async fn is_same_key<'dr>(
+    kid: &str,
+    other_kid: &str,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+) -> Result<bool> {
+    if kid == other_kid {
+        return Ok(true);
+    }
+
+    let (vm, other_vm) = match (
+        did_resolver.resolve_verification_method(kid, None).await?,
+        did_resolver
+            .resolve_verification_method(other_kid, None)
+            .await?,
+    ) {
+        (Some(vm), Some(other_vm)) => (vm, other_vm),
+        _ => return Ok(false),
+    };
+
+    if vm.key_alg() == KnownKeyAlg::Unsupported || vm.key_alg() != other_vm.key_alg() {
+        return Ok(false);
+    }
+
+    Ok(vm.as_key_pair()?.public_bytes()? == other_vm.as_key_pair()?.public_bytes()?)
+}
+
+/// JWK `crv` of curves usable for ECDH key agreement in JOSE (RFC 7518, RFC 8037).
+const ECDH_CURVES: &[&str] = &["X25519", "X448", "P-256", "P-384", "P-521"];
+
+/// Checks that the verification method is declared in `keyAgreement` of the DID Doc
+/// and its key can be used for ECDH, so it's either supported by key agreement algorithms
+/// or is a JWK of ECDH curve. Keys of ECDH curves without supported algorithms pass the check
+/// and are skipped later on key agreement algorithm selection.
+pub(crate) fn check_key_agreement_method(
+    did_doc: &DIDDoc,
+    vm: &VerificationMethod,
+    algorithms: &AlgorithmRegistry,
+) -> Result<()> {
+    if !did_doc.key_agreements.contains(&vm.id) {
+        Err(err_msg(
+            ErrorKind::Malformed,
+            format!("Key {} isn't declared as key agreement", vm.id),
+        ))?
+    }
+
+    let ecdh_curve = match vm.verification_material {
+        VerificationMaterial::JWK { ref value } => value["crv"]
+            .as_str()
+            .map_or(false, |crv| ECDH_CURVES.contains(&crv)),
+        _ => false,
+    };
+
+    if !ecdh_curve && algorithms.key_agreement_algorithm_for_method(vm).is_none() {
+        Err(err_msg(
+            ErrorKind::Malformed,
+            format!("Key agreement {} is of curve unusable for ECDH", vm.id),
+        ))?
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use async_trait::async_trait;
+
+    use crate::{
+        algorithms::{AlgorithmRegistry, AnonCryptAlg},
+        did::{
+            key_purposes,
+            resolvers::{ExampleDIDResolver, RecordingDIDResolver, SlowDIDResolver},
+            DIDDoc, DIDResolver, Purpose, VerificationMethod,
+        },
+        error::{ErrorKind, Result},
+        message::anoncrypt,
+        secrets::resolvers::ExampleSecretsResolver,
+        test_vectors::{
+            ALICE_DID, ALICE_DID_DOC, ALICE_SECRETS, BOB_DID, BOB_DID_DOC, BOB_SECRETS,
+            BOB_VERIFICATION_METHOD_KEY_AGREEM_X25519_1, MESSAGE_SIMPLE,
+        },
+        Message, PackEncryptedOptions, UnpackOptions,
+    };
+
+    use super::{resolve_key_directly, CachingDIDResolver, TimeoutDIDResolver};
+
+    #[tokio::test]
+    async fn resolve_verification_method_works() {
+        let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+
+        let vm = did_resolver
+            .resolve_verification_method(&BOB_VERIFICATION_METHOD_KEY_AGREEM_X25519_1.id, None)
+            .await
+            .expect("resolve_verification_method is ok.");
+
+        assert_eq!(
+            vm.map(|vm| vm.id),
+            Some(BOB_VERIFICATION_METHOD_KEY_AGREEM_X25519_1.id.clone())
+        );
+
+        let vm = did_resolver
+            .resolve_verification_method("did:example:bob#not-found", None)
+            .await
+            .expect("resolve_verification_method is ok.");
+
+        assert!(vm.is_none());
+
+        let vm = did_resolver
+            .resolve_verification_method("did:example:unknown#key-1", None)
+            .await
+            .expect("resolve_verification_method is ok.");
+
+        assert!(vm.is_none());
+    }
+
+    #[tokio::test]
+    async fn resolve_verification_method_works_purpose() {
+        let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+        let kid = &BOB_VERIFICATION_METHOD_KEY_AGREEM_X25519_1.id;
+
+        let vm = did_resolver
+            .resolve_verification_method(kid, Some(Purpose::KeyAgreement))
+            .await
+            .expect("resolve_verification_method is ok.");
+
+        assert_eq!(vm.map(|vm| vm.id), Some(kid.clone()));
+
+        let vm = did_resolver
+            .resolve_verification_method(kid, Some(Purpose::Authentication))
+            .await
+            .expect("resolve_verification_method is ok.");
+
+        assert!(vm.is_none());
+    }
+
+    #[tokio::test]
+    async fn resolve_key_directly_works_purpose() {
+        let did_resolver = KeyIndexedDIDResolver::new(vec![], &[&BOB_DID_DOC], true);
+        let kid = &BOB_VERIFICATION_METHOD_KEY_AGREEM_X25519_1.id;
+
+        let vm = resolve_key_directly(kid, Purpose::KeyAgreement, &did_resolver)
+            .await
+            .expect("resolve_key_directly is ok.");
+
+        assert_eq!(vm.map(|vm| vm.id), Some(kid.clone()));
+
+        // Key agreement key isn't accepted as signer key
+        let vm = resolve_key_directly(kid, Purpose::Authentication, &did_resolver)
+            .await
+            .expect("resolve_key_directly is ok.");
+
+        assert!(vm.is_none());
+    }
+
+    #[tokio::test]
+    async fn resolve_verification_method_works_overridden() {
+        // DID Doc only references key agreement keys, materials are kept in key-indexed storage
+        let bob_did_doc = DIDDoc {
+            verification_methods: vec![],
+            ..BOB_DID_DOC.clone()
+        };
+
+        let did_resolver = KeyIndexedDIDResolver::new(vec![bob_did_doc], &[&BOB_DID_DOC], false);
+        let did_resolver = RecordingDIDResolver::new(&did_resolver);
+
+        let plaintext = serde_json::to_string(&*MESSAGE_SIMPLE).expect("serialize is ok.");
+
+        let (msg, to_kids) = anoncrypt(
+            BOB_DID,
+            None,
+            &did_resolver,
+            plaintext.as_bytes(),
+            &AnonCryptAlg::default(),
+            &AlgorithmRegistry::default(),
+            None,
+            None,
+            None,
+            false,
+        )
+        .await
+        .expect("anoncrypt is ok.");
+
+        assert!(!to_kids.is_empty());
+
+        // Only recipient DID Doc is resolved, keys are looked up directly
+        // instead of resolving full DID Doc for each key agreement.
+        assert_eq!(did_resolver.resolved_dids(), vec![BOB_DID]);
+
+        assert_eq!(
+            did_resolver.resolved_kids().len(),
+            BOB_DID_DOC.key_agreements.len()
+        );
+
+        let (unpacked, _) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&unpacked, &*MESSAGE_SIMPLE);
+    }
+
+    #[tokio::test]
+    async fn resolve_verification_method_works_keys_directly() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let (msg, _) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                Some(ALICE_DID),
+                Some(ALICE_DID),
+                &did_resolver,
+                &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("pack_encrypted is ok.");
+
+        let did_resolver = KeyIndexedDIDResolver::new(
+            vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()],
+            &[&ALICE_DID_DOC, &BOB_DID_DOC],
+            true,
+        );
+        let did_resolver = RecordingDIDResolver::new(&did_resolver);
+
+        let (unpacked, metadata) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&unpacked, &*MESSAGE_SIMPLE);
+        assert!(metadata.authenticated);
+        assert!(metadata.non_repudiation);
+
+        // Sender and signer keys are looked up directly without resolving of DID Docs
+        assert!(did_resolver.resolved_dids().is_empty());
+        assert_eq!(did_resolver.resolved_kids().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn timeout_did_resolver_works() {
+        let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+        let slow_did_resolver = SlowDIDResolver::new(&did_resolver, Duration::from_millis(50));
+
+        for timeout in [None, Some(Duration::from_secs(10))] {
+            let did_doc = TimeoutDIDResolver::new(&slow_did_resolver, timeout)
+                .resolve(BOB_DID)
+                .await
+                .expect("resolve is ok.");
+
+            assert_eq!(did_doc.map(|d| d.did), Some(BOB_DID.to_owned()));
+        }
+    }
+
+    #[tokio::test]
+    async fn timeout_did_resolver_works_timed_out() {
+        let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+        let slow_did_resolver = SlowDIDResolver::new(&did_resolver, Duration::from_secs(10));
+
+        let did_resolver =
+            TimeoutDIDResolver::new(&slow_did_resolver, Some(Duration::from_millis(10)));
+
+        let err = did_resolver.resolve(BOB_DID).await.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IoError);
+
+        let err = did_resolver
+            .resolve_verification_method(&BOB_VERIFICATION_METHOD_KEY_AGREEM_X25519_1.id, None)
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IoError);
+    }
+
+    #[tokio::test]
+    async fn caching_did_resolver_works_concurrent() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let recording_did_resolver = RecordingDIDResolver::new(&did_resolver);
+
+        let slow_did_resolver =
+            SlowDIDResolver::new(&recording_did_resolver, Duration::from_millis(50));
+
+        let did_resolver = CachingDIDResolver::new(&slow_did_resolver);
+
+        let dids = [BOB_DID, ALICE_DID, BOB_DID, BOB_DID, ALICE_DID];
+
+        let did_docs =
+            futures::future::join_all(dids.iter().map(|did| did_resolver.resolve(did))).await;
+
+        for (did_doc, did) in did_docs.into_iter().zip(dids) {
+            let did_doc = did_doc.expect("resolve is ok.");
+            assert_eq!(did_doc.map(|d| d.did), Some(did.to_owned()));
+        }
+
+        // Concurrent requests of the same DID share one in-flight resolution
+        assert_eq!(recording_did_resolver.resolved_dids().len(), 2);
+
+        let did_doc = did_resolver.resolve(BOB_DID).await.expect("resolve is ok.");
+        assert_eq!(did_doc.map(|d| d.did), Some(BOB_DID.to_owned()));
+        assert_eq!(recording_did_resolver.resolved_dids().len(), 2);
+    }
+
+    struct KeyIndexedDIDResolver {
+        did_resolver: ExampleDIDResolver,
+        keys: HashMap<String, (VerificationMethod, Vec<Purpose>)>,
+        resolves_keys_directly: bool,
+    }
+
+    impl KeyIndexedDIDResolver {
+        /// Resolves the given DID Docs, keys of `key_did_docs` are kept in key-indexed storage.
+        fn new(
+            did_docs: Vec<DIDDoc>,
+            key_did_docs: &[&DIDDoc],
+            resolves_keys_directly: bool,
+        ) -> Self {
+            KeyIndexedDIDResolver {
+                did_resolver: ExampleDIDResolver::new(did_docs),
+                keys: key_did_docs
+                    .iter()
+                    .flat_map(|did_doc| {
+                        did_doc.verification_methods.iter().map(move |vm| {
+                            (vm.id.clone(), (vm.clone(), key_purposes(did_doc, &vm.id)))
+                        })
+                    })
+                    .collect(),
+                resolves_keys_directly,
+            }
+        }
+    }
+
+    #[cfg_attr(feature = "uniffi", async_trait)]
+    #[cfg_attr(not(feature = "uniffi"), async_trait(?Send))]
+    impl DIDResolver for KeyIndexedDIDResolver {
+        async fn resolve(&self, did: &str) -> Result<Option<DIDDoc>> {
+            self.did_resolver.resolve(did).await
+        }
+
+        async fn resolve_verification_method(
+            &self,
+            kid: &str,
+            purpose: Option<Purpose>,
+        ) -> Result<Option<VerificationMethod>> {
+            let vm = self
+                .keys
+                .get(kid)
+                .filter(|(_, purposes)| purpose.map_or(true, |p| purposes.contains(&p)))
+                .map(|(vm, _)| vm.clone());
+
+            Ok(vm)
+        }
+
+        fn resolves_keys_directly(&self) -> bool {
+            self.resolves_keys_directly
+        }
+    }
 }
@@ -0,0 +1,265 @@
+//! Selection of DIDComm messaging service (https://identity.foundation/didcomm-messaging/spec/#did-document-service-endpoint)
+
+use serde_json::Value;
+
+use crate::did::{DIDDoc, Service, ServiceKind};
+
+/// Service types of legacy (not typed as `DIDCommMessaging` kind) DIDComm service entries.
+const LEGACY_SERVICE_TYPES: [&str; 2] = ["DIDCommMessaging", "did-communication"];
+
+/// Finds DIDComm messaging service in the resolved DID Doc the message can be sent to.
+/// Both `DIDCommMessaging` services and legacy service entries (`DIDCommMessaging` or
+/// `did-communication` typed JSON) are considered.
+///
+/// # Params
+/// - `did_doc` resolved DID Doc of the recipient.
+/// - `preferred_accept` media type profiles (for ex. `didcomm/v2`) supported by the sender
+///   ordered by preference. If empty the first DIDComm service is returned.
+///
+/// # Returns
+/// Tuple `(service_id, service_endpoint, routing_keys)` of the first service accepting the most
+/// preferred profile. Services that don't declare accepted profiles are used only if no service
+/// explicitly accepts any of preferred profiles. None if there is no suitable service.
+pub fn find_didcomm_service(
+    did_doc: &DIDDoc,
+    preferred_accept: &[String],
+) -> Option<(String, String, Vec<String>)> {
+    let services: Vec<_> = did_doc
+        .services
+        .iter()
+        .filter_map(_as_didcomm_service)
+        .collect();
+
+    let service = if preferred_accept.is_empty() {
+        services.into_iter().next()
+    } else {
+        let preferred = preferred_accept
+            .iter()
+            .find_map(|profile| services.iter().position(|s| s.accept.contains(profile)));
+
+        match preferred {
+            Some(i) => services.into_iter().nth(i),
+            None => services.into_iter().find(|s| s.accept.is_empty()),
+        }
+    };
+
+    service.map(|s| (s.id, s.service_endpoint, s.routing_keys))
+}
+
+struct DIDCommService {
+    id: String,
+    service_endpoint: String,
+    accept: Vec<String>,
+    routing_keys: Vec<String>,
+}
+
+fn _as_didcomm_service(service: &Service) -> Option<DIDCommService> {
+    match service.kind {
+        ServiceKind::DIDCommMessaging { ref value } => Some(DIDCommService {
+            id: service.id.clone(),
+            service_endpoint: value.service_endpoint.clone(),
+            accept: value.accept.clone(),
+            routing_keys: value.routing_keys.clone(),
+        }),
+        ServiceKind::Other { ref value } => {
+            let type_ = value.get("type")?.as_str()?;
+
+            if !LEGACY_SERVICE_TYPES.contains(&type_) {
+                return None;
+            }
+
+            // Endpoint is either URI or object with `uri`, `accept` and `routingKeys` fields
+            let endpoint = value.get("serviceEndpoint")?;
+
+            let (service_endpoint, props) = match endpoint {
+                Value::String(uri) => (uri.clone(), value),
+                Value::Object(props) => (props.get("uri")?.as_str()?.to_owned(), endpoint),
+                _ => return None,
+            };
+
+            Some(DIDCommService {
+                id: service.id.clone(),
+                service_endpoint,
+                accept: _strings(props.get("accept")),
+                routing_keys: _strings(props.get("routingKeys")),
+            })
+        }
+    }
+}
+
+fn _strings(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .map(|values| {
+            values
+                .iter()
+                .filter_map(|v| v.as_str().map(str::to_owned))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        did::{DIDCommMessagingService, DIDDoc, Service, ServiceKind},
+        test_vectors::{BOB_DID_DOC, BOB_SERVICE, CHARLIE_DID_DOC},
+    };
+
+    use super::find_didcomm_service;
+
+    #[test]
+    fn find_didcomm_service_works() {
+        let did_doc = _did_doc_with_services();
+
+        assert_eq!(
+            find_didcomm_service(&did_doc, &[]),
+            Some((
+                "did:example:bob#didcomm-v2".into(),
+                "https://example.com/v2".into(),
+                vec!["did:example:mediator1#key-x25519-1".into()],
+            ))
+        );
+
+        assert_eq!(
+            find_didcomm_service(&did_doc, &["didcomm/v2".into()]),
+            Some((
+                "did:example:bob#didcomm-v2".into(),
+                "https://example.com/v2".into(),
+                vec!["did:example:mediator1#key-x25519-1".into()],
+            ))
+        );
+
+        assert_eq!(
+            find_didcomm_service(
+                &did_doc,
+                &["didcomm/aip2;env=rfc19".into(), "didcomm/v2".into()]
+            ),
+            Some((
+                "did:example:bob#legacy".into(),
+                "https://example.com/legacy".into(),
+                vec!["did:example:mediator2#key-x25519-1".into()],
+            ))
+        );
+
+        assert_eq!(
+            find_didcomm_service(&did_doc, &["didcomm/aip2;env=rfc587".into()]),
+            Some((
+                "did:example:bob#didcomm-object".into(),
+                "https://example.com/object".into(),
+                vec![],
+            ))
+        );
+
+        assert_eq!(
+            find_didcomm_service(&did_doc, &["didcomm/unknown".into()]),
+            Some((
+                "did:example:bob#didcomm-no-accept".into(),
+                "https://example.com/any".into(),
+                vec![],
+            ))
+        );
+    }
+
+    #[test]
+    fn find_didcomm_service_works_test_vectors() {
+        assert_eq!(
+            find_didcomm_service(&BOB_DID_DOC, &["didcomm/v2".into()]).map(|s| s.0),
+            Some(BOB_SERVICE.id.clone())
+        );
+
+        assert_eq!(
+            find_didcomm_service(&CHARLIE_DID_DOC, &[]).map(|s| s.1),
+            Some("did:example:mediator3".into())
+        );
+    }
+
+    #[test]
+    fn find_didcomm_service_works_no_service() {
+        let mut did_doc = _did_doc_with_services();
+
+        did_doc
+            .services
+            .retain(|s| s.id != "did:example:bob#didcomm-no-accept");
+
+        assert_eq!(
+            find_didcomm_service(&did_doc, &["didcomm/unknown".into()]),
+            None
+        );
+
+        did_doc.services = vec![Service {
+            id: "did:example:bob#linked-domain".into(),
+            kind: ServiceKind::Other {
+                value: json!({
+                    "type": "LinkedDomains",
+                    "serviceEndpoint": "https://bar.example.com"
+                }),
+            },
+        }];
+
+        assert_eq!(find_didcomm_service(&did_doc, &[]), None);
+    }
+
+    fn _did_doc_with_services() -> DIDDoc {
+        let mut did_doc = BOB_DID_DOC.clone();
+
+        did_doc.services = vec![
+            Service {
+                id: "did:example:bob#linked-domain".into(),
+                kind: ServiceKind::Other {
+                    value: json!({
+                        "type": "LinkedDomains",
+                        "serviceEndpoint": "https://bar.example.com"
+                    }),
+                },
+            },
+            Service {
+                id: "did:example:bob#didcomm-v2".into(),
+                kind: ServiceKind::DIDCommMessaging {
+                    value: DIDCommMessagingService {
+                        service_endpoint: "https://example.com/v2".into(),
+                        accept: vec!["didcomm/v2".into()],
+                        routing_keys: vec!["did:example:mediator1#key-x25519-1".into()],
+                    },
+                },
+            },
+            Service {
+                id: "did:example:bob#legacy".into(),
+                kind: ServiceKind::Other {
+                    value: json!({
+                        "type": "did-communication",
+                        "serviceEndpoint": "https://example.com/legacy",
+                        "accept": ["didcomm/aip2;env=rfc19"],
+                        "routingKeys": ["did:example:mediator2#key-x25519-1"]
+                    }),
+                },
+            },
+            Service {
+                id: "did:example:bob#didcomm-object".into(),
+                kind: ServiceKind::Other {
+                    value: json!({
+                        "type": "DIDCommMessaging",
+                        "serviceEndpoint": {
+                            "uri": "https://example.com/object",
+                            "accept": ["didcomm/aip2;env=rfc587"]
+                        }
+                    }),
+                },
+            },
+            Service {
+                id: "did:example:bob#didcomm-no-accept".into(),
+                kind: ServiceKind::DIDCommMessaging {
+                    value: DIDCommMessagingService {
+                        service_endpoint: "https://example.com/any".into(),
+                        accept: vec![],
+                        routing_keys: vec![],
+                    },
+                },
+            },
+        ];
+
+        did_doc
+    }
+}
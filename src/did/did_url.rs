@@ -0,0 +1,138 @@
+//! Validation of DIDs and DID URLs (https://www.w3.org/TR/did-core/#did-syntax)
+//! used as key IDs.
+
+use crate::error::{err_msg, ErrorKind, Result};
+
+/// Parses DID or DID URL with a fragment (key ID) validating its syntax.
+/// DID URLs with path or query aren't accepted as they can't identify a key.
+///
+/// # Params
+/// - `s` DID (for ex. `did:example:alice`) or DID URL (for ex. `did:example:alice#key-1`).
+///
+/// # Returns
+/// Tuple `(did, fragment)`, fragment is `None` for a bare DID.
+///
+/// # Errors
+/// - `IllegalArgument` the value is not a valid DID or DID URL.
+pub fn parse_did_url(s: &str) -> Result<(&str, Option<&str>)> {
+    let (did, fragment) = match s.split_once('#') {
+        Some((did, fragment)) => (did, Some(fragment)),
+        None => (s, None),
+    };
+
+    let invalid = |reason: &str| {
+        err_msg(
+            ErrorKind::IllegalArgument,
+            format!("`{}` is not a valid DID or DID URL: {}", s, reason),
+        )
+    };
+
+    let (method, id) = did
+        .strip_prefix("did:")
+        .and_then(|rest| rest.split_once(':'))
+        .ok_or_else(|| invalid("expected `did:<method>:<method-specific-id>`"))?;
+
+    if method.is_empty()
+        || !method
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+    {
+        Err(invalid("method name must be lowercase letters and digits"))?
+    }
+
+    if id.is_empty() || id.ends_with(':') || !id.chars().all(_is_id_char) {
+        Err(invalid("invalid method specific ID"))?
+    }
+
+    if let Some(fragment) = fragment {
+        if fragment.is_empty() || fragment.contains(|c: char| c == '#' || c.is_whitespace()) {
+            Err(invalid("invalid fragment"))?
+        }
+    }
+
+    Ok((did, fragment))
+}
+
+fn _is_id_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '%' | ':')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorKind;
+
+    use super::parse_did_url;
+
+    #[test]
+    fn parse_did_url_works_did() {
+        assert_eq!(
+            parse_did_url("did:example:alice").expect("parse is ok."),
+            ("did:example:alice", None)
+        );
+
+        assert_eq!(
+            parse_did_url("did:web:example.com:users:alice").expect("parse is ok."),
+            ("did:web:example.com:users:alice", None)
+        );
+
+        assert_eq!(
+            parse_did_url("did:peer:2.Ez6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc")
+                .expect("parse is ok."),
+            (
+                "did:peer:2.Ez6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc",
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn parse_did_url_works_did_url() {
+        assert_eq!(
+            parse_did_url("did:example:alice#key-1").expect("parse is ok."),
+            ("did:example:alice", Some("key-1"))
+        );
+
+        assert_eq!(
+            parse_did_url("did:example:alice#6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc")
+                .expect("parse is ok."),
+            (
+                "did:example:alice",
+                Some("6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc")
+            )
+        );
+    }
+
+    #[test]
+    fn parse_did_url_works_invalid() {
+        for s in [
+            "",
+            "not-a-did",
+            "did",
+            "did:",
+            "did::",
+            "did:example",
+            "did:example:",
+            "did::alice",
+            "did:Example:alice",
+            "did:example:alice:",
+            "did:example:al ice",
+            "did:example:alice/path",
+            "did:example:alice?query=1",
+            "did:example:alice#",
+            "did:example:alice#key#1",
+            "#key-1",
+            "example:alice",
+            "https://example.com",
+        ] {
+            let err = parse_did_url(s).expect_err("res is ok");
+            assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+        }
+
+        let err = parse_did_url("not-a-did").expect_err("res is ok");
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: `not-a-did` is not a valid DID or DID URL: expected `did:<method>:<method-specific-id>`"
+        );
+    }
+}
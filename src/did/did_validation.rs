@@ -0,0 +1,113 @@
+//! Validation of key material of resolved DID Docs
+
+use crate::{did::DIDDoc, utils::crypto::AsKnownKeyPair};
+
+/// Checks that all verification methods of the DID Doc contain well-formed
+/// key material (JWK, base58 or multibase) of types supported by the library.
+/// Allows to catch bugs of DID resolvers before trusting resolved DID Docs.
+///
+/// # Params
+/// - `did_doc` resolved DID Doc to be validated.
+///
+/// # Returns
+/// `Ok` if key material of all verification methods is valid, otherwise
+/// the list of issues (one for each invalid verification method) in form `<id>: <issue>`.
+pub fn validate_did_doc(did_doc: &DIDDoc) -> Result<(), Vec<String>> {
+    let issues: Vec<_> = did_doc
+        .verification_methods
+        .iter()
+        .filter_map(|vm| match vm.as_key_pair() {
+            Ok(_) => None,
+            Err(err) => Some(format!("{}: {}", vm.id, err)),
+        })
+        .collect();
+
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(issues)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        did::{DIDDoc, VerificationMaterial, VerificationMethod, VerificationMethodType},
+        test_vectors::{
+            CHARLIE_AUTH_METHOD_25519, CHARLIE_DID_DOC,
+            CHARLIE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
+        },
+    };
+
+    use super::validate_did_doc;
+
+    #[test]
+    fn validate_did_doc_works() {
+        validate_did_doc(&CHARLIE_DID_DOC).expect("validate_did_doc is ok.");
+
+        let did_doc = DIDDoc {
+            verification_methods: vec![],
+            ..CHARLIE_DID_DOC.clone()
+        };
+
+        validate_did_doc(&did_doc).expect("validate_did_doc is ok.");
+    }
+
+    #[test]
+    fn validate_did_doc_works_malformed_keys() {
+        let did_doc = DIDDoc {
+            verification_methods: vec![
+                CHARLIE_VERIFICATION_METHOD_KEY_AGREEM_X25519.clone(),
+                VerificationMethod {
+                    id: "did:example:charlie#key-malformed".into(),
+                    verification_material: VerificationMaterial::JWK {
+                        value: json!({
+                            "kty": "OKP",
+                            "crv": "Ed25519",
+                            "x": "not-a-key",
+                        }),
+                    },
+                    ..CHARLIE_AUTH_METHOD_25519.clone()
+                },
+                VerificationMethod {
+                    id: "did:example:charlie#key-unsupported".into(),
+                    verification_material: VerificationMaterial::JWK {
+                        value: json!({
+                            "kty": "EC",
+                            "crv": "P-384",
+                            "x": "MHNkq1S4lAkqOPGyZ-nWLvAB3mwDbPOSjmRXlG5bfN2ImF8FM7MmBWv21_rZTbTS",
+                            "y": "LaRRbSdJp9yO9qlAD-5O7OvzRqzGSn0f_Cgf3CeOf0fVKLnw5Se1tKG1_3ZTPmjx",
+                        }),
+                    },
+                    ..CHARLIE_AUTH_METHOD_25519.clone()
+                },
+                VerificationMethod {
+                    id: "did:example:charlie#key-mismatched".into(),
+                    type_: VerificationMethodType::X25519KeyAgreementKey2020,
+                    verification_material: VerificationMaterial::Multibase {
+                        value: "z6MkqRYqQiSgvZQdnBytw86Qbs2ZWUkGv22od935YF4s8M7V".into(),
+                    },
+                    ..CHARLIE_AUTH_METHOD_25519.clone()
+                },
+            ],
+            ..CHARLIE_DID_DOC.clone()
+        };
+
+        let issues = validate_did_doc(&did_doc).expect_err("res is ok");
+
+        assert_eq!(issues.len(), 3);
+        assert!(issues[0].starts_with("did:example:charlie#key-malformed: Malformed"));
+
+        assert_eq!(
+            issues[1],
+            "did:example:charlie#key-unsupported: Unsupported crypto or method: Unsupported key type or curve"
+        );
+
+        assert_eq!(
+            issues[2],
+            "did:example:charlie#key-mismatched: Illegal argument: Wrong codec in multibase secret material"
+        );
+    }
+}
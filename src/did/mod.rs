@@ -1,11 +1,23 @@
 pub mod resolvers;
 
 pub(crate) mod did_doc;
+pub(crate) mod did_jwk;
+pub(crate) mod did_peer;
+pub(crate) mod did_purpose;
 pub(crate) mod did_resolver;
+pub(crate) mod did_service;
+pub(crate) mod did_url;
+pub(crate) mod did_validation;
 
 pub use did_doc::{
     DIDCommMessagingService, DIDDoc, Service, ServiceKind, VerificationMaterial,
     VerificationMethod, VerificationMethodType,
 };
 
-pub use did_resolver::DIDResolver;
+pub use did_jwk::build_did_jwk_kid;
+pub use did_peer::build_did_peer_2;
+pub use did_purpose::{key_purposes, Purpose};
+pub use did_resolver::{DIDResolver, VersionedDIDResolver};
+pub use did_service::find_didcomm_service;
+pub use did_url::parse_did_url;
+pub use did_validation::validate_did_doc;
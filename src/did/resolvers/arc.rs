@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    did::{DIDDoc, DIDResolver, Purpose, VerificationMethod},
+    error::Result,
+};
+
+/// DID resolver shared by `Arc`. Can be cloned and stored in structs or moved
+/// to other tasks without `&'dr dyn DIDResolver` lifetimes.
+/// All calls are delegated to the wrapped resolver.
+#[derive(Clone)]
+pub struct ArcDIDResolver(Arc<dyn DIDResolver + Send + Sync>);
+
+impl ArcDIDResolver {
+    pub fn new(resolver: impl DIDResolver + Send + Sync + 'static) -> Self {
+        ArcDIDResolver(Arc::new(resolver))
+    }
+}
+
+impl From<Arc<dyn DIDResolver + Send + Sync>> for ArcDIDResolver {
+    fn from(resolver: Arc<dyn DIDResolver + Send + Sync>) -> Self {
+        ArcDIDResolver(resolver)
+    }
+}
+
+#[cfg_attr(feature = "uniffi", async_trait)]
+#[cfg_attr(not(feature = "uniffi"), async_trait(?Send))]
+impl DIDResolver for ArcDIDResolver {
+    async fn resolve(&self, did: &str) -> Result<Option<DIDDoc>> {
+        self.0.resolve(did).await
+    }
+
+    async fn resolve_verification_method(
+        &self,
+        kid: &str,
+        purpose: Option<Purpose>,
+    ) -> Result<Option<VerificationMethod>> {
+        self.0.resolve_verification_method(kid, purpose).await
+    }
+
+    fn resolves_keys_directly(&self) -> bool {
+        self.0.resolves_keys_directly()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future::join;
+
+    use crate::{
+        did::resolvers::ExampleDIDResolver,
+        secrets::resolvers::{ArcSecretsResolver, ExampleSecretsResolver},
+        test_vectors::{
+            ALICE_DID_DOC, BOB_DID_DOC, BOB_SECRETS, ENCRYPTED_MSG_ANON_XC20P_1,
+            ENCRYPTED_MSG_AUTH_X25519, MESSAGE_SIMPLE,
+        },
+        Message, UnpackOptions,
+    };
+
+    use super::ArcDIDResolver;
+
+    #[tokio::test]
+    async fn arc_resolvers_work_shared() {
+        let did_resolver = ArcDIDResolver::new(ExampleDIDResolver::new(vec![
+            ALICE_DID_DOC.clone(),
+            BOB_DID_DOC.clone(),
+        ]));
+
+        let secrets_resolver =
+            ArcSecretsResolver::new(ExampleSecretsResolver::new(BOB_SECRETS.clone()));
+
+        let unpack = |msg: &'static str| {
+            let did_resolver = did_resolver.clone();
+            let secrets_resolver = secrets_resolver.clone();
+
+            async move {
+                Message::unpack(
+                    msg,
+                    &did_resolver,
+                    &secrets_resolver,
+                    &UnpackOptions::default(),
+                )
+                .await
+            }
+        };
+
+        let (anoncrypted, authcrypted) = join(
+            unpack(ENCRYPTED_MSG_ANON_XC20P_1),
+            unpack(ENCRYPTED_MSG_AUTH_X25519),
+        )
+        .await;
+
+        let (msg, metadata) = anoncrypted.expect("unpack is ok.");
+        assert_eq!(msg, *MESSAGE_SIMPLE);
+        assert!(metadata.anonymous_sender);
+
+        let (msg, metadata) = authcrypted.expect("unpack is ok.");
+        assert_eq!(msg, *MESSAGE_SIMPLE);
+        assert!(metadata.authenticated);
+    }
+}
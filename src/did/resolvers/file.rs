@@ -0,0 +1,234 @@
+use async_trait::async_trait;
+use futures::channel::oneshot;
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+};
+
+use crate::{
+    did::{DIDDoc, DIDResolver},
+    error::{err_msg, ErrorKind, Result, ResultExt},
+};
+
+/// Resolves DIDs from a directory of DID Doc JSON files (for ex. for local development and tests).
+///
+/// DID Doc of the DID is loaded from `{dir}/{sanitized DID}.json` file on the first request,
+/// where the sanitized DID is the DID with all characters except ASCII letters, digits,
+/// `-` and `.` replaced with `_` (for ex. `did_example_alice.json` for `did:example:alice`).
+/// Loaded DID Docs are cached, missing files aren't, so they are picked up once created.
+/// Files are read on a separate thread without holding the cache lock, so neither
+/// the executor nor concurrent requests of other DIDs are blocked by file IO.
+pub struct FileDIDResolver {
+    dir: PathBuf,
+    cache: Mutex<HashMap<String, DIDDoc>>,
+}
+
+impl FileDIDResolver {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        FileDIDResolver {
+            dir: dir.as_ref().to_owned(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Path of the file DID Doc of the given DID is loaded from.
+    pub fn did_doc_path(&self, did: &str) -> PathBuf {
+        let name: String = did
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '.' => c,
+                _ => '_',
+            })
+            .collect();
+
+        self.dir.join(format!("{}.json", name))
+    }
+
+    async fn _load(&self, did: &str) -> Result<Option<DIDDoc>> {
+        let path = self.did_doc_path(did);
+
+        let content = match _read_file(path.clone()).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => Err(err).kind(
+                ErrorKind::IoError,
+                format!("Unable read DID Doc file `{}`", path.display()),
+            )?,
+        };
+
+        let did_doc: DIDDoc = serde_json::from_slice(&content).kind(
+            ErrorKind::Malformed,
+            format!("Unable deserialize DID Doc file `{}`", path.display()),
+        )?;
+
+        if did_doc.did != did {
+            Err(err_msg(
+                ErrorKind::Malformed,
+                format!(
+                    "DID Doc file `{}` contains DID `{}` instead of `{}`",
+                    path.display(),
+                    did_doc.did,
+                    did
+                ),
+            ))?
+        }
+
+        Ok(Some(did_doc))
+    }
+}
+
+/// Reads the file on a separate thread, so the calling executor isn't blocked.
+async fn _read_file(path: PathBuf) -> io::Result<Vec<u8>> {
+    let (sender, receiver) = oneshot::channel();
+
+    thread::Builder::new()
+        .name("didcomm-file-did-resolver".to_owned())
+        .spawn(move || {
+            // Receiver is dropped only if resolving is cancelled
+            let _ = sender.send(fs::read(path));
+        })?;
+
+    receiver
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::Other, "File reading thread panicked"))?
+}
+
+#[cfg_attr(feature = "uniffi", async_trait)]
+#[cfg_attr(not(feature = "uniffi"), async_trait(?Send))]
+impl DIDResolver for FileDIDResolver {
+    async fn resolve(&self, did: &str) -> Result<Option<DIDDoc>> {
+        if let Some(did_doc) = self
+            .cache
+            .lock()
+            .map_err(|_| err_msg(ErrorKind::InvalidState, "Unable lock DID Docs cache"))?
+            .get(did)
+        {
+            return Ok(Some(did_doc.clone()));
+        }
+
+        let did_doc = self._load(did).await?;
+
+        if let Some(ref did_doc) = did_doc {
+            self.cache
+                .lock()
+                .map_err(|_| err_msg(ErrorKind::InvalidState, "Unable lock DID Docs cache"))?
+                .insert(did.to_owned(), did_doc.clone());
+        }
+
+        Ok(did_doc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use uuid::Uuid;
+
+    use crate::{
+        did::DIDResolver,
+        error::ErrorKind,
+        test_vectors::{ALICE_DID, ALICE_DID_DOC, BOB_DID, BOB_DID_DOC},
+    };
+
+    use super::FileDIDResolver;
+
+    #[tokio::test]
+    async fn file_did_resolver_works() {
+        let dir = _temp_dir();
+        let resolver = FileDIDResolver::new(&dir);
+
+        for did_doc in [&*ALICE_DID_DOC, &*BOB_DID_DOC] {
+            fs::write(
+                resolver.did_doc_path(&did_doc.did),
+                serde_json::to_vec(did_doc).expect("Unable serialize"),
+            )
+            .expect("Unable write");
+        }
+
+        assert_eq!(
+            resolver.did_doc_path(ALICE_DID),
+            dir.join("did_example_alice.json")
+        );
+
+        for did_doc in [&*ALICE_DID_DOC, &*BOB_DID_DOC] {
+            let resolved = resolver
+                .resolve(&did_doc.did)
+                .await
+                .expect("resolve is ok.")
+                .expect("did doc is some.");
+
+            assert_eq!(
+                serde_json::to_value(&resolved).expect("Unable serialize"),
+                serde_json::to_value(did_doc).expect("Unable serialize")
+            );
+        }
+
+        // Loaded DID Docs are cached
+        fs::remove_file(resolver.did_doc_path(BOB_DID)).expect("Unable remove");
+
+        let resolved = resolver.resolve(BOB_DID).await.expect("resolve is ok.");
+        assert_eq!(resolved.map(|d| d.did), Some(BOB_DID.to_owned()));
+
+        fs::remove_dir_all(dir).expect("Unable remove dir");
+    }
+
+    #[tokio::test]
+    async fn file_did_resolver_works_missing_file() {
+        let dir = _temp_dir();
+        let resolver = FileDIDResolver::new(&dir);
+
+        let resolved = resolver.resolve(ALICE_DID).await.expect("resolve is ok.");
+        assert!(resolved.is_none());
+
+        // Missing files aren't cached
+        fs::write(
+            resolver.did_doc_path(ALICE_DID),
+            serde_json::to_vec(&*ALICE_DID_DOC).expect("Unable serialize"),
+        )
+        .expect("Unable write");
+
+        let resolved = resolver.resolve(ALICE_DID).await.expect("resolve is ok.");
+        assert_eq!(resolved.map(|d| d.did), Some(ALICE_DID.to_owned()));
+
+        // Not existing directory
+        let resolver = FileDIDResolver::new(dir.join("unknown"));
+
+        let resolved = resolver.resolve(ALICE_DID).await.expect("resolve is ok.");
+        assert!(resolved.is_none());
+
+        fs::remove_dir_all(dir).expect("Unable remove dir");
+    }
+
+    #[tokio::test]
+    async fn file_did_resolver_works_malformed() {
+        let dir = _temp_dir();
+        let resolver = FileDIDResolver::new(&dir);
+
+        fs::write(resolver.did_doc_path(ALICE_DID), "{}").expect("Unable write");
+
+        let err = resolver.resolve(ALICE_DID).await.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        // File contains DID Doc of other DID
+        fs::write(
+            resolver.did_doc_path(ALICE_DID),
+            serde_json::to_vec(&*BOB_DID_DOC).expect("Unable serialize"),
+        )
+        .expect("Unable write");
+
+        let err = resolver.resolve(ALICE_DID).await.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        fs::remove_dir_all(dir).expect("Unable remove dir");
+    }
+
+    fn _temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("didcomm-file-resolver-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).expect("Unable create dir");
+        dir
+    }
+}
@@ -1,6 +1,8 @@
 use async_trait::async_trait;
+use futures_timer::Delay;
 use std::cell::RefCell;
 use std::sync::Mutex;
+use std::time::Duration;
 
 use crate::did::{DIDDoc, DIDResolver};
 
@@ -23,3 +25,24 @@ impl DIDResolver for MockDidResolver {
         self.results.lock().unwrap().borrow_mut().pop().unwrap()
     }
 }
+
+/// Resolves DID Docs with the wrapped resolver after the given delay (for ex. to emulate hanging network calls).
+pub struct SlowDIDResolver<'dr> {
+    resolver: &'dr (dyn DIDResolver + 'dr),
+    delay: Duration,
+}
+
+impl<'dr> SlowDIDResolver<'dr> {
+    pub fn new(resolver: &'dr (dyn DIDResolver + 'dr), delay: Duration) -> Self {
+        Self { resolver, delay }
+    }
+}
+
+#[cfg_attr(feature = "uniffi", async_trait)]
+#[cfg_attr(not(feature = "uniffi"), async_trait(?Send))]
+impl<'dr> DIDResolver for SlowDIDResolver<'dr> {
+    async fn resolve(&self, did: &str) -> crate::error::Result<Option<DIDDoc>> {
+        Delay::new(self.delay).await;
+        self.resolver.resolve(did).await
+    }
+}
@@ -1,9 +1,25 @@
+mod arc;
+#[cfg(any(test, feature = "example-resolvers"))]
 mod example;
+#[cfg(any(test, feature = "example-resolvers"))]
+mod file;
+mod peer;
+mod pre_resolved;
+#[cfg(any(test, feature = "example-resolvers"))]
+mod recording;
 
 #[cfg(test)]
 mod mock;
 
+pub use arc::ArcDIDResolver;
+#[cfg(any(test, feature = "example-resolvers"))]
 pub use example::ExampleDIDResolver;
+#[cfg(any(test, feature = "example-resolvers"))]
+pub use file::FileDIDResolver;
+pub use peer::DIDPeerResolver;
+pub use pre_resolved::PreResolvedDIDResolver;
+#[cfg(any(test, feature = "example-resolvers"))]
+pub use recording::RecordingDIDResolver;
 
 #[cfg(test)]
-pub(crate) use mock::MockDidResolver;
+pub(crate) use mock::{MockDidResolver, SlowDIDResolver};
@@ -0,0 +1,336 @@
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::{
+    did::{
+        DIDCommMessagingService, DIDDoc, DIDResolver, Service, ServiceKind, VerificationMaterial,
+        VerificationMethod, VerificationMethodType,
+    },
+    error::{err_msg, ErrorKind, Result, ResultExt},
+};
+
+const DID_PEER_2_PREFIX: &str = "did:peer:2";
+
+/// Resolves `did:peer` DIDs with numalgo 2
+/// (https://identity.foundation/peer-did-method-spec/#method-2-multiple-inception-key-without-doc).
+///
+/// DID Doc is expanded from the keys and services encoded in the DID itself,
+/// so no external resolution is performed. Other DIDs are not found.
+#[derive(Default)]
+pub struct DIDPeerResolver {}
+
+impl DIDPeerResolver {
+    pub fn new() -> Self {
+        DIDPeerResolver {}
+    }
+}
+
+#[cfg_attr(feature = "uniffi", async_trait)]
+#[cfg_attr(not(feature = "uniffi"), async_trait(?Send))]
+impl DIDResolver for DIDPeerResolver {
+    async fn resolve(&self, did: &str) -> Result<Option<DIDDoc>> {
+        if !did.starts_with(DID_PEER_2_PREFIX) {
+            return Ok(None);
+        }
+
+        resolve_did_peer_2(did).map(Some)
+    }
+}
+
+fn resolve_did_peer_2(did: &str) -> Result<DIDDoc> {
+    let elements = did[DID_PEER_2_PREFIX.len()..]
+        .strip_prefix('.')
+        .ok_or_else(|| err_msg(ErrorKind::Malformed, "Invalid did:peer:2 value"))?
+        .split('.');
+
+    let mut did_doc = DIDDoc {
+        did: did.to_owned(),
+        key_agreements: vec![],
+        authentications: vec![],
+        verification_methods: vec![],
+        services: vec![],
+        also_known_as: vec![],
+    };
+
+    for element in elements {
+        let (purpose, value) = element.split_at(element.chars().next().map_or(0, char::len_utf8));
+
+        match purpose {
+            "E" => {
+                let vm = _key_verification_method(
+                    did,
+                    value,
+                    VerificationMethodType::X25519KeyAgreementKey2020,
+                )?;
+
+                did_doc.key_agreements.push(vm.id.clone());
+                did_doc.verification_methods.push(vm);
+            }
+            "V" => {
+                let vm = _key_verification_method(
+                    did,
+                    value,
+                    VerificationMethodType::Ed25519VerificationKey2020,
+                )?;
+
+                did_doc.authentications.push(vm.id.clone());
+                did_doc.verification_methods.push(vm);
+            }
+            "S" => {
+                let service = base64::decode_config(value, base64::URL_SAFE_NO_PAD)
+                    .kind(ErrorKind::Malformed, "Unable decode did:peer:2 service")?;
+
+                let service: Value = serde_json::from_slice(&service)
+                    .kind(ErrorKind::Malformed, "Unable parse did:peer:2 service")?;
+
+                let services = match service {
+                    Value::Array(services) => services,
+                    service => vec![service],
+                };
+
+                for service in services {
+                    let id = format!("{}#didcommmessaging-{}", did, did_doc.services.len());
+                    did_doc.services.push(_service(id, service)?);
+                }
+            }
+            _ => Err(err_msg(
+                ErrorKind::Malformed,
+                format!("Unsupported did:peer:2 element purpose `{}`", purpose),
+            ))?,
+        }
+    }
+
+    Ok(did_doc)
+}
+
+fn _key_verification_method(
+    did: &str,
+    multibase: &str,
+    type_: VerificationMethodType,
+) -> Result<VerificationMethod> {
+    if !multibase.starts_with('z') {
+        Err(err_msg(
+            ErrorKind::Malformed,
+            "Multibase must start with 'z'",
+        ))?
+    }
+
+    Ok(VerificationMethod {
+        id: format!("{}#{}", did, &multibase[1..]),
+        type_,
+        controller: did.to_owned(),
+        verification_material: VerificationMaterial::Multibase {
+            value: multibase.to_owned(),
+        },
+    })
+}
+
+fn _service(id: String, service: Value) -> Result<Service> {
+    let kind = match service["t"].as_str() {
+        Some("dm") | Some("DIDCommMessaging") => {
+            let service_endpoint = service["s"]
+                .as_str()
+                .ok_or_else(|| {
+                    err_msg(
+                        ErrorKind::Malformed,
+                        "No service endpoint in did:peer:2 service",
+                    )
+                })?
+                .to_owned();
+
+            ServiceKind::DIDCommMessaging {
+                value: DIDCommMessagingService {
+                    service_endpoint,
+                    accept: _string_array(&service["a"])?,
+                    routing_keys: _string_array(&service["r"])?,
+                },
+            }
+        }
+        _ => ServiceKind::Other { value: service },
+    };
+
+    Ok(Service { id, kind })
+}
+
+fn _string_array(value: &Value) -> Result<Vec<String>> {
+    match value {
+        Value::Null => Ok(vec![]),
+        Value::Array(values) => values
+            .iter()
+            .map(|v| {
+                v.as_str().map(|s| s.to_owned()).ok_or_else(|| {
+                    err_msg(
+                        ErrorKind::Malformed,
+                        "Expected array of strings in did:peer:2 service",
+                    )
+                })
+            })
+            .collect(),
+        _ => Err(err_msg(
+            ErrorKind::Malformed,
+            "Expected array of strings in did:peer:2 service",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        did::{DIDResolver, ServiceKind},
+        error::ErrorKind,
+        secrets::resolvers::ExampleSecretsResolver,
+        utils::crypto::{AsKnownKeyPair, KnownKeyAlg},
+        Message, PackEncryptedOptions,
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    const DID_PEER_2: &str = "did:peer:2\
+        .Ez6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc\
+        .Vz6MkqRYqQiSgvZQdnBytw86Qbs2ZWUkGv22od935YF4s8M7V\
+        .Vz6MkgoLTnTypo3tDRwCkZXSccTPHRLhF4ZnjhueYAFpEX6vg\
+        .SeyJ0IjoiZG0iLCJzIjoiaHR0cHM6Ly9leGFtcGxlLmNvbS9lbmRwb2ludCIsInIiOlsiZGlkOmV4YW1wbGU6c29tZW1lZGlhdG9yI3NvbWVrZXkiXSwiYSI6WyJkaWRjb21tL3YyIiwiZGlkY29tbS9haXAyO2Vudj1yZmM1ODciXX0";
+
+    #[tokio::test]
+    async fn did_peer_resolver_works() {
+        let did_doc = DIDPeerResolver::new()
+            .resolve(DID_PEER_2)
+            .await
+            .expect("resolve is ok.")
+            .expect("did doc is found.");
+
+        assert_eq!(did_doc.did, DID_PEER_2);
+
+        assert_eq!(
+            did_doc.key_agreements,
+            vec![format!(
+                "{}#6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc",
+                DID_PEER_2
+            )]
+        );
+
+        assert_eq!(
+            did_doc.authentications,
+            vec![
+                format!(
+                    "{}#6MkqRYqQiSgvZQdnBytw86Qbs2ZWUkGv22od935YF4s8M7V",
+                    DID_PEER_2
+                ),
+                format!(
+                    "{}#6MkgoLTnTypo3tDRwCkZXSccTPHRLhF4ZnjhueYAFpEX6vg",
+                    DID_PEER_2
+                ),
+            ]
+        );
+
+        let key_algs: Vec<_> = did_doc
+            .verification_methods
+            .iter()
+            .map(|vm| vm.key_alg())
+            .collect();
+
+        assert_eq!(
+            key_algs,
+            vec![
+                KnownKeyAlg::X25519,
+                KnownKeyAlg::Ed25519,
+                KnownKeyAlg::Ed25519
+            ]
+        );
+
+        for vm in did_doc.verification_methods.iter() {
+            vm.as_key_pair().expect("key is valid.");
+        }
+
+        assert_eq!(did_doc.services.len(), 1);
+        assert_eq!(
+            did_doc.services[0].id,
+            format!("{}#didcommmessaging-0", DID_PEER_2)
+        );
+
+        match did_doc.services[0].kind {
+            ServiceKind::DIDCommMessaging { ref value } => {
+                assert_eq!(value.service_endpoint, "https://example.com/endpoint");
+                assert_eq!(
+                    value.routing_keys,
+                    vec!["did:example:somemediator#somekey".to_owned()]
+                );
+                assert_eq!(
+                    value.accept,
+                    vec![
+                        "didcomm/v2".to_owned(),
+                        "didcomm/aip2;env=rfc587".to_owned()
+                    ]
+                );
+            }
+            _ => panic!("DIDCommMessaging service expected"),
+        }
+    }
+
+    #[tokio::test]
+    async fn did_peer_resolver_works_other_did() {
+        let did_doc = DIDPeerResolver::new()
+            .resolve("did:example:alice")
+            .await
+            .expect("resolve is ok.");
+
+        assert!(did_doc.is_none());
+    }
+
+    #[tokio::test]
+    async fn did_peer_resolver_works_malformed() {
+        let err = DIDPeerResolver::new()
+            .resolve("did:peer:2.Xz6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc")
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        let err = DIDPeerResolver::new()
+            .resolve("did:peer:2.SnotBase64!")
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_did_peer_2() {
+        let did_resolver = DIDPeerResolver::new();
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+
+        let msg = Message::build(
+            "1234567890".to_owned(),
+            "http://example.com/protocols/lets_do_lunch/1.0/proposal".to_owned(),
+            json!({"messagespecificattribute": "and its value"}),
+        )
+        .to(DID_PEER_2.to_owned())
+        .finalize();
+
+        let (msg, metadata) = msg
+            .pack_encrypted(
+                DID_PEER_2,
+                None,
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("pack is ok.");
+
+        assert_eq!(
+            metadata.to_kids,
+            vec![format!(
+                "{}#6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc",
+                DID_PEER_2
+            )]
+        );
+
+        assert!(msg.contains("6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc"));
+    }
+}
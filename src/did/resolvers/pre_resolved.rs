@@ -0,0 +1,159 @@
+use async_trait::async_trait;
+
+use crate::{
+    did::{
+        did_resolver::did_doc_verification_method, DIDDoc, DIDResolver, Purpose, VerificationMethod,
+    },
+    error::Result,
+    utils::did::did_or_url,
+};
+
+/// Wraps DID resolver and short-circuits resolution of DIDs which DID Docs are already
+/// known to the caller (for ex. the recipient DID Doc got by a prior lookup in a request handler).
+/// Other DIDs are resolved by the wrapped resolver.
+///
+/// Can be passed to `pack_encrypted`, `unpack` and other methods instead of the wrapped resolver.
+pub struct PreResolvedDIDResolver<'dr> {
+    did_docs: Vec<DIDDoc>,
+    resolver: &'dr (dyn DIDResolver + 'dr),
+}
+
+impl<'dr> PreResolvedDIDResolver<'dr> {
+    pub fn new(did_docs: Vec<DIDDoc>, resolver: &'dr (dyn DIDResolver + 'dr)) -> Self {
+        PreResolvedDIDResolver { did_docs, resolver }
+    }
+
+    fn _pre_resolved(&self, did: &str) -> Option<&DIDDoc> {
+        self.did_docs.iter().find(|ddoc| ddoc.did == did)
+    }
+}
+
+#[cfg_attr(feature = "uniffi", async_trait)]
+#[cfg_attr(not(feature = "uniffi"), async_trait(?Send))]
+impl<'dr> DIDResolver for PreResolvedDIDResolver<'dr> {
+    async fn resolve(&self, did: &str) -> Result<Option<DIDDoc>> {
+        match self._pre_resolved(did) {
+            Some(ddoc) => Ok(Some(ddoc.clone())),
+            None => self.resolver.resolve(did).await,
+        }
+    }
+
+    async fn resolve_verification_method(
+        &self,
+        kid: &str,
+        purpose: Option<Purpose>,
+    ) -> Result<Option<VerificationMethod>> {
+        let (did, _) = did_or_url(kid);
+
+        match self._pre_resolved(did) {
+            Some(ddoc) => Ok(did_doc_verification_method(ddoc, kid, purpose)),
+            None => {
+                self.resolver
+                    .resolve_verification_method(kid, purpose)
+                    .await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        did::{
+            resolvers::{ExampleDIDResolver, RecordingDIDResolver},
+            DIDResolver,
+        },
+        secrets::resolvers::ExampleSecretsResolver,
+        test_vectors::{
+            ALICE_AUTH_METHOD_25519, ALICE_DID, ALICE_DID_DOC, ALICE_SECRETS, BOB_DID, BOB_DID_DOC,
+            BOB_SECRETS, MESSAGE_SIMPLE,
+        },
+        Message, PackEncryptedOptions, UnpackOptions,
+    };
+
+    use super::PreResolvedDIDResolver;
+
+    #[tokio::test]
+    async fn pre_resolved_did_resolver_works() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let recording_resolver = RecordingDIDResolver::new(&did_resolver);
+        let resolver = PreResolvedDIDResolver::new(vec![BOB_DID_DOC.clone()], &recording_resolver);
+
+        let ddoc = resolver.resolve(BOB_DID).await.expect("resolve is ok.");
+        assert_eq!(ddoc.map(|d| d.did), Some(BOB_DID.to_owned()));
+
+        let ddoc = resolver.resolve(ALICE_DID).await.expect("resolve is ok.");
+        assert_eq!(ddoc.map(|d| d.did), Some(ALICE_DID.to_owned()));
+
+        let ddoc = resolver
+            .resolve("did:example:unknown")
+            .await
+            .expect("resolve is ok.");
+
+        assert!(ddoc.is_none());
+
+        let vm = resolver
+            .resolve_verification_method(&ALICE_AUTH_METHOD_25519.id, None)
+            .await
+            .expect("resolve_verification_method is ok.");
+
+        assert_eq!(vm.map(|vm| vm.id), Some(ALICE_AUTH_METHOD_25519.id.clone()));
+
+        assert_eq!(
+            recording_resolver.resolved_dids(),
+            vec![ALICE_DID, "did:example:unknown"]
+        );
+
+        assert_eq!(
+            recording_resolver.resolved_kids(),
+            vec![ALICE_AUTH_METHOD_25519.id.clone()]
+        );
+    }
+
+    #[tokio::test]
+    async fn pre_resolved_did_resolver_works_pack_unpack() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        // Sender holds the recipient DID Doc
+        let recording_resolver = RecordingDIDResolver::new(&did_resolver);
+        let resolver = PreResolvedDIDResolver::new(vec![BOB_DID_DOC.clone()], &recording_resolver);
+
+        let (msg, _) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                Some(ALICE_DID),
+                Some(ALICE_DID),
+                &resolver,
+                &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("pack_encrypted is ok.");
+
+        assert_eq!(recording_resolver.resolve_count(BOB_DID), 0);
+        assert!(recording_resolver.resolve_count(ALICE_DID) > 0);
+
+        // Recipient holds the sender DID Doc
+        let recording_resolver = RecordingDIDResolver::new(&did_resolver);
+        let resolver =
+            PreResolvedDIDResolver::new(vec![ALICE_DID_DOC.clone()], &recording_resolver);
+
+        let (unpacked_msg, metadata) = Message::unpack(
+            &msg,
+            &resolver,
+            &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(unpacked_msg, *MESSAGE_SIMPLE);
+        assert!(metadata.authenticated);
+        assert!(metadata.non_repudiation);
+        assert_eq!(recording_resolver.resolve_count(ALICE_DID), 0);
+    }
+}
@@ -0,0 +1,126 @@
+use async_trait::async_trait;
+use std::sync::Mutex;
+
+use crate::{
+    did::{DIDDoc, DIDResolver, Purpose, VerificationMethod},
+    error::{err_msg, ErrorKind, Result},
+};
+
+/// Wraps DID resolver and records DIDs and key IDs requested from it
+/// (for ex. to check caching in tests).
+pub struct RecordingDIDResolver<'dr> {
+    resolver: &'dr (dyn DIDResolver + 'dr),
+    resolved_dids: Mutex<Vec<String>>,
+    resolved_kids: Mutex<Vec<String>>,
+}
+
+impl<'dr> RecordingDIDResolver<'dr> {
+    pub fn new(resolver: &'dr (dyn DIDResolver + 'dr)) -> Self {
+        RecordingDIDResolver {
+            resolver,
+            resolved_dids: Mutex::new(vec![]),
+            resolved_kids: Mutex::new(vec![]),
+        }
+    }
+
+    /// Returns DIDs requested with `resolve` so far in order of requests.
+    pub fn resolved_dids(&self) -> Vec<String> {
+        self.resolved_dids
+            .lock()
+            .map(|dids| dids.clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns key IDs requested with `resolve_verification_method` so far in order of requests.
+    pub fn resolved_kids(&self) -> Vec<String> {
+        self.resolved_kids
+            .lock()
+            .map(|kids| kids.clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns how many times the given DID was requested with `resolve`.
+    pub fn resolve_count(&self, did: &str) -> usize {
+        self.resolved_dids()
+            .iter()
+            .filter(|resolved| *resolved == did)
+            .count()
+    }
+
+    fn _record(resolved: &Mutex<Vec<String>>, id: &str) -> Result<()> {
+        resolved
+            .lock()
+            .map_err(|_| err_msg(ErrorKind::InvalidState, "Unable lock resolved ids"))?
+            .push(id.to_owned());
+
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "uniffi", async_trait)]
+#[cfg_attr(not(feature = "uniffi"), async_trait(?Send))]
+impl<'dr> DIDResolver for RecordingDIDResolver<'dr> {
+    async fn resolve(&self, did: &str) -> Result<Option<DIDDoc>> {
+        Self::_record(&self.resolved_dids, did)?;
+        self.resolver.resolve(did).await
+    }
+
+    async fn resolve_verification_method(
+        &self,
+        kid: &str,
+        purpose: Option<Purpose>,
+    ) -> Result<Option<VerificationMethod>> {
+        Self::_record(&self.resolved_kids, kid)?;
+        self.resolver
+            .resolve_verification_method(kid, purpose)
+            .await
+    }
+
+    fn resolves_keys_directly(&self) -> bool {
+        self.resolver.resolves_keys_directly()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        did::{resolvers::ExampleDIDResolver, DIDResolver},
+        test_vectors::{ALICE_DID, ALICE_DID_DOC, BOB_DID},
+    };
+
+    use super::RecordingDIDResolver;
+
+    #[tokio::test]
+    async fn recording_did_resolver_works() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let did_resolver = RecordingDIDResolver::new(&did_resolver);
+
+        assert!(did_resolver.resolved_dids().is_empty());
+
+        let ddoc = did_resolver
+            .resolve(ALICE_DID)
+            .await
+            .expect("resolve is ok.");
+        assert_eq!(ddoc.map(|ddoc| ddoc.did), Some(ALICE_DID.to_owned()));
+
+        let ddoc = did_resolver.resolve(BOB_DID).await.expect("resolve is ok.");
+        assert!(ddoc.is_none());
+
+        let vm = did_resolver
+            .resolve_verification_method("did:example:alice#key-1", None)
+            .await
+            .expect("resolve_verification_method is ok.");
+
+        assert!(vm.is_some());
+
+        assert_eq!(did_resolver.resolved_dids(), vec![ALICE_DID, BOB_DID]);
+        assert_eq!(
+            did_resolver.resolved_kids(),
+            vec!["did:example:alice#key-1"]
+        );
+
+        assert_eq!(did_resolver.resolve_count(ALICE_DID), 1);
+        assert_eq!(did_resolver.resolve_count(BOB_DID), 1);
+        assert_eq!(did_resolver.resolve_count("did:example:charlie"), 0);
+    }
+}
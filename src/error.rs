@@ -3,6 +3,8 @@ use std::fmt;
 use serde::Serialize;
 use serde_json::error::Category;
 
+use crate::UnpackMetadata;
+
 #[derive(thiserror::Error, Debug, Copy, Clone, Eq, PartialEq, Serialize)]
 pub enum ErrorKind {
     #[error("DID not resolved")]
@@ -31,6 +33,12 @@ pub enum ErrorKind {
 
     #[error("Illegal argument")]
     IllegalArgument,
+
+    #[error("Untrusted")]
+    Untrusted,
+
+    #[error("Too large")]
+    TooLarge,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -38,6 +46,7 @@ pub enum ErrorKind {
 pub struct Error {
     kind: ErrorKind,
     pub source: anyhow::Error,
+    partial_unpack_metadata: Option<Box<UnpackMetadata>>,
 }
 
 impl Error {
@@ -45,6 +54,21 @@ impl Error {
         self.kind
     }
 
+    /// Metadata gathered by `Message::unpack` before the failure if the message
+    /// was successfully decrypted, but a later stage (for ex. signature verification) failed.
+    /// Intended for debugging only, as the message failed to unpack nothing in this
+    /// metadata can be trusted (for ex. the sender isn't authenticated if authcrypt failed).
+    pub fn partial_unpack_metadata(&self) -> Option<&UnpackMetadata> {
+        self.partial_unpack_metadata.as_deref()
+    }
+
+    pub(crate) fn with_partial_unpack_metadata(self, metadata: UnpackMetadata) -> Error {
+        Error {
+            partial_unpack_metadata: Some(Box::new(metadata)),
+            ..self
+        }
+    }
+
     pub fn new<E>(kind: ErrorKind, source: E) -> Error
     where
         E: std::error::Error + Send + Sync + 'static,
@@ -52,6 +76,7 @@ impl Error {
         Error {
             kind,
             source: anyhow::Error::new(source),
+            partial_unpack_metadata: None,
         }
     }
 
@@ -62,6 +87,7 @@ impl Error {
         Error {
             kind,
             source: anyhow::Error::msg(msg),
+            partial_unpack_metadata: None,
         }
     }
 }
@@ -85,6 +111,7 @@ where
         self.map_err(|e| Error {
             kind,
             source: anyhow::Error::new(e).context(msg),
+            partial_unpack_metadata: None,
         })
     }
 }
@@ -122,11 +149,16 @@ impl<T> ResultContext<T> for Result<T> {
         D: fmt::Display + fmt::Debug + Send + Sync + 'static,
     {
         self.map_err(|e| {
-            let Error { kind, source } = e;
+            let Error {
+                kind,
+                source,
+                partial_unpack_metadata,
+            } = e;
 
             Error {
                 kind,
                 source: source.context(msg),
+                partial_unpack_metadata,
             }
         })
     }
@@ -7,7 +7,7 @@ use askar_crypto::{
 
 use crate::{
     error::{err_msg, ErrorKind, Result, ResultContext, ResultExt},
-    jwe::ParsedJWE,
+    jwe::{envelope::JWE, parse::decode_base64url, ParsedJWE},
     jwk::{FromJwkValue, ToJwkValue},
     utils::crypto::{JoseKDF, KeyWrap},
 };
@@ -44,13 +44,13 @@ impl<'a, 'b> ParsedJWE<'a, 'b> {
                 .ok_or_else(|| err_msg(ErrorKind::InvalidState, "Recipient not found"))?
                 .encrypted_key;
 
-            base64::decode_config(encrypted_key, base64::URL_SAFE_NO_PAD)
+            decode_base64url(encrypted_key, self.lenient_base64)
                 .kind(ErrorKind::Malformed, "Unable decode encrypted_key")?
         };
 
         let epk = KE::from_jwk_value(&self.protected.epk).context("Unable instantiate epk")?;
 
-        let tag = base64::decode_config(self.jwe.tag, base64::URL_SAFE_NO_PAD)
+        let tag = decode_base64url(self.jwe.tag, self.lenient_base64)
             .kind(ErrorKind::Malformed, "Unable decode tag")?;
 
         let kw = KDF::derive_key(
@@ -69,10 +69,10 @@ impl<'a, 'b> ParsedJWE<'a, 'b> {
             .unwrap_key(&encrypted_key)
             .kind(ErrorKind::Malformed, "Unable unwrap cek")?;
 
-        let ciphertext = base64::decode_config(self.jwe.ciphertext, base64::URL_SAFE_NO_PAD)
+        let ciphertext = decode_base64url(self.jwe.ciphertext, self.lenient_base64)
             .kind(ErrorKind::Malformed, "Unable decode ciphertext")?;
 
-        let iv = base64::decode_config(self.jwe.iv, base64::URL_SAFE_NO_PAD)
+        let iv = decode_base64url(self.jwe.iv, self.lenient_base64)
             .kind(ErrorKind::Malformed, "Unable decode iv")?;
 
         let plaintext = {
@@ -80,7 +80,9 @@ impl<'a, 'b> ParsedJWE<'a, 'b> {
             buf.extend_from_slice(&ciphertext);
             buf.extend_from_slice(&tag);
 
-            cek.decrypt_in_place(&mut buf, &iv, self.jwe.protected.as_bytes())
+            let aad = JWE::content_aad(self.jwe.protected, self.jwe.aad);
+
+            cek.decrypt_in_place(&mut buf, &iv, &aad)
                 .kind(ErrorKind::Malformed, "Unable decrypt content")?;
 
             buf.to_vec()
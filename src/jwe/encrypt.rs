@@ -1,16 +1,26 @@
 use askar_crypto::{
     buffer::SecretBytes,
     encrypt::{KeyAeadInPlace, KeyAeadMeta},
-    kdf::{FromKeyDerivation, KeyExchange},
+    kdf::{FromKeyDerivation, KeyExchange, KeyMaterial},
     random,
-    repr::{KeyGen, ToSecretBytes},
+    repr::{KeyGen, KeySecretBytes, ToSecretBytes},
 };
 
+use zeroize::Zeroizing;
+
+#[cfg(test)]
+use std::cell::RefCell;
+
+#[cfg(test)]
 use sha2::{Digest, Sha256};
 
 use crate::{
-    error::{ErrorKind, Result, ResultExt},
-    jwe::envelope::{Algorithm, EncAlgorithm, PerRecipientHeader, ProtectedHeader, Recipient, JWE},
+    algorithms::apv_digest,
+    error::{err_msg, ErrorKind, Result, ResultContext, ResultExt},
+    jwe::{
+        envelope::{Algorithm, EncAlgorithm, PerRecipientHeader, ProtectedHeader, Recipient, JWE},
+        NonceSource, SessionKeyProvider,
+    },
     jwk::ToJwkValue,
     utils::crypto::{JoseKDF, KeyWrap},
 };
@@ -19,11 +29,14 @@ pub(crate) fn encrypt<CE, KDF, KE, KW>(
     plaintext: &[u8],
     alg: Algorithm,
     enc: EncAlgorithm,
-    sender: Option<(&str, &KE)>, // (skid, sender key)
-    recipients: &[(&str, &KE)],  // (kid, recipient key)
+    sender: Option<(&str, &KE)>,            // (skid, sender key)
+    recipients: &[(&str, &KE)],             // (kid, recipient key)
+    nonce_source: Option<&dyn NonceSource>, // OS CSPRNG if not set
+    session_key_provider: Option<&dyn SessionKeyProvider>, // random CEK if not set
+    aad: Option<&[u8]>,                     // JWE AAD (not encrypted, but integrity protected)
 ) -> Result<String>
 where
-    CE: KeyAeadInPlace + KeyAeadMeta + KeyGen + ToSecretBytes,
+    CE: KeyAeadInPlace + KeyAeadMeta + KeyGen + KeySecretBytes + ToSecretBytes,
     KDF: JoseKDF<KE, KW>,
     KE: KeyExchange + KeyGen + ToJwkValue,
     KW: KeyWrap + FromKeyDerivation,
@@ -33,21 +46,27 @@ where
         None => (None, None),
     };
 
-    let mut rng = random::default_rng();
-    let cek = CE::generate(&mut rng).kind(ErrorKind::InvalidState, "Unable generate cek")?;
+    let cek = match session_key_provider {
+        Some(session_key_provider) => {
+            let mut cek = Zeroizing::new(vec![0u8; _cek_len(&enc)?]);
+
+            session_key_provider
+                .next_cek(&mut cek)
+                .context("Unable generate cek")?;
 
-    let apv = {
-        let mut kids = recipients.iter().map(|r| r.0).collect::<Vec<_>>();
-        kids.sort();
-        Sha256::digest(kids.join(".").as_bytes())
+            CE::from_secret_bytes(&cek).kind(ErrorKind::InvalidState, "Unable generate cek")?
+        }
+        None => CE::generate(JweRng).kind(ErrorKind::InvalidState, "Unable generate cek")?,
     };
 
-    let epk = KE::generate(&mut rng).kind(ErrorKind::InvalidState, "Unable generate epk")?;
+    let apv = apv_digest(&recipients.iter().map(|r| r.0).collect::<Vec<_>>());
+
+    let epk = KE::generate(JweRng).kind(ErrorKind::InvalidState, "Unable generate epk")?;
 
     let protected = {
         let epk = epk.to_jwk_public_value()?;
         let apu = skid.map(|skid| base64::encode_config(skid, base64::URL_SAFE_NO_PAD));
-        let apv = base64::encode_config(apv, base64::URL_SAFE_NO_PAD);
+        let apv = base64::encode_config(&apv, base64::URL_SAFE_NO_PAD);
 
         let p = ProtectedHeader {
             typ: Some("application/didcomm-encrypted+json"),
@@ -65,6 +84,8 @@ where
         base64::encode_config(&p, base64::URL_SAFE_NO_PAD)
     };
 
+    let aad = aad.map(|aad| base64::encode_config(aad, base64::URL_SAFE_NO_PAD));
+
     let mut buf = {
         let mut buf = SecretBytes::with_capacity(plaintext.len() + cek.aead_params().tag_length);
 
@@ -73,11 +94,21 @@ where
     };
 
     let (ciphertext, tag, tag_raw, iv) = {
-        // TODO: use `rng` based version when available
-        let iv = CE::random_nonce();
+        let mut iv = vec![0u8; cek.aead_params().nonce_length];
+
+        match nonce_source {
+            Some(nonce_source) => nonce_source
+                .fill_nonce(&mut iv)
+                .context("Unable generate nonce")?,
+            None => JweRng.read_okm(&mut iv),
+        }
 
         let ciphertext_len = cek
-            .encrypt_in_place(&mut buf, &iv[..], protected.as_bytes())
+            .encrypt_in_place(
+                &mut buf,
+                &iv[..],
+                &JWE::content_aad(&protected, aad.as_deref()),
+            )
             .kind(ErrorKind::InvalidState, "Unable encrypt content")?;
 
         let ciphertext = &buf.as_ref()[0..ciphertext_len];
@@ -131,6 +162,7 @@ where
         iv: &iv,
         ciphertext: &ciphertext,
         tag: &tag,
+        aad: aad.as_deref(),
     };
 
     let jwe = serde_json::to_string(&jwe).kind(ErrorKind::InvalidState, "Unable serialize jwe")?;
@@ -138,6 +170,87 @@ where
     Ok(jwe)
 }
 
+/// Content encryption key length in bytes for the given content encryption algorithm.
+fn _cek_len(enc: &EncAlgorithm) -> Result<usize> {
+    let len = match enc {
+        EncAlgorithm::A256cbcHs512 => 64,
+        EncAlgorithm::A128cbcHs256 => 32,
+        EncAlgorithm::A256Gcm => 32,
+        EncAlgorithm::Xc20P => 32,
+        EncAlgorithm::A128Gcm => 16,
+        EncAlgorithm::Other(_) => Err(err_msg(
+            ErrorKind::Unsupported,
+            "Unsupported content encryption algorithm",
+        ))?,
+    };
+
+    Ok(len)
+}
+
+/// Source of randomness for CEK, ephemeral keys and IVs.
+/// Uses OS CSPRNG unless deterministic RNG is set for the current thread (in tests only).
+struct JweRng;
+
+impl KeyMaterial for JweRng {
+    fn read_okm(&mut self, buf: &mut [u8]) {
+        #[cfg(test)]
+        {
+            let filled = DETERMINISTIC_RNG.with(|rng| match *rng.borrow_mut() {
+                Some(ref mut rng) => {
+                    rng.fill(buf);
+                    true
+                }
+                None => false,
+            });
+
+            if filled {
+                return;
+            }
+        }
+
+        random::fill_random(buf);
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    static DETERMINISTIC_RNG: RefCell<Option<DeterministicRng>> = RefCell::new(None);
+}
+
+/// Makes encryption in the current thread deterministic: CEK, ephemeral keys and IVs
+/// are derived from the given seed, so packing the same message with the same seed
+/// produces byte-identical output. `None` restores OS CSPRNG.
+///
+/// Compiled in tests only, as predictable ephemeral keys allow anyone to decrypt messages.
+/// Production code can control IVs and CEKs via `NonceSource` and `SessionKeyProvider`.
+#[cfg(test)]
+pub(crate) fn set_deterministic_rng(seed: Option<[u8; 32]>) {
+    DETERMINISTIC_RNG
+        .with(|rng| *rng.borrow_mut() = seed.map(|seed| DeterministicRng { seed, counter: 0 }));
+}
+
+/// SHA-256 in counter mode over the seed.
+#[cfg(test)]
+struct DeterministicRng {
+    seed: [u8; 32],
+    counter: u64,
+}
+
+#[cfg(test)]
+impl DeterministicRng {
+    fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(32) {
+            let mut hasher = Sha256::new();
+            hasher.update(&self.seed);
+            hasher.update(&self.counter.to_le_bytes());
+            self.counter += 1;
+
+            let block = hasher.finalize();
+            chunk.copy_from_slice(&block[..chunk.len()]);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use askar_crypto::{
@@ -147,6 +260,7 @@ mod tests {
             p256::P256KeyPair,
             x25519::X25519KeyPair,
         },
+        buffer::SecretBytes,
         encrypt::{KeyAeadInPlace, KeyAeadMeta},
         jwk::FromJwk,
         kdf::{ecdh_1pu::Ecdh1PU, ecdh_es::EcdhEs, FromKeyDerivation, KeyExchange},
@@ -164,6 +278,21 @@ mod tests {
         utils::crypto::{JoseKDF, KeyWrap},
     };
 
+    use zeroize::Zeroize;
+
+    #[test]
+    fn encrypt_works_zeroized_keys() {
+        // Compile-time check that content encryption keys, derived key wrapping keys
+        // and intermediate buffers are zeroized
+        fn _assert_zeroize<T: Zeroize>() {}
+
+        _assert_zeroize::<AesKey<A256CbcHs512>>();
+        _assert_zeroize::<AesKey<A256Gcm>>();
+        _assert_zeroize::<Chacha20Key<XC20P>>();
+        _assert_zeroize::<AesKey<A256Kw>>();
+        _assert_zeroize::<SecretBytes>();
+    }
+
     #[test]
     fn encrypt_works() {
         _encrypt_works::<
@@ -392,6 +521,9 @@ mod tests {
                 enc_alg.clone(),
                 alice_priv,
                 &bob_pub,
+                None,
+                None,
+                None,
             )
             .expect("Unable encrypt");
 
@@ -436,6 +568,9 @@ mod tests {
             EncAlgorithm::A256cbcHs512,
             None,
             &[(bob_kid, &bob_pkey)],
+            None,
+            None,
+            None,
         );
 
         let err = res.expect_err("res is ok");
@@ -21,6 +21,85 @@ pub(crate) struct JWE<'a> {
 
     /// BASE64URL(JWE Authentication Tag)
     pub tag: &'a str,
+
+    /// BASE64URL(JWE AAD) binding external context to the message.
+    /// Note: if present, this field value is appended to `protected` field value
+    /// (separated by '.') to form AAD for JWE Ciphertext
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aad: Option<&'a str>,
+}
+
+impl<'a> JWE<'a> {
+    /// AAD for JWE Ciphertext: ASCII(protected) or ASCII(protected || '.' || aad) if aad is present.
+    pub(crate) fn content_aad(protected: &str, aad: Option<&str>) -> Vec<u8> {
+        match aad {
+            Some(aad) => format!("{}.{}", protected, aad).into_bytes(),
+            None => protected.as_bytes().to_vec(),
+        }
+    }
+}
+
+/// JWE in flattened json serialization form
+/// (https://datatracker.ietf.org/doc/html/rfc7516#section-7.2.2) for a single recipient:
+/// recipient header and encrypted key are merged at top level instead of `recipients` array.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FlattenedJWE<'a> {
+    /// BASE64URL(UTF8(JWE Protected Header))
+    pub protected: &'a str,
+
+    /// Per-recipient header of the only recipient
+    pub header: PerRecipientHeader<'a>,
+
+    /// BASE64URL(JWE Encrypted Key) of the only recipient
+    pub encrypted_key: &'a str,
+
+    /// BASE64URL(JWE Initialization Vector)
+    pub iv: &'a str,
+
+    /// BASE64URL(JWE Ciphertext)
+    pub ciphertext: &'a str,
+
+    /// BASE64URL(JWE Authentication Tag)
+    pub tag: &'a str,
+
+    /// BASE64URL(JWE AAD)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aad: Option<&'a str>,
+}
+
+impl<'a> JWE<'a> {
+    /// Returns the JWE in flattened json serialization form or None
+    /// if it hasn't exactly one recipient.
+    pub(crate) fn to_flattened(&self) -> Option<FlattenedJWE<'a>> {
+        match self.recipients.as_slice() {
+            [recipient] => Some(FlattenedJWE {
+                protected: self.protected,
+                header: recipient.header.clone(),
+                encrypted_key: recipient.encrypted_key,
+                iv: self.iv,
+                ciphertext: self.ciphertext,
+                tag: self.tag,
+                aad: self.aad,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> From<FlattenedJWE<'a>> for JWE<'a> {
+    fn from(jwe: FlattenedJWE<'a>) -> Self {
+        JWE {
+            protected: jwe.protected,
+            recipients: vec![Recipient {
+                header: jwe.header,
+                encrypted_key: jwe.encrypted_key,
+            }],
+            iv: jwe.iv,
+            ciphertext: jwe.ciphertext,
+            tag: jwe.tag,
+            aad: jwe.aad,
+        }
+    }
 }
 
 /// Protected header for authcrypt/anoncrypt-specific JWE.
@@ -53,6 +132,7 @@ pub(crate) struct ProtectedHeader<'a> {
     /// EPK generated once for all recipients.
     /// It MUST be of the same type and curve as all recipient keys since kdf
     /// with the sender key must be on the same curve.
+    #[serde(default)]
     pub epk: Value,
 }
 /// Recipient part of authcrypt/anoncrypt-specific JWE
@@ -111,6 +191,12 @@ pub(crate) enum EncAlgorithm {
     #[serde(rename = "A256GCM")]
     A256Gcm,
 
+    #[serde(rename = "A128CBC-HS256")]
+    A128cbcHs256,
+
+    #[serde(rename = "A128GCM")]
+    A128Gcm,
+
     #[serde(other)]
     Other(String),
 }
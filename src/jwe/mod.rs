@@ -14,13 +14,22 @@ mod parse;
 #[allow(dead_code)]
 pub(crate) mod envelope;
 
+mod nonce;
+mod session_key;
+
+pub use nonce::NonceSource;
+pub use session_key::SessionKeyProvider;
+
 // TODO: remove allow
 #[allow(unused_imports)]
 pub(crate) use encrypt::encrypt;
 
+#[cfg(test)]
+pub(crate) use encrypt::set_deterministic_rng;
+
 // TODO: remove allow
 #[allow(unused_imports)]
-pub(crate) use parse::{parse, ParsedJWE};
+pub(crate) use parse::{decode_base64url, parse, ParsedJWE};
 
 // TODO: remove allow
 #[allow(unused_imports)]
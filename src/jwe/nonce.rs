@@ -0,0 +1,35 @@
+use std::fmt;
+
+use crate::{error::Result, utils::identity::ByIdentity};
+
+/// Source of IVs (nonces) for content encryption of packed messages.
+/// By default nonces are generated by OS CSPRNG, custom source can be set
+/// with `PackEncryptedOptions::nonce_source` (for ex. counter-based nonces
+/// within a session required by FIPS-style deployments).
+///
+/// Security: nonce misuse breaks security of the encrypted messages.
+/// Nonces must never repeat for the same content encryption key (AES-GCM and XC20P
+/// lose both confidentiality and integrity on reuse) and AES-CBC IVs must be unpredictable.
+/// Implementations must be safe to call concurrently for messages packed in parallel.
+pub trait NonceSource: Send + Sync {
+    /// Fills `nonce` with the next nonce. Length of `nonce` is defined by the content encryption
+    /// algorithm: 16 bytes for AES-CBC, 12 bytes for AES-GCM and 24 bytes for XC20P.
+    ///
+    /// # Errors
+    /// Any error fails packing of the message (for ex. `InvalidState` if nonces are exhausted).
+    fn fill_nonce(&self, nonce: &mut [u8]) -> Result<()>;
+}
+
+impl fmt::Debug for dyn NonceSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("NonceSource")
+    }
+}
+
+impl PartialEq for dyn NonceSource {
+    fn eq(&self, other: &Self) -> bool {
+        ByIdentity(self) == ByIdentity(other)
+    }
+}
+
+impl Eq for dyn NonceSource {}
@@ -3,7 +3,8 @@ use sha2::{Digest, Sha256};
 use crate::error::ToResult;
 use crate::{
     error::{err_msg, ErrorKind, Result, ResultExt},
-    jwe::envelope::{ProtectedHeader, JWE},
+    jwe::envelope::{Algorithm, EncAlgorithm, FlattenedJWE, ProtectedHeader, JWE},
+    utils::jose::check_processing_headers,
 };
 
 #[derive(Debug, PartialEq, Eq)]
@@ -12,6 +13,8 @@ pub(crate) struct ParsedJWE<'a, 'b> {
     pub(crate) protected: ProtectedHeader<'b>,
     pub(crate) apu: Option<Vec<u8>>,
     pub(crate) apv: Vec<u8>,
+    pub(crate) aad: Option<Vec<u8>>,
+    pub(crate) lenient_base64: bool,
 }
 
 pub(crate) fn parse<'a, 'b>(jwe: &'a str, buf: &'b mut Vec<u8>) -> Result<ParsedJWE<'a, 'b>> {
@@ -19,38 +22,233 @@ pub(crate) fn parse<'a, 'b>(jwe: &'a str, buf: &'b mut Vec<u8>) -> Result<Parsed
 }
 
 impl<'a> JWE<'a> {
+    /// Parses JWE in general json serialization form or flattened one for a single recipient.
+    /// Errors of general form parsing are reported if both fail.
     pub(crate) fn from_str(s: &str) -> Result<JWE> {
-        serde_json::from_str(s).to_didcomm("Unable parse jwe")
+        match serde_json::from_str(s) {
+            Ok(jwe) => Ok(jwe),
+            Err(err) => match serde_json::from_str::<FlattenedJWE>(s) {
+                Ok(jwe) => Ok(jwe.into()),
+                Err(_) => Err(err).to_didcomm("Unable parse jwe"),
+            },
+        }
     }
 
     pub(crate) fn parse<'b>(self, buf: &'b mut Vec<u8>) -> Result<ParsedJWE<'a, 'b>> {
-        base64::decode_config_buf(self.protected, base64::URL_SAFE_NO_PAD, buf)
+        self.parse_with(buf, false)
+    }
+
+    /// Same as `parse`, but if `lenient_base64` is true, padded and standard alphabet
+    /// base64 values are accepted in addition to unpadded base64url ones.
+    pub(crate) fn parse_with<'b>(
+        self,
+        buf: &'b mut Vec<u8>,
+        lenient_base64: bool,
+    ) -> Result<ParsedJWE<'a, 'b>> {
+        let protected = decode_base64url(self.protected, lenient_base64)
             .kind(ErrorKind::Malformed, "Unable decode protected header")?;
 
+        buf.extend_from_slice(&protected);
+
         let protected: ProtectedHeader =
             serde_json::from_slice(buf).to_didcomm("Unable parse protected header")?;
 
-        let apv = base64::decode_config(protected.apv, base64::URL_SAFE_NO_PAD)
+        // No JWE extension parameters are understood
+        check_processing_headers(buf, &[])?;
+
+        let apv = decode_base64url(protected.apv, lenient_base64)
             .kind(ErrorKind::Malformed, "Unable decode apv")?;
 
         let apu = protected
             .apu
-            .map(|apu| base64::decode_config(apu, base64::URL_SAFE_NO_PAD))
+            .map(|apu| decode_base64url(apu, lenient_base64))
             .transpose()
             .kind(ErrorKind::Malformed, "Unable decode apu")?;
 
+        let aad = self
+            .aad
+            .map(|aad| decode_base64url(aad, lenient_base64))
+            .transpose()
+            .kind(ErrorKind::Malformed, "Unable decode aad")?;
+
         let jwe = ParsedJWE {
             jwe: self,
             protected,
             apu,
             apv,
+            aad,
+            lenient_base64,
         };
 
+        jwe.verify_consistency()?;
         Ok(jwe)
     }
 }
 
+/// Decodes unpadded base64url value. If `lenient` is true, padding and
+/// characters of standard base64 alphabet are accepted too.
+pub(crate) fn decode_base64url(
+    value: &str,
+    lenient: bool,
+) -> std::result::Result<Vec<u8>, base64::DecodeError> {
+    if !lenient {
+        return base64::decode_config(value, base64::URL_SAFE_NO_PAD);
+    }
+
+    let value: String = value
+        .trim_end_matches('=')
+        .chars()
+        .map(|c| match c {
+            '+' => '-',
+            '/' => '_',
+            c => c,
+        })
+        .collect();
+
+    base64::decode_config(value, base64::URL_SAFE_NO_PAD)
+}
+
 impl<'a, 'b> ParsedJWE<'a, 'b> {
+    /// Verifies that `alg`, `enc` and headers required by `alg` are consistent.
+    fn verify_consistency(&self) -> Result<()> {
+        match self.protected.alg {
+            Algorithm::EcdhEsA256kw if !self.protected.epk.is_object() => Err(err_msg(
+                ErrorKind::Malformed,
+                "No epk presented for ECDH-ES+A256KW alg",
+            ))?,
+            Algorithm::EcdhEsA256kw if self.protected.skid.is_some() => Err(err_msg(
+                ErrorKind::Malformed,
+                "skid can't be presented for ECDH-ES+A256KW alg",
+            ))?,
+            Algorithm::Ecdh1puA256kw if !self.protected.epk.is_object() => Err(err_msg(
+                ErrorKind::Malformed,
+                "No epk presented for ECDH-1PU+A256KW alg",
+            ))?,
+            Algorithm::Ecdh1puA256kw
+                if !matches!(
+                    self.protected.enc,
+                    EncAlgorithm::A256cbcHs512 | EncAlgorithm::A128cbcHs256
+                ) =>
+            {
+                Err(err_msg(
+                    ErrorKind::Malformed,
+                    "ECDH-1PU+A256KW alg can be used only with A256CBC-HS512 or A128CBC-HS256 enc",
+                ))?
+            }
+            Algorithm::Ecdh1puA256kw
+                if self.protected.apu.is_none() && self.protected.skid.is_none() =>
+            {
+                Err(err_msg(
+                    ErrorKind::Malformed,
+                    "No apu or skid presented for ECDH-1PU+A256KW alg",
+                ))?
+            }
+            Algorithm::Other(ref alg) if alg == "dir" && self.jwe.recipients.len() > 1 => {
+                Err(err_msg(
+                    ErrorKind::Malformed,
+                    "dir alg can't be used with multiple recipients",
+                ))?
+            }
+            Algorithm::Other(ref alg) => Err(err_msg(
+                ErrorKind::Unsupported,
+                format!("Unsupported alg `{}`", alg),
+            ))?,
+            _ => (),
+        }
+
+        // Same recipient may be listed multiple times only with the same encrypted key
+        for (i, recipient) in self.jwe.recipients.iter().enumerate() {
+            let conflicting = self.jwe.recipients[..i].iter().any(|r| {
+                r.header.kid == recipient.header.kid && r.encrypted_key != recipient.encrypted_key
+            });
+
+            if conflicting {
+                Err(err_msg(
+                    ErrorKind::Malformed,
+                    format!(
+                        "Recipient `{}` presented multiple times with different encrypted keys",
+                        recipient.header.kid
+                    ),
+                ))?
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Verifies that `recipients` isn't empty and each recipient has `kid` and encrypted key.
+    pub(crate) fn verify_recipients(&self) -> Result<()> {
+        if self.jwe.recipients.is_empty() {
+            Err(err_msg(ErrorKind::Malformed, "JWE `recipients` is empty"))?
+        }
+
+        for (i, recipient) in self.jwe.recipients.iter().enumerate() {
+            if recipient.header.kid.is_empty() {
+                Err(err_msg(
+                    ErrorKind::Malformed,
+                    format!("JWE recipient {} has empty `kid`", i),
+                ))?
+            }
+
+            if recipient.encrypted_key.is_empty() {
+                Err(err_msg(
+                    ErrorKind::Malformed,
+                    format!(
+                        "JWE recipient `{}` has empty `encrypted_key`",
+                        recipient.header.kid
+                    ),
+                ))?
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Verifies that `iv` length matches the one required by `enc`, so malformed
+    /// messages are rejected before unwrapping of the key and decryption.
+    pub(crate) fn verify_iv(&self) -> Result<()> {
+        let (exp_len, enc) = match self.protected.enc {
+            EncAlgorithm::A256cbcHs512 => (16, "A256CBC-HS512"),
+            EncAlgorithm::A128cbcHs256 => (16, "A128CBC-HS256"),
+            EncAlgorithm::A256Gcm => (12, "A256GCM"),
+            EncAlgorithm::A128Gcm => (12, "A128GCM"),
+            EncAlgorithm::Xc20P => (24, "XC20P"),
+            // Unsupported enc is rejected on decryption
+            EncAlgorithm::Other(_) => return Ok(()),
+        };
+
+        let iv = decode_base64url(self.jwe.iv, self.lenient_base64)
+            .kind(ErrorKind::Malformed, "Unable decode iv")?;
+
+        if iv.len() != exp_len {
+            Err(err_msg(
+                ErrorKind::Malformed,
+                format!(
+                    "Invalid iv length {} bytes for {} enc, expected {} bytes",
+                    iv.len(),
+                    enc,
+                    exp_len
+                ),
+            ))?
+        }
+
+        Ok(())
+    }
+
+    /// Returns kids of recipients in order of presence. Duplicated recipients
+    /// are returned once (see `verify_consistency`).
+    pub(crate) fn to_kids(&self) -> Vec<&'a str> {
+        let mut to_kids: Vec<&'a str> = Vec::with_capacity(self.jwe.recipients.len());
+
+        for recipient in &self.jwe.recipients {
+            if !to_kids.contains(&recipient.header.kid) {
+                to_kids.push(recipient.header.kid);
+            }
+        }
+
+        to_kids
+    }
+
     /// Verifies that apv and apu filled according DID Comm specification.
     pub(crate) fn verify_didcomm(self) -> Result<Self> {
         let did_comm_apv = {
@@ -77,9 +275,11 @@ impl<'a, 'b> ParsedJWE<'a, 'b> {
             .kind(ErrorKind::Malformed, "Invalid utf8 for apu")?;
 
         match (did_comm_apu, self.protected.skid) {
-            (Some(apu), Some(skid)) if apu != skid => {
-                Err(err_msg(ErrorKind::Malformed, "APU mismatch"))?
-            }
+            // Sender key is taken from apu, so skid disagreeing with it is a tampering signal
+            (Some(apu), Some(skid)) if apu != skid => Err(err_msg(
+                ErrorKind::Malformed,
+                format!("APU mismatch: apu `{}` doesn't match skid `{}`", apu, skid),
+            ))?,
             (None, Some(_)) => Err(err_msg(ErrorKind::Malformed, "SKID present, but no apu"))?,
             _ => (),
         };
@@ -97,8 +297,14 @@ mod tests {
         jwe::{
             self,
             envelope::{EncAlgorithm, PerRecipientHeader, ProtectedHeader, Recipient, JWE},
+            parse::decode_base64url,
             ParsedJWE,
         },
+        test_vectors::{
+            remove_protected_field, update_protected_field, update_protected_field_value,
+            EnvelopeBuilder, ENCRYPTED_MSG_ANON_XC20P_1, ENCRYPTED_MSG_AUTH_X25519,
+            INVALID_ENCRYPTED_MSG_ANON_XC20P_SKID,
+        },
     };
 
     #[test]
@@ -156,6 +362,7 @@ mod tests {
                 iv: "ESpmcyGiZpRjc5urDela21TOOTW8Wqd1",
                 ciphertext: "KWS7gJU7TbyJlcT9dPkCw-ohNigGaHSukR9MUqFM0THbCTCNkY-g5tahBFyszlKIKXs7qOtqzYyWbPou2q77XlAeYs93IhF6NvaIjyNqYklvj-OtJt9W2Pj5CLOMdsR0C30wchGoXd6wEQZY4ttbzpxYznqPmJ0b9KW6ZP-l4_DSRYe9B-1oSWMNmqMPwluKbtguC-riy356Xbu2C9ShfWmpmjz1HyJWQhZfczuwkWWlE63g26FMskIZZd_jGpEhPFHKUXCFwbuiw_Iy3R0BIzmXXdK_w7PZMMPbaxssl2UeJmLQgCAP8j8TukxV96EKa6rGgULvlo7qibjJqsS5j03bnbxkuxwbfyu3OxwgVzFWlyHbUH6p",
                 tag: "6ylC_iAs4JvDQzXeY6MuYQ",
+                aad: None,
             },
             protected: ProtectedHeader {
                 typ: Some("application/didcomm-encrypted+json"),
@@ -172,11 +379,29 @@ mod tests {
             },
             apu: None,
             apv: vec![53, 203, 46, 2, 122, 209, 124, 242, 186, 244, 15, 171, 145, 157, 11, 245, 117, 148, 27, 136, 204, 188, 208, 183, 102, 14, 248, 4, 252, 249, 220, 240],
+            aad: None,
+            lenient_base64: false,
         };
 
         assert_eq!(res, exp);
     }
 
+    #[test]
+    fn decode_base64url_works() {
+        for (value, lenient, exp) in [
+            ("-_8", false, Some(vec![251, 255])),
+            ("-_8", true, Some(vec![251, 255])),
+            ("-_8=", false, None),
+            ("-_8=", true, Some(vec![251, 255])),
+            ("+/8=", false, None),
+            ("+/8=", true, Some(vec![251, 255])),
+            ("+/8", true, Some(vec![251, 255])),
+            ("-_8*", true, None),
+        ] {
+            assert_eq!(decode_base64url(value, lenient).ok(), exp);
+        }
+    }
+
     #[test]
     fn parse_works_anoncrypt_unknown_fields() {
         let msg = r#"
@@ -233,6 +458,7 @@ mod tests {
                 iv: "ESpmcyGiZpRjc5urDela21TOOTW8Wqd1",
                 ciphertext: "KWS7gJU7TbyJlcT9dPkCw-ohNigGaHSukR9MUqFM0THbCTCNkY-g5tahBFyszlKIKXs7qOtqzYyWbPou2q77XlAeYs93IhF6NvaIjyNqYklvj-OtJt9W2Pj5CLOMdsR0C30wchGoXd6wEQZY4ttbzpxYznqPmJ0b9KW6ZP-l4_DSRYe9B-1oSWMNmqMPwluKbtguC-riy356Xbu2C9ShfWmpmjz1HyJWQhZfczuwkWWlE63g26FMskIZZd_jGpEhPFHKUXCFwbuiw_Iy3R0BIzmXXdK_w7PZMMPbaxssl2UeJmLQgCAP8j8TukxV96EKa6rGgULvlo7qibjJqsS5j03bnbxkuxwbfyu3OxwgVzFWlyHbUH6p",
                 tag: "6ylC_iAs4JvDQzXeY6MuYQ",
+                aad: None,
             },
             protected: ProtectedHeader {
                 typ: Some("application/didcomm-encrypted+json"),
@@ -249,6 +475,8 @@ mod tests {
             },
             apu: None,
             apv: vec![53, 203, 46, 2, 122, 209, 124, 242, 186, 244, 15, 171, 145, 157, 11, 245, 117, 148, 27, 136, 204, 188, 208, 183, 102, 14, 248, 4, 252, 249, 220, 240],
+            aad: None,
+            lenient_base64: false,
         };
 
         assert_eq!(res, exp);
@@ -309,6 +537,7 @@ mod tests {
                 iv: "ESpmcyGiZpRjc5urDela21TOOTW8Wqd1",
                 ciphertext: "KWS7gJU7TbyJlcT9dPkCw-ohNigGaHSukR9MUqFM0THbCTCNkY-g5tahBFyszlKIKXs7qOtqzYyWbPou2q77XlAeYs93IhF6NvaIjyNqYklvj-OtJt9W2Pj5CLOMdsR0C30wchGoXd6wEQZY4ttbzpxYznqPmJ0b9KW6ZP-l4_DSRYe9B-1oSWMNmqMPwluKbtguC-riy356Xbu2C9ShfWmpmjz1HyJWQhZfczuwkWWlE63g26FMskIZZd_jGpEhPFHKUXCFwbuiw_Iy3R0BIzmXXdK_w7PZMMPbaxssl2UeJmLQgCAP8j8TukxV96EKa6rGgULvlo7qibjJqsS5j03bnbxkuxwbfyu3OxwgVzFWlyHbUH6p",
                 tag: "6ylC_iAs4JvDQzXeY6MuYQ",
+                aad: None,
             },
             protected: ProtectedHeader {
                 typ: Some("application/didcomm-encrypted+json"),
@@ -325,6 +554,8 @@ mod tests {
             },
             apu: None,
             apv: vec![53, 203, 46, 2, 122, 209, 124, 242, 186, 244, 15, 171, 145, 157, 11, 245, 117, 148, 27, 136, 204, 188, 208, 183, 102, 14, 248, 4, 252, 249, 220, 240],
+            aad: None,
+            lenient_base64: false,
         };
 
         assert_eq!(res, exp);
@@ -385,6 +616,7 @@ mod tests {
                 iv: "o02OXDQ6_-sKz2PX_6oyJg",
                 ciphertext: "MJezmxJ8DzUB01rMjiW6JViSaUhsZBhMvYtezkhmwts1qXWtDB63i4-FHZP6cJSyCI7eU-gqH8lBXO_UVuviWIqnIUrTRLaumanZ4q1dNKAnxNL-dHmb3coOqSvy3ZZn6W17lsVudjw7hUUpMbeMbQ5W8GokK9ZCGaaWnqAzd1ZcuGXDuemWeA8BerQsfQw_IQm-aUKancldedHSGrOjVWgozVL97MH966j3i9CJc3k9jS9xDuE0owoWVZa7SxTmhl1PDetmzLnYIIIt-peJtNYGdpd-FcYxIFycQNRUoFEr77h4GBTLbC-vqbQHJC1vW4O2LEKhnhOAVlGyDYkNbA4DSL-LMwKxenQXRARsKSIMn7z-ZIqTE-VCNj9vbtgR",
                 tag: "uYeo7IsZjN7AnvBjUZE5lNryNENbf6_zew_VC-d4b3U",
+                aad: None,
             },
             protected: ProtectedHeader {
                 typ: Some("application/didcomm-encrypted+json"),
@@ -401,6 +633,8 @@ mod tests {
             },
             apu: Some(b"did:example:alice#key-x25519-1".to_vec()),
             apv: vec![53, 203, 46, 2, 122, 209, 124, 242, 186, 244, 15, 171, 145, 157, 11, 245, 117, 148, 27, 136, 204, 188, 208, 183, 102, 14, 248, 4, 252, 249, 220, 240],
+            aad: None,
+            lenient_base64: false,
         };
 
         assert_eq!(res, exp);
@@ -462,6 +696,7 @@ mod tests {
                 iv: "o02OXDQ6_-sKz2PX_6oyJg",
                 ciphertext: "MJezmxJ8DzUB01rMjiW6JViSaUhsZBhMvYtezkhmwts1qXWtDB63i4-FHZP6cJSyCI7eU-gqH8lBXO_UVuviWIqnIUrTRLaumanZ4q1dNKAnxNL-dHmb3coOqSvy3ZZn6W17lsVudjw7hUUpMbeMbQ5W8GokK9ZCGaaWnqAzd1ZcuGXDuemWeA8BerQsfQw_IQm-aUKancldedHSGrOjVWgozVL97MH966j3i9CJc3k9jS9xDuE0owoWVZa7SxTmhl1PDetmzLnYIIIt-peJtNYGdpd-FcYxIFycQNRUoFEr77h4GBTLbC-vqbQHJC1vW4O2LEKhnhOAVlGyDYkNbA4DSL-LMwKxenQXRARsKSIMn7z-ZIqTE-VCNj9vbtgR",
                 tag: "uYeo7IsZjN7AnvBjUZE5lNryNENbf6_zew_VC-d4b3U",
+                aad: None,
             },
             protected: ProtectedHeader {
                 typ: Some("application/didcomm-encrypted+json"),
@@ -478,6 +713,8 @@ mod tests {
             },
             apu: Some(b"did:example:alice#key-x25519-1".to_vec()),
             apv: vec![53, 203, 46, 2, 122, 209, 124, 242, 186, 244, 15, 171, 145, 157, 11, 245, 117, 148, 27, 136, 204, 188, 208, 183, 102, 14, 248, 4, 252, 249, 220, 240],
+            aad: None,
+            lenient_base64: false,
         };
 
         assert_eq!(res, exp);
@@ -538,6 +775,7 @@ mod tests {
                 iv: "o02OXDQ6_-sKz2PX_6oyJg",
                 ciphertext: "MJezmxJ8DzUB01rMjiW6JViSaUhsZBhMvYtezkhmwts1qXWtDB63i4-FHZP6cJSyCI7eU-gqH8lBXO_UVuviWIqnIUrTRLaumanZ4q1dNKAnxNL-dHmb3coOqSvy3ZZn6W17lsVudjw7hUUpMbeMbQ5W8GokK9ZCGaaWnqAzd1ZcuGXDuemWeA8BerQsfQw_IQm-aUKancldedHSGrOjVWgozVL97MH966j3i9CJc3k9jS9xDuE0owoWVZa7SxTmhl1PDetmzLnYIIIt-peJtNYGdpd-FcYxIFycQNRUoFEr77h4GBTLbC-vqbQHJC1vW4O2LEKhnhOAVlGyDYkNbA4DSL-LMwKxenQXRARsKSIMn7z-ZIqTE-VCNj9vbtgR",
                 tag: "uYeo7IsZjN7AnvBjUZE5lNryNENbf6_zew_VC-d4b3U",
+                aad: None,
             },
             protected: ProtectedHeader {
                 typ: Some("application/didcomm-encrypted+json"),
@@ -554,6 +792,8 @@ mod tests {
             },
             apu: Some(b"did:example:alice#key-x25519-1".to_vec()),
             apv: vec![53, 203, 46, 2, 122, 209, 124, 242, 186, 244, 15, 171, 145, 157, 11, 245, 117, 148, 27, 136, 204, 188, 208, 183, 102, 14, 248, 4, 252, 249, 220, 240],
+            aad: None,
+            lenient_base64: false,
         };
 
         assert_eq!(res, exp);
@@ -963,6 +1203,7 @@ mod tests {
                 iv: "ESpmcyGiZpRjc5urDela21TOOTW8Wqd1",
                 ciphertext: "KWS7gJU7TbyJlcT9dPkCw-ohNigGaHSukR9MUqFM0THbCTCNkY-g5tahBFyszlKIKXs7qOtqzYyWbPou2q77XlAeYs93IhF6NvaIjyNqYklvj-OtJt9W2Pj5CLOMdsR0C30wchGoXd6wEQZY4ttbzpxYznqPmJ0b9KW6ZP-l4_DSRYe9B-1oSWMNmqMPwluKbtguC-riy356Xbu2C9ShfWmpmjz1HyJWQhZfczuwkWWlE63g26FMskIZZd_jGpEhPFHKUXCFwbuiw_Iy3R0BIzmXXdK_w7PZMMPbaxssl2UeJmLQgCAP8j8TukxV96EKa6rGgULvlo7qibjJqsS5j03bnbxkuxwbfyu3OxwgVzFWlyHbUH6p",
                 tag: "6ylC_iAs4JvDQzXeY6MuYQ",
+                aad: None,
             },
             protected: ProtectedHeader {
                 typ: Some("application/didcomm-encrypted+json"),
@@ -979,6 +1220,8 @@ mod tests {
             },
             apu: None,
             apv: vec![53, 203, 46, 2, 122, 209, 124, 242, 186, 244, 15, 171, 145, 157, 11, 245, 117, 148, 27, 136, 204, 188, 208, 183, 102, 14, 248, 4, 252, 249, 220, 240],
+            aad: None,
+            lenient_base64: false,
         };
 
         assert_eq!(res, exp);
@@ -1042,6 +1285,7 @@ mod tests {
                 iv: "o02OXDQ6_-sKz2PX_6oyJg",
                 ciphertext: "MJezmxJ8DzUB01rMjiW6JViSaUhsZBhMvYtezkhmwts1qXWtDB63i4-FHZP6cJSyCI7eU-gqH8lBXO_UVuviWIqnIUrTRLaumanZ4q1dNKAnxNL-dHmb3coOqSvy3ZZn6W17lsVudjw7hUUpMbeMbQ5W8GokK9ZCGaaWnqAzd1ZcuGXDuemWeA8BerQsfQw_IQm-aUKancldedHSGrOjVWgozVL97MH966j3i9CJc3k9jS9xDuE0owoWVZa7SxTmhl1PDetmzLnYIIIt-peJtNYGdpd-FcYxIFycQNRUoFEr77h4GBTLbC-vqbQHJC1vW4O2LEKhnhOAVlGyDYkNbA4DSL-LMwKxenQXRARsKSIMn7z-ZIqTE-VCNj9vbtgR",
                 tag: "uYeo7IsZjN7AnvBjUZE5lNryNENbf6_zew_VC-d4b3U",
+                aad: None,
             },
             protected: ProtectedHeader {
                 typ: Some("application/didcomm-encrypted+json"),
@@ -1058,6 +1302,8 @@ mod tests {
             },
             apu: Some(b"did:example:alice#key-x25519-1".to_vec()),
             apv: vec![53, 203, 46, 2, 122, 209, 124, 242, 186, 244, 15, 171, 145, 157, 11, 245, 117, 148, 27, 136, 204, 188, 208, 183, 102, 14, 248, 4, 252, 249, 220, 240],
+            aad: None,
+            lenient_base64: false,
         };
 
         assert_eq!(res, exp);
@@ -1103,7 +1349,11 @@ mod tests {
         let err = res.expect_err("res is ok");
         assert_eq!(err.kind(), ErrorKind::Malformed);
 
-        assert_eq!(format!("{}", err), "Malformed: APU mismatch");
+        assert_eq!(
+            format!("{}", err),
+            "Malformed: APU mismatch: apu `did:example:alice#key-x25519-1` \
+            doesn't match skid `did:example:alice#key-x25519-2`"
+        );
     }
 
     #[test]
@@ -1148,4 +1398,261 @@ mod tests {
 
         assert_eq!(format!("{}", err), "Malformed: SKID present, but no apu");
     }
+
+    #[test]
+    fn parse_works_inconsistent_headers() {
+        _parse_returns_malformed(
+            &remove_protected_field(ENCRYPTED_MSG_ANON_XC20P_1, "epk"),
+            "Malformed: No epk presented for ECDH-ES+A256KW alg",
+        );
+
+        _parse_returns_malformed(
+            &update_protected_field(ENCRYPTED_MSG_ANON_XC20P_1, "epk", "invalid"),
+            "Malformed: No epk presented for ECDH-ES+A256KW alg",
+        );
+
+        _parse_returns_malformed(
+            INVALID_ENCRYPTED_MSG_ANON_XC20P_SKID,
+            "Malformed: skid can't be presented for ECDH-ES+A256KW alg",
+        );
+
+        _parse_returns_malformed(
+            &remove_protected_field(ENCRYPTED_MSG_AUTH_X25519, "epk"),
+            "Malformed: No epk presented for ECDH-1PU+A256KW alg",
+        );
+
+        _parse_returns_malformed(
+            &update_protected_field(ENCRYPTED_MSG_AUTH_X25519, "enc", "XC20P"),
+            "Malformed: ECDH-1PU+A256KW alg can be used only with A256CBC-HS512 or A128CBC-HS256 enc",
+        );
+
+        _parse_returns_malformed(
+            &update_protected_field(ENCRYPTED_MSG_AUTH_X25519, "enc", "A256GCM"),
+            "Malformed: ECDH-1PU+A256KW alg can be used only with A256CBC-HS512 or A128CBC-HS256 enc",
+        );
+
+        _parse_returns_malformed(
+            &update_protected_field(ENCRYPTED_MSG_AUTH_X25519, "enc", "A128GCM"),
+            "Malformed: ECDH-1PU+A256KW alg can be used only with A256CBC-HS512 or A128CBC-HS256 enc",
+        );
+
+        _parse_returns_malformed(
+            &remove_protected_field(
+                &remove_protected_field(ENCRYPTED_MSG_AUTH_X25519, "apu"),
+                "skid",
+            ),
+            "Malformed: No apu or skid presented for ECDH-1PU+A256KW alg",
+        );
+
+        _parse_returns_malformed(
+            &update_protected_field(ENCRYPTED_MSG_ANON_XC20P_1, "alg", "dir"),
+            "Malformed: dir alg can't be used with multiple recipients",
+        );
+    }
+
+    #[test]
+    fn parse_works_duplicated_recipients() {
+        let mut msg: serde_json::Value =
+            serde_json::from_str(ENCRYPTED_MSG_ANON_XC20P_1).expect("Unable from_str");
+
+        msg["recipients"][1] = msg["recipients"][0].clone();
+        let msg = msg.to_string();
+
+        let mut buf = vec![];
+        let res = jwe::parse(&msg, &mut buf).expect("Unable parse");
+
+        assert_eq!(res.jwe.recipients.len(), 3);
+
+        assert_eq!(
+            res.to_kids(),
+            vec![
+                "did:example:bob#key-x25519-1",
+                "did:example:bob#key-x25519-3"
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_works_duplicated_recipients_conflicting_keys() {
+        let mut msg: serde_json::Value =
+            serde_json::from_str(ENCRYPTED_MSG_ANON_XC20P_1).expect("Unable from_str");
+
+        msg["recipients"][1]["header"]["kid"] = msg["recipients"][0]["header"]["kid"].clone();
+        let msg = msg.to_string();
+
+        _parse_returns_malformed(
+            &msg,
+            "Malformed: Recipient `did:example:bob#key-x25519-1` presented multiple times with different encrypted keys",
+        );
+    }
+
+    #[test]
+    fn parse_works_unsupported_alg() {
+        for (msg, alg) in [
+            (ENCRYPTED_MSG_ANON_XC20P_1, "ECDH-ES+A128KW"),
+            (ENCRYPTED_MSG_ANON_XC20P_1, "ECDH-ES"),
+            (ENCRYPTED_MSG_AUTH_X25519, "ECDH-1PU+A128KW"),
+        ] {
+            let msg = update_protected_field(msg, "alg", alg);
+
+            let mut buf = vec![];
+            let err = jwe::parse(&msg, &mut buf).expect_err("res is ok");
+            assert_eq!(err.kind(), ErrorKind::Unsupported);
+            assert_eq!(
+                format!("{}", err),
+                format!("Unsupported crypto or method: Unsupported alg `{}`", alg)
+            );
+        }
+    }
+
+    #[test]
+    fn parse_works_processing_headers() {
+        for (field, value, exp_err_kind, exp_err_msg) in [
+            (
+                "crit",
+                json!(["exp"]),
+                ErrorKind::Unsupported,
+                "Unsupported crypto or method: Unsupported critical header parameter `exp`",
+            ),
+            (
+                "crit",
+                json!(["b64"]),
+                ErrorKind::Unsupported,
+                "Unsupported crypto or method: Unsupported critical header parameter `b64`",
+            ),
+            (
+                "crit",
+                json!([]),
+                ErrorKind::Malformed,
+                "Malformed: Critical header parameters list is empty",
+            ),
+            (
+                "b64",
+                json!(false),
+                ErrorKind::Unsupported,
+                "Unsupported crypto or method: Unencoded payload (`b64` is false) is unsupported",
+            ),
+        ] {
+            let msg = update_protected_field_value(ENCRYPTED_MSG_ANON_XC20P_1, field, value);
+
+            let mut buf = vec![];
+            let err = jwe::parse(&msg, &mut buf).expect_err("res is ok");
+            assert_eq!(err.kind(), exp_err_kind);
+            assert_eq!(format!("{}", err), exp_err_msg);
+        }
+    }
+
+    #[test]
+    fn verify_recipients_works() {
+        let mut buf = vec![];
+        let res = jwe::parse(ENCRYPTED_MSG_ANON_XC20P_1, &mut buf).expect("Unable parse");
+        res.verify_recipients().expect("verify_recipients is ok.");
+    }
+
+    #[test]
+    fn verify_recipients_works_malformed() {
+        let msg: serde_json::Value =
+            serde_json::from_str(ENCRYPTED_MSG_ANON_XC20P_1).expect("Unable from_str");
+
+        let mut empty_recipients = msg.clone();
+        empty_recipients["recipients"] = json!([]);
+
+        let mut empty_kid = msg.clone();
+        empty_kid["recipients"][1]["header"]["kid"] = json!("");
+
+        let mut empty_key = msg;
+        empty_key["recipients"][1]["encrypted_key"] = json!("");
+
+        for (msg, exp_err_msg) in [
+            (empty_recipients, "Malformed: JWE `recipients` is empty"),
+            (empty_kid, "Malformed: JWE recipient 1 has empty `kid`"),
+            (
+                empty_key,
+                "Malformed: JWE recipient `did:example:bob#key-x25519-2` has empty `encrypted_key`",
+            ),
+        ] {
+            let msg = msg.to_string();
+
+            let mut buf = vec![];
+            let res = jwe::parse(&msg, &mut buf).expect("Unable parse");
+
+            let err = res.verify_recipients().expect_err("res is ok");
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+            assert_eq!(format!("{}", err), exp_err_msg);
+        }
+    }
+
+    #[test]
+    fn verify_iv_works() {
+        for (enc, iv_len) in [
+            ("A256CBC-HS512", 16),
+            ("A128CBC-HS256", 16),
+            ("A256GCM", 12),
+            ("A128GCM", 12),
+            ("XC20P", 24),
+        ] {
+            let msg = EnvelopeBuilder::new(ENCRYPTED_MSG_ANON_XC20P_1)
+                .protected("enc", json!(enc))
+                .field("iv", json!(_iv(iv_len)))
+                .build();
+
+            let mut buf = vec![];
+            let res = jwe::parse(&msg, &mut buf).expect("Unable parse");
+            res.verify_iv().expect("verify_iv is ok.");
+        }
+    }
+
+    #[test]
+    fn verify_iv_works_mismatched_length() {
+        for (enc, iv_len, exp_err_msg) in [
+            (
+                "A256CBC-HS512",
+                12,
+                "Malformed: Invalid iv length 12 bytes for A256CBC-HS512 enc, expected 16 bytes",
+            ),
+            (
+                "A128CBC-HS256",
+                24,
+                "Malformed: Invalid iv length 24 bytes for A128CBC-HS256 enc, expected 16 bytes",
+            ),
+            (
+                "A256GCM",
+                16,
+                "Malformed: Invalid iv length 16 bytes for A256GCM enc, expected 12 bytes",
+            ),
+            (
+                "A128GCM",
+                0,
+                "Malformed: Invalid iv length 0 bytes for A128GCM enc, expected 12 bytes",
+            ),
+            (
+                "XC20P",
+                12,
+                "Malformed: Invalid iv length 12 bytes for XC20P enc, expected 24 bytes",
+            ),
+        ] {
+            let msg = EnvelopeBuilder::new(ENCRYPTED_MSG_ANON_XC20P_1)
+                .protected("enc", json!(enc))
+                .field("iv", json!(_iv(iv_len)))
+                .build();
+
+            let mut buf = vec![];
+            let res = jwe::parse(&msg, &mut buf).expect("Unable parse");
+
+            let err = res.verify_iv().expect_err("res is ok");
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+            assert_eq!(format!("{}", err), exp_err_msg);
+        }
+    }
+
+    fn _iv(len: usize) -> String {
+        base64::encode_config(vec![1u8; len], base64::URL_SAFE_NO_PAD)
+    }
+
+    fn _parse_returns_malformed(msg: &str, exp_err_msg: &str) {
+        let mut buf = vec![];
+        let err = jwe::parse(msg, &mut buf).expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+        assert_eq!(format!("{}", err), exp_err_msg);
+    }
 }
@@ -0,0 +1,37 @@
+use std::fmt;
+
+use crate::{error::Result, utils::identity::ByIdentity};
+
+/// Source of content encryption keys (CEKs) for anonymously encrypted messages.
+/// By default a fresh random CEK is generated by OS CSPRNG for each message, custom
+/// provider can be set with `PackEncryptedOptions::session_key_provider` (for ex. to derive
+/// per-message CEKs from a shared session secret with a ratchet, so forward secrecy
+/// semantics are controlled by the caller).
+///
+/// Security: CEK is the only secret protecting message content. Provided keys must be
+/// indistinguishable from random and must never repeat. Compromise of the session secret
+/// compromises all messages which CEKs can be derived from it.
+/// Implementations must be safe to call concurrently for messages packed in parallel.
+pub trait SessionKeyProvider: Send + Sync {
+    /// Fills `cek` with the next content encryption key. Length of `cek` is defined by
+    /// the content encryption algorithm: 64 bytes for A256CBC-HS512, 32 bytes for A128CBC-HS256,
+    /// A256GCM and XC20P and 16 bytes for A128GCM.
+    ///
+    /// # Errors
+    /// Any error fails packing of the message (for ex. `InvalidState` if the session is over).
+    fn next_cek(&self, cek: &mut [u8]) -> Result<()>;
+}
+
+impl fmt::Debug for dyn SessionKeyProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SessionKeyProvider")
+    }
+}
+
+impl PartialEq for dyn SessionKeyProvider {
+    fn eq(&self, other: &Self) -> bool {
+        ByIdentity(self) == ByIdentity(other)
+    }
+}
+
+impl Eq for dyn SessionKeyProvider {}
@@ -20,7 +20,10 @@ pub(crate) use envelope::{Algorithm, CompactHeader, Header, ProtectedHeader, Sig
 
 // TODO: Remove allow
 #[allow(unused_imports)]
-pub(crate) use sign::{sign, sign_compact};
+pub(crate) use sign::{
+    sign, sign_compact, sign_compact_with_algorithm, sign_remote, sign_with_algorithm,
+    sign_with_headers, SIGNED_TYP,
+};
 
 // TODO: Remove allow
 #[allow(unused_imports)]
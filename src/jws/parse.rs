@@ -2,8 +2,13 @@ use crate::error::ToResult;
 use crate::{
     error::{err_msg, ErrorKind, Result, ResultExt},
     jws::envelope::{CompactHeader, ProtectedHeader, JWS},
+    utils::jose::check_processing_headers,
 };
 
+/// Critical protected header parameters understood on JWS parsing. Only encoded
+/// payload (`b64` is true) is supported.
+const UNDERSTOOD_CRITICAL_HEADERS: &[&str] = &["b64"];
+
 #[derive(Debug, PartialEq, Eq)]
 pub(crate) struct ParsedJWS<'a, 'b> {
     pub(crate) jws: JWS<'a>,
@@ -37,6 +42,8 @@ impl<'a> JWS<'a> {
                 let p: ProtectedHeader =
                     serde_json::from_slice(b).to_didcomm("Unable parse protected header")?;
 
+                check_processing_headers(b, UNDERSTOOD_CRITICAL_HEADERS)?;
+
                 protected.push(p);
             }
 
@@ -80,6 +87,8 @@ pub(crate) fn parse_compact<'a>(
     let parsed_header: CompactHeader =
         serde_json::from_slice(buf).kind(ErrorKind::Malformed, "Unable parse header")?;
 
+    check_processing_headers(buf, UNDERSTOOD_CRITICAL_HEADERS)?;
+
     Ok(ParsedCompactJWS {
         header,
         parsed_header,
@@ -90,6 +99,8 @@ pub(crate) fn parse_compact<'a>(
 
 #[cfg(test)]
 mod tests {
+    use serde_json::{json, Value};
+
     use crate::jws::{CompactHeader, ParsedCompactJWS};
     use crate::{
         error::ErrorKind,
@@ -421,6 +432,48 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_works_processing_headers() {
+        for (protected, exp_err_kind, exp_err_msg) in _unsupported_processing_headers() {
+            let protected = base64::encode_config(protected.to_string(), base64::URL_SAFE_NO_PAD);
+
+            let msg = json!({
+                "payload": PAYLOAD,
+                "signatures": [{
+                    "protected": protected,
+                    "signature": "FW33NnvOHV0Ted9-F7GZbkia-vYAfBKtH4oBxbrttWAhBZ6UFJMxcGjL3lwOl4YohI3kyyd08LHPWNMgP2EVCQ",
+                    "header": {"kid": "did:example:alice#key-1"},
+                }],
+            })
+            .to_string();
+
+            let mut buf = vec![];
+            let err = jws::parse(&msg, &mut buf).expect_err("res is ok");
+
+            assert_eq!(err.kind(), exp_err_kind);
+            assert_eq!(format!("{}", err), exp_err_msg);
+        }
+    }
+
+    #[test]
+    fn parse_compact_works_processing_headers() {
+        for (mut header, exp_err_kind, exp_err_msg) in _unsupported_processing_headers() {
+            header["kid"] = json!("did:example:alice#key-1");
+            let header = base64::encode_config(header.to_string(), base64::URL_SAFE_NO_PAD);
+
+            let msg = format!(
+                "{}.{}.iMi3kOWHTWoKiuTT4JxD9CkcUwSby9ekpOQk0Xdm9_H6jDpLPuhfX4U2EYgdPIJERl95MIecEhrufvO4bHgtCg",
+                header, PAYLOAD
+            );
+
+            let mut buf = vec![];
+            let err = jws::parse_compact(&msg, &mut buf).expect_err("res is ok");
+
+            assert_eq!(err.kind(), exp_err_kind);
+            assert_eq!(format!("{}", err), exp_err_msg);
+        }
+    }
+
     #[test]
     fn parse_compact_works() {
         let msg =
@@ -631,4 +684,32 @@ mod tests {
             "Malformed: Unable parse header: missing field `alg` at line 1 column 55"
         );
     }
+
+    const PAYLOAD: &str = "eyJpZCI6IjEyMzQ1Njc4OTAiLCJ0eXAiOiJhcHBsaWNhdGlvbi9kaWRjb21tLXBsYWluK2pzb24iLCJ0eXBlIjoiaHR0cDovL2V4YW1wbGUuY29tL3Byb3RvY29scy9sZXRzX2RvX2x1bmNoLzEuMC9wcm9wb3NhbCIsImZyb20iOiJkaWQ6ZXhhbXBsZTphbGljZSIsInRvIjpbImRpZDpleGFtcGxlOmJvYiJdLCJjcmVhdGVkX3RpbWUiOjE1MTYyNjkwMjIsImV4cGlyZXNfdGltZSI6MTUxNjM4NTkzMSwiYm9keSI6eyJtZXNzYWdlc3BlY2lmaWNhdHRyaWJ1dGUiOiJhbmQgaXRzIHZhbHVlIn19";
+
+    /// (protected header, expected error kind, expected error message)
+    fn _unsupported_processing_headers() -> Vec<(Value, ErrorKind, &'static str)> {
+        vec![
+            (
+                json!({
+                    "typ": "application/didcomm-signed+json",
+                    "alg": "EdDSA",
+                    "crit": ["exp"],
+                    "exp": 1516385931,
+                }),
+                ErrorKind::Unsupported,
+                "Unsupported crypto or method: Unsupported critical header parameter `exp`",
+            ),
+            (
+                json!({
+                    "typ": "application/didcomm-signed+json",
+                    "alg": "EdDSA",
+                    "b64": false,
+                    "crit": ["b64"],
+                }),
+                ErrorKind::Unsupported,
+                "Unsupported crypto or method: Unencoded payload (`b64` is false) is unsupported",
+            ),
+        ]
+    }
 }
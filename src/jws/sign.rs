@@ -1,23 +1,109 @@
+use std::collections::HashMap;
+
 use askar_crypto::sign::KeySign;
+use serde::Serialize;
+use serde_json::Value;
 
 use crate::{
-    error::{ErrorKind, Result, ResultExt},
+    algorithms::SignatureAlgorithm,
+    error::{ErrorKind, Result, ResultContext, ResultExt},
     jws::envelope::{Algorithm, CompactHeader, Header, ProtectedHeader, Signature, JWS},
+    secrets::{RemoteCrypto, Secret},
 };
 
+/// Media type of DIDComm signed messages put to `typ` protected header by default.
+pub(crate) const SIGNED_TYP: &str = "application/didcomm-signed+json";
+
 pub(crate) fn sign<Key: KeySign>(
     payload: &[u8],
     signer: (&str, &Key),
     alg: Algorithm,
+) -> Result<String> {
+    sign_with_headers(payload, signer, alg, SIGNED_TYP, &HashMap::new())
+}
+
+/// Same as `sign`, but allows to override `typ` and to add custom parameters
+/// to JWS protected header. Custom parameters must not conflict with `typ` and `alg`.
+pub(crate) fn sign_with_headers<Key: KeySign>(
+    payload: &[u8],
+    signer: (&str, &Key),
+    alg: Algorithm,
+    typ: &str,
+    extra_headers: &HashMap<String, Value>,
 ) -> Result<String> {
     let (kid, key) = signer;
 
     let sig_type = alg.sig_type()?;
+    let (protected, payload) = _signing_parts(payload, alg, typ, extra_headers)?;
+
+    let signature = key
+        .create_signature(_sign_input(&protected, &payload).as_bytes(), Some(sig_type))
+        .kind(ErrorKind::InvalidState, "Unable create signature")?;
+
+    _jws(kid, &protected, &payload, &signature)
+}
+
+/// Same as `sign_with_headers`, but signature is produced by the given `SignatureAlgorithm`
+/// (built-in or registered by application). `alg` header is set to the algorithm identifier.
+pub(crate) fn sign_with_algorithm(
+    payload: &[u8],
+    signer: (&str, &Secret),
+    algorithm: &dyn SignatureAlgorithm,
+    typ: &str,
+    extra_headers: &HashMap<String, Value>,
+) -> Result<String> {
+    let (kid, secret) = signer;
+
+    let alg = _algorithm_header(algorithm);
+    let (protected, payload) = _signing_parts(payload, alg, typ, extra_headers)?;
 
+    let signature = algorithm
+        .sign(secret, _sign_input(&protected, &payload).as_bytes())
+        .context("Unable create signature")?;
+
+    _jws(kid, &protected, &payload, &signature)
+}
+
+/// Same as `sign`, but signature is produced by `RemoteCrypto` (for ex. HSM)
+/// that doesn't expose the private key.
+pub(crate) async fn sign_remote<'rc>(
+    payload: &[u8],
+    kid: &str,
+    alg: Algorithm,
+    remote_crypto: &'rc (dyn RemoteCrypto + 'rc),
+) -> Result<String> {
+    alg.sig_type()?;
+    let (protected, payload) = _signing_parts(payload, alg, SIGNED_TYP, &HashMap::new())?;
+
+    let signature = remote_crypto
+        .sign(kid, _sign_input(&protected, &payload).as_bytes())
+        .await
+        .context("Unable create signature")?;
+
+    _jws(kid, &protected, &payload, &signature)
+}
+
+/// JWS protected header with custom parameters.
+#[derive(Serialize)]
+struct ExtendedProtectedHeader<'a> {
+    #[serde(flatten)]
+    header: ProtectedHeader<'a>,
+
+    #[serde(flatten)]
+    extra_headers: &'a HashMap<String, Value>,
+}
+
+/// Returns BASE64URL(UTF8(JWS Protected Header)) and BASE64URL(JWS Payload)
+fn _signing_parts(
+    payload: &[u8],
+    alg: Algorithm,
+    typ: &str,
+    extra_headers: &HashMap<String, Value>,
+) -> Result<(String, String)> {
     let protected = {
-        let protected = ProtectedHeader {
-            typ: "application/didcomm-signed+json",
-            alg,
+        let protected = ExtendedProtectedHeader {
+            header: ProtectedHeader { typ, alg },
+            extra_headers,
         };
 
         let protected = serde_json::to_string(&protected)
@@ -28,28 +114,28 @@ pub(crate) fn sign<Key: KeySign>(
 
     let payload = base64::encode_config(payload, base64::URL_SAFE_NO_PAD);
 
-    let signature = {
-        // JWS Signing Input
-        // The input to the digital signature or MAC computation.  Its value
-        // is ASCII(BASE64URL(UTF8(JWS Protected Header)) || '.' || BASE64URL(JWS Payload)).
-        let sign_input = format!("{}.{}", protected, payload);
+    Ok((protected, payload))
+}
 
-        let signature = key
-            .create_signature(sign_input.as_bytes(), Some(sig_type))
-            .kind(ErrorKind::InvalidState, "Unable create signature")?;
+/// JWS Signing Input
+/// The input to the digital signature or MAC computation.  Its value
+/// is ASCII(BASE64URL(UTF8(JWS Protected Header)) || '.' || BASE64URL(JWS Payload)).
+fn _sign_input(protected: &str, payload: &str) -> String {
+    format!("{}.{}", protected, payload)
+}
 
-        base64::encode_config(&signature, base64::URL_SAFE_NO_PAD)
-    };
+fn _jws(kid: &str, protected: &str, payload: &str, signature: &[u8]) -> Result<String> {
+    let signature = base64::encode_config(signature, base64::URL_SAFE_NO_PAD);
 
     let signature = Signature {
         header: Header { kid },
-        protected: &protected,
+        protected,
         signature: &signature,
     };
 
     let jws = JWS {
         signatures: vec![signature],
-        payload: &payload,
+        payload,
     };
 
     let jws = serde_json::to_string(&jws).kind(ErrorKind::InvalidState, "Unable serialize jws")?;
@@ -96,6 +182,50 @@ pub(crate) fn sign_compact<Key: KeySign>(
     Ok(compact_jws)
 }
 
+/// Same as `sign_compact`, but signature is produced by the given `SignatureAlgorithm`
+/// (built-in or registered by application). `alg` header is set to the algorithm identifier.
+pub(crate) fn sign_compact_with_algorithm(
+    payload: &[u8],
+    signer: (&str, &Secret),
+    typ: &str,
+    algorithm: &dyn SignatureAlgorithm,
+) -> Result<String> {
+    let (kid, secret) = signer;
+
+    let header = {
+        let header = CompactHeader {
+            typ,
+            alg: _algorithm_header(algorithm),
+            kid,
+        };
+
+        let header = serde_json::to_string(&header)
+            .kind(ErrorKind::InvalidState, "Unable serialize header")?;
+
+        base64::encode_config(header, base64::URL_SAFE_NO_PAD)
+    };
+
+    let payload = base64::encode_config(payload, base64::URL_SAFE_NO_PAD);
+
+    let signature = algorithm
+        .sign(secret, _sign_input(&header, &payload).as_bytes())
+        .context("Unable create signature")?;
+
+    let signature = base64::encode_config(&signature, base64::URL_SAFE_NO_PAD);
+
+    Ok(format!("{}.{}.{}", header, payload, signature))
+}
+
+/// JWS `alg` header value for the given algorithm.
+fn _algorithm_header(algorithm: &dyn SignatureAlgorithm) -> Algorithm {
+    match algorithm.alg() {
+        "EdDSA" => Algorithm::EdDSA,
+        "ES256" => Algorithm::Es256,
+        "ES256K" => Algorithm::Es256K,
+        other => Algorithm::Other(other.to_owned()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use askar_crypto::{
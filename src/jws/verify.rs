@@ -7,23 +7,49 @@ use crate::{
 
 impl<'a, 'b> ParsedJWS<'a, 'b> {
     pub(crate) fn verify<Key: KeySigVerify>(&self, signer: (&str, &Key)) -> Result<bool> {
+        self.verify_with_payload(signer, self.jws.payload)
+    }
+
+    /// Verifies signature as it was produced over the given base64url encoded `payload`
+    /// instead of JWS payload.
+    pub(crate) fn verify_with_payload<Key: KeySigVerify>(
+        &self,
+        signer: (&str, &Key),
+        payload: &str,
+    ) -> Result<bool> {
         let (kid, key) = signer;
 
-        let (i, signature) = self
+        let i = self
             .jws
             .signatures
             .iter()
-            .enumerate()
-            .find(|(_, sig)| sig.header.kid == kid)
+            .position(|sig| sig.header.kid == kid)
             .ok_or_else(|| err_msg(ErrorKind::InvalidState, "KID not found"))?;
 
+        self.verify_at_with_payload(i, key, payload)
+    }
+
+    /// Verifies `i`-th signature as it was produced over the given base64url encoded `payload`.
+    /// Unlike lookup by kid it allows to verify each of signatures with the same kid.
+    pub(crate) fn verify_at_with_payload<Key: KeySigVerify>(
+        &self,
+        i: usize,
+        key: &Key,
+        payload: &str,
+    ) -> Result<bool> {
+        let signature = self
+            .jws
+            .signatures
+            .get(i)
+            .ok_or_else(|| err_msg(ErrorKind::InvalidState, "Invalid signature index"))?;
+
         let protected = self
             .protected
             .get(i)
             .ok_or_else(|| err_msg(ErrorKind::InvalidState, "Invalid protected header index"))?;
 
         let sig_type = protected.alg.sig_type()?;
-        let sign_input = format!("{}.{}", signature.protected, self.jws.payload);
+        let sign_input = format!("{}.{}", signature.protected, payload);
 
         let signature = base64::decode_config(&signature.signature, base64::URL_SAFE_NO_PAD)
             .kind(ErrorKind::Malformed, "Unable decode signature")?;
@@ -23,12 +23,21 @@ pub mod error;
 pub mod protocols;
 pub mod secrets;
 
+#[cfg(feature = "v1-compat")]
+pub mod packv1;
+
 pub use message::{
-    Attachment, AttachmentBuilder, AttachmentData, Base64AttachmentData, FromPrior,
-    JsonAttachmentData, LinksAttachmentData, Message, MessageBuilder, MessagingServiceMetadata,
-    PackEncryptedMetadata, PackEncryptedOptions, PackSignedMetadata, UnpackMetadata, UnpackOptions,
+    Attachment, AttachmentBuilder, AttachmentData, AttachmentSize, Base64AttachmentData,
+    BodyValidator, BodyValidators, DIDCommClient, EnvelopeDescription, FieldDiff, ForwardHopInfo,
+    FromPrior, FromPriorSpec, JsonAttachmentData, LayerInfo, LinksAttachmentData, Message,
+    MessageBuilder, MessagePreview, MessagingServiceMetadata, PackContext, PackEncryptedMetadata,
+    PackEncryptedOptions, PackPlaintextOptions, PackPlan, PackSignedMetadata, PackedKind,
+    ReturnRoute, SignOptions, SizeBreakdown, UnpackMetadata, UnpackMetadataBuilder, UnpackOptions,
+    ValidationError, MAX_DECOMPRESSED_ATTACHMENT_BYTES,
 };
 
+pub use jwe::{NonceSource, SessionKeyProvider};
+
 #[cfg(test)]
 mod tests {
     use serde_json::json;
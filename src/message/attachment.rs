@@ -1,5 +1,29 @@
+use std::{
+    collections::HashSet,
+    io::{Read, Write},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    algorithms::AlgorithmRegistry,
+    did::DIDResolver,
+    error::{err_msg, ErrorKind, Result, ResultContext, ResultExt},
+    jws,
+    message::unpack::_verify_jws,
+    utils::json::to_canonical_string,
+};
+
+/// `format` of base64 attachments which content is compressed with GZIP.
+const GZIP_FORMAT: &str = "gzip";
+
+/// Maximum size of decompressed content `Attachment::decode` accepts (16 MiB),
+/// so a small compression bomb can't exhaust memory.
+pub const MAX_DECOMPRESSED_ATTACHMENT_BYTES: u64 = 16 * 1024 * 1024;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct Attachment {
@@ -37,12 +61,14 @@ pub struct Attachment {
     /// A hint about when the content in this attachment was last modified
     /// in UTC Epoch Seconds (seconds since 1970-01-01T00:00:00Z UTC).
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, deserialize_with = "crate::utils::serde::_optional_u64")]
     pub lastmod_time: Option<u64>,
 
     /// Mostly relevant when content is included by reference instead of by value.
     /// Lets the receiver guess how expensive it will be, in time, bandwidth, and storage,
     /// to fully fetch the attachment.
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, deserialize_with = "crate::utils::serde::_optional_u64")]
     pub byte_count: Option<u64>,
 }
 
@@ -53,6 +79,26 @@ impl Attachment {
         })
     }
 
+    /// Creates base64 attachment with the given content compressed with GZIP.
+    /// Attachment `format` is set to `gzip`, so `decode` decompresses the content transparently.
+    /// `media_type` still can be used to describe the uncompressed content.
+    ///
+    /// # Errors
+    /// - `InvalidState` Unable to compress the content.
+    pub fn base64_compressed(content: &[u8]) -> Result<AttachmentBuilder> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+
+        encoder
+            .write_all(content)
+            .kind(ErrorKind::InvalidState, "Unable compress attachment")?;
+
+        let compressed = encoder
+            .finish()
+            .kind(ErrorKind::InvalidState, "Unable compress attachment")?;
+
+        Ok(Attachment::base64(base64::encode(compressed)).format(GZIP_FORMAT.to_owned()))
+    }
+
     pub fn json(json: Value) -> AttachmentBuilder {
         AttachmentBuilder::new(AttachmentData::Json {
             value: JsonAttachmentData { json, jws: None },
@@ -68,6 +114,141 @@ impl Attachment {
             },
         })
     }
+
+    /// Returns attachment content:
+    /// - decoded `base64` data for base64 attachments, decompressed if attachment `format` is `gzip`
+    ///   (see `base64_compressed`),
+    /// - serialized `json` data for json attachments.
+    ///
+    /// # Errors
+    /// - `Malformed` base64 data is invalid or can't be decompressed.
+    /// - `TooLarge` decompressed content exceeds `MAX_DECOMPRESSED_ATTACHMENT_BYTES`.
+    /// - `Unsupported` Links attachment content isn't embedded in the message.
+    pub fn decode(&self) -> Result<Vec<u8>> {
+        self.decode_with_limit(MAX_DECOMPRESSED_ATTACHMENT_BYTES)
+    }
+
+    /// Same as `decode`, but with custom maximum size of decompressed content.
+    ///
+    /// # Errors
+    /// - `TooLarge` decompressed content exceeds `max_bytes`.
+    /// - other errors are the same as for `decode`.
+    pub fn decode_with_limit(&self, max_bytes: u64) -> Result<Vec<u8>> {
+        match self.data {
+            AttachmentData::Base64 { ref value } => {
+                let content = value.decoded_bytes()?;
+
+                if self.format.as_deref() != Some(GZIP_FORMAT) {
+                    return Ok(content);
+                }
+
+                let mut decompressed = vec![];
+
+                // One extra byte allows to detect that the limit is exceeded
+                GzDecoder::new(&content[..])
+                    .take(max_bytes.saturating_add(1))
+                    .read_to_end(&mut decompressed)
+                    .kind(ErrorKind::Malformed, "Unable decompress gzip attachment")?;
+
+                if decompressed.len() as u64 > max_bytes {
+                    Err(err_msg(
+                        ErrorKind::TooLarge,
+                        format!("Decompressed gzip attachment exceeds {} bytes", max_bytes),
+                    ))?
+                }
+
+                Ok(decompressed)
+            }
+            AttachmentData::Json { ref value } => serde_json::to_vec(&value.json)
+                .kind(ErrorKind::InvalidState, "Unable serialize json attachment"),
+            AttachmentData::Links { .. } => Err(err_msg(
+                ErrorKind::Unsupported,
+                "Decoding of links attachment is unsupported",
+            )),
+        }
+    }
+
+    /// Verifies JWS carried in attachment data (`data.jws`) over the attachment content.
+    ///
+    /// JWS is expected in General JSON Serialization with a single signature made
+    /// by `authentication` key of the signer DID. The payload can be detached (empty or absent),
+    /// otherwise it must match the attachment content. Signed content is:
+    /// - decoded `base64` data for base64 attachments,
+    /// - JCS (RFC 8785) canonicalized `json` data for json attachments.
+    ///
+    /// # Params
+    /// - `did_resolver` instance of `DIDResolver` to resolve signer DID.
+    ///
+    /// # Returns
+    /// Key ID of the signer or None if attachment data has no JWS.
+    ///
+    /// # Errors
+    /// - `Malformed` JWS or attachment content is invalid, JWS payload doesn't match
+    ///   the content or signature is wrong.
+    /// - `DIDNotResolved` Signer DID not found.
+    /// - `DIDUrlNotFound` Signer key isn't found in `authentication` of signer DID Doc.
+    /// - `Unsupported` Links attachment or used signature algorithm are unsupported.
+    /// - `IOError` IO error during DID resolving.
+    pub async fn verify_jws<'dr>(
+        &self,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+    ) -> Result<Option<String>> {
+        let (jws, content) = match self.data {
+            AttachmentData::Base64 { ref value } => match value.jws {
+                Some(ref jws) => (jws, value.decoded_bytes()?),
+                None => return Ok(None),
+            },
+            AttachmentData::Json { ref value } => match value.jws {
+                Some(ref jws) => (jws, to_canonical_string(&value.json)?.into_bytes()),
+                None => return Ok(None),
+            },
+            AttachmentData::Links { ref value } => match value.jws {
+                Some(_) => Err(err_msg(
+                    ErrorKind::Unsupported,
+                    "Verification of links attachment jws is unsupported",
+                ))?,
+                None => return Ok(None),
+            },
+        };
+
+        let payload = base64::encode_config(content, base64::URL_SAFE_NO_PAD);
+
+        let mut jws: Value =
+            serde_json::from_str(jws).kind(ErrorKind::Malformed, "Unable parse attachment jws")?;
+
+        let jws_props = jws
+            .as_object_mut()
+            .ok_or_else(|| err_msg(ErrorKind::Malformed, "Attachment jws is not an object"))?;
+
+        // Detached payload is replaced with the attachment content
+        match jws_props.get("payload").and_then(Value::as_str) {
+            None | Some("") => {
+                jws_props.insert("payload".into(), Value::String(payload));
+            }
+            Some(jws_payload) if jws_payload == payload => {}
+            Some(_) => Err(err_msg(
+                ErrorKind::Malformed,
+                "Attachment jws payload doesn't match attachment content",
+            ))?,
+        }
+
+        let jws = serde_json::to_string(&jws)
+            .kind(ErrorKind::InvalidState, "Unable serialize attachment jws")?;
+
+        let mut buf = vec![];
+        let parsed_jws = jws::parse(&jws, &mut buf)?;
+
+        let (signer_kid, _) = _verify_jws(
+            &parsed_jws,
+            did_resolver,
+            false,
+            &AlgorithmRegistry::default(),
+        )
+        .await
+        .context("Unable verify attachment jws")?;
+
+        Ok(Some(signer_kid))
+    }
 }
 
 pub struct AttachmentBuilder {
@@ -79,6 +260,7 @@ pub struct AttachmentBuilder {
     format: Option<String>,
     lastmod_time: Option<u64>,
     byte_count: Option<u64>,
+    content_id: bool,
 }
 
 impl AttachmentBuilder {
@@ -92,14 +274,27 @@ impl AttachmentBuilder {
             format: None,
             lastmod_time: None,
             byte_count: None,
+            content_id: false,
         }
     }
 
+    /// Sets attachment id. If not set, random UUID is generated on `finalize`.
     pub fn id(mut self, id: String) -> Self {
         self.id = Some(id);
         self
     }
 
+    /// Derives attachment id from SHA-256 hash of the attachment data instead of
+    /// generating random UUID, so the same content always gets the same id.
+    /// Ignored if id is set explicitly.
+    ///
+    /// `MessageBuilder` adds numeric suffix to derived ids of attachments with the same
+    /// content, so ids remain unique within the message.
+    pub fn content_id(mut self) -> Self {
+        self.content_id = true;
+        self
+    }
+
     pub fn description(mut self, description: String) -> Self {
         self.description = Some(description);
         self
@@ -141,9 +336,15 @@ impl AttachmentBuilder {
     }
 
     pub fn finalize(self) -> Attachment {
+        let id = match self.id {
+            Some(id) => id,
+            None if self.content_id => _content_id(&self.data),
+            None => Uuid::new_v4().to_string(),
+        };
+
         Attachment {
             data: self.data,
-            id: self.id,
+            id: Some(id),
             description: self.description,
             filename: self.filename,
             media_type: self.media_type,
@@ -154,6 +355,47 @@ impl AttachmentBuilder {
     }
 }
 
+/// Attachment id derived from the data: hex encoded first 16 bytes of SHA-256 hash
+/// of the data in canonical JSON serialization.
+fn _content_id(data: &AttachmentData) -> String {
+    // Serialization of attachment data doesn't fail, fallback only keeps the id stable
+    let data = serde_json::to_value(data)
+        .ok()
+        .and_then(|data| to_canonical_string(&data).ok())
+        .unwrap_or_default();
+
+    format!("{:x}", Sha256::digest(data.as_bytes()))[..32].to_owned()
+}
+
+/// Makes ids derived with `AttachmentBuilder::content_id` unique within the attachments
+/// by adding numeric suffix (`<id>-1`, `<id>-2`, ...) to ids of repeated content.
+/// Other duplicate ids are kept as is and rejected by message validation.
+pub(crate) fn _unique_content_ids(attachments: &mut [Attachment]) {
+    let mut ids: HashSet<String> = attachments.iter().filter_map(|a| a.id.clone()).collect();
+    let mut seen = HashSet::new();
+
+    for attachment in attachments {
+        let id = match attachment.id {
+            Some(ref id) => id.clone(),
+            None => continue,
+        };
+
+        if seen.insert(id.clone()) || id != _content_id(&attachment.data) {
+            continue;
+        }
+
+        let mut i = 1;
+
+        while ids.contains(&format!("{}-{}", id, i)) {
+            i += 1;
+        }
+
+        let unique_id = format!("{}-{}", id, i);
+        ids.insert(unique_id.clone());
+        attachment.id = Some(unique_id);
+    }
+}
+
 // Attention: we are using untagged enum serialization variant.
 // Serde will try to match the data against each variant in order and the
 // first one that deserializes successfully is the one returned.
@@ -177,6 +419,23 @@ pub enum AttachmentData {
     },
 }
 
+impl AttachmentData {
+    /// Creates json attachment data embedding packed (encrypted or signed) message,
+    /// the shape expected by mediators in `Forward` message attachments.
+    /// Messages in JSON serialization are embedded as JSON objects, all others
+    /// (for ex. compact serialization) are embedded as JSON strings.
+    pub fn from_packed_message(packed: &str) -> AttachmentData {
+        let json = match serde_json::from_str(packed) {
+            Ok(json @ Value::Object(_)) | Ok(json @ Value::String(_)) => json,
+            _ => Value::String(packed.trim().to_owned()),
+        };
+
+        AttachmentData::Json {
+            value: JsonAttachmentData { json, jws: None },
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct Base64AttachmentData {
     /// Base64-encoded data, when representing arbitrary content inline.
@@ -187,6 +446,17 @@ pub struct Base64AttachmentData {
     pub jws: Option<String>,
 }
 
+impl Base64AttachmentData {
+    /// Decodes attachment content. Content isn't decoded while the message is parsed,
+    /// so big attachments cost nothing until they are accessed.
+    ///
+    /// # Errors
+    /// - `Malformed` attachment content isn't a valid base64.
+    pub fn decoded_bytes(&self) -> Result<Vec<u8>> {
+        base64::decode(&self.base64).kind(ErrorKind::Malformed, "Unable decode base64 attachment")
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct JsonAttachmentData {
     /// Directly embedded JSON data.
@@ -215,6 +485,16 @@ mod tests {
     use core::panic;
     use serde_json::json;
 
+    use crate::{
+        did::resolvers::ExampleDIDResolver,
+        protocols::routing::try_parse_forward,
+        test_vectors::{
+            ALICE_AUTH_METHOD_25519, ALICE_DID_DOC, ALICE_SECRETS, ENCRYPTED_MSG_ANON_XC20P_1,
+        },
+        utils::crypto::AsKnownKeyPair,
+        Message,
+    };
+
     use super::*;
 
     #[test]
@@ -236,6 +516,7 @@ mod tests {
         };
 
         assert_eq!(data.base64, "ZXhhbXBsZQ==");
+        assert_eq!(data.decoded_bytes().expect("decode is ok."), b"example");
         assert_eq!(data.jws, Some("jws".to_owned()));
         assert_eq!(attachment.id, Some("example-1".to_owned()));
 
@@ -251,6 +532,34 @@ mod tests {
         assert_eq!(attachment.byte_count, Some(200));
     }
 
+    #[test]
+    fn attachment_base64_works_lazy_decoding() {
+        let msg = Message::from_str(
+            r#"
+            {
+                "id": "1234567890",
+                "typ": "application/didcomm-plain+json",
+                "type": "http://example.com/protocols/lets_do_lunch/1.0/proposal",
+                "body": {},
+                "attachments": [{"id": "1", "data": {"base64": "not a base64!"}}]
+            }
+            "#,
+        )
+        .expect("parse is ok.")
+        .validate()
+        .expect("validate is ok.");
+
+        let data = match msg.attachments.as_ref().expect("attachments are ok.")[0].data {
+            AttachmentData::Base64 { ref value } => value,
+            _ => panic!("data isn't base64."),
+        };
+
+        assert_eq!(data.base64, "not a base64!");
+
+        let err = data.decoded_bytes().expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+    }
+
     #[test]
     fn attachment_json_works() {
         let attachment = Attachment::json(json!("example"))
@@ -330,4 +639,295 @@ mod tests {
         assert_eq!(attachment.lastmod_time, Some(10000));
         assert_eq!(attachment.byte_count, Some(200));
     }
+
+    #[tokio::test]
+    async fn attachment_verify_jws_works() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+
+        let content = b"example";
+        let jws = _sign_attachment(content);
+
+        let attachment = Attachment::base64(base64::encode(content))
+            .jws(jws.clone())
+            .finalize();
+
+        let signer_kid = attachment
+            .verify_jws(&did_resolver)
+            .await
+            .expect("verify_jws is ok.");
+
+        assert_eq!(signer_kid, Some(ALICE_AUTH_METHOD_25519.id.clone()));
+
+        // Detached payload
+        let mut detached: Value = serde_json::from_str(&jws).unwrap();
+        detached["payload"] = json!("");
+
+        let attachment = Attachment::base64(base64::encode(content))
+            .jws(detached.to_string())
+            .finalize();
+
+        let signer_kid = attachment
+            .verify_jws(&did_resolver)
+            .await
+            .expect("verify_jws is ok.");
+
+        assert_eq!(signer_kid, Some(ALICE_AUTH_METHOD_25519.id.clone()));
+
+        // JSON content is signed canonicalized
+        let json = json!({"foo": "bar", "baz": 1});
+        let jws = _sign_attachment(to_canonical_string(&json).unwrap().as_bytes());
+
+        let attachment = Attachment::json(json).jws(jws).finalize();
+
+        let signer_kid = attachment
+            .verify_jws(&did_resolver)
+            .await
+            .expect("verify_jws is ok.");
+
+        assert_eq!(signer_kid, Some(ALICE_AUTH_METHOD_25519.id.clone()));
+    }
+
+    #[tokio::test]
+    async fn attachment_verify_jws_works_no_jws() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+
+        let attachment = Attachment::base64(base64::encode(b"example")).finalize();
+
+        let signer_kid = attachment
+            .verify_jws(&did_resolver)
+            .await
+            .expect("verify_jws is ok.");
+
+        assert_eq!(signer_kid, None);
+    }
+
+    #[tokio::test]
+    async fn attachment_verify_jws_works_tampered() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+
+        let jws = _sign_attachment(b"example");
+
+        // Content doesn't match attached payload
+        let attachment = Attachment::base64(base64::encode(b"tampered"))
+            .jws(jws.clone())
+            .finalize();
+
+        let err = attachment
+            .verify_jws(&did_resolver)
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        // Content doesn't match detached payload
+        let mut detached: Value = serde_json::from_str(&jws).unwrap();
+        detached["payload"] = json!("");
+
+        let attachment = Attachment::base64(base64::encode(b"tampered"))
+            .jws(detached.to_string())
+            .finalize();
+
+        let err = attachment
+            .verify_jws(&did_resolver)
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+    }
+
+    #[test]
+    fn attachment_base64_compressed_works() {
+        let content = json!({ "items": vec!["repeated value"; 100] })
+            .to_string()
+            .into_bytes();
+
+        let attachment = Attachment::base64_compressed(&content)
+            .expect("compress is ok.")
+            .id("example-1".to_owned())
+            .media_type("application/json".to_owned())
+            .finalize();
+
+        assert_eq!(attachment.format, Some("gzip".to_owned()));
+        assert_eq!(attachment.media_type, Some("application/json".to_owned()));
+
+        let data = match attachment.data {
+            AttachmentData::Base64 { ref value } => value,
+            _ => panic!("data isn't base64."),
+        };
+
+        assert!(data.decoded_bytes().expect("decode is ok.").len() < content.len());
+
+        let msg = Message::build(
+            "1234567890".to_owned(),
+            "http://example.com/protocols/lets_do_lunch/1.0/proposal".to_owned(),
+            json!({}),
+        )
+        .attachment(attachment)
+        .finalize();
+
+        let msg = Message::from_str(&serde_json::to_string(&msg).expect("serialize is ok."))
+            .expect("parse is ok.");
+
+        let attachment = &msg.attachments.as_ref().expect("attachments are ok.")[0];
+        assert_eq!(attachment.decode().expect("decode is ok."), content);
+    }
+
+    #[test]
+    fn attachment_builder_works_generated_ids() {
+        let msg = Message::build("1".to_owned(), "example/v1".to_owned(), json!({}))
+            .attachments(vec![
+                Attachment::base64("ZXhhbXBsZQ==".to_owned()).finalize(),
+                Attachment::base64("ZXhhbXBsZQ==".to_owned()).finalize(),
+                Attachment::json(json!({"foo": "bar"}))
+                    .content_id()
+                    .finalize(),
+                Attachment::json(json!({"foo": "bar"}))
+                    .content_id()
+                    .finalize(),
+                Attachment::json(json!({"foo": "bar"}))
+                    .content_id()
+                    .finalize(),
+                Attachment::json(json!({"foo": "baz"}))
+                    .content_id()
+                    .finalize(),
+                Attachment::json(json!({"foo": "bar"}))
+                    .id("example-1".to_owned())
+                    .content_id()
+                    .finalize(),
+            ])
+            .finalize();
+
+        let ids: Vec<_> = msg
+            .attachments
+            .iter()
+            .flatten()
+            .map(|a| a.id.clone().expect("id is generated."))
+            .collect();
+
+        let unique_ids: HashSet<_> = ids.iter().collect();
+        assert_eq!(unique_ids.len(), ids.len());
+        msg.validate_attachments().expect("validate is ok.");
+
+        // Derived ids are deterministic
+        let content_id = Attachment::json(json!({"foo": "bar"}))
+            .content_id()
+            .finalize()
+            .id
+            .expect("id is generated.");
+
+        assert_eq!(content_id.len(), 32);
+        assert_eq!(ids[2], content_id);
+        assert_eq!(ids[3], format!("{}-1", content_id));
+        assert_eq!(ids[4], format!("{}-2", content_id));
+        assert_ne!(ids[5], content_id);
+        assert_eq!(ids[6], "example-1");
+    }
+
+    #[test]
+    fn attachment_decode_works() {
+        let attachment = Attachment::base64("ZXhhbXBsZQ==".to_owned())
+            .format("json".to_owned())
+            .finalize();
+
+        assert_eq!(attachment.decode().expect("decode is ok."), b"example");
+
+        let attachment = Attachment::json(json!({"example": 1})).finalize();
+        assert_eq!(
+            attachment.decode().expect("decode is ok."),
+            br#"{"example":1}"#
+        );
+
+        let attachment =
+            Attachment::links(vec!["http://example.com".to_owned()], "hash".to_owned()).finalize();
+
+        let err = attachment.decode().expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn attachment_decode_works_invalid_gzip() {
+        let attachment = Attachment::base64("ZXhhbXBsZQ==".to_owned())
+            .format("gzip".to_owned())
+            .finalize();
+
+        let err = attachment.decode().expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+    }
+
+    #[test]
+    fn attachment_decode_works_gzip_too_large() {
+        let content = vec![0u8; 1024 * 1024];
+
+        let attachment = Attachment::base64_compressed(&content)
+            .expect("base64_compressed is ok.")
+            .finalize();
+
+        // Highly compressible content is small in the message
+        let packed = serde_json::to_string(&attachment).expect("to_string is ok.");
+        assert!(packed.len() < 8 * 1024);
+
+        assert_eq!(
+            attachment
+                .decode_with_limit(content.len() as u64)
+                .expect("decode is ok."),
+            content
+        );
+
+        let err = attachment
+            .decode_with_limit(content.len() as u64 - 1)
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::TooLarge);
+
+        assert_eq!(
+            format!("{}", err),
+            "Too large: Decompressed gzip attachment exceeds 1048575 bytes"
+        );
+    }
+
+    #[test]
+    fn attachment_data_from_packed_message_works() {
+        for (packed, exp_forwarded_msg) in [
+            (
+                ENCRYPTED_MSG_ANON_XC20P_1,
+                serde_json::from_str(ENCRYPTED_MSG_ANON_XC20P_1).expect("from_str is ok."),
+            ),
+            (
+                "eyJhbGciOiJFZERTQSJ9.eyJpZCI6IjEifQ.c2ln",
+                json!("eyJhbGciOiJFZERTQSJ9.eyJpZCI6IjEifQ.c2ln"),
+            ),
+            (
+                "\"eyJhbGciOiJFZERTQSJ9.eyJpZCI6IjEifQ.c2ln\"",
+                json!("eyJhbGciOiJFZERTQSJ9.eyJpZCI6IjEifQ.c2ln"),
+            ),
+        ] {
+            let forward = Message::build(
+                "1234567890".to_owned(),
+                "https://didcomm.org/routing/2.0/forward".to_owned(),
+                json!({ "next": "did:example:bob" }),
+            )
+            .attachment(
+                AttachmentBuilder::new(AttachmentData::from_packed_message(packed)).finalize(),
+            )
+            .finalize();
+
+            let forward =
+                Message::from_str(&serde_json::to_string(&forward).expect("to_string is ok."))
+                    .expect("from_str is ok.");
+
+            let parsed_forward = try_parse_forward(&forward).expect("forward is some.");
+            assert_eq!(parsed_forward.forwarded_msg, exp_forwarded_msg);
+        }
+    }
+
+    fn _sign_attachment(content: &[u8]) -> String {
+        let secret = ALICE_SECRETS
+            .iter()
+            .find(|s| s.id == ALICE_AUTH_METHOD_25519.id)
+            .expect("Secret not found");
+
+        let key = secret.as_ed25519().expect("Unable instantiate key");
+
+        jws::sign(content, (&secret.id, &key), jws::Algorithm::EdDSA).expect("Unable sign")
+    }
 }
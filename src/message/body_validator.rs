@@ -0,0 +1,231 @@
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use serde_json::Value;
+
+use crate::{
+    error::{Result, ResultContext},
+    protocols::routing::{validate_forward_body, FORWARD_MSG_TYPE},
+    utils::identity::ByIdentity,
+};
+
+/// Validates `body` of messages of some type. Should return `Malformed` error
+/// if the body doesn't match the schema of the protocol message.
+pub type BodyValidator = dyn Fn(&Value) -> Result<()> + Send + Sync;
+
+/// Validators of message `body` registered by application per message `type`.
+/// Passed through `UnpackOptions::body_validators` (or to `Message::validate_body`),
+/// so different parts of application can use different sets of validators.
+/// Built-in validators (routing forward) are used for types without registered validator.
+#[derive(Clone, Default)]
+pub struct BodyValidators {
+    validators: HashMap<String, Arc<BodyValidator>>,
+}
+
+impl BodyValidators {
+    /// Creates registry without registered validators.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers validator of message `body` for the given message `type`.
+    /// Replaces the previous validator registered for the type.
+    /// Built-in validators (routing forward) can be overridden the same way.
+    ///
+    /// # Params
+    /// - `type_` message type (for ex. `https://didcomm.org/basicmessage/2.0/message`)
+    /// - `validator` validator of message body
+    pub fn register<F>(&mut self, type_: &str, validator: F)
+    where
+        F: Fn(&Value) -> Result<()> + Send + Sync + 'static,
+    {
+        self.validators
+            .insert(type_.to_owned(), Arc::new(validator));
+    }
+
+    /// Removes validator registered for the given message `type`.
+    /// Built-in validators can't be removed, but can be overridden by `register`.
+    pub fn unregister(&mut self, type_: &str) {
+        self.validators.remove(type_);
+    }
+
+    /// Validates message body with validator registered for the message type if any,
+    /// otherwise with built-in one.
+    pub(crate) fn validate(&self, type_: &str, body: &Value) -> Result<()> {
+        let res = match self.validators.get(type_) {
+            Some(validator) => validator(body),
+            None => match type_ {
+                FORWARD_MSG_TYPE => validate_forward_body(body),
+                _ => Ok(()),
+            },
+        };
+
+        res.context(format!("Invalid body of `{}` message", type_))
+    }
+}
+
+impl fmt::Debug for BodyValidators {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut types: Vec<_> = self.validators.keys().collect();
+        types.sort();
+
+        f.debug_struct("BodyValidators")
+            .field("validators", &types)
+            .finish()
+    }
+}
+
+impl PartialEq for BodyValidators {
+    fn eq(&self, other: &Self) -> bool {
+        self.validators.len() == other.validators.len()
+            && self.validators.iter().all(|(type_, validator)| {
+                other.validators.get(type_).map_or(false, |other| {
+                    ByIdentity(&**validator) == ByIdentity(&**other)
+                })
+            })
+    }
+}
+
+impl Eq for BodyValidators {}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::{
+        did::resolvers::ExampleDIDResolver,
+        error::{err_msg, ErrorKind},
+        protocols::routing::FORWARD_MSG_TYPE,
+        secrets::resolvers::ExampleSecretsResolver,
+        Message, UnpackOptions,
+    };
+
+    use super::BodyValidators;
+
+    #[test]
+    fn register_body_validator_works() {
+        let type_ = "https://example.com/register-body-validator-works/1.0/ping";
+
+        let mut validators = BodyValidators::new();
+
+        validators.register(type_, |body| match body.get("response_requested") {
+            Some(Value::Bool(_)) | None => Ok(()),
+            Some(_) => Err(err_msg(
+                ErrorKind::Malformed,
+                "`response_requested` must be a boolean",
+            )),
+        });
+
+        let msg = Message::from_value(_msg(type_, json!({ "response_requested": true })))
+            .expect("from_value is ok.");
+
+        msg.validate_body(&validators).expect("validate is ok.");
+
+        // Registered validators are consulted only where passed
+        let msg = Message::from_value(_msg(type_, json!({ "response_requested": "yes" })))
+            .expect("from_value is ok.");
+
+        let err = msg.validate_body(&validators).expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        assert_eq!(
+            format!("{}", err),
+            format!(
+                "Malformed: Invalid body of `{}` message: `response_requested` must be a boolean",
+                type_
+            )
+        );
+
+        validators.unregister(type_);
+        msg.validate_body(&validators).expect("validate is ok.");
+    }
+
+    #[tokio::test]
+    async fn register_body_validator_works_unpack() {
+        let type_ = "https://example.com/register-body-validator-works-unpack/1.0/ping";
+
+        let mut validators = BodyValidators::new();
+
+        validators.register(type_, |_| {
+            Err(err_msg(ErrorKind::Malformed, "Pings are not accepted"))
+        });
+
+        let msg = serde_json::to_string(&_msg(type_, json!({}))).expect("msg is ok.");
+        let did_resolver = ExampleDIDResolver::new(vec![]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+
+        Message::unpack(
+            &msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        let err = Message::unpack(
+            &msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                body_validators: validators,
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        assert_eq!(
+            format!("{}", err),
+            format!(
+                "Malformed: Invalid body of `{}` message: Pings are not accepted",
+                type_
+            )
+        );
+    }
+
+    #[test]
+    fn validate_body_works_forward() {
+        Message::from_value(_msg(FORWARD_MSG_TYPE, json!({ "next": "did:example:bob" })))
+            .expect("from_value is ok.");
+
+        Message::from_value(_msg(
+            FORWARD_MSG_TYPE,
+            json!({ "next": "did:example:bob#key-x25519-1" }),
+        ))
+        .expect("from_value is ok.");
+
+        _validate_body_works_forward_invalid(json!({}), "`next` is required");
+        _validate_body_works_forward_invalid(json!({ "next": 1 }), "`next` must be a string");
+
+        _validate_body_works_forward_invalid(
+            json!({ "next": "bob" }),
+            "`next` must be a DID or DID URL",
+        );
+
+        fn _validate_body_works_forward_invalid(body: Value, reason: &str) {
+            let err = Message::from_value(_msg(FORWARD_MSG_TYPE, body)).expect_err("res is ok");
+
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+
+            assert_eq!(
+                format!("{}", err),
+                format!(
+                    "Malformed: Invalid body of `{}` message: {}",
+                    FORWARD_MSG_TYPE, reason
+                )
+            );
+        }
+    }
+
+    fn _msg(type_: &str, body: Value) -> Value {
+        json!({
+            "id": "1234567890",
+            "typ": "application/didcomm-plain+json",
+            "type": type_,
+            "body": body,
+        })
+    }
+}
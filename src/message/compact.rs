@@ -0,0 +1,141 @@
+use crate::{
+    error::{ErrorKind, Result, ResultExt, ToResult},
+    utils::json::to_canonical_string,
+    Message,
+};
+
+impl Message {
+    /// Serializes the message to compact deterministic form intended for storage:
+    /// JSON with object keys (including keys of body, attachments and custom headers)
+    /// sorted lexicographically and without whitespaces. The same message always produces
+    /// the same bytes, so they can be hashed or deduplicated.
+    ///
+    /// It isn't a wire format, use `pack_plaintext` to send plaintext messages.
+    pub fn to_compact_bytes(&self) -> Result<Vec<u8>> {
+        let value =
+            serde_json::to_value(self).kind(ErrorKind::InvalidState, "Unable serialize message")?;
+
+        Ok(to_canonical_string(&value)?.into_bytes())
+    }
+
+    /// Restores the message stored with `to_compact_bytes`.
+    ///
+    /// # Errors
+    /// - `Malformed` bytes aren't a serialized message.
+    pub fn from_compact_bytes(bytes: &[u8]) -> Result<Message> {
+        serde_json::from_slice(bytes).to_didcomm("Unable deserialize compact message")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        error::ErrorKind,
+        test_vectors::{
+            MESSAGE_ATTACHMENT_BASE64, MESSAGE_ATTACHMENT_JSON, MESSAGE_ATTACHMENT_LINKS,
+            MESSAGE_ATTACHMENT_MULTI_1, MESSAGE_ATTACHMENT_MULTI_2, MESSAGE_FROM_PRIOR_FULL,
+            MESSAGE_MINIMAL, MESSAGE_SIMPLE,
+        },
+        Message,
+    };
+
+    #[test]
+    fn to_compact_bytes_works() {
+        let bytes = MESSAGE_MINIMAL
+            .to_compact_bytes()
+            .expect("to_compact_bytes is ok.");
+
+        assert_eq!(
+            String::from_utf8(bytes).expect("bytes are utf8."),
+            r#"{"body":{},"id":"1234567890","typ":"application/didcomm-plain+json","type":"http://example.com/protocols/lets_do_lunch/1.0/proposal"}"#
+        );
+
+        let mut msg = Message {
+            body: json!({"z": 1, "a": {"y": [2, 1], "b": null}}),
+            created_time: Some(1516269022),
+            ..MESSAGE_MINIMAL.clone()
+        };
+
+        msg.extra_headers.insert("z-header".into(), json!("z"));
+
+        msg.extra_headers
+            .insert("a-header".into(), json!({"y": true, "x": false}));
+
+        let bytes = msg.to_compact_bytes().expect("to_compact_bytes is ok.");
+
+        assert_eq!(
+            String::from_utf8(bytes).expect("bytes are utf8."),
+            r#"{"a-header":{"x":false,"y":true},"body":{"a":{"b":null,"y":[2,1]},"z":1},"created_time":1516269022,"id":"1234567890","typ":"application/didcomm-plain+json","type":"http://example.com/protocols/lets_do_lunch/1.0/proposal","z-header":"z"}"#
+        );
+    }
+
+    #[test]
+    fn to_compact_bytes_works_stable() {
+        // Order of custom headers insertion doesn't matter
+        let mut msg1 = MESSAGE_MINIMAL.clone();
+        let mut msg2 = MESSAGE_MINIMAL.clone();
+
+        for (key, value) in [("a", json!(1)), ("b", json!(2)), ("c", json!(3))] {
+            msg1.extra_headers.insert(key.into(), value);
+        }
+
+        for (key, value) in [("c", json!(3)), ("a", json!(1)), ("b", json!(2))] {
+            msg2.extra_headers.insert(key.into(), value);
+        }
+
+        assert_eq!(
+            msg1.to_compact_bytes().expect("to_compact_bytes is ok."),
+            msg2.to_compact_bytes().expect("to_compact_bytes is ok.")
+        );
+
+        // Restored message produces the same bytes
+        let bytes = MESSAGE_ATTACHMENT_MULTI_1
+            .to_compact_bytes()
+            .expect("to_compact_bytes is ok.");
+
+        let restored = Message::from_compact_bytes(&bytes).expect("from_compact_bytes is ok.");
+
+        assert_eq!(
+            restored
+                .to_compact_bytes()
+                .expect("to_compact_bytes is ok."),
+            bytes
+        );
+    }
+
+    #[test]
+    fn from_compact_bytes_works() {
+        let mut with_headers = MESSAGE_SIMPLE.clone();
+
+        with_headers
+            .extra_headers
+            .insert("example-header".into(), json!({"nested": ["value", null]}));
+
+        for msg in [
+            &*MESSAGE_SIMPLE,
+            &*MESSAGE_MINIMAL,
+            &*MESSAGE_FROM_PRIOR_FULL,
+            &*MESSAGE_ATTACHMENT_BASE64,
+            &*MESSAGE_ATTACHMENT_JSON,
+            &*MESSAGE_ATTACHMENT_LINKS,
+            &*MESSAGE_ATTACHMENT_MULTI_1,
+            &*MESSAGE_ATTACHMENT_MULTI_2,
+            &with_headers,
+        ] {
+            let bytes = msg.to_compact_bytes().expect("to_compact_bytes is ok.");
+            let restored = Message::from_compact_bytes(&bytes).expect("from_compact_bytes is ok.");
+            assert_eq!(&restored, msg);
+        }
+    }
+
+    #[test]
+    fn from_compact_bytes_works_malformed() {
+        let err = Message::from_compact_bytes(b"{\"id\":\"1234567890\"}").expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        let err = Message::from_compact_bytes(&[0xff, 0xfe]).expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+    }
+}
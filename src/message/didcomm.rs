@@ -0,0 +1,182 @@
+use crate::{
+    did::DIDResolver, error::Result, secrets::SecretsResolver, Message, PackEncryptedMetadata,
+    PackEncryptedOptions, PackSignedMetadata, UnpackMetadata, UnpackOptions,
+};
+
+/// Facade capturing DID and secrets resolvers for applications that use one resolver pair
+/// throughout. Methods delegate to the corresponding `Message` methods, see them for details.
+pub struct DIDCommClient<'dr, 'sr> {
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+}
+
+impl<'dr, 'sr> DIDCommClient<'dr, 'sr> {
+    /// Creates facade using given resolvers for all operations.
+    ///
+    /// # Params
+    /// - `did_resolver` instance of `DIDResolver` to resolve DIDs.
+    /// - `secrets_resolver` instance of `SecretsResolver` to resolve secrets.
+    pub fn new(
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+    ) -> Self {
+        DIDCommClient {
+            did_resolver,
+            secrets_resolver,
+        }
+    }
+
+    /// Produces `DIDComm Encrypted Message`, see `Message::pack_encrypted`.
+    pub async fn pack_encrypted(
+        &self,
+        msg: &Message,
+        to: &str,
+        from: Option<&str>,
+        sign_by: Option<&str>,
+        options: &PackEncryptedOptions,
+    ) -> Result<(String, PackEncryptedMetadata)> {
+        msg.pack_encrypted(
+            to,
+            from,
+            sign_by,
+            self.did_resolver,
+            self.secrets_resolver,
+            options,
+        )
+        .await
+    }
+
+    /// Produces `DIDComm Signed Message`, see `Message::pack_signed`.
+    pub async fn pack_signed(
+        &self,
+        msg: &Message,
+        sign_by: &str,
+    ) -> Result<(String, PackSignedMetadata)> {
+        msg.pack_signed(sign_by, self.did_resolver, self.secrets_resolver)
+            .await
+    }
+
+    /// Produces `DIDComm Plaintext Messages`, see `Message::pack_plaintext`.
+    pub async fn pack_plaintext(&self, msg: &Message) -> Result<String> {
+        msg.pack_plaintext(self.did_resolver).await
+    }
+
+    /// Unpacks the packed DIDComm message, see `Message::unpack`.
+    pub async fn unpack(
+        &self,
+        msg: &str,
+        options: &UnpackOptions,
+    ) -> Result<(Message, UnpackMetadata)> {
+        Message::unpack(msg, self.did_resolver, self.secrets_resolver, options).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        did::resolvers::ExampleDIDResolver,
+        secrets::resolvers::ExampleSecretsResolver,
+        test_vectors::{
+            ALICE_DID, ALICE_DID_DOC, ALICE_SECRETS, BOB_DID, BOB_DID_DOC, BOB_SECRETS,
+            MESSAGE_SIMPLE, PLAINTEXT_MSG_SIMPLE, SIGNED_MSG_ALICE_KEY_1,
+        },
+        PackEncryptedOptions, UnpackOptions,
+    };
+
+    use super::DIDCommClient;
+
+    #[tokio::test]
+    async fn didcomm_pack_encrypted_works() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let alice = DIDCommClient::new(&did_resolver, &alice_secrets_resolver);
+        let bob = DIDCommClient::new(&did_resolver, &bob_secrets_resolver);
+
+        let (packed, metadata) = alice
+            .pack_encrypted(
+                &MESSAGE_SIMPLE,
+                BOB_DID,
+                Some(ALICE_DID),
+                Some(ALICE_DID),
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("pack is ok.");
+
+        assert!(metadata.from_kid.is_some());
+        assert!(metadata.sign_by_kid.is_some());
+
+        let (msg, metadata) = bob
+            .unpack(&packed, &UnpackOptions::default())
+            .await
+            .expect("unpack is ok.");
+
+        assert_eq!(&msg, &*MESSAGE_SIMPLE);
+        assert!(metadata.encrypted);
+        assert!(metadata.authenticated);
+        assert!(metadata.non_repudiation);
+    }
+
+    #[tokio::test]
+    async fn didcomm_pack_signed_works() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let didcomm = DIDCommClient::new(&did_resolver, &secrets_resolver);
+
+        let (packed, metadata) = didcomm
+            .pack_signed(&MESSAGE_SIMPLE, "did:example:alice#key-1")
+            .await
+            .expect("pack is ok.");
+
+        assert_eq!(metadata.sign_by_kid, "did:example:alice#key-1");
+
+        let (msg, metadata) = didcomm
+            .unpack(&packed, &UnpackOptions::default())
+            .await
+            .expect("unpack is ok.");
+
+        assert_eq!(&msg, &*MESSAGE_SIMPLE);
+        assert!(metadata.non_repudiation);
+
+        let (msg, _) = didcomm
+            .unpack(SIGNED_MSG_ALICE_KEY_1, &UnpackOptions::default())
+            .await
+            .expect("unpack is ok.");
+
+        assert_eq!(&msg, &*MESSAGE_SIMPLE);
+    }
+
+    #[tokio::test]
+    async fn didcomm_pack_plaintext_works() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+        let didcomm = DIDCommClient::new(&did_resolver, &secrets_resolver);
+
+        let packed = didcomm
+            .pack_plaintext(&MESSAGE_SIMPLE)
+            .await
+            .expect("pack is ok.");
+
+        let (msg, metadata) = didcomm
+            .unpack(&packed, &UnpackOptions::default())
+            .await
+            .expect("unpack is ok.");
+
+        assert_eq!(&msg, &*MESSAGE_SIMPLE);
+        assert!(!metadata.encrypted);
+
+        let (msg, _) = didcomm
+            .unpack(PLAINTEXT_MSG_SIMPLE, &UnpackOptions::default())
+            .await
+            .expect("unpack is ok.");
+
+        assert_eq!(&msg, &*MESSAGE_SIMPLE);
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::BTreeSet;
+
+use serde_json::{Map, Value};
+
+use crate::Message;
+
+/// Difference of a top-level field of two messages (see `Message::diff`).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FieldDiff {
+    /// Name of the field as in plaintext message JSON (for ex. `type` or `created_time`).
+    /// Custom headers are reported by their names too.
+    pub field: String,
+
+    /// Value of the field in `self` message. None if the field is absent.
+    pub left: Option<Value>,
+
+    /// Value of the field in `other` message. None if the field is absent.
+    pub right: Option<Value>,
+}
+
+impl Message {
+    /// Compares messages ignoring the given volatile fields (for ex. `created_time` or `id`
+    /// of messages built independently). Field names are the same as in plaintext message JSON,
+    /// all other fields including type, body, attachments, threading and custom headers
+    /// must be equal.
+    pub fn semantic_eq(&self, other: &Message, volatile: &[&str]) -> bool {
+        self.diff(other)
+            .iter()
+            .all(|diff| volatile.contains(&diff.field.as_str()))
+    }
+
+    /// Lists top-level fields of the messages with different values in order of field names.
+    /// Empty if the messages are equal. Intended for debugging and tests.
+    pub fn diff(&self, other: &Message) -> Vec<FieldDiff> {
+        let left = self._fields();
+        let right = other._fields();
+
+        left.keys()
+            .chain(right.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter(|&field| left.get(field) != right.get(field))
+            .map(|field| FieldDiff {
+                field: field.to_owned(),
+                left: left.get(field).cloned(),
+                right: right.get(field).cloned(),
+            })
+            .collect()
+    }
+
+    fn _fields(&self) -> Map<String, Value> {
+        // Message is always serialized as JSON object
+        match serde_json::to_value(self) {
+            Ok(Value::Object(fields)) => fields,
+            _ => Map::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::FieldDiff;
+    use crate::{test_vectors::MESSAGE_SIMPLE, Message};
+
+    #[test]
+    fn diff_works() {
+        let other = Message {
+            created_time: Some(MESSAGE_SIMPLE.created_time.unwrap() + 1),
+            ..MESSAGE_SIMPLE.clone()
+        };
+
+        assert_eq!(MESSAGE_SIMPLE.diff(&MESSAGE_SIMPLE), vec![]);
+
+        assert_eq!(
+            MESSAGE_SIMPLE.diff(&other),
+            vec![FieldDiff {
+                field: "created_time".into(),
+                left: Some(json!(MESSAGE_SIMPLE.created_time.unwrap())),
+                right: Some(json!(MESSAGE_SIMPLE.created_time.unwrap() + 1)),
+            }]
+        );
+
+        let mut other = Message {
+            created_time: None,
+            body: json!({"messagespecificattribute": "other value"}),
+            ..MESSAGE_SIMPLE.clone()
+        };
+
+        other
+            .extra_headers
+            .insert("example-header".into(), json!("example-value"));
+
+        assert_eq!(
+            MESSAGE_SIMPLE.diff(&other),
+            vec![
+                FieldDiff {
+                    field: "body".into(),
+                    left: Some(MESSAGE_SIMPLE.body.clone()),
+                    right: Some(json!({"messagespecificattribute": "other value"})),
+                },
+                FieldDiff {
+                    field: "created_time".into(),
+                    left: Some(json!(MESSAGE_SIMPLE.created_time.unwrap())),
+                    right: None,
+                },
+                FieldDiff {
+                    field: "example-header".into(),
+                    left: None,
+                    right: Some(json!("example-value")),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn semantic_eq_works() {
+        let other = Message {
+            created_time: Some(MESSAGE_SIMPLE.created_time.unwrap() + 1),
+            ..MESSAGE_SIMPLE.clone()
+        };
+
+        assert!(MESSAGE_SIMPLE.semantic_eq(&MESSAGE_SIMPLE, &[]));
+        assert!(MESSAGE_SIMPLE.semantic_eq(&other, &["created_time"]));
+        assert!(MESSAGE_SIMPLE.semantic_eq(&other, &["id", "created_time"]));
+        assert!(!MESSAGE_SIMPLE.semantic_eq(&other, &[]));
+        assert!(!MESSAGE_SIMPLE.semantic_eq(&other, &["expires_time"]));
+
+        let other = Message {
+            thid: Some("other thread".into()),
+            ..other
+        };
+
+        assert!(!MESSAGE_SIMPLE.semantic_eq(&other, &["created_time"]));
+    }
+}
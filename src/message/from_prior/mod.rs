@@ -25,6 +25,19 @@ pub struct FromPrior {
     pub jti: Option<String>,
 }
 
+/// Describes `from_prior` header to be added to the message on packing
+/// after the sender rotated its DID (see `PackEncryptedOptions::from_prior`).
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+pub struct FromPriorSpec {
+    /// Prior DID of the sender (`iss` of `from_prior`). The new DID (`sub` of `from_prior`)
+    /// is the message `from` value.
+    pub iss: String,
+
+    /// Identifier (DID URL) of prior DID `authentication` key to sign `from_prior` with.
+    /// If not set, the first prior DID `authentication` key the secret is known for is used.
+    pub issuer_kid: Option<String>,
+}
+
 const JWT_TYP: &str = "JWT";
 
 impl FromPrior {
@@ -1,13 +1,11 @@
 use crate::{
+    algorithms::AlgorithmRegistry,
     did::DIDResolver,
     error::{err_msg, ErrorKind, Result, ResultContext, ResultExt},
-    jws::{self, Algorithm},
+    jws,
     message::from_prior::JWT_TYP,
     secrets::SecretsResolver,
-    utils::{
-        crypto::{AsKnownKeyPair, KnownKeyPair},
-        did::{did_or_url, is_did},
-    },
+    utils::did::{did_or_url, is_did},
     FromPrior,
 };
 
@@ -17,12 +15,69 @@ impl FromPrior {
         issuer_kid: Option<&str>,
         did_resolver: &'dr (dyn DIDResolver + 'dr),
         secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+    ) -> Result<(String, String)> {
+        self._pack(
+            issuer_kid,
+            did_resolver,
+            secrets_resolver,
+            &AlgorithmRegistry::default(),
+        )
+        .await
+    }
+
+    /// Same as `pack`, but signature algorithms registered in `algorithms`
+    /// are consulted before built-in ones.
+    pub(crate) async fn _pack<'dr, 'sr>(
+        &self,
+        issuer_kid: Option<&str>,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        algorithms: &AlgorithmRegistry,
     ) -> Result<(String, String)> {
         self.validate_pack(issuer_kid)?;
 
         let from_prior_str = serde_json::to_string(self)
             .kind(ErrorKind::InvalidState, "Unable serialize message")?;
 
+        let kid = self
+            .issuer_kid(issuer_kid, did_resolver, Some(secrets_resolver))
+            .await?;
+
+        let secret = secrets_resolver
+            .get_secret(&kid)
+            .await
+            .context("Unable to find secret")?
+            .ok_or_else(|| {
+                err_msg(
+                    ErrorKind::SecretNotFound,
+                    "from_prior issuer secret not found",
+                )
+            })?;
+
+        let algorithm = algorithms
+            .signature_algorithm_for_secret(&secret)
+            .context("Unable to instantiate from_prior issuer key")?;
+
+        let from_prior_jwt = jws::sign_compact_with_algorithm(
+            from_prior_str.as_bytes(),
+            (kid.as_str(), &secret),
+            JWT_TYP,
+            algorithm.as_ref(),
+        )
+        .context("Unable to produce signature")?;
+
+        Ok((from_prior_jwt, kid))
+    }
+
+    /// Finds the first issuer `authentication` key the secret is known for.
+    /// If `secrets_resolver` isn't provided (see `Message::pack_encrypted_plan`)
+    /// the first issuer `authentication` key is returned.
+    pub(crate) async fn issuer_kid<'dr, 'sr>(
+        &self,
+        issuer_kid: Option<&str>,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: Option<&'sr (dyn SecretsResolver + 'sr)>,
+    ) -> Result<String> {
         let did_doc = did_resolver
             .resolve(&self.iss)
             .await
@@ -64,57 +119,22 @@ impl FromPrior {
             did_doc.authentications.iter().map(|s| s.as_str()).collect()
         };
 
-        let kid = *secrets_resolver
-            .find_secrets(&authentication_kids)
-            .await
-            .context("Unable to find secrets")?
-            .get(0)
-            .ok_or_else(|| {
-                err_msg(
-                    ErrorKind::SecretNotFound,
-                    "No from_prior issuer secrets found",
-                )
-            })?;
-
-        let secret = secrets_resolver
-            .get_secret(kid)
-            .await
-            .context("Unable to find secret")?
-            .ok_or_else(|| {
-                err_msg(
-                    ErrorKind::SecretNotFound,
-                    "from_prior issuer secret not found",
-                )
-            })?;
-
-        let sign_key = secret
-            .as_key_pair()
-            .context("Unable to instantiate from_prior issuer key")?;
+        let authentication_kids = match secrets_resolver {
+            Some(secrets_resolver) => secrets_resolver
+                .find_secrets(&authentication_kids)
+                .await
+                .context("Unable to find secrets")?,
+            None => authentication_kids,
+        };
 
-        let from_prior_jwt = match sign_key {
-            KnownKeyPair::Ed25519(ref key) => jws::sign_compact(
-                from_prior_str.as_bytes(),
-                (kid, key),
-                JWT_TYP,
-                Algorithm::EdDSA,
-            ),
-            KnownKeyPair::P256(ref key) => jws::sign_compact(
-                from_prior_str.as_bytes(),
-                (kid, key),
-                JWT_TYP,
-                Algorithm::Es256,
-            ),
-            KnownKeyPair::K256(ref key) => jws::sign_compact(
-                from_prior_str.as_bytes(),
-                (kid, key),
-                JWT_TYP,
-                Algorithm::Es256K,
-            ),
-            _ => Err(err_msg(ErrorKind::Unsupported, "Unsupported signature alg"))?,
-        }
-        .context("Unable to produce signature")?;
+        let kid = *authentication_kids.get(0).ok_or_else(|| {
+            err_msg(
+                ErrorKind::SecretNotFound,
+                "No from_prior issuer secrets found",
+            )
+        })?;
 
-        Ok((from_prior_jwt, String::from(kid)))
+        Ok(kid.to_owned())
     }
 
     pub(crate) fn validate_pack(&self, issuer_kid: Option<&str>) -> Result<()> {
@@ -1,16 +1,29 @@
 use crate::{
-    did::DIDResolver,
+    algorithms::AlgorithmRegistry,
+    did::{
+        did_resolver::{find_verification_method, resolve_key_directly},
+        DIDResolver, Purpose, VerificationMethod,
+    },
     error::{err_msg, ErrorKind, Result, ResultContext, ResultExt},
     jws,
-    utils::{crypto::AsKnownKeyPair, did::did_or_url},
+    utils::did::did_or_url,
     FromPrior,
 };
-use askar_crypto::alg::{ed25519::Ed25519KeyPair, k256::K256KeyPair, p256::P256KeyPair};
 
 impl FromPrior {
     pub async fn unpack<'dr>(
         from_prior_jwt: &str,
         did_resolver: &'dr (dyn DIDResolver + 'dr),
+    ) -> Result<(FromPrior, String)> {
+        Self::_unpack(from_prior_jwt, did_resolver, &AlgorithmRegistry::default()).await
+    }
+
+    /// Same as `unpack`, but signature algorithms registered in `algorithms`
+    /// are consulted before built-in ones.
+    pub(crate) async fn _unpack<'dr>(
+        from_prior_jwt: &str,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        algorithms: &AlgorithmRegistry,
     ) -> Result<(FromPrior, String)> {
         let mut buf = vec![];
         let parsed = jws::parse_compact(from_prior_jwt, &mut buf)?;
@@ -35,74 +48,26 @@ impl FromPrior {
             ))?
         }
 
-        let did_doc = did_resolver
-            .resolve(did)
+        let key = match resolve_key_directly(kid, Purpose::Authentication, did_resolver)
             .await
-            .context("Unable to resolve from_prior issuer DID")?
-            .ok_or_else(|| {
-                err_msg(
-                    ErrorKind::DIDNotResolved,
-                    "from_prior issuer DIDDoc not found",
-                )
-            })?;
-
-        let kid = did_doc
-            .authentications
-            .iter()
-            .find(|&k| k.as_str() == kid)
-            .ok_or_else(|| {
-                err_msg(
-                    ErrorKind::DIDUrlNotFound,
-                    "from_prior issuer kid not found in DIDDoc",
-                )
-            })?
-            .as_str();
-
-        let key = did_doc
-            .verification_methods
-            .iter()
-            .find(|&vm| &vm.id == kid)
-            .ok_or_else(|| {
-                err_msg(
-                    ErrorKind::DIDUrlNotFound,
-                    "from_prior issuer verification method not found in DIDDoc",
-                )
-            })?;
-
-        let valid = match alg {
-            jws::Algorithm::EdDSA => {
-                let key = key
-                    .as_ed25519()
-                    .context("Unable to instantiate from_prior issuer key")?;
-
-                parsed
-                    .verify::<Ed25519KeyPair>(&key)
-                    .context("Unable to verify from_prior signature")?
-            }
-            jws::Algorithm::Es256 => {
-                let key = key
-                    .as_p256()
-                    .context("Unable to instantiate from_prior issuer key")?;
-
-                parsed
-                    .verify::<P256KeyPair>(&key)
-                    .context("Unable to verify from_prior signature")?
-            }
-            jws::Algorithm::Es256K => {
-                let key = key
-                    .as_k256()
-                    .context("Unable to instantiate from_prior issuer key")?;
-
-                parsed
-                    .verify::<K256KeyPair>(&key)
-                    .context("Unable to verify from_prior signature")?
-            }
-            jws::Algorithm::Other(_) => Err(err_msg(
-                ErrorKind::Unsupported,
-                "Unsupported signature algorithm",
-            ))?,
+            .context("Unable to resolve from_prior issuer key")?
+        {
+            Some(key) => key,
+            None => _issuer_key(did, kid, did_resolver).await?,
         };
 
+        let algorithm = algorithms.signature_algorithm(&alg.to_string())?;
+
+        let signature = base64::decode_config(parsed.signature, base64::URL_SAFE_NO_PAD)
+            .kind(ErrorKind::Malformed, "Unable decode signature")
+            .context("Unable to verify from_prior signature")?;
+
+        let sign_input = format!("{}.{}", parsed.header, parsed.payload);
+
+        let valid = algorithm
+            .verify(&key, sign_input.as_bytes(), &signature)
+            .context("Unable to verify from_prior signature")?;
+
         if !valid {
             Err(err_msg(ErrorKind::Malformed, "Wrong from_prior signature"))?
         }
@@ -124,6 +89,46 @@ impl FromPrior {
     }
 }
 
+/// Resolves issuer DID Doc and looks for the issuer `authentication` key in it.
+async fn _issuer_key<'dr>(
+    did: &str,
+    kid: &str,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+) -> Result<VerificationMethod> {
+    let did_doc = did_resolver
+        .resolve(did)
+        .await
+        .context("Unable to resolve from_prior issuer DID")?
+        .ok_or_else(|| {
+            err_msg(
+                ErrorKind::DIDNotResolved,
+                "from_prior issuer DIDDoc not found",
+            )
+        })?;
+
+    let kid = did_doc
+        .authentications
+        .iter()
+        .find(|&k| k.as_str() == kid)
+        .ok_or_else(|| {
+            err_msg(
+                ErrorKind::DIDUrlNotFound,
+                "from_prior issuer kid not found in DIDDoc",
+            )
+        })?
+        .as_str();
+
+    find_verification_method(&did_doc, kid, did_resolver)
+        .await
+        .context("Unable to resolve from_prior issuer key")?
+        .ok_or_else(|| {
+            err_msg(
+                ErrorKind::DIDUrlNotFound,
+                "from_prior issuer verification method not found in DIDDoc",
+            )
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
@@ -0,0 +1,197 @@
+//! Interop tests against vectors shared with other DIDComm implementations (see `test_vectors::interop`).
+//!
+//! Unpacking is checked for identical plaintext and metadata. Packing is checked by re-packing
+//! unpacked plaintext with the same parameters and comparing deterministic fields only.
+//! The following fields are skipped as nondeterministic:
+//! - JWE `epk` (ephemeral key is generated for each message)
+//! - JWE `iv`, `ciphertext`, `tag` and recipients `encrypted_key` (depend on random CEK and IV)
+//! - JWS `signature` (depends on exact payload serialization and ECDSA nonces);
+//!   JWS `payload` is compared as decoded JSON for the same reason.
+
+use serde_json::{Map, Value};
+
+use crate::{
+    did::resolvers::ExampleDIDResolver,
+    secrets::resolvers::ExampleSecretsResolver,
+    test_vectors::{interop_did_docs, interop_secrets, interop_vectors, InteropVector},
+    Message, PackEncryptedOptions, UnpackMetadata, UnpackOptions,
+};
+
+#[tokio::test]
+async fn interop_unpack_works() {
+    for vector in interop_vectors() {
+        let (msg, metadata) = _unpack(&vector).await;
+
+        assert_eq!(
+            serde_json::to_value(&msg).unwrap(),
+            vector.expected_plaintext,
+            "Unexpected plaintext for vector `{}`",
+            vector.name
+        );
+
+        let metadata = serde_json::to_value(&metadata).unwrap();
+
+        for (field, exp_value) in _object(&vector.expected_metadata) {
+            assert_eq!(
+                metadata.get(field),
+                Some(exp_value),
+                "Unexpected metadata field `{}` for vector `{}`",
+                field,
+                vector.name
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn interop_pack_works() {
+    let did_resolver = ExampleDIDResolver::new(interop_did_docs());
+
+    for vector in interop_vectors() {
+        let (msg, metadata) = _unpack(&vector).await;
+
+        let packed = if metadata.encrypted {
+            let to_kids = metadata
+                .encrypted_to_kids
+                .as_ref()
+                .expect("Encrypted message without recipient kids");
+
+            let to_kids: Vec<_> = to_kids.iter().map(|kid| kid.as_str()).collect();
+            let from = metadata.encrypted_from_kid.as_deref();
+
+            let sender_secrets = from
+                .map(|from| interop_secrets(_party(from)))
+                .unwrap_or_default();
+
+            let sender_secrets = match metadata.sign_from {
+                Some(ref sign_from) => {
+                    let mut secrets = sender_secrets;
+                    secrets.extend(interop_secrets(_party(sign_from)));
+                    secrets
+                }
+                None => sender_secrets,
+            };
+
+            let options = PackEncryptedOptions {
+                forward: false,
+                protect_sender: metadata.anonymous_sender && from.is_some(),
+                enc_alg_auth: metadata.enc_alg_auth.clone().unwrap_or_default(),
+                enc_alg_anon: metadata.enc_alg_anon.clone().unwrap_or_default(),
+                ..PackEncryptedOptions::default()
+            };
+
+            let (packed, _) = msg
+                .pack_encrypted_to_kids(
+                    &to_kids,
+                    from,
+                    metadata.sign_from.as_deref(),
+                    &did_resolver,
+                    &ExampleSecretsResolver::new(sender_secrets),
+                    &options,
+                )
+                .await
+                .unwrap_or_else(|e| panic!("Unable pack vector `{}`: {}", vector.name, e));
+
+            packed
+        } else if let Some(ref sign_from) = metadata.sign_from {
+            let (packed, _) = msg
+                .pack_signed(
+                    sign_from,
+                    &did_resolver,
+                    &ExampleSecretsResolver::new(interop_secrets(_party(sign_from))),
+                )
+                .await
+                .unwrap_or_else(|e| panic!("Unable pack vector `{}`: {}", vector.name, e));
+
+            packed
+        } else {
+            msg.pack_plaintext(&did_resolver)
+                .await
+                .unwrap_or_else(|e| panic!("Unable pack vector `{}`: {}", vector.name, e))
+        };
+
+        assert_eq!(
+            _deterministic_fields(&packed),
+            _deterministic_fields(&vector.packed()),
+            "Packed message doesn't match vector `{}`",
+            vector.name
+        );
+    }
+}
+
+async fn _unpack(vector: &InteropVector) -> (Message, UnpackMetadata) {
+    let did_resolver = ExampleDIDResolver::new(interop_did_docs());
+    let secrets_resolver = ExampleSecretsResolver::new(interop_secrets(&vector.recipient));
+
+    Message::unpack(
+        &vector.packed(),
+        &did_resolver,
+        &secrets_resolver,
+        &UnpackOptions::default(),
+    )
+    .await
+    .unwrap_or_else(|e| panic!("Unable unpack vector `{}`: {}", vector.name, e))
+}
+
+/// Party name (for ex. `alice`) of the given DID URL (for ex. `did:example:alice#key-1`).
+fn _party(kid: &str) -> &str {
+    kid.split(&[':', '#'][..])
+        .nth(2)
+        .expect("Unexpected kid format")
+}
+
+/// Drops nondeterministic fields of packed message and decodes base64 encoded JSON fields.
+fn _deterministic_fields(packed: &str) -> Value {
+    let packed: Value = serde_json::from_str(packed).expect("Unable parse packed message");
+    let packed = _object(&packed);
+
+    if let Some(recipients) = packed.get("recipients") {
+        let mut protected = _decode_json(&packed["protected"]);
+
+        protected
+            .as_object_mut()
+            .expect("Protected header is not an object")
+            .remove("epk");
+
+        let kids: Vec<_> = recipients
+            .as_array()
+            .expect("Recipients is not an array")
+            .iter()
+            .map(|r| r["header"]["kid"].clone())
+            .collect();
+
+        serde_json::json!({ "protected": protected, "kids": kids })
+    } else if let Some(signatures) = packed.get("signatures") {
+        let signatures: Vec<_> = signatures
+            .as_array()
+            .expect("Signatures is not an array")
+            .iter()
+            .map(|s| {
+                serde_json::json!({
+                    "protected": _decode_json(&s["protected"]),
+                    "header": s["header"],
+                })
+            })
+            .collect();
+
+        serde_json::json!({
+            "payload": _decode_json(&packed["payload"]),
+            "signatures": signatures,
+        })
+    } else {
+        Value::Object(packed.clone())
+    }
+}
+
+fn _decode_json(value: &Value) -> Value {
+    let value = value.as_str().expect("Base64 field is not a string");
+
+    let value =
+        base64::decode_config(value, base64::URL_SAFE_NO_PAD).expect("Unable decode base64 field");
+
+    serde_json::from_slice(&value).expect("Unable parse base64 field as JSON")
+}
+
+fn _object(value: &Value) -> &Map<String, Value> {
+    value.as_object().expect("Value is not an object")
+}
@@ -1,9 +1,13 @@
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::collections::HashMap;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
 
-use super::Attachment;
-use crate::error::{err_msg, ErrorKind, Result, ToResult};
+use super::{attachment::_unique_content_ids, Attachment, AttachmentData, BodyValidators};
+use crate::{
+    error::{err_msg, Error, ErrorKind, Result, ResultExt, ToResult},
+    protocols::threading::_thread_id,
+    utils::did::is_did,
+};
 
 /// Wrapper for plain message. Provides helpers for message building and packing/unpacking.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -46,6 +50,7 @@ pub struct Message {
     pub pthid: Option<String>,
 
     /// Custom message headers.
+    /// Unknown top-level fields of unpacked message are preserved here.
     #[serde(flatten)]
     #[serde(skip_serializing_if = "HashMap::is_empty")]
     pub extra_headers: HashMap<String, Value>,
@@ -55,6 +60,7 @@ pub struct Message {
     /// UTC Epoch Seconds (seconds since 1970-01-01T00:00:00Z UTC).
     /// This attribute is informative to the recipient, and may be relied on by protocols.
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, deserialize_with = "crate::utils::serde::_optional_u64")]
     pub created_time: Option<u64>,
 
     /// The expires_time attribute is used for the sender to express when they consider
@@ -62,6 +68,7 @@ pub struct Message {
     /// This attribute signals when the message is considered no longer valid by the sender.
     /// When omitted, the message is considered to have no expiration by the sender.
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, deserialize_with = "crate::utils::serde::_optional_u64")]
     pub expires_time: Option<u64>,
 
     /// from_prior is a compactly serialized signed JWT containing FromPrior value
@@ -73,32 +80,261 @@ pub struct Message {
     pub attachments: Option<Vec<Attachment>>,
 }
 
-const PLAINTEXT_TYP: &str = "application/didcomm-plain+json";
+pub(crate) const PLAINTEXT_TYP: &str = "application/didcomm-plain+json";
+
+const RESERVED_HEADERS: [&str; 12] = [
+    "id",
+    "typ",
+    "type",
+    "body",
+    "from",
+    "to",
+    "thid",
+    "pthid",
+    "created_time",
+    "expires_time",
+    "from_prior",
+    "attachments",
+];
+
+/// Values of `return_route` header that asks the recipient to send responses
+/// back using the transport connection the message was received through.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReturnRoute {
+    /// Responses must not be returned over the inbound connection
+    None,
+
+    /// Responses to any message must be returned over the inbound connection
+    All,
+
+    /// Only responses to messages of the same thread must be returned over the inbound connection
+    Thread,
+}
+
+impl ReturnRoute {
+    /// Whether the response (for ex. queued for the sender) can be returned over the connection
+    /// the inbound message with this `return_route` was received through.
+    /// For `Thread` the response must belong to the thread of the inbound message
+    /// (`thid` or `id` if `thid` isn't set).
+    pub fn allows_response(self, inbound: &Message, response: &Message) -> bool {
+        match self {
+            ReturnRoute::None => false,
+            ReturnRoute::All => true,
+            ReturnRoute::Thread => _thread_id(response) == _thread_id(inbound),
+        }
+    }
+}
+
+const RETURN_ROUTE_HEADER: &str = "return_route";
+
+/// Header requesting acknowledgements (https://identity.foundation/didcomm-messaging/spec/#acks)
+const PLEASE_ACK_HEADER: &str = "please_ack";
+
+/// Header declaring language of human readable message content
+/// (https://identity.foundation/didcomm-messaging/spec/#internationalization-i18n)
+const LANG_HEADER: &str = "lang";
 
 impl Message {
     pub fn build(id: String, type_: String, body: Value) -> MessageBuilder {
         MessageBuilder::new(id, type_, body)
     }
 
+    /// Value of `return_route` header. None if the header is absent or has unknown value.
+    pub fn return_route(&self) -> Option<ReturnRoute> {
+        self.extra_headers
+            .get(RETURN_ROUTE_HEADER)
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+    }
+
+    /// Value of `please_ack` header: list of events (for ex. `receipt`) the sender
+    /// requests acknowledgements on. None if the header is absent or isn't a list of strings.
+    pub fn please_ack(&self) -> Option<Vec<String>> {
+        self.extra_headers
+            .get(PLEASE_ACK_HEADER)
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+    }
+
+    /// Value of `lang` header: language of human readable message content (for ex. `en`).
+    /// None if the header is absent or isn't a string.
+    pub fn lang(&self) -> Option<&str> {
+        self.extra_headers.get(LANG_HEADER).and_then(Value::as_str)
+    }
+
+    /// Recipients of the message (`to` header). Empty if the header is absent.
+    pub fn recipients(&self) -> &[String] {
+        self.to.as_deref().unwrap_or(&[])
+    }
+
+    /// Attachments with the given media type (for ex. `application/json` or `image/png`).
+    /// Attachments without `media_type` are skipped.
+    pub fn attachments_by_media_type<'a>(
+        &'a self,
+        media_type: &'a str,
+    ) -> impl Iterator<Item = &'a Attachment> {
+        self.attachments
+            .iter()
+            .flatten()
+            .filter(move |a| a.media_type.as_deref() == Some(media_type))
+    }
+
+    /// Deserializes message `body` into the given type (for ex. protocol specific body struct).
+    ///
+    /// # Errors
+    /// - `Malformed` body doesn't match the given type.
+    pub fn body_as<T: DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_value(self.body.clone()).kind(
+            ErrorKind::Malformed,
+            "Message body doesn't match the expected type",
+        )
+    }
+
+    /// Parses plaintext message from the given JSON value with the same validation
+    /// as for plaintext message string, but without serialize/parse cycle.
+    /// Stringified JSON attachment data is rejected (as `unpack` does unless
+    /// `UnpackOptions::lenient_json_attachments` is set).
+    ///
+    /// # Errors
+    /// - `Malformed` value is not a valid plaintext message.
+    pub fn from_value(value: Value) -> Result<Message> {
+        serde_json::from_value::<Message>(value)
+            .to_didcomm("Unable deserialize jwm")?
+            .validate()
+    }
+
+    /// Converts the message into JSON value equal to the one `pack_plaintext` serializes,
+    /// but without serialize/parse cycle.
+    ///
+    /// # Errors
+    /// - `InvalidState` message can't be represented as JSON.
+    pub fn to_value(&self) -> Result<Value> {
+        serde_json::to_value(self).kind(ErrorKind::InvalidState, "Unable to serialize message")
+    }
+
     pub(crate) fn from_str(s: &str) -> Result<Message> {
         serde_json::from_str(s).to_didcomm("Unable deserialize jwm")
     }
 
+    /// Validates message `body` with validator registered in `validators` for the message type
+    /// or built-in one (routing forward). Unpack does the same with `UnpackOptions::body_validators`.
+    ///
+    /// # Errors
+    /// - `Malformed` body doesn't match the schema of the message type.
+    pub fn validate_body(&self, validators: &BodyValidators) -> Result<()> {
+        validators.validate(&self.type_, &self.body)
+    }
+
     pub(crate) fn validate(self) -> Result<Self> {
-        if self.typ != PLAINTEXT_TYP {
+        self.validate_accepting_typ(&[], &BodyValidators::default(), false)
+    }
+
+    /// Validates the message accepting listed `typ` values in addition to the standard one.
+    /// If `lenient_json_attachments` is true, stringified JSON attachment data is accepted.
+    pub(crate) fn validate_accepting_typ(
+        self,
+        accepted_typ: &[String],
+        body_validators: &BodyValidators,
+        lenient_json_attachments: bool,
+    ) -> Result<Self> {
+        if self.typ != PLAINTEXT_TYP && !accepted_typ.contains(&self.typ) {
             Err(err_msg(
                 ErrorKind::Malformed,
                 format!("`typ` must be \"{}\"", PLAINTEXT_TYP),
             ))?;
         }
+
+        if let Some((_, err)) = self
+            .content_issues(body_validators, lenient_json_attachments)
+            .into_iter()
+            .next()
+        {
+            Err(err)?;
+        }
+
         Ok(self)
     }
+
+    /// Checks of parsed message content shared by unpacking and `Message::validate_all`,
+    /// so both reject the same messages. Returns all failed checks with the field
+    /// each one is about.
+    pub(crate) fn content_issues(
+        &self,
+        body_validators: &BodyValidators,
+        lenient_json_attachments: bool,
+    ) -> Vec<(&'static str, Error)> {
+        let mut checks = vec![
+            ("body", self.validate_body(body_validators)),
+            ("attachments", self.validate_attachments()),
+        ];
+
+        if !lenient_json_attachments {
+            checks.push(("attachments", self._check_json_attachments()));
+        }
+
+        checks
+            .into_iter()
+            .filter_map(|(field, res)| res.err().map(|err| (field, err)))
+            .collect()
+    }
+
+    /// Checks that attachment ids are unique within the message.
+    /// Attachments without `id` aren't checked.
+    pub(crate) fn validate_attachments(&self) -> Result<()> {
+        let mut ids = HashSet::new();
+
+        if let Some(id) = self
+            .attachments
+            .iter()
+            .flatten()
+            .filter_map(|a| a.id.as_deref())
+            .find(|&id| !ids.insert(id))
+        {
+            Err(err_msg(
+                ErrorKind::Malformed,
+                format!("Duplicate attachment id `{}`", id),
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    /// Rejects `json` attachment data being stringified JSON object or array.
+    fn _check_json_attachments(&self) -> Result<()> {
+        let stringified =
+            self.attachments
+                .iter()
+                .flatten()
+                .any(|attachment| match attachment.data {
+                    AttachmentData::Json { ref value } => stringified_json(&value.json).is_some(),
+                    _ => false,
+                });
+
+        if stringified {
+            Err(err_msg(
+                ErrorKind::Malformed,
+                "Attachment json data is stringified JSON",
+            ))?
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns parsed JSON object or array if the value is a string containing it.
+pub(crate) fn stringified_json(value: &Value) -> Option<Value> {
+    match value {
+        Value::String(json) => match serde_json::from_str::<Value>(json) {
+            Ok(parsed) if parsed.is_object() || parsed.is_array() => Some(parsed),
+            _ => None,
+        },
+        _ => None,
+    }
 }
 
 pub struct MessageBuilder {
     id: String,
     type_: String,
-    body: Value,
+    pub(crate) body: Value,
     from: Option<String>,
     to: Option<Vec<String>>,
     thid: Option<String>,
@@ -128,6 +364,18 @@ impl MessageBuilder {
         }
     }
 
+    /// Sets message body serialized from the given value (for ex. protocol specific body struct).
+    ///
+    /// # Errors
+    /// - `IllegalArgument` value can't be serialized as JSON.
+    pub fn body_typed<T: Serialize>(mut self, value: &T) -> Result<Self> {
+        self.body = serde_json::to_value(value)
+            .kind(ErrorKind::IllegalArgument, "Unable serialize message body")?;
+
+        Ok(self)
+    }
+
+    /// Adds recipient to the `to` header. Can be called multiple times to add several recipients.
     pub fn to(mut self, to: String) -> Self {
         if let Some(ref mut sto) = self.to {
             sto.push(to);
@@ -149,6 +397,21 @@ impl MessageBuilder {
         }
     }
 
+    /// Adds several recipients to the `to` header checking each one is a valid DID.
+    ///
+    /// # Errors
+    /// - `IllegalArgument` one of recipients is not a valid DID.
+    pub fn tos(self, tos: Vec<String>) -> Result<Self> {
+        if let Some(to) = tos.iter().find(|to| !is_did(to)) {
+            Err(err_msg(
+                ErrorKind::IllegalArgument,
+                format!("`{}` is not a valid DID", to),
+            ))?;
+        }
+
+        Ok(self.to_many(tos))
+    }
+
     pub fn from(mut self, from: String) -> Self {
         self.from = Some(from);
         self
@@ -164,8 +427,39 @@ impl MessageBuilder {
         self
     }
 
-    pub fn header(mut self, key: String, value: Value) -> Self {
+    /// Adds application-specific header serialized at the top level of the message.
+    ///
+    /// # Errors
+    /// - `IllegalArgument` header name is reserved for one of the known message fields.
+    pub fn header(mut self, key: String, value: Value) -> Result<Self> {
+        if RESERVED_HEADERS.contains(&key.as_str()) {
+            Err(err_msg(
+                ErrorKind::IllegalArgument,
+                format!("Header `{}` is reserved", key),
+            ))?;
+        }
+
         self.extra_headers.insert(key, value);
+        Ok(self)
+    }
+
+    pub fn return_route(mut self, return_route: ReturnRoute) -> Self {
+        self.extra_headers
+            .insert(RETURN_ROUTE_HEADER.to_owned(), json!(return_route));
+        self
+    }
+
+    /// Requests acknowledgements on the given events (for ex. `receipt`) with `please_ack` header.
+    pub fn please_ack(mut self, on: Vec<String>) -> Self {
+        self.extra_headers
+            .insert(PLEASE_ACK_HEADER.to_owned(), json!(on));
+        self
+    }
+
+    /// Sets language of human readable message content (for ex. `en`) with `lang` header.
+    pub fn lang(mut self, lang: String) -> Self {
+        self.extra_headers
+            .insert(LANG_HEADER.to_owned(), Value::String(lang));
         self
     }
 
@@ -205,7 +499,11 @@ impl MessageBuilder {
         }
     }
 
-    pub fn finalize(self) -> Message {
+    pub fn finalize(mut self) -> Message {
+        if let Some(ref mut attachments) = self.attachments {
+            _unique_content_ids(attachments);
+        }
+
         Message {
             id: self.id,
             typ: PLAINTEXT_TYP.to_owned(),
@@ -226,7 +524,18 @@ impl MessageBuilder {
 
 #[cfg(test)]
 mod tests {
-    use serde_json::json;
+    use crate::{
+        did::resolvers::ExampleDIDResolver,
+        secrets::resolvers::ExampleSecretsResolver,
+        test_vectors::{
+            ALICE_SECRETS, BOB_DID, BOB_DID_DOC, BOB_SECRETS,
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_STRINGIFIED_JSON, INVALID_PLAINTEXT_MSG_NO_ID,
+            INVALID_PLAINTEXT_MSG_WRONG_TYP, MESSAGE_ATTACHMENT_MULTI_1, MESSAGE_MINIMAL,
+            MESSAGE_SIMPLE, PLAINTEXT_MSG_ATTACHMENT_JSON, PLAINTEXT_MSG_MINIMAL,
+            PLAINTEXT_MSG_SIMPLE,
+        },
+        PackEncryptedOptions, UnpackOptions,
+    };
 
     use super::*;
 
@@ -243,7 +552,9 @@ mod tests {
         .thid("example-thread-1".into())
         .pthid("example-parent-thread-1".into())
         .header("example-header-1".into(), json!("example-header-1-value"))
+        .expect("header is ok.")
         .header("example-header-2".into(), json!("example-header-2-value"))
+        .expect("header is ok.")
         .created_time(10000)
         .expires_time(20000)
         .attachment(
@@ -303,4 +614,442 @@ mod tests {
         assert_eq!(attachments[1].id, Some("attachment2".into()));
         assert_eq!(attachments[2].id, Some("attachment3".into()));
     }
+
+    #[test]
+    fn message_from_value_works() {
+        for msg in [
+            PLAINTEXT_MSG_SIMPLE,
+            PLAINTEXT_MSG_MINIMAL,
+            PLAINTEXT_MSG_ATTACHMENT_JSON,
+        ] {
+            let value: Value = serde_json::from_str(msg).expect("Unable parse msg");
+
+            let message = Message::from_value(value).expect("from_value is ok.");
+
+            let exp_message = Message::from_str(msg)
+                .expect("from_str is ok.")
+                .validate()
+                .expect("validate is ok.");
+
+            assert_eq!(message, exp_message);
+        }
+    }
+
+    #[test]
+    fn message_from_value_works_invalid() {
+        for msg in [INVALID_PLAINTEXT_MSG_NO_ID, INVALID_PLAINTEXT_MSG_WRONG_TYP] {
+            let value: Value = serde_json::from_str(msg).expect("Unable parse msg");
+
+            let err = Message::from_value(value).expect_err("res is ok");
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+        }
+
+        // Stringified JSON attachment data is rejected as `unpack` does by default
+        let value: Value = serde_json::from_str(INVALID_PLAINTEXT_MSG_ATTACHMENTS_STRINGIFIED_JSON)
+            .expect("Unable parse msg");
+
+        let err = Message::from_value(value).expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        assert_eq!(
+            format!("{}", err),
+            "Malformed: Attachment json data is stringified JSON"
+        );
+    }
+
+    #[tokio::test]
+    async fn message_to_value_works() {
+        let did_resolver = ExampleDIDResolver::new(vec![]);
+
+        for message in [
+            &*MESSAGE_SIMPLE,
+            &*MESSAGE_MINIMAL,
+            &*MESSAGE_ATTACHMENT_MULTI_1,
+        ] {
+            let value = message.to_value().expect("to_value is ok.");
+
+            let packed = message
+                .pack_plaintext(&did_resolver)
+                .await
+                .expect("pack is ok.");
+
+            let exp_value: Value = serde_json::from_str(&packed).expect("Unable parse packed");
+
+            assert_eq!(value, exp_value);
+            assert_eq!(
+                &Message::from_value(value).expect("from_value is ok."),
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn message_build_works_reserved_header() {
+        for header in RESERVED_HEADERS {
+            let err = Message::build(
+                "example-1".into(),
+                "example/v1".into(),
+                json!("example-body"),
+            )
+            .header(header.into(), json!("example-header-value"))
+            .err()
+            .expect("res is ok");
+
+            assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+            assert_eq!(
+                format!("{}", err),
+                format!("Illegal argument: Header `{}` is reserved", header)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn message_build_works_custom_header_round_trip() {
+        let message = Message::build(
+            "example-1".into(),
+            "example/v1".into(),
+            json!("example-body"),
+        )
+        .header("return_route".into(), json!("all"))
+        .expect("header is ok.")
+        .finalize();
+
+        let did_resolver = ExampleDIDResolver::new(vec![]);
+
+        let packed = message
+            .pack_plaintext(&did_resolver)
+            .await
+            .expect("pack is ok.");
+
+        let packed_value: Value = serde_json::from_str(&packed).expect("parse is ok.");
+        assert_eq!(packed_value["return_route"], json!("all"));
+
+        let (unpacked, _) = Message::unpack(
+            &packed,
+            &did_resolver,
+            &ExampleSecretsResolver::new(vec![]),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(unpacked.extra_headers["return_route"], json!("all"));
+        assert_eq!(unpacked, message);
+    }
+
+    #[tokio::test]
+    async fn message_build_works_return_route() {
+        for (return_route, exp_value) in [
+            (ReturnRoute::None, "none"),
+            (ReturnRoute::All, "all"),
+            (ReturnRoute::Thread, "thread"),
+        ] {
+            let message = Message::build(
+                "example-1".into(),
+                "example/v1".into(),
+                json!("example-body"),
+            )
+            .return_route(return_route)
+            .finalize();
+
+            assert_eq!(message.return_route(), Some(return_route));
+            assert_eq!(message.extra_headers["return_route"], json!(exp_value));
+
+            let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+            let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+            let plaintext = message
+                .pack_plaintext(&did_resolver)
+                .await
+                .expect("pack is ok.");
+
+            let (encrypted, _) = message
+                .pack_encrypted(
+                    BOB_DID,
+                    None,
+                    None,
+                    &did_resolver,
+                    &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
+                    &PackEncryptedOptions {
+                        forward: false,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("pack is ok.");
+
+            for packed in [plaintext, encrypted] {
+                let (unpacked, metadata) = Message::unpack(
+                    &packed,
+                    &did_resolver,
+                    &secrets_resolver,
+                    &UnpackOptions::default(),
+                )
+                .await
+                .expect("unpack is ok.");
+
+                assert_eq!(unpacked.return_route(), Some(return_route));
+                assert_eq!(metadata.return_route, Some(return_route));
+            }
+        }
+    }
+
+    #[test]
+    fn return_route_allows_response_works() {
+        let inbound = Message {
+            thid: Some("thread-1".into()),
+            ..MESSAGE_SIMPLE.clone()
+        };
+
+        let same_thread = Message {
+            id: "response-1".into(),
+            thid: Some("thread-1".into()),
+            ..MESSAGE_SIMPLE.clone()
+        };
+
+        let other_thread = Message {
+            id: "response-2".into(),
+            thid: Some("thread-2".into()),
+            ..MESSAGE_SIMPLE.clone()
+        };
+
+        assert!(ReturnRoute::All.allows_response(&inbound, &same_thread));
+        assert!(ReturnRoute::All.allows_response(&inbound, &other_thread));
+
+        assert!(ReturnRoute::Thread.allows_response(&inbound, &same_thread));
+        assert!(!ReturnRoute::Thread.allows_response(&inbound, &other_thread));
+
+        assert!(!ReturnRoute::None.allows_response(&inbound, &same_thread));
+        assert!(!ReturnRoute::None.allows_response(&inbound, &other_thread));
+
+        // Thread of the inbound message without `thid` is its `id`
+        let reply = Message {
+            id: "response-3".into(),
+            thid: Some(MESSAGE_SIMPLE.id.clone()),
+            ..MESSAGE_SIMPLE.clone()
+        };
+
+        assert!(ReturnRoute::Thread.allows_response(&MESSAGE_SIMPLE, &reply));
+    }
+
+    #[test]
+    fn message_return_route_works_absent() {
+        assert_eq!(MESSAGE_SIMPLE.return_route(), None);
+
+        let message = Message::build(
+            "example-1".into(),
+            "example/v1".into(),
+            json!("example-body"),
+        )
+        .header("return_route".into(), json!("unknown"))
+        .expect("header is ok.")
+        .finalize();
+
+        assert_eq!(message.return_route(), None);
+    }
+
+    #[tokio::test]
+    async fn message_build_works_please_ack() {
+        let message = Message::build(
+            "example-1".into(),
+            "example/v1".into(),
+            json!("example-body"),
+        )
+        .please_ack(vec!["receipt".into()])
+        .finalize();
+
+        assert_eq!(message.please_ack(), Some(vec!["receipt".to_owned()]));
+        assert_eq!(message.extra_headers["please_ack"], json!(["receipt"]));
+
+        let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let plaintext = message
+            .pack_plaintext(&did_resolver)
+            .await
+            .expect("pack is ok.");
+
+        let plaintext_value: Value = serde_json::from_str(&plaintext).expect("plaintext is json.");
+        assert_eq!(plaintext_value["please_ack"], json!(["receipt"]));
+
+        let (unpacked, _) = Message::unpack(
+            &plaintext,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(unpacked.please_ack(), Some(vec!["receipt".to_owned()]));
+        assert_eq!(unpacked, message);
+    }
+
+    #[test]
+    fn message_build_works_lang() {
+        let message = Message::build(
+            "example-1".into(),
+            "example/v1".into(),
+            json!("example-body"),
+        )
+        .lang("en".into())
+        .finalize();
+
+        assert_eq!(message.lang(), Some("en"));
+        assert_eq!(message.extra_headers["lang"], json!("en"));
+
+        let message = Message::build(
+            "example-1".into(),
+            "example/v1".into(),
+            json!("example-body"),
+        )
+        .header("lang".into(), json!(["en"]))
+        .expect("header is ok.")
+        .finalize();
+
+        assert_eq!(message.lang(), None);
+    }
+
+    #[test]
+    fn message_please_ack_works_absent() {
+        assert_eq!(MESSAGE_SIMPLE.please_ack(), None);
+
+        let message = Message::build(
+            "example-1".into(),
+            "example/v1".into(),
+            json!("example-body"),
+        )
+        .header("please_ack".into(), json!("receipt"))
+        .expect("header is ok.")
+        .finalize();
+
+        assert_eq!(message.please_ack(), None);
+    }
+
+    #[test]
+    fn message_attachments_by_media_type_works() {
+        assert_eq!(
+            MESSAGE_ATTACHMENT_MULTI_1
+                .attachments_by_media_type("application/json")
+                .count(),
+            0
+        );
+
+        let mut message = MESSAGE_ATTACHMENT_MULTI_1.clone();
+
+        for (attachment, media_type) in message.attachments.iter_mut().flatten().zip([
+            "application/json",
+            "image/png",
+            "application/json",
+        ]) {
+            attachment.media_type = Some(media_type.into());
+        }
+
+        let ids: Vec<_> = message
+            .attachments_by_media_type("application/json")
+            .map(|a| a.id.as_deref())
+            .collect();
+
+        assert_eq!(ids, vec![Some("23"), Some("25")]);
+
+        let ids: Vec<_> = message
+            .attachments_by_media_type("image/png")
+            .map(|a| a.id.as_deref())
+            .collect();
+
+        assert_eq!(ids, vec![Some("24")]);
+
+        assert_eq!(message.attachments_by_media_type("text/plain").count(), 0);
+        assert_eq!(
+            MESSAGE_SIMPLE
+                .attachments_by_media_type("image/png")
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn message_body_typed_works() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct ExampleBody {
+            comment: String,
+            count: u32,
+            tags: Vec<String>,
+        }
+
+        let body = ExampleBody {
+            comment: "example".into(),
+            count: 2,
+            tags: vec!["tag-1".into(), "tag-2".into()],
+        };
+
+        let message = Message::build("example-1".into(), "example/v1".into(), json!({}))
+            .body_typed(&body)
+            .expect("body_typed is ok.")
+            .finalize();
+
+        assert_eq!(
+            message.body,
+            json!({"comment": "example", "count": 2, "tags": ["tag-1", "tag-2"]})
+        );
+
+        let message =
+            Message::from_str(&serde_json::to_string(&message).unwrap()).expect("from_str is ok.");
+
+        let parsed: ExampleBody = message.body_as().expect("body_as is ok.");
+        assert_eq!(parsed, body);
+    }
+
+    #[test]
+    fn message_body_as_works_mismatch() {
+        #[derive(Debug, Deserialize)]
+        struct ExampleBody {
+            #[allow(dead_code)]
+            count: u32,
+        }
+
+        let err = MESSAGE_SIMPLE
+            .body_as::<ExampleBody>()
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+    }
+
+    #[tokio::test]
+    async fn message_tos_works() {
+        let message = Message::build("example-1".into(), "example/v1".into(), json!({}))
+            .to("did:example:1".into())
+            .tos(vec!["did:example:2".into(), "did:example:3".into()])
+            .expect("tos is ok.")
+            .finalize();
+
+        let exp_recipients = vec![
+            "did:example:1".to_owned(),
+            "did:example:2".to_owned(),
+            "did:example:3".to_owned(),
+        ];
+
+        assert_eq!(message.recipients(), &exp_recipients[..]);
+
+        let plaintext = message
+            .pack_plaintext(&ExampleDIDResolver::new(vec![]))
+            .await
+            .expect("pack_plaintext is ok.");
+
+        let plaintext: Value = serde_json::from_str(&plaintext).expect("plaintext is ok.");
+        assert_eq!(plaintext["to"], json!(exp_recipients));
+
+        assert!(MESSAGE_MINIMAL.recipients().is_empty());
+    }
+
+    #[test]
+    fn message_tos_works_invalid_did() {
+        let err = Message::build("example-1".into(), "example/v1".into(), json!({}))
+            .tos(vec!["did:example:1".into(), "not-a-did".into()])
+            .err()
+            .expect("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+    }
 }
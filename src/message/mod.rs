@@ -1,21 +1,48 @@
 mod attachment;
+mod body_validator;
+mod compact;
+mod didcomm;
+mod diff;
 mod from_prior;
 mod message;
 mod pack_encrypted;
 mod pack_plaintext;
 mod pack_signed;
+mod peek;
+mod reencrypt;
+mod size;
+mod storage;
 mod unpack;
+mod validate;
+
+#[cfg(test)]
+mod interop;
+#[cfg(test)]
+mod wire_format;
 
 pub use attachment::{
     Attachment, AttachmentBuilder, AttachmentData, Base64AttachmentData, JsonAttachmentData,
-    LinksAttachmentData,
+    LinksAttachmentData, MAX_DECOMPRESSED_ATTACHMENT_BYTES,
 };
 
-pub use from_prior::FromPrior;
+pub use body_validator::{BodyValidator, BodyValidators};
+
+pub use diff::FieldDiff;
+
+pub use didcomm::DIDCommClient;
 
-pub use message::{Message, MessageBuilder};
-pub use pack_encrypted::{MessagingServiceMetadata, PackEncryptedMetadata, PackEncryptedOptions};
-pub use pack_signed::PackSignedMetadata;
-pub use unpack::{UnpackMetadata, UnpackOptions};
+pub use from_prior::{FromPrior, FromPriorSpec};
+
+pub use message::{Message, MessageBuilder, ReturnRoute};
+pub use pack_encrypted::{
+    MessagePreview, MessagingServiceMetadata, PackContext, PackEncryptedMetadata,
+    PackEncryptedOptions, PackPlan,
+};
+pub use pack_plaintext::PackPlaintextOptions;
+pub use pack_signed::{PackSignedMetadata, SignOptions};
+pub use peek::{EnvelopeDescription, PackedKind};
+pub use size::{AttachmentSize, SizeBreakdown};
+pub use unpack::{ForwardHopInfo, LayerInfo, UnpackMetadata, UnpackMetadataBuilder, UnpackOptions};
+pub use validate::ValidationError;
 
-pub(crate) use pack_encrypted::anoncrypt;
+pub(crate) use pack_encrypted::{_content_hash, anoncrypt, authcrypt};
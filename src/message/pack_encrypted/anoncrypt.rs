@@ -1,30 +1,129 @@
-use askar_crypto::{
-    alg::{
-        aes::{A256CbcHs512, A256Gcm, A256Kw, AesKey},
-        chacha20::{Chacha20Key, XC20P},
-        p256::P256KeyPair,
-        x25519::X25519KeyPair,
-    },
-    kdf::ecdh_es::EcdhEs,
-};
+use std::sync::Arc;
+
+use serde_json::Value;
 
+use super::_recipient_keys;
 use crate::{
-    algorithms::AnonCryptAlg,
-    did::DIDResolver,
+    algorithms::{AlgorithmRegistry, AnonCryptAlg, KeyAgreementAlgorithm},
+    did::{did_jwk::did_jwk_key_agreement, DIDResolver, VerificationMethod},
     error::{err_msg, ErrorKind, Result, ResultContext},
-    jwe,
-    utils::{
-        crypto::{AsKnownKeyPair, KnownKeyAlg},
-        did::did_or_url,
-    },
+    jwe::{NonceSource, SessionKeyProvider},
+    utils::did::did_or_url,
 };
 
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "anoncrypt",
+        level = "debug",
+        skip_all,
+        fields(to = %to, enc_alg = ?enc_alg_anon),
+        err(level = "debug")
+    )
+)]
 pub(crate) async fn anoncrypt<'dr, 'sr>(
     to: &str,
+    to_subset: Option<&[&str]>,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    msg: &[u8],
+    enc_alg_anon: &AnonCryptAlg,
+    algorithms: &AlgorithmRegistry,
+    nonce_source: Option<&dyn NonceSource>,
+    session_key_provider: Option<&dyn SessionKeyProvider>,
+    aad: Option<&[u8]>,
+    preserve_kids_order: bool,
+) -> Result<(String, Vec<String>)> /* (msg, to_kids) */ {
+    let (algorithm, to_keys) =
+        anoncrypt_keys(to, to_subset, did_resolver, algorithms, preserve_kids_order).await?;
+
+    _anoncrypt(
+        algorithm.as_ref(),
+        &to_keys,
+        msg,
+        enc_alg_anon,
+        nonce_source,
+        session_key_provider,
+        aad,
+    )
+}
+
+/// Selects recipient keys and key agreement algorithm `anoncrypt` encrypts the message with.
+/// Also used by `Message::pack_encrypted_plan`, so the plan matches actual packing.
+pub(crate) async fn anoncrypt_keys<'dr>(
+    to: &str,
+    to_subset: Option<&[&str]>,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    algorithms: &AlgorithmRegistry,
+    preserve_kids_order: bool,
+) -> Result<(Arc<dyn KeyAgreementAlgorithm>, Vec<VerificationMethod>)> /* (algorithm, to_keys) */
+{
+    let to_keys = _to_keys(to, to_subset, did_resolver, algorithms).await?;
+    _select_keys(&[to_keys], algorithms, preserve_kids_order)
+}
+
+/// Same as `anoncrypt`, but produces single JWE for keys of multiple recipient DIDs.
+pub(crate) async fn anoncrypt_multi<'dr, 'sr>(
+    to: &[&str],
     did_resolver: &'dr (dyn DIDResolver + 'dr),
     msg: &[u8],
     enc_alg_anon: &AnonCryptAlg,
+    algorithms: &AlgorithmRegistry,
+    nonce_source: Option<&dyn NonceSource>,
+    session_key_provider: Option<&dyn SessionKeyProvider>,
+    aad: Option<&[u8]>,
+    preserve_kids_order: bool,
+) -> Result<(String, Vec<String>)> /* (msg, to_kids) */ {
+    let mut to_keys = Vec::with_capacity(to.len());
+
+    for to in to {
+        to_keys.push(_to_keys(to, None, did_resolver, algorithms).await?);
+    }
+
+    let (algorithm, to_keys) = _select_keys(&to_keys, algorithms, preserve_kids_order)?;
+
+    _anoncrypt(
+        algorithm.as_ref(),
+        &to_keys,
+        msg,
+        enc_alg_anon,
+        nonce_source,
+        session_key_provider,
+        aad,
+    )
+}
+
+/// Same as `anoncrypt`, but encrypts for the given public key JWK without DID resolution.
+/// The key is identified by `did:jwk` key ID (see `build_did_jwk_kid`).
+pub(crate) fn anoncrypt_to_jwk(
+    to_jwk: &Value,
+    msg: &[u8],
+    enc_alg_anon: &AnonCryptAlg,
+    algorithms: &AlgorithmRegistry,
+    nonce_source: Option<&dyn NonceSource>,
+    session_key_provider: Option<&dyn SessionKeyProvider>,
+    aad: Option<&[u8]>,
 ) -> Result<(String, Vec<String>)> /* (msg, to_kids) */ {
+    let to_key = did_jwk_key_agreement(to_jwk).context("Invalid recipient jwk")?;
+    let (algorithm, to_keys) = _select_keys(&[vec![to_key]], algorithms, true)?;
+
+    _anoncrypt(
+        algorithm.as_ref(),
+        &to_keys,
+        msg,
+        enc_alg_anon,
+        nonce_source,
+        session_key_provider,
+        aad,
+    )
+}
+
+/// Resolves key agreement verification methods of the recipient.
+async fn _to_keys<'dr>(
+    to: &str,
+    to_subset: Option<&[&str]>,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    algorithms: &AlgorithmRegistry,
+) -> Result<Vec<VerificationMethod>> {
     let (to_did, to_kid) = did_or_url(to);
 
     // TODO: Avoid resolving of same dids multiple times
@@ -35,171 +134,95 @@ pub(crate) async fn anoncrypt<'dr, 'sr>(
         .context("Unable resolve recipient did")?
         .ok_or_else(|| err_msg(ErrorKind::DIDNotResolved, "Recipient did not found"))?;
 
-    // Initial list of recipient key ids is all key_agreements of recipient did doc
-    // or one key if url was explicitly provided
-    let to_kids: Vec<_> = to_ddoc
-        .key_agreements
-        .iter()
-        .filter(|kid| to_kid.map(|to_kid| kid == &to_kid).unwrap_or(true))
-        .map(|s| s.as_str())
-        .collect();
-
-    if to_kids.is_empty() {
-        Err(err_msg(
-            ErrorKind::DIDUrlNotFound,
-            "No recipient key agreements found",
-        ))?
-    }
+    _recipient_keys(
+        &to_ddoc,
+        to_kid,
+        to_subset,
+        did_resolver,
+        algorithms,
+        |_| {
+            err_msg(
+                ErrorKind::Unsupported,
+                "External keys are unsupported in this version",
+            )
+        },
+    )
+    .await
+}
 
-    // Resolve materials for recipient keys
-    let to_keys = to_kids
+/// Determines key agreement algorithm and keys of all recipients the message is encrypted for.
+/// `to_keys` contains key agreement verification methods of each recipient.
+fn _select_keys(
+    to_keys: &[Vec<VerificationMethod>],
+    algorithms: &AlgorithmRegistry,
+    preserve_kids_order: bool,
+) -> Result<(Arc<dyn KeyAgreementAlgorithm>, Vec<VerificationMethod>)> /* (algorithm, to_keys) */
+{
+    // Looking for first supported key to determine what key agreement algorithm to use.
+    // JWE contains single epk, so all recipient keys must be of the same curve.
+    // If recipient has key agreements of different curves, the curve of the first
+    // supported key in `keyAgreement` order is used and keys of other curves are skipped.
+    // For multiple recipients the first curve of the first recipient all other recipients
+    // have keys of is used.
+    let algorithm = to_keys
+        .first()
         .into_iter()
-        .map(|kid| {
-            to_ddoc
-                .verification_methods
+        .flatten()
+        .filter_map(|key| algorithms.key_agreement_algorithm_for_method(key))
+        .find(|algorithm| {
+            to_keys
                 .iter()
-                .find(|vm| vm.id == kid)
-                .ok_or_else(|| {
-                    // TODO: support external keys
-                    err_msg(
-                        ErrorKind::Unsupported,
-                        "External keys are unsupported in this version",
-                    )
-                })
+                .all(|keys| keys.iter().any(|key| algorithm.supports_method(key)))
         })
-        .collect::<Result<Vec<_>>>()?;
-
-    // Looking for first supported key to determine what key alg to use
-    let key_alg = to_keys
-        .iter()
-        .filter(|key| key.key_alg() != KnownKeyAlg::Unsupported)
-        .map(|key| key.key_alg())
-        .next()
         .ok_or_else(|| {
             err_msg(
-                ErrorKind::InvalidState,
-                "No key agreement keys found for recipient",
+                ErrorKind::NoCompatibleCrypto,
+                "No recipient key agreements of supported curves found",
             )
         })?;
 
-    // Keep only keys with determined key alg
-    let to_keys: Vec<_> = to_keys
+    // Keep only keys supported by determined algorithm
+    let mut to_keys: Vec<_> = to_keys
         .iter()
-        .filter(|key| key.key_alg() == key_alg)
+        .flatten()
+        .filter(|key| algorithm.supports_method(key))
+        .cloned()
         .collect();
 
-    let msg = match key_alg {
-        KnownKeyAlg::X25519 => {
-            let _to_keys = to_keys
-                .iter()
-                .map(|vm| vm.as_x25519().map(|k| (&vm.id, k)))
-                .collect::<Result<Vec<_>>>()?;
+    if !preserve_kids_order {
+        to_keys.sort_by(|a, b| a.id.cmp(&b.id));
+    }
 
-            let to_keys: Vec<_> = _to_keys
-                .iter()
-                .map(|(id, key)| (id.as_str(), key))
-                .collect();
-
-            match enc_alg_anon {
-                AnonCryptAlg::A256cbcHs512EcdhEsA256kw => jwe::encrypt::<
-                    AesKey<A256CbcHs512>,
-                    EcdhEs<'_, X25519KeyPair>,
-                    X25519KeyPair,
-                    AesKey<A256Kw>,
-                >(
-                    msg,
-                    jwe::Algorithm::EcdhEsA256kw,
-                    jwe::EncAlgorithm::A256cbcHs512,
-                    None,
-                    &to_keys,
-                )
-                .context("Unable produce anoncrypt envelope")?,
-                AnonCryptAlg::Xc20pEcdhEsA256kw => jwe::encrypt::<
-                    Chacha20Key<XC20P>,
-                    EcdhEs<'_, X25519KeyPair>,
-                    X25519KeyPair,
-                    AesKey<A256Kw>,
-                >(
-                    msg,
-                    jwe::Algorithm::EcdhEsA256kw,
-                    jwe::EncAlgorithm::Xc20P,
-                    None,
-                    &to_keys,
-                )
-                .context("Unable produce anoncrypt envelope")?,
-                AnonCryptAlg::A256gcmEcdhEsA256kw => jwe::encrypt::<
-                    AesKey<A256Gcm>,
-                    EcdhEs<'_, X25519KeyPair>,
-                    X25519KeyPair,
-                    AesKey<A256Kw>,
-                >(
-                    msg,
-                    jwe::Algorithm::EcdhEsA256kw,
-                    jwe::EncAlgorithm::A256Gcm,
-                    None,
-                    &to_keys,
-                )
-                .context("Unable produce anoncrypt envelope")?,
-            }
-        }
-        KnownKeyAlg::P256 => {
-            let _to_keys = to_keys
-                .iter()
-                .map(|vm| vm.as_p256().map(|k| (&vm.id, k)))
-                .collect::<Result<Vec<_>>>()?;
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        to_kids = ?to_keys.iter().map(|key| &key.id).collect::<Vec<_>>(),
+        crv = %algorithm.crv(),
+        "recipient keys selected"
+    );
 
-            let to_keys: Vec<_> = _to_keys
-                .iter()
-                .map(|(id, key)| (id.as_str(), key))
-                .collect();
-
-            match enc_alg_anon {
-                AnonCryptAlg::A256cbcHs512EcdhEsA256kw => jwe::encrypt::<
-                    AesKey<A256CbcHs512>,
-                    EcdhEs<'_, P256KeyPair>,
-                    P256KeyPair,
-                    AesKey<A256Kw>,
-                >(
-                    msg,
-                    jwe::Algorithm::EcdhEsA256kw,
-                    jwe::EncAlgorithm::A256cbcHs512,
-                    None,
-                    &to_keys,
-                )
-                .context("Unable produce anoncrypt envelope")?,
-                AnonCryptAlg::Xc20pEcdhEsA256kw => jwe::encrypt::<
-                    Chacha20Key<XC20P>,
-                    EcdhEs<'_, P256KeyPair>,
-                    P256KeyPair,
-                    AesKey<A256Kw>,
-                >(
-                    msg,
-                    jwe::Algorithm::EcdhEsA256kw,
-                    jwe::EncAlgorithm::Xc20P,
-                    None,
-                    &to_keys,
-                )
-                .context("Unable produce anoncrypt envelope")?,
-                AnonCryptAlg::A256gcmEcdhEsA256kw => jwe::encrypt::<
-                    AesKey<A256Gcm>,
-                    EcdhEs<'_, P256KeyPair>,
-                    P256KeyPair,
-                    AesKey<A256Kw>,
-                >(
-                    msg,
-                    jwe::Algorithm::EcdhEsA256kw,
-                    jwe::EncAlgorithm::A256Gcm,
-                    None,
-                    &to_keys,
-                )
-                .context("Unable produce anoncrypt envelope")?,
-            }
-        }
-        _ => Err(err_msg(
-            ErrorKind::InvalidState,
-            "Unsupported recipient key agreement alg",
-        ))?,
-    };
+    Ok((algorithm, to_keys))
+}
+
+/// Encrypts the message for the selected recipient keys.
+fn _anoncrypt(
+    algorithm: &dyn KeyAgreementAlgorithm,
+    to_keys: &[VerificationMethod],
+    msg: &[u8],
+    enc_alg_anon: &AnonCryptAlg,
+    nonce_source: Option<&dyn NonceSource>,
+    session_key_provider: Option<&dyn SessionKeyProvider>,
+    aad: Option<&[u8]>,
+) -> Result<(String, Vec<String>)> /* (msg, to_kids) */ {
+    let to_keys: Vec<_> = to_keys.iter().collect();
+
+    let msg = algorithm.encrypt(
+        msg,
+        &to_keys,
+        enc_alg_anon,
+        nonce_source,
+        session_key_provider,
+        aad,
+    )?;
 
     let to_kids: Vec<_> = to_keys.into_iter().map(|vm| vm.id.clone()).collect();
     Ok((msg, to_kids))
@@ -1,27 +1,28 @@
-use askar_crypto::{
-    alg::{
-        aes::{A256CbcHs512, A256Gcm, A256Kw, AesKey},
-        chacha20::{Chacha20Key, XC20P},
-        p256::P256KeyPair,
-        x25519::X25519KeyPair,
-    },
-    kdf::{ecdh_1pu::Ecdh1PU, ecdh_es::EcdhEs},
-};
+use std::sync::Arc;
 
+use super::_recipient_keys;
 use crate::{
-    algorithms::{AnonCryptAlg, AuthCryptAlg},
-    did::DIDResolver,
+    algorithms::{AlgorithmRegistry, AnonCryptAlg, AuthCryptAlg, KeyAgreementAlgorithm},
+    did::{did_resolver::find_verification_method, DIDResolver, VerificationMethod},
     error::{err_msg, ErrorKind, Result, ResultContext},
-    jwe,
+    jwe::NonceSource,
     secrets::SecretsResolver,
-    utils::{
-        crypto::{AsKnownKeyPair, KnownKeyAlg},
-        did::did_or_url,
-    },
+    utils::did::{did_or_url, key_agreements_not_found},
 };
 
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "authcrypt",
+        level = "debug",
+        skip_all,
+        fields(to = %to, from = %from, enc_alg = ?enc_alg_auth, protect_sender),
+        err(level = "debug")
+    )
+)]
 pub(crate) async fn authcrypt<'dr, 'sr>(
     to: &str,
+    to_subset: Option<&[&str]>,
     from: &str,
     did_resolver: &'dr (dyn DIDResolver + 'dr),
     secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
@@ -29,7 +30,74 @@ pub(crate) async fn authcrypt<'dr, 'sr>(
     enc_alg_auth: &AuthCryptAlg,
     enc_alg_anon: &AnonCryptAlg,
     protect_sender: bool,
+    algorithms: &AlgorithmRegistry,
+    nonce_source: Option<&dyn NonceSource>,
+    aad: Option<&[u8]>,
+    preserve_kids_order: bool,
 ) -> Result<(String, String, Vec<String>)> /* (msg, from_kid, to_kids) */ {
+    let (algorithm, from_key, to_keys) = authcrypt_keys(
+        to,
+        to_subset,
+        from,
+        did_resolver,
+        Some(secrets_resolver),
+        algorithms,
+        preserve_kids_order,
+    )
+    .await?;
+
+    // Resolve secret for found sender key
+    let from_priv_key = secrets_resolver
+        .get_secret(&from_key.id)
+        .await
+        .context("Unable resolve sender secret")?
+        .ok_or_else(|| err_msg(ErrorKind::InvalidState, "Sender secret not found"))?;
+
+    let to_keys: Vec<_> = to_keys.iter().collect();
+
+    let msg = algorithm.encrypt_authenticated(
+        msg,
+        &from_priv_key,
+        &to_keys,
+        enc_alg_auth,
+        nonce_source,
+        aad,
+    )?;
+
+    let msg = if protect_sender {
+        algorithm.encrypt(
+            msg.as_bytes(),
+            &to_keys,
+            enc_alg_anon,
+            nonce_source,
+            None,
+            aad,
+        )?
+    } else {
+        msg
+    };
+
+    let to_kids: Vec<_> = to_keys.into_iter().map(|vm| vm.id.clone()).collect();
+    Ok((msg, from_key.id, to_kids))
+}
+
+/// Selects sender and recipient keys `authcrypt` encrypts the message with and
+/// the key agreement algorithm of their curve.
+/// Sender keys are limited to the ones `secrets_resolver` knows secrets for. If it isn't provided
+/// (see `Message::pack_encrypted_plan`) all sender key agreements are considered.
+pub(crate) async fn authcrypt_keys<'dr, 'sr>(
+    to: &str,
+    to_subset: Option<&[&str]>,
+    from: &str,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    secrets_resolver: Option<&'sr (dyn SecretsResolver + 'sr)>,
+    algorithms: &AlgorithmRegistry,
+    preserve_kids_order: bool,
+) -> Result<(
+    Arc<dyn KeyAgreementAlgorithm>,
+    VerificationMethod,
+    Vec<VerificationMethod>,
+)> /* (algorithm, from_key, to_keys) */ {
     let (to_did, to_kid) = did_or_url(to);
 
     // TODO: Avoid resolving of same dids multiple times
@@ -58,17 +126,20 @@ pub(crate) async fn authcrypt<'dr, 'sr>(
         .collect();
 
     if from_kids.is_empty() {
-        Err(err_msg(
-            ErrorKind::DIDUrlNotFound,
+        Err(key_agreements_not_found(
             "No sender key agreements found",
+            &from_ddoc,
         ))?
     }
 
     // Keep only sender keys present in the wallet
-    let from_kids = secrets_resolver
-        .find_secrets(&from_kids)
-        .await
-        .context("Unable find secrets")?;
+    let from_kids = match secrets_resolver {
+        Some(secrets_resolver) => secrets_resolver
+            .find_secrets(&from_kids)
+            .await
+            .context("Unable find secrets")?,
+        None => from_kids,
+    };
 
     if from_kids.is_empty() {
         Err(err_msg(
@@ -78,75 +149,57 @@ pub(crate) async fn authcrypt<'dr, 'sr>(
     }
 
     // Resolve materials for sender keys
-    let from_keys = from_kids
-        .into_iter()
-        .map(|kid| {
-            from_ddoc
-                .verification_methods
-                .iter()
-                .find(|vm| vm.id == kid)
-                .ok_or_else(|| {
-                    // TODO: support external keys
-                    err_msg(
-                        ErrorKind::Malformed,
-                        format!(
-                            "No verification material found for sender key agreement {}",
-                            kid
-                        ),
-                    )
-                })
-        })
-        .collect::<Result<Vec<_>>>()?;
-
-    // Initial list of recipient keys is all key_agreements of recipient did doc
-    // or filtered to keep only provided key
-    let to_kids: Vec<_> = to_ddoc
-        .key_agreements
-        .iter()
-        .filter(|kid| to_kid.map(|to_kid| kid == &to_kid).unwrap_or(true))
-        .map(|s| s.as_str())
-        .collect();
+    let mut from_keys = Vec::with_capacity(from_kids.len());
+
+    for kid in from_kids {
+        let from_key = find_verification_method(&from_ddoc, kid, did_resolver)
+            .await
+            .context("Unable resolve sender key")?
+            .ok_or_else(|| {
+                err_msg(
+                    ErrorKind::Malformed,
+                    format!(
+                        "No verification material found for sender key agreement {}",
+                        kid
+                    ),
+                )
+            })?;
 
-    if to_kids.is_empty() {
-        Err(err_msg(
-            ErrorKind::DIDUrlNotFound,
-            "No recipient key agreements found",
-        ))?
+        from_keys.push(from_key);
     }
 
-    // Resolve materials for recipient keys
-    let to_keys = to_kids
-        .into_iter()
-        .map(|kid| {
-            to_ddoc
-                .verification_methods
-                .iter()
-                .find(|vm| vm.id == kid)
-                .ok_or_else(|| {
-                    // TODO: support external keys
-                    err_msg(
-                        ErrorKind::Malformed,
-                        format!(
-                            "No verification material found for recipient key agreement {}",
-                            kid
-                        ),
-                    )
-                })
-        })
-        .collect::<Result<Vec<_>>>()?;
+    let to_keys = _recipient_keys(
+        &to_ddoc,
+        to_kid,
+        to_subset,
+        did_resolver,
+        algorithms,
+        |kid| {
+            err_msg(
+                ErrorKind::Malformed,
+                format!(
+                    "No verification material found for recipient key agreement {}",
+                    kid
+                ),
+            )
+        },
+    )
+    .await?;
 
     // Looking for first sender key that has supported crypto and intersects with recipient keys
-    // by key alg
-    let from_key = from_keys
-        .iter()
-        .filter(|key| key.key_alg() != KnownKeyAlg::Unsupported)
-        .find(|from_key| {
+    // by key agreement algorithm
+    let (algorithm, from_key) = from_keys
+        .into_iter()
+        .filter_map(|key| {
+            algorithms
+                .key_agreement_algorithm_for_method(&key)
+                .map(|algorithm| (algorithm, key))
+        })
+        .find(|(algorithm, _)| {
             to_keys
                 .iter()
-                .find(|to_key| to_key.key_alg() == from_key.key_alg())
-                .is_some()
+                .any(|to_key| algorithm.supports_method(to_key))
         })
-        .map(|&key| key)
         .ok_or_else(|| {
             err_msg(
                 ErrorKind::NoCompatibleCrypto,
@@ -154,174 +207,23 @@ pub(crate) async fn authcrypt<'dr, 'sr>(
             )
         })?;
 
-    // Resolve secret for found sender key
-    let from_priv_key = secrets_resolver
-        .get_secret(&from_key.id)
-        .await
-        .context("Unable resolve sender secret")?
-        .ok_or_else(|| err_msg(ErrorKind::InvalidState, "Sender secret not found"))?;
-
-    let key_alg = from_key.key_alg();
-
     // Keep only recipient keys compatible with sender key
-    let to_keys: Vec<_> = to_keys
+    let mut to_keys: Vec<_> = to_keys
         .into_iter()
-        .filter(|key| key.key_alg() == key_alg)
+        .filter(|key| algorithm.supports_method(key))
         .collect();
 
-    let msg = match key_alg {
-        KnownKeyAlg::X25519 => {
-            let _to_keys = to_keys
-                .iter()
-                .map(|vm| vm.as_x25519().map(|k| (&vm.id, k)))
-                .collect::<Result<Vec<_>>>()?;
-
-            let to_keys: Vec<_> = _to_keys
-                .iter()
-                .map(|(id, key)| (id.as_str(), key))
-                .collect();
-
-            let msg = match enc_alg_auth {
-                AuthCryptAlg::A256cbcHs512Ecdh1puA256kw => jwe::encrypt::<
-                    AesKey<A256CbcHs512>,
-                    Ecdh1PU<'_, X25519KeyPair>,
-                    X25519KeyPair,
-                    AesKey<A256Kw>,
-                >(
-                    msg,
-                    jwe::Algorithm::Ecdh1puA256kw,
-                    jwe::EncAlgorithm::A256cbcHs512,
-                    Some((&from_key.id, &from_priv_key.as_x25519()?)),
-                    &to_keys,
-                )
-                .context("Unable produce authcrypt envelope")?,
-            };
-
-            if protect_sender {
-                match enc_alg_anon {
-                    AnonCryptAlg::A256cbcHs512EcdhEsA256kw => jwe::encrypt::<
-                        AesKey<A256CbcHs512>,
-                        EcdhEs<'_, X25519KeyPair>,
-                        X25519KeyPair,
-                        AesKey<A256Kw>,
-                    >(
-                        msg.as_bytes(),
-                        jwe::Algorithm::EcdhEsA256kw,
-                        jwe::EncAlgorithm::A256cbcHs512,
-                        None,
-                        &to_keys,
-                    )
-                    .context("Unable produce authcrypt envelope")?,
-                    AnonCryptAlg::Xc20pEcdhEsA256kw => jwe::encrypt::<
-                        Chacha20Key<XC20P>,
-                        EcdhEs<'_, X25519KeyPair>,
-                        X25519KeyPair,
-                        AesKey<A256Kw>,
-                    >(
-                        msg.as_bytes(),
-                        jwe::Algorithm::EcdhEsA256kw,
-                        jwe::EncAlgorithm::Xc20P,
-                        None,
-                        &to_keys,
-                    )
-                    .context("Unable produce authcrypt envelope")?,
-                    AnonCryptAlg::A256gcmEcdhEsA256kw => jwe::encrypt::<
-                        AesKey<A256Gcm>,
-                        EcdhEs<'_, X25519KeyPair>,
-                        X25519KeyPair,
-                        AesKey<A256Kw>,
-                    >(
-                        msg.as_bytes(),
-                        jwe::Algorithm::EcdhEsA256kw,
-                        jwe::EncAlgorithm::A256Gcm,
-                        None,
-                        &to_keys,
-                    )
-                    .context("Unable produce authcrypt envelope")?,
-                }
-            } else {
-                msg
-            }
-        }
-        KnownKeyAlg::P256 => {
-            let _to_keys = to_keys
-                .iter()
-                .map(|vm| vm.as_p256().map(|k| (&vm.id, k)))
-                .collect::<Result<Vec<_>>>()?;
-
-            let to_keys: Vec<_> = _to_keys
-                .iter()
-                .map(|(id, key)| (id.as_str(), key))
-                .collect();
-
-            let msg = match enc_alg_auth {
-                AuthCryptAlg::A256cbcHs512Ecdh1puA256kw => jwe::encrypt::<
-                    AesKey<A256CbcHs512>,
-                    Ecdh1PU<'_, P256KeyPair>,
-                    P256KeyPair,
-                    AesKey<A256Kw>,
-                >(
-                    msg,
-                    jwe::Algorithm::Ecdh1puA256kw,
-                    jwe::EncAlgorithm::A256cbcHs512,
-                    Some((&from_key.id, &from_priv_key.as_p256()?)),
-                    &to_keys,
-                )
-                .context("Unable produce authcrypt envelope")?,
-            };
+    if !preserve_kids_order {
+        to_keys.sort_by(|a, b| a.id.cmp(&b.id));
+    }
 
-            if protect_sender {
-                match enc_alg_anon {
-                    AnonCryptAlg::A256cbcHs512EcdhEsA256kw => jwe::encrypt::<
-                        AesKey<A256CbcHs512>,
-                        EcdhEs<'_, P256KeyPair>,
-                        P256KeyPair,
-                        AesKey<A256Kw>,
-                    >(
-                        msg.as_bytes(),
-                        jwe::Algorithm::EcdhEsA256kw,
-                        jwe::EncAlgorithm::A256cbcHs512,
-                        None,
-                        &to_keys,
-                    )
-                    .context("Unable produce authcrypt envelope")?,
-                    AnonCryptAlg::Xc20pEcdhEsA256kw => jwe::encrypt::<
-                        Chacha20Key<XC20P>,
-                        EcdhEs<'_, P256KeyPair>,
-                        P256KeyPair,
-                        AesKey<A256Kw>,
-                    >(
-                        msg.as_bytes(),
-                        jwe::Algorithm::EcdhEsA256kw,
-                        jwe::EncAlgorithm::Xc20P,
-                        None,
-                        &to_keys,
-                    )
-                    .context("Unable produce authcrypt envelope")?,
-                    AnonCryptAlg::A256gcmEcdhEsA256kw => jwe::encrypt::<
-                        AesKey<A256Gcm>,
-                        EcdhEs<'_, P256KeyPair>,
-                        P256KeyPair,
-                        AesKey<A256Kw>,
-                    >(
-                        msg.as_bytes(),
-                        jwe::Algorithm::EcdhEsA256kw,
-                        jwe::EncAlgorithm::A256Gcm,
-                        None,
-                        &to_keys,
-                    )
-                    .context("Unable produce authcrypt envelope")?,
-                }
-            } else {
-                msg
-            }
-        }
-        _ => Err(err_msg(
-            ErrorKind::Unsupported,
-            "Unsupported recipient key agreement method",
-        ))?,
-    };
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        from_kid = %from_key.id,
+        to_kids = ?to_keys.iter().map(|key| &key.id).collect::<Vec<_>>(),
+        crv = %algorithm.crv(),
+        "sender and recipient keys selected"
+    );
 
-    let to_kids: Vec<_> = to_keys.into_iter().map(|vm| vm.id.clone()).collect();
-    Ok((msg, from_key.id.clone(), to_kids))
+    Ok((algorithm, from_key, to_keys))
 }
@@ -0,0 +1,254 @@
+use crate::{
+    did::{did_resolver::CachingDIDResolver, DIDResolver},
+    error::{err_msg, ErrorKind, Result, ResultContext},
+    secrets::SecretsResolver,
+    utils::did::{did_or_url, is_did},
+    Message, PackEncryptedMetadata, PackEncryptedOptions,
+};
+
+/// Allows to pack many messages for the same recipient (and sender) reusing resolution work.
+///
+/// DID Docs of the recipient, the sender and the signer are resolved once on context creation and cached
+/// for the lifetime of the context together with DID Docs of mediators resolved on the first pack.
+/// Each message is still encrypted with a fresh content encryption key, IV and ephemeral key.
+/// Sender key agreement for authcrypt is still derived per message, as it depends
+/// on the ephemeral key of the message.
+pub struct PackContext<'dr, 'sr> {
+    to: String,
+    from: Option<String>,
+    sign_by: Option<String>,
+    did_resolver: CachingDIDResolver<'dr>,
+    secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+}
+
+impl<'dr, 'sr> PackContext<'dr, 'sr> {
+    /// Creates context resolving DID Docs of the recipient, the sender and the signer.
+    ///
+    /// # Params
+    /// - `to` recipient DID or key ID the messages will be encrypted for.
+    /// - `from` a sender DID or key ID for authcrypt. If None, anoncrypt is used.
+    /// - `sign_by` if `Some` messages will be additionally signed to provide non-repudiation
+    ///   by the given DID or key ID (see `Message::pack_encrypted`).
+    /// - `did_resolver` instance of `DIDResolver` to resolve DIDs.
+    /// - `secrets_resolver` instance of `SecretsResolver` to resolve sender DID keys secrets.
+    ///
+    /// # Errors
+    /// - `DIDNotResolved` Sender, signer or recipient DID not found.
+    /// - `IllegalArgument` `to`, `from` or `sign_by` is not a valid DID or DID URL.
+    /// - `IOError` IO error during DID resolving.
+    pub async fn new(
+        to: &str,
+        from: Option<&str>,
+        sign_by: Option<&str>,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+    ) -> Result<PackContext<'dr, 'sr>> {
+        let did_resolver = CachingDIDResolver::new(did_resolver);
+
+        for did_or_kid in std::iter::once(to).chain(from).chain(sign_by) {
+            if !is_did(did_or_kid) {
+                Err(err_msg(
+                    ErrorKind::IllegalArgument,
+                    format!("`{}` is not a valid DID or DID URL", did_or_kid),
+                ))?;
+            }
+
+            let (did, _) = did_or_url(did_or_kid);
+
+            did_resolver
+                .resolve(did)
+                .await
+                .context("Unable resolve DID")?
+                .ok_or_else(|| err_msg(ErrorKind::DIDNotResolved, "DID not found"))?;
+        }
+
+        Ok(PackContext {
+            to: to.to_owned(),
+            from: from.map(str::to_owned),
+            sign_by: sign_by.map(str::to_owned),
+            did_resolver,
+            secrets_resolver,
+        })
+    }
+
+    /// Packs the message the same way as `Message::pack_encrypted` does with recipient
+    /// and sender of this context, signing it by the signer of this context if any.
+    ///
+    /// # Returns
+    /// Tuple `(encrypted_message, metadata)`. See `Message::pack_encrypted` for details.
+    ///
+    /// # Errors
+    /// The same as `Message::pack_encrypted`.
+    pub async fn pack_encrypted(
+        &self,
+        msg: &Message,
+        options: &PackEncryptedOptions,
+    ) -> Result<(String, PackEncryptedMetadata)> {
+        msg.pack_encrypted(
+            &self.to,
+            self.from.as_deref(),
+            self.sign_by.as_deref(),
+            &self.did_resolver,
+            self.secrets_resolver,
+            options,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        did::resolvers::{ExampleDIDResolver, RecordingDIDResolver},
+        error::ErrorKind,
+        secrets::resolvers::ExampleSecretsResolver,
+        test_vectors::{
+            ALICE_DID, ALICE_DID_DOC, ALICE_SECRETS, BOB_DID, BOB_DID_DOC, BOB_SECRETS,
+            CHARLIE_DID, MESSAGE_SIMPLE,
+        },
+        Message, PackEncryptedOptions, UnpackOptions,
+    };
+
+    use super::PackContext;
+
+    #[tokio::test]
+    async fn pack_context_works() {
+        _pack_context_works(Some(ALICE_DID), None).await;
+        _pack_context_works(None, None).await;
+        _pack_context_works(Some(ALICE_DID), Some(ALICE_DID)).await;
+        _pack_context_works(None, Some(ALICE_DID)).await;
+
+        async fn _pack_context_works(from: Option<&str>, sign_by: Option<&str>) {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+            let recording_did_resolver = RecordingDIDResolver::new(&did_resolver);
+            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+            let ctx = PackContext::new(
+                BOB_DID,
+                from,
+                sign_by,
+                &recording_did_resolver,
+                &secrets_resolver,
+            )
+            .await
+            .expect("PackContext::new is ok.");
+
+            let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+            let mut packed_msgs = vec![];
+
+            for id in ["1", "2", "3"] {
+                let msg = Message {
+                    id: id.into(),
+                    ..MESSAGE_SIMPLE.clone()
+                };
+
+                let (packed_msg, _) = ctx
+                    .pack_encrypted(
+                        &msg,
+                        &PackEncryptedOptions {
+                            forward: false,
+                            ..PackEncryptedOptions::default()
+                        },
+                    )
+                    .await
+                    .expect("pack_encrypted is ok.");
+
+                let (unpacked_msg, unpack_metadata) = Message::unpack(
+                    &packed_msg,
+                    &did_resolver,
+                    &bob_secrets_resolver,
+                    &UnpackOptions::default(),
+                )
+                .await
+                .expect("unpack is ok.");
+
+                assert_eq!(unpacked_msg, msg);
+                assert!(unpack_metadata.encrypted);
+                assert_eq!(
+                    unpack_metadata.authenticated,
+                    from.is_some() || sign_by.is_some()
+                );
+                assert_eq!(unpack_metadata.non_repudiation, sign_by.is_some());
+
+                packed_msgs.push(packed_msg);
+            }
+
+            // Each message is encrypted with fresh keys
+            assert_ne!(packed_msgs[0], packed_msgs[1]);
+            assert_ne!(packed_msgs[1], packed_msgs[2]);
+
+            // Each DID is resolved once for all messages
+            assert_eq!(recording_did_resolver.resolve_count(BOB_DID), 1);
+
+            assert_eq!(
+                recording_did_resolver.resolve_count(ALICE_DID),
+                if from.is_some() || sign_by.is_some() {
+                    1
+                } else {
+                    0
+                }
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_context_works_did_not_resolved() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let err = PackContext::new(
+            BOB_DID,
+            Some(ALICE_DID),
+            None,
+            &did_resolver,
+            &secrets_resolver,
+        )
+        .await
+        .err()
+        .expect("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::DIDNotResolved);
+
+        let err = PackContext::new(
+            ALICE_DID,
+            Some(CHARLIE_DID),
+            None,
+            &did_resolver,
+            &secrets_resolver,
+        )
+        .await
+        .err()
+        .expect("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::DIDNotResolved);
+
+        let err = PackContext::new(
+            ALICE_DID,
+            None,
+            Some(CHARLIE_DID),
+            &did_resolver,
+            &secrets_resolver,
+        )
+        .await
+        .err()
+        .expect("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::DIDNotResolved);
+    }
+
+    #[tokio::test]
+    async fn pack_context_works_invalid_did() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let err = PackContext::new("not-a-did", None, None, &did_resolver, &secrets_resolver)
+            .await
+            .err()
+            .expect("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+    }
+}
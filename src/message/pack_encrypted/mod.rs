@@ -1,24 +1,40 @@
 mod anoncrypt;
 mod authcrypt;
+mod context;
+mod plan;
+mod preview;
 
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 
 use crate::{
-    algorithms::{AnonCryptAlg, AuthCryptAlg},
-    did::DIDResolver,
-    error::{err_msg, ErrorKind, Result, ResultContext},
+    algorithms::{AlgorithmRegistry, AnonCryptAlg, AuthCryptAlg, SignAlg},
+    did::{
+        did_resolver::{
+            check_key_agreement_method, find_verification_method, is_same_key,
+            RelativeDIDUrlsResolver, TimeoutDIDResolver,
+        },
+        DIDDoc, DIDResolver, VerificationMethod,
+    },
+    error::{err_msg, Error, ErrorKind, Result, ResultContext, ResultExt},
+    jwe::{envelope::JWE, NonceSource, SessionKeyProvider},
+    message::pack_signed::{sign_kid, sign_payload},
     protocols::routing::wrap_in_forward_if_needed,
     secrets::SecretsResolver,
-    utils::did::{did_or_url, is_did},
-    Message, PackSignedMetadata,
+    utils::did::{did_or_url, is_did, key_agreements_not_found},
+    FromPrior, FromPriorSpec, Message, PackSignedMetadata, SignOptions,
 };
 
-pub(crate) use self::anoncrypt::anoncrypt;
+pub(crate) use self::anoncrypt::{anoncrypt, anoncrypt_multi, anoncrypt_to_jwk};
+pub(crate) use self::authcrypt::authcrypt;
 
-use self::authcrypt::authcrypt;
+use self::authcrypt::authcrypt_keys;
+pub use self::context::PackContext;
+pub use self::plan::PackPlan;
+pub use self::preview::MessagePreview;
 
 impl Message {
     /// Produces `DIDComm Encrypted Message`
@@ -37,13 +53,22 @@ impl Message {
     ///    which are compatible the sender's key.
     ///  - if `to` is a key ID, then encryption is done for the receiver's `keyAgreement`
     ///    verification method identified by the given key ID.
-    ///  - if `from` is a DID, then sender `keyAgreement` will be negotiated based on recipient preference and
-    ///    sender-recipient crypto compatibility.
+    ///  - if `to` is a DID with `keyAgreement` keys of different curves, then encryption is done
+    ///    only for keys of one curve, as all recipients of a message share one ephemeral key.
+    ///    For anonymous encryption it is the curve of the first supported key in `keyAgreement`
+    ///    list, for authenticated encryption the first curve the sender has a key for.
+    ///  - if `from` is a DID, then the first sender `keyAgreement` key (in DID Doc order) with known secret
+    ///    and curve of at least one recipient key is used, `NoCompatibleCrypto` error is returned if there is
+    ///    no such key. The chosen key is reported as `PackEncryptedMetadata::from_kid`.
     ///  - if `from` is a key ID, then the sender's `keyAgreement` verification method
     ///    identified by the given key ID is used.
     ///  - if `from` is None, then anonymous encryption is done and there will be no sender authentication property.
     ///
     /// It's possible to add non-repudiation by providing `sign_by` parameter.
+    /// By default the plaintext is signed and then encrypted (sign-then-encrypt),
+    /// so the signature is visible only to the recipients. If `options.sign_outside` is set
+    /// the encrypted message is signed instead (encrypt-then-sign), see the option for
+    /// security implications.
     ///
     /// # Params
     /// - `to` recipient DID or key ID the sender uses encryption.
@@ -69,10 +94,14 @@ impl Message {
     /// - `DIDNotResolved` Sender or recipient DID not found.
     /// - `DIDUrlNotResolved` DID doesn't contain mentioned DID Urls (for ex., key id)
     /// - `SecretNotFound` Sender secret is not found.
-    /// - `NoCompatibleCrypto` No compatible keys are found between sender and recipient.
+    /// - `NoCompatibleCrypto` No compatible keys are found between sender and recipient
+    ///   or recipient messaging service doesn't accept DIDComm v2 messages.
+    /// - `IllegalArgument` `options.sign_outside` and `options.protect_sender` are both set
+    ///   for signed authenticated message.
     /// - `Unsupported` Used crypto or method is unsupported.
     /// - `InvalidState` Indicates library error.
     /// - `IOError` IO error during DID or secrets resolving
+    ///   or DID resolver call timed out (see `options.resolver_timeout`)
     /// TODO: verify and update errors list
     pub async fn pack_encrypted<'dr, 'sr>(
         &self,
@@ -84,27 +113,329 @@ impl Message {
         options: &PackEncryptedOptions,
     ) -> Result<(String, PackEncryptedMetadata)> {
         self._validate_pack_encrypted(to, from, sign_by)?;
+
+        self._pack_encrypted(
+            to,
+            None,
+            from,
+            sign_by,
+            did_resolver,
+            secrets_resolver,
+            options,
+        )
+        .await
+    }
+
+    /// Produces `DIDComm Encrypted Message` for the explicitly chosen subset of recipient keys
+    /// instead of all compatible `keyAgreement` keys of the recipient DID
+    /// (for ex. to encrypt only for the recipient's current device).
+    ///
+    /// Works the same way as `pack_encrypted` except the recipient is given as
+    /// a list of key IDs (DID URLs) that must belong to the same DID.
+    /// All keys must be present in `keyAgreement` verification relationship of the recipient
+    /// DID Doc and must be of the same curve, as all recipients of a message share one ephemeral key.
+    ///
+    /// # Params
+    /// - `to_kids` recipient key IDs (DID URLs) of the same DID the message will be encrypted for.
+    /// - other params are the same as for `pack_encrypted`.
+    ///
+    /// # Returns
+    /// Tuple `(encrypted_message, metadata)` as for `pack_encrypted`.
+    /// `metadata.to_kids` contains exactly the given key IDs.
+    ///
+    /// # Errors
+    /// - `IllegalArgument` `to_kids` is empty, contains duplicates, values are not DID URLs
+    ///   or don't belong to the same DID.
+    /// - `DIDUrlNotFound` Some of the keys are not found in recipient `keyAgreement` keys.
+    /// - `NoCompatibleCrypto` Keys are of different curves or not compatible with the sender keys.
+    /// - other errors are the same as for `pack_encrypted`.
+    pub async fn pack_encrypted_to_kids<'dr, 'sr>(
+        &self,
+        to_kids: &[&str],
+        from: Option<&str>,
+        sign_by: Option<&str>,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        options: &PackEncryptedOptions,
+    ) -> Result<(String, PackEncryptedMetadata)> {
+        let to = _to_kids_did(to_kids)?;
+        self._validate_pack_encrypted(to, from, sign_by)?;
+
+        let (msg, metadata) = self
+            ._pack_encrypted(
+                to,
+                Some(to_kids),
+                from,
+                sign_by,
+                did_resolver,
+                secrets_resolver,
+                options,
+            )
+            .await?;
+
+        if metadata.to_kids.len() != to_kids.len() {
+            Err(err_msg(
+                ErrorKind::NoCompatibleCrypto,
+                "Recipient keys are of different curves",
+            ))?
+        }
+
+        Ok((msg, metadata))
+    }
+
+    /// Produces anonymous `DIDComm Encrypted Message` for multiple recipient DIDs at once
+    /// (for ex. for group messaging). Single JWE is produced with recipient entries for keys
+    /// of all recipients, so the message is encrypted only once.
+    ///
+    /// Only anonymous encryption is supported as authenticated encryption proves the sender
+    /// to exactly one recipient. Keys of all recipients must share one curve, as all recipients
+    /// of a message share one ephemeral key. The first curve of the first recipient other
+    /// recipients have `keyAgreement` keys of is used.
+    ///
+    /// As recipients may use different mediators, the message is never wrapped in `Forward`
+    /// messages and `options.forward`, `options.forward_headers`, `options.forward_from`
+    /// and `options.messaging_service` are ignored.
+    ///
+    /// # Params
+    /// - `to` recipient DIDs or key IDs the message will be encrypted for. Must belong to different DIDs.
+    /// - `sign_by` the same as for `pack_encrypted`.
+    /// - `did_resolver` instance of `DIDResolver` to resolve DIDs.
+    /// - `secrets_resolver` instance of SecretsResolver` to resolve signer DID keys secrets.
+    /// - `options` allow fine configuration of packing process and have implemented `Default`.
+    ///
+    /// # Returns
+    /// Tuple `(encrypted_message, metadata)` as for `pack_encrypted`.
+    /// `metadata.to_kids` contains keys of all recipients.
+    ///
+    /// # Errors
+    /// - `IllegalArgument` `to` is empty, contains values of the same DID or values are not DIDs.
+    /// - `NoCompatibleCrypto` Recipients have no `keyAgreement` keys of the same curve.
+    /// - other errors are the same as for `pack_encrypted`.
+    pub async fn pack_encrypted_multi<'dr, 'sr>(
+        &self,
+        to: &[&str],
+        sign_by: Option<&str>,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        options: &PackEncryptedOptions,
+    ) -> Result<(String, PackEncryptedMetadata)> {
+        if to.is_empty() {
+            Err(err_msg(ErrorKind::IllegalArgument, "`to` is empty"))?
+        }
+
+        for (i, to_i) in to.iter().enumerate() {
+            self._validate_pack_encrypted(to_i, None, sign_by)?;
+
+            if to[..i]
+                .iter()
+                .any(|t| did_or_url(t).0 == did_or_url(to_i).0)
+            {
+                Err(err_msg(
+                    ErrorKind::IllegalArgument,
+                    "`to` values must belong to different DIDs",
+                ))?
+            }
+        }
+
+        let timeout_did_resolver = TimeoutDIDResolver::new(did_resolver, options.resolver_timeout);
+        let did_resolver: &dyn DIDResolver =
+            &RelativeDIDUrlsResolver::new(&timeout_did_resolver, options.strict_did_urls);
+
+        let (msg, sign_by_kid, sign_alg, from_prior_issuer_kid) = self
+            ._pack_payload(sign_by, did_resolver, secrets_resolver, options)
+            .await?;
+
+        let (msg, to_kids) = anoncrypt_multi(
+            to,
+            did_resolver,
+            msg.as_bytes(),
+            &options.enc_alg_anon,
+            &options.algorithms,
+            options.nonce_source(),
+            options.session_key_provider(),
+            options.aad.as_deref().map(str::as_bytes),
+            options.preserve_recipient_kids_order,
+        )
+        .await?;
+
+        let msg = _flatten_jwe(msg, options)?;
+
+        let (msg, sign_by_kid, sign_alg) = match sign_by {
+            Some(sign_by) if options.sign_outside => {
+                _sign_encrypted(&msg, sign_by, did_resolver, secrets_resolver, options).await?
+            }
+            _ => (msg, sign_by_kid, sign_alg),
+        };
+
+        let metadata = PackEncryptedMetadata {
+            messaging_service: None,
+            forwarded: false,
+            from_kid: None,
+            sign_by_kid,
+            sign_alg,
+            from_prior_issuer_kid,
+            to_kids,
+            content_hash: _content_hash(&msg, options),
+        };
+
+        Ok((msg, metadata))
+    }
+
+    /// Produces anonymous `DIDComm Encrypted Message` for the given raw public key JWK
+    /// instead of a DID (for ex. for connectionless out-of-band invitations where the recipient
+    /// has no resolvable DID yet, only a public key).
+    ///
+    /// The recipient key isn't resolved and is identified in the message by synthesized
+    /// `did:jwk` key ID (see `did::build_did_jwk_kid`). The recipient must use the same
+    /// key ID for the matching secret to unpack the message.
+    ///
+    /// As the recipient has no DID Doc, the message is never wrapped in `Forward` messages
+    /// and `options.forward`, `options.forward_headers`, `options.forward_from`
+    /// and `options.messaging_service` are ignored.
+    ///
+    /// # Params
+    /// - `to_jwk` recipient public key agreement key (X25519 or P-256) as JWK.
+    /// - `sign_by` the same as for `pack_encrypted`.
+    /// - `did_resolver` instance of `DIDResolver` to resolve DIDs.
+    /// - `secrets_resolver` instance of SecretsResolver` to resolve signer DID keys secrets.
+    /// - `options` allow fine configuration of packing process and have implemented `Default`.
+    ///
+    /// # Returns
+    /// Tuple `(encrypted_message, metadata)` as for `pack_encrypted`.
+    /// `metadata.to_kids` contains synthesized key ID of the recipient key.
+    ///
+    /// # Errors
+    /// - `IllegalArgument` `to_jwk` is not a JSON object or contains private key,
+    ///   `sign_by` is not a valid DID or DID URL.
+    /// - `Unsupported` `to_jwk` isn't X25519 or P-256 key.
+    /// - `Malformed` `to_jwk` is invalid.
+    /// - other errors are the same as for `pack_encrypted`.
+    pub async fn pack_encrypted_to_jwk<'dr, 'sr>(
+        &self,
+        to_jwk: &Value,
+        sign_by: Option<&str>,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        options: &PackEncryptedOptions,
+    ) -> Result<(String, PackEncryptedMetadata)> {
+        match sign_by {
+            Some(sign_by) if !is_did(sign_by) => Err(err_msg(
+                ErrorKind::IllegalArgument,
+                "`sign_from` value is not a valid DID or DID URL",
+            ))?,
+            _ => {}
+        }
+
+        let timeout_did_resolver = TimeoutDIDResolver::new(did_resolver, options.resolver_timeout);
+        let did_resolver: &dyn DIDResolver =
+            &RelativeDIDUrlsResolver::new(&timeout_did_resolver, options.strict_did_urls);
+
+        let (msg, sign_by_kid, sign_alg, from_prior_issuer_kid) = self
+            ._pack_payload(sign_by, did_resolver, secrets_resolver, options)
+            .await?;
+
+        let (msg, to_kids) = anoncrypt_to_jwk(
+            to_jwk,
+            msg.as_bytes(),
+            &options.enc_alg_anon,
+            &options.algorithms,
+            options.nonce_source(),
+            options.session_key_provider(),
+            options.aad.as_deref().map(str::as_bytes),
+        )?;
+
+        let msg = _flatten_jwe(msg, options)?;
+
+        let (msg, sign_by_kid, sign_alg) = match sign_by {
+            Some(sign_by) if options.sign_outside => {
+                _sign_encrypted(&msg, sign_by, did_resolver, secrets_resolver, options).await?
+            }
+            _ => (msg, sign_by_kid, sign_alg),
+        };
+
+        let metadata = PackEncryptedMetadata {
+            messaging_service: None,
+            forwarded: false,
+            from_kid: None,
+            sign_by_kid,
+            sign_alg,
+            from_prior_issuer_kid,
+            to_kids,
+            content_hash: _content_hash(&msg, options),
+        };
+
+        Ok((msg, metadata))
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "pack_encrypted",
+            level = "debug",
+            skip_all,
+            fields(to = %to, from = ?from, sign_by = ?sign_by),
+            err(level = "debug")
+        )
+    )]
+    async fn _pack_encrypted<'dr, 'sr>(
+        &self,
+        to: &str,
+        to_subset: Option<&[&str]>,
+        from: Option<&str>,
+        sign_by: Option<&str>,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        options: &PackEncryptedOptions,
+    ) -> Result<(String, PackEncryptedMetadata)> {
         // TODO: Think how to avoid resolving of did multiple times
         // and perform async operations in parallel
 
-        let (msg, sign_by_kid) = if let Some(sign_by) = sign_by {
-            let (msg, PackSignedMetadata { sign_by_kid }) = self
-                .pack_signed(sign_by, did_resolver, secrets_resolver)
-                .await
-                .context("Unable produce sign envelope")?;
+        _check_sign_outside(from, sign_by, options)?;
 
-            (msg, Some(sign_by_kid))
-        } else {
-            let msg = self
-                .pack_plaintext(did_resolver)
-                .await
-                .context("Unable produce plaintext")?;
-            (msg, None)
+        let timeout_did_resolver = TimeoutDIDResolver::new(did_resolver, options.resolver_timeout);
+        let did_resolver: &dyn DIDResolver =
+            &RelativeDIDUrlsResolver::new(&timeout_did_resolver, options.strict_did_urls);
+
+        let prefixed_kids = match (to_subset, options.recipient_kid_prefix.as_deref()) {
+            (None, Some(prefix)) => {
+                Some(_recipient_kids_with_prefix(to, prefix, did_resolver).await?)
+            }
+            _ => None,
         };
 
+        let prefixed_kids: Option<Vec<&str>> = prefixed_kids
+            .as_ref()
+            .map(|kids| kids.iter().map(String::as_str).collect());
+
+        let to_subset = prefixed_kids.as_deref().or(to_subset);
+
+        // Keys are selected the same way as on encryption and signing below,
+        // so key reuse is rejected before any crypto is performed
+        if let (true, Some(from), Some(sign_by)) = (options.reject_key_reuse, from, sign_by) {
+            let sign_by_kid = sign_kid(sign_by, did_resolver, Some(secrets_resolver)).await?;
+
+            let (_, from_key, _) = authcrypt_keys(
+                to,
+                to_subset,
+                from,
+                did_resolver,
+                Some(secrets_resolver),
+                &options.algorithms,
+                options.preserve_recipient_kids_order,
+            )
+            .await?;
+
+            _check_key_reuse(&sign_by_kid, &from_key.id, did_resolver).await?;
+        }
+
+        let (msg, sign_by_kid, sign_alg, from_prior_issuer_kid) = self
+            ._pack_payload(sign_by, did_resolver, secrets_resolver, options)
+            .await?;
+
         let (msg, from_kid, to_kids) = if let Some(from) = from {
             let (msg, from_kid, to_kids) = authcrypt(
                 to,
+                to_subset,
                 from,
                 did_resolver,
                 secrets_resolver,
@@ -112,33 +443,177 @@ impl Message {
                 &options.enc_alg_auth,
                 &options.enc_alg_anon,
                 options.protect_sender,
+                &options.algorithms,
+                options.nonce_source(),
+                options.aad.as_deref().map(str::as_bytes),
+                options.preserve_recipient_kids_order,
             )
             .await?;
 
             (msg, Some(from_kid), to_kids)
         } else {
-            let (msg, to_kids) =
-                anoncrypt(to, did_resolver, msg.as_bytes(), &options.enc_alg_anon).await?;
+            let (msg, to_kids) = anoncrypt(
+                to,
+                to_subset,
+                did_resolver,
+                msg.as_bytes(),
+                &options.enc_alg_anon,
+                &options.algorithms,
+                options.nonce_source(),
+                options.session_key_provider(),
+                options.aad.as_deref().map(str::as_bytes),
+                options.preserve_recipient_kids_order,
+            )
+            .await?;
 
             (msg, None, to_kids)
         };
 
-        let (msg, messaging_service) =
-            match wrap_in_forward_if_needed(&msg, to, did_resolver, options).await? {
-                Some((forward_msg, messaging_service)) => (forward_msg, Some(messaging_service)),
-                None => (msg, None),
+        let msg = _flatten_jwe(msg, options)?;
+
+        let (msg, sign_by_kid, sign_alg) = match sign_by {
+            Some(sign_by) if options.sign_outside => {
+                _sign_encrypted(&msg, sign_by, did_resolver, secrets_resolver, options).await?
+            }
+            _ => (msg, sign_by_kid, sign_alg),
+        };
+
+        let (msg, messaging_service, forwarded) =
+            match wrap_in_forward_if_needed(&msg, to, did_resolver, secrets_resolver, options)
+                .await?
+            {
+                Some((forward_msg, messaging_service)) => {
+                    (forward_msg, Some(messaging_service), true)
+                }
+                None => (msg, None, false),
             };
 
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            to_kids = ?to_kids,
+            from_kid = ?from_kid,
+            sign_by_kid = ?sign_by_kid,
+            forwarded,
+            "message packed"
+        );
+
         let metadata = PackEncryptedMetadata {
             messaging_service,
+            forwarded,
             from_kid,
             sign_by_kid,
+            sign_alg,
+            from_prior_issuer_kid,
             to_kids,
+            content_hash: _content_hash(&msg, options),
         };
 
         Ok((msg, metadata))
     }
 
+    /// Produces signed or plaintext message to be encrypted.
+    /// The message isn't signed if `options.sign_outside` is set as encrypted message is signed instead.
+    /// If `options.from_prior` is set, signed `from_prior` header is added to the message first.
+    async fn _pack_payload<'dr, 'sr>(
+        &self,
+        sign_by: Option<&str>,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        options: &PackEncryptedOptions,
+    ) -> Result<(String, Option<String>, Option<SignAlg>, Option<String>)> /* (msg, sign_by_kid, sign_alg, from_prior_issuer_kid) */
+    {
+        let (msg, from_prior_issuer_kid) = match options.from_prior {
+            Some(ref from_prior) => {
+                let (msg, from_prior_issuer_kid) = self
+                    ._with_from_prior(
+                        from_prior,
+                        did_resolver,
+                        secrets_resolver,
+                        &options.algorithms,
+                    )
+                    .await?;
+
+                (Some(msg), Some(from_prior_issuer_kid))
+            }
+            None => (None, None),
+        };
+
+        let msg = msg.as_ref().unwrap_or(self);
+
+        let res = match sign_by {
+            Some(sign_by) if !options.sign_outside => {
+                let (msg, PackSignedMetadata { sign_by_kid }, sign_alg) = msg
+                    ._pack_signed(
+                        sign_by,
+                        did_resolver,
+                        secrets_resolver,
+                        &SignOptions {
+                            algorithms: options.algorithms.clone(),
+                            ..SignOptions::default()
+                        },
+                    )
+                    .await
+                    .context("Unable produce sign envelope")?;
+
+                (msg, Some(sign_by_kid), sign_alg, from_prior_issuer_kid)
+            }
+            _ => {
+                let msg = msg
+                    ._pack_plaintext(did_resolver, &options.algorithms)
+                    .await
+                    .context("Unable produce plaintext")?;
+                (msg, None, None, from_prior_issuer_kid)
+            }
+        };
+
+        Ok(res)
+    }
+
+    /// Returns copy of the message with signed `from_prior` built from the given spec
+    /// and identifier of the key used for `from_prior` signing.
+    pub(crate) async fn _with_from_prior<'dr, 'sr>(
+        &self,
+        from_prior: &FromPriorSpec,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        algorithms: &AlgorithmRegistry,
+    ) -> Result<(Message, String)> {
+        let (from_prior, from_prior_issuer_kid) = self
+            ._build_from_prior(from_prior)?
+            ._pack(
+                from_prior.issuer_kid.as_deref(),
+                did_resolver,
+                secrets_resolver,
+                algorithms,
+            )
+            .await
+            .context("Unable produce from_prior")?;
+
+        let mut msg = self.clone();
+        msg.from_prior = Some(from_prior);
+
+        Ok((msg, from_prior_issuer_kid))
+    }
+
+    /// Builds `from_prior` header of the message described by the given spec.
+    fn _build_from_prior(&self, from_prior: &FromPriorSpec) -> Result<FromPrior> {
+        if self.from_prior.is_some() {
+            Err(err_msg(
+                ErrorKind::IllegalArgument,
+                "`message.from_prior` is already set",
+            ))?
+        }
+
+        let sub = self.from.as_ref().ok_or_else(|| {
+            err_msg(
+                ErrorKind::IllegalArgument,
+                "`message.from` is required for `from_prior`",
+            )
+        })?;
+
+        Ok(FromPrior::build(from_prior.iss.clone(), sub.clone()).finalize())
+    }
+
     fn _validate_pack_encrypted(
         &self,
         to: &str,
@@ -192,6 +667,228 @@ impl Message {
     }
 }
 
+/// Checks that `options.sign_outside` doesn't disclose the sender protected by `options.protect_sender`.
+fn _check_sign_outside(
+    from: Option<&str>,
+    sign_by: Option<&str>,
+    options: &PackEncryptedOptions,
+) -> Result<()> {
+    if options.sign_outside && options.protect_sender && from.is_some() && sign_by.is_some() {
+        Err(err_msg(
+            ErrorKind::IllegalArgument,
+            "`sign_outside` can't be used with `protect_sender` as outer signature discloses the sender",
+        ))?
+    }
+
+    Ok(())
+}
+
+/// Checks that signing and sender encryption keys aren't the same key
+/// (see `PackEncryptedOptions::reject_key_reuse`).
+async fn _check_key_reuse<'dr>(
+    sign_by_kid: &str,
+    from_kid: &str,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+) -> Result<()> {
+    if is_same_key(sign_by_kid, from_kid, did_resolver).await? {
+        Err(err_msg(
+            ErrorKind::IllegalArgument,
+            format!(
+                "Signing key {} and encryption key {} are the same key",
+                sign_by_kid, from_kid
+            ),
+        ))?
+    }
+
+    Ok(())
+}
+
+/// Signs encrypted message with `sign_by` key (see `PackEncryptedOptions::sign_outside`).
+async fn _sign_encrypted<'dr, 'sr>(
+    msg: &str,
+    sign_by: &str,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+    options: &PackEncryptedOptions,
+) -> Result<(String, Option<String>, Option<SignAlg>)> /* (msg, sign_by_kid, sign_alg) */ {
+    let (msg, sign_by_kid, sign_alg) = sign_payload(
+        msg.as_bytes(),
+        sign_by,
+        did_resolver,
+        secrets_resolver,
+        &options.algorithms,
+    )
+    .await
+    .context("Unable produce outer sign envelope")?;
+
+    Ok((msg, Some(sign_by_kid), sign_alg))
+}
+
+/// Re-serializes the encrypted message in flattened JWE JSON serialization
+/// if `options.flattened_jwe` is set and the message has a single recipient.
+fn _flatten_jwe(msg: String, options: &PackEncryptedOptions) -> Result<String> {
+    if !options.flattened_jwe {
+        return Ok(msg);
+    }
+
+    let flattened = JWE::from_str(&msg)?
+        .to_flattened()
+        .map(|jwe| serde_json::to_string(&jwe))
+        .transpose()
+        .kind(ErrorKind::InvalidState, "Unable serialize jwe")?;
+
+    Ok(flattened.unwrap_or(msg))
+}
+
+/// Hex encoded SHA-256 hash of the packed message if `options.content_hash` is set.
+pub(crate) fn _content_hash(msg: &str, options: &PackEncryptedOptions) -> Option<String> {
+    if options.content_hash {
+        Some(format!("{:x}", Sha256::digest(msg.as_bytes())))
+    } else {
+        None
+    }
+}
+
+/// Returns recipient `keyAgreement` key IDs starting with the given prefix.
+/// If `to` is a key ID, the key itself is returned if it matches.
+async fn _recipient_kids_with_prefix<'dr>(
+    to: &str,
+    prefix: &str,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+) -> Result<Vec<String>> {
+    let (to_did, to_kid) = did_or_url(to);
+
+    let to_ddoc = did_resolver
+        .resolve(to_did)
+        .await
+        .context("Unable resolve recipient did")?
+        .ok_or_else(|| err_msg(ErrorKind::DIDNotResolved, "Recipient did not found"))?;
+
+    let kids: Vec<_> = to_ddoc
+        .key_agreements
+        .iter()
+        .filter(|kid| to_kid.map(|to_kid| kid == &to_kid).unwrap_or(true))
+        .filter(|kid| kid.starts_with(prefix))
+        .cloned()
+        .collect();
+
+    if kids.is_empty() {
+        Err(key_agreements_not_found(
+            &format!("No recipient key agreements with prefix `{}` found", prefix),
+            &to_ddoc,
+        ))?
+    }
+
+    Ok(kids)
+}
+
+/// Returns DID all given recipient key IDs belong to.
+fn _to_kids_did<'a>(to_kids: &[&'a str]) -> Result<&'a str> {
+    let to = match to_kids.first() {
+        Some(&to) => did_or_url(to).0,
+        None => Err(err_msg(ErrorKind::IllegalArgument, "`to_kids` is empty"))?,
+    };
+
+    for (i, kid) in to_kids.iter().enumerate() {
+        match did_or_url(kid) {
+            (did, Some(_)) if is_did(kid) && did == to => {}
+            (_, Some(_)) if is_did(kid) => Err(err_msg(
+                ErrorKind::IllegalArgument,
+                "`to_kids` values must belong to the same DID",
+            ))?,
+            _ => Err(err_msg(
+                ErrorKind::IllegalArgument,
+                "`to_kids` value is not a valid DID URL",
+            ))?,
+        }
+
+        if to_kids[..i].contains(kid) {
+            Err(err_msg(
+                ErrorKind::IllegalArgument,
+                "`to_kids` contains duplicates",
+            ))?
+        }
+    }
+
+    Ok(to)
+}
+
+/// Resolves recipient key agreement verification methods the message is encrypted for.
+/// `to_kid` is the key ID of `to` if it's a DID URL. `missing_key_err` builds the error
+/// returned for a key which verification material isn't found.
+async fn _recipient_keys<'dr>(
+    to_ddoc: &DIDDoc,
+    to_kid: Option<&str>,
+    to_subset: Option<&[&str]>,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    algorithms: &AlgorithmRegistry,
+    missing_key_err: impl Fn(&str) -> Error,
+) -> Result<Vec<VerificationMethod>> {
+    // Initial list of recipient key ids is all key_agreements of recipient did doc
+    // or one key if url was explicitly provided
+    // or keys of explicitly provided subset
+    // Explicitly provided key of the DID doc is taken as is even if it isn't declared
+    // as key agreement, so key of other verification relationship is rejected
+    // by key agreement check below instead of being reported as not found
+    let own_to_kid = to_kid.filter(|&to_kid| {
+        to_ddoc
+            .verification_methods
+            .iter()
+            .any(|vm| vm.id == to_kid)
+    });
+
+    let to_kids: Vec<_> = match own_to_kid {
+        Some(to_kid) => vec![to_kid],
+        None => to_ddoc
+            .key_agreements
+            .iter()
+            .map(|s| s.as_str())
+            .filter(|&kid| to_kid.map(|to_kid| kid == to_kid).unwrap_or(true))
+            .collect(),
+    };
+
+    let to_kids: Vec<_> = to_kids
+        .into_iter()
+        .filter(|kid| {
+            to_subset
+                .map(|to_subset| to_subset.contains(kid))
+                .unwrap_or(true)
+        })
+        .collect();
+
+    if to_kids.is_empty() {
+        Err(key_agreements_not_found(
+            "No recipient key agreements found",
+            to_ddoc,
+        ))?
+    }
+
+    match to_subset {
+        Some(to_subset) if to_subset.len() != to_kids.len() => Err(key_agreements_not_found(
+            "Not all recipient key agreements found",
+            to_ddoc,
+        ))?,
+        _ => {}
+    }
+
+    // Resolve materials for recipient keys
+    let mut to_keys = Vec::with_capacity(to_kids.len());
+
+    for kid in to_kids {
+        let to_key = find_verification_method(to_ddoc, kid, did_resolver)
+            .await
+            .context("Unable resolve recipient key")?
+            .ok_or_else(|| missing_key_err(kid))?;
+
+        check_key_agreement_method(to_ddoc, &to_key, algorithms)
+            .context("Invalid recipient key agreement")?;
+
+        to_keys.push(to_key);
+    }
+
+    Ok(to_keys)
+}
+
 /// Allow fine configuration of packing process.
 #[derive(Debug, PartialEq, Eq, Deserialize, Clone)]
 pub struct PackEncryptedOptions {
@@ -206,12 +903,22 @@ pub struct PackEncryptedOptions {
     pub forward: bool,
 
     /// if forward is enabled these optional headers can be passed to the wrapping `Forward` messages.
+    /// `expires_time` and `created_time` headers set corresponding fields of `Forward` messages
+    /// (for ex. for scheduled delivery by mediators) and must be non-negative integers.
     /// If forward is disabled this property will be ignored.
     pub forward_headers: Option<HashMap<String, Value>>,
 
+    /// If forward is enabled and this DID or key ID (DID URL) of the sender is present,
+    /// the outermost `Forward` message is authcrypted (instead of anoncrypted) for the first mediator,
+    /// so the mediator can authenticate the sender (for ex. to authorize or bill it).
+    /// Inner `Forward` messages for other mediators are still anoncrypted.
+    /// If forward is disabled this property will be ignored.
+    pub forward_from: Option<String>,
+
     /// Identifier (DID URL) of messaging service (https://identity.foundation/didcomm-messaging/spec/#did-document-service-endpoint).
     /// If DID doc contains multiple messaging services it allows specify what service to use.
-    /// If not present first service will be used.
+    /// If not present the first service accepting `didcomm/v2` profile will be used,
+    /// services that don't declare `accept` are used only if there is no such service.
     pub messaging_service: Option<String>,
 
     /// Algorithm used for authenticated encryption
@@ -221,17 +928,133 @@ pub struct PackEncryptedOptions {
     /// Algorithm used for anonymous encryption
     #[serde(default)]
     pub enc_alg_anon: AnonCryptAlg,
-}
 
-impl Default for PackEncryptedOptions {
-    fn default() -> Self {
-        PackEncryptedOptions {
-            protect_sender: false,
+    /// If `true` and `sign_by` is set, the encrypted message is signed (encrypt-then-sign)
+    /// instead of the plaintext being signed before encryption (sign-then-encrypt).
+    /// Only needed for interoperability with implementations expecting this structure.
+    ///
+    /// Security implications:
+    /// - the outer signature is visible to anyone including mediators, so the signer
+    ///   identity is disclosed and can't be protected by `protect_sender` (such combination is rejected);
+    /// - the signature covers the ciphertext, not the plaintext, so it doesn't prove to third parties
+    ///   the signer produced the plaintext, only that the signer sent this encrypted message;
+    /// - the signature can be stripped or replaced by anyone in transit without breaking decryption,
+    ///   so recipients expecting a signature must check `UnpackMetadata.non_repudiation`.
+    ///
+    /// False by default.
+    #[serde(default)]
+    pub sign_outside: bool,
+
+    /// If set, signed `from_prior` header linking the sender prior DID to the message `from` DID
+    /// is added to the message before signing and encryption, so the recipient learns about
+    /// the sender DID rotation. Message `from` must be set and message `from_prior` must not.
+    pub from_prior: Option<FromPriorSpec>,
+
+    /// Maximum duration of each `DIDResolver` call. Calls that don't complete in time
+    /// fail packing with `IOError`. Milliseconds if deserialized. No limit by default.
+    #[serde(default, deserialize_with = "crate::utils::serde::_duration_ms")]
+    pub resolver_timeout: Option<Duration>,
+
+    /// If `true`, DID Docs using relative DID URLs (for ex. `#key-1`) for verification methods
+    /// are rejected with `Malformed` error. Otherwise relative DID URLs are expanded against the DID.
+    /// False by default.
+    #[serde(default)]
+    pub strict_did_urls: bool,
+
+    /// Additional authenticated data (JWE `aad`) binding external context (for ex. a session
+    /// or transport identifier) to the encrypted message. It isn't encrypted and is visible to anyone,
+    /// but is covered by the authentication tag, so the message can't be decrypted if it is tampered.
+    /// The recipient gets it in `UnpackMetadata::aad`. Not applied to `Forward` envelopes. None by default.
+    pub aad: Option<String>,
+
+    /// If `true`, packing fails with `IllegalArgument` error if `sign_by` and `from` resolve
+    /// to the same key material (for ex. the same P-256 key declared for both authentication
+    /// and key agreement). Using the same key for signing and key agreement is discouraged.
+    /// Checked before the message is signed or encrypted and by `Message::pack_encrypted_plan`.
+    /// False by default.
+    #[serde(default)]
+    pub reject_key_reuse: bool,
+
+    /// If `true`, recipient keys are used in the order of recipient DID Doc `keyAgreement`.
+    /// Otherwise recipient keys are sorted by key ID, so JWE recipients and `to_kids`
+    /// of the metadata are reproducible regardless of DID Doc ordering.
+    /// False by default.
+    #[serde(default)]
+    pub preserve_recipient_kids_order: bool,
+
+    /// If set, the message is encrypted only for recipient `keyAgreement` keys which key IDs
+    /// (DID URLs) start with the given prefix (for ex. `did:example:bob#device-`) instead of
+    /// all recipient keys. Packing fails with `DIDUrlNotFound` error if no key matches.
+    /// Ignored if recipient keys are given explicitly (`pack_encrypted_to_kids`) and for
+    /// multiple recipients (`pack_encrypted_multi`). None by default.
+    pub recipient_kid_prefix: Option<String>,
+
+    /// If `true`, hex encoded SHA-256 hash of the produced message is returned
+    /// in `PackEncryptedMetadata::content_hash` (for ex. to be used as a cache key or ETag).
+    /// False by default.
+    #[serde(default)]
+    pub content_hash: bool,
+
+    /// If `true`, the encrypted message for a single recipient key is produced in flattened
+    /// JWE JSON serialization (recipient header and encrypted key at top level instead of
+    /// `recipients` array), which is more compact. Messages for multiple recipient keys
+    /// are always produced in general JWE JSON serialization. `unpack` accepts both forms.
+    /// Not applied to `Forward` envelopes. False by default.
+    #[serde(default)]
+    pub flattened_jwe: bool,
+
+    /// Custom source of content encryption IVs (nonces). OS CSPRNG is used if not set.
+    /// Nonce misuse (for ex. reuse) breaks confidentiality and integrity of packed messages,
+    /// see `NonceSource` for requirements. Not available in FFI bindings.
+    #[serde(skip)]
+    pub nonce_source: Option<Arc<dyn NonceSource>>,
+
+    /// Custom source of content encryption keys for anonymous encryption (for ex. a ratchet
+    /// deriving per-message keys from a shared session secret). A fresh random key is generated
+    /// for each message if not set. Not used for authenticated encryption and Forward envelopes,
+    /// see `SessionKeyProvider` for requirements. Not available in FFI bindings.
+    #[serde(skip)]
+    pub session_key_provider: Option<Arc<dyn SessionKeyProvider>>,
+
+    /// Signature and key agreement algorithms consulted before built-in ones on signing
+    /// and anonymous encryption. No registered algorithms by default. Not available in FFI bindings.
+    #[serde(skip)]
+    pub algorithms: AlgorithmRegistry,
+}
+
+impl PackEncryptedOptions {
+    pub(crate) fn nonce_source(&self) -> Option<&dyn NonceSource> {
+        self.nonce_source.as_deref()
+    }
+
+    pub(crate) fn session_key_provider(&self) -> Option<&dyn SessionKeyProvider> {
+        self.session_key_provider.as_deref()
+    }
+}
+
+impl Default for PackEncryptedOptions {
+    fn default() -> Self {
+        PackEncryptedOptions {
+            protect_sender: false,
             forward: true,
             forward_headers: None,
+            forward_from: None,
             messaging_service: None,
             enc_alg_auth: AuthCryptAlg::default(),
             enc_alg_anon: AnonCryptAlg::default(),
+            sign_outside: false,
+            from_prior: None,
+            resolver_timeout: None,
+            strict_did_urls: false,
+            aad: None,
+            reject_key_reuse: false,
+            preserve_recipient_kids_order: false,
+            recipient_kid_prefix: None,
+            content_hash: false,
+            flattened_jwe: false,
+            nonce_source: None,
+            session_key_provider: None,
+            algorithms: AlgorithmRegistry::default(),
         }
     }
 }
@@ -244,14 +1067,31 @@ pub struct PackEncryptedMetadata {
     /// Practically `service_endpoint` field can be used to transport the message.
     pub messaging_service: Option<MessagingServiceMetadata>,
 
+    /// Whether the message is wrapped in Forward envelope(s) for mediator(s)
+    /// instead of being packed for the recipient directly.
+    pub forwarded: bool,
+
     /// Identifier (DID URL) of sender key used for message encryption.
     pub from_kid: Option<String>,
 
     /// Identifier (DID URL) of sender key used for message sign.
     pub sign_by_kid: Option<String>,
 
+    /// Algorithm used for signing of inner message (or of encrypted message
+    /// if `options.sign_outside` was set) if `sign_by` was set.
+    /// None for signature algorithms registered by application (see `AlgorithmRegistry`).
+    pub sign_alg: Option<SignAlg>,
+
+    /// Identifier (DID URL) of prior DID key used for `from_prior` signing
+    /// if `options.from_prior` was set.
+    pub from_prior_issuer_kid: Option<String>,
+
     /// Identifiers (DID URLs) of recipient keys used for message encryption.
     pub to_kids: Vec<String>,
+
+    /// Hex encoded SHA-256 hash of the produced message if `options.content_hash` was set.
+    /// Encryption isn't deterministic, so the hash is of the actual returned message.
+    pub content_hash: Option<String>,
 }
 
 /// Information about messaging service used for message preparation.
@@ -267,11 +1107,20 @@ pub struct MessagingServiceMetadata {
 
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashMap, iter::FromIterator};
+    use std::{
+        collections::HashMap,
+        iter::FromIterator,
+        str::FromStr,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
 
     use askar_crypto::{
         alg::{
-            aes::{A256CbcHs512, A256Gcm, A256Kw, AesKey},
+            aes::{A128CbcHs256, A128Gcm, A256CbcHs512, A256Gcm, A256Kw, AesKey},
             chacha20::{Chacha20Key, XC20P},
             ed25519::Ed25519KeyPair,
             k256::K256KeyPair,
@@ -285,37 +1134,52 @@ mod tests {
     };
 
     use serde_json::{json, Value};
+    use sha2::{Digest, Sha256};
 
     use crate::{
-        algorithms::AnonCryptAlg,
-        did::{resolvers::ExampleDIDResolver, VerificationMaterial, VerificationMethod},
-        error::ErrorKind,
-        jwe,
+        algorithms::{AnonCryptAlg, AuthCryptAlg, SignAlg},
+        did::{
+            build_did_jwk_kid,
+            resolvers::{ExampleDIDResolver, SlowDIDResolver},
+            DIDCommMessagingService, DIDDoc, Service, ServiceKind, VerificationMaterial,
+            VerificationMethod,
+        },
+        error::{err_msg, ErrorKind, Result},
+        jwe::{self, set_deterministic_rng, NonceSource, SessionKeyProvider},
         jwk::{FromJwkValue, ToJwkValue},
         jws,
         message::MessagingServiceMetadata,
-        protocols::routing::{try_parse_forward, wrap_in_forward},
-        secrets::{resolvers::ExampleSecretsResolver, Secret, SecretMaterial},
+        protocols::{
+            routing::{
+                try_parse_forward, wrap_in_forward, wrap_in_forward_chain,
+                wrap_in_forward_with_options, WrapInForwardOptions,
+            },
+            test_support::bob_service,
+        },
+        secrets::{resolvers::ExampleSecretsResolver, Secret, SecretMaterial, SecretsResolver},
         test_vectors::{
             ALICE_AUTH_METHOD_25519, ALICE_AUTH_METHOD_P256, ALICE_AUTH_METHOD_SECPP256K1,
             ALICE_DID, ALICE_DID_DOC, ALICE_DID_DOC_WITH_NO_SECRETS, ALICE_SECRETS,
-            ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256, ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
-            BOB_DID, BOB_DID_COMM_MESSAGING_SERVICE, BOB_DID_DOC, BOB_DID_DOC_NO_SECRETS,
-            BOB_SECRETS, BOB_SECRET_KEY_AGREEMENT_KEY_P256_1, BOB_SECRET_KEY_AGREEMENT_KEY_P256_2,
-            BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1, BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2,
-            BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3, BOB_SERVICE, CHARLIE_DID, CHARLIE_DID_DOC,
-            CHARLIE_ROTATED_TO_ALICE_SECRETS, CHARLIE_SECRETS, CHARLIE_SECRET_AUTH_KEY_ED25519,
+            ALICE_SECRET_KEY_AGREEMENT_KEY_P256, ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519, BOB_DID, BOB_DID_COMM_MESSAGING_SERVICE,
+            BOB_DID_DOC, BOB_DID_DOC_NO_SECRETS, BOB_SECRETS, BOB_SECRET_KEY_AGREEMENT_KEY_P256_1,
+            BOB_SECRET_KEY_AGREEMENT_KEY_P256_2, BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1,
+            BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2, BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3,
+            BOB_SERVICE, CHARLIE_DID, CHARLIE_DID_DOC, CHARLIE_ROTATED_TO_ALICE_SECRETS,
+            CHARLIE_SECRETS, CHARLIE_SECRET_AUTH_KEY_ED25519,
             CHARLIE_SECRET_KEY_AGREEMENT_KEY_X25519, CHARLIE_SERVICE, FROM_PRIOR_FULL,
-            MEDIATOR1_DID_DOC, MEDIATOR1_SECRETS, MEDIATOR2_DID_DOC, MEDIATOR2_SECRETS,
-            MEDIATOR2_VERIFICATION_METHOD_KEY_AGREEM_X25519_1,
+            MEDIATOR1_DID_DOC, MEDIATOR1_SECRETS,
+            MEDIATOR1_VERIFICATION_METHOD_KEY_AGREEM_X25519_1, MEDIATOR2_DID_DOC,
+            MEDIATOR2_SECRETS, MEDIATOR2_VERIFICATION_METHOD_KEY_AGREEM_X25519_1,
             MEDIATOR3_DID_COMM_MESSAGING_SERVICE, MEDIATOR3_DID_DOC, MEDIATOR3_SECRETS,
-            MESSAGE_FROM_PRIOR_FULL, MESSAGE_SIMPLE, PLAINTEXT_MSG_SIMPLE,
+            MESSAGE_FROM_PRIOR_FULL, MESSAGE_MINIMAL, MESSAGE_SIMPLE, PLAINTEXT_MSG_SIMPLE,
         },
         utils::{
             crypto::{JoseKDF, KeyWrap},
             did::did_or_url,
         },
-        Message, PackEncryptedMetadata, PackEncryptedOptions, UnpackOptions,
+        FromPrior, FromPriorSpec, Message, PackEncryptedMetadata, PackEncryptedOptions,
+        UnpackOptions,
     };
 
     #[tokio::test]
@@ -334,6 +1198,8 @@ mod tests {
             ],
             ALICE_DID,
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            jwe::EncAlgorithm::A256cbcHs512,
         )
         .await;
 
@@ -347,6 +1213,8 @@ mod tests {
             vec![&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2],
             ALICE_DID,
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            jwe::EncAlgorithm::A256cbcHs512,
         )
         .await;
 
@@ -360,6 +1228,8 @@ mod tests {
             vec![&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2],
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            jwe::EncAlgorithm::A256cbcHs512,
         )
         .await;
 
@@ -376,6 +1246,8 @@ mod tests {
             ],
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            jwe::EncAlgorithm::A256cbcHs512,
         )
         .await;
 
@@ -389,6 +1261,8 @@ mod tests {
             vec![&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1],
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            jwe::EncAlgorithm::A256cbcHs512,
         )
         .await;
 
@@ -402,6 +1276,8 @@ mod tests {
             vec![&BOB_SECRET_KEY_AGREEMENT_KEY_P256_2],
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            jwe::EncAlgorithm::A256cbcHs512,
         )
         .await;
 
@@ -418,6 +1294,8 @@ mod tests {
             ],
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            jwe::EncAlgorithm::A256cbcHs512,
         )
         .await;
 
@@ -431,6 +1309,138 @@ mod tests {
             vec![&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1],
             ALICE_DID,
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            jwe::EncAlgorithm::A256cbcHs512,
+        )
+        .await;
+
+        _pack_encrypted_works_authcrypt::<
+            AesKey<A128CbcHs256>,
+            Ecdh1PU<'_, X25519KeyPair>,
+            X25519KeyPair,
+            AesKey<A256Kw>,
+        >(
+            BOB_DID,
+            vec![
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3,
+            ],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
+            AuthCryptAlg::A128cbcHs256Ecdh1puA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        _pack_encrypted_works_authcrypt::<
+            AesKey<A128CbcHs256>,
+            Ecdh1PU<'_, X25519KeyPair>,
+            X25519KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
+            AuthCryptAlg::A128cbcHs256Ecdh1puA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        _pack_encrypted_works_authcrypt::<
+            AesKey<A128CbcHs256>,
+            Ecdh1PU<'_, X25519KeyPair>,
+            X25519KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
+            AuthCryptAlg::A128cbcHs256Ecdh1puA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        _pack_encrypted_works_authcrypt::<
+            AesKey<A128CbcHs256>,
+            Ecdh1PU<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+        >(
+            BOB_DID,
+            vec![
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2,
+            ],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            AuthCryptAlg::A128cbcHs256Ecdh1puA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        _pack_encrypted_works_authcrypt::<
+            AesKey<A128CbcHs256>,
+            Ecdh1PU<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            AuthCryptAlg::A128cbcHs256Ecdh1puA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        _pack_encrypted_works_authcrypt::<
+            AesKey<A128CbcHs256>,
+            Ecdh1PU<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_P256_2],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            AuthCryptAlg::A128cbcHs256Ecdh1puA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        _pack_encrypted_works_authcrypt::<
+            AesKey<A128CbcHs256>,
+            Ecdh1PU<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+        >(
+            BOB_DID,
+            vec![
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2,
+            ],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            AuthCryptAlg::A128cbcHs256Ecdh1puA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        _pack_encrypted_works_authcrypt::<
+            AesKey<A128CbcHs256>,
+            Ecdh1PU<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            AuthCryptAlg::A128cbcHs256Ecdh1puA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
         )
         .await;
 
@@ -439,6 +1449,8 @@ mod tests {
             to_keys: Vec<&Secret>,
             from: &str,
             from_key: &VerificationMethod,
+            enc_alg_auth: AuthCryptAlg,
+            enc_alg_auth_jwe: jwe::EncAlgorithm,
         ) where
             CE: KeyAeadInPlace + KeySecretBytes,
             KDF: JoseKDF<KE, KW>,
@@ -459,6 +1471,7 @@ mod tests {
                     &secrets_resolver,
                     &PackEncryptedOptions {
                         forward: false,
+                        enc_alg_auth,
                         ..PackEncryptedOptions::default()
                     },
                 )
@@ -469,13 +1482,18 @@ mod tests {
                 metadata,
                 PackEncryptedMetadata {
                     messaging_service: None,
+                    forwarded: false,
                     from_kid: Some(from_key.id.clone()),
                     sign_by_kid: None,
+                    sign_alg: None,
+                    from_prior_issuer_kid: None,
                     to_kids: to_keys.iter().map(|s| s.id.clone()).collect::<Vec<_>>(),
+                    content_hash: None,
                 }
             );
 
-            let msg = _verify_authcrypt::<CE, KDF, KE, KW>(&msg, to_keys, from_key);
+            let msg =
+                _verify_authcrypt::<CE, KDF, KE, KW>(&msg, to_keys, from_key, enc_alg_auth_jwe);
             _verify_plaintext(&msg, PLAINTEXT_MSG_SIMPLE);
         }
     }
@@ -737,83 +1755,15 @@ mod tests {
         )
         .await;
 
-        async fn _pack_encrypted_works_authcrypt_protected_sender<
-            CE,
-            KDF,
-            KE,
-            KW,
-            ACE,
-            AKDF,
-            AKE,
-            AKW,
-        >(
-            to: &str,
-            to_keys: Vec<&Secret>,
-            from: &str,
-            from_key: &VerificationMethod,
-            enc_alg_anon: AnonCryptAlg,
-            enc_alg_anon_jwe: jwe::EncAlgorithm,
-        ) where
-            CE: KeyAeadInPlace + KeySecretBytes,
-            KDF: JoseKDF<KE, KW>,
-            KE: KeyExchange + KeyGen + ToJwkValue + FromJwkValue,
-            KW: KeyWrap + FromKeyDerivation,
-            ACE: KeyAeadInPlace + KeySecretBytes,
-            AKDF: JoseKDF<AKE, AKW>,
-            AKE: KeyExchange + KeyGen + ToJwkValue + FromJwkValue,
-            AKW: KeyWrap + FromKeyDerivation,
-        {
-            let did_resolver =
-                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
-
-            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
-
-            let (msg, metadata) = MESSAGE_SIMPLE
-                .pack_encrypted(
-                    to,
-                    Some(from),
-                    None,
-                    &did_resolver,
-                    &secrets_resolver,
-                    &PackEncryptedOptions {
-                        forward: false,
-                        protect_sender: true,
-                        enc_alg_anon,
-                        ..PackEncryptedOptions::default()
-                    },
-                )
-                .await
-                .expect("encrypt is ok.");
-
-            assert_eq!(
-                metadata,
-                PackEncryptedMetadata {
-                    messaging_service: None,
-                    from_kid: Some(from_key.id.clone()),
-                    sign_by_kid: None,
-                    to_kids: to_keys.iter().map(|s| s.id.clone()).collect::<Vec<_>>(),
-                }
-            );
-
-            let msg =
-                _verify_anoncrypt::<ACE, AKDF, AKE, AKW>(&msg, to_keys.clone(), enc_alg_anon_jwe);
-            let msg = _verify_authcrypt::<CE, KDF, KE, KW>(&msg, to_keys, from_key);
-            _verify_plaintext(&msg, PLAINTEXT_MSG_SIMPLE);
-        }
-    }
-
-    #[tokio::test]
-    async fn pack_encrypted_works_authcrypt_protected_sender_signed() {
-        _pack_encrypted_works_authcrypt_protected_sender_signed::<
+        _pack_encrypted_works_authcrypt_protected_sender::<
             AesKey<A256CbcHs512>,
             Ecdh1PU<'_, X25519KeyPair>,
             X25519KeyPair,
             AesKey<A256Kw>,
-            AesKey<A256CbcHs512>,
+            AesKey<A128CbcHs256>,
             EcdhEs<'_, X25519KeyPair>,
             X25519KeyPair,
             AesKey<A256Kw>,
-            Ed25519KeyPair,
         >(
             BOB_DID,
             vec![
@@ -823,50 +1773,81 @@ mod tests {
             ],
             ALICE_DID,
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-            jwe::EncAlgorithm::A256cbcHs512,
-            ALICE_DID,
-            &ALICE_AUTH_METHOD_25519,
-            jws::Algorithm::EdDSA,
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
         )
         .await;
 
-        _pack_encrypted_works_authcrypt_protected_sender_signed::<
+        _pack_encrypted_works_authcrypt_protected_sender::<
             AesKey<A256CbcHs512>,
-            Ecdh1PU<'_, P256KeyPair>,
-            P256KeyPair,
+            Ecdh1PU<'_, X25519KeyPair>,
+            X25519KeyPair,
             AesKey<A256Kw>,
-            AesKey<A256CbcHs512>,
-            EcdhEs<'_, P256KeyPair>,
-            P256KeyPair,
+            AesKey<A128Gcm>,
+            EcdhEs<'_, X25519KeyPair>,
+            X25519KeyPair,
             AesKey<A256Kw>,
-            P256KeyPair,
         >(
             BOB_DID,
             vec![
-                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3,
             ],
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-            jwe::EncAlgorithm::A256cbcHs512,
-            &ALICE_AUTH_METHOD_P256.id,
-            &ALICE_AUTH_METHOD_P256,
-            jws::Algorithm::Es256,
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
+            AnonCryptAlg::A128gcmEcdhEsA256kw,
+            jwe::EncAlgorithm::A128Gcm,
         )
         .await;
 
-        _pack_encrypted_works_authcrypt_protected_sender_signed::<
+        _pack_encrypted_works_authcrypt_protected_sender::<
             AesKey<A256CbcHs512>,
-            Ecdh1PU<'_, P256KeyPair>,
-            P256KeyPair,
+            Ecdh1PU<'_, X25519KeyPair>,
+            X25519KeyPair,
             AesKey<A256Kw>,
-            AesKey<A256CbcHs512>,
-            EcdhEs<'_, P256KeyPair>,
+            AesKey<A128CbcHs256>,
+            EcdhEs<'_, X25519KeyPair>,
+            X25519KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        _pack_encrypted_works_authcrypt_protected_sender::<
+            AesKey<A256CbcHs512>,
+            Ecdh1PU<'_, X25519KeyPair>,
+            X25519KeyPair,
+            AesKey<A256Kw>,
+            AesKey<A128CbcHs256>,
+            EcdhEs<'_, X25519KeyPair>,
+            X25519KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        _pack_encrypted_works_authcrypt_protected_sender::<
+            AesKey<A256CbcHs512>,
+            Ecdh1PU<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+            AesKey<A128CbcHs256>,
+            EcdhEs<'_, P256KeyPair>,
             P256KeyPair,
             AesKey<A256Kw>,
-            K256KeyPair,
         >(
             BOB_DID,
             vec![
@@ -875,15 +1856,116 @@ mod tests {
             ],
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-            jwe::EncAlgorithm::A256cbcHs512,
-            &ALICE_AUTH_METHOD_SECPP256K1.id,
-            &ALICE_AUTH_METHOD_SECPP256K1,
-            jws::Algorithm::Es256K,
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
         )
         .await;
 
-        async fn _pack_encrypted_works_authcrypt_protected_sender_signed<
+        _pack_encrypted_works_authcrypt_protected_sender::<
+            AesKey<A256CbcHs512>,
+            Ecdh1PU<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+            AesKey<A128Gcm>,
+            EcdhEs<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+        >(
+            BOB_DID,
+            vec![
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2,
+            ],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            AnonCryptAlg::A128gcmEcdhEsA256kw,
+            jwe::EncAlgorithm::A128Gcm,
+        )
+        .await;
+
+        _pack_encrypted_works_authcrypt_protected_sender::<
+            AesKey<A256CbcHs512>,
+            Ecdh1PU<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+            AesKey<A128CbcHs256>,
+            EcdhEs<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        _pack_encrypted_works_authcrypt_protected_sender::<
+            AesKey<A256CbcHs512>,
+            Ecdh1PU<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+            AesKey<A128CbcHs256>,
+            EcdhEs<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_P256_2],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        _pack_encrypted_works_authcrypt_protected_sender::<
+            AesKey<A256CbcHs512>,
+            Ecdh1PU<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+            AesKey<A128CbcHs256>,
+            EcdhEs<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+        >(
+            BOB_DID,
+            vec![
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2,
+            ],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        _pack_encrypted_works_authcrypt_protected_sender::<
+            AesKey<A256CbcHs512>,
+            Ecdh1PU<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+            AesKey<A128CbcHs256>,
+            EcdhEs<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+        >(
+            BOB_DID,
+            vec![
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2,
+            ],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        async fn _pack_encrypted_works_authcrypt_protected_sender<
             CE,
             KDF,
             KE,
@@ -892,7 +1974,6 @@ mod tests {
             AKDF,
             AKE,
             AKW,
-            SK,
         >(
             to: &str,
             to_keys: Vec<&Secret>,
@@ -900,9 +1981,6 @@ mod tests {
             from_key: &VerificationMethod,
             enc_alg_anon: AnonCryptAlg,
             enc_alg_anon_jwe: jwe::EncAlgorithm,
-            sign_by: &str,
-            sign_by_key: &VerificationMethod,
-            sign_alg: jws::Algorithm,
         ) where
             CE: KeyAeadInPlace + KeySecretBytes,
             KDF: JoseKDF<KE, KW>,
@@ -912,7 +1990,6 @@ mod tests {
             AKDF: JoseKDF<AKE, AKW>,
             AKE: KeyExchange + KeyGen + ToJwkValue + FromJwkValue,
             AKW: KeyWrap + FromKeyDerivation,
-            SK: KeySigVerify + FromJwkValue,
         {
             let did_resolver =
                 ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
@@ -923,7 +2000,7 @@ mod tests {
                 .pack_encrypted(
                     to,
                     Some(from),
-                    Some(sign_by),
+                    None,
                     &did_resolver,
                     &secrets_resolver,
                     &PackEncryptedOptions {
@@ -940,27 +2017,39 @@ mod tests {
                 metadata,
                 PackEncryptedMetadata {
                     messaging_service: None,
+                    forwarded: false,
                     from_kid: Some(from_key.id.clone()),
-                    sign_by_kid: Some(sign_by_key.id.clone()),
+                    sign_by_kid: None,
+                    sign_alg: None,
+                    from_prior_issuer_kid: None,
                     to_kids: to_keys.iter().map(|s| s.id.clone()).collect::<Vec<_>>(),
+                    content_hash: None,
                 }
             );
 
             let msg =
                 _verify_anoncrypt::<ACE, AKDF, AKE, AKW>(&msg, to_keys.clone(), enc_alg_anon_jwe);
-            let msg = _verify_authcrypt::<CE, KDF, KE, KW>(&msg, to_keys, from_key);
-            let msg = _verify_signed::<SK>(&msg, sign_by_key, sign_alg);
+            let msg = _verify_authcrypt::<CE, KDF, KE, KW>(
+                &msg,
+                to_keys,
+                from_key,
+                jwe::EncAlgorithm::A256cbcHs512,
+            );
             _verify_plaintext(&msg, PLAINTEXT_MSG_SIMPLE);
         }
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_authcrypt_sign() {
-        _pack_encrypted_works_authcrypt_sign::<
+    async fn pack_encrypted_works_authcrypt_protected_sender_signed() {
+        _pack_encrypted_works_authcrypt_protected_sender_signed::<
             AesKey<A256CbcHs512>,
             Ecdh1PU<'_, X25519KeyPair>,
             X25519KeyPair,
             AesKey<A256Kw>,
+            AesKey<A256CbcHs512>,
+            EcdhEs<'_, X25519KeyPair>,
+            X25519KeyPair,
+            AesKey<A256Kw>,
             Ed25519KeyPair,
         >(
             BOB_DID,
@@ -971,51 +2060,49 @@ mod tests {
             ],
             ALICE_DID,
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+            jwe::EncAlgorithm::A256cbcHs512,
             ALICE_DID,
             &ALICE_AUTH_METHOD_25519,
             jws::Algorithm::EdDSA,
         )
         .await;
 
-        _pack_encrypted_works_authcrypt_sign::<
+        _pack_encrypted_works_authcrypt_protected_sender_signed::<
             AesKey<A256CbcHs512>,
-            Ecdh1PU<'_, X25519KeyPair>,
-            X25519KeyPair,
+            Ecdh1PU<'_, P256KeyPair>,
+            P256KeyPair,
             AesKey<A256Kw>,
-            Ed25519KeyPair,
-        >(
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2],
-            ALICE_DID,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
-            &ALICE_AUTH_METHOD_25519.id,
-            &ALICE_AUTH_METHOD_25519,
-            jws::Algorithm::EdDSA,
-        )
-        .await;
-
-        _pack_encrypted_works_authcrypt_sign::<
             AesKey<A256CbcHs512>,
-            Ecdh1PU<'_, X25519KeyPair>,
-            X25519KeyPair,
+            EcdhEs<'_, P256KeyPair>,
+            P256KeyPair,
             AesKey<A256Kw>,
             P256KeyPair,
         >(
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2],
-            ALICE_DID,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
+            BOB_DID,
+            vec![
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2,
+            ],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+            jwe::EncAlgorithm::A256cbcHs512,
             &ALICE_AUTH_METHOD_P256.id,
             &ALICE_AUTH_METHOD_P256,
             jws::Algorithm::Es256,
         )
         .await;
 
-        _pack_encrypted_works_authcrypt_sign::<
+        _pack_encrypted_works_authcrypt_protected_sender_signed::<
             AesKey<A256CbcHs512>,
             Ecdh1PU<'_, P256KeyPair>,
             P256KeyPair,
             AesKey<A256Kw>,
+            AesKey<A256CbcHs512>,
+            EcdhEs<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
             K256KeyPair,
         >(
             BOB_DID,
@@ -1025,17 +2112,31 @@ mod tests {
             ],
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+            jwe::EncAlgorithm::A256cbcHs512,
             &ALICE_AUTH_METHOD_SECPP256K1.id,
             &ALICE_AUTH_METHOD_SECPP256K1,
             jws::Algorithm::Es256K,
         )
         .await;
 
-        async fn _pack_encrypted_works_authcrypt_sign<CE, KDF, KE, KW, SK>(
+        async fn _pack_encrypted_works_authcrypt_protected_sender_signed<
+            CE,
+            KDF,
+            KE,
+            KW,
+            ACE,
+            AKDF,
+            AKE,
+            AKW,
+            SK,
+        >(
             to: &str,
             to_keys: Vec<&Secret>,
             from: &str,
             from_key: &VerificationMethod,
+            enc_alg_anon: AnonCryptAlg,
+            enc_alg_anon_jwe: jwe::EncAlgorithm,
             sign_by: &str,
             sign_by_key: &VerificationMethod,
             sign_alg: jws::Algorithm,
@@ -1044,6 +2145,10 @@ mod tests {
             KDF: JoseKDF<KE, KW>,
             KE: KeyExchange + KeyGen + ToJwkValue + FromJwkValue,
             KW: KeyWrap + FromKeyDerivation,
+            ACE: KeyAeadInPlace + KeySecretBytes,
+            AKDF: JoseKDF<AKE, AKW>,
+            AKE: KeyExchange + KeyGen + ToJwkValue + FromJwkValue,
+            AKW: KeyWrap + FromKeyDerivation,
             SK: KeySigVerify + FromJwkValue,
         {
             let did_resolver =
@@ -1060,6 +2165,8 @@ mod tests {
                     &secrets_resolver,
                     &PackEncryptedOptions {
                         forward: false,
+                        protect_sender: true,
+                        enc_alg_anon,
                         ..PackEncryptedOptions::default()
                     },
                 )
@@ -1070,25 +2177,39 @@ mod tests {
                 metadata,
                 PackEncryptedMetadata {
                     messaging_service: None,
+                    forwarded: false,
                     from_kid: Some(from_key.id.clone()),
                     sign_by_kid: Some(sign_by_key.id.clone()),
+                    sign_alg: Some(
+                        SignAlg::from_str(&sign_alg.to_string()).expect("sign alg is ok.")
+                    ),
+                    from_prior_issuer_kid: None,
                     to_kids: to_keys.iter().map(|s| s.id.clone()).collect::<Vec<_>>(),
+                    content_hash: None,
                 }
             );
 
-            let msg = _verify_authcrypt::<CE, KDF, KE, KW>(&msg, to_keys, from_key);
+            let msg =
+                _verify_anoncrypt::<ACE, AKDF, AKE, AKW>(&msg, to_keys.clone(), enc_alg_anon_jwe);
+            let msg = _verify_authcrypt::<CE, KDF, KE, KW>(
+                &msg,
+                to_keys,
+                from_key,
+                jwe::EncAlgorithm::A256cbcHs512,
+            );
             let msg = _verify_signed::<SK>(&msg, sign_by_key, sign_alg);
             _verify_plaintext(&msg, PLAINTEXT_MSG_SIMPLE);
         }
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_anoncrypt() {
-        _pack_encrypted_works_anoncrypt::<
+    async fn pack_encrypted_works_authcrypt_sign() {
+        _pack_encrypted_works_authcrypt_sign::<
             AesKey<A256CbcHs512>,
-            EcdhEs<'_, X25519KeyPair>,
+            Ecdh1PU<'_, X25519KeyPair>,
             X25519KeyPair,
             AesKey<A256Kw>,
+            Ed25519KeyPair,
         >(
             BOB_DID,
             vec![
@@ -1096,25 +2217,163 @@ mod tests {
                 &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2,
                 &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3,
             ],
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-            jwe::EncAlgorithm::A256cbcHs512,
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
+            ALICE_DID,
+            &ALICE_AUTH_METHOD_25519,
+            jws::Algorithm::EdDSA,
         )
         .await;
 
-        _pack_encrypted_works_anoncrypt::<
-            AesKey<A256Gcm>,
-            EcdhEs<'_, X25519KeyPair>,
+        _pack_encrypted_works_authcrypt_sign::<
+            AesKey<A256CbcHs512>,
+            Ecdh1PU<'_, X25519KeyPair>,
             X25519KeyPair,
             AesKey<A256Kw>,
+            Ed25519KeyPair,
         >(
-            BOB_DID,
-            vec![
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3,
-            ],
-            AnonCryptAlg::A256gcmEcdhEsA256kw,
-            jwe::EncAlgorithm::A256Gcm,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
+            &ALICE_AUTH_METHOD_25519.id,
+            &ALICE_AUTH_METHOD_25519,
+            jws::Algorithm::EdDSA,
+        )
+        .await;
+
+        _pack_encrypted_works_authcrypt_sign::<
+            AesKey<A256CbcHs512>,
+            Ecdh1PU<'_, X25519KeyPair>,
+            X25519KeyPair,
+            AesKey<A256Kw>,
+            P256KeyPair,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
+            &ALICE_AUTH_METHOD_P256.id,
+            &ALICE_AUTH_METHOD_P256,
+            jws::Algorithm::Es256,
+        )
+        .await;
+
+        _pack_encrypted_works_authcrypt_sign::<
+            AesKey<A256CbcHs512>,
+            Ecdh1PU<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+            K256KeyPair,
+        >(
+            BOB_DID,
+            vec![
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2,
+            ],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            &ALICE_AUTH_METHOD_SECPP256K1.id,
+            &ALICE_AUTH_METHOD_SECPP256K1,
+            jws::Algorithm::Es256K,
+        )
+        .await;
+
+        async fn _pack_encrypted_works_authcrypt_sign<CE, KDF, KE, KW, SK>(
+            to: &str,
+            to_keys: Vec<&Secret>,
+            from: &str,
+            from_key: &VerificationMethod,
+            sign_by: &str,
+            sign_by_key: &VerificationMethod,
+            sign_alg: jws::Algorithm,
+        ) where
+            CE: KeyAeadInPlace + KeySecretBytes,
+            KDF: JoseKDF<KE, KW>,
+            KE: KeyExchange + KeyGen + ToJwkValue + FromJwkValue,
+            KW: KeyWrap + FromKeyDerivation,
+            SK: KeySigVerify + FromJwkValue,
+        {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+            let (msg, metadata) = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    to,
+                    Some(from),
+                    Some(sign_by),
+                    &did_resolver,
+                    &secrets_resolver,
+                    &PackEncryptedOptions {
+                        forward: false,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("encrypt is ok.");
+
+            assert_eq!(
+                metadata,
+                PackEncryptedMetadata {
+                    messaging_service: None,
+                    forwarded: false,
+                    from_kid: Some(from_key.id.clone()),
+                    sign_by_kid: Some(sign_by_key.id.clone()),
+                    sign_alg: Some(
+                        SignAlg::from_str(&sign_alg.to_string()).expect("sign alg is ok.")
+                    ),
+                    from_prior_issuer_kid: None,
+                    to_kids: to_keys.iter().map(|s| s.id.clone()).collect::<Vec<_>>(),
+                    content_hash: None,
+                }
+            );
+
+            let msg = _verify_authcrypt::<CE, KDF, KE, KW>(
+                &msg,
+                to_keys,
+                from_key,
+                jwe::EncAlgorithm::A256cbcHs512,
+            );
+            let msg = _verify_signed::<SK>(&msg, sign_by_key, sign_alg);
+            _verify_plaintext(&msg, PLAINTEXT_MSG_SIMPLE);
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_anoncrypt() {
+        _pack_encrypted_works_anoncrypt::<
+            AesKey<A256CbcHs512>,
+            EcdhEs<'_, X25519KeyPair>,
+            X25519KeyPair,
+            AesKey<A256Kw>,
+        >(
+            BOB_DID,
+            vec![
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3,
+            ],
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+            jwe::EncAlgorithm::A256cbcHs512,
+        )
+        .await;
+
+        _pack_encrypted_works_anoncrypt::<
+            AesKey<A256Gcm>,
+            EcdhEs<'_, X25519KeyPair>,
+            X25519KeyPair,
+            AesKey<A256Kw>,
+        >(
+            BOB_DID,
+            vec![
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3,
+            ],
+            AnonCryptAlg::A256gcmEcdhEsA256kw,
+            jwe::EncAlgorithm::A256Gcm,
         )
         .await;
 
@@ -1291,6 +2550,144 @@ mod tests {
         )
         .await;
 
+        _pack_encrypted_works_anoncrypt::<
+            AesKey<A128CbcHs256>,
+            EcdhEs<'_, X25519KeyPair>,
+            X25519KeyPair,
+            AesKey<A256Kw>,
+        >(
+            BOB_DID,
+            vec![
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3,
+            ],
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        _pack_encrypted_works_anoncrypt::<
+            AesKey<A128CbcHs256>,
+            EcdhEs<'_, X25519KeyPair>,
+            X25519KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2],
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        _pack_encrypted_works_anoncrypt::<
+            AesKey<A128CbcHs256>,
+            EcdhEs<'_, X25519KeyPair>,
+            X25519KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2],
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        _pack_encrypted_works_anoncrypt::<
+            AesKey<A128CbcHs256>,
+            EcdhEs<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1],
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        _pack_encrypted_works_anoncrypt::<
+            AesKey<A128CbcHs256>,
+            EcdhEs<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_P256_2],
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+            jwe::EncAlgorithm::A128cbcHs256,
+        )
+        .await;
+
+        _pack_encrypted_works_anoncrypt::<
+            AesKey<A128Gcm>,
+            EcdhEs<'_, X25519KeyPair>,
+            X25519KeyPair,
+            AesKey<A256Kw>,
+        >(
+            BOB_DID,
+            vec![
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3,
+            ],
+            AnonCryptAlg::A128gcmEcdhEsA256kw,
+            jwe::EncAlgorithm::A128Gcm,
+        )
+        .await;
+
+        _pack_encrypted_works_anoncrypt::<
+            AesKey<A128Gcm>,
+            EcdhEs<'_, X25519KeyPair>,
+            X25519KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2],
+            AnonCryptAlg::A128gcmEcdhEsA256kw,
+            jwe::EncAlgorithm::A128Gcm,
+        )
+        .await;
+
+        _pack_encrypted_works_anoncrypt::<
+            AesKey<A128Gcm>,
+            EcdhEs<'_, X25519KeyPair>,
+            X25519KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2],
+            AnonCryptAlg::A128gcmEcdhEsA256kw,
+            jwe::EncAlgorithm::A128Gcm,
+        )
+        .await;
+
+        _pack_encrypted_works_anoncrypt::<
+            AesKey<A128Gcm>,
+            EcdhEs<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1],
+            AnonCryptAlg::A128gcmEcdhEsA256kw,
+            jwe::EncAlgorithm::A128Gcm,
+        )
+        .await;
+
+        _pack_encrypted_works_anoncrypt::<
+            AesKey<A128Gcm>,
+            EcdhEs<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
+            vec![&BOB_SECRET_KEY_AGREEMENT_KEY_P256_2],
+            AnonCryptAlg::A128gcmEcdhEsA256kw,
+            jwe::EncAlgorithm::A128Gcm,
+        )
+        .await;
+
         async fn _pack_encrypted_works_anoncrypt<CE, KDF, KE, KW>(
             to: &str,
             to_keys: Vec<&Secret>,
@@ -1327,9 +2724,13 @@ mod tests {
                 metadata,
                 PackEncryptedMetadata {
                     messaging_service: None,
+                    forwarded: false,
                     from_kid: None,
                     sign_by_kid: None,
+                    sign_alg: None,
+                    from_prior_issuer_kid: None,
                     to_kids: to_keys.iter().map(|s| s.id.clone()).collect::<Vec<_>>(),
+                    content_hash: None,
                 }
             );
 
@@ -1339,29 +2740,301 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_anoncrypt_sign() {
-        _pack_encrypted_works_anoncrypt_sign::<
-            AesKey<A256CbcHs512>,
-            EcdhEs<'_, X25519KeyPair>,
-            X25519KeyPair,
-            AesKey<A256Kw>,
-            Ed25519KeyPair,
-        >(
-            BOB_DID,
-            vec![
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3,
-            ],
-            ALICE_DID,
-            &ALICE_AUTH_METHOD_25519,
-            jws::Algorithm::EdDSA,
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-            jwe::EncAlgorithm::A256cbcHs512,
-        )
-        .await;
+    async fn pack_encrypted_works_anoncrypt_mixed_curves() {
+        let mut bob_did_doc = BOB_DID_DOC.clone();
+
+        bob_did_doc.key_agreements = vec![
+            "did:example:bob#key-p384-1".into(),
+            "did:example:bob#key-p256-1".into(),
+            "did:example:bob#key-x25519-1".into(),
+            "did:example:bob#key-p256-2".into(),
+            "did:example:bob#key-x25519-2".into(),
+        ];
+
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), bob_did_doc]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
 
-        _pack_encrypted_works_anoncrypt_sign::<
+        let (msg, metadata) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                None,
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("encrypt is ok.");
+
+        // Unsupported P-384 key is skipped and P-256 is the first supported curve
+        assert_eq!(
+            metadata.to_kids,
+            vec![
+                BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id.clone(),
+                BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id.clone(),
+            ]
+        );
+
+        let msg = _verify_anoncrypt::<
+            Chacha20Key<XC20P>,
+            EcdhEs<'_, P256KeyPair>,
+            P256KeyPair,
+            AesKey<A256Kw>,
+        >(
+            &msg,
+            vec![
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2,
+            ],
+            jwe::EncAlgorithm::Xc20P,
+        );
+
+        _verify_plaintext(&msg, PLAINTEXT_MSG_SIMPLE);
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_anoncrypt_single_epk() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let (msg, metadata) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                None,
+                None,
+                &did_resolver,
+                &alice_secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("encrypt is ok.");
+
+        assert_eq!(metadata.to_kids.len(), 3);
+
+        let jwe: Value = serde_json::from_str(&msg).expect("Unable parse jwe");
+
+        // The only ephemeral key is shared by all same-curve recipients in the protected header
+        let recipients = jwe["recipients"].as_array().expect("No recipients");
+        assert_eq!(recipients.len(), 3);
+
+        for recipient in recipients {
+            assert!(recipient["header"].get("epk").is_none());
+        }
+
+        assert!(jwe.get("epk").is_none());
+        assert!(jwe.get("unprotected").is_none());
+
+        let protected = base64::decode_config(
+            jwe["protected"].as_str().expect("No protected header"),
+            base64::URL_SAFE_NO_PAD,
+        )
+        .expect("Unable decode protected header");
+
+        let protected: Value =
+            serde_json::from_slice(&protected).expect("Unable parse protected header");
+
+        assert!(protected["epk"].is_object());
+        assert_eq!(msg.matches("\"epk\"").count(), 0);
+
+        let (unpacked_msg, unpack_metadata) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &bob_secrets_resolver,
+            &UnpackOptions {
+                expect_decrypt_by_all_keys: true,
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&unpacked_msg, &*MESSAGE_SIMPLE);
+        assert!(unpack_metadata.anonymous_sender);
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_anoncrypt_no_supported_curves() {
+        let mut bob_did_doc = BOB_DID_DOC.clone();
+
+        bob_did_doc.key_agreements = vec![
+            "did:example:bob#key-p384-1".into(),
+            "did:example:bob#key-p521-1".into(),
+        ];
+
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), bob_did_doc]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let err = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                None,
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::NoCompatibleCrypto);
+
+        assert_eq!(
+            format!("{}", err),
+            "No compatible crypto: No recipient key agreements of supported curves found"
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_signing_key_in_key_agreements() {
+        _pack_encrypted_works_signing_key_in_key_agreements(None).await;
+        _pack_encrypted_works_signing_key_in_key_agreements(Some(BOB_DID)).await;
+
+        async fn _pack_encrypted_works_signing_key_in_key_agreements(from: Option<&str>) {
+            let mut alice_did_doc = ALICE_DID_DOC.clone();
+
+            // Ed25519 authentication key is mistakenly listed as key agreement
+            alice_did_doc
+                .key_agreements
+                .insert(0, "did:example:alice#key-1".into());
+
+            let did_resolver = ExampleDIDResolver::new(vec![alice_did_doc, BOB_DID_DOC.clone()]);
+            let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+            let err = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    ALICE_DID,
+                    from,
+                    None,
+                    &did_resolver,
+                    &secrets_resolver,
+                    &PackEncryptedOptions {
+                        forward: false,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect_err("res is ok");
+
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+
+            assert_eq!(
+                format!("{}", err),
+                "Malformed: Invalid recipient key agreement: Key agreement did:example:alice#key-1 is of curve unusable for ECDH"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_secp256k1_key_in_key_agreements() {
+        _pack_encrypted_works_secp256k1_key_in_key_agreements(None).await;
+        _pack_encrypted_works_secp256k1_key_in_key_agreements(Some(BOB_DID)).await;
+
+        async fn _pack_encrypted_works_secp256k1_key_in_key_agreements(from: Option<&str>) {
+            let mut alice_did_doc = ALICE_DID_DOC.clone();
+
+            // secp256k1 is a JWS-only curve (RFC 8812) and can't be used for ECDH
+            alice_did_doc
+                .key_agreements
+                .insert(0, "did:example:alice#key-3".into());
+
+            let did_resolver = ExampleDIDResolver::new(vec![alice_did_doc, BOB_DID_DOC.clone()]);
+            let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+            let err = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    ALICE_DID,
+                    from,
+                    None,
+                    &did_resolver,
+                    &secrets_resolver,
+                    &PackEncryptedOptions {
+                        forward: false,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect_err("res is ok");
+
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+
+            assert_eq!(
+                format!("{}", err),
+                "Malformed: Invalid recipient key agreement: Key agreement did:example:alice#key-3 is of curve unusable for ECDH"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_to_key_of_other_relationship() {
+        _pack_encrypted_works_to_key_of_other_relationship(None).await;
+        _pack_encrypted_works_to_key_of_other_relationship(Some(BOB_DID)).await;
+
+        async fn _pack_encrypted_works_to_key_of_other_relationship(from: Option<&str>) {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+            let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+            // Authentication key isn't declared as key agreement
+            let err = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    "did:example:alice#key-1",
+                    from,
+                    None,
+                    &did_resolver,
+                    &secrets_resolver,
+                    &PackEncryptedOptions {
+                        forward: false,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect_err("res is ok");
+
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+
+            assert_eq!(
+                format!("{}", err),
+                "Malformed: Invalid recipient key agreement: Key did:example:alice#key-1 isn't declared as key agreement"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_anoncrypt_sign() {
+        _pack_encrypted_works_anoncrypt_sign::<
+            AesKey<A256CbcHs512>,
+            EcdhEs<'_, X25519KeyPair>,
+            X25519KeyPair,
+            AesKey<A256Kw>,
+            Ed25519KeyPair,
+        >(
+            BOB_DID,
+            vec![
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3,
+            ],
+            ALICE_DID,
+            &ALICE_AUTH_METHOD_25519,
+            jws::Algorithm::EdDSA,
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+            jwe::EncAlgorithm::A256cbcHs512,
+        )
+        .await;
+
+        _pack_encrypted_works_anoncrypt_sign::<
             AesKey<A256Gcm>,
             EcdhEs<'_, X25519KeyPair>,
             X25519KeyPair,
@@ -1494,9 +3167,15 @@ mod tests {
                 metadata,
                 PackEncryptedMetadata {
                     messaging_service: None,
+                    forwarded: false,
                     from_kid: None,
                     sign_by_kid: Some(sign_by_key.id.clone()),
+                    sign_alg: Some(
+                        SignAlg::from_str(&sign_alg.to_string()).expect("sign alg is ok.")
+                    ),
+                    from_prior_issuer_kid: None,
                     to_kids: to_keys.iter().map(|s| s.id.clone()).collect::<Vec<_>>(),
+                    content_hash: None,
                 }
             );
 
@@ -1507,87 +3186,348 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_single_mediator() {
-        _pack_encrypted_works_single_mediator(BOB_DID, None, None).await;
-
-        _pack_encrypted_works_single_mediator(BOB_DID, None, Some(ALICE_DID)).await;
-
-        _pack_encrypted_works_single_mediator(BOB_DID, Some(ALICE_DID), None).await;
-
-        _pack_encrypted_works_single_mediator(BOB_DID, Some(ALICE_DID), Some(ALICE_DID)).await;
-
-        _pack_encrypted_works_single_mediator(
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            None,
-            None,
-        )
-        .await;
-
-        _pack_encrypted_works_single_mediator(
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            None,
-            Some(ALICE_DID),
-        )
-        .await;
-
-        _pack_encrypted_works_single_mediator(
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            Some(ALICE_DID),
-            None,
-        )
-        .await;
+    async fn pack_encrypted_works_single_mediator_forward_from() {
+        _pack_encrypted_works_single_mediator_forward_from(None).await;
+        _pack_encrypted_works_single_mediator_forward_from(Some(ALICE_DID)).await;
 
-        _pack_encrypted_works_single_mediator(
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            Some(ALICE_DID),
-            Some(ALICE_DID),
-        )
-        .await;
-
-        async fn _pack_encrypted_works_single_mediator(
-            to: &str,
-            from: Option<&str>,
-            sign_by: Option<&str>,
-        ) {
+        async fn _pack_encrypted_works_single_mediator_forward_from(from: Option<&str>) {
             let did_resolver = ExampleDIDResolver::new(vec![
                 ALICE_DID_DOC.clone(),
                 BOB_DID_DOC.clone(),
                 MEDIATOR1_DID_DOC.clone(),
             ]);
 
-            let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
-
-            let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
-
-            let mediator1_secrets_resolver = ExampleSecretsResolver::new(MEDIATOR1_SECRETS.clone());
-
             let (msg, pack_metadata) = MESSAGE_SIMPLE
                 .pack_encrypted(
-                    to,
+                    BOB_DID,
                     from,
-                    sign_by,
+                    None,
                     &did_resolver,
-                    &alice_secrets_resolver,
-                    &PackEncryptedOptions::default(),
+                    &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
+                    &PackEncryptedOptions {
+                        forward_from: Some(ALICE_DID.into()),
+                        ..PackEncryptedOptions::default()
+                    },
                 )
                 .await
                 .expect("Unable encrypt");
 
-            assert_eq!(
-                pack_metadata.messaging_service.as_ref(),
-                Some(&MessagingServiceMetadata {
-                    id: BOB_SERVICE.id.clone(),
-                    service_endpoint: BOB_DID_COMM_MESSAGING_SERVICE.service_endpoint.clone(),
-                })
-            );
+            assert!(pack_metadata.forwarded);
 
-            assert_eq!(
-                pack_metadata.from_kid.map(|k| did_or_url(&k).0.to_owned()),
-                from.map(|d| d.to_owned())
-            );
-            assert_eq!(
-                pack_metadata
-                    .sign_by_kid
+            let (unpacked_msg_mediator1, unpack_metadata_mediator1) = Message::unpack(
+                &msg,
+                &did_resolver,
+                &ExampleSecretsResolver::new(MEDIATOR1_SECRETS.clone()),
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect("Unable unpack");
+
+            let forward =
+                try_parse_forward(&unpacked_msg_mediator1).expect("Message is not Forward");
+
+            assert_eq!(&forward.next, BOB_DID);
+
+            // Mediator authenticates the sender of the Forward
+            assert!(unpack_metadata_mediator1.encrypted);
+            assert!(unpack_metadata_mediator1.authenticated);
+            assert!(!unpack_metadata_mediator1.anonymous_sender);
+
+            assert_eq!(
+                unpack_metadata_mediator1
+                    .encrypted_from_kid
+                    .as_deref()
+                    .map(|kid| did_or_url(kid).0),
+                Some(ALICE_DID)
+            );
+
+            let forwarded_msg = serde_json::to_string(&forward.forwarded_msg)
+                .expect("Unable serialize forwarded message");
+
+            let (unpacked_msg, unpack_metadata) = Message::unpack(
+                &forwarded_msg,
+                &did_resolver,
+                &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect("Unable unpack");
+
+            assert_eq!(&unpacked_msg, &*MESSAGE_SIMPLE);
+            assert_eq!(unpack_metadata.authenticated, from.is_some());
+            assert_eq!(unpack_metadata.anonymous_sender, from.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_forwarded_flag() {
+        let mut bob_did_doc_no_routing_keys = BOB_DID_DOC.clone();
+
+        bob_did_doc_no_routing_keys.services = vec![Service {
+            id: BOB_SERVICE.id.clone(),
+            kind: ServiceKind::DIDCommMessaging {
+                value: DIDCommMessagingService {
+                    routing_keys: vec![],
+                    ..BOB_DID_COMM_MESSAGING_SERVICE.clone()
+                },
+            },
+        }];
+
+        let mut bob_did_doc_no_services = BOB_DID_DOC.clone();
+        bob_did_doc_no_services.services = vec![];
+
+        _pack_encrypted_works_forwarded_flag(BOB_DID_DOC.clone(), true, true).await;
+        _pack_encrypted_works_forwarded_flag(BOB_DID_DOC.clone(), false, false).await;
+        _pack_encrypted_works_forwarded_flag(bob_did_doc_no_routing_keys, true, false).await;
+        _pack_encrypted_works_forwarded_flag(bob_did_doc_no_services, true, false).await;
+
+        async fn _pack_encrypted_works_forwarded_flag(
+            bob_did_doc: DIDDoc,
+            forward: bool,
+            exp_forwarded: bool,
+        ) {
+            let did_resolver = ExampleDIDResolver::new(vec![
+                ALICE_DID_DOC.clone(),
+                bob_did_doc,
+                MEDIATOR1_DID_DOC.clone(),
+            ]);
+
+            let (msg, pack_metadata) = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    BOB_DID,
+                    Some(ALICE_DID),
+                    None,
+                    &did_resolver,
+                    &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
+                    &PackEncryptedOptions {
+                        forward,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("Unable encrypt");
+
+            assert_eq!(pack_metadata.forwarded, exp_forwarded);
+            assert_eq!(pack_metadata.messaging_service.is_some(), exp_forwarded);
+
+            let forward = Message::unpack(
+                &msg,
+                &did_resolver,
+                &ExampleSecretsResolver::new(MEDIATOR1_SECRETS.clone()),
+                &UnpackOptions::default(),
+            )
+            .await
+            .ok()
+            .and_then(|(msg, _)| try_parse_forward(&msg));
+
+            assert_eq!(forward.is_some(), exp_forwarded);
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_service_accept() {
+        let aip2_service = bob_service("did:example:bob#didcomm-aip2", &["didcomm/aip2;env=rfc19"]);
+        let v2_service = bob_service("did:example:bob#didcomm-v2", &["didcomm/v2"]);
+        let no_accept_service = bob_service("did:example:bob#didcomm-no-accept", &[]);
+
+        _pack_encrypted_works_service_accept(
+            vec![aip2_service.clone(), v2_service.clone()],
+            None,
+            &v2_service.id,
+        )
+        .await;
+
+        _pack_encrypted_works_service_accept(
+            vec![no_accept_service.clone(), v2_service.clone()],
+            None,
+            &v2_service.id,
+        )
+        .await;
+
+        _pack_encrypted_works_service_accept(
+            vec![aip2_service.clone(), no_accept_service.clone()],
+            None,
+            &no_accept_service.id,
+        )
+        .await;
+
+        _pack_encrypted_works_service_accept(
+            vec![v2_service.clone(), no_accept_service.clone()],
+            Some(&no_accept_service.id),
+            &no_accept_service.id,
+        )
+        .await;
+
+        async fn _pack_encrypted_works_service_accept(
+            services: Vec<Service>,
+            messaging_service: Option<&str>,
+            exp_service_id: &str,
+        ) {
+            let mut bob_did_doc = BOB_DID_DOC.clone();
+            bob_did_doc.services = services;
+
+            let did_resolver = ExampleDIDResolver::new(vec![
+                ALICE_DID_DOC.clone(),
+                bob_did_doc,
+                MEDIATOR1_DID_DOC.clone(),
+            ]);
+
+            let (_, pack_metadata) = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    BOB_DID,
+                    Some(ALICE_DID),
+                    None,
+                    &did_resolver,
+                    &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
+                    &PackEncryptedOptions {
+                        messaging_service: messaging_service.map(str::to_owned),
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("Unable encrypt");
+
+            assert!(pack_metadata.forwarded);
+
+            assert_eq!(
+                pack_metadata.messaging_service,
+                Some(MessagingServiceMetadata {
+                    id: exp_service_id.into(),
+                    service_endpoint: BOB_DID_COMM_MESSAGING_SERVICE.service_endpoint.clone(),
+                })
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_service_accept_not_compatible() {
+        let aip2_service = bob_service("did:example:bob#didcomm-aip2", &["didcomm/aip2;env=rfc19"]);
+        let v2_service = bob_service("did:example:bob#didcomm-v2", &["didcomm/v2"]);
+
+        _pack_encrypted_works_service_accept_not_compatible(vec![aip2_service.clone()], None).await;
+
+        _pack_encrypted_works_service_accept_not_compatible(
+            vec![aip2_service.clone(), v2_service],
+            Some(&aip2_service.id),
+        )
+        .await;
+
+        async fn _pack_encrypted_works_service_accept_not_compatible(
+            services: Vec<Service>,
+            messaging_service: Option<&str>,
+        ) {
+            let mut bob_did_doc = BOB_DID_DOC.clone();
+            bob_did_doc.services = services;
+
+            let did_resolver = ExampleDIDResolver::new(vec![
+                ALICE_DID_DOC.clone(),
+                bob_did_doc,
+                MEDIATOR1_DID_DOC.clone(),
+            ]);
+
+            let err = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    BOB_DID,
+                    Some(ALICE_DID),
+                    None,
+                    &did_resolver,
+                    &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
+                    &PackEncryptedOptions {
+                        messaging_service: messaging_service.map(str::to_owned),
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect_err("res is ok");
+
+            assert_eq!(err.kind(), ErrorKind::NoCompatibleCrypto);
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_single_mediator() {
+        _pack_encrypted_works_single_mediator(BOB_DID, None, None).await;
+
+        _pack_encrypted_works_single_mediator(BOB_DID, None, Some(ALICE_DID)).await;
+
+        _pack_encrypted_works_single_mediator(BOB_DID, Some(ALICE_DID), None).await;
+
+        _pack_encrypted_works_single_mediator(BOB_DID, Some(ALICE_DID), Some(ALICE_DID)).await;
+
+        _pack_encrypted_works_single_mediator(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            None,
+            None,
+        )
+        .await;
+
+        _pack_encrypted_works_single_mediator(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            None,
+            Some(ALICE_DID),
+        )
+        .await;
+
+        _pack_encrypted_works_single_mediator(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            Some(ALICE_DID),
+            None,
+        )
+        .await;
+
+        _pack_encrypted_works_single_mediator(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            Some(ALICE_DID),
+            Some(ALICE_DID),
+        )
+        .await;
+
+        async fn _pack_encrypted_works_single_mediator(
+            to: &str,
+            from: Option<&str>,
+            sign_by: Option<&str>,
+        ) {
+            let did_resolver = ExampleDIDResolver::new(vec![
+                ALICE_DID_DOC.clone(),
+                BOB_DID_DOC.clone(),
+                MEDIATOR1_DID_DOC.clone(),
+            ]);
+
+            let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+            let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+            let mediator1_secrets_resolver = ExampleSecretsResolver::new(MEDIATOR1_SECRETS.clone());
+
+            let (msg, pack_metadata) = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    to,
+                    from,
+                    sign_by,
+                    &did_resolver,
+                    &alice_secrets_resolver,
+                    &PackEncryptedOptions::default(),
+                )
+                .await
+                .expect("Unable encrypt");
+
+            assert_eq!(
+                pack_metadata.messaging_service.as_ref(),
+                Some(&MessagingServiceMetadata {
+                    id: BOB_SERVICE.id.clone(),
+                    service_endpoint: BOB_DID_COMM_MESSAGING_SERVICE.service_endpoint.clone(),
+                })
+            );
+
+            assert!(pack_metadata.forwarded);
+
+            assert_eq!(
+                pack_metadata.from_kid.map(|k| did_or_url(&k).0.to_owned()),
+                from.map(|d| d.to_owned())
+            );
+            assert_eq!(
+                pack_metadata
+                    .sign_by_kid
                     .map(|k| did_or_url(&k).0.to_owned()),
                 sign_by.map(|d| d.to_owned())
             );
@@ -1772,6 +3712,8 @@ mod tests {
                 })
             );
 
+            assert!(pack_metadata.forwarded);
+
             assert_eq!(
                 pack_metadata.from_kid.map(|k| did_or_url(&k).0.to_owned()),
                 from.map(|d| d.to_owned())
@@ -1932,33 +3874,101 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn wrap_in_forward_works_mediator_unknown_by_sender() {
-        _wrap_in_forward_works_mediator_unknown_by_sender(BOB_DID, None, None).await;
+    async fn pack_encrypted_works_forward_expires_time() {
+        let did_resolver = ExampleDIDResolver::new(vec![
+            ALICE_DID_DOC.clone(),
+            BOB_DID_DOC.clone(),
+            MEDIATOR1_DID_DOC.clone(),
+        ]);
 
-        _wrap_in_forward_works_mediator_unknown_by_sender(BOB_DID, None, Some(ALICE_DID)).await;
+        let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
 
-        _wrap_in_forward_works_mediator_unknown_by_sender(BOB_DID, Some(ALICE_DID), None).await;
+        let mediator1_secrets_resolver = ExampleSecretsResolver::new(MEDIATOR1_SECRETS.clone());
 
-        _wrap_in_forward_works_mediator_unknown_by_sender(
-            BOB_DID,
-            Some(ALICE_DID),
-            Some(ALICE_DID),
-        )
-        .await;
+        let (msg, _) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                None,
+                None,
+                &did_resolver,
+                &alice_secrets_resolver,
+                &PackEncryptedOptions {
+                    forward_headers: Some(HashMap::from_iter([
+                        ("expires_time".into(), json!(1681382400)),
+                        ("sender_order".into(), json!(1)),
+                    ])),
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("Unable encrypt");
 
-        _wrap_in_forward_works_mediator_unknown_by_sender(
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            None,
-            None,
+        let (unpacked_msg_mediator1, _) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &mediator1_secrets_resolver,
+            &UnpackOptions::default(),
         )
-        .await;
+        .await
+        .expect("Unable unpack");
 
-        _wrap_in_forward_works_mediator_unknown_by_sender(
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            None,
-            Some(ALICE_DID),
-        )
-        .await;
+        assert_eq!(unpacked_msg_mediator1.expires_time, Some(1681382400));
+
+        let forward = try_parse_forward(&unpacked_msg_mediator1).expect("Message is not Forward");
+
+        assert_eq!(forward.expires_time, Some(1681382400));
+        assert_eq!(forward.headers.get("sender_order"), Some(&json!(1)));
+        assert!(!forward.headers.contains_key("expires_time"));
+
+        let res = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                None,
+                None,
+                &did_resolver,
+                &alice_secrets_resolver,
+                &PackEncryptedOptions {
+                    forward_headers: Some(HashMap::from_iter([(
+                        "expires_time".into(),
+                        json!("tomorrow"),
+                    )])),
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+
+        let err = res.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+    }
+
+    #[tokio::test]
+    async fn wrap_in_forward_works_mediator_unknown_by_sender() {
+        _wrap_in_forward_works_mediator_unknown_by_sender(BOB_DID, None, None).await;
+
+        _wrap_in_forward_works_mediator_unknown_by_sender(BOB_DID, None, Some(ALICE_DID)).await;
+
+        _wrap_in_forward_works_mediator_unknown_by_sender(BOB_DID, Some(ALICE_DID), None).await;
+
+        _wrap_in_forward_works_mediator_unknown_by_sender(
+            BOB_DID,
+            Some(ALICE_DID),
+            Some(ALICE_DID),
+        )
+        .await;
+
+        _wrap_in_forward_works_mediator_unknown_by_sender(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            None,
+            None,
+        )
+        .await;
+
+        _wrap_in_forward_works_mediator_unknown_by_sender(
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            None,
+            Some(ALICE_DID),
+        )
+        .await;
 
         _wrap_in_forward_works_mediator_unknown_by_sender(
             &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
@@ -2017,6 +4027,8 @@ mod tests {
                 })
             );
 
+            assert!(pack_metadata.forwarded);
+
             let (unpacked_msg_mediator1, unpack_metadata_mediator1) = Message::unpack(
                 &msg,
                 &did_resolver,
@@ -2101,197 +4113,1534 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_from_not_did_or_did_url() {
-        let did_resolver =
-            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+    async fn wrap_in_forward_works_forward_from() {
+        _wrap_in_forward_works_forward_from(None).await;
+        _wrap_in_forward_works_forward_from(Some(ALICE_DID)).await;
 
-        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        async fn _wrap_in_forward_works_forward_from(forward_from: Option<&str>) {
+            let did_resolver = ExampleDIDResolver::new(vec![
+                ALICE_DID_DOC.clone(),
+                BOB_DID_DOC.clone(),
+                MEDIATOR1_DID_DOC.clone(),
+            ]);
 
-        let res = MESSAGE_SIMPLE
-            .pack_encrypted(
-                BOB_DID,
-                "not-a-did".into(),
+            let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+            let alice_secrets_resolver: &dyn SecretsResolver = &alice_secrets_resolver;
+
+            let (msg, _) = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    BOB_DID,
+                    None,
+                    None,
+                    &did_resolver,
+                    alice_secrets_resolver,
+                    &PackEncryptedOptions {
+                        forward: false,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("Unable encrypt");
+
+            let enc_alg_auth = AuthCryptAlg::default();
+
+            let forward_msg = wrap_in_forward_with_options(
+                &msg,
                 None,
+                BOB_DID,
+                &vec![MEDIATOR1_VERIFICATION_METHOD_KEY_AGREEM_X25519_1.id.clone()],
+                &AnonCryptAlg::default(),
                 &did_resolver,
-                &secrets_resolver,
-                &PackEncryptedOptions {
-                    forward: false,
-                    ..PackEncryptedOptions::default()
+                &WrapInForwardOptions {
+                    forward_from: forward_from
+                        .map(|from| (from, &enc_alg_auth, alice_secrets_resolver)),
                 },
             )
-            .await;
+            .await
+            .expect("Unable wrap in forward");
 
-        let err = res.expect_err("res is ok");
-        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+            let (unpacked_msg_mediator1, unpack_metadata_mediator1) = Message::unpack(
+                &forward_msg,
+                &did_resolver,
+                &ExampleSecretsResolver::new(MEDIATOR1_SECRETS.clone()),
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect("Unable unpack");
 
-        assert_eq!(
-            format!("{}", err),
-            "Illegal argument: `from` value is not a valid DID or DID URL"
-        );
+            let forward =
+                try_parse_forward(&unpacked_msg_mediator1).expect("Message is not Forward");
+
+            assert_eq!(&forward.next, BOB_DID);
+
+            assert_eq!(
+                unpack_metadata_mediator1.authenticated,
+                forward_from.is_some()
+            );
+
+            assert_eq!(
+                unpack_metadata_mediator1.anonymous_sender,
+                forward_from.is_none()
+            );
+
+            assert_eq!(
+                unpack_metadata_mediator1
+                    .encrypted_from_kid
+                    .as_deref()
+                    .map(|kid| did_or_url(kid).0),
+                forward_from
+            );
+        }
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_to_not_did_or_did_url() {
-        let did_resolver =
-            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+    async fn wrap_in_forward_chain_works() {
+        let did_resolver = ExampleDIDResolver::new(vec![
+            ALICE_DID_DOC.clone(),
+            BOB_DID_DOC.clone(),
+            MEDIATOR1_DID_DOC.clone(),
+            MEDIATOR2_DID_DOC.clone(),
+        ]);
 
-        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+        let mediator1_secrets_resolver = ExampleSecretsResolver::new(MEDIATOR1_SECRETS.clone());
+        let mediator2_secrets_resolver = ExampleSecretsResolver::new(MEDIATOR2_SECRETS.clone());
 
-        let res = MESSAGE_SIMPLE
+        let (msg, _) = MESSAGE_SIMPLE
             .pack_encrypted(
-                "not-a-did".into(),
+                BOB_DID,
                 None,
                 None,
                 &did_resolver,
-                &secrets_resolver,
+                &alice_secrets_resolver,
                 &PackEncryptedOptions {
                     forward: false,
                     ..PackEncryptedOptions::default()
                 },
             )
-            .await;
+            .await
+            .expect("Unable encrypt");
 
-        let err = res.expect_err("res is ok");
-        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+        let routing_keys = vec![
+            MEDIATOR1_VERIFICATION_METHOD_KEY_AGREEM_X25519_1.id.clone(),
+            MEDIATOR2_VERIFICATION_METHOD_KEY_AGREEM_X25519_1.id.clone(),
+        ];
 
-        assert_eq!(
-            format!("{}", err),
-            "Illegal argument: `to` value is not a valid DID or DID URL"
-        );
-    }
+        let chain = wrap_in_forward_chain(
+            &msg,
+            None,
+            BOB_DID,
+            &routing_keys,
+            &AnonCryptAlg::default(),
+            &did_resolver,
+        )
+        .await
+        .expect("Unable wrap in forward chain");
 
-    #[tokio::test]
-    async fn pack_encrypted_works_sign_by_not_did_or_did_url() {
-        let did_resolver =
-            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        assert_eq!(chain.len(), 2);
+        assert_eq!(&chain[0].0, &routing_keys[0]);
+        assert_eq!(&chain[1].0, &routing_keys[1]);
+        assert_ne!(&chain[0].1, &chain[1].1);
 
-        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        // The first envelope is the nested form forwarding the second one
+        let (unpacked_msg_mediator1, _) = Message::unpack(
+            &chain[0].1,
+            &did_resolver,
+            &mediator1_secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
 
-        let res = MESSAGE_SIMPLE
-            .pack_encrypted(
-                BOB_DID,
-                ALICE_DID.into(),
-                "not-a-did".into(),
-                &did_resolver,
-                &secrets_resolver,
-                &PackEncryptedOptions {
-                    forward: false,
-                    ..PackEncryptedOptions::default()
-                },
-            )
-            .await;
+        let forward_at_mediator1 =
+            try_parse_forward(&unpacked_msg_mediator1).expect("Message is not Forward");
 
-        let err = res.expect_err("res is ok");
-        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+        assert_eq!(&forward_at_mediator1.next, &routing_keys[1]);
 
         assert_eq!(
-            format!("{}", err),
-            "Illegal argument: `sign_from` value is not a valid DID or DID URL"
+            forward_at_mediator1.forwarded_msg,
+            serde_json::from_str::<Value>(&chain[1].1).expect("Unable parse envelope")
         );
-    }
-
-    #[tokio::test]
-    async fn pack_encrypted_works_from_differs_msg_from() {
-        let did_resolver =
-            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
 
-        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        // The second envelope forwards the message to the recipient
+        let (unpacked_msg_mediator2, _) = Message::unpack(
+            &chain[1].1,
+            &did_resolver,
+            &mediator2_secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
 
-        let mut msg = MESSAGE_SIMPLE.clone();
-        msg.from = CHARLIE_DID.to_string().into();
-        let res = msg
-            .pack_encrypted(
-                BOB_DID,
-                ALICE_DID.into(),
-                None,
-                &did_resolver,
-                &secrets_resolver,
-                &PackEncryptedOptions {
-                    forward: false,
-                    ..PackEncryptedOptions::default()
-                },
-            )
-            .await;
+        let forward_at_mediator2 =
+            try_parse_forward(&unpacked_msg_mediator2).expect("Message is not Forward");
 
-        let err = res.expect_err("res is ok");
-        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+        assert_eq!(&forward_at_mediator2.next, BOB_DID);
 
         assert_eq!(
-            format!("{}", err),
-            "Illegal argument: `message.from` value is not equal to `from` value's DID"
+            forward_at_mediator2.forwarded_msg,
+            serde_json::from_str::<Value>(&msg).expect("Unable parse message")
         );
-    }
 
-    #[tokio::test]
-    async fn pack_encrypted_works_to_differs_msg_to() {
-        let did_resolver =
-            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let forwarded_msg_at_mediator2 = serde_json::to_string(&forward_at_mediator2.forwarded_msg)
+            .expect("Unable serialize forwarded message");
 
-        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let (unpacked_msg, _) = Message::unpack(
+            &forwarded_msg_at_mediator2,
+            &did_resolver,
+            &bob_secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
 
-        let mut msg = MESSAGE_SIMPLE.clone();
-        msg.to = Some(vec![CHARLIE_DID.to_string()]);
-        let res = msg
-            .pack_encrypted(
-                BOB_DID,
-                ALICE_DID.into(),
-                None,
-                &did_resolver,
-                &secrets_resolver,
-                &PackEncryptedOptions {
-                    forward: false,
-                    ..PackEncryptedOptions::default()
-                },
-            )
-            .await;
+        assert_eq!(&unpacked_msg, &*MESSAGE_SIMPLE);
+
+        let err = wrap_in_forward_chain(
+            &msg,
+            None,
+            BOB_DID,
+            &vec![],
+            &AnonCryptAlg::default(),
+            &did_resolver,
+        )
+        .await
+        .expect_err("res is ok");
 
-        let err = res.expect_err("res is ok");
         assert_eq!(err.kind(), ErrorKind::IllegalArgument);
 
         assert_eq!(
             format!("{}", err),
-            "Illegal argument: `message.to` value does not contain `to` value's DID"
+            "Illegal argument: No routing keys provided"
         );
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_to_presented_in_msg_to() {
-        let did_resolver =
-            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+    async fn pack_encrypted_to_kids_works() {
+        _pack_encrypted_to_kids_works(None).await;
+        _pack_encrypted_to_kids_works(Some(ALICE_DID)).await;
 
-        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        async fn _pack_encrypted_to_kids_works(from: Option<&str>) {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
 
-        let mut msg = MESSAGE_SIMPLE.clone();
-        msg.to = Some(vec![CHARLIE_DID.to_string(), BOB_DID.to_string()]);
-        let _ = msg
-            .pack_encrypted(
-                BOB_DID,
-                ALICE_DID.into(),
-                None,
-                &did_resolver,
-                &secrets_resolver,
-                &PackEncryptedOptions {
-                    forward: false,
-                    ..PackEncryptedOptions::default()
-                },
-            )
-            .await;
-    }
+            let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
 
-    #[tokio::test]
-    async fn pack_encrypted_works_from_not_did_or_did_url_in_msg() {
+            let to_kids = [
+                BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.as_str(),
+                BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id.as_str(),
+            ];
+
+            let (msg, pack_metadata) = MESSAGE_SIMPLE
+                .pack_encrypted_to_kids(
+                    &to_kids,
+                    from,
+                    None,
+                    &did_resolver,
+                    &alice_secrets_resolver,
+                    &PackEncryptedOptions {
+                        forward: false,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("Unable encrypt");
+
+            assert_eq!(pack_metadata.to_kids, to_kids);
+
+            let (unpacked_msg, unpack_metadata) = Message::unpack(
+                &msg,
+                &did_resolver,
+                &ExampleSecretsResolver::new(vec![BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.clone()]),
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect("Unable unpack");
+
+            assert_eq!(&unpacked_msg, &*MESSAGE_SIMPLE);
+            assert_eq!(
+                unpack_metadata.encrypted_to_kids,
+                Some(to_kids.iter().map(|&k| k.to_owned()).collect())
+            );
+
+            // Message isn't encrypted for the key out of the subset
+            let res = Message::unpack(
+                &msg,
+                &did_resolver,
+                &ExampleSecretsResolver::new(vec![BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.clone()]),
+                &UnpackOptions::default(),
+            )
+            .await;
+
+            assert!(res.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_to_kids_works_invalid_kids() {
+        _pack_encrypted_to_kids_works_invalid_kids(&[], ErrorKind::IllegalArgument).await;
+
+        _pack_encrypted_to_kids_works_invalid_kids(&[BOB_DID], ErrorKind::IllegalArgument).await;
+
+        _pack_encrypted_to_kids_works_invalid_kids(
+            &[
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+                &CHARLIE_SECRET_KEY_AGREEMENT_KEY_X25519.id,
+            ],
+            ErrorKind::IllegalArgument,
+        )
+        .await;
+
+        _pack_encrypted_to_kids_works_invalid_kids(
+            &[
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+            ],
+            ErrorKind::IllegalArgument,
+        )
+        .await;
+
+        _pack_encrypted_to_kids_works_invalid_kids(
+            &[
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+                "did:example:bob#key-x25519-unknown",
+            ],
+            ErrorKind::DIDUrlNotFound,
+        )
+        .await;
+
+        _pack_encrypted_to_kids_works_invalid_kids(
+            &[
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+            ],
+            ErrorKind::NoCompatibleCrypto,
+        )
+        .await;
+
+        async fn _pack_encrypted_to_kids_works_invalid_kids(to_kids: &[&str], kind: ErrorKind) {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+            let err = MESSAGE_SIMPLE
+                .pack_encrypted_to_kids(
+                    to_kids,
+                    None,
+                    None,
+                    &did_resolver,
+                    &secrets_resolver,
+                    &PackEncryptedOptions {
+                        forward: false,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect_err("res is ok");
+
+            assert_eq!(err.kind(), kind);
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_content_hash() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        for from in [None, Some(ALICE_DID)] {
+            let (msg, metadata) = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    BOB_DID,
+                    from,
+                    None,
+                    &did_resolver,
+                    &secrets_resolver,
+                    &PackEncryptedOptions {
+                        forward: false,
+                        content_hash: true,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("Unable encrypt");
+
+            let content_hash = metadata.content_hash.expect("content_hash is some.");
+            assert_eq!(content_hash.len(), 64);
+            assert_eq!(
+                content_hash,
+                format!("{:x}", Sha256::digest(msg.as_bytes()))
+            );
+        }
+
+        // Not computed by default
+        let (_, metadata) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                None,
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("Unable encrypt");
+
+        assert_eq!(metadata.content_hash, None);
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_flattened_jwe() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let options = PackEncryptedOptions {
+            forward: false,
+            flattened_jwe: true,
+            ..PackEncryptedOptions::default()
+        };
+
+        for from in [None, Some(ALICE_DID)] {
+            let (msg, metadata) = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+                    from,
+                    None,
+                    &did_resolver,
+                    &alice_secrets_resolver,
+                    &options,
+                )
+                .await
+                .expect("Unable encrypt");
+
+            let jwe: Value = serde_json::from_str(&msg).expect("Unable parse jwe");
+            assert!(jwe.get("recipients").is_none());
+            assert_eq!(
+                jwe["header"]["kid"],
+                BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.as_str()
+            );
+            assert!(jwe["encrypted_key"].is_string());
+
+            let (unpacked_msg, unpack_metadata) = Message::unpack(
+                &msg,
+                &did_resolver,
+                &bob_secrets_resolver,
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect("Unable unpack");
+
+            assert_eq!(unpacked_msg, *MESSAGE_SIMPLE);
+            assert_eq!(unpack_metadata.authenticated, from.is_some());
+            assert_eq!(
+                unpack_metadata.encrypted_to_kids,
+                Some(metadata.to_kids.clone())
+            );
+        }
+
+        // General serialization is kept for multiple recipient keys
+        let (msg, _) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                None,
+                None,
+                &did_resolver,
+                &alice_secrets_resolver,
+                &options,
+            )
+            .await
+            .expect("Unable encrypt");
+
+        let jwe: Value = serde_json::from_str(&msg).expect("Unable parse jwe");
+        assert_eq!(jwe["recipients"].as_array().map(Vec::len), Some(3));
+        assert!(jwe.get("header").is_none());
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_recipient_kid_prefix() {
+        let bob_did_doc = DIDDoc {
+            key_agreements: vec![
+                BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone(),
+                BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id.clone(),
+                BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id.clone(),
+            ],
+            ..BOB_DID_DOC.clone()
+        };
+
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), bob_did_doc]);
+        let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let options = PackEncryptedOptions {
+            forward: false,
+            recipient_kid_prefix: Some("did:example:bob#key-x25519-".into()),
+            ..PackEncryptedOptions::default()
+        };
+
+        let expected_to_kids = vec![
+            BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone(),
+            BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id.clone(),
+        ];
+
+        let plan = MESSAGE_SIMPLE
+            .pack_encrypted_plan(BOB_DID, None, None, &did_resolver, &options)
+            .await
+            .expect("pack_encrypted_plan is ok.");
+
+        assert_eq!(plan.to_kids, expected_to_kids);
+
+        let (msg, metadata) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                None,
+                None,
+                &did_resolver,
+                &alice_secrets_resolver,
+                &options,
+            )
+            .await
+            .expect("pack_encrypted is ok.");
+
+        assert_eq!(metadata.to_kids, expected_to_kids);
+
+        let (unpacked_msg, unpack_metadata) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &bob_secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&unpacked_msg, &*MESSAGE_SIMPLE);
+        assert_eq!(unpack_metadata.encrypted_to_kids, Some(expected_to_kids));
+
+        // No key matches the prefix
+        let err = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                None,
+                None,
+                &did_resolver,
+                &alice_secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    recipient_kid_prefix: Some("did:example:bob#key-p521-".into()),
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::DIDUrlNotFound);
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_multi_works() {
+        _pack_encrypted_multi_works(None).await;
+        _pack_encrypted_multi_works(Some(ALICE_DID)).await;
+
+        async fn _pack_encrypted_multi_works(sign_by: Option<&str>) {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+            let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+            let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+            let msg = Message::build(
+                "1234567890".to_owned(),
+                "http://example.com/protocols/group/1.0/message".to_owned(),
+                json!({"text": "hello group"}),
+            )
+            .to_many(vec![ALICE_DID.to_owned(), BOB_DID.to_owned()])
+            .finalize();
+
+            let (packed_msg, pack_metadata) = msg
+                .pack_encrypted_multi(
+                    &[&ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id, BOB_DID],
+                    sign_by,
+                    &did_resolver,
+                    &alice_secrets_resolver,
+                    &PackEncryptedOptions {
+                        aad: Some("group-session-1".into()),
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("Unable encrypt");
+
+            let exp_to_kids: Vec<String> = vec![
+                ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id.clone(),
+                BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone(),
+                BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id.clone(),
+                BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id.clone(),
+            ];
+
+            assert_eq!(pack_metadata.to_kids, exp_to_kids);
+            assert_eq!(pack_metadata.from_kid, None);
+            assert_eq!(pack_metadata.messaging_service, None);
+            assert!(!pack_metadata.forwarded);
+
+            assert_eq!(pack_metadata.sign_by_kid.is_some(), sign_by.is_some());
+            assert_eq!(pack_metadata.sign_alg, sign_by.map(|_| SignAlg::EdDSA));
+
+            for secrets_resolver in [&alice_secrets_resolver, &bob_secrets_resolver] {
+                let (unpacked_msg, unpack_metadata) = Message::unpack(
+                    &packed_msg,
+                    &did_resolver,
+                    secrets_resolver,
+                    &UnpackOptions::default(),
+                )
+                .await
+                .expect("Unable unpack");
+
+                assert_eq!(unpacked_msg, msg);
+                assert!(unpack_metadata.encrypted);
+                assert!(unpack_metadata.anonymous_sender);
+                assert_eq!(unpack_metadata.non_repudiation, sign_by.is_some());
+                assert_eq!(
+                    unpack_metadata.encrypted_to_kids.as_ref(),
+                    Some(&exp_to_kids)
+                );
+                assert_eq!(unpack_metadata.aad.as_deref(), Some("group-session-1"));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_multi_works_invalid_to() {
+        _pack_encrypted_multi_works_invalid_to(&[], ErrorKind::IllegalArgument).await;
+
+        _pack_encrypted_multi_works_invalid_to(&["not-a-did"], ErrorKind::IllegalArgument).await;
+
+        _pack_encrypted_multi_works_invalid_to(
+            &[BOB_DID, &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id],
+            ErrorKind::IllegalArgument,
+        )
+        .await;
+
+        // Not in `message.to`
+        _pack_encrypted_multi_works_invalid_to(&[BOB_DID, CHARLIE_DID], ErrorKind::IllegalArgument)
+            .await;
+
+        _pack_encrypted_multi_works_invalid_to(
+            &[
+                &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+            ],
+            ErrorKind::NoCompatibleCrypto,
+        )
+        .await;
+
+        async fn _pack_encrypted_multi_works_invalid_to(to: &[&str], kind: ErrorKind) {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+            let msg = Message::build(
+                "1234567890".to_owned(),
+                "http://example.com/protocols/group/1.0/message".to_owned(),
+                json!({}),
+            )
+            .to_many(vec![ALICE_DID.to_owned(), BOB_DID.to_owned()])
+            .finalize();
+
+            let err = msg
+                .pack_encrypted_multi(
+                    to,
+                    None,
+                    &did_resolver,
+                    &secrets_resolver,
+                    &PackEncryptedOptions::default(),
+                )
+                .await
+                .expect_err("res is ok");
+
+            assert_eq!(err.kind(), kind);
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_to_jwk_works() {
+        _pack_encrypted_to_jwk_works(None).await;
+        _pack_encrypted_to_jwk_works(Some(ALICE_DID)).await;
+
+        async fn _pack_encrypted_to_jwk_works(sign_by: Option<&str>) {
+            let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+            let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+            let to_jwk = json!({
+                "kty": "OKP",
+                "crv": "X25519",
+                "x": "GDTrI66K0pFfO54tlCSvfjjNapIs44dzpneBgyx0S3E",
+            });
+
+            let to_kid = build_did_jwk_kid(&to_jwk).expect("build_did_jwk_kid is ok.");
+
+            let msg = Message::build(
+                "1234567890".to_owned(),
+                "http://example.com/protocols/lets_do_lunch/1.0/proposal".to_owned(),
+                json!({"messagespecificattribute": "and its value"}),
+            )
+            .finalize();
+
+            let (packed_msg, pack_metadata) = msg
+                .pack_encrypted_to_jwk(
+                    &to_jwk,
+                    sign_by,
+                    &did_resolver,
+                    &alice_secrets_resolver,
+                    &PackEncryptedOptions {
+                        aad: Some("invitation-1".into()),
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("Unable encrypt");
+
+            assert_eq!(pack_metadata.to_kids, vec![to_kid.clone()]);
+            assert_eq!(pack_metadata.from_kid, None);
+            assert_eq!(pack_metadata.messaging_service, None);
+            assert!(!pack_metadata.forwarded);
+            assert_eq!(pack_metadata.sign_alg, sign_by.map(|_| SignAlg::EdDSA));
+
+            // Recipient keeps the secret of the raw key under synthesized key ID
+            let recipient_secrets_resolver = ExampleSecretsResolver::new(vec![Secret {
+                id: to_kid.clone(),
+                ..BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.clone()
+            }]);
+
+            let (unpacked_msg, unpack_metadata) = Message::unpack(
+                &packed_msg,
+                &did_resolver,
+                &recipient_secrets_resolver,
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect("Unable unpack");
+
+            assert_eq!(unpacked_msg, msg);
+            assert!(unpack_metadata.encrypted);
+            assert!(unpack_metadata.anonymous_sender);
+            assert_eq!(unpack_metadata.non_repudiation, sign_by.is_some());
+            assert_eq!(unpack_metadata.encrypted_to_kids, Some(vec![to_kid]));
+            assert_eq!(unpack_metadata.aad.as_deref(), Some("invitation-1"));
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_to_jwk_works_invalid_jwk() {
+        _pack_encrypted_to_jwk_works_invalid_jwk(
+            json!({
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "x": "G-boxFB6vOZBu-wXkm-9Lh79I8nf9Z50cILaOgKKGww",
+            }),
+            ErrorKind::Unsupported,
+        )
+        .await;
+
+        _pack_encrypted_to_jwk_works_invalid_jwk(
+            json!({
+                "kty": "OKP",
+                "d": "b9NnuOCB0hm7YGNvaE9DMhwH_wjZA1-gWD6dA0JWdL0",
+                "crv": "X25519",
+                "x": "GDTrI66K0pFfO54tlCSvfjjNapIs44dzpneBgyx0S3E",
+            }),
+            ErrorKind::IllegalArgument,
+        )
+        .await;
+
+        _pack_encrypted_to_jwk_works_invalid_jwk(
+            json!({
+                "kty": "OKP",
+                "crv": "X25519",
+                "x": "invalid",
+            }),
+            ErrorKind::Malformed,
+        )
+        .await;
+
+        async fn _pack_encrypted_to_jwk_works_invalid_jwk(to_jwk: Value, kind: ErrorKind) {
+            let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+            let err = MESSAGE_SIMPLE
+                .pack_encrypted_to_jwk(
+                    &to_jwk,
+                    None,
+                    &did_resolver,
+                    &secrets_resolver,
+                    &PackEncryptedOptions::default(),
+                )
+                .await
+                .expect_err("res is ok");
+
+            assert_eq!(err.kind(), kind);
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_resolver_timeout() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let slow_did_resolver = SlowDIDResolver::new(&did_resolver, Duration::from_secs(10));
+
+        let err = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                Some(ALICE_DID),
+                None,
+                &slow_did_resolver,
+                &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
+                &PackEncryptedOptions {
+                    resolver_timeout: Some(Duration::from_millis(10)),
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IoError);
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_relative_did_urls() {
+        let did_resolver = ExampleDIDResolver::new(vec![
+            _relative_did_doc(&ALICE_DID_DOC),
+            _relative_did_doc(&BOB_DID_DOC),
+        ]);
+
+        let (msg, metadata) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                Some(ALICE_DID),
+                Some(ALICE_DID),
+                &did_resolver,
+                &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("pack_encrypted is ok.");
+
+        assert_eq!(
+            metadata.from_kid.as_deref(),
+            Some("did:example:alice#key-x25519-1")
+        );
+
+        assert_eq!(
+            metadata.sign_by_kid.as_deref(),
+            Some("did:example:alice#key-1")
+        );
+
+        assert_eq!(
+            metadata.to_kids,
+            vec![
+                "did:example:bob#key-x25519-1",
+                "did:example:bob#key-x25519-2",
+                "did:example:bob#key-x25519-3",
+            ]
+        );
+
+        let (unpacked_msg, unpack_metadata) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&unpacked_msg, &*MESSAGE_SIMPLE);
+        assert!(unpack_metadata.authenticated);
+        assert!(unpack_metadata.non_repudiation);
+
+        assert_eq!(
+            unpack_metadata.encrypted_from_kid.as_deref(),
+            Some("did:example:alice#key-x25519-1")
+        );
+
+        assert_eq!(
+            unpack_metadata.sign_from.as_deref(),
+            Some("did:example:alice#key-1")
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_relative_did_urls_strict() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), _relative_did_doc(&BOB_DID_DOC)]);
+
+        let err = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                Some(ALICE_DID),
+                None,
+                &did_resolver,
+                &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
+                &PackEncryptedOptions {
+                    forward: false,
+                    strict_did_urls: true,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        assert_eq!(
+            format!("{}", err),
+            "Malformed: Unable resolve recipient did: Relative DID URL `#key-x25519-1` isn't allowed"
+        );
+    }
+
+    fn _relative_did_doc(did_doc: &DIDDoc) -> DIDDoc {
+        let relative = |url: &String| url.replacen(&did_doc.did, "", 1);
+
+        DIDDoc {
+            key_agreements: did_doc.key_agreements.iter().map(relative).collect(),
+            authentications: did_doc.authentications.iter().map(relative).collect(),
+            verification_methods: did_doc
+                .verification_methods
+                .iter()
+                .map(|vm| VerificationMethod {
+                    id: relative(&vm.id),
+                    ..vm.clone()
+                })
+                .collect(),
+            ..did_doc.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_from_not_did_or_did_url() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let res = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                "not-a-did".into(),
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+
+        let err = res.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: `from` value is not a valid DID or DID URL"
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_to_not_did_or_did_url() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let res = MESSAGE_SIMPLE
+            .pack_encrypted(
+                "not-a-did".into(),
+                None,
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+
+        let err = res.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: `to` value is not a valid DID or DID URL"
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_sign_by_not_did_or_did_url() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let res = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                ALICE_DID.into(),
+                "not-a-did".into(),
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+
+        let err = res.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: `sign_from` value is not a valid DID or DID URL"
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_sign_outside_protect_sender() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let res = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                ALICE_DID.into(),
+                ALICE_DID.into(),
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    protect_sender: true,
+                    sign_outside: true,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+
+        let err = res.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: `sign_outside` can't be used with `protect_sender` as outer signature discloses the sender"
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_from_differs_msg_from() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let mut msg = MESSAGE_SIMPLE.clone();
+        msg.from = CHARLIE_DID.to_string().into();
+        let res = msg
+            .pack_encrypted(
+                BOB_DID,
+                ALICE_DID.into(),
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+
+        let err = res.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: `message.from` value is not equal to `from` value's DID"
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_to_differs_msg_to() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let mut msg = MESSAGE_SIMPLE.clone();
+        msg.to = Some(vec![CHARLIE_DID.to_string()]);
+        let res = msg
+            .pack_encrypted(
+                BOB_DID,
+                ALICE_DID.into(),
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+
+        let err = res.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: `message.to` value does not contain `to` value's DID"
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_to_presented_in_msg_to() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let mut msg = MESSAGE_SIMPLE.clone();
+        msg.to = Some(vec![CHARLIE_DID.to_string(), BOB_DID.to_string()]);
+        let _ = msg
+            .pack_encrypted(
+                BOB_DID,
+                ALICE_DID.into(),
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_from_not_did_or_did_url_in_msg() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let mut msg = MESSAGE_SIMPLE.clone();
+        msg.from = "not-a-did".to_string().into();
+        let res = msg
+            .pack_encrypted(
+                BOB_DID,
+                "not-a-did".into(),
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+
+        let err = res.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: `from` value is not a valid DID or DID URL"
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_to_not_did_or_did_url_in_msg() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let mut msg = MESSAGE_SIMPLE.clone();
+        msg.to = Some(vec!["not-a-did".to_string()]);
+        let res = msg
+            .pack_encrypted(
+                "not-a-did".into(),
+                ALICE_DID.into(),
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+
+        let err = res.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: `to` value is not a valid DID or DID URL"
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_from_did_url_from_msg_did_positive() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let _ = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                "did:example:alice#key-x25519-1".into(),
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_to_did_url_to_msg_did_positive() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let mut msg = MESSAGE_SIMPLE.clone();
+        msg.to = Some(vec![ALICE_DID.to_string(), BOB_DID.to_string()]);
+        let _ = msg
+            .pack_encrypted(
+                "did:example:bob#key-x25519-1".into(),
+                None,
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_sign_by_differs_msg_from_positive() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let _ = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                ALICE_DID.into(),
+                CHARLIE_DID.into(),
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_from_did_from_msg_did_url() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let mut msg = MESSAGE_SIMPLE.clone();
+        msg.from = "did:example:alice#key-x25519-1".to_string().into();
+
+        let res = msg
+            .pack_encrypted(
+                BOB_DID,
+                ALICE_DID.into(),
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+
+        let err = res.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: `message.from` value is not equal to `from` value's DID"
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_to_did_to_msg_did_url() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let mut msg = MESSAGE_SIMPLE.clone();
+        msg.to = Some(vec!["did:example:bob#key-x25519-1".into()]);
+        let res = msg
+            .pack_encrypted(
+                BOB_DID,
+                None,
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+
+        let err = res.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: `message.to` value does not contain `to` value's DID"
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_from_unknown_did() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let mut msg = MESSAGE_SIMPLE.clone();
+        msg.from = "did:example:unknown".to_string().into();
+        let res = msg
+            .pack_encrypted(
+                BOB_DID,
+                "did:example:unknown".into(),
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+
+        let err = res.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::DIDNotResolved);
+
+        assert_eq!(format!("{}", err), "DID not resolved: Sender did not found");
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_from_unknown_did_url() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let from = ALICE_DID.to_string() + "#unknown-key";
+        let res = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                from.as_str().into(),
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+
+        let err = res.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::DIDUrlNotFound);
+
+        assert_eq!(
+            format!("{}", err),
+            format!(
+                "DID URL not found: No sender key agreements found. \
+                DID `{}` is resolved, present key agreements: {}",
+                ALICE_DID,
+                ALICE_DID_DOC.key_agreements.join(", ")
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_to_unknown_did() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let mut msg = MESSAGE_SIMPLE.clone();
+        msg.to = Some(vec!["did:example:unknown".into()]);
+        let res = msg
+            .pack_encrypted(
+                "did:example:unknown",
+                None,
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+
+        let err = res.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::DIDNotResolved);
+
+        assert_eq!(
+            format!("{}", err),
+            "DID not resolved: Recipient did not found"
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_to_unknown_did_url() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let to = BOB_DID.to_string() + "#unknown-key";
+        let res = MESSAGE_SIMPLE
+            .pack_encrypted(
+                to.as_str(),
+                ALICE_DID.into(),
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+
+        let err = res.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::DIDUrlNotFound);
+
+        assert_eq!(
+            format!("{}", err),
+            format!(
+                "DID URL not found: No recipient key agreements found. \
+                DID `{}` is resolved, present key agreements: {}",
+                BOB_DID,
+                BOB_DID_DOC.key_agreements.join(", ")
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_to_key_agreement_without_verification_method() {
+        // Key agreement is declared, but its verification method is absent in the DID Doc
+        let bob_did_doc = DIDDoc {
+            verification_methods: BOB_DID_DOC
+                .verification_methods
+                .iter()
+                .filter(|vm| vm.id != BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id)
+                .cloned()
+                .collect(),
+            ..BOB_DID_DOC.clone()
+        };
+
+        assert!(bob_did_doc
+            .key_agreements
+            .contains(&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id));
+
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), bob_did_doc]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        // Resolved DID isn't reported as not resolved
+        _pack_encrypted_works_to_key_agreement_without_verification_method(
+            None,
+            &did_resolver,
+            &secrets_resolver,
+            ErrorKind::Unsupported,
+        )
+        .await;
+
+        _pack_encrypted_works_to_key_agreement_without_verification_method(
+            Some(ALICE_DID),
+            &did_resolver,
+            &secrets_resolver,
+            ErrorKind::Malformed,
+        )
+        .await;
+
+        async fn _pack_encrypted_works_to_key_agreement_without_verification_method(
+            from: Option<&str>,
+            did_resolver: &ExampleDIDResolver,
+            secrets_resolver: &ExampleSecretsResolver,
+            exp_err_kind: ErrorKind,
+        ) {
+            let res = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    BOB_DID,
+                    from,
+                    None,
+                    did_resolver,
+                    secrets_resolver,
+                    &PackEncryptedOptions {
+                        forward: false,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await;
+
+            let err = res.expect_err("res is ok");
+            assert_eq!(err.kind(), exp_err_kind);
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_sign_by_unknown_did_url() {
         let did_resolver =
             ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
 
         let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
 
-        let mut msg = MESSAGE_SIMPLE.clone();
-        msg.from = "not-a-did".to_string().into();
-        let res = msg
+        let sign_by = ALICE_DID.to_string() + "#unknown-key";
+        let res = MESSAGE_SIMPLE
             .pack_encrypted(
                 BOB_DID,
-                "not-a-did".into(),
-                None,
+                ALICE_DID.into(),
+                sign_by.as_str().into(),
                 &did_resolver,
                 &secrets_resolver,
                 &PackEncryptedOptions {
@@ -2302,27 +5651,25 @@ mod tests {
             .await;
 
         let err = res.expect_err("res is ok");
-        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+        assert_eq!(err.kind(), ErrorKind::DIDUrlNotFound);
 
         assert_eq!(
             format!("{}", err),
-            "Illegal argument: `from` value is not a valid DID or DID URL"
+            "DID URL not found: Unable produce sign envelope: Signer key id not found in did doc"
         );
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_to_not_did_or_did_url_in_msg() {
+    async fn pack_encrypted_works_from_not_in_secrets() {
         let did_resolver =
             ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
 
         let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
 
-        let mut msg = MESSAGE_SIMPLE.clone();
-        msg.to = Some(vec!["not-a-did".to_string()]);
-        let res = msg
+        let res = MESSAGE_SIMPLE
             .pack_encrypted(
-                "not-a-did".into(),
-                ALICE_DID.into(),
+                BOB_DID,
+                "did:example:alice#key-x25519-not-in-secrets-1".into(),
                 None,
                 &did_resolver,
                 &secrets_resolver,
@@ -2334,25 +5681,58 @@ mod tests {
             .await;
 
         let err = res.expect_err("res is ok");
-        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+        assert_eq!(err.kind(), ErrorKind::SecretNotFound);
 
         assert_eq!(
             format!("{}", err),
-            "Illegal argument: `to` value is not a valid DID or DID URL"
+            "Secret not found: No sender secrets found"
         );
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_from_did_url_from_msg_did_positive() {
+    async fn pack_encrypted_works_sign_by_not_in_secrets() {
+        let did_resolver = ExampleDIDResolver::new(vec![
+            ALICE_DID_DOC_WITH_NO_SECRETS.clone(),
+            BOB_DID_DOC.clone(),
+        ]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let res = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                ALICE_DID.into(),
+                "did:example:alice#key-not-in-secrets-1".into(),
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+
+        let err = res.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::SecretNotFound);
+
+        assert_eq!(
+            format!("{}", err),
+            "Secret not found: Unable produce sign envelope: No signer secrets found"
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_to_not_in_secrets_positive() {
         let did_resolver =
-            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC_NO_SECRETS.clone()]);
 
         let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
 
+        let to = "did:example:bob#key-x25519-not-secrets-1";
         let _ = MESSAGE_SIMPLE
             .pack_encrypted(
-                BOB_DID,
-                "did:example:alice#key-x25519-1".into(),
+                to,
+                ALICE_DID.into(),
                 None,
                 &did_resolver,
                 &secrets_resolver,
@@ -2365,465 +5745,960 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_to_did_url_to_msg_did_positive() {
-        let did_resolver =
-            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+    async fn pack_encrypted_works_from_did_key_chosen_by_curve() {
+        let mut bob_did_doc = BOB_DID_DOC.clone();
+
+        bob_did_doc.key_agreements = vec![
+            "did:example:bob#key-p256-1".into(),
+            "did:example:bob#key-p256-2".into(),
+        ];
 
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), bob_did_doc]);
         let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
 
-        let mut msg = MESSAGE_SIMPLE.clone();
-        msg.to = Some(vec![ALICE_DID.to_string(), BOB_DID.to_string()]);
-        let _ = msg
+        let (msg, metadata) = MESSAGE_SIMPLE
             .pack_encrypted(
-                "did:example:bob#key-x25519-1".into(),
+                BOB_DID,
+                Some(ALICE_DID),
+                None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("encrypt is ok.");
+
+        // Alice X25519 key goes first in `keyAgreement`, but Bob has only P-256 keys
+        assert_eq!(
+            metadata.from_kid,
+            Some(ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id.clone())
+        );
+
+        assert_eq!(
+            metadata.to_kids,
+            vec![
+                BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id.clone(),
+                BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id.clone(),
+            ]
+        );
+
+        let (unpacked_msg, unpack_metadata) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&unpacked_msg, &*MESSAGE_SIMPLE);
+
+        assert_eq!(
+            unpack_metadata.encrypted_from_kid,
+            Some(ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id.clone())
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_from_did_no_key_of_recipient_curve() {
+        let mut bob_did_doc = BOB_DID_DOC.clone();
+
+        bob_did_doc.key_agreements = vec![
+            "did:example:bob#key-p384-1".into(),
+            "did:example:bob#key-p384-2".into(),
+        ];
+
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), bob_did_doc]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let err = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                Some(ALICE_DID),
                 None,
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::NoCompatibleCrypto);
+
+        assert_eq!(
+            format!("{}", err),
+            "No compatible crypto: No common keys between sender and recipient found"
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_to_from_different_curves() {
+        _pack_encrypted_works_to_from_different_curves(
+            "did:example:alice#key-x25519-1".into(),
+            "did:example:bob#key-p256-1",
+        )
+        .await;
+        _pack_encrypted_works_to_from_different_curves(
+            "did:example:alice#key-x25519-1".into(),
+            "did:example:bob#key-p384-1",
+        )
+        .await;
+        _pack_encrypted_works_to_from_different_curves(
+            "did:example:alice#key-x25519-1".into(),
+            "did:example:bob#key-p521-1",
+        )
+        .await;
+        _pack_encrypted_works_to_from_different_curves(
+            "did:example:alice#key-p256-1".into(),
+            "did:example:bob#key-p384-1",
+        )
+        .await;
+        _pack_encrypted_works_to_from_different_curves(
+            "did:example:alice#key-p256-1".into(),
+            "did:example:bob#key-p521-1",
+        )
+        .await;
+        _pack_encrypted_works_to_from_different_curves(
+            "did:example:alice#key-p521-1".into(),
+            "did:example:bob#key-p384-1",
+        )
+        .await;
+
+        async fn _pack_encrypted_works_to_from_different_curves(from: Option<&str>, to: &str) {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+            let res = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    to,
+                    from,
+                    None,
+                    &did_resolver,
+                    &secrets_resolver,
+                    &PackEncryptedOptions {
+                        forward: false,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await;
+
+            let err = res.expect_err("res is ok");
+            assert_eq!(err.kind(), ErrorKind::NoCompatibleCrypto);
+
+            assert_eq!(
+                format!("{}", err),
+                "No compatible crypto: No common keys between sender and recipient found"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_from_prior() {
+        let did_resolver = ExampleDIDResolver::new(vec![
+            ALICE_DID_DOC.clone(),
+            BOB_DID_DOC.clone(),
+            CHARLIE_DID_DOC.clone(),
+        ]);
+        let charlie_rotated_to_alice_secrets_resolver =
+            ExampleSecretsResolver::new(CHARLIE_ROTATED_TO_ALICE_SECRETS.clone());
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let (packed_msg, _pack_metadata) = MESSAGE_FROM_PRIOR_FULL
+            .pack_encrypted(
+                BOB_DID,
+                Some(ALICE_DID),
                 None,
                 &did_resolver,
-                &secrets_resolver,
-                &PackEncryptedOptions {
+                &charlie_rotated_to_alice_secrets_resolver,
+                &&PackEncryptedOptions {
                     forward: false,
                     ..PackEncryptedOptions::default()
                 },
             )
-            .await;
+            .await
+            .expect("Unable pack_encrypted");
+
+        let (unpacked_msg, unpack_metadata) = Message::unpack(
+            &packed_msg,
+            &did_resolver,
+            &bob_secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
+
+        assert_eq!(&unpacked_msg, &*MESSAGE_FROM_PRIOR_FULL);
+        assert_eq!(
+            unpack_metadata.from_prior_issuer_kid.as_ref(),
+            Some(&CHARLIE_SECRET_AUTH_KEY_ED25519.id)
+        );
+        assert_eq!(unpack_metadata.from_prior.as_ref(), Some(&*FROM_PRIOR_FULL));
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_sign_by_differs_msg_from_positive() {
-        let did_resolver =
-            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+    async fn pack_encrypted_works_empty_body() {
+        _pack_encrypted_works_empty_body(None, None).await;
+        _pack_encrypted_works_empty_body(Some(ALICE_DID), None).await;
+        _pack_encrypted_works_empty_body(Some(ALICE_DID), Some(ALICE_DID)).await;
+        _pack_encrypted_works_empty_body(None, Some(ALICE_DID)).await;
 
-        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        async fn _pack_encrypted_works_empty_body(from: Option<&str>, sign_by: Option<&str>) {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
 
-        let _ = MESSAGE_SIMPLE
-            .pack_encrypted(
-                BOB_DID,
-                ALICE_DID.into(),
-                CHARLIE_DID.into(),
+            let (msg, _) = MESSAGE_MINIMAL
+                .pack_encrypted(
+                    BOB_DID,
+                    from,
+                    sign_by,
+                    &did_resolver,
+                    &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
+                    &PackEncryptedOptions {
+                        forward: false,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("Unable pack_encrypted");
+
+            let (unpacked, metadata) = Message::unpack(
+                &msg,
                 &did_resolver,
-                &secrets_resolver,
-                &PackEncryptedOptions {
-                    forward: false,
-                    ..PackEncryptedOptions::default()
-                },
+                &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+                &UnpackOptions::default(),
             )
-            .await;
+            .await
+            .expect("Unable unpack");
+
+            assert_eq!(&unpacked, &*MESSAGE_MINIMAL);
+            assert!(metadata.encrypted);
+            assert_eq!(metadata.anonymous_sender, from.is_none());
+            assert_eq!(metadata.non_repudiation, sign_by.is_some());
+        }
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_from_did_from_msg_did_url() {
-        let did_resolver =
-            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
-
-        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
-
-        let mut msg = MESSAGE_SIMPLE.clone();
-        msg.from = "did:example:alice#key-x25519-1".to_string().into();
+    async fn pack_encrypted_works_from_prior_option() {
+        let did_resolver = ExampleDIDResolver::new(vec![
+            ALICE_DID_DOC.clone(),
+            BOB_DID_DOC.clone(),
+            CHARLIE_DID_DOC.clone(),
+        ]);
+        let charlie_rotated_to_alice_secrets_resolver =
+            ExampleSecretsResolver::new(CHARLIE_ROTATED_TO_ALICE_SECRETS.clone());
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
 
-        let res = msg
+        let (packed_msg, pack_metadata) = MESSAGE_SIMPLE
             .pack_encrypted(
                 BOB_DID,
-                ALICE_DID.into(),
+                Some(ALICE_DID),
                 None,
                 &did_resolver,
-                &secrets_resolver,
+                &charlie_rotated_to_alice_secrets_resolver,
                 &PackEncryptedOptions {
                     forward: false,
+                    from_prior: Some(FromPriorSpec {
+                        iss: CHARLIE_DID.into(),
+                        issuer_kid: Some(CHARLIE_SECRET_AUTH_KEY_ED25519.id.clone()),
+                    }),
                     ..PackEncryptedOptions::default()
                 },
             )
-            .await;
+            .await
+            .expect("Unable pack_encrypted");
 
-        let err = res.expect_err("res is ok");
-        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+        assert_eq!(
+            pack_metadata.from_prior_issuer_kid.as_ref(),
+            Some(&CHARLIE_SECRET_AUTH_KEY_ED25519.id)
+        );
+
+        let (unpacked_msg, unpack_metadata) = Message::unpack(
+            &packed_msg,
+            &did_resolver,
+            &bob_secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
+
+        assert!(unpacked_msg.from_prior.is_some());
 
         assert_eq!(
-            format!("{}", err),
-            "Illegal argument: `message.from` value is not equal to `from` value's DID"
+            Message {
+                from_prior: None,
+                ..unpacked_msg
+            },
+            *MESSAGE_SIMPLE
+        );
+
+        assert_eq!(
+            unpack_metadata.from_prior_issuer_kid.as_ref(),
+            Some(&CHARLIE_SECRET_AUTH_KEY_ED25519.id)
+        );
+
+        assert_eq!(
+            unpack_metadata.from_prior,
+            Some(FromPrior::build(CHARLIE_DID.into(), ALICE_DID.into()).finalize())
         );
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_to_did_to_msg_did_url() {
-        let did_resolver =
-            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
-
-        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+    async fn pack_encrypted_works_from_prior_option_already_set() {
+        let did_resolver = ExampleDIDResolver::new(vec![
+            ALICE_DID_DOC.clone(),
+            BOB_DID_DOC.clone(),
+            CHARLIE_DID_DOC.clone(),
+        ]);
+        let charlie_rotated_to_alice_secrets_resolver =
+            ExampleSecretsResolver::new(CHARLIE_ROTATED_TO_ALICE_SECRETS.clone());
 
-        let mut msg = MESSAGE_SIMPLE.clone();
-        msg.to = Some(vec!["did:example:bob#key-x25519-1".into()]);
-        let res = msg
+        let err = MESSAGE_FROM_PRIOR_FULL
             .pack_encrypted(
                 BOB_DID,
-                None,
+                Some(ALICE_DID),
                 None,
                 &did_resolver,
-                &secrets_resolver,
+                &charlie_rotated_to_alice_secrets_resolver,
                 &PackEncryptedOptions {
                     forward: false,
+                    from_prior: Some(FromPriorSpec {
+                        iss: CHARLIE_DID.into(),
+                        issuer_kid: None,
+                    }),
                     ..PackEncryptedOptions::default()
                 },
             )
-            .await;
+            .await
+            .expect_err("res is ok");
 
-        let err = res.expect_err("res is ok");
         assert_eq!(err.kind(), ErrorKind::IllegalArgument);
 
         assert_eq!(
             format!("{}", err),
-            "Illegal argument: `message.to` value does not contain `to` value's DID"
+            "Illegal argument: `message.from_prior` is already set"
         );
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_from_unknown_did() {
-        let did_resolver =
-            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+    async fn pack_encrypted_works_deterministic_rng() {
+        _pack_encrypted_works_deterministic_rng(None, None, false).await;
+        _pack_encrypted_works_deterministic_rng(Some(ALICE_DID), None, false).await;
+        _pack_encrypted_works_deterministic_rng(Some(ALICE_DID), None, true).await;
+        _pack_encrypted_works_deterministic_rng(Some(ALICE_DID), Some(ALICE_DID), false).await;
 
-        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        async fn _pack_encrypted_works_deterministic_rng(
+            from: Option<&str>,
+            sign_by: Option<&str>,
+            protect_sender: bool,
+        ) {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
 
-        let mut msg = MESSAGE_SIMPLE.clone();
-        msg.from = "did:example:unknown".to_string().into();
-        let res = msg
-            .pack_encrypted(
-                BOB_DID,
-                "did:example:unknown".into(),
-                None,
+            let options = PackEncryptedOptions {
+                forward: false,
+                protect_sender,
+                ..PackEncryptedOptions::default()
+            };
+
+            let pack = |seed: Option<[u8; 32]>| {
+                set_deterministic_rng(seed);
+
+                let did_resolver = &did_resolver;
+                let secrets_resolver = &secrets_resolver;
+                let options = &options;
+
+                async move {
+                    let (msg, _) = MESSAGE_SIMPLE
+                        .pack_encrypted(
+                            BOB_DID,
+                            from,
+                            sign_by,
+                            did_resolver,
+                            secrets_resolver,
+                            options,
+                        )
+                        .await
+                        .expect("encrypt is ok.");
+
+                    msg
+                }
+            };
+
+            let msg = pack(Some([1; 32])).await;
+            assert_eq!(pack(Some([1; 32])).await, msg);
+            assert_ne!(pack(Some([2; 32])).await, msg);
+
+            // OS CSPRNG is restored
+            let msg = pack(None).await;
+            assert_ne!(pack(None).await, msg);
+
+            let (unpacked, _) = Message::unpack(
+                &msg,
                 &did_resolver,
-                &secrets_resolver,
-                &PackEncryptedOptions {
-                    forward: false,
-                    ..PackEncryptedOptions::default()
-                },
+                &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+                &UnpackOptions::default(),
             )
-            .await;
-
-        let err = res.expect_err("res is ok");
-        assert_eq!(err.kind(), ErrorKind::DIDNotResolved);
+            .await
+            .expect("unpack is ok.");
 
-        assert_eq!(format!("{}", err), "DID not resolved: Sender did not found");
+            assert_eq!(&unpacked, &*MESSAGE_SIMPLE);
+        }
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_from_unknown_did_url() {
-        let did_resolver =
-            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+    async fn pack_encrypted_works_nonce_source() {
+        _pack_encrypted_works_nonce_source(None, AnonCryptAlg::A256gcmEcdhEsA256kw, 12).await;
+        _pack_encrypted_works_nonce_source(None, AnonCryptAlg::Xc20pEcdhEsA256kw, 24).await;
+        _pack_encrypted_works_nonce_source(Some(ALICE_DID), AnonCryptAlg::A256gcmEcdhEsA256kw, 16)
+            .await;
 
-        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        async fn _pack_encrypted_works_nonce_source(
+            from: Option<&str>,
+            enc_alg_anon: AnonCryptAlg,
+            nonce_len: usize,
+        ) {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
 
-        let from = ALICE_DID.to_string() + "#unknown-key";
-        let res = MESSAGE_SIMPLE
-            .pack_encrypted(
-                BOB_DID,
-                from.as_str().into(),
-                None,
-                &did_resolver,
-                &secrets_resolver,
-                &PackEncryptedOptions {
-                    forward: false,
-                    ..PackEncryptedOptions::default()
-                },
-            )
-            .await;
+            let options = PackEncryptedOptions {
+                forward: false,
+                enc_alg_anon,
+                nonce_source: Some(Arc::new(CounterNonceSource(AtomicU64::new(1)))),
+                ..PackEncryptedOptions::default()
+            };
 
-        let err = res.expect_err("res is ok");
-        assert_eq!(err.kind(), ErrorKind::DIDUrlNotFound);
+            for counter in 1..=2 {
+                let (msg, _) = MESSAGE_SIMPLE
+                    .pack_encrypted(
+                        BOB_DID,
+                        from,
+                        None,
+                        &did_resolver,
+                        &secrets_resolver,
+                        &options,
+                    )
+                    .await
+                    .expect("encrypt is ok.");
 
-        assert_eq!(
-            format!("{}", err),
-            "DID URL not found: No sender key agreements found"
-        );
-    }
+                assert_eq!(_jwe_iv(&msg), _counter_nonce(nonce_len, counter));
+
+                let (unpacked, _) = Message::unpack(
+                    &msg,
+                    &did_resolver,
+                    &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+                    &UnpackOptions::default(),
+                )
+                .await
+                .expect("unpack is ok.");
 
+                assert_eq!(&unpacked, &*MESSAGE_SIMPLE);
+            }
+        }
+    }
     #[tokio::test]
-    async fn pack_encrypted_works_to_unknown_did() {
+    async fn pack_encrypted_works_nonce_source_error() {
         let did_resolver =
             ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
-
         let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
 
-        let mut msg = MESSAGE_SIMPLE.clone();
-        msg.to = Some(vec!["did:example:unknown".into()]);
-        let res = msg
+        let res = MESSAGE_SIMPLE
             .pack_encrypted(
-                "did:example:unknown",
+                BOB_DID,
                 None,
                 None,
                 &did_resolver,
                 &secrets_resolver,
                 &PackEncryptedOptions {
                     forward: false,
+                    nonce_source: Some(Arc::new(FailingNonceSource)),
                     ..PackEncryptedOptions::default()
                 },
             )
             .await;
 
         let err = res.expect_err("res is ok");
-        assert_eq!(err.kind(), ErrorKind::DIDNotResolved);
+        assert_eq!(err.kind(), ErrorKind::InvalidState);
 
         assert_eq!(
             format!("{}", err),
-            "DID not resolved: Recipient did not found"
+            "Invalid state: Unable produce anoncrypt envelope: Unable generate nonce: Nonces exhausted"
         );
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_to_unknown_did_url() {
-        let did_resolver =
-            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+    async fn pack_encrypted_works_session_key_provider() {
+        _pack_encrypted_works_session_key_provider(AnonCryptAlg::A256cbcHs512EcdhEsA256kw, 64)
+            .await;
+        _pack_encrypted_works_session_key_provider(AnonCryptAlg::A256gcmEcdhEsA256kw, 32).await;
+        _pack_encrypted_works_session_key_provider(AnonCryptAlg::Xc20pEcdhEsA256kw, 32).await;
 
-        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        async fn _pack_encrypted_works_session_key_provider(
+            enc_alg_anon: AnonCryptAlg,
+            cek_len: usize,
+        ) {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
 
-        let to = BOB_DID.to_string() + "#unknown-key";
-        let res = MESSAGE_SIMPLE
-            .pack_encrypted(
-                to.as_str(),
-                ALICE_DID.into(),
-                None,
-                &did_resolver,
-                &secrets_resolver,
-                &PackEncryptedOptions {
-                    forward: false,
-                    ..PackEncryptedOptions::default()
-                },
-            )
-            .await;
+            let pack = |ratchet: Arc<RatchetSessionKeyProvider>| {
+                let did_resolver = &did_resolver;
+                let secrets_resolver = &secrets_resolver;
+                let enc_alg_anon = enc_alg_anon.clone();
 
-        let err = res.expect_err("res is ok");
-        assert_eq!(err.kind(), ErrorKind::DIDUrlNotFound);
+                async move {
+                    let options = PackEncryptedOptions {
+                        forward: false,
+                        enc_alg_anon,
+                        session_key_provider: Some(ratchet),
+                        ..PackEncryptedOptions::default()
+                    };
+
+                    let (msg, _) = MESSAGE_SIMPLE
+                        .pack_encrypted(
+                            BOB_DID,
+                            None,
+                            None,
+                            did_resolver,
+                            secrets_resolver,
+                            &options,
+                        )
+                        .await
+                        .expect("encrypt is ok.");
+
+                    msg
+                }
+            };
+
+            let ratchet = Arc::new(RatchetSessionKeyProvider::new([1; 32]));
+            let msg1 = pack(ratchet.clone()).await;
+            let msg2 = pack(ratchet.clone()).await;
+
+            let ceks = ratchet.ceks.lock().unwrap().clone();
+            assert_eq!(ceks.len(), 2);
+            assert_eq!(ceks[0].len(), cek_len);
+            assert_ne!(ceks[0], ceks[1]);
+
+            // The ratchet is deterministic, so the same session secret reproduces the CEKs
+            let other_ratchet = Arc::new(RatchetSessionKeyProvider::new([1; 32]));
+            pack(other_ratchet.clone()).await;
+            pack(other_ratchet.clone()).await;
+            assert_eq!(*other_ratchet.ceks.lock().unwrap(), ceks);
+
+            for msg in [msg1, msg2].iter() {
+                let (unpacked, metadata) = Message::unpack(
+                    msg,
+                    &did_resolver,
+                    &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+                    &UnpackOptions::default(),
+                )
+                .await
+                .expect("unpack is ok.");
 
-        assert_eq!(
-            format!("{}", err),
-            "DID URL not found: No recipient key agreements found"
-        );
+                assert_eq!(&unpacked, &*MESSAGE_SIMPLE);
+                assert!(metadata.anonymous_sender);
+            }
+        }
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_sign_by_unknown_did_url() {
+    async fn pack_encrypted_works_session_key_provider_authcrypt() {
         let did_resolver =
             ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
-
         let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let ratchet = Arc::new(RatchetSessionKeyProvider::new([1; 32]));
 
-        let sign_by = ALICE_DID.to_string() + "#unknown-key";
-        let res = MESSAGE_SIMPLE
+        MESSAGE_SIMPLE
             .pack_encrypted(
                 BOB_DID,
-                ALICE_DID.into(),
-                sign_by.as_str().into(),
+                Some(ALICE_DID),
+                None,
                 &did_resolver,
                 &secrets_resolver,
                 &PackEncryptedOptions {
                     forward: false,
+                    session_key_provider: Some(ratchet.clone()),
                     ..PackEncryptedOptions::default()
                 },
             )
-            .await;
-
-        let err = res.expect_err("res is ok");
-        assert_eq!(err.kind(), ErrorKind::DIDUrlNotFound);
+            .await
+            .expect("encrypt is ok.");
 
-        assert_eq!(
-            format!("{}", err),
-            "DID URL not found: Unable produce sign envelope: Signer key id not found in did doc"
-        );
+        // Authenticated encryption keeps random CEKs
+        assert!(ratchet.ceks.lock().unwrap().is_empty());
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_from_not_in_secrets() {
+    async fn pack_encrypted_multi_works_nonce_source_session_key_provider() {
         let did_resolver =
             ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
-
         let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let ratchet = Arc::new(RatchetSessionKeyProvider::new([1; 32]));
 
-        let res = MESSAGE_SIMPLE
-            .pack_encrypted(
-                BOB_DID,
-                "did:example:alice#key-x25519-not-in-secrets-1".into(),
+        let msg = Message::build(
+            "1234567890".to_owned(),
+            "http://example.com/protocols/group/1.0/message".to_owned(),
+            json!({"text": "hello group"}),
+        )
+        .to_many(vec![ALICE_DID.to_owned(), BOB_DID.to_owned()])
+        .finalize();
+
+        let (packed_msg, _) = msg
+            .pack_encrypted_multi(
+                &[&ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id, BOB_DID],
                 None,
                 &did_resolver,
                 &secrets_resolver,
                 &PackEncryptedOptions {
-                    forward: false,
+                    nonce_source: Some(Arc::new(CounterNonceSource(AtomicU64::new(7)))),
+                    session_key_provider: Some(ratchet.clone()),
                     ..PackEncryptedOptions::default()
                 },
             )
-            .await;
+            .await
+            .expect("Unable encrypt");
 
-        let err = res.expect_err("res is ok");
-        assert_eq!(err.kind(), ErrorKind::SecretNotFound);
+        assert_eq!(_jwe_iv(&packed_msg), _counter_nonce(24, 7));
+        assert_eq!(ratchet.ceks.lock().unwrap().len(), 1);
 
-        assert_eq!(
-            format!("{}", err),
-            "Secret not found: No sender secrets found"
-        );
+        let (unpacked_msg, _) = Message::unpack(
+            &packed_msg,
+            &did_resolver,
+            &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
+
+        assert_eq!(unpacked_msg, msg);
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_sign_by_not_in_secrets() {
-        let did_resolver = ExampleDIDResolver::new(vec![
-            ALICE_DID_DOC_WITH_NO_SECRETS.clone(),
-            BOB_DID_DOC.clone(),
-        ]);
-
+    async fn pack_encrypted_to_jwk_works_nonce_source_session_key_provider() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
         let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let ratchet = Arc::new(RatchetSessionKeyProvider::new([1; 32]));
 
-        let res = MESSAGE_SIMPLE
-            .pack_encrypted(
-                BOB_DID,
-                ALICE_DID.into(),
-                "did:example:alice#key-not-in-secrets-1".into(),
+        let to_jwk = json!({
+            "kty": "OKP",
+            "crv": "X25519",
+            "x": "GDTrI66K0pFfO54tlCSvfjjNapIs44dzpneBgyx0S3E",
+        });
+
+        let to_kid = build_did_jwk_kid(&to_jwk).expect("build_did_jwk_kid is ok.");
+
+        let msg = Message::build(
+            "1234567890".to_owned(),
+            "http://example.com/protocols/lets_do_lunch/1.0/proposal".to_owned(),
+            json!({"messagespecificattribute": "and its value"}),
+        )
+        .finalize();
+
+        let (packed_msg, _) = msg
+            .pack_encrypted_to_jwk(
+                &to_jwk,
+                None,
                 &did_resolver,
                 &secrets_resolver,
                 &PackEncryptedOptions {
-                    forward: false,
+                    nonce_source: Some(Arc::new(CounterNonceSource(AtomicU64::new(7)))),
+                    session_key_provider: Some(ratchet.clone()),
                     ..PackEncryptedOptions::default()
                 },
             )
-            .await;
+            .await
+            .expect("Unable encrypt");
 
-        let err = res.expect_err("res is ok");
-        assert_eq!(err.kind(), ErrorKind::SecretNotFound);
+        assert_eq!(_jwe_iv(&packed_msg), _counter_nonce(24, 7));
+        assert_eq!(ratchet.ceks.lock().unwrap().len(), 1);
 
-        assert_eq!(
-            format!("{}", err),
-            "Secret not found: Unable produce sign envelope: No signer secrets found"
-        );
+        let (unpacked_msg, _) = Message::unpack(
+            &packed_msg,
+            &did_resolver,
+            &ExampleSecretsResolver::new(vec![Secret {
+                id: to_kid,
+                ..BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.clone()
+            }]),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
+
+        assert_eq!(unpacked_msg, msg);
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_to_not_in_secrets_positive() {
-        let did_resolver =
-            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC_NO_SECRETS.clone()]);
+    async fn pack_encrypted_works_recipient_kids_order() {
+        // Recipient key agreements are declared in reverse order
+        let mut bob_did_doc = BOB_DID_DOC.clone();
+
+        bob_did_doc.key_agreements = vec![
+            BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id.clone(),
+            BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id.clone(),
+            BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone(),
+        ];
+
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), bob_did_doc]);
+        let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
 
-        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let sorted_kids = vec![
+            BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone(),
+            BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id.clone(),
+            BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id.clone(),
+        ];
+
+        let mut document_kids = sorted_kids.clone();
+        document_kids.reverse();
+
+        for from in [None, Some(ALICE_DID)] {
+            for (preserve_recipient_kids_order, exp_kids) in
+                [(false, &sorted_kids), (true, &document_kids)]
+            {
+                let (msg, pack_metadata) = MESSAGE_SIMPLE
+                    .pack_encrypted(
+                        BOB_DID,
+                        from,
+                        None,
+                        &did_resolver,
+                        &alice_secrets_resolver,
+                        &PackEncryptedOptions {
+                            forward: false,
+                            preserve_recipient_kids_order,
+                            ..PackEncryptedOptions::default()
+                        },
+                    )
+                    .await
+                    .expect("Unable encrypt");
 
-        let to = "did:example:bob#key-x25519-not-secrets-1";
-        let _ = MESSAGE_SIMPLE
+                assert_eq!(&pack_metadata.to_kids, exp_kids);
+
+                let jwe: Value = serde_json::from_str(&msg).expect("Unable parse jwe");
+
+                let jwe_kids: Vec<String> = jwe["recipients"]
+                    .as_array()
+                    .expect("recipients is array")
+                    .iter()
+                    .map(|r| r["header"]["kid"].as_str().expect("kid is str").to_owned())
+                    .collect();
+
+                assert_eq!(&jwe_kids, exp_kids);
+
+                let (_, unpack_metadata) = Message::unpack(
+                    &msg,
+                    &did_resolver,
+                    &bob_secrets_resolver,
+                    &UnpackOptions::default(),
+                )
+                .await
+                .expect("Unable unpack");
+
+                assert_eq!(unpack_metadata.encrypted_to_kids.as_ref(), Some(exp_kids));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_works_reject_key_reuse() {
+        // The same P-256 key material is declared for both authentication and key agreement
+        let signing_kid = "did:example:alice#key-p256-signing";
+
+        let mut alice_did_doc = ALICE_DID_DOC.clone();
+        alice_did_doc.authentications.push(signing_kid.into());
+
+        alice_did_doc.verification_methods.push(VerificationMethod {
+            id: signing_kid.into(),
+            ..ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.clone()
+        });
+
+        let mut alice_secrets = ALICE_SECRETS.clone();
+
+        alice_secrets.push(Secret {
+            id: signing_kid.into(),
+            ..ALICE_SECRET_KEY_AGREEMENT_KEY_P256.clone()
+        });
+
+        let did_resolver = ExampleDIDResolver::new(vec![alice_did_doc, BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(alice_secrets);
+
+        let (_, metadata) = _pack(signing_kid, false, &did_resolver, &secrets_resolver)
+            .await
+            .expect("pack is ok.");
+
+        assert_eq!(metadata.sign_by_kid.as_deref(), Some(signing_kid));
+
+        assert_eq!(
+            metadata.from_kid.as_ref(),
+            Some(&ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id)
+        );
+
+        let err = _pack(signing_kid, true, &did_resolver, &secrets_resolver)
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: Signing key did:example:alice#key-p256-signing and encryption key did:example:alice#key-p256-1 are the same key"
+        );
+
+        // Key reuse is rejected before encryption
+        let err = MESSAGE_SIMPLE
             .pack_encrypted(
-                to,
-                ALICE_DID.into(),
-                None,
+                BOB_DID,
+                Some(&ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id),
+                Some(signing_kid),
                 &did_resolver,
                 &secrets_resolver,
                 &PackEncryptedOptions {
                     forward: false,
+                    reject_key_reuse: true,
+                    nonce_source: Some(Arc::new(FailingNonceSource)),
                     ..PackEncryptedOptions::default()
                 },
             )
-            .await;
-    }
+            .await
+            .expect_err("res is ok");
 
-    #[tokio::test]
-    async fn pack_encrypted_works_to_from_different_curves() {
-        _pack_encrypted_works_to_from_different_curves(
-            "did:example:alice#key-x25519-1".into(),
-            "did:example:bob#key-p256-1",
-        )
-        .await;
-        _pack_encrypted_works_to_from_different_curves(
-            "did:example:alice#key-x25519-1".into(),
-            "did:example:bob#key-p384-1",
-        )
-        .await;
-        _pack_encrypted_works_to_from_different_curves(
-            "did:example:alice#key-x25519-1".into(),
-            "did:example:bob#key-p521-1",
-        )
-        .await;
-        _pack_encrypted_works_to_from_different_curves(
-            "did:example:alice#key-p256-1".into(),
-            "did:example:bob#key-p384-1",
-        )
-        .await;
-        _pack_encrypted_works_to_from_different_curves(
-            "did:example:alice#key-p256-1".into(),
-            "did:example:bob#key-p521-1",
-        )
-        .await;
-        _pack_encrypted_works_to_from_different_curves(
-            "did:example:alice#key-p521-1".into(),
-            "did:example:bob#key-p384-1",
-        )
-        .await;
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
 
-        async fn _pack_encrypted_works_to_from_different_curves(from: Option<&str>, to: &str) {
-            let did_resolver =
-                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let err = MESSAGE_SIMPLE
+            .pack_encrypted_plan(
+                BOB_DID,
+                Some(&ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id),
+                Some(signing_kid),
+                &did_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    reject_key_reuse: true,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect_err("res is ok");
 
-            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
 
-            let res = MESSAGE_SIMPLE
+        // Different keys
+        _pack(
+            &ALICE_AUTH_METHOD_P256.id,
+            true,
+            &did_resolver,
+            &secrets_resolver,
+        )
+        .await
+        .expect("pack is ok.");
+
+        async fn _pack(
+            sign_by: &str,
+            reject_key_reuse: bool,
+            did_resolver: &ExampleDIDResolver,
+            secrets_resolver: &ExampleSecretsResolver,
+        ) -> Result<(String, PackEncryptedMetadata)> {
+            MESSAGE_SIMPLE
                 .pack_encrypted(
-                    to,
-                    from,
-                    None,
-                    &did_resolver,
-                    &secrets_resolver,
+                    BOB_DID,
+                    Some(&ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id),
+                    Some(sign_by),
+                    did_resolver,
+                    secrets_resolver,
                     &PackEncryptedOptions {
                         forward: false,
+                        reject_key_reuse,
                         ..PackEncryptedOptions::default()
                     },
                 )
-                .await;
+                .await
+        }
+    }
 
-            let err = res.expect_err("res is ok");
-            assert_eq!(err.kind(), ErrorKind::NoCompatibleCrypto);
+    struct FailingNonceSource;
 
-            assert_eq!(
-                format!("{}", err),
-                "No compatible crypto: No common keys between sender and recipient found"
-            );
+    impl NonceSource for FailingNonceSource {
+        fn fill_nonce(&self, _nonce: &mut [u8]) -> Result<()> {
+            Err(err_msg(ErrorKind::InvalidState, "Nonces exhausted"))
         }
     }
 
-    #[tokio::test]
-    async fn pack_encrypted_works_from_prior() {
-        let did_resolver = ExampleDIDResolver::new(vec![
-            ALICE_DID_DOC.clone(),
-            BOB_DID_DOC.clone(),
-            CHARLIE_DID_DOC.clone(),
-        ]);
-        let charlie_rotated_to_alice_secrets_resolver =
-            ExampleSecretsResolver::new(CHARLIE_ROTATED_TO_ALICE_SECRETS.clone());
-        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+    // Big-endian counter in the last 8 bytes of the nonce
+    struct CounterNonceSource(AtomicU64);
 
-        let (packed_msg, _pack_metadata) = MESSAGE_FROM_PRIOR_FULL
-            .pack_encrypted(
-                BOB_DID,
-                Some(ALICE_DID),
-                None,
-                &did_resolver,
-                &charlie_rotated_to_alice_secrets_resolver,
-                &&PackEncryptedOptions {
-                    forward: false,
-                    ..PackEncryptedOptions::default()
-                },
-            )
-            .await
-            .expect("Unable pack_encrypted");
+    fn _counter_nonce(nonce_len: usize, counter: u64) -> Vec<u8> {
+        let mut nonce = vec![0; nonce_len];
+        nonce[nonce_len - 8..].copy_from_slice(&counter.to_be_bytes());
+        nonce
+    }
 
-        let (unpacked_msg, unpack_metadata) = Message::unpack(
-            &packed_msg,
-            &did_resolver,
-            &bob_secrets_resolver,
-            &UnpackOptions::default(),
-        )
-        .await
-        .expect("Unable unpack");
+    fn _jwe_iv(msg: &str) -> Vec<u8> {
+        let msg: Value = serde_json::from_str(msg).expect("Unable from_str");
+        base64::decode_config(msg["iv"].as_str().unwrap(), base64::URL_SAFE_NO_PAD)
+            .expect("Unable decode iv")
+    }
 
-        assert_eq!(&unpacked_msg, &*MESSAGE_FROM_PRIOR_FULL);
-        assert_eq!(
-            unpack_metadata.from_prior_issuer_kid.as_ref(),
-            Some(&CHARLIE_SECRET_AUTH_KEY_ED25519.id)
-        );
-        assert_eq!(unpack_metadata.from_prior.as_ref(), Some(&*FROM_PRIOR_FULL));
+    impl NonceSource for CounterNonceSource {
+        fn fill_nonce(&self, nonce: &mut [u8]) -> Result<()> {
+            let counter = self.0.fetch_add(1, Ordering::SeqCst);
+            let len = nonce.len();
+            nonce.fill(0);
+            nonce[len - 8..].copy_from_slice(&counter.to_be_bytes());
+            Ok(())
+        }
+    }
+
+    // Hash ratchet: each CEK is derived from the current chain key that is then replaced
+    // with its hash, so previous CEKs can't be derived from the current state.
+    // Produced CEKs are recorded for assertions.
+    struct RatchetSessionKeyProvider {
+        chain_key: Mutex<[u8; 32]>,
+        ceks: Mutex<Vec<Vec<u8>>>,
+    }
+
+    impl RatchetSessionKeyProvider {
+        fn new(session_secret: [u8; 32]) -> Self {
+            RatchetSessionKeyProvider {
+                chain_key: Mutex::new(session_secret),
+                ceks: Mutex::new(vec![]),
+            }
+        }
+    }
+
+    impl SessionKeyProvider for RatchetSessionKeyProvider {
+        fn next_cek(&self, cek: &mut [u8]) -> Result<()> {
+            let mut chain_key = self.chain_key.lock().unwrap();
+
+            for (i, chunk) in cek.chunks_mut(32).enumerate() {
+                let block = Sha256::new()
+                    .chain(&*chain_key)
+                    .chain(&[1u8, i as u8])
+                    .finalize();
+
+                chunk.copy_from_slice(&block[..chunk.len()]);
+            }
+
+            let next_chain_key = Sha256::new().chain(&*chain_key).chain(&[0u8]).finalize();
+            chain_key.copy_from_slice(&next_chain_key);
+
+            self.ceks.lock().unwrap().push(cek.to_vec());
+
+            Ok(())
+        }
     }
 
     fn _verify_authcrypt<CE, KDF, KE, KW>(
         msg: &str,
         to_keys: Vec<&Secret>,
         from_key: &VerificationMethod,
+        enc_alg: jwe::EncAlgorithm,
     ) -> String
     where
         CE: KeyAeadInPlace + KeySecretBytes,
@@ -2849,7 +6724,7 @@ mod tests {
         );
 
         assert_eq!(msg.protected.alg, jwe::Algorithm::Ecdh1puA256kw);
-        assert_eq!(msg.protected.enc, jwe::EncAlgorithm::A256cbcHs512);
+        assert_eq!(msg.protected.enc, enc_alg);
         assert_eq!(msg.protected.skid, Some(from_key.id.as_ref()));
 
         let mut common_msg: Option<Vec<u8>> = None;
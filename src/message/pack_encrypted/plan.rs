@@ -0,0 +1,675 @@
+use serde::Serialize;
+
+use crate::{
+    algorithms::{AnonCryptAlg, AuthCryptAlg},
+    did::{
+        did_resolver::{RelativeDIDUrlsResolver, TimeoutDIDResolver},
+        DIDResolver,
+    },
+    error::{ErrorKind, Result, ResultContext, ResultExt},
+    message::{
+        pack_encrypted::{
+            _check_key_reuse, _check_sign_outside, _recipient_kids_with_prefix,
+            anoncrypt::anoncrypt_keys, authcrypt::authcrypt_keys,
+        },
+        pack_signed::sign_kid,
+    },
+    protocols::routing::{build_forward_message, resolve_forward_route},
+    utils::crypto::{AsKnownKeyPair, KnownKeyAlg},
+    Message, MessagingServiceMetadata, PackEncryptedOptions,
+};
+
+/// Prediction of `pack_encrypted` execution made by `Message::pack_encrypted_plan`.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct PackPlan {
+    /// Identifiers (DID URLs) of recipient keys the message will be encrypted for.
+    pub to_kids: Vec<String>,
+
+    /// Identifier (DID URL) of sender key that will be used for message encryption.
+    pub from_kid: Option<String>,
+
+    /// Identifier (DID URL) of sender key that will be used for message sign.
+    pub sign_by_kid: Option<String>,
+
+    /// Identifier (DID URL) of prior DID key that will be used for `from_prior` header sign
+    /// (see `PackEncryptedOptions::from_prior`).
+    pub from_prior_issuer_kid: Option<String>,
+
+    /// Algorithm that will be used for authenticated encryption.
+    pub enc_alg_auth: Option<AuthCryptAlg>,
+
+    /// Algorithm that will be used for anonymous encryption
+    /// (of the message itself or to protect the sender).
+    pub enc_alg_anon: Option<AnonCryptAlg>,
+
+    /// Whether the message will be wrapped in Forward envelope(s) for mediator(s).
+    pub forwarded: bool,
+
+    /// Information about messaging service that will be used.
+    pub messaging_service: Option<MessagingServiceMetadata>,
+
+    /// Estimated size of the packed message in bytes.
+    /// It can slightly differ from actual size (for ex. because of generated Forward message ids).
+    pub estimated_size: usize,
+}
+
+impl Message {
+    /// Plans `pack_encrypted` execution without performing any crypto operations.
+    /// Reports recipient and sender keys, algorithms, forwarding and estimated size
+    /// of the packed message.
+    ///
+    /// Keys are selected by the same code `pack_encrypted` uses, but only DIDs are resolved
+    /// and secrets are not consulted. So if `from` (or `sign_by`, `options.from_prior` issuer)
+    /// is a DID the first compatible key of the sender DID Doc is reported, while `pack_encrypted`
+    /// uses the first key the sender has a secret for. Pass key IDs to get exact prediction.
+    ///
+    /// # Params
+    /// Same as for `pack_encrypted` except `secrets_resolver`.
+    ///
+    /// # Returns
+    /// Predicted parameters of `pack_encrypted` execution.
+    ///
+    /// # Errors
+    /// - `DIDNotResolved` Sender or recipient DID not found.
+    /// - `DIDUrlNotFound` DID doesn't contain mentioned DID Urls (for ex., key id)
+    /// - `NoCompatibleCrypto` No compatible keys are found between sender and recipient.
+    /// - `IllegalArgument` Invalid `to`, `from` or `sign_by` value or incompatible options.
+    /// - `Unsupported` Used crypto or method is unsupported.
+    pub async fn pack_encrypted_plan<'dr>(
+        &self,
+        to: &str,
+        from: Option<&str>,
+        sign_by: Option<&str>,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        options: &PackEncryptedOptions,
+    ) -> Result<PackPlan> {
+        self._validate_pack_encrypted(to, from, sign_by)?;
+        _check_sign_outside(from, sign_by, options)?;
+
+        let timeout_did_resolver = TimeoutDIDResolver::new(did_resolver, options.resolver_timeout);
+        let did_resolver: &dyn DIDResolver =
+            &RelativeDIDUrlsResolver::new(&timeout_did_resolver, options.strict_did_urls);
+
+        let prefixed_kids = match options.recipient_kid_prefix.as_deref() {
+            Some(prefix) => Some(_recipient_kids_with_prefix(to, prefix, did_resolver).await?),
+            None => None,
+        };
+
+        let to_subset: Option<Vec<&str>> = prefixed_kids
+            .as_ref()
+            .map(|kids| kids.iter().map(String::as_str).collect());
+
+        let to_subset = to_subset.as_deref();
+
+        let (mut size, from_prior_issuer_kid) = self._payload_size(did_resolver, options).await?;
+
+        let sign_by_kid = match sign_by {
+            Some(sign_by) => Some(sign_kid(sign_by, did_resolver, None).await?),
+            None => None,
+        };
+
+        match sign_by_kid {
+            Some(ref sign_by_kid) if !options.sign_outside => {
+                size = _jws_size(size, sign_by_kid);
+            }
+            _ => {}
+        }
+
+        let aad = options.aad.as_deref();
+
+        let (to_kids, from_kid, enc_alg_auth, enc_alg_anon) = if let Some(from) = from {
+            let (_, from_key, to_keys) = authcrypt_keys(
+                to,
+                to_subset,
+                from,
+                did_resolver,
+                None,
+                &options.algorithms,
+                options.preserve_recipient_kids_order,
+            )
+            .await?;
+
+            let x25519 = from_key.key_alg() == KnownKeyAlg::X25519;
+            let to_kids: Vec<_> = to_keys.into_iter().map(|key| key.id).collect();
+
+            size = _jwe_size(
+                size,
+                x25519,
+                &to_kids,
+                Some(from_key.id.as_str()),
+                aad,
+                _auth_enc(&options.enc_alg_auth),
+            );
+
+            let enc_alg_anon = if options.protect_sender {
+                size = _jwe_size(
+                    size,
+                    x25519,
+                    &to_kids,
+                    None,
+                    aad,
+                    _anon_enc(&options.enc_alg_anon),
+                );
+
+                Some(options.enc_alg_anon.clone())
+            } else {
+                None
+            };
+
+            (
+                to_kids,
+                Some(from_key.id),
+                Some(options.enc_alg_auth.clone()),
+                enc_alg_anon,
+            )
+        } else {
+            let (algorithm, to_keys) = anoncrypt_keys(
+                to,
+                to_subset,
+                did_resolver,
+                &options.algorithms,
+                options.preserve_recipient_kids_order,
+            )
+            .await?;
+
+            let to_kids: Vec<_> = to_keys.into_iter().map(|key| key.id).collect();
+
+            size = _jwe_size(
+                size,
+                algorithm.crv() == "X25519",
+                &to_kids,
+                None,
+                aad,
+                _anon_enc(&options.enc_alg_anon),
+            );
+
+            (to_kids, None, None, Some(options.enc_alg_anon.clone()))
+        };
+
+        if let (true, Some(sign_by_kid), Some(from_kid)) =
+            (options.reject_key_reuse, &sign_by_kid, &from_kid)
+        {
+            _check_key_reuse(sign_by_kid, from_kid, did_resolver).await?;
+        }
+
+        if options.flattened_jwe && to_kids.len() == 1 {
+            size -= FLATTENED_JWE_SAVING;
+        }
+
+        match sign_by_kid {
+            Some(ref sign_by_kid) if options.sign_outside => {
+                size = _jws_size(size, sign_by_kid);
+            }
+            _ => {}
+        }
+
+        let (messaging_service, forwarded) =
+            match resolve_forward_route(to, did_resolver, options).await? {
+                Some((routing_keys, messaging_service)) => {
+                    size = _forward_size(size, to, &routing_keys, did_resolver, options).await?;
+                    (Some(messaging_service), true)
+                }
+                None => (None, false),
+            };
+
+        Ok(PackPlan {
+            to_kids,
+            from_kid,
+            sign_by_kid,
+            from_prior_issuer_kid,
+            enc_alg_auth,
+            enc_alg_anon,
+            forwarded,
+            messaging_service,
+            estimated_size: size,
+        })
+    }
+
+    /// Size of the plaintext message to be signed or encrypted including `from_prior` header
+    /// added by `options.from_prior`, and key the header will be signed with.
+    async fn _payload_size<'dr>(
+        &self,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        options: &PackEncryptedOptions,
+    ) -> Result<(usize, Option<String>)> /* (size, from_prior_issuer_kid) */ {
+        let (msg, from_prior_issuer_kid) = match options.from_prior {
+            Some(ref spec) => {
+                let from_prior = self._build_from_prior(spec)?;
+                from_prior.validate_pack(spec.issuer_kid.as_deref())?;
+
+                let issuer_kid = from_prior
+                    .issuer_kid(spec.issuer_kid.as_deref(), did_resolver, None)
+                    .await
+                    .context("Unable produce from_prior")?;
+
+                let from_prior = serde_json::to_string(&from_prior)
+                    .kind(ErrorKind::InvalidState, "Unable serialize from_prior")?;
+
+                // ASCII placeholder of the same size as the compact JWS `from_prior` value
+                let mut msg = self.clone();
+                msg.from_prior = Some("x".repeat(_jwt_size(from_prior.len(), &issuer_kid)));
+
+                (msg, Some(issuer_kid))
+            }
+            None => (self.clone(), None),
+        };
+
+        let size = serde_json::to_string(&msg)
+            .kind(ErrorKind::InvalidState, "Unable serialize message")?
+            .len();
+
+        Ok((size, from_prior_issuer_kid))
+    }
+}
+
+async fn _forward_size<'dr>(
+    size: usize,
+    to: &str,
+    routing_keys: &[String],
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    options: &PackEncryptedOptions,
+) -> Result<usize> {
+    let mut nexts: Vec<_> = routing_keys.iter().skip(1).map(String::as_str).collect();
+    nexts.push(to);
+
+    let mut size = size;
+
+    for (i, (to_, next_)) in routing_keys.iter().zip(nexts).enumerate().rev() {
+        // Empty JSON object is a placeholder of the forwarded message
+        let forward_msg = build_forward_message("{}", next_, options.forward_headers.as_ref())?;
+        size += forward_msg.len() - 2;
+
+        // Only the outermost Forward addressed to the first mediator is authenticated
+        size = match options.forward_from.as_deref().filter(|_| i == 0) {
+            Some(forward_from) => {
+                let (_, from_key, to_keys) = authcrypt_keys(
+                    to_,
+                    None,
+                    forward_from,
+                    did_resolver,
+                    None,
+                    &options.algorithms,
+                    false,
+                )
+                .await?;
+
+                let to_kids: Vec<_> = to_keys.into_iter().map(|key| key.id).collect();
+
+                _jwe_size(
+                    size,
+                    from_key.key_alg() == KnownKeyAlg::X25519,
+                    &to_kids,
+                    Some(from_key.id.as_str()),
+                    None,
+                    _auth_enc(&options.enc_alg_auth),
+                )
+            }
+            None => {
+                let (algorithm, to_keys) =
+                    anoncrypt_keys(to_, None, did_resolver, &options.algorithms, false).await?;
+
+                let to_kids: Vec<_> = to_keys.into_iter().map(|key| key.id).collect();
+
+                _jwe_size(
+                    size,
+                    algorithm.crv() == "X25519",
+                    &to_kids,
+                    None,
+                    None,
+                    _anon_enc(&options.enc_alg_anon),
+                )
+            }
+        };
+    }
+
+    Ok(size)
+}
+
+/// Content encryption parameters affecting JWE size.
+struct Enc {
+    /// JWA name
+    name: &'static str,
+
+    /// Block size for block ciphers
+    block: Option<usize>,
+
+    /// Size of BASE64URL(IV)
+    iv: usize,
+
+    /// Size of BASE64URL(tag)
+    tag: usize,
+
+    /// Size of BASE64URL(wrapped CEK)
+    encrypted_key: usize,
+}
+
+const ENC_A256CBC_HS512: Enc = Enc {
+    name: "A256CBC-HS512",
+    block: Some(16),
+    iv: 22,
+    tag: 43,
+    encrypted_key: 96,
+};
+
+const ENC_A128CBC_HS256: Enc = Enc {
+    name: "A128CBC-HS256",
+    block: Some(16),
+    iv: 22,
+    tag: 22,
+    encrypted_key: 54,
+};
+
+fn _auth_enc(alg: &AuthCryptAlg) -> Enc {
+    match alg {
+        AuthCryptAlg::A256cbcHs512Ecdh1puA256kw => ENC_A256CBC_HS512,
+        AuthCryptAlg::A128cbcHs256Ecdh1puA256kw => ENC_A128CBC_HS256,
+    }
+}
+
+fn _anon_enc(alg: &AnonCryptAlg) -> Enc {
+    match alg {
+        AnonCryptAlg::A256cbcHs512EcdhEsA256kw => ENC_A256CBC_HS512,
+        AnonCryptAlg::Xc20pEcdhEsA256kw => Enc {
+            name: "XC20P",
+            block: None,
+            iv: 32,
+            tag: 22,
+            encrypted_key: 54,
+        },
+        AnonCryptAlg::A256gcmEcdhEsA256kw => Enc {
+            name: "A256GCM",
+            block: None,
+            iv: 16,
+            tag: 22,
+            encrypted_key: 54,
+        },
+        AnonCryptAlg::A128cbcHs256EcdhEsA256kw => ENC_A128CBC_HS256,
+        AnonCryptAlg::A128gcmEcdhEsA256kw => Enc {
+            name: "A128GCM",
+            block: None,
+            iv: 16,
+            tag: 22,
+            encrypted_key: 32,
+        },
+    }
+}
+
+/// Size of BASE64URL (no padding) of `len` bytes.
+fn _b64_size(len: usize) -> usize {
+    (len * 4 + 2) / 3
+}
+
+/// Length of signature algorithm name assumed for size estimation,
+/// as signature algorithm is determined by the signer secret (for ex. "EdDSA").
+const SIGN_ALG_SIZE: usize = 5;
+
+/// Size of JWS in general JSON serialization produced by `pack_signed`.
+fn _jws_size(payload: usize, kid: &str) -> usize {
+    // {"typ":"application/didcomm-signed+json","alg":"..."}
+    let protected = 50 + SIGN_ALG_SIZE;
+
+    // 64 bytes signature of all supported algs
+    let signature = 86;
+
+    81 + _b64_size(payload) + _b64_size(protected) + signature + kid.len()
+}
+
+/// Size of JWS in compact serialization used for `from_prior` header.
+fn _jwt_size(payload: usize, kid: &str) -> usize {
+    // {"typ":"JWT","alg":"...","kid":"..."}
+    let header = 31 + SIGN_ALG_SIZE + kid.len();
+
+    // 64 bytes signature of all supported algs
+    let signature = 86;
+
+    _b64_size(header) + 1 + _b64_size(payload) + 1 + signature
+}
+
+/// Bytes saved by flattened JWE serialization: `"recipients":[{` and `}]` are omitted.
+const FLATTENED_JWE_SAVING: usize = 17;
+
+/// Size of JWE in general JSON serialization produced by `anoncrypt` and `authcrypt`.
+fn _jwe_size(
+    payload: usize,
+    x25519: bool,
+    to_kids: &[String],
+    skid: Option<&str>,
+    aad: Option<&str>,
+    enc: Enc,
+) -> usize {
+    let ciphertext = match enc.block {
+        Some(block) => payload + block - payload % block,
+        None => payload,
+    };
+
+    let epk = if x25519 { 78 } else { 126 };
+
+    let protected = match skid {
+        // {"epk":...,"apv":"...","skid":"...","apu":"...","typ":"...","enc":"...","alg":"ECDH-1PU+A256KW"}
+        Some(skid) => 121 + epk + enc.name.len() + 15 + 19 + skid.len() + _b64_size(skid.len()),
+        // {"epk":...,"apv":"...","typ":"...","enc":"...","alg":"ECDH-ES+A256KW"}
+        None => 121 + epk + enc.name.len() + 14,
+    };
+
+    let recipients: usize = to_kids
+        .iter()
+        .map(|kid| 40 + enc.encrypted_key + kid.len())
+        .sum::<usize>()
+        + to_kids.len()
+        - 1;
+
+    // ,"aad":"..."
+    let aad = aad.map(|aad| 9 + _b64_size(aad.len())).unwrap_or(0);
+
+    65 + _b64_size(ciphertext) + _b64_size(protected) + recipients + enc.iv + enc.tag + aad
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        algorithms::{AnonCryptAlg, AuthCryptAlg},
+        did::resolvers::ExampleDIDResolver,
+        error::ErrorKind,
+        secrets::resolvers::ExampleSecretsResolver,
+        test_vectors::{
+            ALICE_AUTH_METHOD_25519, ALICE_DID, ALICE_DID_DOC,
+            ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256, ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519,
+            BOB_DID, BOB_DID_DOC, BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2, CHARLIE_DID,
+            CHARLIE_DID_DOC, CHARLIE_ROTATED_TO_ALICE_SECRETS, MEDIATOR1_DID_DOC, MESSAGE_SIMPLE,
+        },
+        FromPriorSpec, PackEncryptedOptions,
+    };
+
+    #[tokio::test]
+    async fn pack_encrypted_plan_works() {
+        let alice_kid = ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id.as_str();
+        let alice_sign_kid = ALICE_AUTH_METHOD_25519.id.as_str();
+        let alice_p256_kid = ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id.as_str();
+        let bob_kid = BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id.as_str();
+
+        for (to, from, sign_by) in [
+            (BOB_DID, None, None),
+            (BOB_DID, Some(alice_kid), None),
+            (BOB_DID, None, Some(alice_sign_kid)),
+            (BOB_DID, Some(alice_kid), Some(alice_sign_kid)),
+            (bob_kid, None, None),
+            (bob_kid, Some(alice_kid), Some(alice_sign_kid)),
+        ] {
+            for protect_sender in [false, true] {
+                for forward in [false, true] {
+                    _pack_encrypted_plan_works(
+                        to,
+                        from,
+                        sign_by,
+                        &PackEncryptedOptions {
+                            protect_sender,
+                            forward,
+                            ..PackEncryptedOptions::default()
+                        },
+                    )
+                    .await;
+                }
+            }
+        }
+
+        for enc_alg_anon in [
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+            AnonCryptAlg::A256gcmEcdhEsA256kw,
+            AnonCryptAlg::Xc20pEcdhEsA256kw,
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+            AnonCryptAlg::A128gcmEcdhEsA256kw,
+        ] {
+            _pack_encrypted_plan_works(
+                BOB_DID,
+                None,
+                None,
+                &PackEncryptedOptions {
+                    enc_alg_anon,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await;
+        }
+
+        _pack_encrypted_plan_works(
+            BOB_DID,
+            Some(ALICE_DID),
+            None,
+            &PackEncryptedOptions {
+                enc_alg_auth: AuthCryptAlg::A128cbcHs256Ecdh1puA256kw,
+                ..PackEncryptedOptions::default()
+            },
+        )
+        .await;
+
+        for from in [None, Some(ALICE_DID), Some(alice_p256_kid)] {
+            for options in vec![
+                PackEncryptedOptions {
+                    sign_outside: true,
+                    protect_sender: false,
+                    ..PackEncryptedOptions::default()
+                },
+                PackEncryptedOptions {
+                    aad: Some("context".into()),
+                    ..PackEncryptedOptions::default()
+                },
+                PackEncryptedOptions {
+                    flattened_jwe: true,
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+                PackEncryptedOptions {
+                    recipient_kid_prefix: Some("did:example:bob#key-p256-1".into()),
+                    flattened_jwe: true,
+                    ..PackEncryptedOptions::default()
+                },
+                PackEncryptedOptions {
+                    preserve_recipient_kids_order: true,
+                    ..PackEncryptedOptions::default()
+                },
+                PackEncryptedOptions {
+                    forward_from: Some(ALICE_DID.into()),
+                    ..PackEncryptedOptions::default()
+                },
+                PackEncryptedOptions {
+                    from_prior: Some(FromPriorSpec {
+                        iss: CHARLIE_DID.into(),
+                        issuer_kid: None,
+                    }),
+                    ..PackEncryptedOptions::default()
+                },
+            ] {
+                _pack_encrypted_plan_works(BOB_DID, from, Some(alice_sign_kid), &options).await;
+            }
+        }
+
+        async fn _pack_encrypted_plan_works(
+            to: &str,
+            from: Option<&str>,
+            sign_by: Option<&str>,
+            options: &PackEncryptedOptions,
+        ) {
+            let did_resolver = ExampleDIDResolver::new(vec![
+                ALICE_DID_DOC.clone(),
+                BOB_DID_DOC.clone(),
+                CHARLIE_DID_DOC.clone(),
+                MEDIATOR1_DID_DOC.clone(),
+            ]);
+
+            let secrets_resolver =
+                ExampleSecretsResolver::new(CHARLIE_ROTATED_TO_ALICE_SECRETS.clone());
+
+            let plan = MESSAGE_SIMPLE
+                .pack_encrypted_plan(to, from, sign_by, &did_resolver, options)
+                .await
+                .expect("Unable plan pack");
+
+            let (msg, metadata) = MESSAGE_SIMPLE
+                .pack_encrypted(to, from, sign_by, &did_resolver, &secrets_resolver, options)
+                .await
+                .expect("Unable encrypt");
+
+            assert_eq!(plan.to_kids, metadata.to_kids);
+            assert_eq!(plan.from_kid, metadata.from_kid);
+            assert_eq!(plan.sign_by_kid, metadata.sign_by_kid);
+            assert_eq!(plan.from_prior_issuer_kid, metadata.from_prior_issuer_kid);
+            assert_eq!(plan.forwarded, metadata.forwarded);
+            assert_eq!(plan.messaging_service, metadata.messaging_service);
+
+            assert_eq!(
+                plan.enc_alg_auth,
+                from.map(|_| options.enc_alg_auth.clone())
+            );
+
+            assert_eq!(
+                plan.enc_alg_anon.is_some(),
+                from.is_none() || options.protect_sender
+            );
+
+            // Estimation differs only by possible BASE64 rounding
+            let diff = (plan.estimated_size as i64 - msg.len() as i64).abs();
+            assert!(
+                diff <= 8,
+                "estimated {} actual {}",
+                plan.estimated_size,
+                msg.len()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_plan_works_did_not_found() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+
+        let err = MESSAGE_SIMPLE
+            .pack_encrypted_plan(
+                BOB_DID,
+                None,
+                None,
+                &did_resolver,
+                &PackEncryptedOptions::default(),
+            )
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::DIDNotResolved);
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_plan_works_illegal_to() {
+        let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+
+        let err = MESSAGE_SIMPLE
+            .pack_encrypted_plan(
+                "not-a-did",
+                None,
+                None,
+                &did_resolver,
+                &PackEncryptedOptions::default(),
+            )
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+    }
+}
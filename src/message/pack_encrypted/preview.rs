@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    did::DIDResolver, error::Result, secrets::SecretsResolver, Message, PackEncryptedMetadata,
+    PackEncryptedOptions,
+};
+
+/// Subset of the plaintext message fields that is safe to show or store next to
+/// the encrypted message (for ex. "sent: ..." entries in UI). Body, attachments,
+/// sender and recipients aren't included.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct MessagePreview {
+    /// Message id.
+    pub id: String,
+
+    /// Message type URI.
+    #[serde(rename = "type")]
+    pub type_: String,
+
+    /// Thread identifier if the message belongs to a thread.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub thid: Option<String>,
+
+    /// Parent thread identifier if the thread is a child one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub pthid: Option<String>,
+}
+
+impl Message {
+    /// Returns preview of the message (see `MessagePreview`).
+    pub fn preview(&self) -> MessagePreview {
+        MessagePreview {
+            id: self.id.clone(),
+            type_: self.type_.clone(),
+            thid: self.thid.clone(),
+            pthid: self.pthid.clone(),
+        }
+    }
+
+    /// Packs the message the same way as `pack_encrypted` does and returns preview
+    /// of the source message together, so the sent message doesn't need to be unpacked
+    /// again to be displayed.
+    ///
+    /// # Params
+    /// The same as for `pack_encrypted`.
+    ///
+    /// # Returns
+    /// Tuple `(encrypted_message, metadata, preview)`.
+    /// - `encrypted_message` and `metadata` the same as for `pack_encrypted`.
+    /// - `preview` preview of the source plaintext message.
+    ///
+    /// # Errors
+    /// The same as for `pack_encrypted`.
+    pub async fn pack_encrypted_with_preview<'dr, 'sr>(
+        &self,
+        to: &str,
+        from: Option<&str>,
+        sign_by: Option<&str>,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        options: &PackEncryptedOptions,
+    ) -> Result<(String, PackEncryptedMetadata, MessagePreview)> {
+        let (msg, metadata) = self
+            .pack_encrypted(to, from, sign_by, did_resolver, secrets_resolver, options)
+            .await?;
+
+        Ok((msg, metadata, self.preview()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        did::resolvers::ExampleDIDResolver,
+        secrets::resolvers::ExampleSecretsResolver,
+        test_vectors::{
+            ALICE_DID, ALICE_DID_DOC, ALICE_SECRETS, BOB_DID, BOB_DID_DOC, BOB_SECRETS,
+            MESSAGE_SIMPLE,
+        },
+        Message, PackEncryptedOptions, UnpackOptions,
+    };
+
+    use super::MessagePreview;
+
+    #[tokio::test]
+    async fn pack_encrypted_with_preview_works() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let msg = Message {
+            thid: Some("thread-1".into()),
+            pthid: Some("parent-thread-1".into()),
+            ..MESSAGE_SIMPLE.clone()
+        };
+
+        let (packed_msg, metadata, preview) = msg
+            .pack_encrypted_with_preview(
+                BOB_DID,
+                Some(ALICE_DID),
+                None,
+                &did_resolver,
+                &alice_secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("pack_encrypted_with_preview is ok.");
+
+        assert_eq!(
+            preview,
+            MessagePreview {
+                id: msg.id.clone(),
+                type_: msg.type_.clone(),
+                thid: Some("thread-1".into()),
+                pthid: Some("parent-thread-1".into()),
+            }
+        );
+
+        assert_eq!(
+            serde_json::to_value(&preview).expect("to_value is ok."),
+            json!({
+                "id": msg.id,
+                "type": msg.type_,
+                "thid": "thread-1",
+                "pthid": "parent-thread-1",
+            })
+        );
+
+        assert!(metadata.from_kid.is_some());
+
+        let (unpacked_msg, _) = Message::unpack(
+            &packed_msg,
+            &did_resolver,
+            &bob_secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(unpacked_msg.preview(), preview);
+    }
+
+    #[test]
+    fn preview_works_no_thread() {
+        let preview = MESSAGE_SIMPLE.preview();
+
+        assert_eq!(preview.id, MESSAGE_SIMPLE.id);
+        assert_eq!(preview.type_, MESSAGE_SIMPLE.type_);
+        assert_eq!(preview.thid, None);
+        assert_eq!(preview.pthid, None);
+
+        assert_eq!(
+            serde_json::to_value(&preview).expect("to_value is ok."),
+            json!({ "id": MESSAGE_SIMPLE.id, "type": MESSAGE_SIMPLE.type_ })
+        );
+    }
+}
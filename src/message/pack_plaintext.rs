@@ -1,7 +1,12 @@
+use serde::Deserialize;
+
 use crate::{
+    algorithms::AlgorithmRegistry,
     did::DIDResolver,
     error::{err_msg, ErrorKind, Result, ResultExt},
-    FromPrior, Message,
+    secrets::SecretsResolver,
+    utils::did::is_did,
+    FromPrior, FromPriorSpec, Message,
 };
 
 impl Message {
@@ -19,15 +24,27 @@ impl Message {
     /// - a DIDComm plaintext message s JSON string
     ///
     /// # Errors
+    /// - `Malformed` message has several attachments with the same id.
     /// - InvalidState
     pub async fn pack_plaintext<'dr, 'sr>(
         &self,
         did_resolver: &'dr (dyn DIDResolver + 'dr),
+    ) -> Result<String> {
+        self._pack_plaintext(did_resolver, &AlgorithmRegistry::default())
+            .await
+    }
+
+    /// Same as `pack_plaintext`, but signature algorithms registered in `algorithms`
+    /// are consulted before built-in ones on `from_prior` verification.
+    pub(crate) async fn _pack_plaintext<'dr>(
+        &self,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        algorithms: &AlgorithmRegistry,
     ) -> Result<String> {
         let (from_prior, from_prior_issuer_kid) = match self.from_prior {
             Some(ref from_prior) => {
                 let (from_prior, from_prior_issuer_kid) =
-                    FromPrior::unpack(from_prior, did_resolver).await?;
+                    FromPrior::_unpack(from_prior, did_resolver, algorithms).await?;
                 (Some(from_prior), Some(from_prior_issuer_kid))
             }
             None => (None, None),
@@ -41,11 +58,78 @@ impl Message {
         Ok(msg)
     }
 
+    /// Same as `pack_plaintext`, but allows to customize packing with `options`.
+    /// Unlike `pack_plaintext` checks that message `from` and `to` values are valid DIDs,
+    /// so a subtly invalid message isn't produced.
+    ///
+    /// # Parameters
+    /// - `did_resolver` instance of `DIDResolver` to resolve DIDs.
+    /// - `secrets_resolver` instance of `SecretsResolver` to resolve prior DID keys secrets
+    ///   if `options.from_prior` is set.
+    /// - `options` allow fine configuration of packing process
+    ///
+    /// # Returns
+    /// - a DIDComm plaintext message s JSON string
+    ///
+    /// # Errors
+    /// - `IllegalArgument` message `from` or `to` value is not a valid DID,
+    ///   `options.from_prior` is set but message `from` isn't set or message `from_prior` is set.
+    /// - `DIDNotResolved`, `SecretNotFound` prior DID or its secret for `from_prior` not found.
+    /// - InvalidState
+    pub async fn pack_plaintext_with_options<'dr, 'sr>(
+        &self,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        options: &PackPlaintextOptions,
+    ) -> Result<String> {
+        self._validate_dids()?;
+
+        match options.from_prior {
+            Some(ref from_prior) => {
+                let (msg, _) = self
+                    ._with_from_prior(
+                        from_prior,
+                        did_resolver,
+                        secrets_resolver,
+                        &options.algorithms,
+                    )
+                    .await?;
+
+                msg._pack_plaintext(did_resolver, &options.algorithms).await
+            }
+            None => {
+                self._pack_plaintext(did_resolver, &options.algorithms)
+                    .await
+            }
+        }
+    }
+
+    fn _validate_dids(&self) -> Result<()> {
+        match self.from {
+            Some(ref from) if !is_did(from) => Err(err_msg(
+                ErrorKind::IllegalArgument,
+                "`message.from` value is not a valid DID",
+            ))?,
+            _ => {}
+        }
+
+        if self.to.iter().flatten().any(|to| !is_did(to)) {
+            Err(err_msg(
+                ErrorKind::IllegalArgument,
+                "`message.to` value is not a valid DID",
+            ))?
+        }
+
+        Ok(())
+    }
+
     fn _validate_pack_plaintext(
         &self,
         from_prior: Option<&FromPrior>,
         from_prior_issuer_kid: Option<&str>,
     ) -> Result<()> {
+        self.validate_attachments()?;
+
         if let Some(from_prior) = from_prior {
             from_prior.validate_pack(from_prior_issuer_kid)?;
 
@@ -63,25 +147,41 @@ impl Message {
     }
 }
 
+/// Allow fine configuration of plaintext packing process.
+#[derive(Debug, PartialEq, Eq, Deserialize, Clone, Default)]
+pub struct PackPlaintextOptions {
+    /// If set, signed `from_prior` header linking the sender prior DID to the message `from` DID
+    /// is added to the message, so the recipient learns about the sender DID rotation.
+    /// Message `from` must be set and message `from_prior` must not.
+    #[serde(default)]
+    pub from_prior: Option<FromPriorSpec>,
+
+    /// Signature algorithms consulted before built-in ones on `from_prior` signing
+    /// and verification. No registered algorithms by default.
+    #[serde(skip)]
+    pub algorithms: AlgorithmRegistry,
+}
+
 #[cfg(test)]
 mod tests {
-    use serde_json::Value;
+    use serde_json::{json, Value};
 
     use crate::{
         did::resolvers::ExampleDIDResolver,
         error::ErrorKind,
         secrets::resolvers::ExampleSecretsResolver,
         test_vectors::{
-            ALICE_DID_DOC, BOB_DID_DOC, BOB_SECRETS, CHARLIE_DID_DOC,
-            CHARLIE_SECRET_AUTH_KEY_ED25519, FROM_PRIOR_FULL, MESSAGE_ATTACHMENT_BASE64,
-            MESSAGE_ATTACHMENT_JSON, MESSAGE_ATTACHMENT_LINKS, MESSAGE_ATTACHMENT_MULTI_1,
-            MESSAGE_ATTACHMENT_MULTI_2, MESSAGE_FROM_PRIOR_FULL,
-            MESSAGE_FROM_PRIOR_MISMATCHED_SUB_AND_FROM, MESSAGE_MINIMAL, MESSAGE_SIMPLE,
-            PLAINTEXT_MSG_ATTACHMENT_BASE64, PLAINTEXT_MSG_ATTACHMENT_JSON,
+            ALICE_DID, ALICE_DID_DOC, BOB_DID, BOB_DID_DOC, BOB_SECRETS, CHARLIE_DID,
+            CHARLIE_DID_DOC, CHARLIE_ROTATED_TO_ALICE_SECRETS, CHARLIE_SECRET_AUTH_KEY_ED25519,
+            FROM_PRIOR_FULL, MESSAGE_ATTACHMENT_BASE64, MESSAGE_ATTACHMENT_JSON,
+            MESSAGE_ATTACHMENT_LINKS, MESSAGE_ATTACHMENT_MULTI_1, MESSAGE_ATTACHMENT_MULTI_2,
+            MESSAGE_FROM_PRIOR_FULL, MESSAGE_FROM_PRIOR_MISMATCHED_SUB_AND_FROM, MESSAGE_MINIMAL,
+            MESSAGE_SIMPLE, PLAINTEXT_MSG_ATTACHMENT_BASE64, PLAINTEXT_MSG_ATTACHMENT_JSON,
             PLAINTEXT_MSG_ATTACHMENT_LINKS, PLAINTEXT_MSG_ATTACHMENT_MULTI_1,
             PLAINTEXT_MSG_ATTACHMENT_MULTI_2, PLAINTEXT_MSG_MINIMAL, PLAINTEXT_MSG_SIMPLE,
         },
-        Message, UnpackOptions,
+        Attachment, FromPrior, FromPriorSpec, Message, PackEncryptedOptions, PackPlaintextOptions,
+        UnpackOptions,
     };
 
     #[tokio::test]
@@ -120,6 +220,148 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn pack_plaintext_works_empty_body() {
+        _pack_plaintext_works_empty_body(&MESSAGE_MINIMAL).await;
+
+        _pack_plaintext_works_empty_body(&Message {
+            body: json!({}),
+            ..MESSAGE_SIMPLE.clone()
+        })
+        .await;
+
+        async fn _pack_plaintext_works_empty_body(msg: &Message) {
+            let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+
+            let packed = msg
+                .pack_plaintext(&did_resolver)
+                .await
+                .expect("Unable pack_plaintext");
+
+            assert_eq!(packed, serde_json::to_string(msg).unwrap());
+
+            let (unpacked, metadata) = Message::unpack(
+                &packed,
+                &did_resolver,
+                &ExampleSecretsResolver::new(vec![]),
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect("Unable unpack");
+
+            assert_eq!(&unpacked, msg);
+            assert!(!metadata.encrypted);
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_plaintext_works_attachments_order() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let msg = _message_with_attachments(&["c", "a", "b"]);
+
+        let packed = msg
+            .pack_plaintext(&did_resolver)
+            .await
+            .expect("Unable pack_plaintext");
+
+        let (unpacked, _) = Message::unpack(
+            &packed,
+            &did_resolver,
+            &ExampleSecretsResolver::new(vec![]),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
+
+        assert_eq!(unpacked, msg);
+        assert_eq!(_attachment_ids(&unpacked), vec!["c", "a", "b"]);
+
+        let (packed, _) = msg
+            .pack_encrypted(
+                BOB_DID,
+                None,
+                None,
+                &did_resolver,
+                &ExampleSecretsResolver::new(vec![]),
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("Unable pack_encrypted");
+
+        let (unpacked, _) = Message::unpack(
+            &packed,
+            &did_resolver,
+            &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
+
+        assert_eq!(_attachment_ids(&unpacked), vec!["c", "a", "b"]);
+
+        fn _attachment_ids(msg: &Message) -> Vec<&str> {
+            msg.attachments
+                .iter()
+                .flatten()
+                .filter_map(|a| a.id.as_deref())
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_plaintext_works_duplicate_attachment_ids() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+
+        let err = _message_with_attachments(&["a", "b", "a"])
+            .pack_plaintext(&did_resolver)
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+        assert_eq!(format!("{}", err), "Malformed: Duplicate attachment id `a`");
+    }
+
+    #[tokio::test]
+    async fn unpack_works_duplicate_attachment_ids() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+
+        let msg = serde_json::to_string(&_message_with_attachments(&["a", "a"]))
+            .expect("Unable serialize");
+
+        let err = Message::unpack(
+            &msg,
+            &did_resolver,
+            &ExampleSecretsResolver::new(vec![]),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+    }
+
+    fn _message_with_attachments(ids: &[&str]) -> Message {
+        let attachments = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| {
+                Attachment::json(json!({ "index": i }))
+                    .id((*id).to_owned())
+                    .finalize()
+            })
+            .collect();
+
+        Message {
+            attachments: Some(attachments),
+            ..MESSAGE_SIMPLE.clone()
+        }
+    }
+
     #[tokio::test]
     async fn pack_plaintext_works_from_prior() {
         let did_resolver = ExampleDIDResolver::new(vec![
@@ -171,4 +413,136 @@ mod tests {
             "Malformed: from_prior `sub` value is not equal to message `from` value"
         );
     }
+
+    #[tokio::test]
+    async fn pack_plaintext_with_options_works_from_prior() {
+        let did_resolver = ExampleDIDResolver::new(vec![
+            ALICE_DID_DOC.clone(),
+            BOB_DID_DOC.clone(),
+            CHARLIE_DID_DOC.clone(),
+        ]);
+        let charlie_rotated_to_alice_secrets_resolver =
+            ExampleSecretsResolver::new(CHARLIE_ROTATED_TO_ALICE_SECRETS.clone());
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let packed_msg = MESSAGE_SIMPLE
+            .pack_plaintext_with_options(
+                &did_resolver,
+                &charlie_rotated_to_alice_secrets_resolver,
+                &PackPlaintextOptions {
+                    from_prior: Some(FromPriorSpec {
+                        iss: CHARLIE_DID.into(),
+                        issuer_kid: None,
+                    }),
+                    ..PackPlaintextOptions::default()
+                },
+            )
+            .await
+            .expect("Unable pack_plaintext_with_options");
+
+        let (unpacked_msg, unpack_metadata) = Message::unpack(
+            &packed_msg,
+            &did_resolver,
+            &bob_secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
+
+        assert!(unpacked_msg.from_prior.is_some());
+
+        assert_eq!(
+            Message {
+                from_prior: None,
+                ..unpacked_msg
+            },
+            *MESSAGE_SIMPLE
+        );
+
+        assert_eq!(
+            unpack_metadata.from_prior_issuer_kid.as_ref(),
+            Some(&CHARLIE_SECRET_AUTH_KEY_ED25519.id)
+        );
+
+        assert_eq!(
+            unpack_metadata.from_prior,
+            Some(FromPrior::build(CHARLIE_DID.into(), ALICE_DID.into()).finalize())
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_plaintext_with_options_works_no_options() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+
+        let msg = MESSAGE_SIMPLE
+            .pack_plaintext_with_options(
+                &did_resolver,
+                &secrets_resolver,
+                &PackPlaintextOptions::default(),
+            )
+            .await
+            .expect("Unable pack_plaintext_with_options");
+
+        let expected = MESSAGE_SIMPLE
+            .pack_plaintext(&did_resolver)
+            .await
+            .expect("Unable pack_plaintext");
+
+        assert_eq!(msg, expected);
+    }
+
+    #[tokio::test]
+    async fn pack_plaintext_with_options_works_invalid_from() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+
+        let msg = Message {
+            from: Some("alice".into()),
+            ..MESSAGE_SIMPLE.clone()
+        };
+
+        let err = msg
+            .pack_plaintext_with_options(
+                &did_resolver,
+                &secrets_resolver,
+                &PackPlaintextOptions::default(),
+            )
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: `message.from` value is not a valid DID"
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_plaintext_with_options_works_invalid_to() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+
+        let msg = Message {
+            to: Some(vec!["did:example:bob".into(), "did:bob".into()]),
+            ..MESSAGE_SIMPLE.clone()
+        };
+
+        let err = msg
+            .pack_plaintext_with_options(
+                &did_resolver,
+                &secrets_resolver,
+                &PackPlaintextOptions::default(),
+            )
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: `message.to` value is not a valid DID"
+        );
+    }
 }
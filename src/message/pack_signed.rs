@@ -1,13 +1,19 @@
-use serde::Serialize;
+use std::{collections::HashMap, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use crate::{
-    did::DIDResolver,
-    error::{err_msg, ErrorKind, Result, ResultContext},
-    jws::{self, Algorithm},
-    secrets::SecretsResolver,
+    algorithms::{AlgorithmRegistry, SignAlg, SignatureAlgorithm},
+    did::{did_resolver::RelativeDIDUrlsResolver, DIDDoc, DIDResolver},
+    error::{err_msg, ErrorKind, Result, ResultContext, ResultExt},
+    jws::{self, Algorithm, JWS},
+    message::AttachmentData,
+    secrets::{RemoteCrypto, Secret, SecretsResolver},
     utils::{
-        crypto::{AsKnownKeyPair, KnownKeyPair},
+        crypto::{AsKnownKeyPair, KnownKeyAlg},
         did::{did_or_url, is_did},
+        json::to_canonical_string,
     },
     Message,
 };
@@ -47,6 +53,132 @@ impl Message {
         sign_by: &str,
         did_resolver: &'dr (dyn DIDResolver + 'dr),
         secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+    ) -> Result<(String, PackSignedMetadata)> {
+        self.pack_signed_with_options(
+            sign_by,
+            did_resolver,
+            secrets_resolver,
+            &SignOptions::default(),
+        )
+        .await
+    }
+
+    /// Same as `pack_signed`, but allows to customize signing with `options`.
+    ///
+    /// # Parameters
+    /// - `sign_by` a DID or key ID the sender uses for signing
+    /// - `did_resolver` instance of `DIDResolver` to resolve DIDs.
+    /// - `secrets_resolver` instance of SecretsResolver` to resolve sender DID keys secrets
+    /// - `options` allow fine configuration of signing process
+    ///
+    /// # Returns
+    /// Tuple (signed_message, metadata). See `pack_signed` for details.
+    pub async fn pack_signed_with_options<'dr, 'sr>(
+        &self,
+        sign_by: &str,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        options: &SignOptions,
+    ) -> Result<(String, PackSignedMetadata)> {
+        let (msg, metadata, _) = self
+            ._pack_signed(sign_by, did_resolver, secrets_resolver, options)
+            .await?;
+
+        Ok((msg, metadata))
+    }
+
+    /// Same as `pack_signed_with_options`, but also returns algorithm used for signing
+    /// (`None` for algorithms registered by application and unknown to the library).
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "pack_signed",
+            level = "debug",
+            skip_all,
+            fields(sign_by = %sign_by),
+            err(level = "debug")
+        )
+    )]
+    pub(crate) async fn _pack_signed<'dr, 'sr>(
+        &self,
+        sign_by: &str,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        options: &SignOptions,
+    ) -> Result<(String, PackSignedMetadata, Option<SignAlg>)> {
+        self._validate_pack_signed(sign_by)?;
+        options.validate()?;
+
+        let did_resolver: &dyn DIDResolver = &RelativeDIDUrlsResolver::new(did_resolver, false);
+
+        let (key_id, sign_key, sign_alg) =
+            _sign_key(sign_by, did_resolver, secrets_resolver, &options.algorithms).await?;
+
+        let signed_attachments_msg;
+
+        let msg = if options.sign_attachments {
+            signed_attachments_msg =
+                self._sign_attachments(&key_id, &sign_key, sign_alg.as_ref())?;
+            &signed_attachments_msg
+        } else {
+            self
+        };
+
+        let payload = msg
+            ._pack_plaintext(did_resolver, &options.algorithms)
+            .await?;
+
+        let payload = if options.canonical {
+            let payload: serde_json::Value = serde_json::from_str(&payload).kind(
+                ErrorKind::InvalidState,
+                "Unable deserialize packed plaintext",
+            )?;
+
+            to_canonical_string(&payload)?
+        } else {
+            payload
+        };
+
+        let msg = _sign_with_headers(
+            payload.as_bytes(),
+            &key_id,
+            &sign_key,
+            sign_alg.as_ref(),
+            options.typ.as_deref().unwrap_or(jws::SIGNED_TYP),
+            &options.protected_headers,
+        )?;
+
+        let sign_alg = sign_alg.sign_alg();
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(sign_by_kid = %key_id, sign_alg = ?sign_alg, "message signed");
+
+        let metadata = PackSignedMetadata {
+            sign_by_kid: key_id,
+        };
+
+        Ok((msg, metadata, sign_alg))
+    }
+
+    /// Same as `pack_signed`, but the signature is produced by `RemoteCrypto` provider
+    /// (for ex. HSM) that never exposes private key. Existing `SecretsResolver`
+    /// implementations can be used with `SecretsResolverCrypto` adapter.
+    ///
+    /// # Parameters
+    /// - `sign_by` a DID or key ID the sender uses for signing
+    /// - `did_resolver` instance of `DIDResolver` to resolve DIDs.
+    /// - `remote_crypto` instance of `RemoteCrypto` holding sender DID keys
+    ///
+    /// # Returns
+    /// Tuple (signed_message, metadata). See `pack_signed` for details.
+    ///
+    /// # Errors
+    /// Same as `pack_signed`.
+    pub async fn pack_signed_remote<'dr, 'rc>(
+        &self,
+        sign_by: &str,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        remote_crypto: &'rc (dyn RemoteCrypto + 'rc),
     ) -> Result<(String, PackSignedMetadata)> {
         self._validate_pack_signed(sign_by)?;
 
@@ -58,55 +190,38 @@ impl Message {
             .context("Unable resolve signer did")?
             .ok_or_else(|| err_msg(ErrorKind::DIDNotResolved, "Signer did not found"))?;
 
-        let authentications: Vec<_> = if let Some(key_id) = key_id {
-            did_doc
-                .authentications
-                .iter()
-                .find(|a| *a == key_id)
-                .ok_or_else(|| {
-                    err_msg(
-                        ErrorKind::DIDUrlNotFound,
-                        "Signer key id not found in did doc",
-                    )
-                })?;
-
-            vec![key_id]
-        } else {
-            did_doc.authentications.iter().map(|s| s.as_str()).collect()
-        };
+        let authentications = _authentications(&did_doc, key_id)?;
 
-        let key_id = *secrets_resolver
-            .find_secrets(&authentications)
+        let key_id = *remote_crypto
+            .find_keys(&authentications)
             .await
-            .context("Unable find secrets")?
+            .context("Unable find keys")?
             .get(0)
-            .ok_or_else(|| err_msg(ErrorKind::SecretNotFound, "No signer secrets found"))?;
-
-        let secret = secrets_resolver
-            .get_secret(key_id)
-            .await
-            .context("Unable get secret")?
-            .ok_or_else(|| err_msg(ErrorKind::SecretNotFound, "Signer secret not found"))?;
-
-        let sign_key = secret
-            .as_key_pair()
-            .context("Unable instantiate sign key")?;
+            .ok_or_else(|| err_msg(ErrorKind::SecretNotFound, "No signer keys found"))?;
+
+        let alg = match did_doc
+            .verification_methods
+            .iter()
+            .find(|vm| vm.id == key_id)
+            .ok_or_else(|| {
+                err_msg(
+                    ErrorKind::DIDUrlNotFound,
+                    "Signer verification method not found in did doc",
+                )
+            })?
+            .key_alg()
+        {
+            KnownKeyAlg::Ed25519 => Algorithm::EdDSA,
+            KnownKeyAlg::P256 => Algorithm::Es256,
+            KnownKeyAlg::K256 => Algorithm::Es256K,
+            _ => Err(err_msg(ErrorKind::Unsupported, "Unsupported signature alg"))?,
+        };
 
         let payload = self.pack_plaintext(did_resolver).await?;
 
-        let msg = match sign_key {
-            KnownKeyPair::Ed25519(ref key) => {
-                jws::sign(payload.as_bytes(), (key_id, key), Algorithm::EdDSA)
-            }
-            KnownKeyPair::P256(ref key) => {
-                jws::sign(payload.as_bytes(), (key_id, key), Algorithm::Es256)
-            }
-            KnownKeyPair::K256(ref key) => {
-                jws::sign(payload.as_bytes(), (key_id, key), Algorithm::Es256K)
-            }
-            _ => Err(err_msg(ErrorKind::Unsupported, "Unsupported signature alg"))?,
-        }
-        .context("Unable produce signatire")?;
+        let msg = jws::sign_remote(payload.as_bytes(), key_id, alg, remote_crypto)
+            .await
+            .context("Unable produce signature")?;
 
         let metadata = PackSignedMetadata {
             sign_by_kid: key_id.to_owned(),
@@ -115,6 +230,120 @@ impl Message {
         Ok((msg, metadata))
     }
 
+    /// Appends signature by another key to the existing signed message (for ex. counter-signing
+    /// or notarization). The payload and existing signatures are kept unchanged and aren't verified.
+    /// The new signature uses `typ` of the first existing signature.
+    ///
+    /// # Parameters
+    /// - `jws` a DIDComm signed message as JSON string (JWS in general JSON serialization)
+    /// - `sign_by` a DID or key ID the additional signer uses for signing
+    /// - `did_resolver` instance of `DIDResolver` to resolve DIDs.
+    /// - `secrets_resolver` instance of SecretsResolver` to resolve additional signer DID keys secrets
+    ///
+    /// # Returns
+    /// Signed message with the additional signature as JSON string.
+    ///
+    /// # Errors
+    /// - `IllegalArgument` `jws` is in compact serialization that can't hold multiple signatures,
+    ///   `sign_by` isn't a valid DID or DID URL or the message is already signed by the key.
+    /// - `Malformed` `jws` isn't a valid signed message.
+    /// - Other errors same as `pack_signed`.
+    pub async fn add_signature<'dr, 'sr>(
+        jws: &str,
+        sign_by: &str,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+    ) -> Result<String> {
+        if !is_did(sign_by) {
+            Err(err_msg(
+                ErrorKind::IllegalArgument,
+                "`sign_by` value is not a valid DID or DID URL",
+            ))?;
+        }
+
+        let mut jws_ = match JWS::from_str(jws) {
+            Ok(jws_) => jws_,
+            Err(_) if _is_compact_jws(jws) => Err(err_msg(
+                ErrorKind::IllegalArgument,
+                "Compact JWS can't hold multiple signatures",
+            ))?,
+            Err(err) => Err(err).context("Message is not a signed message")?,
+        };
+
+        let typ = {
+            let mut buf = vec![];
+            let parsed_jws = JWS::from_str(jws)?.parse(&mut buf)?;
+
+            parsed_jws
+                .protected
+                .first()
+                .map(|protected| protected.typ.to_owned())
+                .ok_or_else(|| err_msg(ErrorKind::Malformed, "No signatures found in jws"))?
+        };
+
+        let did_resolver: &dyn DIDResolver = &RelativeDIDUrlsResolver::new(did_resolver, false);
+        let (key_id, sign_key, sign_alg) = _sign_key(
+            sign_by,
+            did_resolver,
+            secrets_resolver,
+            &AlgorithmRegistry::default(),
+        )
+        .await?;
+
+        if jws_.signatures.iter().any(|s| s.header.kid == key_id) {
+            Err(err_msg(
+                ErrorKind::IllegalArgument,
+                format!("Message is already signed by `{}`", key_id),
+            ))?;
+        }
+
+        let payload = base64::decode_config(jws_.payload, base64::URL_SAFE_NO_PAD)
+            .kind(ErrorKind::Malformed, "Signed payload is invalid base64")?;
+
+        let signed = _sign_with_headers(
+            &payload,
+            &key_id,
+            &sign_key,
+            sign_alg.as_ref(),
+            &typ,
+            &HashMap::new(),
+        )?;
+        let signed = JWS::from_str(&signed)?;
+
+        // Signature covers payload as encoded, so it must be encoded the same way
+        if signed.payload != jws_.payload {
+            Err(err_msg(
+                ErrorKind::Malformed,
+                "Signed payload is not canonical base64url",
+            ))?;
+        }
+
+        jws_.signatures.extend(signed.signatures);
+
+        let jws =
+            serde_json::to_string(&jws_).kind(ErrorKind::InvalidState, "Unable serialize jws")?;
+
+        Ok(jws)
+    }
+
+    fn _sign_attachments(
+        &self,
+        key_id: &str,
+        sign_key: &Secret,
+        sign_alg: &dyn SignatureAlgorithm,
+    ) -> Result<Message> {
+        let mut msg = self.clone();
+
+        for attachment in msg.attachments.iter_mut().flatten() {
+            if let AttachmentData::Base64 { ref mut value } = attachment.data {
+                let content = value.decoded_bytes()?;
+                value.jws = Some(_sign(&content, key_id, sign_key, sign_alg)?);
+            }
+        }
+
+        Ok(msg)
+    }
+
     fn _validate_pack_signed(&self, sign_by: &str) -> Result<()> {
         if !is_did(sign_by) {
             Err(err_msg(
@@ -127,6 +356,197 @@ impl Message {
     }
 }
 
+/// Signs arbitrary payload (for ex. encrypted message, see `PackEncryptedOptions::sign_outside`)
+/// with `authentication` key of the signer. Returns tuple (jws, sign_by_kid, sign_alg).
+pub(crate) async fn sign_payload<'dr, 'sr>(
+    payload: &[u8],
+    sign_by: &str,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+    algorithms: &AlgorithmRegistry,
+) -> Result<(String, String, Option<SignAlg>)> {
+    let (key_id, sign_key, sign_alg) =
+        _sign_key(sign_by, did_resolver, secrets_resolver, algorithms).await?;
+    let jws = _sign(payload, &key_id, &sign_key, sign_alg.as_ref())?;
+    Ok((jws, key_id, sign_alg.sign_alg()))
+}
+
+/// Finds the first signer `authentication` key the secret is known for
+/// and signature algorithm to use with it.
+async fn _sign_key<'dr, 'sr>(
+    sign_by: &str,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+    algorithms: &AlgorithmRegistry,
+) -> Result<(String, Secret, Arc<dyn SignatureAlgorithm>)> {
+    let key_id = sign_kid(sign_by, did_resolver, Some(secrets_resolver)).await?;
+
+    let secret = secrets_resolver
+        .get_secret(&key_id)
+        .await
+        .context("Unable get secret")?
+        .ok_or_else(|| err_msg(ErrorKind::SecretNotFound, "Signer secret not found"))?;
+
+    let sign_alg = algorithms.signature_algorithm_for_secret(&secret)?;
+
+    Ok((key_id, secret, sign_alg))
+}
+
+/// Finds the first signer `authentication` key the secret is known for.
+/// If `secrets_resolver` isn't provided (see `Message::pack_encrypted_plan`)
+/// the first signer `authentication` key is returned.
+pub(crate) async fn sign_kid<'dr, 'sr>(
+    sign_by: &str,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    secrets_resolver: Option<&'sr (dyn SecretsResolver + 'sr)>,
+) -> Result<String> {
+    let (did, key_id) = did_or_url(sign_by);
+
+    let did_doc = did_resolver
+        .resolve(did)
+        .await
+        .context("Unable resolve signer did")?
+        .ok_or_else(|| err_msg(ErrorKind::DIDNotResolved, "Signer did not found"))?;
+
+    let authentications = _authentications(&did_doc, key_id)?;
+
+    let authentications = match secrets_resolver {
+        Some(secrets_resolver) => secrets_resolver
+            .find_secrets(&authentications)
+            .await
+            .context("Unable find secrets")?,
+        None => authentications,
+    };
+
+    let key_id = *authentications
+        .get(0)
+        .ok_or_else(|| err_msg(ErrorKind::SecretNotFound, "No signer secrets found"))?;
+
+    Ok(key_id.to_owned())
+}
+
+/// Checks that value looks like compactly serialized JWS: three base64url parts separated by dots.
+fn _is_compact_jws(jws: &str) -> bool {
+    jws.split('.').count() == 3
+        && jws
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+fn _authentications<'a>(did_doc: &'a DIDDoc, key_id: Option<&'a str>) -> Result<Vec<&'a str>> {
+    let authentications = if let Some(key_id) = key_id {
+        did_doc
+            .authentications
+            .iter()
+            .find(|a| *a == key_id)
+            .ok_or_else(|| {
+                err_msg(
+                    ErrorKind::DIDUrlNotFound,
+                    "Signer key id not found in did doc",
+                )
+            })?;
+
+        vec![key_id]
+    } else {
+        did_doc.authentications.iter().map(|s| s.as_str()).collect()
+    };
+
+    Ok(authentications)
+}
+
+fn _sign(
+    payload: &[u8],
+    key_id: &str,
+    sign_key: &Secret,
+    sign_alg: &dyn SignatureAlgorithm,
+) -> Result<String> {
+    _sign_with_headers(
+        payload,
+        key_id,
+        sign_key,
+        sign_alg,
+        jws::SIGNED_TYP,
+        &HashMap::new(),
+    )
+}
+
+fn _sign_with_headers(
+    payload: &[u8],
+    key_id: &str,
+    sign_key: &Secret,
+    sign_alg: &dyn SignatureAlgorithm,
+    typ: &str,
+    extra_headers: &HashMap<String, Value>,
+) -> Result<String> {
+    let jws = jws::sign_with_algorithm(payload, (key_id, sign_key), sign_alg, typ, extra_headers)
+        .context("Unable produce signatire")?;
+
+    Ok(jws)
+}
+
+/// Allow fine configuration of signing process.
+#[derive(Debug, PartialEq, Eq, Deserialize, Clone, Default)]
+pub struct SignOptions {
+    /// If `true`, the signed payload is canonicalized according to JCS (RFC 8785),
+    /// so signatures match other implementations doing the same.
+    /// False by default to keep payloads produced by previous versions unchanged.
+    #[serde(default)]
+    pub canonical: bool,
+
+    /// If `true`, content of each base64 attachment is signed with the same key as the message
+    /// and the signature is put to `jws` field of attachment data. False by default.
+    #[serde(default)]
+    pub sign_attachments: bool,
+
+    /// Overrides `typ` parameter of JWS protected header.
+    /// `application/didcomm-signed+json` is used by default.
+    #[serde(default)]
+    pub typ: Option<String>,
+
+    /// Additional parameters of JWS protected header. Must not contain `typ`, `alg`
+    /// or `kid` parameters as they are set by the library, `crit` parameter as no JWS
+    /// extensions are supported and `b64` parameter other than `true` as unencoded payload
+    /// is unsupported. Empty by default.
+    #[serde(default)]
+    pub protected_headers: HashMap<String, Value>,
+
+    /// Signature algorithms consulted before built-in ones on signing.
+    /// No registered algorithms by default.
+    #[serde(skip)]
+    pub algorithms: AlgorithmRegistry,
+}
+
+impl SignOptions {
+    fn validate(&self) -> Result<()> {
+        if let Some(header) = ["typ", "alg", "kid"]
+            .iter()
+            .find(|&&h| self.protected_headers.contains_key(h))
+        {
+            Err(err_msg(
+                ErrorKind::IllegalArgument,
+                format!("Protected header `{}` can't be set explicitly", header),
+            ))?
+        }
+
+        if self.protected_headers.contains_key("crit") {
+            Err(err_msg(
+                ErrorKind::IllegalArgument,
+                "Protected header `crit` is unsupported",
+            ))?
+        }
+
+        match self.protected_headers.get("b64") {
+            Some(b64) if b64 != &Value::Bool(true) => Err(err_msg(
+                ErrorKind::IllegalArgument,
+                "Protected header `b64` must be true as unencoded payload is unsupported",
+            ))?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
 /// Additional metadata about this `pack` method execution like used key identifiers.
 #[derive(Debug, PartialEq, Eq, Clone, Serialize)]
 pub struct PackSignedMetadata {
@@ -138,30 +558,35 @@ pub struct PackSignedMetadata {
 mod tests {
     use askar_crypto::{
         alg::{ed25519::Ed25519KeyPair, k256::K256KeyPair, p256::P256KeyPair},
-        sign::KeySigVerify,
+        sign::{KeySigVerify, KeySign, SignatureType},
     };
 
-    use serde_json::Value;
+    use async_trait::async_trait;
+    use serde_json::{json, Value};
 
     use crate::{
         did::{
             resolvers::{ExampleDIDResolver, MockDidResolver},
             DIDResolver, VerificationMaterial,
         },
-        error::{err_msg, ErrorKind},
+        error::{err_msg, ErrorKind, Result},
         jwk::FromJwkValue,
         jws::{self, Algorithm, Header, ProtectedHeader},
+        message::{Attachment, AttachmentData},
         secrets::{
-            resolvers::ExampleSecretsResolver, Secret, SecretMaterial, SecretType, SecretsResolver,
+            resolvers::ExampleSecretsResolver, RemoteCrypto, Secret, SecretMaterial, SecretType,
+            SecretsResolver, SecretsResolverCrypto,
         },
         test_vectors::{
             ALICE_AUTH_METHOD_25519, ALICE_AUTH_METHOD_P256, ALICE_AUTH_METHOD_SECPP256K1,
-            ALICE_DID, ALICE_DID_DOC, ALICE_DID_DOC_WITH_NO_SECRETS, ALICE_SECRETS, BOB_DID_DOC,
-            BOB_SECRETS, CHARLIE_DID_DOC, CHARLIE_ROTATED_TO_ALICE_SECRETS,
-            CHARLIE_SECRET_AUTH_KEY_ED25519, FROM_PRIOR_FULL, MESSAGE_FROM_PRIOR_FULL,
-            MESSAGE_SIMPLE, PLAINTEXT_MSG_SIMPLE,
+            ALICE_DID, ALICE_DID_DOC, ALICE_DID_DOC_WITH_NO_SECRETS, ALICE_SECRETS,
+            ALICE_SECRET_AUTH_KEY_ED25519, BOB_DID_DOC, BOB_SECRETS, CHARLIE_DID_DOC,
+            CHARLIE_ROTATED_TO_ALICE_SECRETS, CHARLIE_SECRET_AUTH_KEY_ED25519, FROM_PRIOR_FULL,
+            FROM_PRIOR_JWT_FULL, MESSAGE_FROM_PRIOR_FULL, MESSAGE_MINIMAL, MESSAGE_SIMPLE,
+            PLAINTEXT_MSG_SIMPLE, SIGNED_MSG_ALICE_KEY_1,
         },
-        Message, PackSignedMetadata, UnpackOptions,
+        utils::crypto::AsKnownKeyPair,
+        Message, PackSignedMetadata, SignOptions, UnpackOptions,
     };
 
     #[tokio::test]
@@ -284,6 +709,68 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "arbitrary-precision")]
+    #[tokio::test]
+    async fn pack_signed_works_arbitrary_precision_numbers() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let body =
+            r#"{"amount":123456789012345678901234567890,"rate":0.10000000000000000000000000001}"#;
+
+        let message = Message {
+            body: serde_json::from_str(body).expect("Unable from_str"),
+            ..MESSAGE_SIMPLE.clone()
+        };
+
+        let (msg, _) = message
+            .pack_signed(ALICE_DID, &did_resolver, &secrets_resolver)
+            .await
+            .expect("Unable pack_signed");
+
+        let (unpacked, metadata) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &ExampleSecretsResolver::new(vec![]),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
+
+        assert!(metadata.non_repudiation);
+        assert_eq!(unpacked, message);
+        assert_eq!(unpacked.created_time, MESSAGE_SIMPLE.created_time);
+
+        assert_eq!(
+            serde_json::to_string(&unpacked.body).expect("Unable to_string"),
+            body
+        );
+    }
+
+    #[tokio::test]
+    async fn pack_signed_works_empty_body() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let (msg, metadata) = MESSAGE_MINIMAL
+            .pack_signed(ALICE_DID, &did_resolver, &secrets_resolver)
+            .await
+            .expect("Unable pack_signed");
+
+        let (unpacked, unpack_metadata) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &ExampleSecretsResolver::new(vec![]),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
+
+        assert_eq!(&unpacked, &*MESSAGE_MINIMAL);
+        assert!(unpack_metadata.non_repudiation);
+        assert_eq!(unpack_metadata.sign_from, Some(metadata.sign_by_kid));
+    }
+
     #[tokio::test]
     async fn pack_signed_works_signer_did_not_found() {
         let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
@@ -459,4 +946,500 @@ mod tests {
         );
         assert_eq!(unpack_metadata.from_prior.as_ref(), Some(&*FROM_PRIOR_FULL));
     }
+
+    #[tokio::test]
+    async fn pack_signed_works_canonical() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let (msg, _) = MESSAGE_SIMPLE
+            .pack_signed_with_options(
+                &ALICE_AUTH_METHOD_25519.id,
+                &did_resolver,
+                &secrets_resolver,
+                &SignOptions {
+                    canonical: true,
+                    ..SignOptions::default()
+                },
+            )
+            .await
+            .expect("Unable pack_signed");
+
+        // Payload and signature produced by an independent implementation
+        // signing JCS canonicalized JSON of the same message with the same key
+        let msg: Value = serde_json::from_str(&msg).expect("Unable parse jws");
+
+        assert_eq!(
+            msg["payload"],
+            "eyJib2R5Ijp7Im1lc3NhZ2VzcGVjaWZpY2F0dHJpYnV0ZSI6ImFuZCBpdHMgdmFsdWUifSwiY3JlYXRlZF90aW1lIjoxNTE2MjY5MDIyLCJleHBpcmVzX3RpbWUiOjE1MTYzODU5MzEsImZyb20iOiJkaWQ6ZXhhbXBsZTphbGljZSIsImlkIjoiMTIzNDU2Nzg5MCIsInRvIjpbImRpZDpleGFtcGxlOmJvYiJdLCJ0eXAiOiJhcHBsaWNhdGlvbi9kaWRjb21tLXBsYWluK2pzb24iLCJ0eXBlIjoiaHR0cDovL2V4YW1wbGUuY29tL3Byb3RvY29scy9sZXRzX2RvX2x1bmNoLzEuMC9wcm9wb3NhbCJ9"
+        );
+
+        assert_eq!(
+            msg["signatures"][0]["signature"],
+            "E6rhAjZKDfREbhcPp_1De3ECVHSeZaQYHBJ_ZGRDjQbwKyN53XVqV3luaJS-OwZkLQxBVj0swgFZkGUoiUVfCg"
+        );
+
+        let (unpacked_msg, _) = Message::unpack(
+            &msg.to_string(),
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
+
+        assert_eq!(&unpacked_msg, &*MESSAGE_SIMPLE);
+    }
+
+    #[tokio::test]
+    async fn pack_signed_works_sign_attachments() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let msg = Message::build(
+            "1234567890".to_owned(),
+            "http://example.com/protocols/lets_do_lunch/1.0/proposal".to_owned(),
+            json!({}),
+        )
+        .from(ALICE_DID.to_owned())
+        .attachments(vec![
+            Attachment::base64("ZXhhbXBsZQ==".to_owned()).finalize(),
+            Attachment::json(json!({"foo": "bar"})).finalize(),
+        ])
+        .finalize();
+
+        let (packed_msg, _) = msg
+            .pack_signed_with_options(
+                &ALICE_AUTH_METHOD_25519.id,
+                &did_resolver,
+                &secrets_resolver,
+                &SignOptions {
+                    sign_attachments: true,
+                    ..SignOptions::default()
+                },
+            )
+            .await
+            .expect("Unable pack_signed");
+
+        let (unpacked_msg, unpack_metadata) = Message::unpack(
+            &packed_msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
+
+        assert!(unpack_metadata.non_repudiation);
+
+        let attachments = unpacked_msg.attachments.expect("No attachments");
+
+        let jws = match attachments[0].data {
+            AttachmentData::Base64 { ref value } => value.jws.as_ref().expect("No jws"),
+            _ => panic!("Unexpected attachment data"),
+        };
+
+        match attachments[1].data {
+            AttachmentData::Json { ref value } => assert!(value.jws.is_none()),
+            _ => panic!("Unexpected attachment data"),
+        };
+
+        let mut buf = vec![];
+        let jws = jws::parse(jws, &mut buf).expect("Unable parse");
+
+        assert_eq!(
+            jws.jws.payload,
+            base64::encode_config("example", base64::URL_SAFE_NO_PAD)
+        );
+
+        let signer_key = match ALICE_AUTH_METHOD_25519.verification_material {
+            VerificationMaterial::JWK { ref value } => {
+                Ed25519KeyPair::from_jwk_value(value).expect("Unable from_jwk_value")
+            }
+            _ => panic!("Unexpected verification_material"),
+        };
+
+        let valid = jws
+            .verify((ALICE_AUTH_METHOD_25519.id.as_str(), &signer_key))
+            .expect("Unable verify");
+
+        assert!(valid);
+    }
+
+    #[tokio::test]
+    async fn pack_signed_works_protected_headers() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let (msg, _) = MESSAGE_SIMPLE
+            .pack_signed_with_options(
+                &ALICE_AUTH_METHOD_25519.id,
+                &did_resolver,
+                &secrets_resolver,
+                &SignOptions {
+                    typ: Some("didcomm-signed+json".into()),
+                    protected_headers: vec![("cty".to_owned(), json!("didcomm-plain+json"))]
+                        .into_iter()
+                        .collect(),
+                    ..SignOptions::default()
+                },
+            )
+            .await
+            .expect("Unable pack_signed");
+
+        let msg_json: Value = serde_json::from_str(&msg).expect("Unable parse jws");
+
+        let protected = msg_json["signatures"][0]["protected"]
+            .as_str()
+            .expect("Unable get protected");
+
+        let protected = base64::decode_config(protected, base64::URL_SAFE_NO_PAD)
+            .expect("Unable decode protected");
+
+        let protected: Value = serde_json::from_slice(&protected).expect("Unable parse protected");
+
+        assert_eq!(
+            protected,
+            json!({
+                "typ": "didcomm-signed+json",
+                "alg": "EdDSA",
+                "cty": "didcomm-plain+json",
+            })
+        );
+
+        let (unpacked_msg, unpack_metadata) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
+
+        assert_eq!(&unpacked_msg, &*MESSAGE_SIMPLE);
+        assert!(unpack_metadata.non_repudiation);
+    }
+
+    #[tokio::test]
+    async fn pack_signed_works_protected_headers_conflict() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        for header in ["typ", "alg", "kid"] {
+            let err = MESSAGE_SIMPLE
+                .pack_signed_with_options(
+                    &ALICE_AUTH_METHOD_25519.id,
+                    &did_resolver,
+                    &secrets_resolver,
+                    &SignOptions {
+                        protected_headers: vec![(header.to_owned(), json!("value"))]
+                            .into_iter()
+                            .collect(),
+                        ..SignOptions::default()
+                    },
+                )
+                .await
+                .expect_err("res is ok");
+
+            assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+            assert_eq!(
+                format!("{}", err),
+                format!(
+                    "Illegal argument: Protected header `{}` can't be set explicitly",
+                    header
+                )
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_signed_works_protected_headers_processing() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        for (protected_headers, exp_err_msg) in [
+            (
+                vec![("crit", json!(["exp"])), ("exp", json!(1))],
+                "Illegal argument: Protected header `crit` is unsupported",
+            ),
+            (
+                vec![("b64", json!(false))],
+                "Illegal argument: Protected header `b64` must be true as unencoded payload is unsupported",
+            ),
+        ] {
+            let err = MESSAGE_SIMPLE
+                .pack_signed_with_options(
+                    &ALICE_AUTH_METHOD_25519.id,
+                    &did_resolver,
+                    &secrets_resolver,
+                    &SignOptions {
+                        protected_headers: protected_headers
+                            .into_iter()
+                            .map(|(header, value)| (header.to_owned(), value))
+                            .collect(),
+                        ..SignOptions::default()
+                    },
+                )
+                .await
+                .expect_err("res is ok");
+
+            assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+            assert_eq!(format!("{}", err), exp_err_msg);
+        }
+
+        // Encoded payload can be stated explicitly
+        let (msg, _) = MESSAGE_SIMPLE
+            .pack_signed_with_options(
+                &ALICE_AUTH_METHOD_25519.id,
+                &did_resolver,
+                &secrets_resolver,
+                &SignOptions {
+                    protected_headers: vec![("b64".to_owned(), json!(true))].into_iter().collect(),
+                    ..SignOptions::default()
+                },
+            )
+            .await
+            .expect("Unable pack_signed_with_options");
+
+        let (unpacked_msg, _) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
+
+        assert_eq!(&unpacked_msg, &*MESSAGE_SIMPLE);
+    }
+
+    #[tokio::test]
+    async fn pack_signed_remote_works() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let hsm = MockHsm {
+            kid: ALICE_SECRET_AUTH_KEY_ED25519.id.clone(),
+            key: ALICE_SECRET_AUTH_KEY_ED25519
+                .as_ed25519()
+                .expect("Unable instantiate key"),
+        };
+
+        let (msg, metadata) = MESSAGE_SIMPLE
+            .pack_signed_remote(ALICE_DID, &did_resolver, &hsm)
+            .await
+            .expect("Unable pack_signed_remote");
+
+        assert_eq!(
+            metadata,
+            PackSignedMetadata {
+                sign_by_kid: ALICE_AUTH_METHOD_25519.id.clone(),
+            }
+        );
+
+        // Ed25519 signatures are deterministic
+        let (exp_msg, _) = MESSAGE_SIMPLE
+            .pack_signed(ALICE_DID, &did_resolver, &secrets_resolver)
+            .await
+            .expect("Unable pack_signed");
+
+        assert_eq!(msg, exp_msg);
+
+        let (unpacked_msg, unpack_metadata) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
+
+        assert_eq!(&unpacked_msg, &*MESSAGE_SIMPLE);
+        assert_eq!(
+            unpack_metadata.sign_from.as_ref(),
+            Some(&ALICE_AUTH_METHOD_25519.id)
+        );
+
+        struct MockHsm {
+            kid: String,
+            key: Ed25519KeyPair,
+        }
+
+        #[cfg_attr(feature = "uniffi", async_trait)]
+        #[cfg_attr(not(feature = "uniffi"), async_trait(?Send))]
+        impl RemoteCrypto for MockHsm {
+            async fn find_keys<'a>(&self, kids: &'a [&'a str]) -> Result<Vec<&'a str>> {
+                Ok(kids
+                    .iter()
+                    .filter(|&&k| k == self.kid)
+                    .map(|&k| k)
+                    .collect())
+            }
+
+            async fn sign(&self, kid: &str, data: &[u8]) -> Result<Vec<u8>> {
+                if kid != self.kid {
+                    Err(err_msg(ErrorKind::SecretNotFound, "Key not found"))?
+                }
+
+                let signature = self
+                    .key
+                    .create_signature(data, Some(SignatureType::EdDSA))
+                    .map_err(|_| err_msg(ErrorKind::InvalidState, "Unable sign"))?;
+
+                Ok(signature.as_ref().to_vec())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_signed_remote_works_secrets_resolver_adapter() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let remote_crypto = SecretsResolverCrypto::new(&secrets_resolver);
+
+        for sign_by in [
+            &ALICE_AUTH_METHOD_25519.id,
+            &ALICE_AUTH_METHOD_P256.id,
+            &ALICE_AUTH_METHOD_SECPP256K1.id,
+        ] {
+            let (msg, metadata) = MESSAGE_SIMPLE
+                .pack_signed_remote(sign_by, &did_resolver, &remote_crypto)
+                .await
+                .expect("Unable pack_signed_remote");
+
+            assert_eq!(&metadata.sign_by_kid, sign_by);
+
+            let (unpacked_msg, unpack_metadata) = Message::unpack(
+                &msg,
+                &did_resolver,
+                &secrets_resolver,
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect("Unable unpack");
+
+            assert_eq!(&unpacked_msg, &*MESSAGE_SIMPLE);
+            assert_eq!(unpack_metadata.sign_from.as_ref(), Some(sign_by));
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_signed_remote_works_keys_not_found() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+        let remote_crypto = SecretsResolverCrypto::new(&secrets_resolver);
+
+        let err = MESSAGE_SIMPLE
+            .pack_signed_remote(ALICE_DID, &did_resolver, &remote_crypto)
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::SecretNotFound);
+        assert_eq!(format!("{}", err), "Secret not found: No signer keys found");
+    }
+
+    #[tokio::test]
+    async fn add_signature_works() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let signed = Message::add_signature(
+            SIGNED_MSG_ALICE_KEY_1,
+            &ALICE_AUTH_METHOD_P256.id,
+            &did_resolver,
+            &secrets_resolver,
+        )
+        .await
+        .expect("add_signature is ok.");
+
+        let orig: Value = serde_json::from_str(SIGNED_MSG_ALICE_KEY_1).expect("Unable from_str");
+        let signed_: Value = serde_json::from_str(&signed).expect("Unable from_str");
+
+        assert_eq!(signed_["payload"], orig["payload"]);
+        assert_eq!(signed_["signatures"][0], orig["signatures"][0]);
+        assert_eq!(signed_["signatures"].as_array().map(Vec::len), Some(2));
+
+        let (msg, metadata) = Message::unpack(
+            &signed,
+            &did_resolver,
+            &ExampleSecretsResolver::new(vec![]),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&msg, &*MESSAGE_SIMPLE);
+        assert!(metadata.non_repudiation);
+        assert_eq!(metadata.sign_from, Some(ALICE_AUTH_METHOD_25519.id.clone()));
+
+        assert_eq!(
+            metadata.sign_from_kids,
+            Some(vec![
+                ALICE_AUTH_METHOD_25519.id.clone(),
+                ALICE_AUTH_METHOD_P256.id.clone(),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn add_signature_works_compact() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let err = Message::add_signature(
+            FROM_PRIOR_JWT_FULL,
+            &ALICE_AUTH_METHOD_P256.id,
+            &did_resolver,
+            &secrets_resolver,
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: Compact JWS can't hold multiple signatures"
+        );
+    }
+
+    #[tokio::test]
+    async fn add_signature_works_already_signed() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let err = Message::add_signature(
+            SIGNED_MSG_ALICE_KEY_1,
+            &ALICE_AUTH_METHOD_25519.id,
+            &did_resolver,
+            &secrets_resolver,
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: Message is already signed by `did:example:alice#key-1`"
+        );
+    }
+
+    #[tokio::test]
+    async fn add_signature_works_malformed() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let err = Message::add_signature(
+            PLAINTEXT_MSG_SIMPLE,
+            &ALICE_AUTH_METHOD_P256.id,
+            &did_resolver,
+            &secrets_resolver,
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+    }
 }
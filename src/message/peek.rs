@@ -0,0 +1,510 @@
+use serde::Serialize;
+
+use crate::{
+    did::DIDResolver,
+    error::{err_msg, ErrorKind, Result, ResultContext, ResultExt},
+    jwe::{self, envelope::JWE},
+    jws::{self, JWS},
+    protocols::routing::try_parse_forward,
+    secrets::SecretsResolver,
+    utils::did::{did_or_url, key_agreements_not_found},
+    Message,
+};
+
+/// Kind of packed DIDComm message determined by `Message::peek`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+pub enum PackedKind {
+    /// Plaintext message (JWM).
+    Plaintext,
+
+    /// Signed message (JWS).
+    Signed,
+
+    /// Anonymously encrypted message (JWE with ECDH-ES key agreement).
+    AnonEncrypted,
+
+    /// Authenticated encrypted message (JWE with ECDH-1PU key agreement).
+    AuthEncrypted,
+
+    /// Plaintext `Forward` message of the routing protocol.
+    Forward,
+}
+
+/// Description of packed DIDComm message envelope determined by `Message::describe`
+/// (for ex. to be shown in UI as "Encrypted (XC20P, anonymous)").
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct EnvelopeDescription {
+    /// Kind of the envelope.
+    pub kind: PackedKind,
+
+    /// Declared `alg` of encrypted (key agreement) or signed (signature) message.
+    /// None for plaintext messages.
+    pub alg: Option<String>,
+
+    /// Declared content encryption `enc` of encrypted message. None for other messages.
+    pub enc: Option<String>,
+
+    /// Whether the sender is named in the envelope: `skid` or `apu` of encrypted message,
+    /// signer key of signed message or `from` of plaintext message.
+    /// Note that the sender isn't authenticated until the message is unpacked.
+    pub sender_named: bool,
+
+    /// Whether the envelope is signed. Encrypted messages are reported as not signed
+    /// as the signature of the encrypted payload can't be seen without decryption.
+    pub signed: bool,
+}
+
+impl Message {
+    /// Determines kind of packed message from its outer JSON structure and protected header.
+    /// It is a cheap operation that doesn't decrypt or verify the message, so neither secrets
+    /// nor DID resolution are needed. It can be used for logging and routing.
+    ///
+    /// Note that only plaintext `Forward` messages are reported as `Forward`,
+    /// `Forward` messages packed for mediators are reported as encrypted messages.
+    ///
+    /// # Params
+    /// - `msg` packed message as JSON string
+    ///
+    /// # Returns
+    /// Kind of packed message.
+    ///
+    /// # Errors
+    /// - `Malformed` message is not a plaintext, signed or encrypted DIDComm message.
+    pub fn peek(msg: &str) -> Result<PackedKind> {
+        if let Ok(jwe) = JWE::from_str(msg) {
+            let mut buf = vec![];
+            let parsed_jwe = jwe.parse(&mut buf)?;
+
+            return match parsed_jwe.protected.alg {
+                jwe::Algorithm::EcdhEsA256kw => Ok(PackedKind::AnonEncrypted),
+                jwe::Algorithm::Ecdh1puA256kw => Ok(PackedKind::AuthEncrypted),
+                _ => Err(err_msg(
+                    ErrorKind::Malformed,
+                    "Unknown encrypted message alg",
+                )),
+            };
+        }
+
+        if JWS::from_str(msg).is_ok() {
+            return Ok(PackedKind::Signed);
+        }
+
+        let msg = Message::from_str(msg)
+            .and_then(Message::validate)
+            .map_err(|_| {
+                err_msg(
+                    ErrorKind::Malformed,
+                    "Message is not a plaintext, signed or encrypted message",
+                )
+            })?;
+
+        match try_parse_forward(&msg) {
+            Some(_) => Ok(PackedKind::Forward),
+            None => Ok(PackedKind::Plaintext),
+        }
+    }
+
+    /// Describes packed message envelope from its outer JSON structure and protected headers:
+    /// envelope kind, declared algorithms and whether the sender is named.
+    /// As `peek` it doesn't decrypt or verify the message, so neither secrets
+    /// nor DID resolution are needed.
+    ///
+    /// # Params
+    /// - `msg` packed message as JSON string
+    ///
+    /// # Returns
+    /// Description of the envelope (see `EnvelopeDescription`).
+    ///
+    /// # Errors
+    /// - `Malformed` message is not a plaintext, signed or encrypted DIDComm message.
+    pub fn describe(msg: &str) -> Result<EnvelopeDescription> {
+        let kind = Message::peek(msg)?;
+
+        let description = match kind {
+            PackedKind::AnonEncrypted | PackedKind::AuthEncrypted => {
+                let mut buf = vec![];
+                let parsed_jwe = JWE::from_str(msg)?.parse(&mut buf)?;
+
+                EnvelopeDescription {
+                    kind,
+                    alg: Some(parsed_jwe.protected.alg.as_str().to_owned()),
+                    enc: Some(parsed_jwe.protected.enc.to_string()),
+                    sender_named: parsed_jwe.protected.skid.is_some()
+                        || parsed_jwe.protected.apu.is_some(),
+                    signed: false,
+                }
+            }
+            PackedKind::Signed => {
+                let mut buf = vec![];
+                let parsed_jws = jws::parse(msg, &mut buf)?;
+
+                EnvelopeDescription {
+                    kind,
+                    alg: parsed_jws.protected.first().map(|p| p.alg.to_string()),
+                    enc: None,
+                    sender_named: !parsed_jws.jws.signatures.is_empty(),
+                    signed: true,
+                }
+            }
+            PackedKind::Plaintext | PackedKind::Forward => EnvelopeDescription {
+                kind,
+                alg: None,
+                enc: None,
+                sender_named: Message::from_str(msg)?.from.is_some(),
+                signed: false,
+            },
+        };
+
+        Ok(description)
+    }
+
+    /// Determines DID of the sender of authcrypted message without decrypting the payload.
+    /// Sender key ID is read from the protected header (`apu`/`skid`), checked to be
+    /// a key agreement of the sender DID and the message is checked to be addressed to
+    /// a key we have secret for. It can be used for routing and rate-limiting before `unpack`.
+    ///
+    /// Note that the sender isn't authenticated until the message is unpacked, so the
+    /// returned DID must not be trusted for anything but preliminary decisions.
+    ///
+    /// # Params
+    /// - `msg` packed message as JSON string
+    /// - `did_resolver` instance of `DIDResolver` to resolve sender DID.
+    /// - `secrets_resolver` instance of SecretsResolver` to find recipient secrets.
+    ///
+    /// # Returns
+    /// Sender DID for authcrypted message and `None` for anoncrypted, signed or plaintext message.
+    ///
+    /// # Errors
+    /// - `Malformed` authcrypted message is malformed.
+    /// - `DIDNotResolved` sender DID not found.
+    /// - `DIDUrlNotFound` sender key isn't a key agreement of the sender DID.
+    /// - `SecretNotFound` no recipient secrets found.
+    /// - `IoError` IO error during DID or secrets resolving.
+    pub async fn peek_sender<'dr, 'sr>(
+        msg: &str,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+    ) -> Result<Option<String>> {
+        let jwe = match JWE::from_str(msg) {
+            Ok(jwe) => jwe,
+            Err(_) => return Ok(None),
+        };
+
+        let mut buf = vec![];
+        let parsed_jwe = jwe.parse(&mut buf)?;
+
+        if parsed_jwe.protected.alg != jwe::Algorithm::Ecdh1puA256kw {
+            return Ok(None);
+        }
+
+        let parsed_jwe = parsed_jwe.verify_didcomm()?;
+
+        let from_kid = std::str::from_utf8(
+            parsed_jwe
+                .apu
+                .as_deref()
+                .ok_or_else(|| err_msg(ErrorKind::Malformed, "No apu presented for authcrypt"))?,
+        )
+        .kind(ErrorKind::Malformed, "apu is invalid utf8")?;
+
+        let (from_did, from_url) = did_or_url(from_kid);
+
+        if from_url.is_none() {
+            Err(err_msg(
+                ErrorKind::Malformed,
+                "Sender key can't be resolved to key agreement",
+            ))?;
+        }
+
+        let from_ddoc = did_resolver
+            .resolve(from_did)
+            .await
+            .context("Unable resolve sender did")?
+            .ok_or_else(|| err_msg(ErrorKind::DIDNotResolved, "Sender did not found"))?;
+
+        if !from_ddoc.key_agreements.iter().any(|k| k == from_kid) {
+            Err(key_agreements_not_found(
+                "Sender kid not found in did",
+                &from_ddoc,
+            ))?;
+        }
+
+        let to_kids = parsed_jwe.to_kids();
+
+        let to_kids_found = secrets_resolver.find_secrets(&to_kids).await?;
+
+        if to_kids_found.is_empty() {
+            Err(err_msg(
+                ErrorKind::SecretNotFound,
+                "No recipient secrets found",
+            ))?;
+        }
+
+        Ok(Some(from_did.to_owned()))
+    }
+
+    /// Extracts plaintext message from the payload of signed message WITHOUT verifying
+    /// signatures. Neither DID resolution nor secrets are needed.
+    ///
+    /// Note that signer of the returned message isn't authenticated. Use it only for
+    /// re-processing of messages that were already verified (for ex. by `unpack`).
+    ///
+    /// # Params
+    /// - `jws` signed message as JSON string
+    ///
+    /// # Returns
+    /// Plaintext message from the payload.
+    ///
+    /// # Errors
+    /// - `Malformed` message is not a signed message or its payload is not a valid plaintext message.
+    pub fn from_signed_unverified(jws: &str) -> Result<Message> {
+        let jws = JWS::from_str(jws).context("Message is not a signed message")?;
+
+        let mut buf = vec![];
+        let parsed_jws = jws.parse(&mut buf)?;
+
+        let payload = base64::decode_config(parsed_jws.jws.payload, base64::URL_SAFE_NO_PAD)
+            .kind(ErrorKind::Malformed, "Signed payload is invalid base64")?;
+
+        let payload = std::str::from_utf8(&payload)
+            .kind(ErrorKind::Malformed, "Signed payload is invalid utf8")?;
+
+        Message::from_str(payload)
+            .and_then(Message::validate)
+            .context("Signed payload is not a valid plaintext message")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        did::resolvers::ExampleDIDResolver,
+        error::ErrorKind,
+        protocols::routing::build_forward_message,
+        secrets::resolvers::ExampleSecretsResolver,
+        test_vectors::{
+            ALICE_DID, ALICE_DID_DOC, BOB_DID_DOC, BOB_SECRETS, CHARLIE_SECRETS,
+            ENCRYPTED_MSG_ANON_XC20P_1, ENCRYPTED_MSG_ANON_XC20P_2, ENCRYPTED_MSG_AUTH_P256,
+            ENCRYPTED_MSG_AUTH_P256_SIGNED, ENCRYPTED_MSG_AUTH_X25519,
+            INVALID_PLAINTEXT_MSG_WRONG_TYP, MESSAGE_SIMPLE, PLAINTEXT_FROM_PRIOR,
+            PLAINTEXT_MSG_ATTACHMENT_BASE64, PLAINTEXT_MSG_MINIMAL, PLAINTEXT_MSG_SIMPLE,
+            SIGNED_MSG_ALICE_KEY_1, SIGNED_MSG_ALICE_KEY_1_JCS, SIGNED_MSG_ALICE_KEY_2,
+            SIGNED_MSG_ALICE_KEY_3,
+        },
+        Message,
+    };
+
+    use super::{EnvelopeDescription, PackedKind};
+
+    #[test]
+    fn peek_works() {
+        for (msg, exp_kind) in [
+            (PLAINTEXT_MSG_SIMPLE, PackedKind::Plaintext),
+            (PLAINTEXT_MSG_MINIMAL, PackedKind::Plaintext),
+            (PLAINTEXT_FROM_PRIOR, PackedKind::Plaintext),
+            (PLAINTEXT_MSG_ATTACHMENT_BASE64, PackedKind::Plaintext),
+            (SIGNED_MSG_ALICE_KEY_1, PackedKind::Signed),
+            (SIGNED_MSG_ALICE_KEY_2, PackedKind::Signed),
+            (SIGNED_MSG_ALICE_KEY_3, PackedKind::Signed),
+            (SIGNED_MSG_ALICE_KEY_1_JCS, PackedKind::Signed),
+            (ENCRYPTED_MSG_ANON_XC20P_1, PackedKind::AnonEncrypted),
+            (ENCRYPTED_MSG_ANON_XC20P_2, PackedKind::AnonEncrypted),
+            (ENCRYPTED_MSG_AUTH_X25519, PackedKind::AuthEncrypted),
+            (ENCRYPTED_MSG_AUTH_P256, PackedKind::AuthEncrypted),
+            (ENCRYPTED_MSG_AUTH_P256_SIGNED, PackedKind::AuthEncrypted),
+        ] {
+            assert_eq!(Message::peek(msg).expect("peek is ok."), exp_kind);
+        }
+    }
+
+    #[test]
+    fn peek_works_forward() {
+        let msg = build_forward_message(ENCRYPTED_MSG_ANON_XC20P_1, "did:example:bob", None)
+            .expect("Unable build forward");
+
+        assert_eq!(
+            Message::peek(&msg).expect("peek is ok."),
+            PackedKind::Forward
+        );
+    }
+
+    #[test]
+    fn peek_works_malformed() {
+        for msg in [
+            "",
+            "not a json",
+            "{}",
+            r#"["array"]"#,
+            INVALID_PLAINTEXT_MSG_WRONG_TYP,
+        ] {
+            let err = Message::peek(msg).expect_err("res is ok");
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+        }
+    }
+
+    #[test]
+    fn describe_works() {
+        for (msg, kind, alg, enc, sender_named, signed) in [
+            (
+                ENCRYPTED_MSG_ANON_XC20P_1,
+                PackedKind::AnonEncrypted,
+                Some("ECDH-ES+A256KW"),
+                Some("XC20P"),
+                false,
+                false,
+            ),
+            (
+                ENCRYPTED_MSG_AUTH_X25519,
+                PackedKind::AuthEncrypted,
+                Some("ECDH-1PU+A256KW"),
+                Some("A256CBC-HS512"),
+                true,
+                false,
+            ),
+            (
+                ENCRYPTED_MSG_AUTH_P256,
+                PackedKind::AuthEncrypted,
+                Some("ECDH-1PU+A256KW"),
+                Some("A256CBC-HS512"),
+                true,
+                false,
+            ),
+            (
+                SIGNED_MSG_ALICE_KEY_1,
+                PackedKind::Signed,
+                Some("EdDSA"),
+                None,
+                true,
+                true,
+            ),
+            (
+                SIGNED_MSG_ALICE_KEY_2,
+                PackedKind::Signed,
+                Some("ES256"),
+                None,
+                true,
+                true,
+            ),
+            (
+                SIGNED_MSG_ALICE_KEY_3,
+                PackedKind::Signed,
+                Some("ES256K"),
+                None,
+                true,
+                true,
+            ),
+            (
+                PLAINTEXT_MSG_SIMPLE,
+                PackedKind::Plaintext,
+                None,
+                None,
+                true,
+                false,
+            ),
+            (
+                PLAINTEXT_MSG_MINIMAL,
+                PackedKind::Plaintext,
+                None,
+                None,
+                false,
+                false,
+            ),
+        ] {
+            assert_eq!(
+                Message::describe(msg).expect("describe is ok."),
+                EnvelopeDescription {
+                    kind,
+                    alg: alg.map(str::to_owned),
+                    enc: enc.map(str::to_owned),
+                    sender_named,
+                    signed,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn describe_works_malformed() {
+        for msg in ["", "not a json", "{}", INVALID_PLAINTEXT_MSG_WRONG_TYP] {
+            let err = Message::describe(msg).expect_err("res is ok");
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+        }
+    }
+
+    #[tokio::test]
+    async fn peek_sender_works() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        for msg in [
+            ENCRYPTED_MSG_AUTH_X25519,
+            ENCRYPTED_MSG_AUTH_P256,
+            ENCRYPTED_MSG_AUTH_P256_SIGNED,
+        ] {
+            let sender = Message::peek_sender(msg, &did_resolver, &secrets_resolver)
+                .await
+                .expect("peek_sender is ok.");
+
+            assert_eq!(sender.as_deref(), Some(ALICE_DID));
+        }
+
+        for msg in [
+            ENCRYPTED_MSG_ANON_XC20P_1,
+            SIGNED_MSG_ALICE_KEY_1,
+            PLAINTEXT_MSG_SIMPLE,
+        ] {
+            let sender = Message::peek_sender(msg, &did_resolver, &secrets_resolver)
+                .await
+                .expect("peek_sender is ok.");
+
+            assert_eq!(sender, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn peek_sender_works_errors() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(CHARLIE_SECRETS.clone());
+
+        let err = Message::peek_sender(ENCRYPTED_MSG_AUTH_X25519, &did_resolver, &secrets_resolver)
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::SecretNotFound);
+
+        let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let err = Message::peek_sender(ENCRYPTED_MSG_AUTH_X25519, &did_resolver, &secrets_resolver)
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::DIDNotResolved);
+    }
+
+    #[test]
+    fn from_signed_unverified_works() {
+        for msg in [
+            SIGNED_MSG_ALICE_KEY_1,
+            SIGNED_MSG_ALICE_KEY_2,
+            SIGNED_MSG_ALICE_KEY_3,
+        ] {
+            let msg = Message::from_signed_unverified(msg).expect("from_signed_unverified is ok.");
+            assert_eq!(msg, *MESSAGE_SIMPLE);
+        }
+    }
+
+    #[test]
+    fn from_signed_unverified_works_not_signed() {
+        for msg in [
+            PLAINTEXT_MSG_SIMPLE,
+            ENCRYPTED_MSG_ANON_XC20P_1,
+            "not a json",
+        ] {
+            let err = Message::from_signed_unverified(msg).expect_err("res is ok");
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use crate::{
+    did::{did_resolver::TimeoutDIDResolver, DIDResolver},
+    error::{err_msg, ErrorKind, Result, ResultContext},
+    message::{_content_hash, anoncrypt},
+    protocols::routing::wrap_in_forward_if_needed,
+    secrets::SecretsResolver,
+    utils::did::is_did,
+    Message, PackEncryptedMetadata, PackEncryptedOptions, UnpackOptions,
+};
+
+impl Message {
+    /// Re-encrypts (transcrypts) packed message for a new recipient in one call,
+    /// so plaintext isn't exposed to the caller (for ex. to a mediator or relay
+    /// that moves messages between recipients).
+    ///
+    /// Packed message is unpacked with the given secrets and then anonymously encrypted
+    /// for `new_to` as `pack_encrypted` does. If the message is signed, the original JWS
+    /// is encrypted as is, so the signature is preserved and can be verified by the new recipient.
+    ///
+    /// # Params
+    /// - `packed` packed DIDComm message as JSON string the caller has secrets to decrypt.
+    /// - `new_to` new recipient DID or key ID the message will be encrypted for.
+    /// - `did_resolver` instance of `DIDResolver` to resolve DIDs.
+    /// - `secrets_resolver` instance of `SecretsResolver` to resolve secrets to decrypt the message.
+    /// - `options` allow fine configuration of packing process as for `pack_encrypted`.
+    ///   `protect_sender` is ignored as the message is encrypted anonymously.
+    ///
+    /// # Returns
+    /// Tuple `(encrypted_message, metadata)` as for `pack_encrypted`.
+    /// `metadata.sign_by_kid` and `metadata.sign_alg` describe preserved signature if any.
+    ///
+    /// # Errors
+    /// - `IllegalArgument` `new_to` is not a valid DID or DID URL.
+    /// - errors of `unpack` and `pack_encrypted`.
+    pub async fn reencrypt<'dr, 'sr>(
+        packed: &str,
+        new_to: &str,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        options: &PackEncryptedOptions,
+    ) -> Result<(String, PackEncryptedMetadata)> {
+        if !is_did(new_to) {
+            Err(err_msg(
+                ErrorKind::IllegalArgument,
+                "`new_to` value is not a valid DID or DID URL",
+            ))?;
+        }
+
+        let did_resolver: &dyn DIDResolver =
+            &TimeoutDIDResolver::new(did_resolver, options.resolver_timeout);
+
+        let (msg, unpack_metadata) = Message::unpack(
+            packed,
+            did_resolver,
+            secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .context("Unable unpack message")?;
+
+        let payload = match unpack_metadata.signed_message {
+            Some(signed_message) => signed_message,
+            None => msg
+                .pack_plaintext(did_resolver)
+                .await
+                .context("Unable produce plaintext")?,
+        };
+
+        let (msg, to_kids) = anoncrypt(
+            new_to,
+            None,
+            did_resolver,
+            payload.as_bytes(),
+            &options.enc_alg_anon,
+            &options.algorithms,
+            options.nonce_source(),
+            options.session_key_provider(),
+            options.aad.as_deref().map(str::as_bytes),
+            options.preserve_recipient_kids_order,
+        )
+        .await?;
+
+        let (msg, messaging_service, forwarded) =
+            match wrap_in_forward_if_needed(&msg, new_to, did_resolver, secrets_resolver, options)
+                .await?
+            {
+                Some((forward_msg, messaging_service)) => {
+                    (forward_msg, Some(messaging_service), true)
+                }
+                None => (msg, None, false),
+            };
+
+        let metadata = PackEncryptedMetadata {
+            messaging_service,
+            forwarded,
+            from_kid: None,
+            sign_by_kid: unpack_metadata.sign_from,
+            sign_alg: unpack_metadata.sign_alg,
+            from_prior_issuer_kid: None,
+            to_kids,
+            content_hash: _content_hash(&msg, options),
+        };
+
+        Ok((msg, metadata))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        algorithms::SignAlg,
+        did::resolvers::ExampleDIDResolver,
+        error::ErrorKind,
+        secrets::resolvers::ExampleSecretsResolver,
+        test_vectors::{
+            ALICE_AUTH_METHOD_25519, ALICE_DID, ALICE_DID_DOC, ALICE_SECRETS, BOB_DID, BOB_DID_DOC,
+            BOB_SECRETS, CHARLIE_DID, CHARLIE_DID_DOC, CHARLIE_SECRETS, MESSAGE_SIMPLE,
+        },
+        Message, PackEncryptedOptions, UnpackOptions,
+    };
+
+    #[tokio::test]
+    async fn reencrypt_works() {
+        _reencrypt_works(None, None).await;
+        _reencrypt_works(Some(ALICE_DID), None).await;
+        _reencrypt_works(None, Some(ALICE_DID)).await;
+        _reencrypt_works(Some(ALICE_DID), Some(ALICE_DID)).await;
+
+        async fn _reencrypt_works(from: Option<&str>, sign_by: Option<&str>) {
+            let did_resolver = ExampleDIDResolver::new(vec![
+                ALICE_DID_DOC.clone(),
+                BOB_DID_DOC.clone(),
+                CHARLIE_DID_DOC.clone(),
+            ]);
+
+            let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+            let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+            let charlie_secrets_resolver = ExampleSecretsResolver::new(CHARLIE_SECRETS.clone());
+
+            let options = PackEncryptedOptions {
+                forward: false,
+                ..PackEncryptedOptions::default()
+            };
+
+            let (packed, _) = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    BOB_DID,
+                    from,
+                    sign_by,
+                    &did_resolver,
+                    &alice_secrets_resolver,
+                    &options,
+                )
+                .await
+                .expect("encrypt is ok.");
+
+            let (reencrypted, metadata) = Message::reencrypt(
+                &packed,
+                CHARLIE_DID,
+                &did_resolver,
+                &bob_secrets_resolver,
+                &options,
+            )
+            .await
+            .expect("reencrypt is ok.");
+
+            assert_eq!(metadata.from_kid, None);
+            assert!(!metadata.forwarded);
+            assert!(metadata
+                .to_kids
+                .iter()
+                .all(|kid| kid.starts_with(CHARLIE_DID)));
+
+            let exp_sign_by_kid = sign_by.map(|_| ALICE_AUTH_METHOD_25519.id.clone());
+            assert_eq!(metadata.sign_by_kid, exp_sign_by_kid);
+            assert_eq!(metadata.sign_alg, sign_by.map(|_| SignAlg::EdDSA));
+
+            // Bob can't decrypt re-encrypted message anymore
+            Message::unpack(
+                &reencrypted,
+                &did_resolver,
+                &bob_secrets_resolver,
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect_err("res is ok");
+
+            let (unpacked, unpack_metadata) = Message::unpack(
+                &reencrypted,
+                &did_resolver,
+                &charlie_secrets_resolver,
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect("unpack is ok.");
+
+            assert_eq!(&unpacked, &*MESSAGE_SIMPLE);
+            assert!(unpack_metadata.encrypted);
+            assert!(unpack_metadata.anonymous_sender);
+            assert!(!unpack_metadata.authenticated || sign_by.is_some());
+            assert_eq!(unpack_metadata.non_repudiation, sign_by.is_some());
+            assert_eq!(unpack_metadata.sign_from, exp_sign_by_kid);
+        }
+    }
+
+    #[tokio::test]
+    async fn reencrypt_works_invalid_new_to() {
+        let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let err = Message::reencrypt(
+            "{}",
+            "not-a-did",
+            &did_resolver,
+            &secrets_resolver,
+            &PackEncryptedOptions::default(),
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+    }
+
+    #[tokio::test]
+    async fn reencrypt_works_no_secrets() {
+        let did_resolver = ExampleDIDResolver::new(vec![
+            ALICE_DID_DOC.clone(),
+            BOB_DID_DOC.clone(),
+            CHARLIE_DID_DOC.clone(),
+        ]);
+
+        let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let (packed, _) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                None,
+                None,
+                &did_resolver,
+                &alice_secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("encrypt is ok.");
+
+        let err = Message::reencrypt(
+            &packed,
+            CHARLIE_DID,
+            &did_resolver,
+            &ExampleSecretsResolver::new(CHARLIE_SECRETS.clone()),
+            &PackEncryptedOptions::default(),
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::SecretNotFound);
+    }
+}
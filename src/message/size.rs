@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+
+use serde_json::json;
+
+use crate::{
+    error::{err_msg, ErrorKind, Result},
+    message::AttachmentData,
+    utils::encoding::base64_decoded_size,
+    Message,
+};
+
+/// Sizes in bytes of plaintext message parts (see `Message::size_breakdown`).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SizeBreakdown {
+    /// Size of plaintext message JSON.
+    pub total: usize,
+
+    /// Size of all fields except body and attachments including JSON punctuation,
+    /// so `headers + body + sum of attachments encoded sizes == total`.
+    pub headers: usize,
+
+    /// Size of serialized body.
+    pub body: usize,
+
+    /// Sizes of attachments in order of message attachments.
+    pub attachments: Vec<AttachmentSize>,
+}
+
+/// Size in bytes of a single attachment (see `Message::size_breakdown`).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AttachmentSize {
+    /// Attachment id if set.
+    pub id: Option<String>,
+
+    /// Size of attachment as serialized in plaintext message JSON.
+    pub encoded: usize,
+
+    /// Size of attachment content: decoded bytes for base64 data, serialized JSON for json data.
+    /// 0 for links data as the content isn't embedded into the message.
+    pub decoded: usize,
+
+    /// Estimated bytes added by base64 encoding of the content (encoded minus decoded content size).
+    /// 0 for json and links data.
+    pub base64_overhead: usize,
+}
+
+impl SizeBreakdown {
+    /// Estimated bytes added by base64 encoding of all attachments.
+    pub fn base64_overhead(&self) -> usize {
+        self.attachments.iter().map(|a| a.base64_overhead).sum()
+    }
+}
+
+impl Message {
+    /// Reports sizes of plaintext message parts: headers, body and each attachment.
+    /// Helps to warn before sending huge attachments. Packing (encryption, signing
+    /// and forwarding) adds its own overhead, see `Message::pack_encrypted_plan` for it.
+    ///
+    /// Base64 content is not decoded, its size is calculated from the encoded length.
+    pub fn size_breakdown(&self) -> SizeBreakdown {
+        let total = _json_size(self);
+        let body = _json_size(&self.body);
+
+        let attachments: Vec<_> = self
+            .attachments
+            .iter()
+            .flatten()
+            .map(|attachment| {
+                let (decoded, base64_overhead) = match attachment.data {
+                    AttachmentData::Base64 { ref value } => {
+                        let decoded = base64_decoded_size(&value.base64);
+                        (decoded, value.base64.len().saturating_sub(decoded))
+                    }
+                    AttachmentData::Json { ref value } => (_json_size(&value.json), 0),
+                    AttachmentData::Links { .. } => (0, 0),
+                };
+
+                AttachmentSize {
+                    id: attachment.id.clone(),
+                    encoded: _json_size(attachment),
+                    decoded,
+                    base64_overhead,
+                }
+            })
+            .collect();
+
+        let headers = attachments
+            .iter()
+            .fold(total.saturating_sub(body), |headers, a| {
+                headers.saturating_sub(a.encoded)
+            });
+
+        SizeBreakdown {
+            total,
+            headers,
+            body,
+            attachments,
+        }
+    }
+}
+
+impl Message {
+    /// Splits the message which plaintext exceeds the given size (for ex. mediator limit)
+    /// because of attachments into several messages.
+    ///
+    /// The first message is this message with attachments that fit the limit, they are kept
+    /// in order as long as the message fits. Each other attachment is moved to a separate message
+    /// with empty body, the same `type`, `from`, `to` and timestamps, `{id}-{n}` ID
+    /// and `pthid` referencing the thread of this message (`thid` or `id` if `thid` isn't set).
+    /// The same message always produces the same split.
+    ///
+    /// # Returns
+    /// This message as is if it fits the limit, otherwise the first message
+    /// followed by messages with moved attachments.
+    ///
+    /// # Errors
+    /// - `TooLarge` The message without attachments or a message with a single attachment
+    ///   doesn't fit the limit.
+    pub fn split_by_size(&self, max_bytes: usize) -> Result<Vec<Message>> {
+        if _json_size(self) <= max_bytes {
+            return Ok(vec![self.clone()]);
+        }
+
+        let mut root = self.clone();
+        let attachments = root.attachments.take().unwrap_or_default();
+
+        if _json_size(&root) > max_bytes {
+            Err(err_msg(
+                ErrorKind::TooLarge,
+                format!(
+                    "Message without attachments doesn't fit {} bytes",
+                    max_bytes
+                ),
+            ))?
+        }
+
+        let core = root.clone();
+        let mut moved = vec![];
+
+        for attachment in attachments {
+            let kept = root.attachments.get_or_insert_with(Vec::new);
+            kept.push(attachment);
+
+            if _json_size(&root) > max_bytes {
+                moved.extend(root.attachments.as_mut().and_then(Vec::pop));
+            }
+        }
+
+        if root.attachments.as_ref().map_or(false, Vec::is_empty) {
+            root.attachments = None;
+        }
+
+        let pthid = self.thid.as_ref().unwrap_or(&self.id);
+        let mut messages = vec![root];
+
+        for (i, attachment) in moved.into_iter().enumerate() {
+            let id = attachment.id.clone();
+
+            let msg = Message {
+                id: format!("{}-{}", self.id, i + 1),
+                body: json!({}),
+                thid: None,
+                pthid: Some(pthid.clone()),
+                extra_headers: HashMap::new(),
+                from_prior: None,
+                attachments: Some(vec![attachment]),
+                ..core.clone()
+            };
+
+            if _json_size(&msg) > max_bytes {
+                Err(err_msg(
+                    ErrorKind::TooLarge,
+                    format!(
+                        "Attachment `{}` doesn't fit {} bytes",
+                        id.as_deref().unwrap_or("(no id)"),
+                        max_bytes
+                    ),
+                ))?
+            }
+
+            messages.push(msg);
+        }
+
+        Ok(messages)
+    }
+}
+
+fn _json_size<T: serde::Serialize + ?Sized>(value: &T) -> usize {
+    // Message parts are always serializable
+    serde_json::to_vec(value).map(|v| v.len()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{_json_size, AttachmentSize};
+    use crate::{
+        error::ErrorKind,
+        test_vectors::{MESSAGE_ATTACHMENT_MULTI_1, MESSAGE_SIMPLE},
+        Attachment, Message,
+    };
+
+    #[test]
+    fn size_breakdown_works() {
+        let size = MESSAGE_ATTACHMENT_MULTI_1.size_breakdown();
+
+        assert_eq!(
+            size.attachments,
+            vec![
+                AttachmentSize {
+                    id: Some("23".into()),
+                    encoded: r#"{"data":{"json":{"foo":"bar","links":[2,3]}},"id":"23"}"#.len(),
+                    decoded: r#"{"foo":"bar","links":[2,3]}"#.len(),
+                    base64_overhead: 0,
+                },
+                AttachmentSize {
+                    id: Some("24".into()),
+                    encoded: r#"{"data":{"base64":"qwerty"},"id":"24"}"#.len(),
+                    decoded: 4,
+                    base64_overhead: 2,
+                },
+                AttachmentSize {
+                    id: Some("25".into()),
+                    encoded: r#"{"data":{"links":["1","2","3"],"hash":"qwerty"},"id":"25"}"#.len(),
+                    decoded: 0,
+                    base64_overhead: 0,
+                },
+            ]
+        );
+
+        assert_eq!(size.base64_overhead(), 2);
+        assert_eq!(
+            size.body,
+            r#"{"messagespecificattribute":"and its value"}"#.len()
+        );
+
+        assert_eq!(
+            size.total,
+            serde_json::to_string(&*MESSAGE_ATTACHMENT_MULTI_1)
+                .expect("serialize is ok.")
+                .len()
+        );
+
+        assert_eq!(
+            size.headers + size.body + size.attachments.iter().map(|a| a.encoded).sum::<usize>(),
+            size.total
+        );
+    }
+
+    #[test]
+    fn size_breakdown_works_no_attachments() {
+        let size = MESSAGE_SIMPLE.size_breakdown();
+
+        assert_eq!(size.attachments, vec![]);
+        assert_eq!(size.base64_overhead(), 0);
+        assert_eq!(size.headers + size.body, size.total);
+    }
+
+    #[test]
+    fn split_by_size_works() {
+        let attachments = vec![
+            Attachment::base64("a".repeat(400))
+                .id("1".into())
+                .finalize(),
+            Attachment::json(json!({"foo": "bar"}))
+                .id("2".into())
+                .finalize(),
+            Attachment::base64("b".repeat(400))
+                .id("3".into())
+                .finalize(),
+        ];
+
+        let msg = Message {
+            attachments: Some(attachments.clone()),
+            ..MESSAGE_SIMPLE.clone()
+        };
+
+        let max_bytes = _json_size(&Message {
+            attachments: Some(vec![attachments[0].clone()]),
+            ..MESSAGE_SIMPLE.clone()
+        }) + 10;
+
+        let split = msg.split_by_size(max_bytes).expect("split_by_size is ok.");
+
+        assert_eq!(split.len(), 3);
+
+        assert_eq!(
+            split[0],
+            Message {
+                attachments: Some(vec![attachments[0].clone()]),
+                ..MESSAGE_SIMPLE.clone()
+            }
+        );
+
+        for (piece, (id, attachment)) in split[1..].iter().zip([
+            ("1234567890-1", &attachments[1]),
+            ("1234567890-2", &attachments[2]),
+        ]) {
+            assert_eq!(piece.id, id);
+            assert_eq!(piece.pthid.as_deref(), Some(MESSAGE_SIMPLE.id.as_str()));
+            assert_eq!(piece.thid, None);
+            assert_eq!(piece.type_, MESSAGE_SIMPLE.type_);
+            assert_eq!(piece.body, json!({}));
+            assert_eq!(piece.to, MESSAGE_SIMPLE.to);
+            assert_eq!(piece.attachments, Some(vec![attachment.clone()]));
+        }
+
+        for piece in &split {
+            assert!(_json_size(piece) <= max_bytes);
+        }
+
+        // Split is deterministic
+        assert_eq!(
+            msg.split_by_size(max_bytes).expect("split_by_size is ok."),
+            split
+        );
+    }
+
+    #[test]
+    fn split_by_size_works_fits() {
+        let max_bytes = _json_size(&*MESSAGE_ATTACHMENT_MULTI_1);
+
+        let split = MESSAGE_ATTACHMENT_MULTI_1
+            .split_by_size(max_bytes)
+            .expect("split_by_size is ok.");
+
+        assert_eq!(split, vec![MESSAGE_ATTACHMENT_MULTI_1.clone()]);
+    }
+
+    #[test]
+    fn split_by_size_works_too_large() {
+        let msg = Message {
+            attachments: Some(vec![Attachment::base64("a".repeat(400))
+                .id("1".into())
+                .finalize()]),
+            ..MESSAGE_SIMPLE.clone()
+        };
+
+        for (max_bytes, exp_err_msg) in [
+            (
+                _json_size(&*MESSAGE_SIMPLE) - 1,
+                "Message without attachments doesn't fit",
+            ),
+            (
+                _json_size(&*MESSAGE_SIMPLE) + 10,
+                "Attachment `1` doesn't fit",
+            ),
+        ] {
+            let err = msg.split_by_size(max_bytes).expect_err("res is ok");
+
+            assert_eq!(err.kind(), ErrorKind::TooLarge);
+            assert_eq!(
+                format!("{}", err),
+                format!("Too large: {} {} bytes", exp_err_msg, max_bytes)
+            );
+        }
+    }
+}
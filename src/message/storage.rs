@@ -0,0 +1,221 @@
+use crate::{
+    algorithms::{AlgorithmRegistry, AnonCryptAlg},
+    did::{did_resolver::RelativeDIDUrlsResolver, DIDResolver},
+    error::{err_msg, ErrorKind, Result, ResultContext},
+    message::pack_encrypted::anoncrypt,
+    secrets::SecretsResolver,
+    utils::did::is_did,
+    Message, UnpackOptions,
+};
+
+impl Message {
+    /// Encrypts the message to own key for local storage (encryption at rest).
+    ///
+    /// The message is anonymously encrypted (anoncrypt) for the given key only, without
+    /// sender authentication, signing and forward wrapping, so no other party is involved.
+    /// Unlike `pack_encrypted`, message `to` isn't checked against the key DID, so received
+    /// and sent messages addressed to other parties can be stored as well.
+    /// Use `unpack_from_storage` with the same resolvers to get the message back.
+    ///
+    /// # Params
+    /// - `recipient_kid` own key agreement key ID (DID URL) the message is encrypted for.
+    /// - `did_resolver` instance of `DIDResolver` to resolve own DID.
+    /// - `secrets_resolver` instance of `SecretsResolver` to resolve own secrets.
+    ///
+    /// # Returns
+    /// A DIDComm encrypted message as a JSON string.
+    ///
+    /// # Errors
+    /// - `IllegalArgument` `recipient_kid` is not a valid DID or DID URL.
+    /// - other errors are the same as for `pack_encrypted`.
+    pub async fn pack_for_storage<'dr, 'sr>(
+        &self,
+        recipient_kid: &str,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        _secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+    ) -> Result<String> {
+        if !is_did(recipient_kid) {
+            Err(err_msg(
+                ErrorKind::IllegalArgument,
+                "`recipient_kid` value is not a valid DID or DID URL",
+            ))?;
+        }
+
+        let did_resolver = RelativeDIDUrlsResolver::new(did_resolver, false);
+
+        let msg = self
+            .pack_plaintext(&did_resolver)
+            .await
+            .context("Unable produce plaintext")?;
+
+        let (msg, _) = anoncrypt(
+            recipient_kid,
+            None,
+            &did_resolver,
+            msg.as_bytes(),
+            &AnonCryptAlg::default(),
+            &AlgorithmRegistry::default(),
+            None,
+            None,
+            None,
+            false,
+        )
+        .await?;
+
+        Ok(msg)
+    }
+
+    /// Decrypts the message stored with `pack_for_storage`.
+    ///
+    /// # Params
+    /// - `msg` message packed with `pack_for_storage`.
+    /// - `did_resolver` instance of `DIDResolver` to resolve own DID.
+    /// - `secrets_resolver` instance of `SecretsResolver` to resolve own secrets.
+    ///
+    /// # Errors
+    /// - `Malformed` Message isn't anonymously encrypted plaintext (for ex. it is signed,
+    ///   authenticated or not encrypted).
+    /// - The same as for `unpack`.
+    pub async fn unpack_from_storage<'dr, 'sr>(
+        msg: &str,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+    ) -> Result<Message> {
+        let (msg, metadata) = Message::unpack(
+            msg,
+            did_resolver,
+            secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await?;
+
+        if !metadata.encrypted
+            || metadata.authenticated
+            || metadata.non_repudiation
+            || metadata.re_wrapped_in_forward
+        {
+            Err(err_msg(
+                ErrorKind::Malformed,
+                "Message isn't packed for storage",
+            ))?
+        }
+
+        Ok(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        did::resolvers::ExampleDIDResolver,
+        error::ErrorKind,
+        secrets::resolvers::ExampleSecretsResolver,
+        test_vectors::{
+            ALICE_DID, ALICE_DID_DOC, BOB_DID, BOB_DID_DOC, BOB_SECRETS,
+            BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1, ENCRYPTED_MSG_AUTH_X25519, MESSAGE_SIMPLE,
+            PLAINTEXT_MSG_SIMPLE,
+        },
+        Message,
+    };
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn pack_for_storage_works() {
+        let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let stored = MESSAGE_SIMPLE
+            .pack_for_storage(
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+                &did_resolver,
+                &secrets_resolver,
+            )
+            .await
+            .expect("pack_for_storage is ok.");
+
+        let (_, metadata) = Message::unpack(
+            &stored,
+            &did_resolver,
+            &secrets_resolver,
+            &Default::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert!(metadata.anonymous_sender);
+        assert_eq!(
+            metadata.encrypted_to_kids,
+            Some(vec![BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone()])
+        );
+
+        let msg = Message::unpack_from_storage(&stored, &did_resolver, &secrets_resolver)
+            .await
+            .expect("unpack_from_storage is ok.");
+
+        assert_eq!(msg, *MESSAGE_SIMPLE);
+    }
+
+    #[tokio::test]
+    async fn pack_for_storage_works_message_to_other_party() {
+        let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        // Message sent by Bob to Alice is stored encrypted to Bob's own key
+        let msg = Message::build(
+            "1234567890".to_owned(),
+            "http://example.com/protocols/lets_do_lunch/1.0/proposal".to_owned(),
+            json!({"messagespecificattribute": "and its value"}),
+        )
+        .from(BOB_DID.to_owned())
+        .to(ALICE_DID.to_owned())
+        .finalize();
+
+        let stored = msg
+            .pack_for_storage(
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+                &did_resolver,
+                &secrets_resolver,
+            )
+            .await
+            .expect("pack_for_storage is ok.");
+
+        let unpacked = Message::unpack_from_storage(&stored, &did_resolver, &secrets_resolver)
+            .await
+            .expect("unpack_from_storage is ok.");
+
+        assert_eq!(unpacked, msg);
+    }
+
+    #[tokio::test]
+    async fn pack_for_storage_works_invalid_recipient_kid() {
+        let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let err = MESSAGE_SIMPLE
+            .pack_for_storage("not-a-did", &did_resolver, &secrets_resolver)
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+    }
+
+    #[tokio::test]
+    async fn unpack_from_storage_works_not_stored() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        for msg in [PLAINTEXT_MSG_SIMPLE, ENCRYPTED_MSG_AUTH_X25519] {
+            let err = Message::unpack_from_storage(msg, &did_resolver, &secrets_resolver)
+                .await
+                .expect_err("res is ok");
+
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+            assert_eq!(
+                format!("{}", err),
+                "Malformed: Message isn't packed for storage"
+            );
+        }
+    }
+}
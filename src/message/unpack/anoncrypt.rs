@@ -1,25 +1,22 @@
-use askar_crypto::{
-    alg::{
-        aes::{A256CbcHs512, A256Gcm, A256Kw, AesKey},
-        chacha20::{Chacha20Key, XC20P},
-        p256::P256KeyPair,
-        x25519::X25519KeyPair,
-    },
-    kdf::ecdh_es::EcdhEs,
-};
-
+use super::{_check_enc_typ, _decrypted_payload_to_string, _missing_recipient_kids};
 use crate::{
     algorithms::AnonCryptAlg,
     error::{err_msg, ErrorKind, Result, ResultExt},
     jwe::{self, envelope::JWE},
     secrets::SecretsResolver,
-    utils::{
-        crypto::{AsKnownKeyPair, KnownKeyPair},
-        did::did_or_url,
-    },
+    utils::did::did_or_url,
     UnpackMetadata, UnpackOptions,
 };
 
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "unpack_anoncrypt",
+        level = "debug",
+        skip_all,
+        err(level = "debug")
+    )
+)]
 pub(crate) async fn _try_unpack_anoncrypt<'dr, 'sr>(
     msg: &str,
     secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
@@ -33,43 +30,42 @@ pub(crate) async fn _try_unpack_anoncrypt<'dr, 'sr>(
     };
 
     let mut buf = vec![];
-    let parsed_jwe = jwe.parse(&mut buf)?;
+    let parsed_jwe = jwe.parse_with(&mut buf, opts.lenient_base64)?;
 
     if parsed_jwe.protected.alg != jwe::Algorithm::EcdhEsA256kw {
         return Ok(None);
     }
 
-    let parsed_jwe = parsed_jwe.verify_didcomm()?;
+    parsed_jwe.verify_recipients()?;
+    parsed_jwe.verify_iv()?;
 
-    let to_kids: Vec<_> = parsed_jwe
-        .jwe
-        .recipients
-        .iter()
-        .map(|r| r.header.kid)
-        .collect();
+    let parsed_jwe = parsed_jwe.verify_didcomm()?;
+    _check_enc_typ(parsed_jwe.protected.typ, opts, metadata)?;
 
-    let to_kid = to_kids
-        .first()
-        .map(|&k| k)
-        .ok_or_else(|| err_msg(ErrorKind::Malformed, "No recipient keys found"))?;
+    let to_kids = parsed_jwe.to_kids();
 
-    let (to_did, _) = did_or_url(to_kid);
+    if to_kids.is_empty() {
+        Err(err_msg(ErrorKind::Malformed, "No recipient keys found"))?;
+    }
 
-    if let Some(_) = to_kids.iter().find(|k| {
-        let (k_did, k_url) = did_or_url(k);
-        (k_did != to_did) || (k_url.is_none())
-    }) {
+    // Anoncrypted message can be encrypted for keys of multiple dids (see `pack_encrypted_multi`)
+    if let Some(_) = to_kids.iter().find(|k| did_or_url(k).1.is_none()) {
         Err(err_msg(
             ErrorKind::Malformed,
-            "Recipient keys are outside of one did or can't be resolved to key agreement",
+            "Recipient keys can't be resolved to key agreement",
         ))?;
     }
 
     metadata.encrypted_to_kids = Some(to_kids.iter().map(|&k| k.to_owned()).collect());
     metadata.encrypted = true;
     metadata.anonymous_sender = true;
+    metadata.epk_jwk = Some(parsed_jwe.protected.epk.clone());
 
     let to_kids_found = secrets_resolver.find_secrets(&to_kids).await?;
+    metadata.missing_recipient_kids = _missing_recipient_kids(&to_kids, &to_kids_found);
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!(to_kids = ?to_kids, to_kids_found = ?to_kids_found, enc = ?parsed_jwe.protected.enc, "recipient keys resolved");
 
     if to_kids_found.is_empty() {
         Err(err_msg(
@@ -78,98 +74,64 @@ pub(crate) async fn _try_unpack_anoncrypt<'dr, 'sr>(
         ))?;
     }
 
-    let mut payload: Option<Vec<u8>> = None;
-
-    for to_kid in to_kids_found {
-        let to_key = secrets_resolver
-            .get_secret(to_kid)
-            .await?
-            .ok_or_else(|| {
-                err_msg(
-                    ErrorKind::InvalidState,
-                    "Recipient secret not found after existence checking",
-                )
-            })?
-            .as_key_pair()?;
-
-        let _payload = match (to_key, &parsed_jwe.protected.enc) {
-            (KnownKeyPair::X25519(ref to_key), jwe::EncAlgorithm::A256cbcHs512) => {
-                metadata.enc_alg_anon = Some(AnonCryptAlg::A256cbcHs512EcdhEsA256kw);
-
-                parsed_jwe.decrypt::<
-                        AesKey<A256CbcHs512>,
-                        EcdhEs<'_, X25519KeyPair>,
-                        X25519KeyPair,
-                        AesKey<A256Kw>,
-                    >(None, (to_kid, to_key))?
-            }
-            (KnownKeyPair::X25519(ref to_key), jwe::EncAlgorithm::Xc20P) => {
-                metadata.enc_alg_anon = Some(AnonCryptAlg::Xc20pEcdhEsA256kw);
-
-                parsed_jwe.decrypt::<
-                        Chacha20Key<XC20P>,
-                        EcdhEs<'_, X25519KeyPair>,
-                        X25519KeyPair,
-                        AesKey<A256Kw>,
-                    >(None, (to_kid, to_key))?
-            }
-            (KnownKeyPair::X25519(ref to_key), jwe::EncAlgorithm::A256Gcm) => {
-                metadata.enc_alg_anon = Some(AnonCryptAlg::A256gcmEcdhEsA256kw);
-
-                parsed_jwe.decrypt::<
-                        AesKey<A256Gcm>,
-                        EcdhEs<'_, X25519KeyPair>,
-                        X25519KeyPair,
-                        AesKey<A256Kw>,
-                    >(None, (to_kid, to_key))?
-            }
-            (KnownKeyPair::P256(ref to_key), jwe::EncAlgorithm::A256cbcHs512) => {
-                metadata.enc_alg_anon = Some(AnonCryptAlg::A256cbcHs512EcdhEsA256kw);
-
-                parsed_jwe.decrypt::<
-                        AesKey<A256CbcHs512>,
-                        EcdhEs<'_, P256KeyPair>,
-                        P256KeyPair,
-                        AesKey<A256Kw>,
-                    >(None, (to_kid, to_key))?
-            }
-            (KnownKeyPair::P256(ref to_key), jwe::EncAlgorithm::Xc20P) => {
-                metadata.enc_alg_anon = Some(AnonCryptAlg::Xc20pEcdhEsA256kw);
-
-                parsed_jwe.decrypt::<
-                        Chacha20Key<XC20P>,
-                        EcdhEs<'_, P256KeyPair>,
-                        P256KeyPair,
-                        AesKey<A256Kw>,
-                    >(None, (to_kid, to_key))?
-            }
-            (KnownKeyPair::P256(ref to_key), jwe::EncAlgorithm::A256Gcm) => {
-                metadata.enc_alg_anon = Some(AnonCryptAlg::A256gcmEcdhEsA256kw);
-
-                parsed_jwe.decrypt::<
-                        AesKey<A256Gcm>,
-                        EcdhEs<'_, P256KeyPair>,
-                        P256KeyPair,
-                        AesKey<A256Kw>,
-                    >(None, (to_kid, to_key))?
-            }
-            _ => Err(err_msg(
-                ErrorKind::Unsupported,
-                "Unsupported recipient key agreement method",
-            ))?,
-        };
+    // Secrets of all found keys are fetched in one call to allow backends to batch lookups.
+    // Resolvers without batching look up the keys concurrently (see `get_secrets`).
+    let to_secrets = secrets_resolver.get_secrets(&to_kids_found).await?;
 
-        payload = Some(_payload);
+    if to_secrets.len() != to_kids_found.len() {
+        Err(err_msg(
+            ErrorKind::InvalidState,
+            "Recipient secret not found after existence checking",
+        ))?;
+    }
 
-        if !opts.expect_decrypt_by_all_keys {
-            break;
+    // By default the first successful decryption wins and failures of other keys are ignored,
+    // otherwise all keys must decrypt the message.
+    let mut payload: Option<Vec<u8>> = None;
+    let mut decrypted_with_kid = None;
+    let mut last_err = None;
+
+    for to_secret in to_secrets {
+        let res = opts
+            .algorithms
+            .key_agreement_algorithm_for_secret(&to_secret)
+            .and_then(|algorithm| algorithm.decrypt(msg, &to_secret));
+
+        match res {
+            Ok(_payload) => {
+                payload = Some(_payload);
+                decrypted_with_kid.get_or_insert_with(|| to_secret.id.clone());
+
+                if !opts.expect_decrypt_by_all_keys {
+                    break;
+                }
+            }
+            Err(err) if opts.expect_decrypt_by_all_keys => Err(err)?,
+            Err(err) => last_err = Some(err),
         }
     }
 
-    let payload = payload.ok_or_else(|| err_msg(ErrorKind::InvalidState, "Payload is none"))?;
+    let payload = match (payload, last_err) {
+        (Some(payload), _) => payload,
+        (None, Some(err)) => Err(err)?,
+        (None, None) => Err(err_msg(ErrorKind::InvalidState, "Payload is none"))?,
+    };
+
+    metadata.decrypted_with_kid = decrypted_with_kid;
 
-    let payload = String::from_utf8(payload)
-        .kind(ErrorKind::Malformed, "Anoncrypt payload is invalid utf8")?;
+    if let Some(ref aad) = parsed_jwe.aad {
+        metadata.aad =
+            Some(String::from_utf8(aad.clone()).kind(ErrorKind::Malformed, "aad is invalid utf8")?);
+    }
+
+    metadata.enc_alg_anon = match parsed_jwe.protected.enc {
+        jwe::EncAlgorithm::A256cbcHs512 => Some(AnonCryptAlg::A256cbcHs512EcdhEsA256kw),
+        jwe::EncAlgorithm::Xc20P => Some(AnonCryptAlg::Xc20pEcdhEsA256kw),
+        jwe::EncAlgorithm::A256Gcm => Some(AnonCryptAlg::A256gcmEcdhEsA256kw),
+        jwe::EncAlgorithm::A128cbcHs256 => Some(AnonCryptAlg::A128cbcHs256EcdhEsA256kw),
+        jwe::EncAlgorithm::A128Gcm => Some(AnonCryptAlg::A128gcmEcdhEsA256kw),
+        jwe::EncAlgorithm::Other(_) => None,
+    };
 
-    Ok(Some(payload))
+    Ok(Some(_decrypted_payload_to_string(payload)?))
 }
@@ -1,26 +1,27 @@
-use askar_crypto::{
-    alg::{
-        aes::{A256CbcHs512, A256Kw, AesKey},
-        p256::P256KeyPair,
-        x25519::X25519KeyPair,
-    },
-    kdf::ecdh_1pu::Ecdh1PU,
-};
-
+use super::{_check_enc_typ, _decrypted_payload_to_string, _missing_recipient_kids};
 use crate::jwe::envelope::JWE;
 use crate::{
     algorithms::AuthCryptAlg,
-    did::DIDResolver,
+    did::{
+        did_resolver::{find_verification_method, resolve_key_directly},
+        DIDResolver, Purpose, VerificationMethod,
+    },
     error::{err_msg, ErrorKind, Result, ResultExt},
     jwe,
     secrets::SecretsResolver,
-    utils::{
-        crypto::{AsKnownKeyPair, KnownKeyPair},
-        did::did_or_url,
-    },
+    utils::did::{did_or_url, key_agreements_not_found},
     UnpackMetadata, UnpackOptions,
 };
 
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "unpack_authcrypt",
+        level = "debug",
+        skip_all,
+        err(level = "debug")
+    )
+)]
 pub(crate) async fn _try_unpack_authcrypt<'dr, 'sr>(
     msg: &str,
     did_resolver: &'dr (dyn DIDResolver + 'dr),
@@ -35,13 +36,17 @@ pub(crate) async fn _try_unpack_authcrypt<'dr, 'sr>(
     };
 
     let mut buf = vec![];
-    let parsed_jwe = jwe.parse(&mut buf)?;
+    let parsed_jwe = jwe.parse_with(&mut buf, opts.lenient_base64)?;
 
     if parsed_jwe.protected.alg != jwe::Algorithm::Ecdh1puA256kw {
         return Ok(None);
     }
 
+    parsed_jwe.verify_recipients()?;
+    parsed_jwe.verify_iv()?;
+
     let parsed_jwe = parsed_jwe.verify_didcomm()?;
+    _check_enc_typ(parsed_jwe.protected.typ, opts, metadata)?;
 
     let from_kid = std::str::from_utf8(
         parsed_jwe
@@ -60,36 +65,20 @@ pub(crate) async fn _try_unpack_authcrypt<'dr, 'sr>(
         ))?;
     }
 
-    let from_ddoc = did_resolver
-        .resolve(from_did)
+    let from_key = match resolve_key_directly(from_kid, Purpose::KeyAgreement, did_resolver)
         .await
-        .kind(ErrorKind::InvalidState, "Unable resolve sender did")?
-        .ok_or_else(|| err_msg(ErrorKind::DIDNotResolved, "Sender did not found"))?;
-
-    let from_kid = from_ddoc
-        .key_agreements
-        .iter()
-        .find(|&k| k.as_str() == from_kid)
-        .ok_or_else(|| err_msg(ErrorKind::DIDUrlNotFound, "Sender kid not found in did"))?;
+        .kind(ErrorKind::InvalidState, "Unable resolve sender key")?
+    {
+        Some(from_key) => from_key,
+        None => _sender_key(from_did, from_kid, did_resolver).await?,
+    };
 
-    let from_key = from_ddoc
-        .verification_methods
-        .iter()
-        .find(|&vm| &vm.id == from_kid)
-        .ok_or_else(|| {
-            err_msg(
-                ErrorKind::DIDUrlNotFound,
-                "Sender verification method not found in did",
-            )
-        })?
-        .as_key_pair()?;
+    let from_algorithm = opts
+        .algorithms
+        .key_agreement_algorithm_for_method(&from_key)
+        .ok_or_else(|| err_msg(ErrorKind::Unsupported, "Unsupported key type or curve"))?;
 
-    let to_kids: Vec<_> = parsed_jwe
-        .jwe
-        .recipients
-        .iter()
-        .map(|r| r.header.kid)
-        .collect();
+    let to_kids = parsed_jwe.to_kids();
 
     let to_kid = to_kids
         .first()
@@ -108,7 +97,10 @@ pub(crate) async fn _try_unpack_authcrypt<'dr, 'sr>(
         ))?;
     }
 
-    if metadata.encrypted_to_kids.is_none() {
+    // Recipient keys of anoncrypt envelope take precedence for protected sender
+    let is_outermost = metadata.encrypted_to_kids.is_none();
+
+    if is_outermost {
         metadata.encrypted_to_kids = Some(to_kids.iter().map(|&k| k.to_owned()).collect());
     } else {
         // TODO: Verify that same keys used for authcrypt as for anoncrypt envelope
@@ -117,9 +109,17 @@ pub(crate) async fn _try_unpack_authcrypt<'dr, 'sr>(
     metadata.authenticated = true;
     metadata.encrypted = true;
     metadata.encrypted_from_kid = Some(from_kid.into());
+    metadata.epk_jwk = Some(parsed_jwe.protected.epk.clone());
 
     let to_kids_found = secrets_resolver.find_secrets(&to_kids).await?;
 
+    if is_outermost {
+        metadata.missing_recipient_kids = _missing_recipient_kids(&to_kids, &to_kids_found);
+    }
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!(from_kid = %from_kid, to_kids = ?to_kids, to_kids_found = ?to_kids_found, enc = ?parsed_jwe.protected.enc, "sender and recipient keys resolved");
+
     if to_kids_found.is_empty() {
         Err(err_msg(
             ErrorKind::SecretNotFound,
@@ -130,62 +130,46 @@ pub(crate) async fn _try_unpack_authcrypt<'dr, 'sr>(
     let mut payload: Option<Vec<u8>> = None;
 
     for to_kid in to_kids_found {
-        let to_key = secrets_resolver
-            .get_secret(to_kid)
-            .await?
-            .ok_or_else(|| {
-                err_msg(
-                    ErrorKind::InvalidState,
-                    "Recipient secret not found after existence checking",
-                )
-            })?
-            .as_key_pair()?;
-
-        let _payload = match (&from_key, &to_key, &parsed_jwe.protected.enc) {
-            (
-                KnownKeyPair::X25519(ref from_key),
-                KnownKeyPair::X25519(ref to_key),
-                jwe::EncAlgorithm::A256cbcHs512,
-            ) => {
-                metadata.enc_alg_auth = Some(AuthCryptAlg::A256cbcHs512Ecdh1puA256kw);
-
-                parsed_jwe.decrypt::<
-                    AesKey<A256CbcHs512>,
-                    Ecdh1PU<'_, X25519KeyPair>,
-                    X25519KeyPair,
-                    AesKey<A256Kw>,
-                >(Some((from_kid, from_key)), (to_kid, to_key))?
-            }
-            (
-                KnownKeyPair::P256(ref from_key),
-                KnownKeyPair::P256(ref to_key),
-                jwe::EncAlgorithm::A256cbcHs512,
-            ) => {
-                metadata.enc_alg_auth = Some(AuthCryptAlg::A256cbcHs512Ecdh1puA256kw);
-
-                parsed_jwe.decrypt::<
-                    AesKey<A256CbcHs512>,
-                    Ecdh1PU<'_, P256KeyPair>,
-                    P256KeyPair,
-                    AesKey<A256Kw>,
-                >(Some((from_kid, from_key)), (to_kid, to_key))?
-            }
-            (KnownKeyPair::X25519(_), KnownKeyPair::P256(_), _) => Err(err_msg(
-                ErrorKind::Malformed,
-                "Incompatible sender and recipient key agreement curves",
-            ))?,
-            (KnownKeyPair::P256(_), KnownKeyPair::X25519(_), _) => Err(err_msg(
+        let to_key = secrets_resolver.get_secret(to_kid).await?.ok_or_else(|| {
+            err_msg(
+                ErrorKind::InvalidState,
+                "Recipient secret not found after existence checking",
+            )
+        })?;
+
+        let algorithm = opts
+            .algorithms
+            .key_agreement_algorithm_for_secret(&to_key)?;
+
+        // Sender and recipient keys are swapped or mixed up by some implementations
+        if from_algorithm.crv() != algorithm.crv() {
+            Err(err_msg(
                 ErrorKind::Malformed,
-                "Incompatible sender and recipient key agreement curves",
-            ))?,
+                format!(
+                    "Sender key curve {} does not match recipient key curve {}",
+                    from_algorithm.crv(),
+                    algorithm.crv()
+                ),
+            ))?
+        }
+
+        metadata.enc_alg_auth = Some(match parsed_jwe.protected.enc {
+            jwe::EncAlgorithm::A256cbcHs512 => AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            jwe::EncAlgorithm::A128cbcHs256 => AuthCryptAlg::A128cbcHs256Ecdh1puA256kw,
             _ => Err(err_msg(
                 ErrorKind::Unsupported,
                 "Unsupported key agreement method",
             ))?,
-        };
+        });
+
+        let _payload = algorithm.decrypt_authenticated(msg, &from_key, &to_key)?;
 
         payload = Some(_payload);
 
+        if metadata.decrypted_with_kid.is_none() {
+            metadata.decrypted_with_kid = Some(to_kid.to_owned());
+        }
+
         if !opts.expect_decrypt_by_all_keys {
             break;
         }
@@ -193,8 +177,39 @@ pub(crate) async fn _try_unpack_authcrypt<'dr, 'sr>(
 
     let payload = payload.ok_or_else(|| err_msg(ErrorKind::InvalidState, "Payload is none"))?;
 
-    let payload = String::from_utf8(payload)
-        .kind(ErrorKind::Malformed, "Authcrypt payload is invalid utf8")?;
+    if let Some(ref aad) = parsed_jwe.aad {
+        metadata.aad =
+            Some(String::from_utf8(aad.clone()).kind(ErrorKind::Malformed, "aad is invalid utf8")?);
+    }
+
+    Ok(Some(_decrypted_payload_to_string(payload)?))
+}
+
+/// Resolves sender DID Doc and looks for the sender `keyAgreement` key in it.
+async fn _sender_key<'dr>(
+    from_did: &str,
+    from_kid: &str,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+) -> Result<VerificationMethod> {
+    let from_ddoc = did_resolver
+        .resolve(from_did)
+        .await
+        .kind(ErrorKind::InvalidState, "Unable resolve sender did")?
+        .ok_or_else(|| err_msg(ErrorKind::DIDNotResolved, "Sender did not found"))?;
+
+    let from_kid = from_ddoc
+        .key_agreements
+        .iter()
+        .find(|&k| k.as_str() == from_kid)
+        .ok_or_else(|| key_agreements_not_found("Sender kid not found in did", &from_ddoc))?;
 
-    Ok(Some(payload))
+    find_verification_method(&from_ddoc, from_kid, did_resolver)
+        .await
+        .kind(ErrorKind::InvalidState, "Unable resolve sender key")?
+        .ok_or_else(|| {
+            err_msg(
+                ErrorKind::DIDUrlNotFound,
+                "Sender verification method not found in did",
+            )
+        })
 }
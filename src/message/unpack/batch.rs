@@ -0,0 +1,125 @@
+use futures::future::join_all;
+
+use crate::{
+    did::{did_resolver::CachingDIDResolver, DIDResolver},
+    error::Result,
+    secrets::SecretsResolver,
+    Message, UnpackMetadata, UnpackOptions,
+};
+
+impl Message {
+    /// Unpacks a batch of packed messages (for example messages pulled from a mediator).
+    ///
+    /// DID resolution results are cached and shared across all messages of the batch,
+    /// and messages are unpacked concurrently. Each message is unpacked the same way
+    /// as with `Message::unpack`.
+    ///
+    /// # Params
+    /// - `msgs` the messages as JSON strings to be unpacked
+    /// - `did_resolver` instance of `DIDResolver` to resolve DIDs
+    /// - `secrets_resolver` instance of SecretsResolver` to resolve sender DID keys secrets
+    /// - `options` allow fine configuration of unpacking process and imposing additional restrictions
+    /// to message to be trusted.
+    ///
+    /// # Returns
+    /// Vector of unpack results in the same order as `msgs`. Failure of a message
+    /// doesn't affect unpacking of other messages in the batch.
+    pub async fn unpack_batch<'dr, 'sr>(
+        msgs: &[&str],
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        options: &UnpackOptions,
+    ) -> Vec<Result<(Self, UnpackMetadata)>> {
+        let did_resolver = CachingDIDResolver::new(did_resolver);
+
+        join_all(
+            msgs.iter()
+                .map(|msg| Message::unpack(msg, &did_resolver, secrets_resolver, options)),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        did::resolvers::{ExampleDIDResolver, RecordingDIDResolver},
+        error::ErrorKind,
+        secrets::resolvers::ExampleSecretsResolver,
+        test_vectors::{
+            ALICE_DID, ALICE_DID_DOC, BOB_DID_DOC, BOB_SECRETS, CHARLIE_DID_DOC,
+            ENCRYPTED_MSG_ANON_XC20P_1, ENCRYPTED_MSG_AUTH_X25519, INVALID_PLAINTEXT_MSG_NO_ID,
+            MESSAGE_SIMPLE, PLAINTEXT_MSG_SIMPLE,
+        },
+        Message, UnpackOptions,
+    };
+
+    #[tokio::test]
+    async fn unpack_batch_works() {
+        let did_resolver = ExampleDIDResolver::new(vec![
+            ALICE_DID_DOC.clone(),
+            BOB_DID_DOC.clone(),
+            CHARLIE_DID_DOC.clone(),
+        ]);
+        let did_resolver = RecordingDIDResolver::new(&did_resolver);
+
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let res = Message::unpack_batch(
+            &[
+                ENCRYPTED_MSG_ANON_XC20P_1,
+                "not a message",
+                ENCRYPTED_MSG_AUTH_X25519,
+                INVALID_PLAINTEXT_MSG_NO_ID,
+                ENCRYPTED_MSG_AUTH_X25519,
+                PLAINTEXT_MSG_SIMPLE,
+            ],
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await;
+
+        assert_eq!(res.len(), 6);
+
+        let (msg, metadata) = res[0].as_ref().expect("unpack is ok.");
+        assert_eq!(msg, &*MESSAGE_SIMPLE);
+        assert!(metadata.anonymous_sender);
+
+        let err = res[1].as_ref().expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        let (msg, metadata) = res[2].as_ref().expect("unpack is ok.");
+        assert_eq!(msg, &*MESSAGE_SIMPLE);
+        assert!(metadata.authenticated);
+
+        let err = res[3].as_ref().expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        let (msg, _) = res[4].as_ref().expect("unpack is ok.");
+        assert_eq!(msg, &*MESSAGE_SIMPLE);
+
+        let (msg, metadata) = res[5].as_ref().expect("unpack is ok.");
+        assert_eq!(msg, &*MESSAGE_SIMPLE);
+        assert!(!metadata.encrypted);
+
+        // Sender DID is resolved once for the whole batch
+        assert_eq!(did_resolver.resolved_dids(), vec![ALICE_DID]);
+    }
+
+    #[tokio::test]
+    async fn unpack_batch_works_empty() {
+        let did_resolver = ExampleDIDResolver::new(vec![]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+
+        let res = Message::unpack_batch(
+            &[],
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await;
+
+        assert!(res.is_empty());
+    }
+}
@@ -0,0 +1,185 @@
+use crate::{
+    did::DIDResolver,
+    error::{err_msg, ErrorKind, Result, ResultExt},
+    secrets::SecretsResolver,
+    Message, PackedKind, UnpackMetadata, UnpackOptions,
+};
+
+/// Media type of encrypted messages (JWE).
+const ENCRYPTED_MEDIA_TYPE: &str = "application/didcomm-encrypted+json";
+
+/// Media type of signed messages (JWS).
+const SIGNED_MEDIA_TYPE: &str = "application/didcomm-signed+json";
+
+/// Media type of plaintext messages (JWM).
+const PLAINTEXT_MEDIA_TYPE: &str = "application/didcomm-plain+json";
+
+impl Message {
+    /// Unpacks the message received as HTTP request body checking it matches
+    /// the declared `Content-Type` (for ex. encrypted message declared as plaintext is rejected).
+    ///
+    /// # Params
+    /// - `content_type` value of the `Content-Type` header. Media type parameters
+    ///   (for ex. `; charset=utf-8`) are ignored.
+    /// - `body` HTTP request body
+    /// - `did_resolver` instance of `DIDResolver` to resolve DIDs
+    /// - `secrets_resolver` instance of SecretsResolver` to resolve sender DID keys secrets
+    /// - `options` allow fine configuration of unpacking process and imposing additional restrictions
+    /// to message to be trusted.
+    ///
+    /// # Returns
+    /// Tuple `(message, metadata)` same as `Message::unpack`.
+    ///
+    /// # Errors
+    /// - `Unsupported` content type isn't a DIDComm media type.
+    /// - `Malformed` body isn't valid UTF-8 or the message doesn't match the content type.
+    /// - Other errors same as `Message::unpack`.
+    pub async fn unpack_http<'dr, 'sr>(
+        content_type: &str,
+        body: &[u8],
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        options: &UnpackOptions,
+    ) -> Result<(Self, UnpackMetadata)> {
+        let media_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+
+        let expected_kinds: &[PackedKind] = match media_type.as_str() {
+            ENCRYPTED_MEDIA_TYPE => &[PackedKind::AnonEncrypted, PackedKind::AuthEncrypted],
+            SIGNED_MEDIA_TYPE => &[PackedKind::Signed],
+            PLAINTEXT_MEDIA_TYPE => &[PackedKind::Plaintext, PackedKind::Forward],
+            _ => Err(err_msg(
+                ErrorKind::Unsupported,
+                format!("Unsupported content type `{}`", content_type),
+            ))?,
+        };
+
+        let msg = std::str::from_utf8(body).kind(ErrorKind::Malformed, "Body is invalid utf8")?;
+        let kind = Message::peek(msg)?;
+
+        if !expected_kinds.contains(&kind) {
+            Err(err_msg(
+                ErrorKind::Malformed,
+                format!(
+                    "Content type `{}` doesn't match {:?} message",
+                    media_type, kind
+                ),
+            ))?
+        }
+
+        Message::unpack(msg, did_resolver, secrets_resolver, options).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        did::resolvers::ExampleDIDResolver,
+        error::ErrorKind,
+        secrets::resolvers::ExampleSecretsResolver,
+        test_vectors::{
+            ALICE_DID_DOC, BOB_DID_DOC, BOB_SECRETS, ENCRYPTED_MSG_ANON_XC20P_1,
+            ENCRYPTED_MSG_AUTH_X25519, MESSAGE_SIMPLE, PLAINTEXT_MSG_SIMPLE,
+            SIGNED_MSG_ALICE_KEY_1,
+        },
+        Message, UnpackOptions,
+    };
+
+    #[tokio::test]
+    async fn unpack_http_works() {
+        _unpack_http_works(
+            "application/didcomm-encrypted+json",
+            ENCRYPTED_MSG_ANON_XC20P_1,
+        )
+        .await;
+        _unpack_http_works(
+            "application/didcomm-encrypted+json",
+            ENCRYPTED_MSG_AUTH_X25519,
+        )
+        .await;
+        _unpack_http_works("application/didcomm-signed+json", SIGNED_MSG_ALICE_KEY_1).await;
+        _unpack_http_works("application/didcomm-plain+json", PLAINTEXT_MSG_SIMPLE).await;
+
+        _unpack_http_works(
+            "Application/DIDComm-Plain+JSON; charset=utf-8",
+            PLAINTEXT_MSG_SIMPLE,
+        )
+        .await;
+
+        async fn _unpack_http_works(content_type: &str, msg: &str) {
+            let (msg, _) = _unpack_http(content_type, msg.as_bytes())
+                .await
+                .expect("unpack_http is ok.");
+
+            assert_eq!(msg, *MESSAGE_SIMPLE);
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_http_works_content_type_mismatch() {
+        for (content_type, msg) in [
+            ("application/didcomm-plain+json", ENCRYPTED_MSG_ANON_XC20P_1),
+            ("application/didcomm-signed+json", ENCRYPTED_MSG_AUTH_X25519),
+            ("application/didcomm-encrypted+json", SIGNED_MSG_ALICE_KEY_1),
+            ("application/didcomm-encrypted+json", PLAINTEXT_MSG_SIMPLE),
+        ] {
+            let err = _unpack_http(content_type, msg.as_bytes())
+                .await
+                .expect_err("res is ok");
+
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+        }
+
+        let err = _unpack_http(
+            "application/didcomm-plain+json",
+            ENCRYPTED_MSG_ANON_XC20P_1.as_bytes(),
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(
+            format!("{}", err),
+            "Malformed: Content type `application/didcomm-plain+json` doesn't match AnonEncrypted message"
+        );
+    }
+
+    #[tokio::test]
+    async fn unpack_http_works_unsupported_content_type() {
+        let err = _unpack_http("application/json", PLAINTEXT_MSG_SIMPLE.as_bytes())
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+    }
+
+    #[tokio::test]
+    async fn unpack_http_works_invalid_utf8() {
+        let err = _unpack_http("application/didcomm-plain+json", &[0xff, 0xfe])
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+    }
+
+    async fn _unpack_http(
+        content_type: &str,
+        body: &[u8],
+    ) -> crate::error::Result<(Message, crate::UnpackMetadata)> {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        Message::unpack_http(
+            content_type,
+            body,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+    }
+}
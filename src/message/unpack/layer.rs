@@ -0,0 +1,220 @@
+use serde::Serialize;
+
+use super::{
+    anoncrypt::_try_unpack_anoncrypt, authcrypt::_try_unpack_authcrypt, sign::_try_unapck_sign,
+};
+use crate::{
+    did::{
+        did_resolver::{RelativeDIDUrlsResolver, TimeoutDIDResolver},
+        DIDResolver,
+    },
+    error::{err_msg, ErrorKind, Result, ResultExt},
+    protocols::routing::try_parse_forward,
+    secrets::SecretsResolver,
+    Message, PackedKind, UnpackMetadata, UnpackOptions,
+};
+
+/// Layer removed by `Message::unpack_one_layer`.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct LayerInfo {
+    /// Kind of the removed layer.
+    pub kind: PackedKind,
+
+    /// Metadata gathered while removing the layer (for ex. keys used for decryption
+    /// or signer key IDs). Trust flags are related to this layer only.
+    pub metadata: UnpackMetadata,
+}
+
+impl Message {
+    /// Removes exactly one layer of the packed message (decrypts one JWE, verifies one JWS
+    /// or unwraps plaintext `Forward`) and returns the inner packed message as is.
+    /// It is intended for diagnosing nested envelopes (for ex. protected sender messages where
+    /// authcrypted message is wrapped in anoncrypt). Use `Message::unpack` to get the plaintext.
+    ///
+    /// Restrictions of `options` related to the whole message (for ex. `require_encrypted`
+    /// or `allowed_senders`) aren't applied.
+    ///
+    /// # Params
+    /// - `msg` the packed message as JSON string
+    /// - `did_resolver` instance of `DIDResolver` to resolve DIDs
+    /// - `secrets_resolver` instance of SecretsResolver` to resolve recipient secrets
+    /// - `options` allow fine configuration of unpacking process
+    ///
+    /// # Returns
+    /// Tuple `(inner, layer)`.
+    /// - `inner` the inner packed message as JSON string
+    /// - `layer` kind of the removed layer and metadata about its unpacking
+    ///
+    /// # Errors
+    /// - `IllegalArgument` the message is a plaintext message that has no layers to remove.
+    /// - Other errors same as `Message::unpack`.
+    pub async fn unpack_one_layer<'dr, 'sr>(
+        msg: &str,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        options: &UnpackOptions,
+    ) -> Result<(String, LayerInfo)> {
+        let timeout_did_resolver = TimeoutDIDResolver::new(did_resolver, options.resolver_timeout);
+        let did_resolver: &dyn DIDResolver =
+            &RelativeDIDUrlsResolver::new(&timeout_did_resolver, options.strict_did_urls);
+
+        let kind = Message::peek(msg)?;
+        let mut metadata = UnpackMetadata::default();
+
+        let inner = match kind {
+            PackedKind::AnonEncrypted => {
+                _try_unpack_anoncrypt(msg, secrets_resolver, options, &mut metadata).await?
+            }
+            PackedKind::AuthEncrypted => {
+                _try_unpack_authcrypt(msg, did_resolver, secrets_resolver, options, &mut metadata)
+                    .await?
+            }
+            PackedKind::Signed => {
+                _try_unapck_sign(msg, did_resolver, options, &mut metadata).await?
+            }
+            PackedKind::Forward => {
+                let forward = Message::from_str(msg)
+                    .ok()
+                    .as_ref()
+                    .and_then(try_parse_forward)
+                    .ok_or_else(|| err_msg(ErrorKind::Malformed, "Invalid forward message"))?;
+
+                metadata.re_wrapped_in_forward = true;
+                metadata.re_wrapped_forward_next = Some(forward.next.clone());
+
+                Some(serde_json::to_string(&forward.forwarded_msg).kind(
+                    ErrorKind::InvalidState,
+                    "Unable serialize forwarded message",
+                )?)
+            }
+            PackedKind::Plaintext => Err(err_msg(
+                ErrorKind::IllegalArgument,
+                "Plaintext message has no layers to unpack",
+            ))?,
+        };
+
+        let inner = inner.ok_or_else(|| {
+            err_msg(
+                ErrorKind::InvalidState,
+                format!("Unable unpack {:?} layer", kind),
+            )
+        })?;
+
+        Ok((inner, LayerInfo { kind, metadata }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        did::resolvers::ExampleDIDResolver,
+        error::ErrorKind,
+        secrets::resolvers::ExampleSecretsResolver,
+        test_vectors::{
+            ALICE_DID, ALICE_DID_DOC, ALICE_SECRETS, BOB_DID, BOB_DID_DOC, BOB_SECRETS,
+            MESSAGE_SIMPLE, PLAINTEXT_MSG_SIMPLE,
+        },
+        Message, PackEncryptedOptions, PackedKind, UnpackOptions,
+    };
+
+    #[tokio::test]
+    async fn unpack_one_layer_works_protected_sender() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let (msg, _) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                Some(ALICE_DID),
+                None,
+                &did_resolver,
+                &alice_secrets_resolver,
+                &PackEncryptedOptions {
+                    protect_sender: true,
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("pack is ok.");
+
+        let (inner, layer) = Message::unpack_one_layer(
+            &msg,
+            &did_resolver,
+            &bob_secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack_one_layer is ok.");
+
+        assert_eq!(layer.kind, PackedKind::AnonEncrypted);
+        assert!(layer.metadata.encrypted);
+        assert!(layer.metadata.anonymous_sender);
+        assert!(!layer.metadata.authenticated);
+        assert_eq!(layer.metadata.encrypted_from_kid, None);
+
+        // Inner authcrypted message remains packed
+        assert_eq!(
+            Message::peek(&inner).expect("peek is ok."),
+            PackedKind::AuthEncrypted
+        );
+
+        let (inner, layer) = Message::unpack_one_layer(
+            &inner,
+            &did_resolver,
+            &bob_secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack_one_layer is ok.");
+
+        assert_eq!(layer.kind, PackedKind::AuthEncrypted);
+        assert!(layer.metadata.authenticated);
+        assert!(layer
+            .metadata
+            .encrypted_from_kid
+            .as_deref()
+            .expect("encrypted_from_kid is some.")
+            .starts_with(ALICE_DID));
+
+        assert_eq!(
+            Message::peek(&inner).expect("peek is ok."),
+            PackedKind::Plaintext
+        );
+
+        let err = Message::unpack_one_layer(
+            &inner,
+            &did_resolver,
+            &bob_secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+    }
+
+    #[tokio::test]
+    async fn unpack_one_layer_works_plaintext() {
+        let did_resolver = ExampleDIDResolver::new(vec![]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+
+        let err = Message::unpack_one_layer(
+            PLAINTEXT_MSG_SIMPLE,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        assert_eq!(
+            format!("{}", err),
+            "Illegal argument: Plaintext message has no layers to unpack"
+        );
+    }
+}
@@ -1,31 +1,47 @@
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use anoncrypt::_try_unpack_anoncrypt;
 use authcrypt::_try_unpack_authcrypt;
-use sign::_try_unapck_sign;
+use sign::{_try_unapck_sign, _try_unpack_outer_sign};
 
 use crate::message::unpack::plaintext::_try_unpack_plaintext;
 use crate::protocols::routing::try_parse_forward;
 use crate::utils::did::did_or_url;
 use crate::{
-    algorithms::{AnonCryptAlg, AuthCryptAlg, SignAlg},
-    did::DIDResolver,
-    error::{err_msg, ErrorKind, Result},
+    algorithms::{AlgorithmRegistry, AnonCryptAlg, AuthCryptAlg, SignAlg},
+    did::{
+        did_resolver::{
+            is_same_key, AtTimeDIDResolver, RelativeDIDUrlsResolver, TimeoutDIDResolver,
+        },
+        DIDResolver, VersionedDIDResolver,
+    },
+    error::{err_msg, ErrorKind, Result, ResultContext, ResultExt},
+    jws::JWS,
     secrets::SecretsResolver,
-    FromPrior, Message,
+    BodyValidators, FromPrior, Message, ReturnRoute,
 };
 
 mod anoncrypt;
 mod authcrypt;
+mod batch;
+mod http;
+mod layer;
 mod plaintext;
 mod sign;
 
+pub use layer::LayerInfo;
+
+pub(crate) use sign::_verify_jws;
+
 impl Message {
     /// Unpacks the packed message by doing decryption and verifying the signatures.
     /// This method supports all DID Comm message types (encrypted, signed, plaintext).
     ///
     /// If unpack options expect a particular property (for example that a message is encrypted)
-    /// and the packed message doesn't meet the criteria (it's not encrypted), then a MessageUntrusted
+    /// and the packed message doesn't meet the criteria (it's not encrypted), then an `Untrusted`
     /// error will be returned.
     ///
     /// # Params
@@ -48,7 +64,12 @@ impl Message {
     /// - `Unsupported` Used crypto or method is unsupported.
     /// - `SecretNotFound` No recipient secrets found.
     /// - `InvalidState` Indicates library error.
-    /// - `IOError` IO error during DID or secrets resolving.
+    /// - `IOError` IO error during DID or secrets resolving
+    ///   or DID resolver call timed out (see `options.resolver_timeout`).
+    /// - `Untrusted` Message isn't encrypted or signed as required by `options`.
+    ///
+    /// If the message was decrypted, but a later stage (for ex. signature verification) failed,
+    /// metadata gathered before the failure is available via `Error::partial_unpack_metadata`.
     /// TODO: verify and update errors list
     pub async fn unpack<'dr, 'sr>(
         msg: &str,
@@ -56,30 +77,141 @@ impl Message {
         secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
         options: &UnpackOptions,
     ) -> Result<(Self, UnpackMetadata)> {
+        Self::_unpack(msg, did_resolver, None, secrets_resolver, options).await
+    }
+
+    /// Unpacks the packed message the same way as `unpack`, but verifies signatures
+    /// (including `from_prior` one) against DID Doc versions that were valid at
+    /// the message's `created_time`. So a message signed by a key that was rotated out
+    /// of the sender's DID Doc after the message creation can still be verified.
+    ///
+    /// Encryption envelopes are opened with the current DID Doc versions as `created_time`
+    /// isn't known until the plaintext is decrypted. If the message doesn't have `created_time`
+    /// the current DID Doc versions are used for signatures as well.
+    ///
+    /// # Params
+    /// - `packed_msg` the message as JSON string to be unpacked
+    /// - `did_resolver` instance of `VersionedDIDResolver` to resolve DID Doc versions
+    /// - `secrets_resolver` instance of SecretsResolver` to resolve sender DID keys secrets
+    /// - `options` allow fine configuration of unpacking process and imposing additional restrictions
+    /// to message to be trusted.
+    ///
+    /// # Returns
+    /// Tuple `(message, metadata)` the same as `unpack`.
+    ///
+    /// # Errors
+    /// The same as `unpack`.
+    pub async fn unpack_versioned<'dr, 'sr>(
+        msg: &str,
+        did_resolver: &'dr (dyn VersionedDIDResolver + 'dr),
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        options: &UnpackOptions,
+    ) -> Result<(Self, UnpackMetadata)> {
+        Self::_unpack(
+            msg,
+            &AtTimeDIDResolver::new(did_resolver, None),
+            Some(did_resolver),
+            secrets_resolver,
+            options,
+        )
+        .await
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "unpack", level = "debug", skip_all, err(level = "debug"))
+    )]
+    async fn _unpack<'dr, 'vdr, 'sr>(
+        msg: &str,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        versioned_did_resolver: Option<&'vdr (dyn VersionedDIDResolver + 'vdr)>,
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        options: &UnpackOptions,
+    ) -> Result<(Self, UnpackMetadata)> {
+        if let Some(max_size) = options.max_message_bytes {
+            if msg.len() as u64 > max_size {
+                Err(err_msg(
+                    ErrorKind::TooLarge,
+                    format!(
+                        "Message size {} exceeds max allowed size {}",
+                        msg.len(),
+                        max_size
+                    ),
+                ))?
+            }
+        }
+
         let mut metadata = UnpackMetadata {
-            encrypted: false,
-            authenticated: false,
-            non_repudiation: false,
-            anonymous_sender: false,
-            re_wrapped_in_forward: false,
-            encrypted_from_kid: None,
-            encrypted_to_kids: None,
-            sign_from: None,
-            from_prior_issuer_kid: None,
-            enc_alg_auth: None,
-            enc_alg_anon: None,
-            sign_alg: None,
-            signed_message: None,
-            from_prior: None,
+            original_message: if options.retain_original {
+                Some(msg.to_owned())
+            } else {
+                None
+            },
+            ..UnpackMetadata::default()
+        };
+
+        let res = Self::_unpack_stages(
+            msg,
+            did_resolver,
+            versioned_did_resolver,
+            secrets_resolver,
+            options,
+            &mut metadata,
+        )
+        .await;
+
+        let msg = match res {
+            Ok(msg) => msg,
+            // Decryption succeeded, so metadata gathered so far is returned for debugging
+            Err(err) if metadata.decrypted_with_kid.is_some() => {
+                return Err(err.with_partial_unpack_metadata(metadata))
+            }
+            Err(err) => Err(err)?,
         };
 
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            encrypted = metadata.encrypted,
+            authenticated = metadata.authenticated,
+            non_repudiation = metadata.non_repudiation,
+            anonymous_sender = metadata.anonymous_sender,
+            re_wrapped_in_forward = metadata.re_wrapped_in_forward,
+            "message unpacked"
+        );
+
+        Ok((msg, metadata))
+    }
+
+    async fn _unpack_stages<'dr, 'vdr, 'sr>(
+        msg: &str,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+        versioned_did_resolver: Option<&'vdr (dyn VersionedDIDResolver + 'vdr)>,
+        secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+        options: &UnpackOptions,
+        metadata: &mut UnpackMetadata,
+    ) -> Result<Self> {
+        let timeout_did_resolver = TimeoutDIDResolver::new(did_resolver, options.resolver_timeout);
+        let did_resolver: &dyn DIDResolver =
+            &RelativeDIDUrlsResolver::new(&timeout_did_resolver, options.strict_did_urls);
+
         let mut msg: &str = msg;
+        let mut outer_signed: String;
         let mut anoncrypted: Option<String>;
         let mut forwarded_msg: String;
 
         loop {
-            anoncrypted =
-                _try_unpack_anoncrypt(&msg, secrets_resolver, options, &mut metadata).await?;
+            // Encrypted message signed by the sender (see `PackEncryptedOptions::sign_outside`)
+            // can be also re-wrapped in forward by a mediator
+            if !metadata.signed_outside {
+                if let Some(payload) =
+                    _try_unpack_outer_sign(msg, did_resolver, options, metadata).await?
+                {
+                    outer_signed = payload;
+                    msg = &outer_signed;
+                }
+            }
+
+            anoncrypted = _try_unpack_anoncrypt(&msg, secrets_resolver, options, metadata).await?;
 
             if options.unwrap_re_wrapping_forward && anoncrypted.is_some() {
                 if let Ok(plaintext) = Message::from_str(anoncrypted.as_deref().unwrap()) {
@@ -92,6 +224,15 @@ impl Message {
                         .await?
                         {
                             metadata.re_wrapped_in_forward = true;
+                            metadata.re_wrapped_forward_next = Some(forward_msg.next.clone());
+
+                            metadata.forward_chain.get_or_insert_with(Vec::new).push(
+                                ForwardHopInfo {
+                                    next: forward_msg.next.clone(),
+                                    decrypted_with_kid: metadata.decrypted_with_kid.clone(),
+                                    enc_alg_anon: metadata.enc_alg_anon.clone(),
+                                },
+                            );
 
                             forwarded_msg = serde_json::to_string(&forward_msg.forwarded_msg)?;
                             msg = &forwarded_msg;
@@ -108,14 +249,34 @@ impl Message {
         let msg = anoncrypted.as_deref().unwrap_or(msg);
 
         let authcrypted =
-            _try_unpack_authcrypt(msg, did_resolver, secrets_resolver, options, &mut metadata)
-                .await?;
+            _try_unpack_authcrypt(msg, did_resolver, secrets_resolver, options, metadata).await?;
         let msg = authcrypted.as_deref().unwrap_or(msg);
 
-        let signed = _try_unapck_sign(msg, did_resolver, options, &mut metadata).await?;
+        let at_created_time_did_resolver;
+        let timeout_at_created_time_did_resolver;
+        let relative_urls_at_created_time_did_resolver;
+
+        let did_resolver = match versioned_did_resolver {
+            Some(versioned_did_resolver) => {
+                at_created_time_did_resolver =
+                    AtTimeDIDResolver::new(versioned_did_resolver, _peek_created_time(msg));
+                timeout_at_created_time_did_resolver = TimeoutDIDResolver::new(
+                    &at_created_time_did_resolver,
+                    options.resolver_timeout,
+                );
+                relative_urls_at_created_time_did_resolver = RelativeDIDUrlsResolver::new(
+                    &timeout_at_created_time_did_resolver,
+                    options.strict_did_urls,
+                );
+                &relative_urls_at_created_time_did_resolver as &dyn DIDResolver
+            }
+            None => did_resolver,
+        };
+
+        let signed = _try_unapck_sign(msg, did_resolver, options, metadata).await?;
         let msg = signed.as_deref().unwrap_or(msg);
 
-        let msg = _try_unpack_plaintext(msg, did_resolver, &mut metadata)
+        let msg = _try_unpack_plaintext(msg, did_resolver, options, metadata)
             .await?
             .ok_or_else(|| {
                 err_msg(
@@ -124,14 +285,218 @@ impl Message {
                 )
             })?;
 
-        Ok((msg, metadata))
+        metadata.return_route = msg.return_route();
+
+        if let Some(from_kid) = metadata.encrypted_from_kid.as_deref() {
+            let sign_kids = metadata
+                .sign_from_kids
+                .iter()
+                .flatten()
+                .map(String::as_str)
+                .chain(metadata.sign_from.as_deref());
+
+            for sign_kid in sign_kids {
+                // It's a warning only, so resolving errors don't fail unpacking
+                if is_same_key(sign_kid, from_kid, did_resolver)
+                    .await
+                    .unwrap_or(false)
+                {
+                    metadata.signed_with_encryption_key = true;
+                    break;
+                }
+            }
+        }
+
+        if options.require_signer_matches_sender {
+            _check_signer_matches_sender(metadata)?;
+        }
+
+        if options.verify_to_matches_recipient {
+            _check_to_matches_recipient(&msg, did_resolver, secrets_resolver).await?;
+        }
+
+        if options.require_encrypted && !metadata.encrypted {
+            Err(err_msg(
+                ErrorKind::Untrusted,
+                "Message is not encrypted, but encryption is required",
+            ))?
+        }
+
+        if options.require_signed && !metadata.non_repudiation {
+            Err(err_msg(
+                ErrorKind::Untrusted,
+                "Message is not signed, but signature is required",
+            ))?
+        }
+
+        if let Some(ref allowed_senders) = options.allowed_senders {
+            _check_allowed_senders(
+                allowed_senders,
+                options.follow_also_known_as,
+                metadata,
+                did_resolver,
+            )
+            .await?;
+        }
+
+        Ok(msg)
+    }
+}
+
+/// Checks that all JWS signers belong to the DID of the authcrypt sender.
+fn _check_signer_matches_sender(metadata: &UnpackMetadata) -> Result<()> {
+    let from_kid = match metadata.encrypted_from_kid.as_deref() {
+        Some(from_kid) => from_kid,
+        None => return Ok(()),
+    };
+
+    let (from_did, _) = did_or_url(from_kid);
+
+    let sign_kids = metadata
+        .sign_from_kids
+        .iter()
+        .flatten()
+        .chain(metadata.sign_from.iter());
+
+    for sign_kid in sign_kids {
+        let (sign_did, _) = did_or_url(sign_kid);
+
+        if sign_did != from_did {
+            Err(err_msg(
+                ErrorKind::Untrusted,
+                format!(
+                    "Signer `{}` doesn't belong to the sender DID `{}`",
+                    sign_kid, from_did
+                ),
+            ))?
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the recipient holds key agreement secrets of some DID (or key) in plaintext `to`.
+/// Messages without `to` aren't checked.
+async fn _check_to_matches_recipient<'dr, 'sr>(
+    msg: &Message,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+) -> Result<()> {
+    let to = match msg.to {
+        Some(ref to) if !to.is_empty() => to,
+        _ => return Ok(()),
+    };
+
+    for did_or_kid in to {
+        if has_key_agreement_secret(did_or_kid, did_resolver, secrets_resolver).await? {
+            return Ok(());
+        }
+    }
+
+    Err(err_msg(
+        ErrorKind::Untrusted,
+        format!(
+            "Message is addressed to `{}`, but none of them is the recipient",
+            to.join(", ")
+        ),
+    ))?;
+
+    Ok(())
+}
+
+/// Checks that all authenticated senders (authcrypt and signature ones) are allowed.
+async fn _check_allowed_senders<'dr>(
+    allowed_senders: &[String],
+    follow_also_known_as: bool,
+    metadata: &UnpackMetadata,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+) -> Result<()> {
+    // All signers of multi-signature JWS are checked. Signers which signatures haven't been
+    // verified (see `UnpackOptions::skip_signature_verification`) can be forged, so they don't count.
+    let sign_kids = match metadata.sign_from_kids {
+        _ if metadata.signature_verification_skipped => vec![],
+        Some(ref sign_from_kids) => sign_from_kids.iter().collect::<Vec<_>>(),
+        None => metadata.sign_from.iter().collect(),
+    };
+
+    let mut senders: Vec<_> = metadata
+        .encrypted_from_kid
+        .iter()
+        .chain(sign_kids)
+        .map(|kid| did_or_url(kid).0)
+        .collect();
+
+    senders.dedup();
+
+    if senders.is_empty() {
+        Err(err_msg(
+            ErrorKind::Untrusted,
+            "Message sender is not authenticated, but allowed senders are restricted",
+        ))?
+    }
+
+    for sender in senders {
+        if allowed_senders.iter().any(|allowed| allowed == sender) {
+            continue;
+        }
+
+        if follow_also_known_as {
+            let mut linked = false;
+
+            for allowed in allowed_senders {
+                if _is_also_known_as(sender, allowed, did_resolver).await? {
+                    linked = true;
+                    break;
+                }
+            }
+
+            if linked {
+                continue;
+            }
+        }
+
+        Err(err_msg(
+            ErrorKind::Untrusted,
+            format!("Sender `{}` is not allowed", sender),
+        ))?
+    }
+
+    Ok(())
+}
+
+/// Checks that both DID Docs confirm `did` and `other_did` are the same subject with `alsoKnownAs`.
+async fn _is_also_known_as<'dr>(
+    did: &str,
+    other_did: &str,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+) -> Result<bool> {
+    let did_doc = did_resolver
+        .resolve(did)
+        .await
+        .context("Unable resolve sender did")?
+        .ok_or_else(|| err_msg(ErrorKind::DIDNotResolved, "Sender did not found"))?;
+
+    if !did_doc.also_known_as.iter().any(|aka| aka == other_did) {
+        return Ok(false);
     }
+
+    let other_did_doc = match did_resolver
+        .resolve(other_did)
+        .await
+        .context("Unable resolve allowed sender did")?
+    {
+        Some(other_did_doc) => other_did_doc,
+        None => return Ok(false),
+    };
+
+    Ok(other_did_doc.also_known_as.iter().any(|aka| aka == did))
 }
 
 /// Allows fine customization of unpacking process
 #[derive(Debug, PartialEq, Eq, Deserialize, Clone)]
 pub struct UnpackOptions {
     /// Whether the plaintext must be decryptable by all keys resolved by the secrets resolver. False by default.
+    /// Recipients listed in the message multiple times are tried once.
     #[serde(default)]
     pub expect_decrypt_by_all_keys: bool,
 
@@ -141,6 +506,150 @@ pub struct UnpackOptions {
     /// False by default.
     #[serde(default)]
     pub unwrap_re_wrapping_forward: bool,
+
+    /// If `true`, a signature that doesn't match the base64url encoded JWS payload is additionally
+    /// checked against the payload canonicalized according to JCS (RFC 8785).
+    /// Some implementations sign canonicalized plaintext instead of the transmitted payload.
+    /// False by default.
+    #[serde(default)]
+    pub accept_canonical_signatures: bool,
+
+    /// Maximum allowed sum of decoded sizes of base64 attachments in bytes.
+    /// Message with bigger attachments is rejected with `TooLarge` error. No limit by default.
+    /// Checked independently of `max_attachment_bytes`: json attachments aren't counted here,
+    /// and each attachment within this limit may still exceed `max_attachment_bytes`.
+    #[serde(default)]
+    pub max_total_attachment_size: Option<u64>,
+
+    /// Maximum allowed size of the packed message in bytes. Bigger message is rejected
+    /// with `TooLarge` error before any parsing or decryption. No limit by default.
+    #[serde(default)]
+    pub max_message_bytes: Option<u64>,
+
+    /// Maximum allowed size of each attachment data in bytes: decoded size of base64 data
+    /// (calculated without decoding) and serialized size of json data.
+    /// Message with bigger attachment is rejected with `TooLarge` error. No limit by default.
+    /// Checked independently of `max_total_attachment_size`, so both limits must be met.
+    #[serde(default)]
+    pub max_attachment_bytes: Option<u64>,
+
+    /// Maximum allowed amount of signatures in JWS. JWS with more signatures is considered
+    /// malformed and rejected before any signature is verified. No limit by default.
+    #[serde(default)]
+    pub max_signatures: Option<u64>,
+
+    /// If set, JWS is accepted if at least this amount of its signatures are valid
+    /// (for ex. M-of-N signed governance messages). Invalid signatures and signatures of unresolvable
+    /// signers are ignored and not reported in `UnpackMetadata::sign_from_kids`.
+    /// Otherwise all signatures must be valid. Signatures are verified concurrently. None by default.
+    #[serde(default)]
+    pub min_valid_signatures: Option<u64>,
+
+    /// If `true`, base64 values of encrypted message (JWE) fields are also accepted with padding
+    /// and with characters of standard base64 alphabet. Some non-conformant senders produce such values.
+    /// False by default, so only unpadded base64url values are accepted.
+    #[serde(default)]
+    pub lenient_base64: bool,
+
+    /// Maximum duration of each `DIDResolver` call. Calls that don't complete in time
+    /// fail unpacking with `IOError`. Milliseconds if deserialized. No limit by default.
+    #[serde(default, deserialize_with = "crate::utils::serde::_duration_ms")]
+    pub resolver_timeout: Option<Duration>,
+
+    /// If `true`, the packed message is returned as received in `UnpackMetadata::original_message`,
+    /// so it can be persisted for audit or non-repudiation purposes (for ex. a signed outer JWE).
+    /// False by default.
+    #[serde(default)]
+    pub retain_original: bool,
+
+    /// If `true`, unpacking fails with `Untrusted` error if the message isn't encrypted.
+    /// False by default.
+    #[serde(default)]
+    pub require_encrypted: bool,
+
+    /// If `true`, unpacking fails with `Untrusted` error if the message isn't signed
+    /// (doesn't provide non-repudiation). False by default.
+    #[serde(default)]
+    pub require_signed: bool,
+
+    /// If `true`, DID Docs using relative DID URLs (for ex. `#key-1`) for verification methods
+    /// are rejected with `Malformed` error. Otherwise relative DID URLs are expanded against the DID.
+    /// False by default.
+    #[serde(default)]
+    pub strict_did_urls: bool,
+
+    /// Plaintext `typ` values accepted in addition to the standard `application/didcomm-plain+json`
+    /// (for ex. profile specific media types). Messages with other `typ` values are rejected
+    /// with `Malformed` error. None by default.
+    #[serde(default)]
+    pub accepted_plaintext_typ: Option<Vec<String>>,
+
+    /// If `true`, `json` attachment data embedded as a string containing JSON object or array
+    /// (stringified JSON) is parsed into the embedded JSON. Some non-conformant senders produce such data.
+    /// False by default, so such attachments are rejected with `Malformed` error
+    /// as it is ambiguous whether the data is JSON or a string.
+    #[serde(default)]
+    pub lenient_json_attachments: bool,
+
+    /// If `true`, `created_time` and `expires_time` plaintext headers are also accepted as strings
+    /// containing UNIX timestamps (for ex. `"1516269022"`) and normalized to numbers.
+    /// Some non-conformant senders produce such headers.
+    /// False by default, so only numeric timestamps are accepted as required by the spec.
+    #[serde(default)]
+    pub lenient_timestamps: bool,
+
+    /// DIDs of senders allowed to send messages. If set, messages with an authenticated
+    /// (authcrypted or signed) sender not in the list, as well as not authenticated messages,
+    /// are rejected with `Untrusted` error. None by default, so any sender is accepted.
+    #[serde(default)]
+    pub allowed_senders: Option<Vec<String>>,
+
+    /// If `true`, a sender not in `allowed_senders` is also accepted if its DID is linked
+    /// with an allowed DID by `alsoKnownAs` (https://www.w3.org/TR/did-core/#also-known-as).
+    /// As `alsoKnownAs` is just a claim, the link must be confirmed by both DID Docs:
+    /// the sender's one must list the allowed DID and the allowed DID's one must list the sender.
+    /// False by default.
+    #[serde(default)]
+    pub follow_also_known_as: bool,
+
+    /// INSECURE! If `true`, signatures of signed messages are NOT cryptographically verified.
+    /// JWS is only parsed, `sign_from` and `sign_from_kids` are populated from JWS headers
+    /// and `UnpackMetadata::signature_verification_skipped` is set. Anyone can forge such
+    /// a message, so `authenticated` and `non_repudiation` aren't set by the signature
+    /// and `sign_from` must not be trusted. Use it only if the transport already guarantees
+    /// authenticity (for ex. mutual TLS to a trusted mediator). False by default.
+    #[serde(default)]
+    pub skip_signature_verification: bool,
+
+    /// If `true`, messages both authcrypted and signed are rejected with `Untrusted` error
+    /// if a JWS signer DID differs from the authcrypt sender DID (`encrypted_from_kid`),
+    /// so a relay can't attach someone else's signature. Messages that aren't both
+    /// authcrypted and signed aren't affected. False by default.
+    #[serde(default)]
+    pub require_signer_matches_sender: bool,
+
+    /// If set, encrypted messages (each JWE layer) with protected `typ` header other than
+    /// the given value (for ex. `application/didcomm-encrypted+json`) are rejected
+    /// with `Malformed` error. None by default, so `typ` isn't checked.
+    #[serde(default)]
+    pub expected_enc_typ: Option<String>,
+
+    /// If `true`, messages with plaintext `to` header not listing any DID (or key ID)
+    /// the recipient holds key agreement secrets for are rejected with `Untrusted` error,
+    /// as such messages are likely misrouted. Messages without `to` aren't affected.
+    /// False by default.
+    #[serde(default)]
+    pub verify_to_matches_recipient: bool,
+
+    /// Signature and key agreement algorithms consulted before built-in ones
+    /// on signatures verification and decryption. No registered algorithms by default.
+    #[serde(skip)]
+    pub algorithms: AlgorithmRegistry,
+
+    /// Validators of plaintext message `body` per message `type` consulted before built-in ones.
+    /// No registered validators by default.
+    #[serde(skip)]
+    pub body_validators: BodyValidators,
 }
 
 impl Default for UnpackOptions {
@@ -148,13 +657,38 @@ impl Default for UnpackOptions {
         UnpackOptions {
             expect_decrypt_by_all_keys: false,
             unwrap_re_wrapping_forward: true,
+            accept_canonical_signatures: false,
+            max_total_attachment_size: None,
+            max_message_bytes: None,
+            max_attachment_bytes: None,
+            max_signatures: None,
+            min_valid_signatures: None,
+            lenient_base64: false,
+            resolver_timeout: None,
+            retain_original: false,
+            require_encrypted: false,
+            require_signed: false,
+            strict_did_urls: false,
+            accepted_plaintext_typ: None,
+            lenient_json_attachments: false,
+            lenient_timestamps: false,
+            allowed_senders: None,
+            follow_also_known_as: false,
+            skip_signature_verification: false,
+            require_signer_matches_sender: false,
+            expected_enc_typ: None,
+            verify_to_matches_recipient: false,
+            algorithms: AlgorithmRegistry::default(),
+            body_validators: BodyValidators::default(),
         }
     }
 }
 
 /// Additional metadata about this `unpack` method execution like trust predicates
 /// and used keys identifiers.
-#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+/// Default value has all flags false and all optional fields None (for ex. for mocks in tests),
+/// see also `UnpackMetadata::build`.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize)]
 pub struct UnpackMetadata {
     /// Whether the plaintext has been encrypted
     pub encrypted: bool,
@@ -165,21 +699,44 @@ pub struct UnpackMetadata {
     /// Whether the plaintext has been signed
     pub non_repudiation: bool,
 
-    /// Whether the sender ID was hidden or protected
+    /// Whether the sender ID was hidden or protected.
+    /// For protected sender (authcrypt wrapped in anoncrypt) both `anonymous_sender`
+    /// and `authenticated` are true and `encrypted_from_kid` is the authenticated inner sender.
     pub anonymous_sender: bool,
 
     /// Whether the plaintext was re-wrapped in a forward message by a mediator
     pub re_wrapped_in_forward: bool,
 
+    /// `next` of the re-wrapping forward message (DID or key ID) matched to the recipient keys
+    /// if the plaintext was re-wrapped in forward. Forward messages which `next` doesn't match
+    /// the recipient keys aren't unwrapped and are returned as is.
+    pub re_wrapped_forward_next: Option<String>,
+
+    /// Re-wrapping forward messages unwrapped for the recipient in order from the outermost one
+    /// if the plaintext was re-wrapped in forward (possibly multiple times).
+    pub forward_chain: Option<Vec<ForwardHopInfo>>,
+
+    /// Whether the encrypted message has been signed (encrypt-then-sign, see `PackEncryptedOptions::sign_outside`)
+    /// instead of the plaintext being signed before encryption
+    pub signed_outside: bool,
+
     /// Key ID of the sender used for authentication encryption if the plaintext has been authenticated and encrypted
     pub encrypted_from_kid: Option<String>,
 
     /// Target key IDS for encryption if the plaintext has been encrypted
     pub encrypted_to_kids: Option<Vec<String>>,
 
+    /// Key ID of the recipient secret that actually decrypted the message if the plaintext
+    /// has been encrypted. If the sender is protected, the key decrypted anoncrypt envelope is used.
+    pub decrypted_with_kid: Option<String>,
+
     /// Key ID used for signature if the plaintext has been signed
     pub sign_from: Option<String>,
 
+    /// Key IDs of all valid signatures if the plaintext has been signed
+    /// (multiple for multi-signature JWS). `sign_from` is the first of them.
+    pub sign_from_kids: Option<Vec<String>>,
+
     /// Key ID used for from_prior header signature if from_prior header is present
     pub from_prior_issuer_kid: Option<String>,
 
@@ -189,7 +746,8 @@ pub struct UnpackMetadata {
     /// Algorithm used for anonymous encryption
     pub enc_alg_anon: Option<AnonCryptAlg>,
 
-    /// Algorithm used for message signing
+    /// Algorithm used for message signing.
+    /// None for signature algorithms registered by application (see `AlgorithmRegistry`).
     pub sign_alg: Option<SignAlg>,
 
     /// If the plaintext has been signed, the JWS is returned for non-repudiation purposes
@@ -197,97 +755,387 @@ pub struct UnpackMetadata {
 
     /// If plaintext contains from_prior header, its unpacked value is returned
     pub from_prior: Option<FromPrior>,
-}
 
-async fn has_key_agreement_secret<'dr, 'sr>(
-    did_or_kid: &str,
-    did_resolver: &'dr (dyn DIDResolver + 'dr),
-    secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
-) -> Result<bool> {
-    let kids = match did_or_url(did_or_kid) {
-        (_, Some(kid)) => {
-            vec![kid.to_owned()]
-        }
-        (did, None) => {
-            let did_doc = did_resolver
-                .resolve(did)
-                .await?
-                .ok_or_else(|| err_msg(ErrorKind::DIDNotResolved, "Next DID doc not found"))?;
-            did_doc.key_agreements
-        }
-    };
+    /// Ephemeral public key (as JWK) of the innermost JWE if the plaintext has been encrypted.
+    /// Can be used to bind subsequent traffic to the key agreement of this message.
+    pub epk_jwk: Option<Value>,
 
-    let kids = kids.iter().map(|k| k as &str).collect::<Vec<_>>();
+    /// The packed message exactly as received if `UnpackOptions::retain_original` is set
+    pub original_message: Option<String>,
 
-    let secrets_ids = secrets_resolver.find_secrets(&kids[..]).await?;
+    /// Additional authenticated data (JWE `aad`) of the encrypted message if present,
+    /// see `PackEncryptedOptions::aad`
+    pub aad: Option<String>,
 
-    return Ok(!secrets_ids.is_empty());
-}
+    /// Whether the message is signed and authcrypted with the same key material (for ex. the same
+    /// P-256 key declared for both authentication and key agreement). Such key reuse is discouraged,
+    /// so it's a warning for the recipient, the message is unpacked anyway.
+    pub signed_with_encryption_key: bool,
 
-#[cfg(test)]
-mod test {
-    use crate::{
-        did::resolvers::ExampleDIDResolver,
-        message::MessagingServiceMetadata,
-        protocols::routing::wrap_in_forward,
-        secrets::resolvers::ExampleSecretsResolver,
-        test_vectors::{
-            remove_field, remove_protected_field, update_field, update_protected_field,
-            ALICE_AUTH_METHOD_25519, ALICE_AUTH_METHOD_P256, ALICE_AUTH_METHOD_SECPP256K1,
-            ALICE_DID, ALICE_DID_DOC, ALICE_SECRETS, ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
-            ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519, BOB_DID, BOB_DID_COMM_MESSAGING_SERVICE,
-            BOB_DID_DOC, BOB_SECRETS, BOB_SECRET_KEY_AGREEMENT_KEY_P256_1,
-            BOB_SECRET_KEY_AGREEMENT_KEY_P256_2, BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1,
-            BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2, BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3,
-            BOB_SERVICE, CHARLIE_AUTH_METHOD_25519, CHARLIE_DID_DOC, ENCRYPTED_MSG_ANON_XC20P_1,
-            ENCRYPTED_MSG_ANON_XC20P_2, ENCRYPTED_MSG_AUTH_P256, ENCRYPTED_MSG_AUTH_P256_SIGNED,
-            ENCRYPTED_MSG_AUTH_X25519, FROM_PRIOR_FULL,
-            INVALID_ENCRYPTED_MSG_ANON_P256_EPK_WRONG_POINT,
-            INVALID_PLAINTEXT_MSG_ATTACHMENTS_AS_INT_ARRAY,
-            INVALID_PLAINTEXT_MSG_ATTACHMENTS_AS_STRING,
-            INVALID_PLAINTEXT_MSG_ATTACHMENTS_EMPTY_DATA,
-            INVALID_PLAINTEXT_MSG_ATTACHMENTS_LINKS_NO_HASH,
-            INVALID_PLAINTEXT_MSG_ATTACHMENTS_NO_DATA, INVALID_PLAINTEXT_MSG_ATTACHMENTS_NULL_DATA,
-            INVALID_PLAINTEXT_MSG_ATTACHMENTS_WRONG_DATA,
-            INVALID_PLAINTEXT_MSG_ATTACHMENTS_WRONG_ID, INVALID_PLAINTEXT_MSG_EMPTY,
-            INVALID_PLAINTEXT_MSG_EMPTY_ATTACHMENTS, INVALID_PLAINTEXT_MSG_NO_BODY,
-            INVALID_PLAINTEXT_MSG_NO_ID, INVALID_PLAINTEXT_MSG_NO_TYP,
-            INVALID_PLAINTEXT_MSG_NO_TYPE, INVALID_PLAINTEXT_MSG_STRING,
-            INVALID_PLAINTEXT_MSG_WRONG_TYP, MEDIATOR1_DID_DOC, MEDIATOR1_SECRETS,
-            MESSAGE_ATTACHMENT_BASE64, MESSAGE_ATTACHMENT_JSON, MESSAGE_ATTACHMENT_LINKS,
-            MESSAGE_ATTACHMENT_MULTI_1, MESSAGE_ATTACHMENT_MULTI_2, MESSAGE_FROM_PRIOR_FULL,
-            MESSAGE_MINIMAL, MESSAGE_SIMPLE, PLAINTEXT_FROM_PRIOR,
-            PLAINTEXT_FROM_PRIOR_INVALID_SIGNATURE, PLAINTEXT_INVALID_FROM_PRIOR,
-            PLAINTEXT_MSG_ATTACHMENT_BASE64, PLAINTEXT_MSG_ATTACHMENT_JSON,
-            PLAINTEXT_MSG_ATTACHMENT_LINKS, PLAINTEXT_MSG_ATTACHMENT_MULTI_1,
-            PLAINTEXT_MSG_ATTACHMENT_MULTI_2, PLAINTEXT_MSG_MINIMAL, PLAINTEXT_MSG_SIMPLE,
-            SIGNED_MSG_ALICE_KEY_1, SIGNED_MSG_ALICE_KEY_2, SIGNED_MSG_ALICE_KEY_3,
-        },
-        PackEncryptedOptions,
-    };
+    /// Target key IDs of the encrypted message the recipient has no secrets for.
+    /// The message is decrypted with the available keys (unless `expect_decrypt_by_all_keys`
+    /// requires otherwise), so it's a warning that some recipient keys aren't held by this agent.
+    /// None if all recipient keys are available or the plaintext hasn't been encrypted.
+    pub missing_recipient_kids: Option<Vec<String>>,
 
-    use super::*;
+    /// Whether signatures of the signed message haven't been verified
+    /// (see `UnpackOptions::skip_signature_verification`), so `sign_from` isn't trusted.
+    pub signature_verification_skipped: bool,
 
-    #[tokio::test]
-    async fn unpack_works_plaintext() {
-        let plaintext_metadata = UnpackMetadata {
-            anonymous_sender: false,
-            authenticated: false,
-            non_repudiation: false,
-            encrypted: false,
-            enc_alg_auth: None,
-            enc_alg_anon: None,
-            sign_alg: None,
-            encrypted_from_kid: None,
-            encrypted_to_kids: None,
-            sign_from: None,
-            signed_message: None,
-            from_prior_issuer_kid: None,
-            from_prior: None,
-            re_wrapped_in_forward: false,
-        };
+    /// Protected `typ` header of the innermost JWE if the plaintext has been encrypted
+    /// and the header is present (see `UnpackOptions::expected_enc_typ`).
+    pub enc_typ: Option<String>,
 
-        _verify_unpack(PLAINTEXT_MSG_SIMPLE, &MESSAGE_SIMPLE, &plaintext_metadata).await;
+    /// Value of plaintext `return_route` header if present, so the transport can keep
+    /// the inbound connection open for responses (see `ReturnRoute::allows_response`).
+    pub return_route: Option<ReturnRoute>,
+}
+
+/// Re-wrapping forward message unwrapped by `unpack`, see `UnpackMetadata::forward_chain`.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct ForwardHopInfo {
+    /// `next` of the forward message (DID or key ID) matched to the recipient keys
+    pub next: String,
+
+    /// Key ID of the recipient secret that decrypted the forward message
+    pub decrypted_with_kid: Option<String>,
+
+    /// Algorithm used for anonymous encryption of the forward message
+    pub enc_alg_anon: Option<AnonCryptAlg>,
+}
+
+impl UnpackMetadata {
+    /// Builder of metadata with all flags false and all optional fields None by default.
+    /// Intended for test harnesses and mocks of components consuming `UnpackMetadata`.
+    pub fn build() -> UnpackMetadataBuilder {
+        UnpackMetadataBuilder {
+            metadata: UnpackMetadata::default(),
+        }
+    }
+}
+
+pub struct UnpackMetadataBuilder {
+    metadata: UnpackMetadata,
+}
+
+impl UnpackMetadataBuilder {
+    pub fn encrypted(mut self, encrypted: bool) -> Self {
+        self.metadata.encrypted = encrypted;
+        self
+    }
+
+    pub fn authenticated(mut self, authenticated: bool) -> Self {
+        self.metadata.authenticated = authenticated;
+        self
+    }
+
+    pub fn non_repudiation(mut self, non_repudiation: bool) -> Self {
+        self.metadata.non_repudiation = non_repudiation;
+        self
+    }
+
+    pub fn anonymous_sender(mut self, anonymous_sender: bool) -> Self {
+        self.metadata.anonymous_sender = anonymous_sender;
+        self
+    }
+
+    pub fn re_wrapped_in_forward(mut self, re_wrapped_in_forward: bool) -> Self {
+        self.metadata.re_wrapped_in_forward = re_wrapped_in_forward;
+        self
+    }
+
+    pub fn re_wrapped_forward_next(mut self, re_wrapped_forward_next: String) -> Self {
+        self.metadata.re_wrapped_forward_next = Some(re_wrapped_forward_next);
+        self
+    }
+
+    pub fn forward_chain(mut self, forward_chain: Vec<ForwardHopInfo>) -> Self {
+        self.metadata.forward_chain = Some(forward_chain);
+        self
+    }
+
+    pub fn signed_outside(mut self, signed_outside: bool) -> Self {
+        self.metadata.signed_outside = signed_outside;
+        self
+    }
+
+    pub fn encrypted_from_kid(mut self, encrypted_from_kid: String) -> Self {
+        self.metadata.encrypted_from_kid = Some(encrypted_from_kid);
+        self
+    }
+
+    pub fn encrypted_to_kids(mut self, encrypted_to_kids: Vec<String>) -> Self {
+        self.metadata.encrypted_to_kids = Some(encrypted_to_kids);
+        self
+    }
+
+    pub fn decrypted_with_kid(mut self, decrypted_with_kid: String) -> Self {
+        self.metadata.decrypted_with_kid = Some(decrypted_with_kid);
+        self
+    }
+
+    pub fn sign_from(mut self, sign_from: String) -> Self {
+        self.metadata.sign_from = Some(sign_from);
+        self
+    }
+
+    pub fn sign_from_kids(mut self, sign_from_kids: Vec<String>) -> Self {
+        self.metadata.sign_from_kids = Some(sign_from_kids);
+        self
+    }
+
+    pub fn from_prior_issuer_kid(mut self, from_prior_issuer_kid: String) -> Self {
+        self.metadata.from_prior_issuer_kid = Some(from_prior_issuer_kid);
+        self
+    }
+
+    pub fn enc_alg_auth(mut self, enc_alg_auth: AuthCryptAlg) -> Self {
+        self.metadata.enc_alg_auth = Some(enc_alg_auth);
+        self
+    }
+
+    pub fn enc_alg_anon(mut self, enc_alg_anon: AnonCryptAlg) -> Self {
+        self.metadata.enc_alg_anon = Some(enc_alg_anon);
+        self
+    }
+
+    pub fn sign_alg(mut self, sign_alg: SignAlg) -> Self {
+        self.metadata.sign_alg = Some(sign_alg);
+        self
+    }
+
+    pub fn signed_message(mut self, signed_message: String) -> Self {
+        self.metadata.signed_message = Some(signed_message);
+        self
+    }
+
+    pub fn from_prior(mut self, from_prior: FromPrior) -> Self {
+        self.metadata.from_prior = Some(from_prior);
+        self
+    }
+
+    pub fn epk_jwk(mut self, epk_jwk: Value) -> Self {
+        self.metadata.epk_jwk = Some(epk_jwk);
+        self
+    }
+
+    pub fn original_message(mut self, original_message: String) -> Self {
+        self.metadata.original_message = Some(original_message);
+        self
+    }
+
+    pub fn aad(mut self, aad: String) -> Self {
+        self.metadata.aad = Some(aad);
+        self
+    }
+
+    pub fn signed_with_encryption_key(mut self, signed_with_encryption_key: bool) -> Self {
+        self.metadata.signed_with_encryption_key = signed_with_encryption_key;
+        self
+    }
+
+    pub fn missing_recipient_kids(mut self, missing_recipient_kids: Vec<String>) -> Self {
+        self.metadata.missing_recipient_kids = Some(missing_recipient_kids);
+        self
+    }
+
+    pub fn signature_verification_skipped(mut self, signature_verification_skipped: bool) -> Self {
+        self.metadata.signature_verification_skipped = signature_verification_skipped;
+        self
+    }
+
+    pub fn enc_typ(mut self, enc_typ: String) -> Self {
+        self.metadata.enc_typ = Some(enc_typ);
+        self
+    }
+
+    pub fn return_route(mut self, return_route: ReturnRoute) -> Self {
+        self.metadata.return_route = Some(return_route);
+        self
+    }
+
+    pub fn finalize(self) -> UnpackMetadata {
+        self.metadata
+    }
+}
+
+/// Converts decrypted JWE payload to string. Payload that isn't valid UTF-8 (for ex. corrupted
+/// plaintext) is reported explicitly instead of failing later with a confusing JSON parsing error.
+fn _decrypted_payload_to_string(payload: Vec<u8>) -> Result<String> {
+    String::from_utf8(payload).kind(ErrorKind::Malformed, "Decrypted payload is not valid UTF-8")
+}
+
+/// Records protected `typ` header of JWE and checks it against `UnpackOptions::expected_enc_typ`.
+fn _check_enc_typ(
+    typ: Option<&str>,
+    opts: &UnpackOptions,
+    metadata: &mut UnpackMetadata,
+) -> Result<()> {
+    metadata.enc_typ = typ.map(str::to_owned);
+
+    match opts.expected_enc_typ {
+        Some(ref expected) if typ != Some(expected.as_str()) => Err(err_msg(
+            ErrorKind::Malformed,
+            format!(
+                "Unexpected encrypted message `typ` {}, expected `{}`",
+                typ.map(|typ| format!("`{}`", typ))
+                    .unwrap_or_else(|| "(none)".to_owned()),
+                expected
+            ),
+        ))?,
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Returns target key IDs without found secrets or None if secrets for all keys are found.
+fn _missing_recipient_kids(to_kids: &[&str], to_kids_found: &[&str]) -> Option<Vec<String>> {
+    let missing: Vec<String> = to_kids
+        .iter()
+        .filter(|k| !to_kids_found.contains(k))
+        .map(|&k| k.to_owned())
+        .collect();
+
+    if missing.is_empty() {
+        None
+    } else {
+        Some(missing)
+    }
+}
+
+/// Returns `created_time` of signed or plaintext message without verification.
+fn _peek_created_time(msg: &str) -> Option<u64> {
+    let plaintext = match JWS::from_str(msg) {
+        Ok(jws) => base64::decode_config(jws.payload, base64::URL_SAFE_NO_PAD).ok()?,
+        Err(_) => msg.as_bytes().to_vec(),
+    };
+
+    serde_json::from_slice::<Value>(&plaintext)
+        .ok()?
+        .get("created_time")?
+        .as_u64()
+}
+
+async fn has_key_agreement_secret<'dr, 'sr>(
+    did_or_kid: &str,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+) -> Result<bool> {
+    let kids = match did_or_url(did_or_kid) {
+        (_, Some(kid)) => {
+            vec![kid.to_owned()]
+        }
+        (did, None) => match did_resolver.resolve(did).await? {
+            Some(did_doc) => did_doc.key_agreements,
+            // Forward to DID unknown to the recipient isn't addressed to the recipient
+            None => return Ok(false),
+        },
+    };
+
+    let kids = kids.iter().map(|k| k as &str).collect::<Vec<_>>();
+
+    let secrets_ids = secrets_resolver.find_secrets(&kids[..]).await?;
+
+    return Ok(!secrets_ids.is_empty());
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Mutex, task::Poll};
+
+    use async_trait::async_trait;
+    use serde_json::json;
+
+    use crate::{
+        did::{
+            resolvers::{ExampleDIDResolver, RecordingDIDResolver, SlowDIDResolver},
+            DIDDoc, VerificationMethod,
+        },
+        message::{
+            authcrypt,
+            pack_encrypted::{anoncrypt, anoncrypt_multi},
+            Attachment, AttachmentData, JsonAttachmentData, MessagingServiceMetadata,
+        },
+        protocols::routing::wrap_in_forward,
+        secrets::{
+            resolvers::{ExampleSecretsResolver, RecordingSecretsResolver},
+            Secret,
+        },
+        test_vectors::{
+            remove_field, remove_protected_field, update_field, update_protected_field,
+            EnvelopeBuilder, ALICE_AUTH_METHOD_25519, ALICE_AUTH_METHOD_P256,
+            ALICE_AUTH_METHOD_SECPP256K1, ALICE_DID, ALICE_DID_DOC, ALICE_SECRETS,
+            ALICE_SECRET_KEY_AGREEMENT_KEY_P256, ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256,
+            ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519, BOB_DID, BOB_DID_COMM_MESSAGING_SERVICE,
+            BOB_DID_DOC, BOB_SECRETS, BOB_SECRET_KEY_AGREEMENT_KEY_P256_1,
+            BOB_SECRET_KEY_AGREEMENT_KEY_P256_2, BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1,
+            BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2, BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3,
+            BOB_SERVICE, CHARLIE_AUTH_METHOD_25519, CHARLIE_DID, CHARLIE_DID_DOC, CHARLIE_SECRETS,
+            ENCRYPTED_MSG_ANON_XC20P_1, ENCRYPTED_MSG_ANON_XC20P_2, ENCRYPTED_MSG_AUTH_P256,
+            ENCRYPTED_MSG_AUTH_P256_SIGNED, ENCRYPTED_MSG_AUTH_X25519,
+            ENCRYPTED_MSG_AUTH_X25519_PROTECTED_SENDER, FROM_PRIOR_FULL,
+            INVALID_ENCRYPTED_MSG_ANON_P256_EPK_WRONG_POINT, INVALID_ENCRYPTED_MSG_ANON_XC20P_SKID,
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_AS_INT_ARRAY,
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_AS_STRING,
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_EMPTY_DATA,
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_LINKS_NO_HASH,
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_NO_DATA, INVALID_PLAINTEXT_MSG_ATTACHMENTS_NULL_DATA,
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_STRINGIFIED_JSON,
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_WRONG_DATA,
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_WRONG_ID, INVALID_PLAINTEXT_MSG_EMPTY,
+            INVALID_PLAINTEXT_MSG_EMPTY_ATTACHMENTS, INVALID_PLAINTEXT_MSG_NO_BODY,
+            INVALID_PLAINTEXT_MSG_NO_ID, INVALID_PLAINTEXT_MSG_NO_TYP,
+            INVALID_PLAINTEXT_MSG_NO_TYPE, INVALID_PLAINTEXT_MSG_STRING,
+            INVALID_PLAINTEXT_MSG_WRONG_TYP, MEDIATOR1_DID_DOC, MEDIATOR1_SECRETS,
+            MESSAGE_ATTACHMENT_BASE64, MESSAGE_ATTACHMENT_JSON, MESSAGE_ATTACHMENT_LINKS,
+            MESSAGE_ATTACHMENT_MULTI_1, MESSAGE_ATTACHMENT_MULTI_2, MESSAGE_FROM_PRIOR_FULL,
+            MESSAGE_MINIMAL, MESSAGE_SIMPLE, PLAINTEXT_FROM_PRIOR,
+            PLAINTEXT_FROM_PRIOR_INVALID_SIGNATURE, PLAINTEXT_INVALID_FROM_PRIOR,
+            PLAINTEXT_MSG_ATTACHMENT_BASE64, PLAINTEXT_MSG_ATTACHMENT_JSON,
+            PLAINTEXT_MSG_ATTACHMENT_LINKS, PLAINTEXT_MSG_ATTACHMENT_MULTI_1,
+            PLAINTEXT_MSG_ATTACHMENT_MULTI_2, PLAINTEXT_MSG_MINIMAL, PLAINTEXT_MSG_SIMPLE,
+            SIGNED_MSG_ALICE_KEY_1, SIGNED_MSG_ALICE_KEY_1_JCS, SIGNED_MSG_ALICE_KEY_2,
+            SIGNED_MSG_ALICE_KEY_3,
+        },
+        PackEncryptedOptions,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn unpack_works_plaintext() {
+        let plaintext_metadata = UnpackMetadata {
+            anonymous_sender: false,
+            authenticated: false,
+            non_repudiation: false,
+            encrypted: false,
+            enc_alg_auth: None,
+            enc_alg_anon: None,
+            sign_alg: None,
+            encrypted_from_kid: None,
+            encrypted_to_kids: None,
+            decrypted_with_kid: None,
+            sign_from: None,
+            sign_from_kids: None,
+            signed_message: None,
+            from_prior_issuer_kid: None,
+            from_prior: None,
+            re_wrapped_in_forward: false,
+            signed_outside: false,
+            epk_jwk: None,
+            original_message: None,
+            aad: None,
+            signed_with_encryption_key: false,
+            missing_recipient_kids: None,
+            signature_verification_skipped: false,
+            enc_typ: None,
+            return_route: None,
+            re_wrapped_forward_next: None,
+            forward_chain: None,
+        };
+
+        _verify_unpack(PLAINTEXT_MSG_SIMPLE, &MESSAGE_SIMPLE, &plaintext_metadata).await;
 
         _verify_unpack(PLAINTEXT_MSG_MINIMAL, &MESSAGE_MINIMAL, &plaintext_metadata).await;
 
@@ -327,6 +1175,57 @@ mod test {
         .await;
     }
 
+    #[tokio::test]
+    async fn unpack_works_plaintext_unknown_headers() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let msg = update_field(PLAINTEXT_MSG_SIMPLE, "example-header", "example-value");
+
+        let (msg, _) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &bob_secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(msg.extra_headers.len(), 1);
+        assert_eq!(msg.extra_headers["example-header"], json!("example-value"));
+
+        let (packed, _) = msg
+            .pack_encrypted(
+                BOB_DID,
+                None,
+                None,
+                &did_resolver,
+                &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("pack is ok.");
+
+        let (repacked_msg, _) = Message::unpack(
+            &packed,
+            &did_resolver,
+            &bob_secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(repacked_msg, msg);
+        assert_eq!(
+            repacked_msg.extra_headers["example-header"],
+            json!("example-value")
+        );
+    }
+
     #[tokio::test]
     async fn unpack_works_plaintext_2way() {
         _unpack_works_plaintext_2way(&MESSAGE_SIMPLE).await;
@@ -358,11 +1257,24 @@ mod test {
                     sign_alg: None,
                     encrypted_from_kid: None,
                     encrypted_to_kids: None,
+                    decrypted_with_kid: None,
                     sign_from: None,
+                    sign_from_kids: None,
                     signed_message: None,
                     from_prior_issuer_kid: None,
                     from_prior: None,
                     re_wrapped_in_forward: false,
+                    signed_outside: false,
+                    epk_jwk: None,
+                    original_message: None,
+                    aad: None,
+                    signed_with_encryption_key: false,
+                    missing_recipient_kids: None,
+                    signature_verification_skipped: false,
+                    enc_typ: None,
+                    return_route: None,
+                    re_wrapped_forward_next: None,
+                    forward_chain: None,
                 },
             )
             .await;
@@ -381,11 +1293,24 @@ mod test {
             sign_alg: None,
             encrypted_from_kid: None,
             encrypted_to_kids: None,
+            decrypted_with_kid: None,
             sign_from: None,
+            sign_from_kids: None,
             signed_message: None,
             from_prior_issuer_kid: None,
             from_prior: None,
             re_wrapped_in_forward: false,
+            signed_outside: false,
+            epk_jwk: None,
+            original_message: None,
+            aad: None,
+            signed_with_encryption_key: false,
+            missing_recipient_kids: None,
+            signature_verification_skipped: false,
+            enc_typ: None,
+            return_route: None,
+            re_wrapped_forward_next: None,
+            forward_chain: None,
         };
 
         _verify_unpack(
@@ -393,6 +1318,7 @@ mod test {
             &MESSAGE_SIMPLE,
             &UnpackMetadata {
                 sign_from: Some("did:example:alice#key-1".into()),
+                sign_from_kids: Some(vec!["did:example:alice#key-1".into()]),
                 sign_alg: Some(SignAlg::EdDSA),
                 signed_message: Some(SIGNED_MSG_ALICE_KEY_1.into()),
                 ..sign_metadata.clone()
@@ -405,6 +1331,7 @@ mod test {
             &MESSAGE_SIMPLE,
             &UnpackMetadata {
                 sign_from: Some("did:example:alice#key-2".into()),
+                sign_from_kids: Some(vec!["did:example:alice#key-2".into()]),
                 sign_alg: Some(SignAlg::ES256),
                 signed_message: Some(SIGNED_MSG_ALICE_KEY_2.into()),
                 ..sign_metadata.clone()
@@ -417,6 +1344,7 @@ mod test {
             &MESSAGE_SIMPLE,
             &UnpackMetadata {
                 sign_from: Some("did:example:alice#key-3".into()),
+                sign_from_kids: Some(vec!["did:example:alice#key-3".into()]),
                 sign_alg: Some(SignAlg::ES256K),
                 signed_message: Some(SIGNED_MSG_ALICE_KEY_3.into()),
                 ..sign_metadata.clone()
@@ -478,6 +1406,7 @@ mod test {
                 &MESSAGE_SIMPLE,
                 &UnpackMetadata {
                     sign_from: Some(sign_by_kid.into()),
+                    sign_from_kids: Some(vec![sign_by_kid.into()]),
                     sign_alg: Some(sign_alg),
                     signed_message: Some(msg.clone()),
                     anonymous_sender: false,
@@ -488,9 +1417,21 @@ mod test {
                     enc_alg_anon: None,
                     encrypted_from_kid: None,
                     encrypted_to_kids: None,
+                    decrypted_with_kid: None,
                     from_prior_issuer_kid: None,
                     from_prior: None,
                     re_wrapped_in_forward: false,
+                    signed_outside: false,
+                    epk_jwk: None,
+                    original_message: None,
+                    aad: None,
+                    signed_with_encryption_key: false,
+                    missing_recipient_kids: None,
+                    signature_verification_skipped: false,
+                    enc_typ: None,
+                    return_route: None,
+                    re_wrapped_forward_next: None,
+                    forward_chain: None,
                 },
             )
             .await;
@@ -509,11 +1450,24 @@ mod test {
             sign_alg: None,
             encrypted_from_kid: None,
             encrypted_to_kids: None,
+            decrypted_with_kid: None,
             sign_from: None,
+            sign_from_kids: None,
             signed_message: None,
             from_prior_issuer_kid: None,
             from_prior: None,
             re_wrapped_in_forward: false,
+            signed_outside: false,
+            epk_jwk: None,
+            original_message: None,
+            aad: None,
+            signed_with_encryption_key: false,
+            missing_recipient_kids: None,
+            signature_verification_skipped: false,
+            enc_typ: Some("application/didcomm-encrypted+json".into()),
+            return_route: None,
+            re_wrapped_forward_next: None,
+            forward_chain: None,
         };
 
         _verify_unpack(
@@ -526,6 +1480,12 @@ mod test {
                     "did:example:bob#key-x25519-2".into(),
                     "did:example:bob#key-x25519-3".into(),
                 ]),
+                decrypted_with_kid: Some("did:example:bob#key-x25519-1".into()),
+                epk_jwk: Some(json!({
+                    "kty": "OKP",
+                    "crv": "X25519",
+                    "x": "JHjsmIRZAaB0zRG_wNXLV2rPggF00hdHbW5rj8g0I24",
+                })),
                 ..metadata.clone()
             },
         )
@@ -540,6 +1500,13 @@ mod test {
                     "did:example:bob#key-p256-1".into(),
                     "did:example:bob#key-p256-2".into(),
                 ]),
+                decrypted_with_kid: Some("did:example:bob#key-p256-1".into()),
+                epk_jwk: Some(json!({
+                    "kty": "EC",
+                    "crv": "P-256",
+                    "x": "Es7iP3EhLCHlArP0KcYFcqEyBapr2K6YOA9W8eO8au8",
+                    "y": "N6Al7ETwCdpC6Njdecr2KXAg1UeZy_ufHRQKp9G6KGk",
+                })),
                 ..metadata.clone()
             },
         )
@@ -671,6 +1638,136 @@ mod test {
 
             assert_eq!(&unpacked_msg, &*MESSAGE_SIMPLE);
             assert!(unpack_metadata.re_wrapped_in_forward);
+            assert_eq!(unpack_metadata.re_wrapped_forward_next.as_deref(), Some(to));
+            assert_eq!(unpack_metadata.forward_chain.map(|c| c.len()), Some(1));
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_unwrap_re_wrapping_forward_chain() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let (msg, _) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                Some(ALICE_DID),
+                None,
+                &did_resolver,
+                &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("Unable encrypt");
+
+        // Two hops, each re-wrapping forward is addressed to the own Bob's key
+        let re_wrapping_forward_msg = wrap_in_forward(
+            &msg,
+            None,
+            BOB_DID,
+            &vec![
+                BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone(),
+                BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id.clone(),
+            ],
+            &AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+            &did_resolver,
+        )
+        .await
+        .expect("Unable wrap in forward");
+
+        let (unpacked_msg, unpack_metadata) = Message::unpack(
+            &re_wrapping_forward_msg,
+            &did_resolver,
+            &bob_secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
+
+        assert_eq!(&unpacked_msg, &*MESSAGE_SIMPLE);
+        assert!(unpack_metadata.authenticated);
+        assert!(unpack_metadata.re_wrapped_in_forward);
+        assert_eq!(
+            unpack_metadata.re_wrapped_forward_next.as_deref(),
+            Some(BOB_DID)
+        );
+
+        assert_eq!(
+            unpack_metadata.forward_chain,
+            Some(vec![
+                ForwardHopInfo {
+                    next: BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id.clone(),
+                    decrypted_with_kid: Some(BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone()),
+                    enc_alg_anon: Some(AnonCryptAlg::A256cbcHs512EcdhEsA256kw),
+                },
+                ForwardHopInfo {
+                    next: BOB_DID.to_owned(),
+                    decrypted_with_kid: Some(BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id.clone()),
+                    enc_alg_anon: Some(AnonCryptAlg::A256cbcHs512EcdhEsA256kw),
+                },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn unpack_works_unwrap_re_wrapping_forward_next_mismatch() {
+        let did_resolver = ExampleDIDResolver::new(vec![
+            ALICE_DID_DOC.clone(),
+            BOB_DID_DOC.clone(),
+            CHARLIE_DID_DOC.clone(),
+        ]);
+
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let (msg, _) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                None,
+                None,
+                &did_resolver,
+                &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("Unable encrypt");
+
+        // Forward is encrypted for Bob, but addressed to another DID
+        for next in [CHARLIE_DID, "did:example:unknown"] {
+            let re_wrapping_forward_msg = wrap_in_forward(
+                &msg,
+                None,
+                next,
+                &vec![BOB_DID.to_owned()],
+                &AnonCryptAlg::default(),
+                &did_resolver,
+            )
+            .await
+            .expect("Unable wrap in forward");
+
+            let (unpacked_msg, unpack_metadata) = Message::unpack(
+                &re_wrapping_forward_msg,
+                &did_resolver,
+                &bob_secrets_resolver,
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect("Unable unpack");
+
+            let forward = try_parse_forward(&unpacked_msg).expect("Message is not Forward");
+            assert_eq!(forward.next, next);
+
+            assert!(unpack_metadata.encrypted);
+            assert!(unpack_metadata.anonymous_sender);
+            assert!(!unpack_metadata.re_wrapped_in_forward);
+            assert_eq!(unpack_metadata.re_wrapped_forward_next, None);
         }
     }
 
@@ -841,521 +1938,843 @@ mod test {
         }
     }
 
-    #[tokio::test]
-    async fn unpack_works_anoncrypted_2way() {
-        _unpack_works_anoncrypted_2way(
-            &MESSAGE_SIMPLE,
-            BOB_DID,
-            &[
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
-            ],
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-        )
-        .await;
+    #[test]
+    fn unpack_metadata_default_works() {
+        let metadata = UnpackMetadata {
+            encrypted: true,
+            decrypted_with_kid: Some(BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone()),
+            ..UnpackMetadata::default()
+        };
 
-        _unpack_works_anoncrypted_2way(
-            &MESSAGE_SIMPLE,
+        assert!(metadata.encrypted);
+        assert!(!metadata.authenticated);
+        assert!(!metadata.non_repudiation);
+        assert!(!metadata.anonymous_sender);
+        assert_eq!(
+            metadata.decrypted_with_kid,
+            Some(BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone())
+        );
+        assert_eq!(metadata.encrypted_to_kids, None);
+        assert_eq!(metadata.enc_alg_anon, None);
+        assert_eq!(metadata.original_message, None);
+
+        assert_eq!(
+            UnpackMetadata::build()
+                .encrypted(true)
+                .decrypted_with_kid(BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone())
+                .finalize(),
+            metadata
+        );
+
+        assert_eq!(
+            UnpackMetadata::build()
+                .authenticated(true)
+                .non_repudiation(true)
+                .sign_alg(SignAlg::EdDSA)
+                .finalize(),
+            UnpackMetadata {
+                authenticated: true,
+                non_repudiation: true,
+                sign_alg: Some(SignAlg::EdDSA),
+                ..UnpackMetadata::default()
+            }
+        );
+
+        assert_eq!(
+            UnpackMetadata::build().finalize(),
+            UnpackMetadata::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn unpack_works_partial_metadata_on_error() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let (signed, _) = MESSAGE_SIMPLE
+            .pack_signed(ALICE_DID, &did_resolver, &alice_secrets_resolver)
+            .await
+            .expect("Unable pack_signed");
+
+        let (other_signed, _) = MESSAGE_MINIMAL
+            .pack_signed(ALICE_DID, &did_resolver, &alice_secrets_resolver)
+            .await
+            .expect("Unable pack_signed");
+
+        // Signature of another message by the same key
+        let mut signed: Value = serde_json::from_str(&signed).expect("Unable from_str");
+        let other_signed: Value = serde_json::from_str(&other_signed).expect("Unable from_str");
+        signed["signatures"][0]["signature"] = other_signed["signatures"][0]["signature"].clone();
+        let signed = signed.to_string();
+
+        let (msg, from_kid, _) = authcrypt(
             BOB_DID,
-            &[
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
-            ],
-            AnonCryptAlg::Xc20pEcdhEsA256kw,
+            None,
+            ALICE_DID,
+            &did_resolver,
+            &alice_secrets_resolver,
+            signed.as_bytes(),
+            &AuthCryptAlg::default(),
+            &AnonCryptAlg::default(),
+            false,
+            &AlgorithmRegistry::default(),
+            None,
+            None,
+            false,
         )
-        .await;
+        .await
+        .expect("Unable authcrypt");
 
-        _unpack_works_anoncrypted_2way(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+        let err = Message::unpack(
+            &msg,
+            &did_resolver,
+            &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+            &UnpackOptions::default(),
         )
-        .await;
+        .await
+        .expect_err("res is ok");
 
-        _unpack_works_anoncrypted_2way(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
-            AnonCryptAlg::A256gcmEcdhEsA256kw,
-        )
-        .await;
+        assert_eq!(err.kind(), ErrorKind::Malformed);
 
-        _unpack_works_anoncrypted_2way(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
-            AnonCryptAlg::Xc20pEcdhEsA256kw,
-        )
-        .await;
+        let metadata = err
+            .partial_unpack_metadata()
+            .expect("partial_unpack_metadata is some.");
 
-        _unpack_works_anoncrypted_2way(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-        )
-        .await;
+        assert!(metadata.encrypted);
+        assert!(metadata.authenticated);
+        assert!(!metadata.non_repudiation);
+        assert_eq!(metadata.encrypted_from_kid, Some(from_kid));
+        assert_eq!(metadata.enc_alg_auth, Some(AuthCryptAlg::default()));
 
-        _unpack_works_anoncrypted_2way(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
-            AnonCryptAlg::A256gcmEcdhEsA256kw,
-        )
-        .await;
+        assert_eq!(
+            metadata.decrypted_with_kid,
+            Some(BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone())
+        );
 
-        _unpack_works_anoncrypted_2way(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
-            AnonCryptAlg::Xc20pEcdhEsA256kw,
-        )
-        .await;
+        assert_eq!(metadata.sign_from, None);
 
-        _unpack_works_anoncrypted_2way(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+        // Nothing is reported if decryption failed
+        let err = Message::unpack(
+            &msg,
+            &did_resolver,
+            &ExampleSecretsResolver::new(vec![]),
+            &UnpackOptions::default(),
         )
-        .await;
+        .await
+        .expect_err("res is ok");
 
-        _unpack_works_anoncrypted_2way(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
-            AnonCryptAlg::A256gcmEcdhEsA256kw,
-        )
-        .await;
+        assert_eq!(err.kind(), ErrorKind::SecretNotFound);
+        assert!(err.partial_unpack_metadata().is_none());
+    }
 
-        _unpack_works_anoncrypted_2way(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
-            AnonCryptAlg::Xc20pEcdhEsA256kw,
-        )
-        .await;
+    #[tokio::test]
+    async fn unpack_works_decrypted_payload_invalid_utf8() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
 
-        _unpack_works_anoncrypted_2way(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-        )
-        .await;
+        let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
 
-        _unpack_works_anoncrypted_2way(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id],
-            AnonCryptAlg::A256gcmEcdhEsA256kw,
+        // Corrupted plaintext: truncated multi-byte sequence and invalid bytes
+        let payload: &[u8] = &[b'{', 0xe2, 0x82, b'"', 0xff, 0xfe, b'}'];
+
+        let (anoncrypted, _) = anoncrypt(
+            BOB_DID,
+            None,
+            &did_resolver,
+            payload,
+            &AnonCryptAlg::default(),
+            &AlgorithmRegistry::default(),
+            None,
+            None,
+            None,
+            false,
         )
-        .await;
+        .await
+        .expect("Unable anoncrypt");
 
-        _unpack_works_anoncrypted_2way(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id],
-            AnonCryptAlg::Xc20pEcdhEsA256kw,
+        let (authcrypted, _, _) = authcrypt(
+            BOB_DID,
+            None,
+            ALICE_DID,
+            &did_resolver,
+            &alice_secrets_resolver,
+            payload,
+            &AuthCryptAlg::default(),
+            &AnonCryptAlg::default(),
+            false,
+            &AlgorithmRegistry::default(),
+            None,
+            None,
+            false,
         )
-        .await;
+        .await
+        .expect("Unable authcrypt");
 
-        async fn _unpack_works_anoncrypted_2way(
-            msg: &Message,
-            to: &str,
-            to_kids: &[&str],
-            enc_alg: AnonCryptAlg,
-        ) {
+        for msg in [anoncrypted, authcrypted] {
+            let err = Message::unpack(
+                &msg,
+                &did_resolver,
+                &bob_secrets_resolver,
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect_err("res is ok");
+
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+
+            assert!(
+                format!("{}", err).starts_with("Malformed: Decrypted payload is not valid UTF-8: ")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_aad() {
+        _unpack_works_aad(None, false).await;
+        _unpack_works_aad(Some(ALICE_DID), false).await;
+        _unpack_works_aad(Some(ALICE_DID), true).await;
+
+        async fn _unpack_works_aad(from: Option<&str>, protect_sender: bool) {
             let did_resolver =
                 ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
 
-            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+            let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
 
-            let (packed, _) = msg
+            let (packed, _) = MESSAGE_SIMPLE
                 .pack_encrypted(
-                    to,
-                    None,
+                    BOB_DID,
+                    from,
                     None,
                     &did_resolver,
-                    &secrets_resolver,
+                    &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
                     &PackEncryptedOptions {
                         forward: false,
-                        enc_alg_anon: enc_alg.clone(),
+                        protect_sender,
+                        aad: Some("session-1234".into()),
                         ..PackEncryptedOptions::default()
                     },
                 )
                 .await
-                .expect("Unable pack_encrypted");
+                .expect("pack is ok.");
 
-            _verify_unpack(
+            let (msg, metadata) = Message::unpack(
                 &packed,
-                msg,
-                &UnpackMetadata {
-                    sign_from: None,
-                    sign_alg: None,
-                    signed_message: None,
-                    anonymous_sender: true,
-                    authenticated: false,
-                    non_repudiation: false,
-                    encrypted: true,
-                    enc_alg_auth: None,
-                    enc_alg_anon: Some(enc_alg),
-                    encrypted_from_kid: None,
-                    encrypted_to_kids: Some(to_kids.iter().map(|&k| k.to_owned()).collect()),
-                    from_prior_issuer_kid: None,
-                    from_prior: None,
-                    re_wrapped_in_forward: false,
-                },
+                &did_resolver,
+                &bob_secrets_resolver,
+                &UnpackOptions::default(),
             )
-            .await;
+            .await
+            .expect("unpack is ok.");
+
+            assert_eq!(&msg, &*MESSAGE_SIMPLE);
+            assert_eq!(metadata.aad.as_deref(), Some("session-1234"));
+
+            let packed: Value = serde_json::from_str(&packed).expect("Unable from_str");
+
+            // Tampered aad
+            let mut tampered = packed.clone();
+            tampered["aad"] = json!(base64::encode_config(
+                "session-5678",
+                base64::URL_SAFE_NO_PAD
+            ));
+
+            // Stripped aad
+            let mut stripped = packed.clone();
+            stripped
+                .as_object_mut()
+                .expect("JWE is object")
+                .remove("aad")
+                .expect("aad is some.");
+
+            for msg in [tampered, stripped] {
+                let err = Message::unpack(
+                    &msg.to_string(),
+                    &did_resolver,
+                    &bob_secrets_resolver,
+                    &UnpackOptions::default(),
+                )
+                .await
+                .expect_err("res is ok");
+
+                assert_eq!(err.kind(), ErrorKind::Malformed);
+            }
         }
     }
 
     #[tokio::test]
-    async fn pack_encrypted_works_anoncrypted_signed() {
-        _pack_encrypted_works_anoncrypted_signed(
-            &MESSAGE_SIMPLE,
-            BOB_DID,
-            &[
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
-            ],
-            ALICE_DID,
-            &ALICE_AUTH_METHOD_25519.id,
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-            SignAlg::EdDSA,
-        )
-        .await;
+    async fn unpack_works_missing_recipient_kids() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
 
-        _pack_encrypted_works_anoncrypted_signed(
-            &MESSAGE_SIMPLE,
-            BOB_DID,
-            &[
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
-            ],
-            ALICE_DID,
-            &ALICE_AUTH_METHOD_25519.id,
-            AnonCryptAlg::A256gcmEcdhEsA256kw,
-            SignAlg::EdDSA,
-        )
-        .await;
+        let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
 
-        _pack_encrypted_works_anoncrypted_signed(
-            &MESSAGE_SIMPLE,
-            BOB_DID,
-            &[
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
-            ],
-            ALICE_DID,
-            &ALICE_AUTH_METHOD_25519.id,
-            AnonCryptAlg::Xc20pEcdhEsA256kw,
-            SignAlg::EdDSA,
-        )
-        .await;
+        let bob_secrets_resolver =
+            ExampleSecretsResolver::new(vec![BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.clone()]);
 
-        _pack_encrypted_works_anoncrypted_signed(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
-            &ALICE_AUTH_METHOD_25519.id,
-            &ALICE_AUTH_METHOD_25519.id,
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-            SignAlg::EdDSA,
-        )
-        .await;
+        let bob_all_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
 
-        _pack_encrypted_works_anoncrypted_signed(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
-            &ALICE_AUTH_METHOD_P256.id,
-            &ALICE_AUTH_METHOD_P256.id,
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-            SignAlg::ES256,
-        )
-        .await;
-
-        _pack_encrypted_works_anoncrypted_signed(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
-            &ALICE_AUTH_METHOD_SECPP256K1.id,
-            &ALICE_AUTH_METHOD_SECPP256K1.id,
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-            SignAlg::ES256K,
-        )
-        .await;
-
-        async fn _pack_encrypted_works_anoncrypted_signed(
-            msg: &Message,
-            to: &str,
-            to_kids: &[&str],
-            sign_by: &str,
-            sign_by_kid: &str,
-            enc_alg: AnonCryptAlg,
-            sign_alg: SignAlg,
-        ) {
-            let did_resolver =
-                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
-
-            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
-
-            let (packed, _) = msg
+        for from in [None, Some(ALICE_DID)] {
+            let (packed, _) = MESSAGE_SIMPLE
                 .pack_encrypted(
-                    to,
+                    BOB_DID,
+                    from,
                     None,
-                    Some(sign_by),
                     &did_resolver,
-                    &secrets_resolver,
+                    &alice_secrets_resolver,
                     &PackEncryptedOptions {
                         forward: false,
-                        enc_alg_anon: enc_alg.clone(),
                         ..PackEncryptedOptions::default()
                     },
                 )
                 .await
-                .expect("Unable pack_encrypted");
+                .expect("pack is ok.");
 
-            _verify_unpack_undeterministic(
+            let (msg, metadata) = Message::unpack(
                 &packed,
-                msg,
-                &UnpackMetadata {
-                    sign_from: Some(sign_by_kid.into()),
-                    sign_alg: Some(sign_alg),
-                    signed_message: None,
-                    anonymous_sender: true,
-                    authenticated: true,
-                    non_repudiation: true,
-                    encrypted: true,
-                    enc_alg_auth: None,
-                    enc_alg_anon: Some(enc_alg),
-                    encrypted_from_kid: None,
-                    encrypted_to_kids: Some(to_kids.iter().map(|&k| k.to_owned()).collect()),
-                    from_prior_issuer_kid: None,
-                    from_prior: None,
-                    re_wrapped_in_forward: false,
+                &did_resolver,
+                &bob_secrets_resolver,
+                &UnpackOptions {
+                    expect_decrypt_by_all_keys: false,
+                    ..UnpackOptions::default()
                 },
             )
-            .await;
-        }
-    }
+            .await
+            .expect("unpack is ok.");
 
-    #[tokio::test]
-    async fn unpack_works_authcrypt() {
-        let metadata = UnpackMetadata {
-            anonymous_sender: false,
-            authenticated: true,
-            non_repudiation: false,
-            encrypted: true,
-            enc_alg_auth: None,
-            enc_alg_anon: None,
-            sign_alg: None,
-            encrypted_from_kid: None,
-            encrypted_to_kids: None,
-            sign_from: None,
-            signed_message: None,
-            from_prior_issuer_kid: None,
-            from_prior: None,
-            re_wrapped_in_forward: false,
-        };
+            assert_eq!(&msg, &*MESSAGE_SIMPLE);
+            assert_eq!(metadata.encrypted_to_kids.map(|kids| kids.len()), Some(3));
 
-        _verify_unpack(
-            ENCRYPTED_MSG_AUTH_X25519,
-            &MESSAGE_SIMPLE,
-            &UnpackMetadata {
-                enc_alg_auth: Some(AuthCryptAlg::A256cbcHs512Ecdh1puA256kw),
-                encrypted_from_kid: Some("did:example:alice#key-x25519-1".into()),
-                encrypted_to_kids: Some(vec![
-                    "did:example:bob#key-x25519-1".into(),
-                    "did:example:bob#key-x25519-2".into(),
-                    "did:example:bob#key-x25519-3".into(),
-                ]),
-                ..metadata.clone()
-            },
-        )
-        .await;
+            assert_eq!(
+                metadata.decrypted_with_kid.as_deref(),
+                Some(BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id.as_str())
+            );
 
-        _verify_unpack(
-            ENCRYPTED_MSG_AUTH_P256,
-            &MESSAGE_SIMPLE,
-            &UnpackMetadata {
-                enc_alg_auth: Some(AuthCryptAlg::A256cbcHs512Ecdh1puA256kw),
-                encrypted_from_kid: Some("did:example:alice#key-p256-1".into()),
-                encrypted_to_kids: Some(vec![
-                    "did:example:bob#key-p256-1".into(),
-                    "did:example:bob#key-p256-2".into(),
-                ]),
-                non_repudiation: true,
-                sign_from: Some("did:example:alice#key-1".into()),
-                sign_alg: Some(SignAlg::EdDSA),
-                signed_message: Some(ENCRYPTED_MSG_AUTH_P256_SIGNED.into()),
-                ..metadata.clone()
-            },
-        )
-        .await;
+            assert_eq!(
+                metadata.missing_recipient_kids,
+                Some(vec![
+                    BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone(),
+                    BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id.clone(),
+                ])
+            );
 
-        // TODO: Check hidden sender case
-        // TODO: Check P-384 curve support
-        // TODO: Check P-521 curve support
+            let (_, metadata) = Message::unpack(
+                &packed,
+                &did_resolver,
+                &bob_all_secrets_resolver,
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect("unpack is ok.");
+
+            assert_eq!(metadata.missing_recipient_kids, None);
+        }
     }
 
     #[tokio::test]
-    async fn unpack_works_authcrypted_2way() {
-        _unpack_works_authcrypted_2way(
-            &MESSAGE_SIMPLE,
-            BOB_DID,
-            &[
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
-            ],
-            ALICE_DID,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
-        )
-        .await;
-
-        _unpack_works_authcrypted_2way(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
-            ALICE_DID,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
-        )
-        .await;
-
-        _unpack_works_authcrypted_2way(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
-        )
-        .await;
+    async fn unpack_works_signed_with_encryption_key() {
+        // The same P-256 key material is declared for both authentication and key agreement
+        let signing_kid = "did:example:alice#key-p256-signing";
+
+        let mut alice_did_doc = ALICE_DID_DOC.clone();
+        alice_did_doc.authentications.push(signing_kid.into());
+
+        alice_did_doc.verification_methods.push(VerificationMethod {
+            id: signing_kid.into(),
+            ..ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.clone()
+        });
+
+        let mut alice_secrets = ALICE_SECRETS.clone();
+
+        alice_secrets.push(Secret {
+            id: signing_kid.into(),
+            ..ALICE_SECRET_KEY_AGREEMENT_KEY_P256.clone()
+        });
+
+        let did_resolver = ExampleDIDResolver::new(vec![alice_did_doc, BOB_DID_DOC.clone()]);
+        let alice_secrets_resolver = ExampleSecretsResolver::new(alice_secrets);
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        for (sign_by, signed_with_encryption_key) in [
+            (signing_kid, true),
+            (ALICE_AUTH_METHOD_P256.id.as_str(), false),
+        ] {
+            let (packed, _) = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    BOB_DID,
+                    Some(&ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id),
+                    Some(sign_by),
+                    &did_resolver,
+                    &alice_secrets_resolver,
+                    &PackEncryptedOptions {
+                        forward: false,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("pack is ok.");
 
-        _unpack_works_authcrypted_2way(
-            &MESSAGE_SIMPLE,
-            BOB_DID,
-            &[
-                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
-            ],
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
-        )
-        .await;
+            let (msg, metadata) = Message::unpack(
+                &packed,
+                &did_resolver,
+                &bob_secrets_resolver,
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect("unpack is ok.");
 
-        _unpack_works_authcrypted_2way(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
-        )
-        .await;
+            assert_eq!(&msg, &*MESSAGE_SIMPLE);
+            assert!(metadata.authenticated);
+            assert!(metadata.non_repudiation);
 
-        _unpack_works_authcrypted_2way(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id],
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
-        )
-        .await;
+            assert_eq!(
+                metadata.signed_with_encryption_key,
+                signed_with_encryption_key
+            );
+        }
+    }
 
-        _unpack_works_authcrypted_2way(
-            &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id],
-            ALICE_DID,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
-        )
-        .await;
+    #[tokio::test]
+    async fn unpack_works_require_signer_matches_sender() {
+        _unpack_works_require_signer_matches_sender(&ALICE_AUTH_METHOD_25519.id, true, true).await;
+        _unpack_works_require_signer_matches_sender(&ALICE_AUTH_METHOD_25519.id, false, true).await;
+        _unpack_works_require_signer_matches_sender(&CHARLIE_AUTH_METHOD_25519.id, true, false)
+            .await;
+        _unpack_works_require_signer_matches_sender(&CHARLIE_AUTH_METHOD_25519.id, false, true)
+            .await;
 
-        async fn _unpack_works_authcrypted_2way(
-            msg: &Message,
-            to: &str,
-            to_kids: &[&str],
-            from: &str,
-            from_kid: &str,
-            enc_alg: AuthCryptAlg,
+        async fn _unpack_works_require_signer_matches_sender(
+            sign_by: &str,
+            require_signer_matches_sender: bool,
+            exp_ok: bool,
         ) {
-            let did_resolver =
-                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+            let did_resolver = ExampleDIDResolver::new(vec![
+                ALICE_DID_DOC.clone(),
+                BOB_DID_DOC.clone(),
+                CHARLIE_DID_DOC.clone(),
+            ]);
 
-            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+            let mut sender_secrets = ALICE_SECRETS.clone();
+            sender_secrets.extend(CHARLIE_SECRETS.iter().cloned());
 
-            let (packed, _) = msg
+            let sender_secrets_resolver = ExampleSecretsResolver::new(sender_secrets);
+            let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+            let (packed, _) = MESSAGE_SIMPLE
                 .pack_encrypted(
-                    to,
-                    Some(from),
-                    None,
+                    BOB_DID,
+                    Some(ALICE_DID),
+                    Some(sign_by),
                     &did_resolver,
-                    &secrets_resolver,
+                    &sender_secrets_resolver,
                     &PackEncryptedOptions {
                         forward: false,
                         ..PackEncryptedOptions::default()
                     },
                 )
                 .await
-                .expect("Unable pack_encrypted");
+                .expect("pack is ok.");
 
-            _verify_unpack(
+            let res = Message::unpack(
                 &packed,
-                msg,
-                &UnpackMetadata {
-                    sign_from: None,
-                    sign_alg: None,
-                    signed_message: None,
-                    anonymous_sender: false,
-                    authenticated: true,
-                    non_repudiation: false,
-                    encrypted: true,
-                    enc_alg_auth: Some(enc_alg),
-                    enc_alg_anon: None,
-                    encrypted_from_kid: Some(from_kid.into()),
-                    encrypted_to_kids: Some(to_kids.iter().map(|&k| k.to_owned()).collect()),
-                    from_prior_issuer_kid: None,
-                    from_prior: None,
-                    re_wrapped_in_forward: false,
+                &did_resolver,
+                &bob_secrets_resolver,
+                &UnpackOptions {
+                    require_signer_matches_sender,
+                    ..UnpackOptions::default()
                 },
             )
             .await;
+
+            if exp_ok {
+                let (msg, metadata) = res.expect("unpack is ok.");
+                assert_eq!(&msg, &*MESSAGE_SIMPLE);
+                assert_eq!(metadata.sign_from.as_deref(), Some(sign_by));
+            } else {
+                let err = res.expect_err("res is ok");
+                assert_eq!(err.kind(), ErrorKind::Untrusted);
+
+                assert_eq!(
+                    format!("{}", err),
+                    "Untrusted: Signer `did:example:charlie#key-1` doesn't belong to the sender DID `did:example:alice`"
+                );
+            }
         }
     }
 
     #[tokio::test]
-    async fn unpack_works_authcrypted_protected_sender_2way() {
-        _unpack_works_authcrypted_protected_sender_2way(
-            &MESSAGE_SIMPLE,
-            BOB_DID,
+    async fn unpack_works_expected_enc_typ() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        for msg in [ENCRYPTED_MSG_ANON_XC20P_1, ENCRYPTED_MSG_AUTH_X25519] {
+            let (unpacked, metadata) = Message::unpack(
+                msg,
+                &did_resolver,
+                &secrets_resolver,
+                &UnpackOptions {
+                    expected_enc_typ: Some("application/didcomm-encrypted+json".into()),
+                    ..UnpackOptions::default()
+                },
+            )
+            .await
+            .expect("unpack is ok.");
+
+            assert_eq!(unpacked, *MESSAGE_SIMPLE);
+
+            assert_eq!(
+                metadata.enc_typ.as_deref(),
+                Some("application/didcomm-encrypted+json")
+            );
+        }
+
+        // Plaintext isn't checked
+        let (_, metadata) = Message::unpack(
+            PLAINTEXT_MSG_SIMPLE,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                expected_enc_typ: Some("application/didcomm-encrypted+json".into()),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(metadata.enc_typ, None);
+    }
+
+    #[tokio::test]
+    async fn unpack_works_unexpected_enc_typ() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        for (msg, exp_err_msg) in [
+            (
+                update_protected_field(ENCRYPTED_MSG_ANON_XC20P_1, "typ", "application/json"),
+                "Malformed: Unexpected encrypted message `typ` `application/json`, \
+                expected `application/didcomm-encrypted+json`",
+            ),
+            (
+                update_protected_field(ENCRYPTED_MSG_AUTH_X25519, "typ", "didcomm-encrypted+json"),
+                "Malformed: Unexpected encrypted message `typ` `didcomm-encrypted+json`, \
+                expected `application/didcomm-encrypted+json`",
+            ),
+            (
+                remove_protected_field(ENCRYPTED_MSG_ANON_XC20P_1, "typ"),
+                "Malformed: Unexpected encrypted message `typ` (none), \
+                expected `application/didcomm-encrypted+json`",
+            ),
+        ] {
+            let err = Message::unpack(
+                &msg,
+                &did_resolver,
+                &secrets_resolver,
+                &UnpackOptions {
+                    expected_enc_typ: Some("application/didcomm-encrypted+json".into()),
+                    ..UnpackOptions::default()
+                },
+            )
+            .await
+            .expect_err("res is ok");
+
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+            assert_eq!(format!("{}", err), exp_err_msg);
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_verify_to_matches_recipient() {
+        let did_resolver = ExampleDIDResolver::new(vec![
+            ALICE_DID_DOC.clone(),
+            BOB_DID_DOC.clone(),
+            CHARLIE_DID_DOC.clone(),
+        ]);
+
+        let opts = UnpackOptions {
+            verify_to_matches_recipient: true,
+            ..UnpackOptions::default()
+        };
+
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        for msg in [
+            PLAINTEXT_MSG_SIMPLE,
+            ENCRYPTED_MSG_ANON_XC20P_1,
+            ENCRYPTED_MSG_AUTH_X25519,
+        ] {
+            let (unpacked, _) = Message::unpack(msg, &did_resolver, &secrets_resolver, &opts)
+                .await
+                .expect("unpack is ok.");
+
+            assert_eq!(unpacked, *MESSAGE_SIMPLE);
+        }
+
+        // Message without `to` isn't checked
+        let (unpacked, _) = Message::unpack(
+            PLAINTEXT_MSG_MINIMAL,
+            &did_resolver,
+            &ExampleSecretsResolver::new(CHARLIE_SECRETS.clone()),
+            &opts,
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(unpacked, *MESSAGE_MINIMAL);
+    }
+
+    #[tokio::test]
+    async fn unpack_works_verify_to_matches_recipient_mismatch() {
+        let did_resolver = ExampleDIDResolver::new(vec![
+            ALICE_DID_DOC.clone(),
+            BOB_DID_DOC.clone(),
+            CHARLIE_DID_DOC.clone(),
+        ]);
+
+        let opts = UnpackOptions {
+            verify_to_matches_recipient: true,
+            ..UnpackOptions::default()
+        };
+
+        let mut msg: Value = serde_json::from_str(PLAINTEXT_MSG_SIMPLE).expect("Unable from_str");
+        msg["to"] = json!([CHARLIE_DID, "did:example:unknown"]);
+
+        for (msg, secrets, exp_err_msg) in [
+            (
+                PLAINTEXT_MSG_SIMPLE.to_owned(),
+                CHARLIE_SECRETS.clone(),
+                "Untrusted: Message is addressed to `did:example:bob`, \
+                but none of them is the recipient",
+            ),
+            (
+                msg.to_string(),
+                BOB_SECRETS.clone(),
+                "Untrusted: Message is addressed to `did:example:charlie, did:example:unknown`, \
+                but none of them is the recipient",
+            ),
+        ] {
+            let err = Message::unpack(
+                &msg,
+                &did_resolver,
+                &ExampleSecretsResolver::new(secrets),
+                &opts,
+            )
+            .await
+            .expect_err("res is ok");
+
+            assert_eq!(err.kind(), ErrorKind::Untrusted);
+            assert_eq!(format!("{}", err), exp_err_msg);
+        }
+
+        // Not checked by default
+        Message::unpack(
+            PLAINTEXT_MSG_SIMPLE,
+            &did_resolver,
+            &ExampleSecretsResolver::new(CHARLIE_SECRETS.clone()),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+    }
+
+    #[tokio::test]
+    async fn unpack_works_empty_recipients() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        for msg in [ENCRYPTED_MSG_ANON_XC20P_1, ENCRYPTED_MSG_AUTH_X25519] {
+            let mut msg: Value = serde_json::from_str(msg).expect("Unable from_str");
+            msg["recipients"] = json!([]);
+
+            let err = Message::unpack(
+                &msg.to_string(),
+                &did_resolver,
+                &secrets_resolver,
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect_err("res is ok");
+
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+            assert_eq!(format!("{}", err), "Malformed: JWE `recipients` is empty");
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_anoncrypt_duplicated_recipients() {
+        let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+
+        // Key wrapping is deterministic, so duplicated recipient gets the same encrypted key
+        let (msg, to_kids) = anoncrypt_multi(
             &[
                 &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
                 &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
             ],
-            ALICE_DID,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            &did_resolver,
+            PLAINTEXT_MSG_SIMPLE.as_bytes(),
+            &AnonCryptAlg::default(),
+            &AlgorithmRegistry::default(),
+            None,
+            None,
+            None,
+            false,
         )
-        .await;
+        .await
+        .expect("Unable encrypt");
 
-        _unpack_works_authcrypted_protected_sender_2way(
+        assert_eq!(to_kids.len(), 3);
+
+        for expect_decrypt_by_all_keys in [false, true] {
+            let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+            let secrets_resolver = RecordingSecretsResolver::new(&secrets_resolver);
+
+            let (unpacked, metadata) = Message::unpack(
+                &msg,
+                &did_resolver,
+                &secrets_resolver,
+                &UnpackOptions {
+                    expect_decrypt_by_all_keys,
+                    ..UnpackOptions::default()
+                },
+            )
+            .await
+            .expect("Unable unpack");
+
+            assert_eq!(&unpacked, &*MESSAGE_SIMPLE);
+
+            assert_eq!(
+                metadata.encrypted_to_kids,
+                Some(vec![
+                    BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone(),
+                    BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id.clone(),
+                ])
+            );
+
+            assert_eq!(
+                metadata.decrypted_with_kid,
+                Some(BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone())
+            );
+
+            // Secret of each recipient is fetched (and used for decryption) once
+            assert_eq!(
+                secrets_resolver.get_secrets_calls(),
+                vec![vec![
+                    BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone(),
+                    BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id.clone(),
+                ]]
+            );
+        }
+
+        // Duplicated recipient with different encrypted key is rejected
+        let mut msg: Value = serde_json::from_str(&msg).expect("Unable from_str");
+        msg["recipients"][2]["encrypted_key"] = msg["recipients"][1]["encrypted_key"].clone();
+        let msg = msg.to_string();
+
+        let err = Message::unpack(
+            &msg,
+            &did_resolver,
+            &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        assert_eq!(
+            format!("{}", err),
+            "Malformed: Recipient `did:example:bob#key-x25519-1` presented multiple times with different encrypted keys"
+        );
+    }
+
+    #[tokio::test]
+    async fn unpack_works_anoncrypt_multiple_keys() {
+        let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+
+        // Secret of the first key doesn't match the key from DID Doc,
+        // so decryption by this key fails.
+        let mut wrong_secrets = BOB_SECRETS.clone();
+
+        wrong_secrets[0] = Secret {
+            id: BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone(),
+            ..BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.clone()
+        };
+
+        let good_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+        let wrong_secrets_resolver = ExampleSecretsResolver::new(wrong_secrets);
+
+        for _ in 0..10 {
+            let (msg, _) = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    BOB_DID,
+                    None,
+                    None,
+                    &did_resolver,
+                    &good_secrets_resolver,
+                    &PackEncryptedOptions {
+                        forward: false,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("Unable encrypt");
+
+            for expect_decrypt_by_all_keys in [false, true] {
+                let (unpacked, metadata) = Message::unpack(
+                    &msg,
+                    &did_resolver,
+                    &good_secrets_resolver,
+                    &UnpackOptions {
+                        expect_decrypt_by_all_keys,
+                        ..UnpackOptions::default()
+                    },
+                )
+                .await
+                .expect("Unable unpack");
+
+                assert_eq!(&unpacked, &*MESSAGE_SIMPLE);
+                assert_eq!(metadata.enc_alg_anon, Some(AnonCryptAlg::Xc20pEcdhEsA256kw));
+            }
+
+            // Failing key doesn't abort trial of other keys
+            let (unpacked, _) = Message::unpack(
+                &msg,
+                &did_resolver,
+                &wrong_secrets_resolver,
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect("Unable unpack");
+
+            assert_eq!(&unpacked, &*MESSAGE_SIMPLE);
+
+            let res = Message::unpack(
+                &msg,
+                &did_resolver,
+                &wrong_secrets_resolver,
+                &UnpackOptions {
+                    expect_decrypt_by_all_keys: true,
+                    ..UnpackOptions::default()
+                },
+            )
+            .await;
+
+            assert!(res.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_anoncrypted_2way() {
+        _unpack_works_anoncrypted_2way(
             &MESSAGE_SIMPLE,
             BOB_DID,
             &[
@@ -1363,14 +2782,11 @@ mod test {
                 &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
                 &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
             ],
-            ALICE_DID,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
-            AnonCryptAlg::A256gcmEcdhEsA256kw,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
         )
         .await;
 
-        _unpack_works_authcrypted_protected_sender_2way(
+        _unpack_works_anoncrypted_2way(
             &MESSAGE_SIMPLE,
             BOB_DID,
             &[
@@ -1378,96 +2794,143 @@ mod test {
                 &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
                 &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
             ],
-            ALICE_DID,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
             AnonCryptAlg::Xc20pEcdhEsA256kw,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
         )
         .await;
 
-        _unpack_works_authcrypted_protected_sender_2way(
+        _unpack_works_anoncrypted_2way(
             &MESSAGE_SIMPLE,
             &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
             &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
-            ALICE_DID,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
             AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
         )
         .await;
 
-        _unpack_works_authcrypted_protected_sender_2way(
+        _unpack_works_anoncrypted_2way(
             &MESSAGE_SIMPLE,
-            BOB_DID,
-            &[
-                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
-            ],
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
+            AnonCryptAlg::A256gcmEcdhEsA256kw,
         )
         .await;
 
-        _unpack_works_authcrypted_protected_sender_2way(
+        _unpack_works_anoncrypted_2way(
             &MESSAGE_SIMPLE,
-            BOB_DID,
-            &[
-                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
-            ],
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
+            AnonCryptAlg::Xc20pEcdhEsA256kw,
+        )
+        .await;
+
+        _unpack_works_anoncrypted_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+        )
+        .await;
+
+        _unpack_works_anoncrypted_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
             AnonCryptAlg::A256gcmEcdhEsA256kw,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
         )
         .await;
 
-        _unpack_works_authcrypted_protected_sender_2way(
+        _unpack_works_anoncrypted_2way(
             &MESSAGE_SIMPLE,
-            BOB_DID,
-            &[
-                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
-            ],
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
             AnonCryptAlg::Xc20pEcdhEsA256kw,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
         )
         .await;
 
-        _unpack_works_authcrypted_protected_sender_2way(
+        _unpack_works_anoncrypted_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+        )
+        .await;
+
+        _unpack_works_anoncrypted_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
+            AnonCryptAlg::A256gcmEcdhEsA256kw,
+        )
+        .await;
+
+        _unpack_works_anoncrypted_2way(
             &MESSAGE_SIMPLE,
             &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
             &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
-            ALICE_DID,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
             AnonCryptAlg::Xc20pEcdhEsA256kw,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
         )
         .await;
 
-        _unpack_works_authcrypted_protected_sender_2way(
+        _unpack_works_anoncrypted_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+        )
+        .await;
+
+        _unpack_works_anoncrypted_2way(
             &MESSAGE_SIMPLE,
             &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
             &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id],
-            ALICE_DID,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            AnonCryptAlg::A256gcmEcdhEsA256kw,
         )
         .await;
 
-        async fn _unpack_works_authcrypted_protected_sender_2way(
+        _unpack_works_anoncrypted_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id],
+            AnonCryptAlg::Xc20pEcdhEsA256kw,
+        )
+        .await;
+
+        _unpack_works_anoncrypted_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+        )
+        .await;
+
+        _unpack_works_anoncrypted_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
+            AnonCryptAlg::A128gcmEcdhEsA256kw,
+        )
+        .await;
+
+        _unpack_works_anoncrypted_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
+            AnonCryptAlg::A128cbcHs256EcdhEsA256kw,
+        )
+        .await;
+
+        _unpack_works_anoncrypted_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
+            AnonCryptAlg::A128gcmEcdhEsA256kw,
+        )
+        .await;
+
+        async fn _unpack_works_anoncrypted_2way(
             msg: &Message,
             to: &str,
             to_kids: &[&str],
-            from: &str,
-            from_kid: &str,
-            enc_alg_anon: AnonCryptAlg,
-            enc_alg_auth: AuthCryptAlg,
+            enc_alg: AnonCryptAlg,
         ) {
             let did_resolver =
                 ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
@@ -1477,38 +2940,50 @@ mod test {
             let (packed, _) = msg
                 .pack_encrypted(
                     to,
-                    Some(from),
+                    None,
                     None,
                     &did_resolver,
                     &secrets_resolver,
                     &PackEncryptedOptions {
                         forward: false,
-                        protect_sender: true,
-                        enc_alg_anon: enc_alg_anon.clone(),
+                        enc_alg_anon: enc_alg.clone(),
                         ..PackEncryptedOptions::default()
                     },
                 )
                 .await
                 .expect("Unable pack_encrypted");
 
-            _verify_unpack(
+            _verify_unpack_undeterministic(
                 &packed,
                 msg,
                 &UnpackMetadata {
                     sign_from: None,
+                    sign_from_kids: None,
                     sign_alg: None,
                     signed_message: None,
                     anonymous_sender: true,
-                    authenticated: true,
+                    authenticated: false,
                     non_repudiation: false,
                     encrypted: true,
-                    enc_alg_auth: Some(enc_alg_auth),
-                    enc_alg_anon: Some(enc_alg_anon),
-                    encrypted_from_kid: Some(from_kid.into()),
+                    enc_alg_auth: None,
+                    enc_alg_anon: Some(enc_alg),
+                    encrypted_from_kid: None,
                     encrypted_to_kids: Some(to_kids.iter().map(|&k| k.to_owned()).collect()),
+                    decrypted_with_kid: to_kids.first().map(|&k| k.to_owned()),
                     from_prior_issuer_kid: None,
                     from_prior: None,
                     re_wrapped_in_forward: false,
+                    signed_outside: false,
+                    epk_jwk: None,
+                    original_message: None,
+                    aad: None,
+                    signed_with_encryption_key: false,
+                    missing_recipient_kids: None,
+                    signature_verification_skipped: false,
+                    enc_typ: Some("application/didcomm-encrypted+json".into()),
+                    return_route: None,
+                    re_wrapped_forward_next: None,
+                    forward_chain: None,
                 },
             )
             .await;
@@ -1516,8 +2991,8 @@ mod test {
     }
 
     #[tokio::test]
-    async fn unpack_works_authcrypted_protected_sender_signed_2way() {
-        _unpack_works_authcrypted_protected_sender_signed_2way(
+    async fn pack_encrypted_works_anoncrypted_signed() {
+        _pack_encrypted_works_anoncrypted_signed(
             &MESSAGE_SIMPLE,
             BOB_DID,
             &[
@@ -1526,59 +3001,82 @@ mod test {
                 &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
             ],
             ALICE_DID,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
-            &ALICE_AUTH_METHOD_P256.id,
-            &ALICE_AUTH_METHOD_P256.id,
+            &ALICE_AUTH_METHOD_25519.id,
             AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
-            SignAlg::ES256,
+            SignAlg::EdDSA,
         )
         .await;
 
-        _unpack_works_authcrypted_protected_sender_signed_2way(
+        _pack_encrypted_works_anoncrypted_signed(
             &MESSAGE_SIMPLE,
             BOB_DID,
             &[
-                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
             ],
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
-            &ALICE_AUTH_METHOD_25519.id,
+            ALICE_DID,
             &ALICE_AUTH_METHOD_25519.id,
-            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            AnonCryptAlg::A256gcmEcdhEsA256kw,
             SignAlg::EdDSA,
         )
         .await;
 
-        _unpack_works_authcrypted_protected_sender_signed_2way(
+        _pack_encrypted_works_anoncrypted_signed(
             &MESSAGE_SIMPLE,
             BOB_DID,
             &[
-                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
             ],
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            ALICE_DID,
+            &ALICE_AUTH_METHOD_25519.id,
+            AnonCryptAlg::Xc20pEcdhEsA256kw,
+            SignAlg::EdDSA,
+        )
+        .await;
+
+        _pack_encrypted_works_anoncrypted_signed(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
+            &ALICE_AUTH_METHOD_25519.id,
+            &ALICE_AUTH_METHOD_25519.id,
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+            SignAlg::EdDSA,
+        )
+        .await;
+
+        _pack_encrypted_works_anoncrypted_signed(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
+            &ALICE_AUTH_METHOD_P256.id,
+            &ALICE_AUTH_METHOD_P256.id,
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+            SignAlg::ES256,
+        )
+        .await;
+
+        _pack_encrypted_works_anoncrypted_signed(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
             &ALICE_AUTH_METHOD_SECPP256K1.id,
             &ALICE_AUTH_METHOD_SECPP256K1.id,
             AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
             SignAlg::ES256K,
         )
         .await;
 
-        async fn _unpack_works_authcrypted_protected_sender_signed_2way(
+        async fn _pack_encrypted_works_anoncrypted_signed(
             msg: &Message,
             to: &str,
             to_kids: &[&str],
-            from: &str,
-            from_kid: &str,
             sign_by: &str,
             sign_by_kid: &str,
-            enc_alg_anon: AnonCryptAlg,
-            enc_alg_auth: AuthCryptAlg,
+            enc_alg: AnonCryptAlg,
             sign_alg: SignAlg,
         ) {
             let did_resolver =
@@ -1589,14 +3087,13 @@ mod test {
             let (packed, _) = msg
                 .pack_encrypted(
                     to,
-                    Some(from),
+                    None,
                     Some(sign_by),
                     &did_resolver,
                     &secrets_resolver,
                     &PackEncryptedOptions {
                         forward: false,
-                        protect_sender: true,
-                        enc_alg_anon: enc_alg_anon.clone(),
+                        enc_alg_anon: enc_alg.clone(),
                         ..PackEncryptedOptions::default()
                     },
                 )
@@ -1608,19 +3105,32 @@ mod test {
                 msg,
                 &UnpackMetadata {
                     sign_from: Some(sign_by_kid.into()),
+                    sign_from_kids: Some(vec![sign_by_kid.into()]),
                     sign_alg: Some(sign_alg),
-                    signed_message: Some("nondeterministic".into()),
+                    signed_message: None,
                     anonymous_sender: true,
                     authenticated: true,
                     non_repudiation: true,
                     encrypted: true,
-                    enc_alg_auth: Some(enc_alg_auth),
-                    enc_alg_anon: Some(enc_alg_anon),
-                    encrypted_from_kid: Some(from_kid.into()),
+                    enc_alg_auth: None,
+                    enc_alg_anon: Some(enc_alg),
+                    encrypted_from_kid: None,
                     encrypted_to_kids: Some(to_kids.iter().map(|&k| k.to_owned()).collect()),
+                    decrypted_with_kid: to_kids.first().map(|&k| k.to_owned()),
                     from_prior_issuer_kid: None,
                     from_prior: None,
                     re_wrapped_in_forward: false,
+                    signed_outside: false,
+                    epk_jwk: None,
+                    original_message: None,
+                    aad: None,
+                    signed_with_encryption_key: false,
+                    missing_recipient_kids: None,
+                    signature_verification_skipped: false,
+                    enc_typ: Some("application/didcomm-encrypted+json".into()),
+                    return_route: None,
+                    re_wrapped_forward_next: None,
+                    forward_chain: None,
                 },
             )
             .await;
@@ -1628,51 +3138,186 @@ mod test {
     }
 
     #[tokio::test]
-    async fn unpack_works_authcrypted_signed_2way() {
-        _unpack_works_authcrypted_signed_2way(
-            &MESSAGE_SIMPLE,
-            BOB_DID,
-            &[
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
-            ],
-            ALICE_DID,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
-            ALICE_DID,
-            &ALICE_AUTH_METHOD_25519.id,
-            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
-            SignAlg::EdDSA,
-        )
-        .await;
+    async fn unpack_works_authcrypt() {
+        let metadata = UnpackMetadata {
+            anonymous_sender: false,
+            authenticated: true,
+            non_repudiation: false,
+            encrypted: true,
+            enc_alg_auth: None,
+            enc_alg_anon: None,
+            sign_alg: None,
+            encrypted_from_kid: None,
+            encrypted_to_kids: None,
+            decrypted_with_kid: None,
+            sign_from: None,
+            sign_from_kids: None,
+            signed_message: None,
+            from_prior_issuer_kid: None,
+            from_prior: None,
+            re_wrapped_in_forward: false,
+            signed_outside: false,
+            epk_jwk: None,
+            original_message: None,
+            aad: None,
+            signed_with_encryption_key: false,
+            missing_recipient_kids: None,
+            signature_verification_skipped: false,
+            enc_typ: Some("application/didcomm-encrypted+json".into()),
+            return_route: None,
+            re_wrapped_forward_next: None,
+            forward_chain: None,
+        };
 
-        _unpack_works_authcrypted_signed_2way(
+        _verify_unpack(
+            ENCRYPTED_MSG_AUTH_X25519,
             &MESSAGE_SIMPLE,
-            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
-            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
-            ALICE_DID,
-            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
-            &ALICE_AUTH_METHOD_25519.id,
-            &ALICE_AUTH_METHOD_25519.id,
+            &UnpackMetadata {
+                enc_alg_auth: Some(AuthCryptAlg::A256cbcHs512Ecdh1puA256kw),
+                encrypted_from_kid: Some("did:example:alice#key-x25519-1".into()),
+                encrypted_to_kids: Some(vec![
+                    "did:example:bob#key-x25519-1".into(),
+                    "did:example:bob#key-x25519-2".into(),
+                    "did:example:bob#key-x25519-3".into(),
+                ]),
+                decrypted_with_kid: Some("did:example:bob#key-x25519-1".into()),
+                epk_jwk: Some(json!({
+                    "kty": "OKP",
+                    "crv": "X25519",
+                    "x": "GFcMopJljf4pLZfch4a_GhTM_YAf6iNI1dWDGyVCaw0",
+                })),
+                ..metadata.clone()
+            },
+        )
+        .await;
+
+        _verify_unpack(
+            ENCRYPTED_MSG_AUTH_P256,
+            &MESSAGE_SIMPLE,
+            &UnpackMetadata {
+                enc_alg_auth: Some(AuthCryptAlg::A256cbcHs512Ecdh1puA256kw),
+                encrypted_from_kid: Some("did:example:alice#key-p256-1".into()),
+                encrypted_to_kids: Some(vec![
+                    "did:example:bob#key-p256-1".into(),
+                    "did:example:bob#key-p256-2".into(),
+                ]),
+                decrypted_with_kid: Some("did:example:bob#key-p256-1".into()),
+                non_repudiation: true,
+                sign_from: Some("did:example:alice#key-1".into()),
+                sign_from_kids: Some(vec!["did:example:alice#key-1".into()]),
+                sign_alg: Some(SignAlg::EdDSA),
+                signed_message: Some(ENCRYPTED_MSG_AUTH_P256_SIGNED.into()),
+                epk_jwk: Some(json!({
+                    "kty": "EC",
+                    "crv": "P-256",
+                    "x": "NlrwPvtIIneciyEka4s2-4s8OjTbtFEAXfL-vglyzqo",
+                    "y": "hb2vdXNsK5BCe7-XZCG_-64GmTO_k5IMXPZCM1taTBg",
+                })),
+                ..metadata.clone()
+            },
+        )
+        .await;
+
+        _verify_unpack(
+            ENCRYPTED_MSG_AUTH_X25519_PROTECTED_SENDER,
+            &MESSAGE_SIMPLE,
+            &UnpackMetadata {
+                anonymous_sender: true,
+                enc_alg_auth: Some(AuthCryptAlg::A256cbcHs512Ecdh1puA256kw),
+                enc_alg_anon: Some(AnonCryptAlg::A256gcmEcdhEsA256kw),
+                encrypted_from_kid: Some("did:example:alice#key-x25519-1".into()),
+                encrypted_to_kids: Some(vec![
+                    "did:example:bob#key-x25519-1".into(),
+                    "did:example:bob#key-x25519-2".into(),
+                    "did:example:bob#key-x25519-3".into(),
+                ]),
+                decrypted_with_kid: Some("did:example:bob#key-x25519-1".into()),
+                epk_jwk: Some(json!({
+                    "kty": "OKP",
+                    "crv": "X25519",
+                    "x": "GFcMopJljf4pLZfch4a_GhTM_YAf6iNI1dWDGyVCaw0",
+                })),
+                ..metadata.clone()
+            },
+        )
+        .await;
+
+        // TODO: Check P-384 curve support
+        // TODO: Check P-521 curve support
+    }
+
+    #[tokio::test]
+    async fn unpack_works_authcrypt_sender_recipient_curve_mismatch() {
+        // Sender key agreement key is declared with P-256 material while the message
+        // was encrypted to X25519 recipient keys
+        let mut alice_did_doc = ALICE_DID_DOC.clone();
+
+        alice_did_doc
+            .verification_methods
+            .iter_mut()
+            .filter(|vm| vm.id == ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id)
+            .for_each(|vm| {
+                vm.verification_material = ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256
+                    .verification_material
+                    .clone()
+            });
+
+        let did_resolver = ExampleDIDResolver::new(vec![alice_did_doc, BOB_DID_DOC.clone()]);
+
+        let err = Message::unpack(
+            ENCRYPTED_MSG_AUTH_X25519,
+            &did_resolver,
+            &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        assert_eq!(
+            format!("{}", err),
+            "Malformed: Sender key curve P-256 does not match recipient key curve X25519"
+        );
+    }
+
+    #[tokio::test]
+    async fn unpack_works_authcrypted_2way() {
+        _unpack_works_authcrypted_2way(
+            &MESSAGE_SIMPLE,
+            BOB_DID,
+            &[
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
+            ],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
             AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
-            SignAlg::EdDSA,
         )
         .await;
 
-        _unpack_works_authcrypted_signed_2way(
+        _unpack_works_authcrypted_2way(
             &MESSAGE_SIMPLE,
             &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
             &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
             ALICE_DID,
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
-            &ALICE_AUTH_METHOD_P256.id,
-            &ALICE_AUTH_METHOD_P256.id,
             AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
-            SignAlg::ES256,
         )
         .await;
 
-        _unpack_works_authcrypted_signed_2way(
+        _unpack_works_authcrypted_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+        )
+        .await;
+
+        _unpack_works_authcrypted_2way(
             &MESSAGE_SIMPLE,
             BOB_DID,
             &[
@@ -1681,339 +3326,2687 @@ mod test {
             ],
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
             &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
-            &ALICE_AUTH_METHOD_SECPP256K1.id,
-            &ALICE_AUTH_METHOD_SECPP256K1.id,
             AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
-            SignAlg::ES256K,
         )
         .await;
 
-        async fn _unpack_works_authcrypted_signed_2way(
+        _unpack_works_authcrypted_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+        )
+        .await;
+
+        _unpack_works_authcrypted_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+        )
+        .await;
+
+        _unpack_works_authcrypted_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+        )
+        .await;
+
+        _unpack_works_authcrypted_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
+            AuthCryptAlg::A128cbcHs256Ecdh1puA256kw,
+        )
+        .await;
+
+        _unpack_works_authcrypted_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            AuthCryptAlg::A128cbcHs256Ecdh1puA256kw,
+        )
+        .await;
+
+        async fn _unpack_works_authcrypted_2way(
             msg: &Message,
             to: &str,
             to_kids: &[&str],
             from: &str,
             from_kid: &str,
-            sign_by: &str,
-            sign_by_kid: &str,
             enc_alg: AuthCryptAlg,
-            sign_alg: SignAlg,
         ) {
             let did_resolver =
                 ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
 
-            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+            let (packed, _) = msg
+                .pack_encrypted(
+                    to,
+                    Some(from),
+                    None,
+                    &did_resolver,
+                    &secrets_resolver,
+                    &PackEncryptedOptions {
+                        forward: false,
+                        enc_alg_auth: enc_alg.clone(),
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("Unable pack_encrypted");
+
+            _verify_unpack_undeterministic(
+                &packed,
+                msg,
+                &UnpackMetadata {
+                    sign_from: None,
+                    sign_from_kids: None,
+                    sign_alg: None,
+                    signed_message: None,
+                    anonymous_sender: false,
+                    authenticated: true,
+                    non_repudiation: false,
+                    encrypted: true,
+                    enc_alg_auth: Some(enc_alg),
+                    enc_alg_anon: None,
+                    encrypted_from_kid: Some(from_kid.into()),
+                    encrypted_to_kids: Some(to_kids.iter().map(|&k| k.to_owned()).collect()),
+                    decrypted_with_kid: to_kids.first().map(|&k| k.to_owned()),
+                    from_prior_issuer_kid: None,
+                    from_prior: None,
+                    re_wrapped_in_forward: false,
+                    signed_outside: false,
+                    epk_jwk: None,
+                    original_message: None,
+                    aad: None,
+                    signed_with_encryption_key: false,
+                    missing_recipient_kids: None,
+                    signature_verification_skipped: false,
+                    enc_typ: Some("application/didcomm-encrypted+json".into()),
+                    return_route: None,
+                    re_wrapped_forward_next: None,
+                    forward_chain: None,
+                },
+            )
+            .await;
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_authcrypted_protected_sender_2way() {
+        _unpack_works_authcrypted_protected_sender_2way(
+            &MESSAGE_SIMPLE,
+            BOB_DID,
+            &[
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
+            ],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+        )
+        .await;
+
+        _unpack_works_authcrypted_protected_sender_2way(
+            &MESSAGE_SIMPLE,
+            BOB_DID,
+            &[
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
+            ],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
+            AnonCryptAlg::A256gcmEcdhEsA256kw,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+        )
+        .await;
+
+        _unpack_works_authcrypted_protected_sender_2way(
+            &MESSAGE_SIMPLE,
+            BOB_DID,
+            &[
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
+            ],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
+            AnonCryptAlg::Xc20pEcdhEsA256kw,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+        )
+        .await;
+
+        _unpack_works_authcrypted_protected_sender_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+        )
+        .await;
+
+        _unpack_works_authcrypted_protected_sender_2way(
+            &MESSAGE_SIMPLE,
+            BOB_DID,
+            &[
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
+            ],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+        )
+        .await;
+
+        _unpack_works_authcrypted_protected_sender_2way(
+            &MESSAGE_SIMPLE,
+            BOB_DID,
+            &[
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
+            ],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            AnonCryptAlg::A256gcmEcdhEsA256kw,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+        )
+        .await;
+
+        _unpack_works_authcrypted_protected_sender_2way(
+            &MESSAGE_SIMPLE,
+            BOB_DID,
+            &[
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
+            ],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            AnonCryptAlg::Xc20pEcdhEsA256kw,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+        )
+        .await;
+
+        _unpack_works_authcrypted_protected_sender_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            AnonCryptAlg::Xc20pEcdhEsA256kw,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+        )
+        .await;
+
+        _unpack_works_authcrypted_protected_sender_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+        )
+        .await;
+
+        async fn _unpack_works_authcrypted_protected_sender_2way(
+            msg: &Message,
+            to: &str,
+            to_kids: &[&str],
+            from: &str,
+            from_kid: &str,
+            enc_alg_anon: AnonCryptAlg,
+            enc_alg_auth: AuthCryptAlg,
+        ) {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+            let (packed, _) = msg
+                .pack_encrypted(
+                    to,
+                    Some(from),
+                    None,
+                    &did_resolver,
+                    &secrets_resolver,
+                    &PackEncryptedOptions {
+                        forward: false,
+                        protect_sender: true,
+                        enc_alg_anon: enc_alg_anon.clone(),
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("Unable pack_encrypted");
+
+            _verify_unpack_undeterministic(
+                &packed,
+                msg,
+                &UnpackMetadata {
+                    sign_from: None,
+                    sign_from_kids: None,
+                    sign_alg: None,
+                    signed_message: None,
+                    anonymous_sender: true,
+                    authenticated: true,
+                    non_repudiation: false,
+                    encrypted: true,
+                    enc_alg_auth: Some(enc_alg_auth),
+                    enc_alg_anon: Some(enc_alg_anon),
+                    encrypted_from_kid: Some(from_kid.into()),
+                    encrypted_to_kids: Some(to_kids.iter().map(|&k| k.to_owned()).collect()),
+                    decrypted_with_kid: to_kids.first().map(|&k| k.to_owned()),
+                    from_prior_issuer_kid: None,
+                    from_prior: None,
+                    re_wrapped_in_forward: false,
+                    signed_outside: false,
+                    epk_jwk: None,
+                    original_message: None,
+                    aad: None,
+                    signed_with_encryption_key: false,
+                    missing_recipient_kids: None,
+                    signature_verification_skipped: false,
+                    enc_typ: Some("application/didcomm-encrypted+json".into()),
+                    return_route: None,
+                    re_wrapped_forward_next: None,
+                    forward_chain: None,
+                },
+            )
+            .await;
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_authcrypted_protected_sender_signed_2way() {
+        _unpack_works_authcrypted_protected_sender_signed_2way(
+            &MESSAGE_SIMPLE,
+            BOB_DID,
+            &[
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
+            ],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
+            &ALICE_AUTH_METHOD_P256.id,
+            &ALICE_AUTH_METHOD_P256.id,
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            SignAlg::ES256,
+        )
+        .await;
+
+        _unpack_works_authcrypted_protected_sender_signed_2way(
+            &MESSAGE_SIMPLE,
+            BOB_DID,
+            &[
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
+            ],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_AUTH_METHOD_25519.id,
+            &ALICE_AUTH_METHOD_25519.id,
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            SignAlg::EdDSA,
+        )
+        .await;
+
+        _unpack_works_authcrypted_protected_sender_signed_2way(
+            &MESSAGE_SIMPLE,
+            BOB_DID,
+            &[
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
+            ],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_AUTH_METHOD_SECPP256K1.id,
+            &ALICE_AUTH_METHOD_SECPP256K1.id,
+            AnonCryptAlg::A256cbcHs512EcdhEsA256kw,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            SignAlg::ES256K,
+        )
+        .await;
+
+        async fn _unpack_works_authcrypted_protected_sender_signed_2way(
+            msg: &Message,
+            to: &str,
+            to_kids: &[&str],
+            from: &str,
+            from_kid: &str,
+            sign_by: &str,
+            sign_by_kid: &str,
+            enc_alg_anon: AnonCryptAlg,
+            enc_alg_auth: AuthCryptAlg,
+            sign_alg: SignAlg,
+        ) {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+            let (packed, _) = msg
+                .pack_encrypted(
+                    to,
+                    Some(from),
+                    Some(sign_by),
+                    &did_resolver,
+                    &secrets_resolver,
+                    &PackEncryptedOptions {
+                        forward: false,
+                        protect_sender: true,
+                        enc_alg_anon: enc_alg_anon.clone(),
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("Unable pack_encrypted");
+
+            _verify_unpack_undeterministic(
+                &packed,
+                msg,
+                &UnpackMetadata {
+                    sign_from: Some(sign_by_kid.into()),
+                    sign_from_kids: Some(vec![sign_by_kid.into()]),
+                    sign_alg: Some(sign_alg),
+                    signed_message: Some("nondeterministic".into()),
+                    anonymous_sender: true,
+                    authenticated: true,
+                    non_repudiation: true,
+                    encrypted: true,
+                    enc_alg_auth: Some(enc_alg_auth),
+                    enc_alg_anon: Some(enc_alg_anon),
+                    encrypted_from_kid: Some(from_kid.into()),
+                    encrypted_to_kids: Some(to_kids.iter().map(|&k| k.to_owned()).collect()),
+                    decrypted_with_kid: to_kids.first().map(|&k| k.to_owned()),
+                    from_prior_issuer_kid: None,
+                    from_prior: None,
+                    re_wrapped_in_forward: false,
+                    signed_outside: false,
+                    epk_jwk: None,
+                    original_message: None,
+                    aad: None,
+                    signed_with_encryption_key: false,
+                    missing_recipient_kids: None,
+                    signature_verification_skipped: false,
+                    enc_typ: Some("application/didcomm-encrypted+json".into()),
+                    return_route: None,
+                    re_wrapped_forward_next: None,
+                    forward_chain: None,
+                },
+            )
+            .await;
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_authcrypted_signed_2way() {
+        _unpack_works_authcrypted_signed_2way(
+            &MESSAGE_SIMPLE,
+            BOB_DID,
+            &[
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id,
+            ],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
+            ALICE_DID,
+            &ALICE_AUTH_METHOD_25519.id,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            SignAlg::EdDSA,
+        )
+        .await;
+
+        _unpack_works_authcrypted_signed_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
+            &ALICE_AUTH_METHOD_25519.id,
+            &ALICE_AUTH_METHOD_25519.id,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            SignAlg::EdDSA,
+        )
+        .await;
+
+        _unpack_works_authcrypted_signed_2way(
+            &MESSAGE_SIMPLE,
+            &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id,
+            &[&BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id],
+            ALICE_DID,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id,
+            &ALICE_AUTH_METHOD_P256.id,
+            &ALICE_AUTH_METHOD_P256.id,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            SignAlg::ES256,
+        )
+        .await;
+
+        _unpack_works_authcrypted_signed_2way(
+            &MESSAGE_SIMPLE,
+            BOB_DID,
+            &[
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_1.id,
+                &BOB_SECRET_KEY_AGREEMENT_KEY_P256_2.id,
+            ],
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.id,
+            &ALICE_AUTH_METHOD_SECPP256K1.id,
+            &ALICE_AUTH_METHOD_SECPP256K1.id,
+            AuthCryptAlg::A256cbcHs512Ecdh1puA256kw,
+            SignAlg::ES256K,
+        )
+        .await;
+
+        async fn _unpack_works_authcrypted_signed_2way(
+            msg: &Message,
+            to: &str,
+            to_kids: &[&str],
+            from: &str,
+            from_kid: &str,
+            sign_by: &str,
+            sign_by_kid: &str,
+            enc_alg: AuthCryptAlg,
+            sign_alg: SignAlg,
+        ) {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+            let (packed, _) = msg
+                .pack_encrypted(
+                    to,
+                    Some(from),
+                    Some(sign_by),
+                    &did_resolver,
+                    &secrets_resolver,
+                    &PackEncryptedOptions {
+                        forward: false,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("encrypt is ok.");
+
+            _verify_unpack_undeterministic(
+                &packed,
+                msg,
+                &UnpackMetadata {
+                    sign_from: Some(sign_by_kid.into()),
+                    sign_from_kids: Some(vec![sign_by_kid.into()]),
+                    sign_alg: Some(sign_alg),
+                    signed_message: Some("nondeterministic".into()),
+                    anonymous_sender: false,
+                    authenticated: true,
+                    non_repudiation: true,
+                    encrypted: true,
+                    enc_alg_auth: Some(enc_alg),
+                    enc_alg_anon: None,
+                    encrypted_from_kid: Some(from_kid.into()),
+                    encrypted_to_kids: Some(to_kids.iter().map(|&k| k.to_owned()).collect()),
+                    decrypted_with_kid: to_kids.first().map(|&k| k.to_owned()),
+                    from_prior_issuer_kid: None,
+                    from_prior: None,
+                    re_wrapped_in_forward: false,
+                    signed_outside: false,
+                    epk_jwk: None,
+                    original_message: None,
+                    aad: None,
+                    signed_with_encryption_key: false,
+                    missing_recipient_kids: None,
+                    signature_verification_skipped: false,
+                    enc_typ: Some("application/didcomm-encrypted+json".into()),
+                    return_route: None,
+                    re_wrapped_forward_next: None,
+                    forward_chain: None,
+                },
+            )
+            .await;
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_sign_outside() {
+        _unpack_works_sign_outside_2way(
+            Some(&ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id),
+            false,
+        )
+        .await;
+
+        _unpack_works_sign_outside_2way(
+            Some(&ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.id),
+            true,
+        )
+        .await;
+
+        _unpack_works_sign_outside_2way(None, false).await;
+
+        _unpack_works_sign_outside_2way(None, true).await;
+
+        async fn _unpack_works_sign_outside_2way(from: Option<&str>, sign_outside: bool) {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+            let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+            let (packed, pack_metadata) = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    &BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id,
+                    from,
+                    Some(&ALICE_AUTH_METHOD_25519.id),
+                    &did_resolver,
+                    &secrets_resolver,
+                    &PackEncryptedOptions {
+                        forward: false,
+                        sign_outside,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("encrypt is ok.");
+
+            assert_eq!(
+                pack_metadata.sign_by_kid.as_deref(),
+                Some(ALICE_AUTH_METHOD_25519.id.as_str())
+            );
+
+            assert_eq!(pack_metadata.sign_alg, Some(SignAlg::EdDSA));
+
+            let packed_json: Value = serde_json::from_str(&packed).expect("Unable parse packed");
+            assert_eq!(packed_json.get("signatures").is_some(), sign_outside);
+            assert_eq!(packed_json.get("ciphertext").is_some(), !sign_outside);
+
+            let did_resolver = ExampleDIDResolver::new(vec![
+                ALICE_DID_DOC.clone(),
+                BOB_DID_DOC.clone(),
+                CHARLIE_DID_DOC.clone(),
+            ]);
+
+            let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+            let (msg, metadata) = Message::unpack(
+                &packed,
+                &did_resolver,
+                &secrets_resolver,
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect("unpack is ok.");
+
+            assert_eq!(&msg, &*MESSAGE_SIMPLE);
+
+            // Signed message is the packed message itself if the encrypted message is signed
+            let signed_message = metadata.signed_message.clone().expect("No signed message");
+            assert_eq!(signed_message == packed, sign_outside);
+
+            assert_eq!(
+                metadata,
+                UnpackMetadata {
+                    sign_from: Some(ALICE_AUTH_METHOD_25519.id.clone()),
+                    sign_from_kids: Some(vec![ALICE_AUTH_METHOD_25519.id.clone()]),
+                    sign_alg: Some(SignAlg::EdDSA),
+                    signed_message: Some(signed_message),
+                    anonymous_sender: from.is_none(),
+                    authenticated: true,
+                    non_repudiation: true,
+                    encrypted: true,
+                    enc_alg_auth: from.map(|_| AuthCryptAlg::A256cbcHs512Ecdh1puA256kw),
+                    enc_alg_anon: match from {
+                        Some(_) => None,
+                        None => Some(AnonCryptAlg::default()),
+                    },
+                    encrypted_from_kid: from.map(|from| from.to_owned()),
+                    encrypted_to_kids: Some(vec![BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone()]),
+                    decrypted_with_kid: Some(BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone()),
+                    from_prior_issuer_kid: None,
+                    from_prior: None,
+                    re_wrapped_in_forward: false,
+                    signed_outside: sign_outside,
+                    epk_jwk: metadata.epk_jwk.clone(),
+                    original_message: None,
+                    aad: None,
+                    signed_with_encryption_key: false,
+                    missing_recipient_kids: None,
+                    signature_verification_skipped: false,
+                    enc_typ: Some("application/didcomm-encrypted+json".into()),
+                    return_route: None,
+                    re_wrapped_forward_next: None,
+                    forward_chain: None,
+                }
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_decrypted_with_kid() {
+        _unpack_works_decrypted_with_kid(None).await;
+        _unpack_works_decrypted_with_kid(Some(ALICE_DID)).await;
+
+        async fn _unpack_works_decrypted_with_kid(from: Option<&str>) {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+            let (packed, _) = MESSAGE_SIMPLE
+                .pack_encrypted(
+                    BOB_DID,
+                    from,
+                    None,
+                    &did_resolver,
+                    &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
+                    &PackEncryptedOptions {
+                        forward: false,
+                        ..PackEncryptedOptions::default()
+                    },
+                )
+                .await
+                .expect("pack_encrypted is ok.");
+
+            // Only the second of recipient keys is known
+            let secrets_resolver =
+                ExampleSecretsResolver::new(vec![BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.clone()]);
+
+            let (msg, metadata) = Message::unpack(
+                &packed,
+                &did_resolver,
+                &secrets_resolver,
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect("unpack is ok.");
+
+            assert_eq!(&msg, &*MESSAGE_SIMPLE);
+
+            assert_eq!(
+                metadata.encrypted_to_kids,
+                Some(vec![
+                    BOB_SECRET_KEY_AGREEMENT_KEY_X25519_1.id.clone(),
+                    BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id.clone(),
+                    BOB_SECRET_KEY_AGREEMENT_KEY_X25519_3.id.clone(),
+                ])
+            );
+
+            assert_eq!(
+                metadata.decrypted_with_kid,
+                Some(BOB_SECRET_KEY_AGREEMENT_KEY_X25519_2.id.clone())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_retain_original() {
+        _unpack_works_retain_original(PLAINTEXT_MSG_SIMPLE).await;
+        _unpack_works_retain_original(SIGNED_MSG_ALICE_KEY_1).await;
+        _unpack_works_retain_original(ENCRYPTED_MSG_ANON_XC20P_1).await;
+        _unpack_works_retain_original(ENCRYPTED_MSG_AUTH_X25519).await;
+
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let (packed, _) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                Some(ALICE_DID),
+                Some(ALICE_DID),
+                &did_resolver,
+                &secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    sign_outside: true,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("encrypt is ok.");
+
+        _unpack_works_retain_original(&packed).await;
+
+        async fn _unpack_works_retain_original(msg: &str) {
+            let did_resolver = ExampleDIDResolver::new(vec![
+                ALICE_DID_DOC.clone(),
+                BOB_DID_DOC.clone(),
+                CHARLIE_DID_DOC.clone(),
+            ]);
+
+            let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+            let (_, metadata) = Message::unpack(
+                msg,
+                &did_resolver,
+                &secrets_resolver,
+                &UnpackOptions {
+                    retain_original: true,
+                    ..UnpackOptions::default()
+                },
+            )
+            .await
+            .expect("unpack is ok.");
+
+            assert_eq!(metadata.original_message.as_deref(), Some(msg));
+
+            let (_, metadata) = Message::unpack(
+                msg,
+                &did_resolver,
+                &secrets_resolver,
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect("unpack is ok.");
+
+            assert_eq!(metadata.original_message, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_require_encrypted() {
+        let options = UnpackOptions {
+            require_encrypted: true,
+            ..UnpackOptions::default()
+        };
+
+        _unpack_works_required(PLAINTEXT_MSG_SIMPLE, &options, false).await;
+        _unpack_works_required(SIGNED_MSG_ALICE_KEY_1, &options, false).await;
+        _unpack_works_required(ENCRYPTED_MSG_ANON_XC20P_1, &options, true).await;
+        _unpack_works_required(ENCRYPTED_MSG_AUTH_X25519, &options, true).await;
+    }
+
+    #[tokio::test]
+    async fn unpack_works_require_signed() {
+        let options = UnpackOptions {
+            require_signed: true,
+            ..UnpackOptions::default()
+        };
+
+        _unpack_works_required(PLAINTEXT_MSG_SIMPLE, &options, false).await;
+        _unpack_works_required(SIGNED_MSG_ALICE_KEY_1, &options, true).await;
+        _unpack_works_required(ENCRYPTED_MSG_ANON_XC20P_1, &options, false).await;
+        _unpack_works_required(ENCRYPTED_MSG_AUTH_X25519, &options, false).await;
+    }
+
+    async fn _unpack_works_required(msg: &str, options: &UnpackOptions, exp_trusted: bool) {
+        let did_resolver = ExampleDIDResolver::new(vec![
+            ALICE_DID_DOC.clone(),
+            BOB_DID_DOC.clone(),
+            CHARLIE_DID_DOC.clone(),
+        ]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let res = Message::unpack(msg, &did_resolver, &secrets_resolver, options).await;
+
+        if exp_trusted {
+            let (msg, _) = res.expect("unpack is ok.");
+            assert_eq!(msg, *MESSAGE_SIMPLE);
+        } else {
+            let err = res.expect_err("res is ok");
+            assert_eq!(err.kind(), ErrorKind::Untrusted);
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_sign_outside_re_wrapped_in_forward() {
+        let did_resolver = ExampleDIDResolver::new(vec![
+            ALICE_DID_DOC.clone(),
+            BOB_DID_DOC.clone(),
+            MEDIATOR1_DID_DOC.clone(),
+        ]);
+
+        let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let mediator1_secrets_resolver = ExampleSecretsResolver::new(MEDIATOR1_SECRETS.clone());
+
+        let (msg, pack_metadata) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                Some(ALICE_DID),
+                Some(ALICE_DID),
+                &did_resolver,
+                &alice_secrets_resolver,
+                &PackEncryptedOptions {
+                    sign_outside: true,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("Unable encrypt");
+
+        assert!(pack_metadata.forwarded);
+
+        let (unpacked_msg_mediator1, unpack_metadata_mediator1) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &mediator1_secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
+
+        let forward = try_parse_forward(&unpacked_msg_mediator1).expect("Message is not Forward");
+
+        assert!(!unpack_metadata_mediator1.non_repudiation);
+        assert!(!unpack_metadata_mediator1.signed_outside);
+
+        let forwarded_msg = serde_json::to_string(&forward.forwarded_msg)
+            .expect("Unable serialize forwarded message");
+
+        let re_wrapping_forward_msg = wrap_in_forward(
+            &forwarded_msg,
+            None,
+            BOB_DID,
+            &vec![BOB_DID.to_owned()],
+            &AnonCryptAlg::default(),
+            &did_resolver,
+        )
+        .await
+        .expect("Unable wrap in forward");
+
+        let (unpacked_msg, unpack_metadata) = Message::unpack(
+            &re_wrapping_forward_msg,
+            &did_resolver,
+            &bob_secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("Unable unpack");
+
+        assert_eq!(&unpacked_msg, &*MESSAGE_SIMPLE);
+        assert!(unpack_metadata.re_wrapped_in_forward);
+        assert!(unpack_metadata.signed_outside);
+        assert!(unpack_metadata.non_repudiation);
+        assert!(unpack_metadata.authenticated);
+        assert!(!unpack_metadata.anonymous_sender);
+        assert_eq!(unpack_metadata.sign_from, pack_metadata.sign_by_kid);
+        assert_eq!(
+            unpack_metadata.signed_message.as_ref(),
+            Some(&forwarded_msg)
+        );
+    }
+
+    #[tokio::test]
+    async fn unpack_works_unsupported_key_wrapping_alg() {
+        _verify_unpack_returns_error(
+            &update_protected_field(ENCRYPTED_MSG_ANON_XC20P_1, "alg", "ECDH-ES+A128KW"),
+            ErrorKind::Unsupported,
+            "Unsupported crypto or method: Unsupported alg `ECDH-ES+A128KW`",
+        )
+        .await;
+
+        _verify_unpack_returns_error(
+            &update_protected_field(ENCRYPTED_MSG_AUTH_X25519, "alg", "ECDH-1PU+A128KW"),
+            ErrorKind::Unsupported,
+            "Unsupported crypto or method: Unsupported alg `ECDH-1PU+A128KW`",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn unpack_works_authcrypt_apu_skid_mismatch() {
+        _verify_unpack_malformed(
+            &update_protected_field(
+                ENCRYPTED_MSG_AUTH_X25519,
+                "apu",
+                &base64::encode_config("did:example:alice#key-p256-1", base64::URL_SAFE_NO_PAD),
+            ),
+            "Malformed: APU mismatch: apu `did:example:alice#key-p256-1` \
+            doesn't match skid `did:example:alice#key-x25519-1`",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            &update_protected_field(
+                ENCRYPTED_MSG_AUTH_X25519,
+                "skid",
+                "did:example:charlie#key-x25519-1",
+            ),
+            "Malformed: APU mismatch: apu `did:example:alice#key-x25519-1` \
+            doesn't match skid `did:example:charlie#key-x25519-1`",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn unpack_works_invalid_epk_point() {
+        _verify_unpack_malformed(
+            &INVALID_ENCRYPTED_MSG_ANON_P256_EPK_WRONG_POINT,
+            "Malformed: Unable instantiate epk: Unable produce jwk: Invalid key data",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn unpack_works_malformed_anoncrypt_msg() {
+        _verify_unpack_malformed(
+            update_field(ENCRYPTED_MSG_ANON_XC20P_1, "protected", "invalid").as_str(),
+            "Malformed: Unable decode protected header: Invalid last symbol 100, offset 6.",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            INVALID_ENCRYPTED_MSG_ANON_XC20P_SKID,
+            "Malformed: skid can't be presented for ECDH-ES+A256KW alg",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            remove_field(ENCRYPTED_MSG_ANON_XC20P_1, "protected").as_str(),
+            "Malformed: Message is not a valid JWE, JWS or JWM",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            update_field(ENCRYPTED_MSG_ANON_XC20P_1, "iv", "invalid").as_str(),
+            "Malformed: Unable decode iv: Invalid last symbol 100, offset 6.",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            remove_field(ENCRYPTED_MSG_ANON_XC20P_1, "iv").as_str(),
+            "Malformed: Message is not a valid JWE, JWS or JWM",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            update_field(ENCRYPTED_MSG_ANON_XC20P_1, "ciphertext", "invalid").as_str(),
+            "Malformed: Unable decode ciphertext: Invalid last symbol 100, offset 6.",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            remove_field(ENCRYPTED_MSG_ANON_XC20P_1, "ciphertext").as_str(),
+            "Malformed: Message is not a valid JWE, JWS or JWM",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            update_field(ENCRYPTED_MSG_ANON_XC20P_1, "tag", "invalid").as_str(),
+            "Malformed: Unable decode tag: Invalid last symbol 100, offset 6.",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            remove_field(ENCRYPTED_MSG_ANON_XC20P_1, "tag").as_str(),
+            "Malformed: Message is not a valid JWE, JWS or JWM",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            update_protected_field(ENCRYPTED_MSG_ANON_XC20P_1, "apv", "invalid").as_str(),
+            "Malformed: Unable decode apv: Invalid last symbol 100, offset 6.",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            remove_protected_field(ENCRYPTED_MSG_ANON_XC20P_1, "apv").as_str(),
+            "Malformed: Unable parse protected header: missing field `apv` at line 1 column 166",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn unpack_works_malformed_authcrypt_msg() {
+        _verify_unpack_malformed(
+            update_field(ENCRYPTED_MSG_AUTH_X25519, "protected", "invalid").as_str(),
+            "Malformed: Unable decode protected header: Invalid last symbol 100, offset 6.",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            remove_field(ENCRYPTED_MSG_AUTH_X25519, "protected").as_str(),
+            "Malformed: Message is not a valid JWE, JWS or JWM",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            update_field(ENCRYPTED_MSG_AUTH_X25519, "iv", "invalid").as_str(),
+            "Malformed: Unable decode iv: Invalid last symbol 100, offset 6.",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            remove_field(ENCRYPTED_MSG_AUTH_X25519, "iv").as_str(),
+            "Malformed: Message is not a valid JWE, JWS or JWM",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            update_field(ENCRYPTED_MSG_AUTH_X25519, "ciphertext", "invalid").as_str(),
+            "Malformed: Unable decode ciphertext: Invalid last symbol 100, offset 6.",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            remove_field(ENCRYPTED_MSG_AUTH_X25519, "ciphertext").as_str(),
+            "Malformed: Message is not a valid JWE, JWS or JWM",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            update_field(ENCRYPTED_MSG_AUTH_X25519, "tag", "invalid").as_str(),
+            "Malformed: Unable decode tag: Invalid last symbol 100, offset 6.",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            remove_field(ENCRYPTED_MSG_AUTH_X25519, "tag").as_str(),
+            "Malformed: Message is not a valid JWE, JWS or JWM",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            update_protected_field(ENCRYPTED_MSG_AUTH_X25519, "apv", "invalid").as_str(),
+            "Malformed: Unable decode apv: Invalid last symbol 100, offset 6.",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            remove_protected_field(ENCRYPTED_MSG_AUTH_X25519, "apv").as_str(),
+            "Malformed: Unable parse protected header: missing field `apv` at line 1 column 264",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            update_protected_field(ENCRYPTED_MSG_AUTH_X25519, "apu", "invalid").as_str(),
+            "Malformed: Unable decode apu: Invalid last symbol 100, offset 6.",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            remove_protected_field(ENCRYPTED_MSG_AUTH_X25519, "apu").as_str(),
+            "Malformed: SKID present, but no apu",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn unpack_works_malformed_signed_msg() {
+        _verify_unpack_malformed(
+            update_field(SIGNED_MSG_ALICE_KEY_1, "payload", "invalid").as_str(),
+            "Malformed: Wrong signature",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            remove_field(SIGNED_MSG_ALICE_KEY_1, "payload").as_str(),
+            "Malformed: Message is not a valid JWE, JWS or JWM",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            update_field(SIGNED_MSG_ALICE_KEY_1, "signatures", "invalid").as_str(),
+            "Malformed: Message is not a valid JWE, JWS or JWM",
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            remove_field(SIGNED_MSG_ALICE_KEY_1, "signatures").as_str(),
+            "Malformed: Message is not a valid JWE, JWS or JWM",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn unpack_works_malformed_envelope_anoncrypt() {
+        for (msg, exp_err_msg) in [
+            (
+                EnvelopeBuilder::new(ENCRYPTED_MSG_ANON_XC20P_1)
+                    .remove_protected("epk")
+                    .build(),
+                "Malformed: No epk presented for ECDH-ES+A256KW alg",
+            ),
+            (
+                EnvelopeBuilder::new(ENCRYPTED_MSG_ANON_XC20P_1)
+                    .protected("skid", json!("did:example:alice#key-x25519-1"))
+                    .build(),
+                "Malformed: skid can't be presented for ECDH-ES+A256KW alg",
+            ),
+            (
+                EnvelopeBuilder::new(ENCRYPTED_MSG_ANON_XC20P_1)
+                    .recipient(1, "kid", json!(""))
+                    .build(),
+                "Malformed: JWE recipient 1 has empty `kid`",
+            ),
+            (
+                EnvelopeBuilder::new(ENCRYPTED_MSG_ANON_XC20P_1)
+                    .remove_recipient(2)
+                    .build(),
+                "Malformed: APV mismatch",
+            ),
+            (
+                EnvelopeBuilder::new(ENCRYPTED_MSG_ANON_XC20P_1)
+                    .duplicate_recipient(0)
+                    .corrupt_recipient_key(3)
+                    .build(),
+                "Malformed: Recipient `did:example:bob#key-x25519-1` presented multiple times \
+                with different encrypted keys",
+            ),
+            (
+                EnvelopeBuilder::new(ENCRYPTED_MSG_ANON_XC20P_1)
+                    .field("tag", json!("invalid"))
+                    .build(),
+                "Malformed: Unable decode tag: Invalid last symbol 100, offset 6.",
+            ),
+            (
+                EnvelopeBuilder::new(ENCRYPTED_MSG_ANON_XC20P_1)
+                    .field("iv", json!("o02OXDQ6_-sKz2PX_6oyJg"))
+                    .build(),
+                "Malformed: Invalid iv length 16 bytes for XC20P enc, expected 24 bytes",
+            ),
+            (
+                EnvelopeBuilder::new(ENCRYPTED_MSG_ANON_XC20P_1)
+                    .remove_field("iv")
+                    .build(),
+                "Malformed: Message is not a valid JWE, JWS or JWM",
+            ),
+        ] {
+            _verify_unpack_malformed(&msg, exp_err_msg).await;
+        }
+
+        // Values are decodable, but don't match
+        for msg in [ENCRYPTED_MSG_ANON_XC20P_1, ENCRYPTED_MSG_AUTH_X25519] {
+            for field in ["tag", "iv", "ciphertext"] {
+                let msg = EnvelopeBuilder::new(msg).corrupt_field(field).build();
+                _verify_unpack_fails(&msg, ErrorKind::Malformed).await;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_malformed_envelope_signed() {
+        _verify_unpack_malformed(
+            &EnvelopeBuilder::new(SIGNED_MSG_ALICE_KEY_1)
+                .signature(0, "kid", json!(ALICE_DID))
+                .build(),
+            "Malformed: Signer key can't be resolved to key agreement",
+        )
+        .await;
+
+        _verify_unpack_returns_error(
+            &EnvelopeBuilder::new(SIGNED_MSG_ALICE_KEY_1)
+                .signature(0, "kid", json!("did:example:alice#key-unknown"))
+                .build(),
+            ErrorKind::DIDUrlNotFound,
+            "DID URL not found: Signer kid not found in did",
+        )
+        .await;
+
+        _verify_unpack_fails(
+            &EnvelopeBuilder::new(SIGNED_MSG_ALICE_KEY_1)
+                .corrupt_signature(0)
+                .build(),
+            ErrorKind::Malformed,
+        )
+        .await;
+
+        _verify_unpack_malformed(
+            &EnvelopeBuilder::new(SIGNED_MSG_ALICE_KEY_1)
+                .signature_protected(0, "alg", json!("HS256-unknown"))
+                .build(),
+            "Malformed: Unable verify signature: Unsupported signature type",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn unpack_works_signed_canonical() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let (msg, metadata) = Message::unpack(
+            SIGNED_MSG_ALICE_KEY_1_JCS,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                accept_canonical_signatures: true,
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&msg, &*MESSAGE_SIMPLE);
+        assert!(metadata.non_repudiation);
+        assert_eq!(metadata.sign_from, Some("did:example:alice#key-1".into()));
+        assert_eq!(metadata.sign_alg, Some(SignAlg::EdDSA));
+
+        // Regular signatures are still accepted
+        let (msg, _) = Message::unpack(
+            SIGNED_MSG_ALICE_KEY_1,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                accept_canonical_signatures: true,
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&msg, &*MESSAGE_SIMPLE);
+
+        _verify_unpack_malformed(SIGNED_MSG_ALICE_KEY_1_JCS, "Malformed: Wrong signature").await;
+    }
+
+    #[tokio::test]
+    async fn unpack_works_max_signatures() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+
+        let msg = {
+            let mut msg: serde_json::Value =
+                serde_json::from_str(SIGNED_MSG_ALICE_KEY_1).expect("Unable parse jws");
+
+            let signature = msg["signatures"][0].clone();
+
+            msg["signatures"]
+                .as_array_mut()
+                .expect("signatures is array.")
+                .push(signature);
+
+            msg.to_string()
+        };
+
+        let (unpacked_msg, _) = Message::unpack(
+            SIGNED_MSG_ALICE_KEY_1,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                max_signatures: Some(1),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(unpacked_msg, *MESSAGE_SIMPLE);
+
+        let err = Message::unpack(
+            &msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                max_signatures: Some(1),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        assert_eq!(
+            format!("{}", err),
+            "Malformed: Too many signatures for jws, max allowed 1"
+        );
+    }
+
+    #[tokio::test]
+    async fn unpack_works_skip_signature_verification() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+
+        // Signature is replaced with signature of the same message by another key
+        let mut tampered: Value =
+            serde_json::from_str(SIGNED_MSG_ALICE_KEY_1).expect("Unable from_str");
+
+        let other: Value = serde_json::from_str(SIGNED_MSG_ALICE_KEY_2).expect("Unable from_str");
+        tampered["signatures"][0]["signature"] = other["signatures"][0]["signature"].clone();
+        let tampered = tampered.to_string();
+
+        let err = Message::unpack(
+            &tampered,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        // Signer DID isn't even resolved
+        let did_resolver = ExampleDIDResolver::new(vec![]);
+
+        let (msg, metadata) = Message::unpack(
+            &tampered,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                skip_signature_verification: true,
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&msg, &*MESSAGE_SIMPLE);
+        assert!(metadata.signature_verification_skipped);
+        assert!(!metadata.non_repudiation);
+        assert!(!metadata.authenticated);
+        assert_eq!(
+            metadata.sign_from.as_deref(),
+            Some("did:example:alice#key-1")
+        );
+
+        assert_eq!(
+            metadata.sign_from_kids,
+            Some(vec!["did:example:alice#key-1".into()])
+        );
+
+        assert_eq!(metadata.sign_alg, Some(SignAlg::EdDSA));
+        assert_eq!(metadata.signed_message, Some(tampered));
+    }
+
+    #[tokio::test]
+    async fn unpack_works_multiple_signatures() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+
+        let signer_kids = [
+            ALICE_AUTH_METHOD_25519.id.clone(),
+            ALICE_AUTH_METHOD_P256.id.clone(),
+            ALICE_AUTH_METHOD_SECPP256K1.id.clone(),
+        ];
+
+        let mut msg = Value::Null;
+        let mut signatures = vec![];
+
+        for signer_kid in &signer_kids {
+            let (signed, _) = MESSAGE_SIMPLE
+                .pack_signed(signer_kid, &did_resolver, &alice_secrets_resolver)
+                .await
+                .expect("Unable pack_signed");
+
+            msg = serde_json::from_str(&signed).expect("Unable from_str");
+            signatures.push(msg["signatures"][0].clone());
+        }
+
+        msg["signatures"] = Value::Array(signatures);
+        let valid_msg = msg.to_string();
+
+        // Second signature is replaced with signature of another message by the same key
+        let (other_signed, _) = MESSAGE_MINIMAL
+            .pack_signed(&signer_kids[1], &did_resolver, &alice_secrets_resolver)
+            .await
+            .expect("Unable pack_signed");
+
+        let other_signed: Value = serde_json::from_str(&other_signed).expect("Unable from_str");
+        msg["signatures"][1]["signature"] = other_signed["signatures"][0]["signature"].clone();
+        let invalid_msg = msg.to_string();
+
+        let (unpacked, metadata) = Message::unpack(
+            &valid_msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&unpacked, &*MESSAGE_SIMPLE);
+        assert!(metadata.non_repudiation);
+        assert_eq!(metadata.sign_from, Some(signer_kids[0].clone()));
+        assert_eq!(metadata.sign_from_kids, Some(signer_kids.to_vec()));
+        assert_eq!(metadata.sign_alg, Some(SignAlg::EdDSA));
+
+        let err = Message::unpack(
+            &invalid_msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+        assert_eq!(format!("{}", err), "Malformed: Wrong signature");
+
+        let (unpacked, metadata) = Message::unpack(
+            &invalid_msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                min_valid_signatures: Some(2),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&unpacked, &*MESSAGE_SIMPLE);
+        assert_eq!(metadata.sign_from, Some(signer_kids[0].clone()));
+
+        assert_eq!(
+            metadata.sign_from_kids,
+            Some(vec![signer_kids[0].clone(), signer_kids[2].clone()])
+        );
+
+        let err = Message::unpack(
+            &invalid_msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                min_valid_signatures: Some(3),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        assert_eq!(
+            format!("{}", err),
+            "Malformed: Only 2 of 3 signatures are valid and made by distinct signers, \
+            at least 3 required"
+        );
+
+        // The same signature listed twice counts once
+        let mut duplicated_msg: Value = serde_json::from_str(&valid_msg).expect("Unable from_str");
+
+        let signature = duplicated_msg["signatures"][0].clone();
+        duplicated_msg["signatures"] = json!([signature.clone(), signature]);
+        let duplicated_msg = duplicated_msg.to_string();
+
+        let (_, metadata) = Message::unpack(
+            &duplicated_msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(metadata.sign_from_kids, Some(vec![signer_kids[0].clone()]));
+
+        let err = Message::unpack(
+            &duplicated_msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                min_valid_signatures: Some(2),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        assert_eq!(
+            format!("{}", err),
+            "Malformed: Only 1 of 2 signatures are valid and made by distinct signers, \
+            at least 2 required"
+        );
+    }
+
+    #[tokio::test]
+    async fn unpack_works_min_valid_signatures() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+
+        // Charlie DID can't be resolved by the recipient, so Charlie signature can't be verified
+        let signers = [
+            (
+                &*ALICE_DID_DOC,
+                &*ALICE_SECRETS,
+                ALICE_AUTH_METHOD_25519.id.clone(),
+            ),
+            (
+                &*CHARLIE_DID_DOC,
+                &*CHARLIE_SECRETS,
+                CHARLIE_AUTH_METHOD_25519.id.clone(),
+            ),
+            (
+                &*ALICE_DID_DOC,
+                &*ALICE_SECRETS,
+                ALICE_AUTH_METHOD_P256.id.clone(),
+            ),
+        ];
+
+        let mut msg = Value::Null;
+        let mut signatures = vec![];
+
+        for (did_doc, secrets, signer_kid) in &signers {
+            let (signed, _) = MESSAGE_SIMPLE
+                .pack_signed(
+                    signer_kid,
+                    &ExampleDIDResolver::new(vec![(*did_doc).clone()]),
+                    &ExampleSecretsResolver::new((*secrets).clone()),
+                )
+                .await
+                .expect("Unable pack_signed");
+
+            msg = serde_json::from_str(&signed).expect("Unable from_str");
+            signatures.push(msg["signatures"][0].clone());
+        }
+
+        msg["signatures"] = Value::Array(signatures);
+        let msg = msg.to_string();
+
+        // All signatures must be valid by default
+        let err = Message::unpack(
+            &msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::DIDNotResolved);
+
+        // 2-of-3
+        let (unpacked, metadata) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                min_valid_signatures: Some(2),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&unpacked, &*MESSAGE_SIMPLE);
+        assert!(metadata.non_repudiation);
+        assert_eq!(metadata.sign_from, Some(signers[0].2.clone()));
+
+        assert_eq!(
+            metadata.sign_from_kids,
+            Some(vec![signers[0].2.clone(), signers[2].2.clone()])
+        );
+
+        // The same signer can't meet 2-of-3 alone by duplicating its signature
+        let mut duplicated_msg: Value = serde_json::from_str(&msg).expect("Unable from_str");
+        let signature = duplicated_msg["signatures"][0].clone();
+        duplicated_msg["signatures"][2] = signature;
+
+        let err = Message::unpack(
+            &duplicated_msg.to_string(),
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                min_valid_signatures: Some(2),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        assert_eq!(
+            format!("{}", err),
+            "Malformed: Only 1 of 3 signatures are valid and made by distinct signers, \
+            at least 2 required"
+        );
+
+        // Threshold is checked for single signature too
+        let res = Message::unpack(
+            SIGNED_MSG_ALICE_KEY_1,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                min_valid_signatures: Some(2),
+                ..UnpackOptions::default()
+            },
+        )
+        .await;
+
+        let err = res.expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        assert_eq!(
+            format!("{}", err),
+            "Malformed: Only 1 of 1 signatures are valid and made by distinct signers, \
+            at least 2 required"
+        );
+    }
+
+    #[tokio::test]
+    async fn unpack_works_lenient_base64() {
+        let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        // Protected header is left as is as it is used as additional authenticated data
+        let msg = {
+            let mut msg: serde_json::Value =
+                serde_json::from_str(ENCRYPTED_MSG_ANON_XC20P_1).expect("Unable parse jwe");
+
+            for field in ["iv", "ciphertext", "tag"] {
+                msg[field] = _padded(&msg[field]);
+            }
+
+            for recipient in msg["recipients"]
+                .as_array_mut()
+                .expect("recipients is array.")
+            {
+                recipient["encrypted_key"] = _padded(&recipient["encrypted_key"]);
+            }
+
+            msg.to_string()
+        };
+
+        assert_ne!(
+            serde_json::from_str::<serde_json::Value>(ENCRYPTED_MSG_ANON_XC20P_1)
+                .expect("Unable parse jwe"),
+            serde_json::from_str::<serde_json::Value>(&msg).expect("Unable parse jwe")
+        );
+
+        let (unpacked_msg, _) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                lenient_base64: true,
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(unpacked_msg, *MESSAGE_SIMPLE);
+
+        let err = Message::unpack(
+            &msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        fn _padded(value: &serde_json::Value) -> serde_json::Value {
+            let mut value = value.as_str().expect("value is string.").to_owned();
+
+            while value.len() % 4 != 0 {
+                value.push('=');
+            }
+
+            serde_json::Value::String(value)
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_find_secrets_with_jwe_kids_only() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+        let secrets_resolver = RecordingSecretsResolver::new(&secrets_resolver);
+
+        for msg in [ENCRYPTED_MSG_ANON_XC20P_1, ENCRYPTED_MSG_AUTH_X25519] {
+            let calls_before = secrets_resolver.find_secrets_calls().len();
+
+            let (_, metadata) = Message::unpack(
+                msg,
+                &did_resolver,
+                &secrets_resolver,
+                &UnpackOptions::default(),
+            )
+            .await
+            .expect("unpack is ok.");
+
+            let calls = secrets_resolver.find_secrets_calls()[calls_before..].to_vec();
+
+            assert_eq!(
+                calls,
+                vec![metadata
+                    .encrypted_to_kids
+                    .expect("encrypted_to_kids is some.")]
+            );
+
+            // Bob has more secrets than recipients of the message
+            assert!(calls[0].len() < BOB_SECRETS.len());
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_anoncrypt_get_secrets_in_bulk() {
+        let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+        let secrets_resolver = RecordingSecretsResolver::new(&secrets_resolver);
+
+        let (msg, _) = Message::unpack(
+            ENCRYPTED_MSG_ANON_XC20P_1,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                expect_decrypt_by_all_keys: true,
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&msg, &*MESSAGE_SIMPLE);
+
+        // Message is encrypted for 3 keys, all of them are fetched in one batch
+        let get_secrets_calls = secrets_resolver.get_secrets_calls();
+        assert_eq!(get_secrets_calls.len(), 1);
+        assert_eq!(get_secrets_calls[0].len(), 3);
+        assert!(secrets_resolver.get_secret_calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unpack_works_anoncrypt_get_secrets_concurrently() {
+        let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+
+        let secrets_resolver = SlowSecretsResolver {
+            secrets_resolver: ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+            in_flight: Mutex::new(0),
+            max_in_flight: Mutex::new(0),
+        };
+
+        let (msg, _) = Message::unpack(
+            ENCRYPTED_MSG_ANON_XC20P_1,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                expect_decrypt_by_all_keys: true,
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&msg, &*MESSAGE_SIMPLE);
+
+        // Lookups of all 3 recipient keys are in flight at the same time
+        assert_eq!(*secrets_resolver.max_in_flight.lock().unwrap(), 3);
+
+        struct SlowSecretsResolver {
+            secrets_resolver: ExampleSecretsResolver,
+            in_flight: Mutex<usize>,
+            max_in_flight: Mutex<usize>,
+        }
+
+        #[cfg_attr(feature = "uniffi", async_trait)]
+        #[cfg_attr(not(feature = "uniffi"), async_trait(?Send))]
+        impl SecretsResolver for SlowSecretsResolver {
+            async fn get_secret(&self, secret_id: &str) -> Result<Option<Secret>> {
+                {
+                    let mut in_flight = self.in_flight.lock().unwrap();
+                    *in_flight += 1;
+
+                    let mut max_in_flight = self.max_in_flight.lock().unwrap();
+                    *max_in_flight = (*max_in_flight).max(*in_flight);
+                }
+
+                // Yield once to let other lookups start
+                let mut yielded = false;
+
+                futures::future::poll_fn(|cx| {
+                    if yielded {
+                        Poll::Ready(())
+                    } else {
+                        yielded = true;
+                        cx.waker().wake_by_ref();
+                        Poll::Pending
+                    }
+                })
+                .await;
+
+                *self.in_flight.lock().unwrap() -= 1;
+                self.secrets_resolver.get_secret(secret_id).await
+            }
+
+            async fn find_secrets<'a>(&self, secret_ids: &'a [&'a str]) -> Result<Vec<&'a str>> {
+                self.secrets_resolver.find_secrets(secret_ids).await
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_versioned_works_historical_did_doc() {
+        let mut current_alice_did_doc = ALICE_DID_DOC.clone();
+        current_alice_did_doc
+            .authentications
+            .retain(|kid| kid != "did:example:alice#key-1");
+        current_alice_did_doc
+            .verification_methods
+            .retain(|vm| vm.id != "did:example:alice#key-1");
+
+        let did_resolver = TimedDIDResolver {
+            rotated_at: 1600000000,
+            historical: ALICE_DID_DOC.clone(),
+            current: current_alice_did_doc.clone(),
+        };
+
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let (msg, metadata) = Message::unpack_versioned(
+            SIGNED_MSG_ALICE_KEY_1,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&msg, &*MESSAGE_SIMPLE);
+        assert!(metadata.non_repudiation);
+        assert_eq!(
+            metadata.sign_from.as_deref(),
+            Some("did:example:alice#key-1")
+        );
+
+        // The current DID Doc version doesn't contain the signing key
+        let err = Message::unpack(
+            SIGNED_MSG_ALICE_KEY_1,
+            &ExampleDIDResolver::new(vec![current_alice_did_doc]),
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::DIDUrlNotFound);
+
+        struct TimedDIDResolver {
+            rotated_at: u64,
+            historical: DIDDoc,
+            current: DIDDoc,
+        }
+
+        #[cfg_attr(feature = "uniffi", async_trait)]
+        #[cfg_attr(not(feature = "uniffi"), async_trait(?Send))]
+        impl VersionedDIDResolver for TimedDIDResolver {
+            async fn resolve_at(&self, did: &str, time: Option<u64>) -> Result<Option<DIDDoc>> {
+                let did_doc = match time {
+                    Some(time) if time < self.rotated_at => &self.historical,
+                    _ => &self.current,
+                };
+
+                Ok(Some(did_doc.clone()).filter(|did_doc| did_doc.did == did))
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_max_total_attachment_size() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+
+        // Each attachment is 7 bytes after decoding
+        let msg = Message::build(
+            "1234567890".to_owned(),
+            "http://example.com/protocols/lets_do_lunch/1.0/proposal".to_owned(),
+            json!({}),
+        )
+        .attachments(vec![
+            Attachment::base64("ZXhhbXBsZQ==".to_owned()).finalize(),
+            Attachment::json(json!({"foo": "bar"})).finalize(),
+            Attachment::base64("ZXhhbXBsZQ".to_owned()).finalize(),
+            Attachment::base64("ZXhhbXBsZQ==".to_owned()).finalize(),
+        ])
+        .finalize();
+
+        let packed_msg = msg
+            .pack_plaintext(&did_resolver)
+            .await
+            .expect("pack is ok.");
+
+        let (unpacked_msg, _) = Message::unpack(
+            &packed_msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                max_total_attachment_size: Some(21),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(unpacked_msg, msg);
+
+        let err = Message::unpack(
+            &packed_msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                max_total_attachment_size: Some(20),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::TooLarge);
+
+        assert_eq!(
+            format!("{}", err),
+            "Too large: Total attachments size 21 exceeds max allowed size 20"
+        );
+    }
+
+    #[tokio::test]
+    async fn unpack_works_max_message_bytes() {
+        let did_resolver = ExampleDIDResolver::new(vec![BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+        let msg_len = ENCRYPTED_MSG_ANON_XC20P_1.len() as u64;
+
+        let (unpacked_msg, _) = Message::unpack(
+            ENCRYPTED_MSG_ANON_XC20P_1,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                max_message_bytes: Some(msg_len),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
 
-            let (packed, _) = msg
-                .pack_encrypted(
-                    to,
-                    Some(from),
-                    Some(sign_by),
-                    &did_resolver,
-                    &secrets_resolver,
-                    &PackEncryptedOptions {
-                        forward: false,
-                        ..PackEncryptedOptions::default()
-                    },
-                )
-                .await
-                .expect("encrypt is ok.");
+        assert_eq!(&unpacked_msg, &*MESSAGE_SIMPLE);
 
-            _verify_unpack_undeterministic(
-                &packed,
-                msg,
-                &UnpackMetadata {
-                    sign_from: Some(sign_by_kid.into()),
-                    sign_alg: Some(sign_alg),
-                    signed_message: Some("nondeterministic".into()),
-                    anonymous_sender: false,
-                    authenticated: true,
-                    non_repudiation: true,
-                    encrypted: true,
-                    enc_alg_auth: Some(enc_alg),
-                    enc_alg_anon: None,
-                    encrypted_from_kid: Some(from_kid.into()),
-                    encrypted_to_kids: Some(to_kids.iter().map(|&k| k.to_owned()).collect()),
-                    from_prior_issuer_kid: None,
-                    from_prior: None,
-                    re_wrapped_in_forward: false,
-                },
+        // Rejected before decryption, so missed secrets don't matter
+        let err = Message::unpack(
+            ENCRYPTED_MSG_ANON_XC20P_1,
+            &did_resolver,
+            &ExampleSecretsResolver::new(vec![]),
+            &UnpackOptions {
+                max_message_bytes: Some(msg_len - 1),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::TooLarge);
+
+        assert_eq!(
+            format!("{}", err),
+            format!(
+                "Too large: Message size {} exceeds max allowed size {}",
+                msg_len,
+                msg_len - 1
             )
-            .await;
-        }
+        );
     }
 
     #[tokio::test]
-    async fn unpack_works_invalid_epk_point() {
-        _verify_unpack_malformed(
-            &INVALID_ENCRYPTED_MSG_ANON_P256_EPK_WRONG_POINT,
-            "Malformed: Unable instantiate epk: Unable produce jwk: Invalid key data",
+    async fn unpack_works_max_attachment_bytes() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+
+        // Base64 attachment is 7 bytes after decoding, json attachment is 13 bytes serialized
+        let msg = Message::build(
+            "1234567890".to_owned(),
+            "http://example.com/protocols/lets_do_lunch/1.0/proposal".to_owned(),
+            json!({}),
         )
-        .await;
+        .attachments(vec![
+            Attachment::base64("ZXhhbXBsZQ==".to_owned()).finalize(),
+            Attachment::json(json!({"foo": "bar"})).finalize(),
+            Attachment::links(
+                vec!["http://example.com/attachment".to_owned()],
+                "hash".to_owned(),
+            )
+            .finalize(),
+        ])
+        .finalize();
+
+        let packed_msg = msg
+            .pack_plaintext(&did_resolver)
+            .await
+            .expect("pack is ok.");
+
+        let (unpacked_msg, _) = Message::unpack(
+            &packed_msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                max_attachment_bytes: Some(13),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(unpacked_msg, msg);
+
+        let err = Message::unpack(
+            &packed_msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                max_attachment_bytes: Some(12),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::TooLarge);
+
+        assert_eq!(
+            format!("{}", err),
+            "Too large: Attachment size 13 exceeds max allowed size 12"
+        );
+
+        let err = Message::unpack(
+            &packed_msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                max_attachment_bytes: Some(6),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(
+            format!("{}", err),
+            "Too large: Attachment size 7 exceeds max allowed size 6"
+        );
     }
 
     #[tokio::test]
-    async fn unpack_works_malformed_anoncrypt_msg() {
+    async fn unpack_works_malformed_plaintext_msg() {
         _verify_unpack_malformed(
-            update_field(ENCRYPTED_MSG_ANON_XC20P_1, "protected", "invalid").as_str(),
-            "Malformed: Unable decode protected header: Invalid last symbol 100, offset 6.",
+            &INVALID_PLAINTEXT_MSG_EMPTY,
+            "Malformed: Message is not a valid JWE, JWS or JWM",
         )
         .await;
 
         _verify_unpack_malformed(
-            remove_field(ENCRYPTED_MSG_ANON_XC20P_1, "protected").as_str(),
+            &INVALID_PLAINTEXT_MSG_STRING,
             "Malformed: Message is not a valid JWE, JWS or JWM",
         )
         .await;
 
         _verify_unpack_malformed(
-            update_field(ENCRYPTED_MSG_ANON_XC20P_1, "iv", "invalid").as_str(),
-            "Malformed: Unable decode iv: Invalid last symbol 100, offset 6.",
+            &INVALID_PLAINTEXT_MSG_NO_ID,
+            "Malformed: Message is not a valid JWE, JWS or JWM",
         )
         .await;
 
         _verify_unpack_malformed(
-            remove_field(ENCRYPTED_MSG_ANON_XC20P_1, "iv").as_str(),
+            &INVALID_PLAINTEXT_MSG_NO_TYP,
             "Malformed: Message is not a valid JWE, JWS or JWM",
         )
         .await;
 
         _verify_unpack_malformed(
-            update_field(ENCRYPTED_MSG_ANON_XC20P_1, "ciphertext", "invalid").as_str(),
-            "Malformed: Unable decode ciphertext: Invalid last symbol 100, offset 6.",
+            &INVALID_PLAINTEXT_MSG_NO_TYPE,
+            "Malformed: Message is not a valid JWE, JWS or JWM",
         )
         .await;
 
         _verify_unpack_malformed(
-            remove_field(ENCRYPTED_MSG_ANON_XC20P_1, "ciphertext").as_str(),
+            &INVALID_PLAINTEXT_MSG_NO_BODY,
             "Malformed: Message is not a valid JWE, JWS or JWM",
         )
         .await;
 
         _verify_unpack_malformed(
-            update_field(ENCRYPTED_MSG_ANON_XC20P_1, "tag", "invalid").as_str(),
-            "Malformed: Unable decode tag: Invalid last symbol 100, offset 6.",
+            &INVALID_PLAINTEXT_MSG_WRONG_TYP,
+            "Malformed: `typ` must be \"application/didcomm-plain+json\"",
         )
         .await;
 
         _verify_unpack_malformed(
-            remove_field(ENCRYPTED_MSG_ANON_XC20P_1, "tag").as_str(),
+            &INVALID_PLAINTEXT_MSG_EMPTY_ATTACHMENTS,
             "Malformed: Message is not a valid JWE, JWS or JWM",
         )
         .await;
 
         _verify_unpack_malformed(
-            update_protected_field(ENCRYPTED_MSG_ANON_XC20P_1, "apv", "invalid").as_str(),
-            "Malformed: Unable decode apv: Invalid last symbol 100, offset 6.",
+            &INVALID_PLAINTEXT_MSG_ATTACHMENTS_NO_DATA,
+            "Malformed: Message is not a valid JWE, JWS or JWM",
         )
         .await;
 
         _verify_unpack_malformed(
-            remove_protected_field(ENCRYPTED_MSG_ANON_XC20P_1, "apv").as_str(),
-            "Malformed: Unable parse protected header: missing field `apv` at line 1 column 166",
+            &INVALID_PLAINTEXT_MSG_ATTACHMENTS_EMPTY_DATA,
+            "Malformed: Message is not a valid JWE, JWS or JWM",
         )
         .await;
-    }
 
-    #[tokio::test]
-    async fn unpack_works_malformed_authcrypt_msg() {
         _verify_unpack_malformed(
-            update_field(ENCRYPTED_MSG_AUTH_X25519, "protected", "invalid").as_str(),
-            "Malformed: Unable decode protected header: Invalid last symbol 100, offset 6.",
+            &INVALID_PLAINTEXT_MSG_ATTACHMENTS_LINKS_NO_HASH,
+            "Malformed: Message is not a valid JWE, JWS or JWM",
         )
         .await;
 
         _verify_unpack_malformed(
-            remove_field(ENCRYPTED_MSG_AUTH_X25519, "protected").as_str(),
+            &INVALID_PLAINTEXT_MSG_ATTACHMENTS_AS_STRING,
             "Malformed: Message is not a valid JWE, JWS or JWM",
         )
         .await;
 
         _verify_unpack_malformed(
-            update_field(ENCRYPTED_MSG_AUTH_X25519, "iv", "invalid").as_str(),
-            "Malformed: Unable decode iv: Invalid last symbol 100, offset 6.",
+            &INVALID_PLAINTEXT_MSG_ATTACHMENTS_AS_INT_ARRAY,
+            "Malformed: Message is not a valid JWE, JWS or JWM",
         )
         .await;
 
         _verify_unpack_malformed(
-            remove_field(ENCRYPTED_MSG_AUTH_X25519, "iv").as_str(),
+            &INVALID_PLAINTEXT_MSG_ATTACHMENTS_WRONG_DATA,
             "Malformed: Message is not a valid JWE, JWS or JWM",
         )
         .await;
 
         _verify_unpack_malformed(
-            update_field(ENCRYPTED_MSG_AUTH_X25519, "ciphertext", "invalid").as_str(),
-            "Malformed: Unable decode ciphertext: Invalid last symbol 100, offset 6.",
+            &INVALID_PLAINTEXT_MSG_ATTACHMENTS_WRONG_ID,
+            "Malformed: Message is not a valid JWE, JWS or JWM",
         )
         .await;
 
         _verify_unpack_malformed(
-            remove_field(ENCRYPTED_MSG_AUTH_X25519, "ciphertext").as_str(),
+            &INVALID_PLAINTEXT_MSG_ATTACHMENTS_NULL_DATA,
             "Malformed: Message is not a valid JWE, JWS or JWM",
         )
         .await;
 
         _verify_unpack_malformed(
-            update_field(ENCRYPTED_MSG_AUTH_X25519, "tag", "invalid").as_str(),
-            "Malformed: Unable decode tag: Invalid last symbol 100, offset 6.",
+            &INVALID_PLAINTEXT_MSG_ATTACHMENTS_STRINGIFIED_JSON,
+            "Malformed: Attachment json data is stringified JSON",
         )
         .await;
+    }
 
-        _verify_unpack_malformed(
-            remove_field(ENCRYPTED_MSG_AUTH_X25519, "tag").as_str(),
-            "Malformed: Message is not a valid JWE, JWS or JWM",
+    #[tokio::test]
+    async fn unpack_works_recorded_resolver_calls() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        // Signed message: only DID Doc of the signer is resolved
+        let recording_did_resolver = RecordingDIDResolver::new(&did_resolver);
+        let recording_secrets_resolver = RecordingSecretsResolver::new(&secrets_resolver);
+
+        Message::unpack(
+            SIGNED_MSG_ALICE_KEY_1,
+            &recording_did_resolver,
+            &recording_secrets_resolver,
+            &UnpackOptions::default(),
         )
-        .await;
+        .await
+        .expect("unpack is ok.");
 
-        _verify_unpack_malformed(
-            update_protected_field(ENCRYPTED_MSG_AUTH_X25519, "apv", "invalid").as_str(),
-            "Malformed: Unable decode apv: Invalid last symbol 100, offset 6.",
+        assert_eq!(recording_did_resolver.resolved_dids(), vec![ALICE_DID]);
+        assert!(recording_secrets_resolver.resolved_secret_ids().is_empty());
+
+        // Anoncrypted message: existence of secrets for all recipient keys is checked
+        // and then secrets of found keys are fetched. No DIDs are resolved.
+        let recording_did_resolver = RecordingDIDResolver::new(&did_resolver);
+        let recording_secrets_resolver = RecordingSecretsResolver::new(&secrets_resolver);
+
+        let (_, metadata) = Message::unpack(
+            ENCRYPTED_MSG_ANON_XC20P_1,
+            &recording_did_resolver,
+            &recording_secrets_resolver,
+            &UnpackOptions::default(),
         )
-        .await;
+        .await
+        .expect("unpack is ok.");
 
-        _verify_unpack_malformed(
-            remove_protected_field(ENCRYPTED_MSG_AUTH_X25519, "apv").as_str(),
-            "Malformed: Unable parse protected header: missing field `apv` at line 1 column 264",
+        let to_kids = metadata
+            .encrypted_to_kids
+            .expect("encrypted_to_kids is some.");
+
+        let found_kids = to_kids
+            .iter()
+            .filter(|kid| BOB_SECRETS.iter().any(|s| &s.id == *kid))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        assert!(!found_kids.is_empty());
+
+        assert_eq!(
+            recording_secrets_resolver.resolved_secret_ids(),
+            to_kids
+                .iter()
+                .chain(found_kids.iter())
+                .cloned()
+                .collect::<Vec<_>>()
+        );
+
+        assert!(recording_did_resolver.resolved_dids().is_empty());
+
+        // Plaintext message: nothing is resolved
+        let recording_did_resolver = RecordingDIDResolver::new(&did_resolver);
+        let recording_secrets_resolver = RecordingSecretsResolver::new(&secrets_resolver);
+
+        Message::unpack(
+            PLAINTEXT_MSG_SIMPLE,
+            &recording_did_resolver,
+            &recording_secrets_resolver,
+            &UnpackOptions::default(),
         )
-        .await;
+        .await
+        .expect("unpack is ok.");
 
-        _verify_unpack_malformed(
-            update_protected_field(ENCRYPTED_MSG_AUTH_X25519, "apu", "invalid").as_str(),
-            "Malformed: Unable decode apu: Invalid last symbol 100, offset 6.",
+        assert!(recording_did_resolver.resolved_dids().is_empty());
+        assert!(recording_secrets_resolver.resolved_secret_ids().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unpack_plaintext_works_lenient_timestamps() {
+        let did_resolver = ExampleDIDResolver::new(vec![]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+
+        let plaintext = PLAINTEXT_MSG_SIMPLE
+            .replace("1516269022", "\"1516269022\"")
+            .replace("1516385931", "\"1516385931\"");
+
+        let err = Message::unpack(
+            &plaintext,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
         )
-        .await;
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        assert_eq!(
+            format!("{}", err),
+            "Malformed: Message is not a valid JWE, JWS or JWM"
+        );
+
+        let options = UnpackOptions {
+            lenient_timestamps: true,
+            ..UnpackOptions::default()
+        };
+
+        let (msg, _) = Message::unpack(&plaintext, &did_resolver, &secrets_resolver, &options)
+            .await
+            .expect("unpack is ok.");
+
+        assert_eq!(&msg, &*MESSAGE_SIMPLE);
+        assert_eq!(msg.created_time, Some(1516269022));
+        assert_eq!(msg.expires_time, Some(1516385931));
+
+        // Numeric timestamps are accepted as is
+        let (msg, _) = Message::unpack(
+            PLAINTEXT_MSG_SIMPLE,
+            &did_resolver,
+            &secrets_resolver,
+            &options,
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&msg, &*MESSAGE_SIMPLE);
+
+        // Strings not being UNIX timestamps are still rejected
+        for time in ["\"2018-01-18T09:50:22Z\"", "\"-1\"", "\"\""] {
+            let plaintext = PLAINTEXT_MSG_SIMPLE.replace("1516269022", time);
+
+            let err = Message::unpack(&plaintext, &did_resolver, &secrets_resolver, &options)
+                .await
+                .expect_err("res is ok");
+
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+        }
+    }
+
+    #[tokio::test]
+    async fn unpack_works_allowed_senders() {
+        let charlie_did = CHARLIE_DID_DOC.did.as_str();
+
+        let options = UnpackOptions {
+            allowed_senders: Some(vec![ALICE_DID.into()]),
+            ..UnpackOptions::default()
+        };
+
+        _unpack_works_allowed_senders(&ALICE_DID_DOC, &CHARLIE_DID_DOC, &options)
+            .await
+            .expect("res is ok.");
+
+        let err = _unpack_works_allowed_senders(
+            &ALICE_DID_DOC,
+            &CHARLIE_DID_DOC,
+            &UnpackOptions {
+                allowed_senders: Some(vec![charlie_did.into()]),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Untrusted);
+
+        assert_eq!(
+            format!("{}", err),
+            "Untrusted: Sender `did:example:alice` is not allowed"
+        );
+
+        // Not authenticated messages are rejected
+        let did_resolver = ExampleDIDResolver::new(vec![]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+
+        let err = Message::unpack(
+            PLAINTEXT_MSG_SIMPLE,
+            &did_resolver,
+            &secrets_resolver,
+            &options,
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Untrusted);
+
+        assert_eq!(
+            format!("{}", err),
+            "Untrusted: Message sender is not authenticated, but allowed senders are restricted"
+        );
+    }
+
+    #[tokio::test]
+    async fn unpack_works_follow_also_known_as() {
+        let charlie_did = CHARLIE_DID_DOC.did.as_str();
+
+        let alice_did_doc = DIDDoc {
+            also_known_as: vec![charlie_did.into()],
+            ..ALICE_DID_DOC.clone()
+        };
+
+        let charlie_did_doc = DIDDoc {
+            also_known_as: vec![ALICE_DID.into()],
+            ..CHARLIE_DID_DOC.clone()
+        };
+
+        let options = UnpackOptions {
+            allowed_senders: Some(vec![charlie_did.into()]),
+            follow_also_known_as: true,
+            ..UnpackOptions::default()
+        };
+
+        // Alice is linked with allowed Charlie by both DID Docs
+        _unpack_works_allowed_senders(&alice_did_doc, &charlie_did_doc, &options)
+            .await
+            .expect("res is ok.");
+
+        // The link isn't followed by default
+        let err = _unpack_works_allowed_senders(
+            &alice_did_doc,
+            &charlie_did_doc,
+            &UnpackOptions {
+                follow_also_known_as: false,
+                ..options.clone()
+            },
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Untrusted);
+
+        assert_eq!(
+            format!("{}", err),
+            "Untrusted: Sender `did:example:alice` is not allowed"
+        );
+
+        // The link claimed by the sender only isn't enough
+        let err = _unpack_works_allowed_senders(&alice_did_doc, &CHARLIE_DID_DOC, &options)
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Untrusted);
+
+        assert_eq!(
+            format!("{}", err),
+            "Untrusted: Sender `did:example:alice` is not allowed"
+        );
+
+        let err = _unpack_works_allowed_senders(&ALICE_DID_DOC, &charlie_did_doc, &options)
+            .await
+            .expect_err("res is ok");
 
-        _verify_unpack_malformed(
-            remove_protected_field(ENCRYPTED_MSG_AUTH_X25519, "apu").as_str(),
-            "Malformed: SKID present, but no apu",
-        )
-        .await;
+        assert_eq!(err.kind(), ErrorKind::Untrusted);
     }
 
     #[tokio::test]
-    async fn unpack_works_malformed_signed_msg() {
-        _verify_unpack_malformed(
-            update_field(SIGNED_MSG_ALICE_KEY_1, "payload", "invalid").as_str(),
-            "Malformed: Wrong signature",
-        )
-        .await;
+    async fn unpack_works_allowed_senders_multiple_signatures() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), CHARLIE_DID_DOC.clone()]);
 
-        _verify_unpack_malformed(
-            remove_field(SIGNED_MSG_ALICE_KEY_1, "payload").as_str(),
-            "Malformed: Message is not a valid JWE, JWS or JWM",
-        )
-        .await;
+        let mut sender_secrets = ALICE_SECRETS.clone();
+        sender_secrets.extend(CHARLIE_SECRETS.iter().cloned());
+        let sender_secrets_resolver = ExampleSecretsResolver::new(sender_secrets);
 
-        _verify_unpack_malformed(
-            update_field(SIGNED_MSG_ALICE_KEY_1, "signatures", "invalid").as_str(),
-            "Malformed: Message is not a valid JWE, JWS or JWM",
+        let mut msg = Value::Null;
+        let mut signatures = vec![];
+
+        for signer_kid in [&ALICE_AUTH_METHOD_25519.id, &CHARLIE_AUTH_METHOD_25519.id] {
+            let (signed, _) = MESSAGE_SIMPLE
+                .pack_signed(signer_kid, &did_resolver, &sender_secrets_resolver)
+                .await
+                .expect("Unable pack_signed");
+
+            msg = serde_json::from_str(&signed).expect("Unable from_str");
+            signatures.push(msg["signatures"][0].clone());
+        }
+
+        msg["signatures"] = Value::Array(signatures);
+        let msg = msg.to_string();
+
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
+
+        // The second signer isn't allowed
+        let err = Message::unpack(
+            &msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                allowed_senders: Some(vec![ALICE_DID.into()]),
+                ..UnpackOptions::default()
+            },
         )
-        .await;
+        .await
+        .expect_err("res is ok");
 
-        _verify_unpack_malformed(
-            remove_field(SIGNED_MSG_ALICE_KEY_1, "signatures").as_str(),
-            "Malformed: Message is not a valid JWE, JWS or JWM",
+        assert_eq!(err.kind(), ErrorKind::Untrusted);
+
+        assert_eq!(
+            format!("{}", err),
+            "Untrusted: Sender `did:example:charlie` is not allowed"
+        );
+
+        let (unpacked, _) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                allowed_senders: Some(vec![ALICE_DID.into(), CHARLIE_DID.into()]),
+                ..UnpackOptions::default()
+            },
         )
-        .await;
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(&unpacked, &*MESSAGE_SIMPLE);
     }
 
     #[tokio::test]
-    async fn unpack_works_malformed_plaintext_msg() {
-        _verify_unpack_malformed(
-            &INVALID_PLAINTEXT_MSG_EMPTY,
-            "Malformed: Message is not a valid JWE, JWS or JWM",
-        )
-        .await;
+    async fn unpack_works_allowed_senders_skip_signature_verification() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
 
-        _verify_unpack_malformed(
-            &INVALID_PLAINTEXT_MSG_STRING,
-            "Malformed: Message is not a valid JWE, JWS or JWM",
-        )
-        .await;
+        // Signature of allowed Alice's key is forged
+        let mut forged: Value =
+            serde_json::from_str(SIGNED_MSG_ALICE_KEY_1).expect("Unable from_str");
 
-        _verify_unpack_malformed(
-            &INVALID_PLAINTEXT_MSG_NO_ID,
-            "Malformed: Message is not a valid JWE, JWS or JWM",
-        )
-        .await;
+        let other: Value = serde_json::from_str(SIGNED_MSG_ALICE_KEY_2).expect("Unable from_str");
+        forged["signatures"][0]["signature"] = other["signatures"][0]["signature"].clone();
+        let forged = forged.to_string();
 
-        _verify_unpack_malformed(
-            &INVALID_PLAINTEXT_MSG_NO_TYP,
-            "Malformed: Message is not a valid JWE, JWS or JWM",
+        let err = Message::unpack(
+            &forged,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                allowed_senders: Some(vec![ALICE_DID.into()]),
+                skip_signature_verification: true,
+                ..UnpackOptions::default()
+            },
         )
-        .await;
+        .await
+        .expect_err("res is ok");
 
-        _verify_unpack_malformed(
-            &INVALID_PLAINTEXT_MSG_NO_TYPE,
-            "Malformed: Message is not a valid JWE, JWS or JWM",
+        assert_eq!(err.kind(), ErrorKind::Untrusted);
+
+        assert_eq!(
+            format!("{}", err),
+            "Untrusted: Message sender is not authenticated, but allowed senders are restricted"
+        );
+
+        // Authcrypt sender is still checked
+        _unpack_works_allowed_senders(
+            &ALICE_DID_DOC,
+            &CHARLIE_DID_DOC,
+            &UnpackOptions {
+                allowed_senders: Some(vec![ALICE_DID.into()]),
+                skip_signature_verification: true,
+                ..UnpackOptions::default()
+            },
         )
-        .await;
+        .await
+        .expect("res is ok.");
+    }
 
-        _verify_unpack_malformed(
-            &INVALID_PLAINTEXT_MSG_NO_BODY,
-            "Malformed: Message is not a valid JWE, JWS or JWM",
-        )
-        .await;
+    /// Unpacks message authcrypted by Alice to Bob with the given DID Docs of Alice and Charlie.
+    async fn _unpack_works_allowed_senders(
+        alice_did_doc: &DIDDoc,
+        charlie_did_doc: &DIDDoc,
+        options: &UnpackOptions,
+    ) -> Result<Message> {
+        let did_resolver = ExampleDIDResolver::new(vec![
+            alice_did_doc.clone(),
+            BOB_DID_DOC.clone(),
+            charlie_did_doc.clone(),
+        ]);
 
-        _verify_unpack_malformed(
-            &INVALID_PLAINTEXT_MSG_WRONG_TYP,
-            "Malformed: `typ` must be \"application/didcomm-plain+json\"",
-        )
-        .await;
+        let (msg, _) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                Some(ALICE_DID),
+                None,
+                &did_resolver,
+                &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("encrypt is ok.");
 
-        _verify_unpack_malformed(
-            &INVALID_PLAINTEXT_MSG_EMPTY_ATTACHMENTS,
-            "Malformed: Message is not a valid JWE, JWS or JWM",
+        let (msg, _) = Message::unpack(
+            &msg,
+            &did_resolver,
+            &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+            options,
         )
-        .await;
+        .await?;
 
-        _verify_unpack_malformed(
-            &INVALID_PLAINTEXT_MSG_ATTACHMENTS_NO_DATA,
-            "Malformed: Message is not a valid JWE, JWS or JWM",
-        )
-        .await;
+        assert_eq!(&msg, &*MESSAGE_SIMPLE);
+        Ok(msg)
+    }
 
-        _verify_unpack_malformed(
-            &INVALID_PLAINTEXT_MSG_ATTACHMENTS_EMPTY_DATA,
-            "Malformed: Message is not a valid JWE, JWS or JWM",
-        )
-        .await;
+    #[tokio::test]
+    async fn unpack_plaintext_works_lenient_json_attachments() {
+        let did_resolver = ExampleDIDResolver::new(vec![]);
+        let secrets_resolver = ExampleSecretsResolver::new(vec![]);
 
-        _verify_unpack_malformed(
-            &INVALID_PLAINTEXT_MSG_ATTACHMENTS_LINKS_NO_HASH,
-            "Malformed: Message is not a valid JWE, JWS or JWM",
-        )
-        .await;
+        let options = UnpackOptions {
+            lenient_json_attachments: true,
+            ..UnpackOptions::default()
+        };
 
-        _verify_unpack_malformed(
-            &INVALID_PLAINTEXT_MSG_ATTACHMENTS_AS_STRING,
-            "Malformed: Message is not a valid JWE, JWS or JWM",
+        let (msg, _) = Message::unpack(
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_STRINGIFIED_JSON,
+            &did_resolver,
+            &secrets_resolver,
+            &options,
         )
-        .await;
+        .await
+        .expect("unpack is ok.");
 
-        _verify_unpack_malformed(
-            &INVALID_PLAINTEXT_MSG_ATTACHMENTS_AS_INT_ARRAY,
-            "Malformed: Message is not a valid JWE, JWS or JWM",
-        )
-        .await;
+        assert_eq!(&msg, &*MESSAGE_ATTACHMENT_JSON);
 
-        _verify_unpack_malformed(
-            &INVALID_PLAINTEXT_MSG_ATTACHMENTS_WRONG_DATA,
-            "Malformed: Message is not a valid JWE, JWS or JWM",
+        // Embedded JSON isn't changed
+        let (msg, _) = Message::unpack(
+            PLAINTEXT_MSG_ATTACHMENT_JSON,
+            &did_resolver,
+            &secrets_resolver,
+            &options,
         )
-        .await;
+        .await
+        .expect("unpack is ok.");
 
-        _verify_unpack_malformed(
-            &INVALID_PLAINTEXT_MSG_ATTACHMENTS_WRONG_ID,
-            "Malformed: Message is not a valid JWE, JWS or JWM",
-        )
-        .await;
+        assert_eq!(&msg, &*MESSAGE_ATTACHMENT_JSON);
 
-        _verify_unpack_malformed(
-            &INVALID_PLAINTEXT_MSG_ATTACHMENTS_NULL_DATA,
-            "Malformed: Message is not a valid JWE, JWS or JWM",
-        )
-        .await;
+        // Strings not being JSON object or array are not ambiguous
+        for json in ["just a string", "42", "\"quoted\""] {
+            let plaintext = PLAINTEXT_MSG_ATTACHMENT_JSON.replace(
+                r#"{"foo": "bar", "links": [2, 3]}"#,
+                &serde_json::to_string(json).expect("Unable to_string"),
+            );
+
+            for lenient_json_attachments in [false, true] {
+                let (msg, _) = Message::unpack(
+                    &plaintext,
+                    &did_resolver,
+                    &secrets_resolver,
+                    &UnpackOptions {
+                        lenient_json_attachments,
+                        ..UnpackOptions::default()
+                    },
+                )
+                .await
+                .expect("unpack is ok.");
+
+                let attachments = msg.attachments.expect("attachments is some.");
+
+                assert_eq!(
+                    attachments[0].data,
+                    AttachmentData::Json {
+                        value: JsonAttachmentData {
+                            json: json!(json),
+                            jws: None,
+                        },
+                    }
+                );
+            }
+        }
     }
 
     #[tokio::test]
@@ -2028,11 +6021,24 @@ mod test {
             sign_alg: None,
             encrypted_from_kid: None,
             encrypted_to_kids: None,
+            decrypted_with_kid: None,
             sign_from: None,
+            sign_from_kids: None,
             signed_message: None,
             from_prior_issuer_kid: Some(CHARLIE_AUTH_METHOD_25519.id.clone()),
             from_prior: Some(FROM_PRIOR_FULL.clone()),
             re_wrapped_in_forward: false,
+            signed_outside: false,
+            epk_jwk: None,
+            original_message: None,
+            aad: None,
+            signed_with_encryption_key: false,
+            missing_recipient_kids: None,
+            signature_verification_skipped: false,
+            enc_typ: None,
+            return_route: None,
+            re_wrapped_forward_next: None,
+            forward_chain: None,
         };
 
         _verify_unpack(
@@ -2063,6 +6069,173 @@ mod test {
             .await;
     }
 
+    #[tokio::test]
+    async fn unpack_works_accepted_plaintext_typ() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let (msg, metadata) = Message::unpack(
+            INVALID_PLAINTEXT_MSG_WRONG_TYP,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                accepted_plaintext_typ: Some(vec![
+                    "application/didcomm-plain+json-unknown".to_owned()
+                ]),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(msg.typ, "application/didcomm-plain+json-unknown");
+        assert!(!metadata.encrypted);
+
+        Message::unpack(
+            PLAINTEXT_MSG_SIMPLE,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                accepted_plaintext_typ: Some(vec![
+                    "application/didcomm-plain+json-unknown".to_owned()
+                ]),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect("unpack is ok.");
+
+        let err = Message::unpack(
+            INVALID_PLAINTEXT_MSG_WRONG_TYP,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions {
+                accepted_plaintext_typ: Some(vec![
+                    "application/didcomm-plain+json-other".to_owned()
+                ]),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        assert_eq!(
+            format!("{}", err),
+            "Malformed: `typ` must be \"application/didcomm-plain+json\""
+        );
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn unpack_works_tracing_spans() {
+        use std::sync::Arc;
+
+        use tracing::{span, Subscriber};
+        use tracing_subscriber::{
+            layer::{Context, SubscriberExt},
+            registry::LookupSpan,
+            Layer, Registry,
+        };
+
+        struct SpanNames(Arc<Mutex<Vec<&'static str>>>);
+
+        impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SpanNames {
+            fn on_new_span(&self, attrs: &span::Attributes<'_>, _: &span::Id, _: Context<'_, S>) {
+                self.0.lock().unwrap().push(attrs.metadata().name());
+            }
+        }
+
+        let span_names = Arc::new(Mutex::new(vec![]));
+        let subscriber = Registry::default().with(SpanNames(span_names.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+        let (msg, _) = MESSAGE_SIMPLE
+            .pack_encrypted(
+                BOB_DID,
+                Some(ALICE_DID),
+                Some(ALICE_DID),
+                &did_resolver,
+                &ExampleSecretsResolver::new(ALICE_SECRETS.clone()),
+                &PackEncryptedOptions {
+                    protect_sender: true,
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("encrypt is ok.");
+
+        assert_eq!(
+            *span_names.lock().unwrap(),
+            vec![
+                "pack_encrypted",
+                "pack_signed",
+                "authcrypt",
+                "wrap_in_forward"
+            ]
+        );
+
+        span_names.lock().unwrap().clear();
+
+        Message::unpack(
+            &msg,
+            &did_resolver,
+            &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(
+            *span_names.lock().unwrap(),
+            vec![
+                "unpack",
+                "unpack_anoncrypt",
+                "unpack_authcrypt",
+                "unpack_sign",
+                "unpack_plaintext"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn unpack_works_resolver_timeout() {
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let slow_did_resolver = SlowDIDResolver::new(&did_resolver, Duration::from_secs(10));
+
+        let err = Message::unpack(
+            ENCRYPTED_MSG_AUTH_X25519,
+            &slow_did_resolver,
+            &ExampleSecretsResolver::new(BOB_SECRETS.clone()),
+            &UnpackOptions {
+                resolver_timeout: Some(Duration::from_millis(10)),
+                ..UnpackOptions::default()
+            },
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IoError);
+    }
+
+    #[test]
+    fn unpack_options_works_resolver_timeout_deserialization() {
+        let options: UnpackOptions =
+            serde_json::from_value(json!({ "resolver_timeout": 1500 })).expect("from_value is ok.");
+
+        assert_eq!(options.resolver_timeout, Some(Duration::from_millis(1500)));
+
+        let options: UnpackOptions = serde_json::from_value(json!({})).expect("from_value is ok.");
+        assert_eq!(options.resolver_timeout, None);
+    }
+
     async fn _verify_unpack(msg: &str, exp_msg: &Message, exp_metadata: &UnpackMetadata) {
         let did_resolver = ExampleDIDResolver::new(vec![
             ALICE_DID_DOC.clone(),
@@ -2111,6 +6284,11 @@ mod test {
         assert_eq!(&msg, exp_msg);
 
         metadata.signed_message = exp_metadata.signed_message.clone();
+
+        // Ephemeral key is generated on each pack
+        assert_eq!(metadata.epk_jwk.is_some(), exp_metadata.encrypted);
+        metadata.epk_jwk = exp_metadata.epk_jwk.clone();
+
         assert_eq!(&metadata, exp_metadata);
     }
 
@@ -2118,6 +6296,27 @@ mod test {
         _verify_unpack_returns_error(msg, ErrorKind::Malformed, exp_error_str).await
     }
 
+    async fn _verify_unpack_fails(msg: &str, exp_err_kind: ErrorKind) {
+        let did_resolver = ExampleDIDResolver::new(vec![
+            ALICE_DID_DOC.clone(),
+            BOB_DID_DOC.clone(),
+            CHARLIE_DID_DOC.clone(),
+        ]);
+
+        let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let err = Message::unpack(
+            msg,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), exp_err_kind);
+    }
+
     async fn _verify_unpack_returns_error(msg: &str, exp_err_kind: ErrorKind, exp_err_msg: &str) {
         let did_resolver = ExampleDIDResolver::new(vec![
             ALICE_DID_DOC.clone(),
@@ -1,22 +1,52 @@
+use serde_json::Value;
+
 use crate::did::DIDResolver;
-use crate::error::{ErrorKind, Result};
-use crate::{FromPrior, Message, UnpackMetadata};
+use crate::error::{err_msg, ErrorKind, Result, ToResult};
+use crate::message::{message::stringified_json, AttachmentData};
+use crate::utils::encoding::base64_decoded_size;
+use crate::{FromPrior, Message, UnpackMetadata, UnpackOptions};
 
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "unpack_plaintext",
+        level = "debug",
+        skip_all,
+        err(level = "debug")
+    )
+)]
 pub(crate) async fn _try_unpack_plaintext<'dr, 'sr>(
     msg: &str,
     did_resolver: &'dr (dyn DIDResolver + 'dr),
+    opts: &UnpackOptions,
     metadata: &mut UnpackMetadata,
 ) -> Result<Option<Message>> {
-    let msg = match Message::from_str(msg) {
+    let mut msg = match _parse_message(msg, opts.lenient_timestamps) {
         Ok(m) => m,
         Err(e) if e.kind() == ErrorKind::Malformed => return Ok(None),
         Err(e) => Err(e)?,
     }
-    .validate()?;
+    .validate_accepting_typ(
+        opts.accepted_plaintext_typ.as_deref().unwrap_or(&[]),
+        &opts.body_validators,
+        opts.lenient_json_attachments,
+    )?;
+
+    if let Some(max_size) = opts.max_attachment_bytes {
+        _check_attachment_size(&msg, max_size)?;
+    }
+
+    if let Some(max_size) = opts.max_total_attachment_size {
+        _check_attachments_size(&msg, max_size)?;
+    }
+
+    if opts.lenient_json_attachments {
+        _parse_json_attachments(&mut msg);
+    }
 
     if let Some(from_prior) = &msg.from_prior {
         let (unpacked_from_prior, from_prior_issuer_kid) =
-            FromPrior::unpack(from_prior, did_resolver).await?;
+            FromPrior::_unpack(from_prior, did_resolver, &opts.algorithms).await?;
 
         metadata.from_prior = Some(unpacked_from_prior);
         metadata.from_prior_issuer_kid = Some(from_prior_issuer_kid);
@@ -24,3 +54,85 @@ pub(crate) async fn _try_unpack_plaintext<'dr, 'sr>(
 
     Ok(Some(msg))
 }
+
+/// Parses plaintext message. If `lenient_timestamps` is true, timestamps
+/// being strings of digits are converted to numbers first.
+fn _parse_message(msg: &str, lenient_timestamps: bool) -> Result<Message> {
+    if !lenient_timestamps {
+        return Message::from_str(msg);
+    }
+
+    let mut value: Value = serde_json::from_str(msg).to_didcomm("Unable deserialize jwm")?;
+
+    if let Some(fields) = value.as_object_mut() {
+        for field in ["created_time", "expires_time"] {
+            let time = match fields.get(field) {
+                Some(Value::String(time)) => time.parse::<u64>().ok(),
+                _ => None,
+            };
+
+            if let Some(time) = time {
+                fields.insert(field.to_owned(), Value::from(time));
+            }
+        }
+    }
+
+    serde_json::from_value(value).to_didcomm("Unable deserialize jwm")
+}
+
+/// Parses `json` attachment data being stringified JSON.
+fn _parse_json_attachments(msg: &mut Message) {
+    for attachment in msg.attachments.iter_mut().flatten() {
+        if let AttachmentData::Json { ref mut value } = attachment.data {
+            if let Some(parsed) = stringified_json(&value.json) {
+                value.json = parsed;
+            }
+        }
+    }
+}
+
+fn _check_attachment_size(msg: &Message, max_size: u64) -> Result<()> {
+    for attachment in msg.attachments.iter().flatten() {
+        let size = match attachment.data {
+            AttachmentData::Base64 { ref value } => base64_decoded_size(&value.base64) as u64,
+            AttachmentData::Json { ref value } => serde_json::to_string(&value.json)
+                .map(|json| json.len() as u64)
+                .unwrap_or(0),
+            AttachmentData::Links { .. } => continue,
+        };
+
+        if size > max_size {
+            Err(err_msg(
+                ErrorKind::TooLarge,
+                format!(
+                    "Attachment size {} exceeds max allowed size {}",
+                    size, max_size
+                ),
+            ))?
+        }
+    }
+
+    Ok(())
+}
+
+fn _check_attachments_size(msg: &Message, max_size: u64) -> Result<()> {
+    let mut total_size: u64 = 0;
+
+    for attachment in msg.attachments.iter().flatten() {
+        if let AttachmentData::Base64 { ref value } = attachment.data {
+            total_size = total_size.saturating_add(base64_decoded_size(&value.base64) as u64);
+        }
+    }
+
+    if total_size > max_size {
+        Err(err_msg(
+            ErrorKind::TooLarge,
+            format!(
+                "Total attachments size {} exceeds max allowed size {}",
+                total_size, max_size
+            ),
+        ))?
+    }
+
+    Ok(())
+}
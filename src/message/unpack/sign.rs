@@ -1,19 +1,28 @@
-use askar_crypto::alg::{ed25519::Ed25519KeyPair, k256::K256KeyPair, p256::P256KeyPair};
+use std::collections::HashSet;
 
-use crate::jws::JWS;
+use futures::future::join_all;
+
+use crate::jwe::envelope::JWE;
+use crate::jws::{ParsedJWS, JWS};
 use crate::{
-    algorithms::SignAlg,
-    did::DIDResolver,
+    algorithms::{AlgorithmRegistry, SignAlg, SignatureAlgorithm},
+    did::{
+        did_resolver::{find_verification_method, resolve_key_directly},
+        DIDResolver, Purpose, VerificationMethod,
+    },
     error::{err_msg, ErrorKind, Result, ResultContext, ResultExt},
-    jws,
-    utils::{crypto::AsKnownKeyPair, did::did_or_url},
+    utils::{did::did_or_url, json::to_canonical_string},
     UnpackMetadata, UnpackOptions,
 };
 
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "unpack_sign", level = "debug", skip_all, err(level = "debug"))
+)]
 pub(crate) async fn _try_unapck_sign<'dr>(
     msg: &str,
     did_resolver: &'dr (dyn DIDResolver + 'dr),
-    _opts: &UnpackOptions,
+    opts: &UnpackOptions,
     metadata: &mut UnpackMetadata,
 ) -> Result<Option<String>> {
     let jws_json = msg;
@@ -24,37 +33,232 @@ pub(crate) async fn _try_unapck_sign<'dr>(
         Err(e) => Err(e)?,
     };
 
+    if let Some(max_signatures) = opts.max_signatures {
+        if jws.signatures.len() as u64 > max_signatures {
+            Err(err_msg(
+                ErrorKind::Malformed,
+                format!(
+                    "Too many signatures for jws, max allowed {}",
+                    max_signatures
+                ),
+            ))?
+        }
+    }
+
     let mut buf = vec![];
     let parsed_jws = jws.parse(&mut buf)?;
 
-    if parsed_jws.protected.len() != 1 {
+    let (signer_kids, sign_alg) = if opts.skip_signature_verification {
+        metadata.signature_verification_skipped = true;
+        _unverified_jws_signatures(&parsed_jws, &opts.algorithms)?
+    } else {
+        _verify_jws_signatures(&parsed_jws, did_resolver, opts).await?
+    };
+
+    let signer_kid = signer_kids
+        .first()
+        .ok_or_else(|| err_msg(ErrorKind::InvalidState, "No valid signatures"))?
+        .to_owned();
+
+    metadata.sign_alg = sign_alg;
+
+    // TODO: More precise error conversion
+    let payload = base64::decode_config(parsed_jws.jws.payload, base64::URL_SAFE_NO_PAD)
+        .kind(ErrorKind::Malformed, "Signed payloa is invalid base64")?;
+
+    let payload =
+        String::from_utf8(payload).kind(ErrorKind::Malformed, "Signed payload is invalid utf8")?;
+
+    // Unverified signatures don't authenticate the signer
+    if !opts.skip_signature_verification {
+        metadata.authenticated = true;
+        metadata.non_repudiation = true;
+    }
+
+    metadata.sign_from = Some(signer_kid);
+    metadata.sign_from_kids = Some(signer_kids);
+
+    #[cfg(feature = "tracing")]
+    if opts.skip_signature_verification {
+        tracing::debug!(sign_from = ?metadata.sign_from_kids, sign_alg = ?metadata.sign_alg, "signature verification skipped");
+    } else {
+        tracing::debug!(sign_from = ?metadata.sign_from_kids, sign_alg = ?metadata.sign_alg, "signature verified");
+    }
+    metadata.signed_message = Some(jws_json.into());
+
+    Ok(Some(payload))
+}
+
+/// Unpacks JWS wrapping encrypted message (see `PackEncryptedOptions::sign_outside`).
+/// Returns `None` if the message isn't JWS or its payload isn't JWE.
+pub(crate) async fn _try_unpack_outer_sign<'dr>(
+    msg: &str,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    opts: &UnpackOptions,
+    metadata: &mut UnpackMetadata,
+) -> Result<Option<String>> {
+    let jws = match JWS::from_str(msg) {
+        Ok(m) => m,
+        Err(e) if e.kind() == ErrorKind::Malformed => return Ok(None),
+        Err(e) => Err(e)?,
+    };
+
+    let payload = match base64::decode_config(jws.payload, base64::URL_SAFE_NO_PAD) {
+        Ok(payload) => payload,
+        Err(_) => return Ok(None),
+    };
+
+    match std::str::from_utf8(&payload).map(JWE::from_str) {
+        Ok(Ok(_)) => {}
+        _ => return Ok(None),
+    }
+
+    let payload = _try_unapck_sign(msg, did_resolver, opts, metadata).await?;
+    metadata.signed_outside = true;
+
+    Ok(payload)
+}
+
+/// Verifies all signatures of the parsed JWS concurrently. All signatures must be valid or,
+/// if `UnpackOptions::min_valid_signatures` is set, at least this amount of them made
+/// by distinct signer keys (the same key listed several times counts once).
+/// Returns distinct key IDs of valid signatures in order of signatures and algorithm of the first one.
+async fn _verify_jws_signatures<'dr>(
+    parsed_jws: &ParsedJWS<'_, '_>,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    opts: &UnpackOptions,
+) -> Result<(Vec<String>, Option<SignAlg>)> {
+    if parsed_jws.protected.is_empty() {
         Err(err_msg(
             ErrorKind::Malformed,
             "Wrong amount of signatures for jws",
         ))?
     }
 
+    let results = join_all((0..parsed_jws.protected.len()).map(|i| {
+        _verify_jws_signature(
+            parsed_jws,
+            i,
+            did_resolver,
+            opts.accept_canonical_signatures,
+            &opts.algorithms,
+        )
+    }))
+    .await;
+
+    let valid = match opts.min_valid_signatures {
+        Some(min_valid_signatures) => {
+            let total = results.len();
+            let mut valid: Vec<_> = results.into_iter().filter_map(|res| res.ok()).collect();
+            _dedup_signers(&mut valid);
+
+            if valid.is_empty() || (valid.len() as u64) < min_valid_signatures {
+                Err(err_msg(
+                    ErrorKind::Malformed,
+                    format!(
+                        "Only {} of {} signatures are valid and made by distinct signers, \
+                        at least {} required",
+                        valid.len(),
+                        total,
+                        min_valid_signatures.max(1)
+                    ),
+                ))?
+            }
+
+            valid
+        }
+        None => {
+            let mut valid = results.into_iter().collect::<Result<Vec<_>>>()?;
+            _dedup_signers(&mut valid);
+            valid
+        }
+    };
+
+    let sign_alg = valid
+        .first()
+        .map(|(_, sign_alg)| sign_alg.clone())
+        .ok_or_else(|| err_msg(ErrorKind::InvalidState, "No valid signatures"))?;
+
+    let signer_kids = valid.into_iter().map(|(kid, _)| kid).collect();
+
+    Ok((signer_kids, sign_alg))
+}
+
+/// Removes repeated signatures of the same signer key keeping the first one.
+fn _dedup_signers(valid: &mut Vec<(String, Option<SignAlg>)>) {
+    let mut signer_kids = HashSet::new();
+    valid.retain(|(kid, _)| signer_kids.insert(kid.clone()));
+}
+
+/// Returns key IDs of all signatures of the parsed JWS and algorithm of the first one
+/// WITHOUT verifying signatures (see `UnpackOptions::skip_signature_verification`).
+fn _unverified_jws_signatures(
+    parsed_jws: &ParsedJWS<'_, '_>,
+    algorithms: &AlgorithmRegistry,
+) -> Result<(Vec<String>, Option<SignAlg>)> {
     let alg = &parsed_jws
         .protected
         .first()
-        .ok_or_else(|| {
-            err_msg(
-                ErrorKind::InvalidState,
-                "Unexpected absence of first protected header",
-            )
-        })?
+        .ok_or_else(|| err_msg(ErrorKind::Malformed, "Wrong amount of signatures for jws"))?
+        .alg;
+
+    let sign_alg = algorithms.signature_algorithm(&alg.to_string())?.sign_alg();
+
+    let signer_kids = parsed_jws
+        .jws
+        .signatures
+        .iter()
+        .map(|signature| signature.header.kid.to_owned())
+        .collect();
+
+    Ok((signer_kids, sign_alg))
+}
+
+/// Verifies the single signature of the parsed JWS with the signer `authentication` key.
+/// Returns signer key ID and used signature algorithm.
+pub(crate) async fn _verify_jws<'dr>(
+    parsed_jws: &ParsedJWS<'_, '_>,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    accept_canonical_signatures: bool,
+    algorithms: &AlgorithmRegistry,
+) -> Result<(String, Option<SignAlg>)> {
+    if parsed_jws.protected.len() != 1 {
+        Err(err_msg(
+            ErrorKind::Malformed,
+            "Wrong amount of signatures for jws",
+        ))?
+    }
+
+    _verify_jws_signature(
+        parsed_jws,
+        0,
+        did_resolver,
+        accept_canonical_signatures,
+        algorithms,
+    )
+    .await
+}
+
+/// Verifies `i`-th signature of the parsed JWS with the signer `authentication` key.
+/// Returns signer key ID and used signature algorithm.
+async fn _verify_jws_signature<'dr>(
+    parsed_jws: &ParsedJWS<'_, '_>,
+    i: usize,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    accept_canonical_signatures: bool,
+    algorithms: &AlgorithmRegistry,
+) -> Result<(String, Option<SignAlg>)> {
+    let alg = &parsed_jws
+        .protected
+        .get(i)
+        .ok_or_else(|| err_msg(ErrorKind::InvalidState, "Invalid protected header index"))?
         .alg;
 
     let signer_kid = parsed_jws
         .jws
         .signatures
-        .first()
-        .ok_or_else(|| {
-            err_msg(
-                ErrorKind::InvalidState,
-                "Unexpected absence of first signature",
-            )
-        })?
+        .get(i)
+        .ok_or_else(|| err_msg(ErrorKind::InvalidState, "Invalid signature index"))?
         .header
         .kid;
 
@@ -67,6 +271,47 @@ pub(crate) async fn _try_unapck_sign<'dr>(
         ))?
     }
 
+    let signer_key = match resolve_key_directly(signer_kid, Purpose::Authentication, did_resolver)
+        .await
+        .context("Unable resolve signer key")?
+    {
+        Some(signer_key) => signer_key,
+        None => _signer_key(signer_did, signer_kid, did_resolver).await?,
+    };
+
+    // Some implementations sign JCS canonicalized plaintext instead of transmitted payload
+    let canonical_payload = if accept_canonical_signatures {
+        _canonical_payload(parsed_jws.jws.payload)
+    } else {
+        None
+    };
+
+    let canonical_payload = canonical_payload.as_deref();
+
+    let algorithm = algorithms.signature_algorithm(&alg.to_string())?;
+
+    let valid = _verify(
+        parsed_jws,
+        i,
+        algorithm.as_ref(),
+        &signer_key,
+        canonical_payload,
+    )
+    .context("Unable verify sign envelope")?;
+
+    if !valid {
+        Err(err_msg(ErrorKind::Malformed, "Wrong signature"))?
+    }
+
+    Ok((signer_kid.to_owned(), algorithm.sign_alg()))
+}
+
+/// Resolves signer DID Doc and looks for the signer `authentication` key in it.
+async fn _signer_key<'dr>(
+    signer_did: &str,
+    signer_kid: &str,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+) -> Result<VerificationMethod> {
     let signer_ddoc = did_resolver
         .resolve(signer_did)
         .await
@@ -80,72 +325,66 @@ pub(crate) async fn _try_unapck_sign<'dr>(
         .ok_or_else(|| err_msg(ErrorKind::DIDUrlNotFound, "Signer kid not found in did"))?
         .as_str();
 
-    let signer_key = signer_ddoc
-        .verification_methods
-        .iter()
-        .find(|&vm| &vm.id == signer_kid)
+    find_verification_method(&signer_ddoc, signer_kid, did_resolver)
+        .await
+        .context("Unable resolve signer key")?
         .ok_or_else(|| {
             err_msg(
                 ErrorKind::DIDUrlNotFound,
                 "Sender verification method not found in did",
             )
-        })?;
+        })
+}
 
-    let valid = match alg {
-        jws::Algorithm::EdDSA => {
-            metadata.sign_alg = Some(SignAlg::EdDSA);
+fn _verify(
+    parsed_jws: &ParsedJWS,
+    i: usize,
+    algorithm: &dyn SignatureAlgorithm,
+    signer_key: &VerificationMethod,
+    canonical_payload: Option<&str>,
+) -> Result<bool> {
+    if _verify_with_payload(parsed_jws, i, algorithm, signer_key, parsed_jws.jws.payload)? {
+        return Ok(true);
+    }
 
-            let signer_key = signer_key
-                .as_ed25519()
-                .context("Unable instantiate signer key")?;
+    match canonical_payload {
+        Some(payload) => _verify_with_payload(parsed_jws, i, algorithm, signer_key, payload),
+        None => Ok(false),
+    }
+}
 
-            parsed_jws
-                .verify::<Ed25519KeyPair>((signer_kid, &signer_key))
-                .context("Unable verify sign envelope")?
-        }
-        jws::Algorithm::Es256 => {
-            metadata.sign_alg = Some(SignAlg::ES256);
+/// Verifies `i`-th signature as it was produced over the given base64url encoded `payload`.
+fn _verify_with_payload(
+    parsed_jws: &ParsedJWS,
+    i: usize,
+    algorithm: &dyn SignatureAlgorithm,
+    signer_key: &VerificationMethod,
+    payload: &str,
+) -> Result<bool> {
+    let signature = parsed_jws
+        .jws
+        .signatures
+        .get(i)
+        .ok_or_else(|| err_msg(ErrorKind::InvalidState, "Invalid signature index"))?;
 
-            let signer_key = signer_key
-                .as_p256()
-                .context("Unable instantiate signer key")?;
+    let sign_input = format!("{}.{}", signature.protected, payload);
 
-            parsed_jws
-                .verify::<P256KeyPair>((signer_kid, &signer_key))
-                .context("Unable verify sign envelope")?
-        }
-        jws::Algorithm::Es256K => {
-            metadata.sign_alg = Some(SignAlg::ES256K);
+    let signature = base64::decode_config(&signature.signature, base64::URL_SAFE_NO_PAD)
+        .kind(ErrorKind::Malformed, "Unable decode signature")?;
 
-            let signer_key = signer_key
-                .as_k256()
-                .context("Unable instantiate signer key")?;
+    algorithm.verify(signer_key, sign_input.as_bytes(), &signature)
+}
 
-            parsed_jws
-                .verify::<K256KeyPair>((signer_kid, &signer_key))
-                .context("Unable verify sign envelope")?
-        }
-        jws::Algorithm::Other(_) => Err(err_msg(
-            ErrorKind::Unsupported,
-            "Unsupported signature algorithm",
-        ))?,
-    };
+/// Returns base64url encoded JCS canonicalized form of the payload or `None`
+/// if payload isn't JSON or is already canonical.
+fn _canonical_payload(payload: &str) -> Option<String> {
+    let decoded = base64::decode_config(payload, base64::URL_SAFE_NO_PAD).ok()?;
+    let value: serde_json::Value = serde_json::from_slice(&decoded).ok()?;
+    let canonical = to_canonical_string(&value).ok()?;
 
-    if !valid {
-        Err(err_msg(ErrorKind::Malformed, "Wrong signature"))?
+    if canonical.as_bytes() == decoded.as_slice() {
+        return None;
     }
 
-    // TODO: More precise error conversion
-    let payload = base64::decode_config(parsed_jws.jws.payload, base64::URL_SAFE_NO_PAD)
-        .kind(ErrorKind::Malformed, "Signed payloa is invalid base64")?;
-
-    let payload =
-        String::from_utf8(payload).kind(ErrorKind::Malformed, "Signed payload is invalid utf8")?;
-
-    metadata.authenticated = true;
-    metadata.non_repudiation = true;
-    metadata.sign_from = Some(signer_kid.into());
-    metadata.signed_message = Some(jws_json.into());
-
-    Ok(Some(payload))
+    Some(base64::encode_config(canonical, base64::URL_SAFE_NO_PAD))
 }
@@ -0,0 +1,404 @@
+use serde_json::{Map, Value};
+
+use super::message::PLAINTEXT_TYP;
+use crate::{BodyValidators, Message};
+
+/// Structural issue of plaintext message found by `Message::validate_all`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ValidationError {
+    /// Path to invalid field (for ex. `attachments[0].data`). Empty for the message itself.
+    pub field: String,
+
+    /// Human readable description of the issue.
+    pub reason: String,
+}
+
+impl ValidationError {
+    fn new(field: impl Into<String>, reason: impl Into<String>) -> Self {
+        ValidationError {
+            field: field.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl Message {
+    /// Validates the plaintext message given as JSON string. Unlike unpacking it doesn't stop
+    /// on the first problem and reports all found issues at once, so they can be fixed in one pass.
+    /// Besides structure, runs the same content checks as unpacking with default `UnpackOptions`:
+    /// built-in body validators, unique attachment ids and no stringified JSON attachment data.
+    /// Content checks run only if the message structure is valid.
+    ///
+    /// # Params
+    /// - `msg` plaintext message as JSON string
+    ///
+    /// # Returns
+    /// `Ok` if unpacking the message as a plaintext with default `UnpackOptions` passes
+    /// validation or list of all found issues.
+    pub fn validate_all(msg: &str) -> std::result::Result<(), Vec<ValidationError>> {
+        let raw_msg = msg;
+
+        let msg: Value = serde_json::from_str(msg)
+            .map_err(|_| vec![ValidationError::new("", "Message is not a valid JSON")])?;
+
+        let msg = msg
+            .as_object()
+            .ok_or_else(|| vec![ValidationError::new("", "Message must be a JSON object")])?;
+
+        let mut errors = vec![];
+
+        _required_string(msg, "id", &mut errors);
+
+        if let Some(typ) = _required_string(msg, "typ", &mut errors) {
+            if typ != PLAINTEXT_TYP {
+                errors.push(ValidationError::new(
+                    "typ",
+                    format!("`typ` must be \"{}\"", PLAINTEXT_TYP),
+                ));
+            }
+        }
+
+        _required_string(msg, "type", &mut errors);
+
+        if !msg.contains_key("body") {
+            errors.push(ValidationError::new("body", "Field is required"));
+        }
+
+        for field in ["from", "thid", "pthid", "from_prior"] {
+            _optional_string(msg, field, field, &mut errors);
+        }
+
+        for field in ["created_time", "expires_time"] {
+            _optional_u64(msg, field, field, &mut errors);
+        }
+
+        _optional_strings(msg, "to", "to", &mut errors);
+
+        match msg.get("attachments") {
+            None | Some(Value::Null) => (),
+            Some(Value::Array(attachments)) => {
+                for (i, attachment) in attachments.iter().enumerate() {
+                    _validate_attachment(attachment, &format!("attachments[{}]", i), &mut errors);
+                }
+            }
+            Some(_) => errors.push(ValidationError::new(
+                "attachments",
+                "Must be an array of attachments",
+            )),
+        }
+
+        if errors.is_empty() {
+            match Message::from_str(raw_msg) {
+                Ok(msg) => {
+                    for (field, err) in msg.content_issues(&BodyValidators::default(), false) {
+                        errors.push(ValidationError::new(field, format!("{:#}", err.source)));
+                    }
+                }
+                Err(err) => errors.push(ValidationError::new("", format!("{:#}", err.source))),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn _validate_attachment(attachment: &Value, path: &str, errors: &mut Vec<ValidationError>) {
+    let attachment = match attachment.as_object() {
+        Some(attachment) => attachment,
+        None => {
+            errors.push(ValidationError::new(
+                path,
+                "Attachment must be a JSON object",
+            ));
+            return;
+        }
+    };
+
+    for field in ["id", "description", "filename", "media_type", "format"] {
+        _optional_string(attachment, field, &format!("{}.{}", path, field), errors);
+    }
+
+    for field in ["lastmod_time", "byte_count"] {
+        _optional_u64(attachment, field, &format!("{}.{}", path, field), errors);
+    }
+
+    let path = format!("{}.data", path);
+
+    let data = match attachment.get("data") {
+        Some(Value::Object(data)) => data,
+        None | Some(Value::Null) => {
+            errors.push(ValidationError::new(path, "Field is required"));
+            return;
+        }
+        Some(_) => {
+            errors.push(ValidationError::new(path, "Must be a JSON object"));
+            return;
+        }
+    };
+
+    _optional_string(data, "jws", &format!("{}.jws", path), errors);
+
+    if data.contains_key("base64") {
+        _optional_string(data, "base64", &format!("{}.base64", path), errors);
+    } else if data.contains_key("links") && !data.contains_key("json") {
+        _optional_strings(data, "links", &format!("{}.links", path), errors);
+
+        if data.contains_key("hash") {
+            _optional_string(data, "hash", &format!("{}.hash", path), errors);
+        } else {
+            errors.push(ValidationError::new(
+                format!("{}.hash", path),
+                "Field is required for links data",
+            ));
+        }
+    } else if !data.contains_key("json") {
+        errors.push(ValidationError::new(
+            path,
+            "Must contain `base64`, `json` or `links`",
+        ));
+    }
+}
+
+fn _required_string<'a>(
+    obj: &'a Map<String, Value>,
+    field: &str,
+    errors: &mut Vec<ValidationError>,
+) -> Option<&'a str> {
+    match obj.get(field) {
+        Some(Value::String(value)) => Some(value),
+        None => {
+            errors.push(ValidationError::new(field, "Field is required"));
+            None
+        }
+        Some(_) => {
+            errors.push(ValidationError::new(field, "Must be a string"));
+            None
+        }
+    }
+}
+
+fn _optional_string(
+    obj: &Map<String, Value>,
+    field: &str,
+    path: &str,
+    errors: &mut Vec<ValidationError>,
+) {
+    match obj.get(field) {
+        None | Some(Value::Null) | Some(Value::String(_)) => (),
+        Some(_) => errors.push(ValidationError::new(path, "Must be a string")),
+    }
+}
+
+fn _optional_strings(
+    obj: &Map<String, Value>,
+    field: &str,
+    path: &str,
+    errors: &mut Vec<ValidationError>,
+) {
+    match obj.get(field) {
+        None | Some(Value::Null) => (),
+        Some(Value::Array(values)) if values.iter().all(|v| v.is_string()) => (),
+        Some(_) => errors.push(ValidationError::new(path, "Must be an array of strings")),
+    }
+}
+
+fn _optional_u64(
+    obj: &Map<String, Value>,
+    field: &str,
+    path: &str,
+    errors: &mut Vec<ValidationError>,
+) {
+    match obj.get(field) {
+        None | Some(Value::Null) => (),
+        Some(value) if value.is_u64() => (),
+        Some(_) => errors.push(ValidationError::new(path, "Must be a non-negative integer")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_vectors::{
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_AS_INT_ARRAY,
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_AS_STRING,
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_EMPTY_DATA,
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_LINKS_NO_HASH,
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_NO_DATA, INVALID_PLAINTEXT_MSG_ATTACHMENTS_NULL_DATA,
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_STRINGIFIED_JSON,
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_WRONG_DATA,
+            INVALID_PLAINTEXT_MSG_ATTACHMENTS_WRONG_ID, INVALID_PLAINTEXT_MSG_EMPTY,
+            INVALID_PLAINTEXT_MSG_EMPTY_ATTACHMENTS, INVALID_PLAINTEXT_MSG_NO_BODY,
+            INVALID_PLAINTEXT_MSG_NO_ID, INVALID_PLAINTEXT_MSG_NO_TYP,
+            INVALID_PLAINTEXT_MSG_NO_TYPE, INVALID_PLAINTEXT_MSG_STRING,
+            INVALID_PLAINTEXT_MSG_WRONG_TYP, PLAINTEXT_FROM_PRIOR, PLAINTEXT_MSG_ATTACHMENT_BASE64,
+            PLAINTEXT_MSG_ATTACHMENT_JSON, PLAINTEXT_MSG_ATTACHMENT_LINKS,
+            PLAINTEXT_MSG_ATTACHMENT_MULTI_1, PLAINTEXT_MSG_ATTACHMENT_MULTI_2,
+            PLAINTEXT_MSG_MINIMAL, PLAINTEXT_MSG_SIMPLE,
+        },
+        Message,
+    };
+
+    use super::*;
+
+    #[test]
+    fn validate_all_works() {
+        for msg in [
+            PLAINTEXT_MSG_SIMPLE,
+            PLAINTEXT_MSG_MINIMAL,
+            PLAINTEXT_FROM_PRIOR,
+            PLAINTEXT_MSG_ATTACHMENT_BASE64,
+            PLAINTEXT_MSG_ATTACHMENT_JSON,
+            PLAINTEXT_MSG_ATTACHMENT_LINKS,
+            PLAINTEXT_MSG_ATTACHMENT_MULTI_1,
+            PLAINTEXT_MSG_ATTACHMENT_MULTI_2,
+        ] {
+            assert_eq!(Message::validate_all(msg), Ok(()));
+        }
+    }
+
+    #[test]
+    fn validate_all_works_invalid() {
+        for (msg, exp_fields) in [
+            (INVALID_PLAINTEXT_MSG_STRING, vec![""]),
+            (
+                INVALID_PLAINTEXT_MSG_EMPTY,
+                vec!["id", "typ", "type", "body"],
+            ),
+            (INVALID_PLAINTEXT_MSG_NO_ID, vec!["id"]),
+            (INVALID_PLAINTEXT_MSG_NO_TYP, vec!["typ"]),
+            (INVALID_PLAINTEXT_MSG_NO_TYPE, vec!["type"]),
+            (INVALID_PLAINTEXT_MSG_NO_BODY, vec!["body"]),
+            (INVALID_PLAINTEXT_MSG_WRONG_TYP, vec!["typ"]),
+            (
+                INVALID_PLAINTEXT_MSG_EMPTY_ATTACHMENTS,
+                vec!["attachments[0].data"],
+            ),
+            (
+                INVALID_PLAINTEXT_MSG_ATTACHMENTS_NO_DATA,
+                vec!["attachments[0].data"],
+            ),
+            (
+                INVALID_PLAINTEXT_MSG_ATTACHMENTS_EMPTY_DATA,
+                vec!["attachments[0].data"],
+            ),
+            (
+                INVALID_PLAINTEXT_MSG_ATTACHMENTS_LINKS_NO_HASH,
+                vec!["attachments[0].data.hash"],
+            ),
+            (
+                INVALID_PLAINTEXT_MSG_ATTACHMENTS_AS_STRING,
+                vec!["attachments"],
+            ),
+            (
+                INVALID_PLAINTEXT_MSG_ATTACHMENTS_AS_INT_ARRAY,
+                vec!["attachments[0]"],
+            ),
+            (
+                INVALID_PLAINTEXT_MSG_ATTACHMENTS_WRONG_DATA,
+                vec!["attachments[0].data"],
+            ),
+            (
+                INVALID_PLAINTEXT_MSG_ATTACHMENTS_WRONG_ID,
+                vec!["attachments[0].id", "attachments[0].data"],
+            ),
+            (
+                INVALID_PLAINTEXT_MSG_ATTACHMENTS_NULL_DATA,
+                vec!["attachments[0].data"],
+            ),
+        ] {
+            let errors = Message::validate_all(msg).expect_err("res is ok");
+            let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+            assert_eq!(fields, exp_fields);
+
+            // Every message reported here is rejected by unpack as well
+            assert!(Message::from_str(msg)
+                .and_then(|msg| msg.validate())
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn validate_all_works_multiple_issues() {
+        let msg = r#"
+        {
+            "typ": "application/didcomm-plain+json-unknown",
+            "type": 1,
+            "body": {},
+            "to": "did:example:bob",
+            "created_time": -1,
+            "attachments": [
+                {"id": "1", "data": {"base64": "qwerty"}},
+                {"id": 2, "data": {"links": ["1"]}},
+                "invalid"
+            ]
+        }
+        "#;
+
+        let errors = Message::validate_all(msg).expect_err("res is ok");
+
+        assert_eq!(
+            errors,
+            vec![
+                ValidationError::new("id", "Field is required"),
+                ValidationError::new("typ", "`typ` must be \"application/didcomm-plain+json\""),
+                ValidationError::new("type", "Must be a string"),
+                ValidationError::new("created_time", "Must be a non-negative integer"),
+                ValidationError::new("to", "Must be an array of strings"),
+                ValidationError::new("attachments[1].id", "Must be a string"),
+                ValidationError::new(
+                    "attachments[1].data.hash",
+                    "Field is required for links data"
+                ),
+                ValidationError::new("attachments[2]", "Attachment must be a JSON object"),
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_all_works_content_issues() {
+        let msg = r#"
+        {
+            "id": "1234567890",
+            "typ": "application/didcomm-plain+json",
+            "type": "https://didcomm.org/routing/2.0/forward",
+            "body": {"next": "bob"},
+            "attachments": [
+                {"id": "1", "data": {"json": "{\"a\": 1}"}},
+                {"id": "1", "data": {"base64": "qwerty"}}
+            ]
+        }
+        "#;
+
+        let errors = Message::validate_all(msg).expect_err("res is ok");
+
+        assert_eq!(
+            errors,
+            vec![
+                ValidationError::new(
+                    "body",
+                    "Invalid body of `https://didcomm.org/routing/2.0/forward` message: \
+                    `next` must be a DID or DID URL"
+                ),
+                ValidationError::new("attachments", "Duplicate attachment id `1`"),
+                ValidationError::new("attachments", "Attachment json data is stringified JSON"),
+            ]
+        );
+
+        // Every issue reported here is rejected by unpack as well
+        assert!(Message::from_str(msg)
+            .and_then(|msg| msg.validate())
+            .is_err());
+
+        assert_eq!(
+            Message::validate_all(INVALID_PLAINTEXT_MSG_ATTACHMENTS_STRINGIFIED_JSON),
+            Err(vec![ValidationError::new(
+                "attachments",
+                "Attachment json data is stringified JSON"
+            )])
+        );
+    }
+}
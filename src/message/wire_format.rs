@@ -0,0 +1,126 @@
+//! Wire format compatibility tests against frozen outputs of previous releases
+//! (see `test_vectors::wire_format`).
+//!
+//! Interop tests compare deterministic fields only, while these tests require current code
+//! to reproduce frozen vectors byte-for-byte. Only packing paths without randomness are covered:
+//! plaintext and EdDSA signed messages (Ed25519 signatures are deterministic).
+//!
+//! Regenerating vectors after an intentional wire format change:
+//! 1. Run `cargo test wire_format_pack_works`. Failed assertion reports
+//!    the current output (`left`) of the first vector that doesn't match.
+//! 2. Add current outputs to `test_vectors::wire_format` with the next version prefix
+//!    (for ex. `WIRE_V2_`) and switch `_wire_vectors` to them.
+//! 3. Keep previous vectors in `_frozen_vectors`, so they are still checked to be unpackable.
+
+use crate::{
+    did::resolvers::ExampleDIDResolver,
+    secrets::resolvers::ExampleSecretsResolver,
+    test_vectors::{
+        ALICE_DID_DOC, ALICE_SECRETS, BOB_DID_DOC, BOB_SECRETS, MESSAGE_ATTACHMENT_MULTI_1,
+        MESSAGE_MINIMAL, MESSAGE_SIMPLE, WIRE_V1_PLAINTEXT_ATTACHMENT_MULTI,
+        WIRE_V1_PLAINTEXT_MINIMAL, WIRE_V1_PLAINTEXT_SIMPLE,
+        WIRE_V1_SIGNED_ED25519_ATTACHMENT_MULTI, WIRE_V1_SIGNED_ED25519_SIMPLE,
+    },
+    Message, UnpackOptions,
+};
+
+/// How the message is packed.
+#[derive(Clone, Copy)]
+enum Pack {
+    Plaintext,
+    SignedEd25519,
+}
+
+/// (name, message, pack, frozen output) of the current wire format version.
+fn _wire_vectors() -> Vec<(&'static str, &'static Message, Pack, &'static str)> {
+    vec![
+        (
+            "WIRE_V1_PLAINTEXT_SIMPLE",
+            &*MESSAGE_SIMPLE,
+            Pack::Plaintext,
+            WIRE_V1_PLAINTEXT_SIMPLE,
+        ),
+        (
+            "WIRE_V1_PLAINTEXT_MINIMAL",
+            &*MESSAGE_MINIMAL,
+            Pack::Plaintext,
+            WIRE_V1_PLAINTEXT_MINIMAL,
+        ),
+        (
+            "WIRE_V1_PLAINTEXT_ATTACHMENT_MULTI",
+            &*MESSAGE_ATTACHMENT_MULTI_1,
+            Pack::Plaintext,
+            WIRE_V1_PLAINTEXT_ATTACHMENT_MULTI,
+        ),
+        (
+            "WIRE_V1_SIGNED_ED25519_SIMPLE",
+            &*MESSAGE_SIMPLE,
+            Pack::SignedEd25519,
+            WIRE_V1_SIGNED_ED25519_SIMPLE,
+        ),
+        (
+            "WIRE_V1_SIGNED_ED25519_ATTACHMENT_MULTI",
+            &*MESSAGE_ATTACHMENT_MULTI_1,
+            Pack::SignedEd25519,
+            WIRE_V1_SIGNED_ED25519_ATTACHMENT_MULTI,
+        ),
+    ]
+}
+
+/// (name, message, frozen output) of all wire format versions that must stay unpackable.
+fn _frozen_vectors() -> Vec<(&'static str, &'static Message, &'static str)> {
+    _wire_vectors()
+        .into_iter()
+        .map(|(name, msg, _, frozen)| (name, msg, frozen))
+        .collect()
+}
+
+#[tokio::test]
+async fn wire_format_pack_works() {
+    let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+    let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+    for (name, msg, pack, frozen) in _wire_vectors() {
+        let packed = match pack {
+            Pack::Plaintext => msg
+                .pack_plaintext(&did_resolver)
+                .await
+                .expect("pack is ok."),
+            Pack::SignedEd25519 => {
+                msg.pack_signed("did:example:alice#key-1", &did_resolver, &secrets_resolver)
+                    .await
+                    .expect("pack is ok.")
+                    .0
+            }
+        };
+
+        assert_eq!(
+            packed, frozen,
+            "Packed message doesn't match frozen vector `{}`",
+            name
+        );
+    }
+}
+
+#[tokio::test]
+async fn wire_format_unpack_works() {
+    let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+    let secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+    for (name, msg, frozen) in _frozen_vectors() {
+        let (unpacked, _) = Message::unpack(
+            frozen,
+            &did_resolver,
+            &secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .unwrap_or_else(|e| panic!("Unable unpack frozen vector `{}`: {}", name, e));
+
+        assert_eq!(
+            &unpacked, msg,
+            "Unexpected plaintext for frozen vector `{}`",
+            name
+        );
+    }
+}
@@ -0,0 +1,642 @@
+//! Legacy DIDComm v1 encryption envelope
+//! (https://github.com/hyperledger/aries-rfcs/tree/main/features/0019-encryption-envelope).
+//!
+//! Allows to exchange messages with agents that understand only DIDComm v1 during migration.
+//! DIDComm v1 recipients and senders are identified by base58 encoded Ed25519 public keys
+//! (verkeys) instead of DID URLs. Plaintext is opaque for the envelope, so any
+//! DIDComm v1 message (JSON) can be packed.
+
+use askar_crypto::{
+    alg::{
+        chacha20::{Chacha20Key, C20P, XC20P},
+        ed25519::Ed25519KeyPair,
+        x25519::X25519KeyPair,
+    },
+    buffer::SecretBytes,
+    encrypt::{
+        crypto_box::{
+            crypto_box, crypto_box_open, crypto_box_seal, crypto_box_seal_open, CBOX_NONCE_LENGTH,
+        },
+        KeyAeadInPlace,
+    },
+    random,
+    repr::{KeyPublicBytes, KeySecretBytes, ToPublicBytes, ToSecretBytes},
+};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{
+    error::{err_msg, ErrorKind, Result, ResultContext, ResultExt},
+    jwe::decode_base64url,
+    secrets::SecretsResolver,
+    utils::crypto::{AsKnownKeyPair, KnownKeyAlg},
+};
+
+const TYP: &str = "JWM/1.0";
+const ENC_XC20P: &str = "xchacha20poly1305_ietf";
+const ENC_C20P: &str = "chacha20poly1305_ietf";
+const ALG_AUTHCRYPT: &str = "Authcrypt";
+const ALG_ANONCRYPT: &str = "Anoncrypt";
+
+/// Produces DIDComm v1 encrypted message (`Authcrypt` if sender is specified, `Anoncrypt` otherwise).
+/// Content is encrypted with XChaCha20Poly1305, content encryption key is encrypted
+/// for each recipient with libsodium compatible `crypto_box` or `crypto_box_seal`.
+///
+/// # Params
+/// - `msg` DIDComm v1 plaintext message to be encrypted.
+/// - `to` base58 encoded Ed25519 public keys (verkeys) of recipients.
+/// - `from` key ID of sender Ed25519 secret for `Authcrypt`. If None, `Anoncrypt` is used.
+/// - `secrets_resolver` instance of `SecretsResolver` to resolve sender secret.
+///
+/// # Returns
+/// DIDComm v1 encrypted message as JSON string.
+///
+/// # Errors
+/// - `IllegalArgument` No recipients or recipient verkey is invalid.
+/// - `SecretNotFound` Sender secret is not found.
+/// - `Unsupported` Sender secret is not an Ed25519 key.
+/// - `InvalidState` Indicates library error.
+/// - `IOError` IO error during secrets resolving.
+pub async fn pack_encrypted_v1<'sr>(
+    msg: &str,
+    to: &[&str],
+    from: Option<&str>,
+    secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+) -> Result<String> {
+    if to.is_empty() {
+        Err(err_msg(ErrorKind::IllegalArgument, "No recipients"))?
+    }
+
+    let sender = match from {
+        Some(from) => {
+            let key = _ed25519_secret(from, secrets_resolver)
+                .await?
+                .ok_or_else(|| err_msg(ErrorKind::SecretNotFound, "Sender secret not found"))?;
+
+            Some((_verkey(&key)?, key.to_x25519_keypair()))
+        }
+        None => None,
+    };
+
+    let cek = {
+        let mut cek = Zeroizing::new([0u8; 32]);
+        random::fill_random(&mut cek[..]);
+
+        Chacha20Key::<XC20P>::from_secret_bytes(&cek[..])
+            .kind(ErrorKind::InvalidState, "Unable generate cek")?
+    };
+
+    let cek_bytes = cek
+        .to_secret_bytes()
+        .kind(ErrorKind::InvalidState, "Unable get cek bytes")?;
+
+    let mut recipients = Vec::with_capacity(to.len());
+
+    for &verkey in to {
+        let recipient_key = match _x25519_public(verkey) {
+            Ok(key) => key,
+            Err(_) => Err(err_msg(
+                ErrorKind::IllegalArgument,
+                format!("Invalid recipient verkey `{}`", verkey),
+            ))?,
+        };
+
+        let recipient = match sender {
+            Some((ref sender_verkey, ref sender_key)) => {
+                let mut nonce = [0u8; CBOX_NONCE_LENGTH];
+                random::fill_random(&mut nonce);
+
+                let mut encrypted_key = cek_bytes.clone();
+
+                crypto_box(&recipient_key, sender_key, &mut encrypted_key, &nonce)
+                    .kind(ErrorKind::InvalidState, "Unable encrypt cek")?;
+
+                let sender = crypto_box_seal(&recipient_key, sender_verkey.as_bytes())
+                    .kind(ErrorKind::InvalidState, "Unable encrypt sender verkey")?;
+
+                RecipientV1 {
+                    encrypted_key: _encode_base64url(encrypted_key.as_ref()),
+                    header: RecipientHeaderV1 {
+                        kid: verkey.to_owned(),
+                        sender: Some(_encode_base64url(sender.as_ref())),
+                        iv: Some(_encode_base64url(&nonce)),
+                    },
+                }
+            }
+            None => {
+                let encrypted_key = crypto_box_seal(&recipient_key, cek_bytes.as_ref())
+                    .kind(ErrorKind::InvalidState, "Unable encrypt cek")?;
+
+                RecipientV1 {
+                    encrypted_key: _encode_base64url(encrypted_key.as_ref()),
+                    header: RecipientHeaderV1 {
+                        kid: verkey.to_owned(),
+                        sender: None,
+                        iv: None,
+                    },
+                }
+            }
+        };
+
+        recipients.push(recipient);
+    }
+
+    let protected = {
+        let protected = ProtectedV1 {
+            enc: ENC_XC20P.to_owned(),
+            typ: TYP.to_owned(),
+            alg: match sender {
+                Some(_) => ALG_AUTHCRYPT,
+                None => ALG_ANONCRYPT,
+            }
+            .to_owned(),
+            recipients,
+        };
+
+        let protected = serde_json::to_string(&protected)
+            .kind(ErrorKind::InvalidState, "Unable serialize protected header")?;
+
+        _encode_base64url(protected.as_bytes())
+    };
+
+    let mut iv = vec![0u8; cek.aead_params().nonce_length];
+    random::fill_random(&mut iv);
+
+    let mut buf = SecretBytes::with_capacity(msg.len() + cek.aead_params().tag_length);
+    buf.extend_from_slice(msg.as_bytes());
+
+    let ciphertext_len = cek
+        .encrypt_in_place(&mut buf, &iv, protected.as_bytes())
+        .kind(ErrorKind::InvalidState, "Unable encrypt content")?;
+
+    let (ciphertext, tag) = buf.as_ref().split_at(ciphertext_len);
+
+    let envelope = EnvelopeV1 {
+        protected,
+        iv: _encode_base64url(&iv),
+        ciphertext: _encode_base64url(ciphertext),
+        tag: _encode_base64url(tag),
+    };
+
+    let envelope = serde_json::to_string(&envelope)
+        .kind(ErrorKind::InvalidState, "Unable serialize envelope")?;
+
+    Ok(envelope)
+}
+
+/// Unpacks DIDComm v1 encrypted message (`Authcrypt` or `Anoncrypt`).
+///
+/// Recipient secret is looked up among secrets listed by `SecretsResolver::get_available_kids`,
+/// so the resolver must implement it. Only Ed25519 secrets are considered.
+///
+/// # Params
+/// - `msg` DIDComm v1 encrypted message as JSON string.
+/// - `secrets_resolver` instance of `SecretsResolver` to resolve recipient secrets.
+///
+/// # Returns
+/// Tuple `(plaintext, metadata)`.
+/// - `plaintext` decrypted DIDComm v1 message.
+/// - `metadata` recipient and sender (if authenticated) keys of the message.
+///
+/// # Errors
+/// - `Malformed` Message is not a valid DIDComm v1 encrypted message or can't be decrypted.
+/// - `Unsupported` Message uses unsupported `alg` or `enc`.
+/// - `SecretNotFound` No recipient secrets found.
+/// - `InvalidState` Indicates library error.
+/// - `IOError` IO error during secrets resolving.
+pub async fn unpack_v1<'sr>(
+    msg: &str,
+    secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+) -> Result<(String, UnpackV1Metadata)> {
+    let envelope: EnvelopeV1 =
+        serde_json::from_str(msg).kind(ErrorKind::Malformed, "Unable parse envelope")?;
+
+    let protected: ProtectedV1 = {
+        let protected = decode_base64url(&envelope.protected, true)
+            .kind(ErrorKind::Malformed, "Unable decode protected header")?;
+
+        serde_json::from_slice(&protected)
+            .kind(ErrorKind::Malformed, "Unable parse protected header")?
+    };
+
+    if !protected.typ.starts_with("JWM") {
+        Err(err_msg(
+            ErrorKind::Malformed,
+            format!("Unexpected typ `{}`", protected.typ),
+        ))?
+    }
+
+    let authenticated = match protected.alg.as_str() {
+        ALG_AUTHCRYPT => true,
+        ALG_ANONCRYPT => false,
+        alg => Err(err_msg(
+            ErrorKind::Unsupported,
+            format!("Unsupported alg `{}`", alg),
+        ))?,
+    };
+
+    let (recipient, recipient_kid, recipient_key) =
+        _find_recipient(&protected.recipients, secrets_resolver)
+            .await?
+            .ok_or_else(|| err_msg(ErrorKind::SecretNotFound, "No recipient secrets found"))?;
+
+    let recipient_verkey = recipient.header.kid.clone();
+    let recipient_key = recipient_key.to_x25519_keypair();
+
+    let encrypted_key = decode_base64url(&recipient.encrypted_key, true)
+        .kind(ErrorKind::Malformed, "Unable decode encrypted key")?;
+
+    let (cek, sender_verkey) = if authenticated {
+        let sender = recipient
+            .header
+            .sender
+            .as_deref()
+            .ok_or_else(|| err_msg(ErrorKind::Malformed, "No sender in recipient header"))?;
+
+        let sender =
+            decode_base64url(sender, true).kind(ErrorKind::Malformed, "Unable decode sender")?;
+
+        let sender_verkey = crypto_box_seal_open(&recipient_key, &sender)
+            .kind(ErrorKind::Malformed, "Unable decrypt sender verkey")?;
+
+        let sender_verkey = String::from_utf8(sender_verkey.as_ref().to_vec())
+            .kind(ErrorKind::Malformed, "Sender verkey is not a valid string")?;
+
+        let nonce = recipient
+            .header
+            .iv
+            .as_deref()
+            .ok_or_else(|| err_msg(ErrorKind::Malformed, "No iv in recipient header"))?;
+
+        let nonce = decode_base64url(nonce, true).kind(ErrorKind::Malformed, "Unable decode iv")?;
+
+        let sender_key = _x25519_public(&sender_verkey)?;
+
+        let mut cek = SecretBytes::from_slice(&encrypted_key);
+
+        crypto_box_open(&recipient_key, &sender_key, &mut cek, &nonce)
+            .kind(ErrorKind::Malformed, "Unable decrypt cek")?;
+
+        (cek, Some(sender_verkey))
+    } else {
+        let cek = crypto_box_seal_open(&recipient_key, &encrypted_key)
+            .kind(ErrorKind::Malformed, "Unable decrypt cek")?;
+
+        (cek, None)
+    };
+
+    let plaintext = match protected.enc.as_str() {
+        ENC_XC20P => _decrypt_content::<Chacha20Key<XC20P>>(&cek, &envelope)?,
+        ENC_C20P => _decrypt_content::<Chacha20Key<C20P>>(&cek, &envelope)?,
+        enc => Err(err_msg(
+            ErrorKind::Unsupported,
+            format!("Unsupported enc `{}`", enc),
+        ))?,
+    };
+
+    let plaintext = String::from_utf8(plaintext)
+        .kind(ErrorKind::Malformed, "Plaintext is not a valid string")?;
+
+    let metadata = UnpackV1Metadata {
+        authenticated,
+        recipient_kid,
+        recipient_verkey,
+        sender_verkey,
+    };
+
+    Ok((plaintext, metadata))
+}
+
+/// Additional metadata about `unpack_v1` execution.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct UnpackV1Metadata {
+    /// Whether the sender has been authenticated (`Authcrypt`)
+    pub authenticated: bool,
+
+    /// Key ID of the recipient secret used for decryption
+    pub recipient_kid: String,
+
+    /// Base58 encoded Ed25519 public key (verkey) of the recipient
+    pub recipient_verkey: String,
+
+    /// Base58 encoded Ed25519 public key (verkey) of the sender if the sender has been authenticated
+    pub sender_verkey: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EnvelopeV1 {
+    protected: String,
+    iv: String,
+    ciphertext: String,
+    tag: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProtectedV1 {
+    enc: String,
+    typ: String,
+    alg: String,
+    recipients: Vec<RecipientV1>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecipientV1 {
+    encrypted_key: String,
+    header: RecipientHeaderV1,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecipientHeaderV1 {
+    /// Recipient verkey
+    kid: String,
+
+    /// Sender verkey encrypted with `crypto_box_seal` for `Authcrypt`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sender: Option<String>,
+
+    /// Nonce of `crypto_box` used to encrypt cek for `Authcrypt`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    iv: Option<String>,
+}
+
+async fn _find_recipient<'a, 'sr>(
+    recipients: &'a [RecipientV1],
+    secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+) -> Result<Option<(&'a RecipientV1, String, Ed25519KeyPair)>> {
+    let kids = secrets_resolver
+        .get_available_kids()
+        .await
+        .context("Unable get available kids")?;
+
+    for kid in kids {
+        // Only Ed25519 keys can be DIDComm v1 keys
+        let key = match secrets_resolver
+            .get_secret(&kid)
+            .await
+            .context("Unable resolve secret")?
+        {
+            Some(secret) if secret.key_alg() == KnownKeyAlg::Ed25519 => secret.as_ed25519()?,
+            _ => continue,
+        };
+
+        let verkey = _verkey(&key)?;
+
+        if let Some(recipient) = recipients.iter().find(|r| r.header.kid == verkey) {
+            return Ok(Some((recipient, kid, key)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Resolves Ed25519 secret. Returns None if there is no such secret,
+/// `Unsupported` error if the secret is not an Ed25519 key.
+async fn _ed25519_secret<'sr>(
+    kid: &str,
+    secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+) -> Result<Option<Ed25519KeyPair>> {
+    let secret = match secrets_resolver
+        .get_secret(kid)
+        .await
+        .context("Unable resolve secret")?
+    {
+        Some(secret) => secret,
+        None => return Ok(None),
+    };
+
+    if secret.key_alg() != KnownKeyAlg::Ed25519 {
+        Err(err_msg(
+            ErrorKind::Unsupported,
+            "DIDComm v1 keys must be Ed25519 keys",
+        ))?
+    }
+
+    secret.as_ed25519().map(Some)
+}
+
+fn _decrypt_content<CE>(cek: &SecretBytes, envelope: &EnvelopeV1) -> Result<Vec<u8>>
+where
+    CE: KeyAeadInPlace + KeySecretBytes,
+{
+    let cek =
+        CE::from_secret_bytes(cek.as_ref()).kind(ErrorKind::Malformed, "Unable create cek")?;
+
+    let iv = decode_base64url(&envelope.iv, true).kind(ErrorKind::Malformed, "Unable decode iv")?;
+
+    let mut buf = {
+        let ciphertext = decode_base64url(&envelope.ciphertext, true)
+            .kind(ErrorKind::Malformed, "Unable decode ciphertext")?;
+
+        let tag = decode_base64url(&envelope.tag, true)
+            .kind(ErrorKind::Malformed, "Unable decode tag")?;
+
+        let mut buf = SecretBytes::with_capacity(ciphertext.len() + tag.len());
+        buf.extend_from_slice(&ciphertext);
+        buf.extend_from_slice(&tag);
+        buf
+    };
+
+    cek.decrypt_in_place(&mut buf, &iv, envelope.protected.as_bytes())
+        .kind(ErrorKind::Malformed, "Unable decrypt content")?;
+
+    Ok(buf.as_ref().to_vec())
+}
+
+fn _verkey(key: &Ed25519KeyPair) -> Result<String> {
+    let public = key
+        .to_public_bytes()
+        .kind(ErrorKind::InvalidState, "Unable get public key")?;
+
+    Ok(bs58::encode(public.as_ref()).into_string())
+}
+
+fn _x25519_public(verkey: &str) -> Result<X25519KeyPair> {
+    let public = bs58::decode(verkey)
+        .into_vec()
+        .kind(ErrorKind::Malformed, "Unable decode verkey")?;
+
+    let key =
+        Ed25519KeyPair::from_public_bytes(&public).kind(ErrorKind::Malformed, "Invalid verkey")?;
+
+    Ok(key.to_x25519_keypair())
+}
+
+/// DIDComm v1 implementations use padded base64url values.
+fn _encode_base64url(value: &[u8]) -> String {
+    base64::encode_config(value, base64::URL_SAFE)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::ErrorKind,
+        secrets::{resolvers::ExampleSecretsResolver, Secret, SecretMaterial, SecretType},
+        test_vectors::BOB_SECRETS,
+    };
+
+    use super::{pack_encrypted_v1, unpack_v1, UnpackV1Metadata};
+
+    const SENDER_KID: &str = "did:example:v1sender#key-1";
+    const SENDER_VERKEY: &str = "7SrjMG3V4tN2yPUu7NS2RCgAh7w1iT8f7XxDjRZEkKVk";
+    const SENDER_SECRET: &str =
+        "3MhEbkfNKgnSztvrG7C7kNS7Cv71RbTyTicZ8RGwRdhYWWQ5qySvaQoFpa7gNYdQRqkjL2otWpSEpnKBm3GHqqVU";
+
+    const RECIPIENT_KID: &str = "did:example:v1recipient#key-1";
+    const RECIPIENT_VERKEY: &str = "5Ewp2RiHbmwLNCfKBGJKADGR8828Wi9ZjqRtJwpBphy9";
+    const RECIPIENT_SECRET: &str =
+        "4ULitutcUy1cMrsEdFLsRAixkkSX3ZKv8xNj7gYF67CvMGmjHgDt5TgaxdntfLKMrSZTruXMZxR7yfvpojKd6ZLT";
+
+    const MSG: &str = r#"{"@id":"1234567890","@type":"https://didcomm.org/basicmessage/1.0/message","content":"Hello v1"}"#;
+
+    // Produced with libsodium the same way as `pack_message` of indy-sdk does
+    const MSG_AUTHCRYPT: &str = r#"{"protected": "eyJlbmMiOiAieGNoYWNoYTIwcG9seTEzMDVfaWV0ZiIsICJ0eXAiOiAiSldNLzEuMCIsICJhbGciOiAiQXV0aGNyeXB0IiwgInJlY2lwaWVudHMiOiBbeyJlbmNyeXB0ZWRfa2V5IjogIkE2LVZZbkp6MHBMZDVqWmxsdmlORGdVazNGWmF1eG9aZGNwRlh3dG1VcEctWWFveGZnTFNCM1ZkN1RDLWhWZDAiLCAiaGVhZGVyIjogeyJraWQiOiAiNUV3cDJSaUhibXdMTkNmS0JHSktBREdSODgyOFdpOVpqcVJ0SndwQnBoeTkiLCAic2VuZGVyIjogIjhsVXpHMWR4N0Mwd2VoR2xxNFNuc3VsRGN3VzNzZnYyR1lYcTZQVUdZRHpJRHJTak1USFczMi14N0NQTmN2SGx3LWZPbVNwbmV5U05kTm1SQ3J3X2x5MUpWVVdhbW85Yi1lUS1MS19vMnVhdlRHT3A4Q3RCM2hGTlRaOD0iLCAiaXYiOiAiaDRmZE1ULVFnbGxuMTVCZTdYWHM5ZmE0Z3h3VGVjN1oifX1dfQ==", "iv": "izPTzd2CC_Ode5Q7IcYCnc-saXePlFBu", "ciphertext": "q04txpBiXZLYbLGa6ySur-lD8Z5Bmu00FnURiJuAK9JJaLuWIQxND9cxJLFx-j9U-7uhKqZW7F5rcn6kwGemy95KcbCeTtmHe8sSzngtEgLfjzm5QBURsOTvO66xr7Tk", "tag": "JK2Hhas2aJIkH3J47RHhXw=="}"#;
+    const MSG_ANONCRYPT: &str = r#"{"protected": "eyJlbmMiOiAieGNoYWNoYTIwcG9seTEzMDVfaWV0ZiIsICJ0eXAiOiAiSldNLzEuMCIsICJhbGciOiAiQW5vbmNyeXB0IiwgInJlY2lwaWVudHMiOiBbeyJlbmNyeXB0ZWRfa2V5IjogInl4M0JfR210M0hRNThZYVZGOW50cjhTSm5KM1dNVHRmYUdzb09nTmRreFBlbzZ2eGdQSTFDTlczMGROQ2Rta1N3WFJuaGRiQTZBeVhuSlY5UDQxRUdkdWpGaEdBMXRpU2VCNUdsVlZMNEgwPSIsICJoZWFkZXIiOiB7ImtpZCI6ICI1RXdwMlJpSGJtd0xOQ2ZLQkdKS0FER1I4ODI4V2k5WmpxUnRKd3BCcGh5OSJ9fV19", "iv": "ZEqTf9Ig-Sl38nnvVIVtCLzeo3LKxKmu", "ciphertext": "WzujUhssjGPaTuYYke1KYRu99BSPizRrx3XxBtt7XXFnw8qdMouKk9AFS5cwh2JmykGC_sc9cgVBqoAGpxlDee0XVz6N8MgN1a7EMey0hl9kHCZTBt393ALG-2UGSzZr", "tag": "LkRaG7pav7Kn57tzyrBlgw=="}"#;
+
+    #[tokio::test]
+    async fn unpack_v1_works_authcrypt() {
+        let (msg, metadata) = unpack_v1(MSG_AUTHCRYPT, &_recipient_secrets_resolver())
+            .await
+            .expect("unpack_v1 is ok.");
+
+        assert_eq!(msg, MSG);
+
+        assert_eq!(
+            metadata,
+            UnpackV1Metadata {
+                authenticated: true,
+                recipient_kid: RECIPIENT_KID.into(),
+                recipient_verkey: RECIPIENT_VERKEY.into(),
+                sender_verkey: Some(SENDER_VERKEY.into()),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn unpack_v1_works_anoncrypt() {
+        let (msg, metadata) = unpack_v1(MSG_ANONCRYPT, &_recipient_secrets_resolver())
+            .await
+            .expect("unpack_v1 is ok.");
+
+        assert_eq!(msg, MSG);
+
+        assert_eq!(
+            metadata,
+            UnpackV1Metadata {
+                authenticated: false,
+                recipient_kid: RECIPIENT_KID.into(),
+                recipient_verkey: RECIPIENT_VERKEY.into(),
+                sender_verkey: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn unpack_v1_works_no_recipient_secrets() {
+        let secrets_resolver =
+            ExampleSecretsResolver::new(vec![_secret(SENDER_KID, SENDER_SECRET)]);
+
+        let err = unpack_v1(MSG_AUTHCRYPT, &secrets_resolver)
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::SecretNotFound);
+    }
+
+    #[tokio::test]
+    async fn unpack_v1_works_malformed() {
+        let msg = MSG_ANONCRYPT.replace("\"tag\": \"", "\"tag\": \"AA");
+
+        let err = unpack_v1(&msg, &_recipient_secrets_resolver())
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+
+        let err = unpack_v1("{}", &_recipient_secrets_resolver())
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_v1_works() {
+        _pack_encrypted_v1_works(Some(SENDER_KID)).await;
+        _pack_encrypted_v1_works(None).await;
+
+        async fn _pack_encrypted_v1_works(from: Option<&str>) {
+            let sender_secrets_resolver =
+                ExampleSecretsResolver::new(vec![_secret(SENDER_KID, SENDER_SECRET)]);
+
+            let packed =
+                pack_encrypted_v1(MSG, &[RECIPIENT_VERKEY], from, &sender_secrets_resolver)
+                    .await
+                    .expect("pack_encrypted_v1 is ok.");
+
+            let (msg, metadata) = unpack_v1(&packed, &_recipient_secrets_resolver())
+                .await
+                .expect("unpack_v1 is ok.");
+
+            assert_eq!(msg, MSG);
+
+            assert_eq!(
+                metadata,
+                UnpackV1Metadata {
+                    authenticated: from.is_some(),
+                    recipient_kid: RECIPIENT_KID.into(),
+                    recipient_verkey: RECIPIENT_VERKEY.into(),
+                    sender_verkey: from.map(|_| SENDER_VERKEY.into()),
+                }
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_v1_works_invalid_recipient() {
+        let sender_secrets_resolver =
+            ExampleSecretsResolver::new(vec![_secret(SENDER_KID, SENDER_SECRET)]);
+
+        let err = pack_encrypted_v1(MSG, &["not-a-verkey"], None, &sender_secrets_resolver)
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+
+        let err = pack_encrypted_v1(MSG, &[], None, &sender_secrets_resolver)
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+    }
+
+    #[tokio::test]
+    async fn pack_encrypted_v1_works_sender_secret_not_found() {
+        let err = pack_encrypted_v1(
+            MSG,
+            &[RECIPIENT_VERKEY],
+            Some(SENDER_KID),
+            &_recipient_secrets_resolver(),
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::SecretNotFound);
+    }
+
+    fn _recipient_secrets_resolver() -> ExampleSecretsResolver {
+        // Non Ed25519 secrets are skipped while looking for recipient secret
+        let mut secrets = BOB_SECRETS.clone();
+        secrets.push(_secret(RECIPIENT_KID, RECIPIENT_SECRET));
+        ExampleSecretsResolver::new(secrets)
+    }
+
+    fn _secret(kid: &str, value: &str) -> Secret {
+        Secret {
+            id: kid.into(),
+            type_: SecretType::Ed25519VerificationKey2018,
+            secret_material: SecretMaterial::Base58 {
+                value: value.into(),
+            },
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Basic Message protocol (https://didcomm.org/basicmessage/2.0/) helpers
+//! for simple human messaging.
+
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{Message, MessageBuilder};
+
+pub const BASIC_MESSAGE_MSG_TYPE: &str = "https://didcomm.org/basicmessage/2.0/message";
+
+/// Builds Basic Message with random ID.
+/// Sender and recipients are expected to be set with the returned builder.
+///
+/// # Params
+/// - `content` human readable message content.
+/// - `locale` optional locale of the content (for ex. `en`) set as `lang` header.
+pub fn build(content: &str, locale: Option<&str>) -> MessageBuilder {
+    let builder = Message::build(
+        Uuid::new_v4().to_string(),
+        BASIC_MESSAGE_MSG_TYPE.to_owned(),
+        json!({ "content": content }),
+    );
+
+    match locale {
+        Some(locale) => builder.lang(locale.to_owned()),
+        None => builder,
+    }
+}
+
+/// Returns content of the Basic Message or None if the message isn't a Basic Message
+/// or has no textual content.
+pub fn content(msg: &Message) -> Option<&str> {
+    if msg.type_ != BASIC_MESSAGE_MSG_TYPE {
+        return None;
+    }
+
+    msg.body.get("content").and_then(Value::as_str)
+}
+
+/// Returns locale of the Basic Message content (`lang` header) if specified.
+pub fn locale(msg: &Message) -> Option<&str> {
+    if msg.type_ != BASIC_MESSAGE_MSG_TYPE {
+        return None;
+    }
+
+    msg.lang()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{test_vectors::MESSAGE_SIMPLE, Message};
+
+    use super::{build, content, locale, BASIC_MESSAGE_MSG_TYPE};
+
+    #[test]
+    fn basic_message_works() {
+        _basic_message_works(Some("en"));
+        _basic_message_works(None);
+
+        fn _basic_message_works(lang: Option<&str>) {
+            let msg = build("Your hovercraft is full of eels.", lang).finalize();
+
+            assert_eq!(msg.type_, BASIC_MESSAGE_MSG_TYPE);
+            assert_eq!(
+                msg.body,
+                json!({ "content": "Your hovercraft is full of eels." })
+            );
+
+            let msg = Message::from_value(msg.to_value().expect("to_value is ok."))
+                .expect("from_value is ok.");
+
+            assert_eq!(content(&msg), Some("Your hovercraft is full of eels."));
+            assert_eq!(locale(&msg), lang);
+            assert_eq!(msg.extra_headers.get("lang").is_some(), lang.is_some());
+        }
+    }
+
+    #[test]
+    fn content_works_not_basic_message() {
+        assert_eq!(content(&MESSAGE_SIMPLE), None);
+        assert_eq!(locale(&MESSAGE_SIMPLE), None);
+    }
+}
@@ -0,0 +1,195 @@
+//! Discover Features protocol (https://identity.foundation/didcomm-messaging/spec/#discover-features-protocol-20)
+//! helpers to negotiate capabilities of agents.
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{Message, MessageBuilder};
+
+pub const QUERIES_MSG_TYPE: &str = "https://didcomm.org/discover-features/2.0/queries";
+pub const DISCLOSE_MSG_TYPE: &str = "https://didcomm.org/discover-features/2.0/disclose";
+
+/// Body of the `queries` message.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct QueriesBody {
+    pub queries: Vec<Query>,
+}
+
+/// Single query for features of the given type.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Query {
+    /// Type of the feature (for ex. `protocol`, `goal-code` or `header`).
+    #[serde(rename = "feature-type")]
+    pub feature_type: String,
+
+    /// Feature identifier to match. May end with `*` to match any suffix.
+    #[serde(rename = "match")]
+    pub match_: String,
+}
+
+/// Body of the `disclose` message.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct DiscloseBody {
+    pub disclosures: Vec<Disclosure>,
+}
+
+/// Single feature supported by the agent.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Disclosure {
+    /// Type of the feature (for ex. `protocol`, `goal-code` or `header`).
+    #[serde(rename = "feature-type")]
+    pub feature_type: String,
+
+    /// Feature identifier (for ex. protocol type URI).
+    pub id: String,
+
+    /// Roles the agent can play in the protocol.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub roles: Option<Vec<String>>,
+}
+
+/// Builds `queries` message with random ID asking for features of the given type.
+/// Sender and recipients are expected to be set with the returned builder.
+///
+/// # Params
+/// - `feature_type` type of queried features (for ex. `protocol`).
+/// - `match_` feature identifier to match, may end with `*` (for ex. `https://didcomm.org/tictactoe/1.*`).
+pub fn build_query(feature_type: &str, match_: &str) -> MessageBuilder {
+    let body = QueriesBody {
+        queries: vec![Query {
+            feature_type: feature_type.to_owned(),
+            match_: match_.to_owned(),
+        }],
+    };
+
+    Message::build(
+        Uuid::new_v4().to_string(),
+        QUERIES_MSG_TYPE.to_owned(),
+        json!(body),
+    )
+}
+
+/// Builds `disclose` message with random ID listing supported features.
+/// To answer the query, thread it to the query with `MessageBuilder::thid`.
+///
+/// # Params
+/// - `disclosures` list of `(feature_type, id, roles)` of supported features.
+///   Empty roles are omitted.
+pub fn build_disclose(disclosures: &[(&str, &str, &[&str])]) -> MessageBuilder {
+    let body = DiscloseBody {
+        disclosures: disclosures
+            .iter()
+            .map(|(feature_type, id, roles)| Disclosure {
+                feature_type: (*feature_type).to_owned(),
+                id: (*id).to_owned(),
+                roles: if roles.is_empty() {
+                    None
+                } else {
+                    Some(roles.iter().map(|&role| role.to_owned()).collect())
+                },
+            })
+            .collect(),
+    };
+
+    Message::build(
+        Uuid::new_v4().to_string(),
+        DISCLOSE_MSG_TYPE.to_owned(),
+        json!(body),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::protocols::test_support::round_trip;
+
+    use super::{
+        build_disclose, build_query, DiscloseBody, Disclosure, QueriesBody, Query,
+        DISCLOSE_MSG_TYPE, QUERIES_MSG_TYPE,
+    };
+
+    #[tokio::test]
+    async fn build_query_works() {
+        let query = build_query("protocol", "https://didcomm.org/tictactoe/1.*").finalize();
+
+        assert_eq!(query.type_, QUERIES_MSG_TYPE);
+
+        assert_eq!(
+            query.body,
+            json!({
+                "queries": [
+                    { "feature-type": "protocol", "match": "https://didcomm.org/tictactoe/1.*" }
+                ]
+            })
+        );
+
+        let query = round_trip(&query).await;
+
+        assert_eq!(
+            query.body_as::<QueriesBody>().expect("body_as is ok."),
+            QueriesBody {
+                queries: vec![Query {
+                    feature_type: "protocol".into(),
+                    match_: "https://didcomm.org/tictactoe/1.*".into(),
+                }]
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn build_disclose_works() {
+        let disclose = build_disclose(&[
+            (
+                "protocol",
+                "https://didcomm.org/tictactoe/1.0",
+                &["player"][..],
+            ),
+            ("protocol", "https://didcomm.org/trust-ping/2.0", &[][..]),
+        ])
+        .thid("query-1".into())
+        .finalize();
+
+        assert_eq!(disclose.type_, DISCLOSE_MSG_TYPE);
+        assert_eq!(disclose.thid.as_deref(), Some("query-1"));
+
+        assert_eq!(
+            disclose.body,
+            json!({
+                "disclosures": [
+                    {
+                        "feature-type": "protocol",
+                        "id": "https://didcomm.org/tictactoe/1.0",
+                        "roles": ["player"]
+                    },
+                    {
+                        "feature-type": "protocol",
+                        "id": "https://didcomm.org/trust-ping/2.0"
+                    }
+                ]
+            })
+        );
+
+        let disclose = round_trip(&disclose).await;
+
+        assert_eq!(
+            disclose.body_as::<DiscloseBody>().expect("body_as is ok."),
+            DiscloseBody {
+                disclosures: vec![
+                    Disclosure {
+                        feature_type: "protocol".into(),
+                        id: "https://didcomm.org/tictactoe/1.0".into(),
+                        roles: Some(vec!["player".into()]),
+                    },
+                    Disclosure {
+                        feature_type: "protocol".into(),
+                        id: "https://didcomm.org/trust-ping/2.0".into(),
+                        roles: None,
+                    }
+                ]
+            }
+        );
+    }
+}
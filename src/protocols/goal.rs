@@ -0,0 +1,133 @@
+//! Goal code (https://identity.foundation/didcomm-messaging/spec/#goal-codes) helpers
+//! for protocols stating the intent of the message in body (for ex. out of band invitations).
+
+use serde_json::{json, Value};
+
+use crate::{
+    error::{err_msg, ErrorKind, Result},
+    Message, MessageBuilder,
+};
+
+/// Name of the body field carrying goal code.
+pub const GOAL_CODE_FIELD: &str = "goal_code";
+
+/// Name of the body field carrying human readable goal.
+pub const GOAL_FIELD: &str = "goal";
+
+impl MessageBuilder {
+    /// Sets `goal_code` body field (for ex. `issue-vc`).
+    ///
+    /// # Errors
+    /// - `IllegalArgument` message body is not a JSON object.
+    pub fn goal_code(self, goal_code: &str) -> Result<Self> {
+        self._body_field(GOAL_CODE_FIELD, json!(goal_code))
+    }
+
+    /// Sets `goal` body field with human readable statement of the goal.
+    ///
+    /// # Errors
+    /// - `IllegalArgument` message body is not a JSON object.
+    pub fn goal(self, goal: &str) -> Result<Self> {
+        self._body_field(GOAL_FIELD, json!(goal))
+    }
+
+    fn _body_field(mut self, name: &str, value: Value) -> Result<Self> {
+        self.body
+            .as_object_mut()
+            .ok_or_else(|| err_msg(ErrorKind::IllegalArgument, "Message body is not an object"))?
+            .insert(name.to_owned(), value);
+
+        Ok(self)
+    }
+}
+
+impl Message {
+    /// Returns `goal_code` body field if specified.
+    pub fn goal_code(&self) -> Option<&str> {
+        self.body.get(GOAL_CODE_FIELD).and_then(Value::as_str)
+    }
+
+    /// Returns `goal` body field if specified.
+    pub fn goal(&self) -> Option<&str> {
+        self.body.get(GOAL_FIELD).and_then(Value::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        error::ErrorKind,
+        protocols::{
+            discover_features::build_query, oob::build_invitation, test_support::round_trip,
+        },
+        test_vectors::MESSAGE_SIMPLE,
+        Message,
+    };
+
+    #[test]
+    fn goal_works_oob_invitation() {
+        let invitation = build_invitation()
+            .from("did:example:alice".into())
+            .goal_code("issue-vc")
+            .expect("goal_code is ok.")
+            .goal("To issue a Faber College Graduate credential")
+            .expect("goal is ok.")
+            .finalize();
+
+        assert_eq!(
+            invitation.body,
+            json!({
+                "goal_code": "issue-vc",
+                "goal": "To issue a Faber College Graduate credential",
+                "accept": ["didcomm/v2"]
+            })
+        );
+
+        let url = invitation
+            .to_oob_url("https://example.com/path")
+            .expect("to_oob_url is ok.");
+
+        let invitation = Message::from_oob_url(&url).expect("from_oob_url is ok.");
+
+        assert_eq!(invitation.goal_code(), Some("issue-vc"));
+
+        assert_eq!(
+            invitation.goal(),
+            Some("To issue a Faber College Graduate credential")
+        );
+    }
+
+    #[tokio::test]
+    async fn goal_works_query() {
+        let query = build_query("protocol", "https://didcomm.org/issue-credential/3.*")
+            .goal_code("issue-vc")
+            .expect("goal_code is ok.")
+            .finalize();
+
+        let query = round_trip(&query).await;
+
+        assert_eq!(query.goal_code(), Some("issue-vc"));
+        assert_eq!(query.goal(), None);
+    }
+
+    #[test]
+    fn goal_works_no_goal() {
+        assert_eq!(MESSAGE_SIMPLE.goal_code(), None);
+        assert_eq!(MESSAGE_SIMPLE.goal(), None);
+    }
+
+    #[test]
+    fn goal_code_works_body_not_object() {
+        let res = Message::build(
+            "1234567890".to_owned(),
+            "https://didcomm.org/tictactoe/1.0/move".to_owned(),
+            json!("move"),
+        )
+        .goal_code("issue-vc");
+
+        let err = res.map(|_| ()).expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+    }
+}
@@ -0,0 +1,195 @@
+//! Localization (https://github.com/hyperledger/aries-rfcs/tree/main/features/0043-l10n)
+//! metadata helpers shared by protocols carrying human readable strings in message body.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    error::{err_msg, ErrorKind, Result, ResultExt},
+    Message,
+};
+
+/// Name of the body field carrying localization metadata.
+pub const L10N_FIELD: &str = "~l10n";
+
+/// Localization metadata of the message body.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+pub struct L10n {
+    /// Locale of the localizable body fields (for ex. `en`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+
+    /// Names of the body fields holding localizable strings.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub localizable: Option<Vec<String>>,
+
+    /// URIs of message catalogs with translations of the localizable fields.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub catalogs: Option<Vec<String>>,
+}
+
+impl L10n {
+    /// Creates localization metadata for the given locale.
+    pub fn new(locale: &str) -> Self {
+        L10n {
+            locale: Some(locale.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    /// Marks the given body field as localizable.
+    pub fn localizable(mut self, field: &str) -> Self {
+        self.localizable
+            .get_or_insert_with(Vec::new)
+            .push(field.to_owned());
+
+        self
+    }
+
+    /// Adds URI of a message catalog.
+    pub fn catalog(mut self, uri: &str) -> Self {
+        self.catalogs
+            .get_or_insert_with(Vec::new)
+            .push(uri.to_owned());
+
+        self
+    }
+}
+
+/// Sets localization metadata of the message body replacing the existing one.
+///
+/// # Errors
+/// - `IllegalArgument` message body is not a JSON object.
+/// - `InvalidState` metadata can't be serialized.
+pub fn set_l10n(msg: &mut Message, l10n: &L10n) -> Result<()> {
+    let l10n = serde_json::to_value(l10n)
+        .kind(ErrorKind::InvalidState, "Unable serialize l10n metadata")?;
+
+    msg.body
+        .as_object_mut()
+        .ok_or_else(|| err_msg(ErrorKind::IllegalArgument, "Message body is not an object"))?
+        .insert(L10N_FIELD.to_owned(), l10n);
+
+    Ok(())
+}
+
+/// Returns localization metadata of the message body if specified.
+///
+/// # Errors
+/// - `Malformed` body contains localization metadata of unexpected structure.
+pub fn l10n(msg: &Message) -> Result<Option<L10n>> {
+    msg.body
+        .get(L10N_FIELD)
+        .map(|l10n| {
+            serde_json::from_value(l10n.clone())
+                .kind(ErrorKind::Malformed, "Unable deserialize l10n metadata")
+        })
+        .transpose()
+}
+
+/// Returns value of the localizable body field and its locale.
+/// Locale is `None` if the field isn't marked as localizable or no locale is specified.
+///
+/// # Errors
+/// - `Malformed` body contains localization metadata of unexpected structure.
+pub fn localized<'a>(msg: &'a Message, field: &str) -> Result<Option<(&'a str, Option<String>)>> {
+    let value = match msg.body.get(field).and_then(Value::as_str) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+
+    let locale = l10n(msg)?.and_then(|l10n| {
+        let localizable = l10n
+            .localizable
+            .as_ref()
+            .map(|fields| fields.iter().any(|f| f == field))
+            .unwrap_or(false);
+
+        if localizable {
+            l10n.locale
+        } else {
+            None
+        }
+    });
+
+    Ok(Some((value, locale)))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        error::ErrorKind, protocols::test_support::round_trip, test_vectors::MESSAGE_SIMPLE,
+        Message,
+    };
+
+    use super::{l10n, localized, set_l10n, L10n};
+
+    #[tokio::test]
+    async fn l10n_works() {
+        let mut msg = Message::build(
+            "1234567890".to_owned(),
+            "https://didcomm.org/tictactoe/1.0/move".to_owned(),
+            json!({ "comment": "C'est ton tour", "moves": ["X:A1"] }),
+        )
+        .finalize();
+
+        let meta = L10n::new("fr")
+            .localizable("comment")
+            .catalog("https://example.com/catalogs/tictactoe");
+
+        set_l10n(&mut msg, &meta).expect("set_l10n is ok.");
+
+        assert_eq!(
+            msg.body["~l10n"],
+            json!({
+                "locale": "fr",
+                "localizable": ["comment"],
+                "catalogs": ["https://example.com/catalogs/tictactoe"]
+            })
+        );
+
+        let msg = round_trip(&msg).await;
+
+        assert_eq!(l10n(&msg).expect("l10n is ok."), Some(meta));
+
+        assert_eq!(
+            localized(&msg, "comment").expect("localized is ok."),
+            Some(("C'est ton tour", Some("fr".to_owned())))
+        );
+
+        assert_eq!(localized(&msg, "moves").expect("localized is ok."), None);
+    }
+
+    #[test]
+    fn l10n_works_no_l10n() {
+        assert_eq!(l10n(&MESSAGE_SIMPLE).expect("l10n is ok."), None);
+    }
+
+    #[test]
+    fn l10n_works_malformed() {
+        let msg = Message::build(
+            "1234567890".to_owned(),
+            "https://didcomm.org/tictactoe/1.0/move".to_owned(),
+            json!({ "~l10n": "fr" }),
+        )
+        .finalize();
+
+        let err = l10n(&msg).expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+    }
+
+    #[test]
+    fn set_l10n_works_body_not_object() {
+        let mut msg = Message::build(
+            "1234567890".to_owned(),
+            "https://didcomm.org/tictactoe/1.0/move".to_owned(),
+            json!("move"),
+        )
+        .finalize();
+
+        let err = set_l10n(&mut msg, &L10n::new("en")).expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+    }
+}
@@ -0,0 +1,294 @@
+//! Message Pickup protocol (https://didcomm.org/messagepickup/2.0/) helpers
+//! to retrieve messages queued by a mediator.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{Attachment, AttachmentData, Message, MessageBuilder};
+
+pub const STATUS_REQUEST_MSG_TYPE: &str = "https://didcomm.org/messagepickup/2.0/status-request";
+pub const STATUS_MSG_TYPE: &str = "https://didcomm.org/messagepickup/2.0/status";
+pub const DELIVERY_REQUEST_MSG_TYPE: &str =
+    "https://didcomm.org/messagepickup/2.0/delivery-request";
+pub const DELIVERY_MSG_TYPE: &str = "https://didcomm.org/messagepickup/2.0/delivery";
+
+/// Body of the `status-request` message.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+pub struct StatusRequestBody {
+    /// Key the queued messages are addressed to. All queued messages if not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub recipient_key: Option<String>,
+}
+
+/// Body of the `status` message.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+pub struct StatusBody {
+    /// Key the queued messages are addressed to if the status is limited to it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub recipient_key: Option<String>,
+
+    /// Amount of messages in the queue.
+    pub message_count: u64,
+
+    /// Time in seconds the oldest message is waiting in the queue.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub longest_waited_seconds: Option<u64>,
+
+    /// Time the newest message was received in UTC Epoch Seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub newest_received_time: Option<u64>,
+
+    /// Time the oldest message was received in UTC Epoch Seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub oldest_received_time: Option<u64>,
+
+    /// Total size of the queued messages in bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub total_bytes: Option<u64>,
+
+    /// Whether messages are delivered as soon as they are received.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub live_delivery: Option<bool>,
+}
+
+/// Body of the `delivery-request` message.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct DeliveryRequestBody {
+    /// Maximum amount of messages to deliver.
+    pub limit: u64,
+
+    /// Key the requested messages are addressed to. Any queued messages if not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub recipient_key: Option<String>,
+}
+
+/// Body of the `delivery` message. Delivered messages are attachments of the message.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+pub struct DeliveryBody {
+    /// Key the delivered messages are addressed to if the delivery is limited to it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub recipient_key: Option<String>,
+}
+
+/// Builds `status-request` message with random ID.
+/// Sender and recipients are expected to be set with the returned builder.
+///
+/// # Params
+/// - `recipient_key` optional key to limit the status to messages addressed to it.
+pub fn build_status_request(recipient_key: Option<&str>) -> MessageBuilder {
+    let body = StatusRequestBody {
+        recipient_key: recipient_key.map(ToOwned::to_owned),
+    };
+
+    Message::build(
+        Uuid::new_v4().to_string(),
+        STATUS_REQUEST_MSG_TYPE.to_owned(),
+        json!(body),
+    )
+}
+
+/// Builds `status` message with random ID.
+/// To answer the request, thread it to the request with `MessageBuilder::thid`.
+pub fn build_status(status: &StatusBody) -> MessageBuilder {
+    Message::build(
+        Uuid::new_v4().to_string(),
+        STATUS_MSG_TYPE.to_owned(),
+        json!(status),
+    )
+}
+
+/// Builds `delivery-request` message with random ID.
+/// Sender and recipients are expected to be set with the returned builder.
+///
+/// # Params
+/// - `limit` maximum amount of messages to deliver.
+/// - `recipient_key` optional key to limit the delivery to messages addressed to it.
+pub fn build_delivery_request(limit: u64, recipient_key: Option<&str>) -> MessageBuilder {
+    let body = DeliveryRequestBody {
+        limit,
+        recipient_key: recipient_key.map(ToOwned::to_owned),
+    };
+
+    Message::build(
+        Uuid::new_v4().to_string(),
+        DELIVERY_REQUEST_MSG_TYPE.to_owned(),
+        json!(body),
+    )
+}
+
+/// Builds `delivery` message with random ID carrying the queued messages as json attachments.
+/// To answer the request, thread it to the request with `MessageBuilder::thid`.
+///
+/// # Params
+/// - `recipient_key` optional key the delivered messages are addressed to.
+/// - `messages` list of `(id, message)` of the delivered packed messages.
+///   Message IDs are used as attachment IDs, so the recipient can acknowledge them.
+pub fn build_delivery(recipient_key: Option<&str>, messages: &[(&str, Value)]) -> MessageBuilder {
+    let body = DeliveryBody {
+        recipient_key: recipient_key.map(ToOwned::to_owned),
+    };
+
+    let attachments = messages
+        .iter()
+        .map(|(id, msg)| {
+            Attachment::json(msg.clone())
+                .id((*id).to_owned())
+                .finalize()
+        })
+        .collect();
+
+    Message::build(
+        Uuid::new_v4().to_string(),
+        DELIVERY_MSG_TYPE.to_owned(),
+        json!(body),
+    )
+    .attachments(attachments)
+}
+
+/// Returns `(id, message)` of messages delivered with the `delivery` message
+/// or None if the message isn't a `delivery` message.
+/// Attachments without ID or with non json data are skipped.
+pub fn delivered_messages(msg: &Message) -> Option<Vec<(&str, &Value)>> {
+    if msg.type_ != DELIVERY_MSG_TYPE {
+        return None;
+    }
+
+    let messages = msg
+        .attachments
+        .iter()
+        .flatten()
+        .filter_map(|attachment| match (&attachment.id, &attachment.data) {
+            (Some(id), AttachmentData::Json { value }) => Some((id.as_str(), &value.json)),
+            _ => None,
+        })
+        .collect();
+
+    Some(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{protocols::test_support::round_trip, test_vectors::MESSAGE_SIMPLE};
+
+    use super::{
+        build_delivery, build_delivery_request, build_status, build_status_request,
+        delivered_messages, DeliveryBody, DeliveryRequestBody, StatusBody, StatusRequestBody,
+        DELIVERY_MSG_TYPE, DELIVERY_REQUEST_MSG_TYPE, STATUS_MSG_TYPE, STATUS_REQUEST_MSG_TYPE,
+    };
+
+    #[tokio::test]
+    async fn build_status_request_works() {
+        let request = build_status_request(Some("did:example:bob#key-x25519-1")).finalize();
+
+        assert_eq!(request.type_, STATUS_REQUEST_MSG_TYPE);
+        assert_eq!(
+            request.body,
+            json!({ "recipient_key": "did:example:bob#key-x25519-1" })
+        );
+
+        let request = round_trip(&request).await;
+
+        assert_eq!(
+            request
+                .body_as::<StatusRequestBody>()
+                .expect("body_as is ok."),
+            StatusRequestBody {
+                recipient_key: Some("did:example:bob#key-x25519-1".into()),
+            }
+        );
+
+        let request = build_status_request(None).finalize();
+        assert_eq!(request.body, json!({}));
+    }
+
+    #[tokio::test]
+    async fn build_status_works() {
+        let status = build_status(&StatusBody {
+            message_count: 7,
+            live_delivery: Some(false),
+            ..StatusBody::default()
+        })
+        .thid("status-request-1".into())
+        .finalize();
+
+        assert_eq!(status.type_, STATUS_MSG_TYPE);
+        assert_eq!(status.thid.as_deref(), Some("status-request-1"));
+        assert_eq!(
+            status.body,
+            json!({ "message_count": 7, "live_delivery": false })
+        );
+
+        let status = round_trip(&status).await;
+
+        assert_eq!(
+            status.body_as::<StatusBody>().expect("body_as is ok."),
+            StatusBody {
+                message_count: 7,
+                live_delivery: Some(false),
+                ..StatusBody::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn build_delivery_works() {
+        let request = build_delivery_request(10, None).finalize();
+
+        assert_eq!(request.type_, DELIVERY_REQUEST_MSG_TYPE);
+        assert_eq!(
+            request
+                .body_as::<DeliveryRequestBody>()
+                .expect("body_as is ok."),
+            DeliveryRequestBody {
+                limit: 10,
+                recipient_key: None,
+            }
+        );
+
+        let queued = MESSAGE_SIMPLE.to_value().expect("to_value is ok.");
+
+        let delivery = build_delivery(
+            Some("did:example:bob#key-x25519-1"),
+            &[
+                ("queued-1", queued.clone()),
+                ("queued-2", json!({ "n": 2 })),
+            ],
+        )
+        .thid(request.id.clone())
+        .finalize();
+
+        assert_eq!(delivery.type_, DELIVERY_MSG_TYPE);
+        assert_eq!(delivery.attachments.as_ref().map(Vec::len), Some(2));
+
+        let delivery = round_trip(&delivery).await;
+
+        assert_eq!(
+            delivery.body_as::<DeliveryBody>().expect("body_as is ok."),
+            DeliveryBody {
+                recipient_key: Some("did:example:bob#key-x25519-1".into()),
+            }
+        );
+
+        assert_eq!(
+            delivered_messages(&delivery),
+            Some(vec![
+                ("queued-1", &queued),
+                ("queued-2", &json!({ "n": 2 }))
+            ])
+        );
+
+        assert_eq!(delivered_messages(&request), None);
+    }
+}
@@ -1 +1,12 @@
+pub mod basic_message;
+pub mod discover_features;
+pub mod goal;
+pub mod l10n;
+pub mod messagepickup;
+pub mod oob;
 pub mod routing;
+pub mod threading;
+pub mod trust_ping;
+
+#[cfg(test)]
+pub(crate) mod test_support;
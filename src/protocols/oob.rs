@@ -0,0 +1,188 @@
+//! Out Of Band protocol (https://identity.foundation/didcomm-messaging/spec/#out-of-band-messages)
+//! invitations encoding into and decoding from URLs.
+
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    error::{err_msg, ErrorKind, Result, ResultExt},
+    jwe::decode_base64url,
+    Message, MessageBuilder,
+};
+
+pub const OOB_INVITATION_MSG_TYPE: &str = "https://didcomm.org/out-of-band/2.0/invitation";
+
+/// Name of the URL query parameter carrying encoded invitation.
+const OOB_QUERY_PARAM: &str = "_oob";
+
+/// Builds out of band invitation with random ID accepting `didcomm/v2` profile.
+/// Sender (required for invitations) is expected to be set with the returned builder,
+/// goal can be stated with `MessageBuilder::goal_code` and `MessageBuilder::goal`.
+pub fn build_invitation() -> MessageBuilder {
+    Message::build(
+        Uuid::new_v4().to_string(),
+        OOB_INVITATION_MSG_TYPE.to_owned(),
+        json!({ "accept": ["didcomm/v2"] }),
+    )
+}
+
+impl Message {
+    /// Encodes out of band invitation as base64url `_oob` query parameter of the given URL.
+    ///
+    /// # Params
+    /// - `base_url` URL the invitation is appended to (for ex. `https://example.com/path`).
+    ///
+    /// # Returns
+    /// URL with the encoded plaintext invitation.
+    ///
+    /// # Errors
+    /// - `IllegalArgument` the message is not an out of band invitation.
+    /// - `InvalidState` the message can't be serialized.
+    pub fn to_oob_url(&self, base_url: &str) -> Result<String> {
+        if self.type_ != OOB_INVITATION_MSG_TYPE {
+            Err(err_msg(
+                ErrorKind::IllegalArgument,
+                "Message is not an out of band invitation",
+            ))?
+        }
+
+        let msg = serde_json::to_string(self)
+            .kind(ErrorKind::InvalidState, "Unable serialize invitation")?;
+
+        let separator = if base_url.contains('?') { '&' } else { '?' };
+
+        Ok(format!(
+            "{}{}{}={}",
+            base_url,
+            separator,
+            OOB_QUERY_PARAM,
+            base64::encode_config(msg, base64::URL_SAFE_NO_PAD)
+        ))
+    }
+
+    /// Decodes out of band invitation from base64url `_oob` query parameter of the given URL.
+    ///
+    /// # Params
+    /// - `url` URL with the encoded invitation.
+    ///
+    /// # Returns
+    /// Plaintext invitation message.
+    ///
+    /// # Errors
+    /// - `Malformed` URL has no `_oob` parameter, it can't be decoded
+    ///   or the decoded message is not a valid out of band invitation.
+    pub fn from_oob_url(url: &str) -> Result<Message> {
+        let query = url
+            .split_once('?')
+            .map(|(_, query)| query)
+            .ok_or_else(|| err_msg(ErrorKind::Malformed, "URL has no query"))?;
+
+        let query = query.split('#').next().unwrap_or_default();
+
+        let oob = query
+            .split('&')
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| *name == OOB_QUERY_PARAM)
+            .map(|(_, value)| value)
+            .ok_or_else(|| {
+                err_msg(
+                    ErrorKind::Malformed,
+                    format!("URL has no `{}` parameter", OOB_QUERY_PARAM),
+                )
+            })?;
+
+        // Padding can be percent-encoded by some URL builders
+        let oob = oob.trim_end_matches("%3D").trim_end_matches("%3d");
+
+        let msg =
+            decode_base64url(oob, true).kind(ErrorKind::Malformed, "Unable decode invitation")?;
+
+        let msg = String::from_utf8(msg).kind(ErrorKind::Malformed, "Invitation is not UTF-8")?;
+
+        let msg = Message::from_str(&msg)?.validate()?;
+
+        if msg.type_ != OOB_INVITATION_MSG_TYPE {
+            Err(err_msg(
+                ErrorKind::Malformed,
+                "Message is not an out of band invitation",
+            ))?
+        }
+
+        Ok(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{error::ErrorKind, test_vectors::MESSAGE_SIMPLE, Message};
+
+    use super::OOB_INVITATION_MSG_TYPE;
+
+    #[test]
+    fn oob_url_works() {
+        _oob_url_works("https://example.com/path", "https://example.com/path?_oob=");
+
+        _oob_url_works(
+            "https://example.com/path?lang=en",
+            "https://example.com/path?lang=en&_oob=",
+        );
+
+        fn _oob_url_works(base_url: &str, exp_prefix: &str) {
+            let invitation = _invitation();
+
+            let url = invitation.to_oob_url(base_url).expect("to_oob_url is ok.");
+
+            assert!(url.starts_with(exp_prefix));
+
+            let decoded = Message::from_oob_url(&url).expect("from_oob_url is ok.");
+            assert_eq!(decoded, invitation);
+        }
+    }
+
+    #[test]
+    fn to_oob_url_works_not_invitation() {
+        let err = MESSAGE_SIMPLE
+            .to_oob_url("https://example.com")
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::IllegalArgument);
+    }
+
+    #[test]
+    fn from_oob_url_works_invalid() {
+        let not_invitation = format!(
+            "https://example.com?_oob={}",
+            base64::encode_config(
+                serde_json::to_string(&*MESSAGE_SIMPLE).expect("to_string is ok."),
+                base64::URL_SAFE_NO_PAD
+            )
+        );
+
+        for url in [
+            "https://example.com",
+            "https://example.com?lang=en",
+            "https://example.com?_oob=*",
+            "https://example.com?_oob=eyJpZCI6IjEifQ",
+            &not_invitation,
+        ] {
+            let err = Message::from_oob_url(url).expect_err("res is ok");
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+        }
+    }
+
+    fn _invitation() -> Message {
+        Message::build(
+            "69212a3a-d068-4f9d-a2dd-4741bca89af3".into(),
+            OOB_INVITATION_MSG_TYPE.into(),
+            json!({
+                "goal_code": "issue-vc",
+                "goal": "To issue a Faber College Graduate credential",
+                "accept": ["didcomm/v2"]
+            }),
+        )
+        .from("did:example:alice".into())
+        .finalize()
+    }
+}
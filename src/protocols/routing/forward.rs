@@ -1,10 +1,127 @@
+use std::collections::HashMap;
+
 use serde_json::Value;
 
-use crate::Message;
+use crate::{
+    did::{DIDCommMessagingService, DIDResolver},
+    error::Result,
+    utils::did::did_or_url,
+    Message,
+};
+
+use super::{find_did_comm_service, unwrap_did_comm_service, SUPPORTED_PROFILES};
 
 pub struct ParsedForward {
     #[allow(dead_code)]
     pub msg: Message,
     pub next: String,
     pub forwarded_msg: Value,
+
+    /// Time the forwarded message expires at (for ex. for scheduled delivery by mediator).
+    pub expires_time: Option<u64>,
+
+    /// Custom headers of the `Forward` message (for ex. `sender_order` or delay instructions).
+    pub headers: HashMap<String, Value>,
+}
+
+impl ParsedForward {
+    /// Resolves DIDComm messaging service of the `next` hop so the forwarded message can be
+    /// delivered further. If `next` is a key ID, DID part of it is resolved.
+    ///
+    /// # Returns
+    /// Service endpoint and routing keys of the service accepting DIDComm v2 messages
+    /// or None if DID Doc of `next` has no DIDComm messaging services.
+    ///
+    /// # Errors
+    /// - `DIDNotResolved` DID Doc of `next` can't be resolved.
+    /// - `NoCompatibleCrypto` No DIDComm messaging service accepting DIDComm v2 messages found.
+    pub async fn resolve_next_service<'dr>(
+        &self,
+        did_resolver: &'dr (dyn DIDResolver + 'dr),
+    ) -> Result<Option<DIDCommMessagingService>> {
+        let (next_did, _) = did_or_url(&self.next);
+
+        find_did_comm_service(next_did, None, SUPPORTED_PROFILES, did_resolver)
+            .await?
+            .map(|service| unwrap_did_comm_service(&service).map(Clone::clone))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use crate::{
+        did::resolvers::ExampleDIDResolver,
+        error::ErrorKind,
+        test_vectors::{
+            ALICE_DID, ALICE_DID_DOC, BOB_DID, BOB_DID_COMM_MESSAGING_SERVICE, BOB_DID_DOC,
+            MESSAGE_SIMPLE,
+        },
+    };
+
+    use super::ParsedForward;
+
+    #[tokio::test]
+    async fn resolve_next_service_works() {
+        _resolve_next_service_works(BOB_DID).await;
+        _resolve_next_service_works("did:example:bob#key-x25519-1").await;
+
+        async fn _resolve_next_service_works(next: &str) {
+            let did_resolver =
+                ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+
+            let service = _parsed_forward(next)
+                .resolve_next_service(&did_resolver)
+                .await
+                .expect("resolve_next_service is ok.")
+                .expect("service is some.");
+
+            assert_eq!(
+                service.service_endpoint,
+                BOB_DID_COMM_MESSAGING_SERVICE.service_endpoint
+            );
+            assert_eq!(
+                service.routing_keys,
+                BOB_DID_COMM_MESSAGING_SERVICE.routing_keys
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve_next_service_works_no_service() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+
+        let service = _parsed_forward(ALICE_DID)
+            .resolve_next_service(&did_resolver)
+            .await
+            .expect("resolve_next_service is ok.");
+
+        assert!(service.is_none());
+    }
+
+    #[tokio::test]
+    async fn resolve_next_service_works_did_not_resolved() {
+        let did_resolver = ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone()]);
+
+        let err = _parsed_forward(BOB_DID)
+            .resolve_next_service(&did_resolver)
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::DIDNotResolved);
+    }
+
+    fn _parsed_forward(next: &str) -> ParsedForward {
+        ParsedForward {
+            msg: MESSAGE_SIMPLE.clone(),
+            next: next.into(),
+            forwarded_msg: json!({}),
+            expires_time: None,
+            headers: HashMap::new(),
+        }
+    }
 }
@@ -6,10 +6,12 @@ use serde_json::{json, Value};
 use uuid::Uuid;
 
 use crate::{
-    algorithms::AnonCryptAlg,
+    algorithms::{AlgorithmRegistry, AnonCryptAlg, AuthCryptAlg},
     did::{DIDCommMessagingService, DIDResolver, Service, ServiceKind},
     error::{err_msg, ErrorKind, Result, ResultContext, ResultExt},
-    message::{anoncrypt, MessagingServiceMetadata},
+    jwe::NonceSource,
+    message::{anoncrypt, authcrypt, MessagingServiceMetadata},
+    secrets::SecretsResolver,
     utils::did::{did_or_url, is_did},
     Attachment, AttachmentData, Message, PackEncryptedOptions,
 };
@@ -18,9 +20,24 @@ pub use self::forward::ParsedForward;
 
 pub(crate) const FORWARD_MSG_TYPE: &str = "https://didcomm.org/routing/2.0/forward";
 
+/// Media type profile of DIDComm v2 envelopes (https://identity.foundation/didcomm-messaging/spec/#did-document-service-endpoint).
+/// Only DIDComm v2 envelopes can be produced, so messages are sent only to services accepting it.
+pub(crate) const DIDCOMM_V2_PROFILE: &str = "didcomm/v2";
+
+/// Profiles of envelopes produced by the library in order of preference.
+const SUPPORTED_PROFILES: &[&str] = &[DIDCOMM_V2_PROFILE];
+
+/// Finds DIDComm messaging service honoring `accept` profiles advertised by the service.
+/// Services are filtered and ranked by the requested `accept` profiles (in order of preference):
+/// services accepting more preferred profile are used first, services that don't declare
+/// accepted profiles are assumed to accept DIDComm v2 only and are used after services
+/// explicitly accepting it. Services with the same rank are used in order of DID doc.
+/// Services accepting none of the requested profiles (for ex. `didcomm/aip2;env=rfc19`)
+/// are never used, even if `service_id` points to such service.
 async fn find_did_comm_service<'dr>(
     did: &str,
     service_id: Option<&str>,
+    accept: &[&str],
     did_resolver: &'dr (dyn DIDResolver + 'dr),
 ) -> Result<Option<Service>> {
     let did_doc = did_resolver
@@ -42,7 +59,14 @@ async fn find_did_comm_service<'dr>(
                     )
                 })?;
 
-            if let ServiceKind::DIDCommMessaging { value: _ } = service.kind {
+            if let ServiceKind::DIDCommMessaging { ref value } = service.kind {
+                if _service_rank(value, accept).is_none() {
+                    Err(err_msg(
+                        ErrorKind::NoCompatibleCrypto,
+                        "Service with the specified ID doesn't accept requested profiles",
+                    ))?
+                }
+
                 Ok(Some(service.clone()))
             } else {
                 Err(err_msg(
@@ -52,14 +76,58 @@ async fn find_did_comm_service<'dr>(
             }
         }
 
-        None => Ok(did_doc.services.iter().find_map(|service| {
-            if let ServiceKind::DIDCommMessaging { value: _ } = service.kind {
-                Some(service.clone())
-            } else {
-                None
+        None => {
+            let services: Vec<_> = did_doc
+                .services
+                .iter()
+                .filter_map(|service| match service.kind {
+                    ServiceKind::DIDCommMessaging { ref value } => Some((service, value)),
+                    _ => None,
+                })
+                .collect();
+
+            if services.is_empty() {
+                return Ok(None);
             }
-        })),
+
+            let mut ranked: Vec<_> = services
+                .iter()
+                .filter_map(|(service, value)| {
+                    _service_rank(value, accept).map(|rank| (rank, *service))
+                })
+                .collect();
+
+            // Stable sort keeps DID doc order for services with the same rank
+            ranked.sort_by_key(|(rank, _)| *rank);
+
+            let (_, service) = ranked.first().ok_or_else(|| {
+                err_msg(
+                    ErrorKind::NoCompatibleCrypto,
+                    "No DIDCommMessaging service accepting requested profiles found",
+                )
+            })?;
+
+            Ok(Some((*service).clone()))
+        }
+    }
+}
+
+/// Returns rank of the service for the requested `accept` profiles (lower is better)
+/// or `None` if service doesn't accept any of them. Services that don't declare
+/// accepted profiles are assumed to accept DIDComm v2, but are ranked after services
+/// declaring it explicitly.
+fn _service_rank(service: &DIDCommMessagingService, accept: &[&str]) -> Option<(usize, bool)> {
+    if service.accept.is_empty() {
+        return accept
+            .iter()
+            .position(|&p| p == DIDCOMM_V2_PROFILE)
+            .map(|i| (i, true));
     }
+
+    accept
+        .iter()
+        .position(|&p| service.accept.iter().any(|sp| sp.trim() == p))
+        .map(|i| (i, false))
 }
 
 fn unwrap_did_comm_service(service: &Service) -> Result<&DIDCommMessagingService> {
@@ -79,7 +147,8 @@ async fn resolve_did_comm_services_chain<'dr>(
 ) -> Result<Vec<Service>> {
     let (to_did, _) = did_or_url(to);
 
-    let service = find_did_comm_service(to_did, service_id, did_resolver).await?;
+    let service =
+        find_did_comm_service(to_did, service_id, SUPPORTED_PROFILES, did_resolver).await?;
 
     if service.is_none() {
         return Ok(vec![]);
@@ -100,7 +169,7 @@ async fn resolve_did_comm_services_chain<'dr>(
             ));
         }
 
-        service = find_did_comm_service(service_endpoint, None, did_resolver)
+        service = find_did_comm_service(service_endpoint, None, SUPPORTED_PROFILES, did_resolver)
             .await?
             .ok_or_else(|| {
                 err_msg(
@@ -119,7 +188,7 @@ fn generate_message_id() -> String {
     Uuid::new_v4().to_string()
 }
 
-fn build_forward_message(
+pub(crate) fn build_forward_message(
     forwarded_msg: &str,
     next: &str,
     headers: Option<&HashMap<String, Value>>,
@@ -132,7 +201,12 @@ fn build_forward_message(
 
     if let Some(headers) = headers {
         for (name, value) in headers {
-            msg_builder = msg_builder.header(name.to_owned(), value.to_owned());
+            msg_builder = match name.as_str() {
+                // Well-known fields of the Forward message itself
+                "expires_time" => msg_builder.expires_time(_forward_time_header(name, value)?),
+                "created_time" => msg_builder.created_time(_forward_time_header(name, value)?),
+                _ => msg_builder.header(name.to_owned(), value.to_owned())?,
+            };
         }
     }
 
@@ -143,6 +217,33 @@ fn build_forward_message(
     serde_json::to_string(&msg).kind(ErrorKind::InvalidState, "Unable serialize forward message")
 }
 
+/// Validates body of the Forward message: `next` must be a DID or DID URL.
+pub(crate) fn validate_forward_body(body: &Value) -> Result<()> {
+    let next = body
+        .get("next")
+        .ok_or_else(|| err_msg(ErrorKind::Malformed, "`next` is required"))?
+        .as_str()
+        .ok_or_else(|| err_msg(ErrorKind::Malformed, "`next` must be a string"))?;
+
+    if !is_did(next) {
+        Err(err_msg(
+            ErrorKind::Malformed,
+            "`next` must be a DID or DID URL",
+        ))?
+    }
+
+    Ok(())
+}
+
+fn _forward_time_header(name: &str, value: &Value) -> Result<u64> {
+    value.as_u64().ok_or_else(|| {
+        err_msg(
+            ErrorKind::IllegalArgument,
+            format!("Forward header `{}` must be a non-negative integer", name),
+        )
+    })
+}
+
 pub fn try_parse_forward(msg: &Message) -> Option<ParsedForward> {
     if msg.type_ != FORWARD_MSG_TYPE {
         return None;
@@ -183,9 +284,27 @@ pub fn try_parse_forward(msg: &Message) -> Option<ParsedForward> {
         msg: msg.clone(),
         next: next.clone(),
         forwarded_msg: forwarded_msg.clone(),
+        expires_time: msg.expires_time,
+        headers: msg.extra_headers.clone(),
     })
 }
 
+/// Optional parameters of `wrap_in_forward_with_options`.
+#[derive(Default)]
+pub struct WrapInForwardOptions<'sr> {
+    /// If provided as `(from, enc_alg_auth, secrets_resolver)`, the outermost Forward addressed
+    /// to the first mediator is authcrypted by `from` DID or key ID instead of anoncrypt,
+    /// so the mediator learns the authenticated sender (see `PackEncryptedOptions::forward_from`).
+    /// All Forward messages are anonymous by default.
+    pub forward_from: Option<(
+        &'sr str,
+        &'sr AuthCryptAlg,
+        &'sr (dyn SecretsResolver + 'sr),
+    )>,
+}
+
+/// Wraps the given packed DIDComm message in anonymous Forward messages for the given
+/// routing keys. Forward message for the last routing key is the outermost one.
 pub async fn wrap_in_forward<'dr>(
     msg: &str,
     headers: Option<&HashMap<String, Value>>,
@@ -194,6 +313,120 @@ pub async fn wrap_in_forward<'dr>(
     enc_alg_anon: &AnonCryptAlg,
     did_resolver: &'dr (dyn DIDResolver + 'dr),
 ) -> Result<String> {
+    wrap_in_forward_with_options(
+        msg,
+        headers,
+        to,
+        routing_keys,
+        enc_alg_anon,
+        did_resolver,
+        &WrapInForwardOptions::default(),
+    )
+    .await
+}
+
+/// Same as `wrap_in_forward`, but allows to customize wrapping with `WrapInForwardOptions`
+/// (for ex. to authenticate the sender to the first mediator).
+pub async fn wrap_in_forward_with_options<'dr, 'sr>(
+    msg: &str,
+    headers: Option<&HashMap<String, Value>>,
+    to: &str,
+    routing_keys: &Vec<String>,
+    enc_alg_anon: &AnonCryptAlg,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    options: &WrapInForwardOptions<'sr>,
+) -> Result<String> {
+    _wrap_in_forward(
+        msg,
+        headers,
+        to,
+        routing_keys,
+        enc_alg_anon,
+        options.forward_from,
+        did_resolver,
+        None,
+        &AlgorithmRegistry::default(),
+    )
+    .await
+}
+
+/// Wraps the message in Forward for each of the given routing keys as `wrap_in_forward` does,
+/// but returns the envelope of each hop separately instead of the outermost one only.
+///
+/// Envelopes are returned as `(routing_key, envelope)` pairs in order of routing keys,
+/// so the first one is the nested form returned by `wrap_in_forward` and must be sent
+/// to the first mediator. Each next envelope is exactly the message forwarded
+/// (as `json` attachment) by Forward in the previous envelope, and the last one
+/// forwards the given message to `to`.
+pub async fn wrap_in_forward_chain<'dr>(
+    msg: &str,
+    headers: Option<&HashMap<String, Value>>,
+    to: &str,
+    routing_keys: &Vec<String>,
+    enc_alg_anon: &AnonCryptAlg,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+) -> Result<Vec<(String, String)>> /* (routing_key, envelope) */ {
+    _wrap_in_forward_chain(
+        msg,
+        headers,
+        to,
+        routing_keys,
+        enc_alg_anon,
+        None,
+        did_resolver,
+        None,
+        &AlgorithmRegistry::default(),
+    )
+    .await
+}
+
+async fn _wrap_in_forward<'dr, 'sr>(
+    msg: &str,
+    headers: Option<&HashMap<String, Value>>,
+    to: &str,
+    routing_keys: &Vec<String>,
+    enc_alg_anon: &AnonCryptAlg,
+    forward_from: Option<(&str, &AuthCryptAlg, &'sr (dyn SecretsResolver + 'sr))>, // (from, alg, secrets)
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    nonce_source: Option<&dyn NonceSource>,
+    algorithms: &AlgorithmRegistry,
+) -> Result<String> {
+    let mut chain = _wrap_in_forward_chain(
+        msg,
+        headers,
+        to,
+        routing_keys,
+        enc_alg_anon,
+        forward_from,
+        did_resolver,
+        nonce_source,
+        algorithms,
+    )
+    .await?;
+
+    // Chain isn't empty as routing keys are checked, the first envelope is the outermost one
+    let (_, msg) = chain.remove(0);
+    Ok(msg)
+}
+
+async fn _wrap_in_forward_chain<'dr, 'sr>(
+    msg: &str,
+    headers: Option<&HashMap<String, Value>>,
+    to: &str,
+    routing_keys: &Vec<String>,
+    enc_alg_anon: &AnonCryptAlg,
+    forward_from: Option<(&str, &AuthCryptAlg, &'sr (dyn SecretsResolver + 'sr))>, // (from, alg, secrets)
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    nonce_source: Option<&dyn NonceSource>,
+    algorithms: &AlgorithmRegistry,
+) -> Result<Vec<(String, String)>> /* (routing_key, envelope) */ {
+    if routing_keys.is_empty() {
+        Err(err_msg(
+            ErrorKind::IllegalArgument,
+            "No routing keys provided",
+        ))?
+    }
+
     let mut tos = routing_keys.clone();
 
     let mut nexts = tos.clone();
@@ -204,23 +437,117 @@ pub async fn wrap_in_forward<'dr>(
     nexts.reverse();
 
     let mut msg = msg.to_owned();
+    let mut chain = Vec::with_capacity(tos.len());
 
-    for (to_, next_) in tos.iter().zip(nexts.iter()) {
+    for (i, (to_, next_)) in tos.iter().zip(nexts.iter()).enumerate() {
         msg = build_forward_message(&msg, next_, headers)?;
-        msg = anoncrypt(to_, did_resolver, msg.as_bytes(), enc_alg_anon)
-            .await?
-            .0;
+
+        // Only the outermost Forward addressed to the first mediator is authenticated
+        msg = match forward_from {
+            Some((from, enc_alg_auth, secrets_resolver)) if i == tos.len() - 1 => {
+                authcrypt(
+                    to_,
+                    None,
+                    from,
+                    did_resolver,
+                    secrets_resolver,
+                    msg.as_bytes(),
+                    enc_alg_auth,
+                    enc_alg_anon,
+                    false,
+                    algorithms,
+                    nonce_source,
+                    None,
+                    false,
+                )
+                .await
+                .context("Unable authcrypt forward")?
+                .0
+            }
+            _ => {
+                anoncrypt(
+                    to_,
+                    None,
+                    did_resolver,
+                    msg.as_bytes(),
+                    enc_alg_anon,
+                    algorithms,
+                    nonce_source,
+                    None,
+                    None,
+                    false,
+                )
+                .await?
+                .0
+            }
+        };
+
+        chain.push((to_.clone(), msg.clone()));
     }
 
-    Ok(msg)
+    // Envelopes are built from the innermost one
+    chain.reverse();
+    Ok(chain)
 }
 
-pub(crate) async fn wrap_in_forward_if_needed<'dr>(
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "wrap_in_forward",
+        level = "debug",
+        skip_all,
+        fields(to = %to),
+        err(level = "debug")
+    )
+)]
+pub(crate) async fn wrap_in_forward_if_needed<'dr, 'sr>(
     msg: &str,
     to: &str,
     did_resolver: &'dr (dyn DIDResolver + 'dr),
+    secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
     options: &PackEncryptedOptions,
 ) -> Result<Option<(String, MessagingServiceMetadata)>> {
+    let (routing_keys, messaging_service) =
+        match resolve_forward_route(to, did_resolver, options).await? {
+            Some(route) => route,
+            None => return Ok(None),
+        };
+
+    let forward_from = options
+        .forward_from
+        .as_deref()
+        .map(|from| (from, &options.enc_alg_auth, secrets_resolver));
+
+    let forward_msg = _wrap_in_forward(
+        msg,
+        options.forward_headers.as_ref(),
+        to,
+        &routing_keys,
+        &options.enc_alg_anon,
+        forward_from,
+        did_resolver,
+        options.nonce_source(),
+        &options.algorithms,
+    )
+    .await?;
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        routing_keys = ?routing_keys,
+        messaging_service = %messaging_service.id,
+        "message wrapped in forward"
+    );
+
+    Ok(Some((forward_msg, messaging_service)))
+}
+
+/// Resolves routing keys of mediators the message for `to` must be forwarded through.
+/// Returns None if forwarding is disabled or not needed.
+pub(crate) async fn resolve_forward_route<'dr>(
+    to: &str,
+    did_resolver: &'dr (dyn DIDResolver + 'dr),
+    options: &PackEncryptedOptions,
+) -> Result<Option<(Vec<String>, MessagingServiceMetadata)>> /* (routing_keys, service) */ {
     if !options.forward {
         return Ok(None);
     }
@@ -251,16 +578,6 @@ pub(crate) async fn wrap_in_forward_if_needed<'dr>(
         return Ok(None);
     }
 
-    let forward_msg = wrap_in_forward(
-        msg,
-        options.forward_headers.as_ref(),
-        to,
-        &routing_keys,
-        &options.enc_alg_anon,
-        did_resolver,
-    )
-    .await?;
-
     let messaging_service = MessagingServiceMetadata {
         id: services_chain.last().unwrap().id.clone(),
         service_endpoint: unwrap_did_comm_service(services_chain.first().unwrap())?
@@ -268,5 +585,131 @@ pub(crate) async fn wrap_in_forward_if_needed<'dr>(
             .clone(),
     };
 
-    Ok(Some((forward_msg, messaging_service)))
+    Ok(Some((routing_keys, messaging_service)))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        did::{resolvers::ExampleDIDResolver, Service, ServiceKind},
+        error::ErrorKind,
+        protocols::test_support::bob_service,
+        test_vectors::{BOB_DID, BOB_DID_DOC},
+    };
+
+    use super::{find_did_comm_service, DIDCOMM_V2_PROFILE, SUPPORTED_PROFILES};
+
+    const AIP2_RFC19_PROFILE: &str = "didcomm/aip2;env=rfc19";
+
+    #[tokio::test]
+    async fn find_did_comm_service_works_several_services() {
+        let other = Service {
+            id: "did:example:bob#other".into(),
+            kind: ServiceKind::Other {
+                value: json!({"type": "LinkedDomains"}),
+            },
+        };
+
+        let aip2 = bob_service("did:example:bob#aip2", &[AIP2_RFC19_PROFILE]);
+        let no_accept = bob_service("did:example:bob#no-accept", &[]);
+        let v2_1 = bob_service(
+            "did:example:bob#v2-1",
+            &[AIP2_RFC19_PROFILE, DIDCOMM_V2_PROFILE],
+        );
+        let v2_2 = bob_service("did:example:bob#v2-2", &[DIDCOMM_V2_PROFILE]);
+
+        // Services explicitly accepting DIDComm v2 are preferred in order of DID doc
+        _find_did_comm_service_works(
+            vec![
+                other.clone(),
+                aip2.clone(),
+                no_accept.clone(),
+                v2_1.clone(),
+                v2_2.clone(),
+            ],
+            SUPPORTED_PROFILES,
+            &v2_1.id,
+        )
+        .await;
+
+        // Services not declaring accepted profiles are used if there is no such service
+        _find_did_comm_service_works(
+            vec![other.clone(), aip2.clone(), no_accept.clone()],
+            SUPPORTED_PROFILES,
+            &no_accept.id,
+        )
+        .await;
+
+        // Services are ranked by requested profiles order
+        _find_did_comm_service_works(
+            vec![no_accept.clone(), v2_2.clone(), aip2.clone()],
+            &[AIP2_RFC19_PROFILE, DIDCOMM_V2_PROFILE],
+            &aip2.id,
+        )
+        .await;
+
+        _find_did_comm_service_works(
+            vec![no_accept.clone(), v2_2.clone()],
+            &[AIP2_RFC19_PROFILE, DIDCOMM_V2_PROFILE],
+            &v2_2.id,
+        )
+        .await;
+
+        async fn _find_did_comm_service_works(
+            services: Vec<Service>,
+            accept: &[&str],
+            exp_service_id: &str,
+        ) {
+            let mut did_doc = BOB_DID_DOC.clone();
+            did_doc.services = services;
+            let did_resolver = ExampleDIDResolver::new(vec![did_doc]);
+
+            let service = find_did_comm_service(BOB_DID, None, accept, &did_resolver)
+                .await
+                .expect("find_did_comm_service is ok.")
+                .expect("service is some.");
+
+            assert_eq!(service.id, exp_service_id);
+        }
+    }
+
+    #[tokio::test]
+    async fn find_did_comm_service_works_no_did_comm_services() {
+        let mut did_doc = BOB_DID_DOC.clone();
+        did_doc.services = vec![];
+        let did_resolver = ExampleDIDResolver::new(vec![did_doc]);
+
+        let service = find_did_comm_service(BOB_DID, None, SUPPORTED_PROFILES, &did_resolver)
+            .await
+            .expect("find_did_comm_service is ok.");
+
+        assert!(service.is_none());
+    }
+
+    #[tokio::test]
+    async fn find_did_comm_service_works_no_accepted_profiles() {
+        let mut did_doc = BOB_DID_DOC.clone();
+
+        did_doc.services = vec![bob_service("did:example:bob#aip2", &[AIP2_RFC19_PROFILE])];
+        let did_resolver = ExampleDIDResolver::new(vec![did_doc]);
+
+        let err = find_did_comm_service(BOB_DID, None, SUPPORTED_PROFILES, &did_resolver)
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::NoCompatibleCrypto);
+
+        let err = find_did_comm_service(
+            BOB_DID,
+            Some("did:example:bob#aip2"),
+            SUPPORTED_PROFILES,
+            &did_resolver,
+        )
+        .await
+        .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::NoCompatibleCrypto);
+    }
 }
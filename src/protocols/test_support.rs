@@ -0,0 +1,35 @@
+use crate::{
+    did::{resolvers::ExampleDIDResolver, DIDCommMessagingService, Service, ServiceKind},
+    test_vectors::BOB_DID_COMM_MESSAGING_SERVICE,
+    Message,
+};
+
+/// Packs the message to plaintext and parses it back, checking that nothing was lost.
+pub(crate) async fn round_trip(msg: &Message) -> Message {
+    let did_resolver = ExampleDIDResolver::new(vec![]);
+
+    let packed_msg = msg
+        .pack_plaintext(&did_resolver)
+        .await
+        .expect("pack_plaintext is ok.");
+
+    let unpacked_msg = Message::from_str(&packed_msg)
+        .and_then(Message::validate)
+        .expect("unpack is ok.");
+
+    assert_eq!(&unpacked_msg, msg);
+    unpacked_msg
+}
+
+/// Bob's DIDComm messaging service with the given id and accepted profiles.
+pub(crate) fn bob_service(id: &str, accept: &[&str]) -> Service {
+    Service {
+        id: id.into(),
+        kind: ServiceKind::DIDCommMessaging {
+            value: DIDCommMessagingService {
+                accept: accept.iter().map(|&p| p.to_owned()).collect(),
+                ..BOB_DID_COMM_MESSAGING_SERVICE.clone()
+            },
+        },
+    }
+}
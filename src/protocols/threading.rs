@@ -0,0 +1,112 @@
+//! Helpers for [threads](https://identity.foundation/didcomm-messaging/spec/#threads)
+//! spanning many messages.
+
+use std::collections::HashSet;
+
+use crate::Message;
+
+/// Finds ID of the root thread for the thread of the first given message.
+///
+/// Thread of a message is identified by its `thid` or by its `id` if `thid` isn't set.
+/// Parent threads are found by following `pthid` of messages belonging to the thread.
+/// If the parent thread has no messages in the given list its ID is considered to be the root.
+/// Cyclic `pthid` references stop the walk at the last thread visited before the cycle.
+///
+/// # Params
+/// - `messages` messages of the thread and its parent threads in any order.
+///
+/// # Returns
+/// ID of the root thread or `None` if no messages are given.
+pub fn root_thread_id(messages: &[&Message]) -> Option<String> {
+    let mut thid = _thread_id(messages.first()?);
+    let mut visited = HashSet::new();
+
+    while visited.insert(thid) {
+        let pthid = messages
+            .iter()
+            .filter(|m| _thread_id(m) == thid)
+            .find_map(|m| m.pthid.as_deref());
+
+        match pthid {
+            Some(pthid) if !visited.contains(pthid) => thid = pthid,
+            _ => break,
+        }
+    }
+
+    Some(thid.to_owned())
+}
+
+pub(crate) fn _thread_id(msg: &Message) -> &str {
+    msg.thid.as_deref().unwrap_or(&msg.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::Message;
+
+    use super::root_thread_id;
+
+    #[test]
+    fn root_thread_id_works() {
+        // invitation
+        //  └─ request (pthid = invitation)
+        //      ├─ response (thid = request)
+        //      └─ nested (pthid = request)
+        //          └─ nested reply (thid = nested)
+        let invitation = _msg("invitation", None, None);
+        let request = _msg("request", None, Some("invitation"));
+        let response = _msg("response", Some("request"), None);
+        let nested = _msg("nested", None, Some("request"));
+        let nested_reply = _msg("nested-reply", Some("nested"), None);
+
+        let all = [&invitation, &request, &response, &nested, &nested_reply];
+
+        for msg in all {
+            let mut messages = vec![msg];
+            messages.extend(all.iter().filter(|m| m.id != msg.id));
+
+            assert_eq!(root_thread_id(&messages), Some("invitation".to_owned()));
+        }
+
+        assert_eq!(
+            root_thread_id(&[&nested_reply, &nested]),
+            Some("request".to_owned())
+        );
+
+        assert_eq!(root_thread_id(&[&response]), Some("request".to_owned()));
+
+        assert_eq!(root_thread_id(&[]), None);
+    }
+
+    #[test]
+    fn root_thread_id_works_cycle() {
+        let a = _msg("a", None, Some("b"));
+        let b = _msg("b", None, Some("c"));
+        let c = _msg("c", None, Some("a"));
+
+        assert_eq!(root_thread_id(&[&a, &b, &c]), Some("c".to_owned()));
+
+        let self_parent = _msg("d", None, Some("d"));
+        assert_eq!(root_thread_id(&[&self_parent]), Some("d".to_owned()));
+    }
+
+    fn _msg(id: &str, thid: Option<&str>, pthid: Option<&str>) -> Message {
+        let mut builder = Message::build(
+            id.to_owned(),
+            "https://example.com/protocol/1.0/message".to_owned(),
+            json!({}),
+        );
+
+        if let Some(thid) = thid {
+            builder = builder.thid(thid.to_owned());
+        }
+
+        if let Some(pthid) = pthid {
+            builder = builder.pthid(pthid.to_owned());
+        }
+
+        builder.finalize()
+    }
+}
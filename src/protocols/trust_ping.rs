@@ -0,0 +1,113 @@
+//! Trust Ping protocol (https://identity.foundation/didcomm-messaging/spec/#trust-ping-protocol-20)
+//! helpers to check connectivity with another party.
+
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{Message, MessageBuilder};
+
+pub const PING_MSG_TYPE: &str = "https://didcomm.org/trust-ping/2.0/ping";
+pub const PING_RESPONSE_MSG_TYPE: &str = "https://didcomm.org/trust-ping/2.0/ping-response";
+
+/// Builds Trust Ping `ping` message with random ID.
+/// Sender and recipients are expected to be set with the returned builder.
+///
+/// # Params
+/// - `response_requested` whether the recipient is asked to send `ping-response` back.
+pub fn build_ping(response_requested: bool) -> MessageBuilder {
+    Message::build(
+        Uuid::new_v4().to_string(),
+        PING_MSG_TYPE.to_owned(),
+        json!({ "response_requested": response_requested }),
+    )
+}
+
+/// Builds Trust Ping `ping-response` message with random ID threaded to the ping.
+///
+/// # Params
+/// - `thid` ID of the thread the ping belongs to (`thid` of the ping if set, its `id` otherwise).
+pub fn build_ping_response(thid: &str) -> MessageBuilder {
+    Message::build(
+        Uuid::new_v4().to_string(),
+        PING_RESPONSE_MSG_TYPE.to_owned(),
+        json!({}),
+    )
+    .thid(thid.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        did::resolvers::ExampleDIDResolver,
+        secrets::resolvers::ExampleSecretsResolver,
+        test_vectors::{
+            ALICE_DID, ALICE_DID_DOC, ALICE_SECRETS, BOB_DID, BOB_DID_DOC, BOB_SECRETS,
+        },
+        Message, PackEncryptedOptions, UnpackOptions,
+    };
+
+    use super::{build_ping, build_ping_response, PING_MSG_TYPE, PING_RESPONSE_MSG_TYPE};
+
+    #[tokio::test]
+    async fn trust_ping_works() {
+        let ping = build_ping(true)
+            .from(ALICE_DID.into())
+            .to(BOB_DID.into())
+            .finalize();
+
+        assert_eq!(ping.type_, PING_MSG_TYPE);
+        assert_eq!(ping.body, json!({ "response_requested": true }));
+
+        let did_resolver =
+            ExampleDIDResolver::new(vec![ALICE_DID_DOC.clone(), BOB_DID_DOC.clone()]);
+        let alice_secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let bob_secrets_resolver = ExampleSecretsResolver::new(BOB_SECRETS.clone());
+
+        let (packed_msg, _) = ping
+            .pack_encrypted(
+                BOB_DID,
+                Some(ALICE_DID),
+                None,
+                &did_resolver,
+                &alice_secrets_resolver,
+                &PackEncryptedOptions {
+                    forward: false,
+                    ..PackEncryptedOptions::default()
+                },
+            )
+            .await
+            .expect("pack_encrypted is ok.");
+
+        let (unpacked_ping, _) = Message::unpack(
+            &packed_msg,
+            &did_resolver,
+            &bob_secrets_resolver,
+            &UnpackOptions::default(),
+        )
+        .await
+        .expect("unpack is ok.");
+
+        assert_eq!(unpacked_ping, ping);
+
+        let response = build_ping_response(&unpacked_ping.id)
+            .from(BOB_DID.into())
+            .to(ALICE_DID.into())
+            .finalize();
+
+        assert_eq!(response.type_, PING_RESPONSE_MSG_TYPE);
+        assert_eq!(response.thid.as_deref(), Some(ping.id.as_str()));
+        assert_eq!(response.body, json!({}));
+        assert_ne!(response.id, ping.id);
+    }
+
+    #[test]
+    fn build_ping_works_no_response_requested() {
+        let ping = build_ping(false).finalize();
+
+        assert_eq!(ping.type_, PING_MSG_TYPE);
+        assert_eq!(ping.body, json!({ "response_requested": false }));
+        assert_eq!(ping.thid, None);
+    }
+}
@@ -2,7 +2,12 @@
 
 pub mod resolvers;
 
+mod remote;
+
+pub use remote::{RemoteCrypto, SecretsResolverCrypto};
+
 use async_trait::async_trait;
+use futures::future::try_join_all;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -35,6 +40,45 @@ pub trait SecretsResolver: Sync {
     /// # Returns
     /// possible empty list of all secrets that have one of the given IDs.
     async fn find_secrets<'a>(&self, secret_ids: &'a [&'a str]) -> Result<Vec<&'a str>>;
+
+    /// Gets secrets identified by the given key IDs in one call.
+    /// Allows backends (for ex. HSM or remote KMS) to batch lookups of multiple keys.
+    /// Default implementation calls `get_secret` for all IDs concurrently.
+    ///
+    /// # Parameters
+    /// - `secret_ids` the IDs (in form of DID URL) identifying secrets
+    ///
+    /// # Returns
+    /// Secrets in order of the given IDs. IDs without a secret are skipped.
+    ///
+    /// # Errors
+    /// - IOError
+    /// - InvalidState
+    async fn get_secrets(&self, secret_ids: &[&str]) -> Result<Vec<Secret>> {
+        let secrets = try_join_all(
+            secret_ids
+                .iter()
+                .map(|secret_id| self.get_secret(secret_id)),
+        )
+        .await?;
+
+        Ok(secrets.into_iter().flatten().collect())
+    }
+
+    /// Lists IDs of all secrets the resolver currently holds, for ex. for diagnostics
+    /// or to check the resolver has keys a message is encrypted for before unpacking.
+    /// Default implementation returns an empty list as not every backend can enumerate its keys,
+    /// so an empty list doesn't mean there are no secrets.
+    ///
+    /// # Returns
+    /// Possible empty list of key IDs (in form of DID URL).
+    ///
+    /// # Errors
+    /// - IOError
+    /// - InvalidState
+    async fn get_available_kids(&self) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
 }
 
 /// Interface for secrets resolver.
@@ -64,6 +108,45 @@ pub trait SecretsResolver {
     /// # Returns
     /// possible empty list of all secrets that have one of the given IDs.
     async fn find_secrets<'a>(&self, secret_ids: &'a [&'a str]) -> Result<Vec<&'a str>>;
+
+    /// Gets secrets identified by the given key IDs in one call.
+    /// Allows backends (for ex. HSM or remote KMS) to batch lookups of multiple keys.
+    /// Default implementation calls `get_secret` for all IDs concurrently.
+    ///
+    /// # Parameters
+    /// - `secret_ids` the IDs (in form of DID URL) identifying secrets
+    ///
+    /// # Returns
+    /// Secrets in order of the given IDs. IDs without a secret are skipped.
+    ///
+    /// # Errors
+    /// - IOError
+    /// - InvalidState
+    async fn get_secrets(&self, secret_ids: &[&str]) -> Result<Vec<Secret>> {
+        let secrets = try_join_all(
+            secret_ids
+                .iter()
+                .map(|secret_id| self.get_secret(secret_id)),
+        )
+        .await?;
+
+        Ok(secrets.into_iter().flatten().collect())
+    }
+
+    /// Lists IDs of all secrets the resolver currently holds, for ex. for diagnostics
+    /// or to check the resolver has keys a message is encrypted for before unpacking.
+    /// Default implementation returns an empty list as not every backend can enumerate its keys,
+    /// so an empty list doesn't mean there are no secrets.
+    ///
+    /// # Returns
+    /// Possible empty list of key IDs (in form of DID URL).
+    ///
+    /// # Errors
+    /// - IOError
+    /// - InvalidState
+    async fn get_available_kids(&self) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
 }
 
 /// Represents secret.
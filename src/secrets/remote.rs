@@ -0,0 +1,144 @@
+//! Delegation of private key operations to remote crypto providers (for ex. HSM or KMS)
+
+use askar_crypto::sign::{KeySign, SignatureType};
+
+use async_trait::async_trait;
+
+use crate::{
+    error::{err_msg, ErrorKind, Result, ResultContext, ResultExt},
+    secrets::SecretsResolver,
+    utils::crypto::{AsKnownKeyPair, KnownKeyPair},
+};
+
+/// Interface for crypto providers that keep private keys inside (for ex. HSM or remote KMS).
+/// Unlike `SecretsResolver` raw key material is never returned, instead the provider
+/// signs on behalf of DIDComm (see `Message::pack_signed_remote`).
+/// Decryption still requires key agreement secrets from `SecretsResolver`.
+#[cfg(feature = "uniffi")]
+#[async_trait]
+pub trait RemoteCrypto: Sync {
+    /// Find all keys that have one of the given IDs and can be used by this provider.
+    ///
+    /// # Parameters
+    /// - `kids` the key IDs (in form of DID URL) to find keys for
+    ///
+    /// # Returns
+    /// possible empty list of all key IDs the provider has keys for.
+    async fn find_keys<'a>(&self, kids: &'a [&'a str]) -> Result<Vec<&'a str>>;
+
+    /// Signs the given data with the private key identified by the given key ID.
+    ///
+    /// # Parameters
+    /// - `kid` the ID (in form of DID URL) identifying a key
+    /// - `data` the data (JWS signing input) to sign
+    ///
+    /// # Returns
+    /// Raw signature in JWS format (`R || S` for ECDSA, 64 bytes for EdDSA).
+    ///
+    /// # Errors
+    /// - SecretNotFound
+    /// - Unsupported
+    /// - IOError
+    /// - InvalidState
+    async fn sign(&self, kid: &str, data: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Interface for crypto providers that keep private keys inside (for ex. HSM or remote KMS).
+/// Unlike `SecretsResolver` raw key material is never returned, instead the provider
+/// signs on behalf of DIDComm (see `Message::pack_signed_remote`).
+/// Decryption still requires key agreement secrets from `SecretsResolver`.
+#[cfg(not(feature = "uniffi"))]
+#[async_trait(?Send)]
+pub trait RemoteCrypto {
+    /// Find all keys that have one of the given IDs and can be used by this provider.
+    ///
+    /// # Parameters
+    /// - `kids` the key IDs (in form of DID URL) to find keys for
+    ///
+    /// # Returns
+    /// possible empty list of all key IDs the provider has keys for.
+    async fn find_keys<'a>(&self, kids: &'a [&'a str]) -> Result<Vec<&'a str>>;
+
+    /// Signs the given data with the private key identified by the given key ID.
+    ///
+    /// # Parameters
+    /// - `kid` the ID (in form of DID URL) identifying a key
+    /// - `data` the data (JWS signing input) to sign
+    ///
+    /// # Returns
+    /// Raw signature in JWS format (`R || S` for ECDSA, 64 bytes for EdDSA).
+    ///
+    /// # Errors
+    /// - SecretNotFound
+    /// - Unsupported
+    /// - IOError
+    /// - InvalidState
+    async fn sign(&self, kid: &str, data: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Adapter that allows to use any `SecretsResolver` as `RemoteCrypto`.
+/// Private key operations are performed locally with secrets returned by the resolver.
+pub struct SecretsResolverCrypto<'sr> {
+    secrets_resolver: &'sr (dyn SecretsResolver + 'sr),
+}
+
+impl<'sr> SecretsResolverCrypto<'sr> {
+    pub fn new(secrets_resolver: &'sr (dyn SecretsResolver + 'sr)) -> Self {
+        SecretsResolverCrypto { secrets_resolver }
+    }
+
+    async fn _key_pair(&self, kid: &str) -> Result<KnownKeyPair> {
+        let secret = self
+            .secrets_resolver
+            .get_secret(kid)
+            .await
+            .context("Unable get secret")?
+            .ok_or_else(|| err_msg(ErrorKind::SecretNotFound, "Secret not found"))?;
+
+        secret.as_key_pair().context("Unable instantiate key pair")
+    }
+}
+
+#[cfg_attr(feature = "uniffi", async_trait)]
+#[cfg_attr(not(feature = "uniffi"), async_trait(?Send))]
+impl<'sr> RemoteCrypto for SecretsResolverCrypto<'sr> {
+    async fn find_keys<'a>(&self, kids: &'a [&'a str]) -> Result<Vec<&'a str>> {
+        self.secrets_resolver.find_secrets(kids).await
+    }
+
+    async fn sign(&self, kid: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let signature = match self._key_pair(kid).await? {
+            KnownKeyPair::Ed25519(ref key) => {
+                key.create_signature(data, Some(SignatureType::EdDSA))
+            }
+            KnownKeyPair::P256(ref key) => key.create_signature(data, Some(SignatureType::ES256)),
+            KnownKeyPair::K256(ref key) => key.create_signature(data, Some(SignatureType::ES256K)),
+            _ => Err(err_msg(ErrorKind::Unsupported, "Unsupported signature alg"))?,
+        }
+        .kind(ErrorKind::InvalidState, "Unable create signature")?;
+
+        Ok(signature.as_ref().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::ErrorKind, secrets::resolvers::ExampleSecretsResolver, test_vectors::ALICE_SECRETS,
+    };
+
+    use super::{RemoteCrypto, SecretsResolverCrypto};
+
+    #[tokio::test]
+    async fn sign_works_secret_not_found() {
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let crypto = SecretsResolverCrypto::new(&secrets_resolver);
+
+        let err = crypto
+            .sign("did:example:alice#not-found", b"data")
+            .await
+            .expect_err("res is ok");
+
+        assert_eq!(err.kind(), ErrorKind::SecretNotFound);
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    error::Result,
+    secrets::{Secret, SecretsResolver},
+};
+
+/// Secrets resolver shared by `Arc`. Can be cloned and stored in structs or moved
+/// to other tasks without `&'sr dyn SecretsResolver` lifetimes.
+/// All calls are delegated to the wrapped resolver.
+#[derive(Clone)]
+pub struct ArcSecretsResolver(Arc<dyn SecretsResolver + Send + Sync>);
+
+impl ArcSecretsResolver {
+    pub fn new(resolver: impl SecretsResolver + Send + Sync + 'static) -> Self {
+        ArcSecretsResolver(Arc::new(resolver))
+    }
+}
+
+impl From<Arc<dyn SecretsResolver + Send + Sync>> for ArcSecretsResolver {
+    fn from(resolver: Arc<dyn SecretsResolver + Send + Sync>) -> Self {
+        ArcSecretsResolver(resolver)
+    }
+}
+
+#[cfg_attr(feature = "uniffi", async_trait)]
+#[cfg_attr(not(feature = "uniffi"), async_trait(?Send))]
+impl SecretsResolver for ArcSecretsResolver {
+    async fn get_secret(&self, secret_id: &str) -> Result<Option<Secret>> {
+        self.0.get_secret(secret_id).await
+    }
+
+    async fn find_secrets<'a>(&self, secret_ids: &'a [&'a str]) -> Result<Vec<&'a str>> {
+        self.0.find_secrets(secret_ids).await
+    }
+
+    async fn get_secrets(&self, secret_ids: &[&str]) -> Result<Vec<Secret>> {
+        self.0.get_secrets(secret_ids).await
+    }
+
+    async fn get_available_kids(&self) -> Result<Vec<String>> {
+        self.0.get_available_kids().await
+    }
+}
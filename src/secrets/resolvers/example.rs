@@ -33,4 +33,42 @@ impl SecretsResolver for ExampleSecretsResolver {
             .map(|sid| *sid)
             .collect())
     }
+
+    async fn get_available_kids(&self) -> Result<Vec<String>> {
+        Ok(self.known_secrets.iter().map(|s| s.id.clone()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{secrets::SecretsResolver, test_vectors::ALICE_SECRETS};
+
+    use super::ExampleSecretsResolver;
+
+    #[tokio::test]
+    async fn example_secrets_resolver_get_available_kids_works() {
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+
+        let kids = secrets_resolver
+            .get_available_kids()
+            .await
+            .expect("get_available_kids is ok.");
+
+        assert_eq!(
+            kids,
+            ALICE_SECRETS
+                .iter()
+                .map(|s| s.id.clone())
+                .collect::<Vec<_>>()
+        );
+
+        assert!(kids.contains(&"did:example:alice#key-1".to_owned()));
+
+        let kids = ExampleSecretsResolver::new(vec![])
+            .get_available_kids()
+            .await
+            .expect("get_available_kids is ok.");
+
+        assert!(kids.is_empty());
+    }
 }
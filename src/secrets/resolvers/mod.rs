@@ -1,3 +1,11 @@
+mod arc;
+#[cfg(any(test, feature = "example-resolvers"))]
 mod example;
+#[cfg(any(test, feature = "example-resolvers"))]
+mod recording;
 
+pub use arc::ArcSecretsResolver;
+#[cfg(any(test, feature = "example-resolvers"))]
 pub use example::ExampleSecretsResolver;
+#[cfg(any(test, feature = "example-resolvers"))]
+pub use recording::RecordingSecretsResolver;
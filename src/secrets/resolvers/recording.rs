@@ -0,0 +1,181 @@
+use async_trait::async_trait;
+use std::sync::Mutex;
+
+use crate::{
+    error::{err_msg, ErrorKind, Result},
+    secrets::{Secret, SecretsResolver},
+};
+
+/// Wraps secrets resolver and records secret IDs requested from it
+/// by `get_secret`, `find_secrets` and `get_secrets` (for ex. to check which keys are used in tests).
+pub struct RecordingSecretsResolver<'sr> {
+    resolver: &'sr (dyn SecretsResolver + 'sr),
+    calls: Mutex<Vec<(Method, Vec<String>)>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Method {
+    GetSecret,
+    FindSecrets,
+    GetSecrets,
+}
+
+impl<'sr> RecordingSecretsResolver<'sr> {
+    pub fn new(resolver: &'sr (dyn SecretsResolver + 'sr)) -> Self {
+        RecordingSecretsResolver {
+            resolver,
+            calls: Mutex::new(vec![]),
+        }
+    }
+
+    /// Returns secret IDs requested so far by all methods in order of requests.
+    pub fn resolved_secret_ids(&self) -> Vec<String> {
+        self._calls()
+            .into_iter()
+            .flat_map(|(_, secret_ids)| secret_ids)
+            .collect()
+    }
+
+    /// Returns how many times the given secret ID was requested.
+    pub fn resolve_count(&self, secret_id: &str) -> usize {
+        self.resolved_secret_ids()
+            .iter()
+            .filter(|resolved| *resolved == secret_id)
+            .count()
+    }
+
+    /// Returns secret IDs requested by `get_secret` so far in order of requests.
+    pub fn get_secret_calls(&self) -> Vec<String> {
+        self._method_calls(Method::GetSecret)
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Returns secret IDs requested by each `find_secrets` call so far in order of requests.
+    pub fn find_secrets_calls(&self) -> Vec<Vec<String>> {
+        self._method_calls(Method::FindSecrets)
+    }
+
+    /// Returns secret IDs requested by each `get_secrets` call so far in order of requests.
+    pub fn get_secrets_calls(&self) -> Vec<Vec<String>> {
+        self._method_calls(Method::GetSecrets)
+    }
+
+    fn _calls(&self) -> Vec<(Method, Vec<String>)> {
+        self.calls
+            .lock()
+            .map(|calls| calls.clone())
+            .unwrap_or_default()
+    }
+
+    fn _method_calls(&self, method: Method) -> Vec<Vec<String>> {
+        self._calls()
+            .into_iter()
+            .filter(|(m, _)| *m == method)
+            .map(|(_, secret_ids)| secret_ids)
+            .collect()
+    }
+
+    fn _record(&self, method: Method, secret_ids: &[&str]) -> Result<()> {
+        self.calls
+            .lock()
+            .map_err(|_| err_msg(ErrorKind::InvalidState, "Unable lock resolved secret ids"))?
+            .push((method, secret_ids.iter().map(|&id| id.to_owned()).collect()));
+
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "uniffi", async_trait)]
+#[cfg_attr(not(feature = "uniffi"), async_trait(?Send))]
+impl<'sr> SecretsResolver for RecordingSecretsResolver<'sr> {
+    async fn get_secret(&self, secret_id: &str) -> Result<Option<Secret>> {
+        self._record(Method::GetSecret, &[secret_id])?;
+        self.resolver.get_secret(secret_id).await
+    }
+
+    async fn find_secrets<'a>(&self, secret_ids: &'a [&'a str]) -> Result<Vec<&'a str>> {
+        self._record(Method::FindSecrets, secret_ids)?;
+        self.resolver.find_secrets(secret_ids).await
+    }
+
+    async fn get_secrets(&self, secret_ids: &[&str]) -> Result<Vec<Secret>> {
+        self._record(Method::GetSecrets, secret_ids)?;
+        self.resolver.get_secrets(secret_ids).await
+    }
+
+    async fn get_available_kids(&self) -> Result<Vec<String>> {
+        self.resolver.get_available_kids().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        secrets::{resolvers::ExampleSecretsResolver, SecretsResolver},
+        test_vectors::ALICE_SECRETS,
+    };
+
+    use super::RecordingSecretsResolver;
+
+    #[tokio::test]
+    async fn recording_secrets_resolver_works() {
+        let secrets_resolver = ExampleSecretsResolver::new(ALICE_SECRETS.clone());
+        let secrets_resolver = RecordingSecretsResolver::new(&secrets_resolver);
+
+        assert!(secrets_resolver.resolved_secret_ids().is_empty());
+
+        let secret = secrets_resolver
+            .get_secret("did:example:alice#key-1")
+            .await
+            .expect("get_secret is ok.");
+
+        assert!(secret.is_some());
+
+        let found = secrets_resolver
+            .find_secrets(&["did:example:alice#key-1", "did:example:alice#key-unknown"])
+            .await
+            .expect("find_secrets is ok.");
+
+        assert_eq!(found, vec!["did:example:alice#key-1"]);
+
+        let secrets = secrets_resolver
+            .get_secrets(&["did:example:alice#key-2"])
+            .await
+            .expect("get_secrets is ok.");
+
+        assert_eq!(secrets.len(), 1);
+
+        assert_eq!(
+            secrets_resolver.resolved_secret_ids(),
+            vec![
+                "did:example:alice#key-1".to_owned(),
+                "did:example:alice#key-1".to_owned(),
+                "did:example:alice#key-unknown".to_owned(),
+                "did:example:alice#key-2".to_owned(),
+            ]
+        );
+
+        assert_eq!(secrets_resolver.resolve_count("did:example:alice#key-1"), 2);
+        assert_eq!(secrets_resolver.resolve_count("did:example:alice#key-3"), 0);
+
+        assert_eq!(
+            secrets_resolver.get_secret_calls(),
+            vec!["did:example:alice#key-1".to_owned()]
+        );
+
+        assert_eq!(
+            secrets_resolver.find_secrets_calls(),
+            vec![vec![
+                "did:example:alice#key-1".to_owned(),
+                "did:example:alice#key-unknown".to_owned(),
+            ]]
+        );
+
+        assert_eq!(
+            secrets_resolver.get_secrets_calls(),
+            vec![vec!["did:example:alice#key-2".to_owned()]]
+        );
+    }
+}
@@ -19,6 +19,11 @@ pub fn remove_field(msg: &str, field: &str) -> String {
 }
 
 pub fn update_protected_field(msg: &str, field: &str, value: &str) -> String {
+    update_protected_field_value(msg, field, value.into())
+}
+
+/// Same as `update_protected_field`, but the field can be set to any JSON value.
+pub fn update_protected_field_value(msg: &str, field: &str, value: Value) -> String {
     let parsed: Value = serde_json::from_str(&msg).unwrap();
     let mut msg_dict: Map<String, Value> = parsed.as_object().unwrap().clone();
 
@@ -31,9 +36,8 @@ pub fn update_protected_field(msg: &str, field: &str, value: &str) -> String {
     .unwrap();
     let parsed_protected: Value = serde_json::from_slice(&buffer).unwrap();
     let mut protected_dict: Map<String, Value> = parsed_protected.as_object().unwrap().clone();
-    protected_dict.insert(String::from(field), value.into());
+    protected_dict.insert(String::from(field), value);
     let protected_str = serde_json::to_string(&protected_dict).unwrap();
-    println!("{}", &protected_str);
     let protected_str_base64 = base64::encode_config(protected_str, base64::URL_SAFE_NO_PAD);
     msg_dict.insert(String::from("protected"), protected_str_base64.into());
     serde_json::to_string(&msg_dict).unwrap()
@@ -124,6 +124,7 @@ lazy_static! {
             "did:example:alice#key-p521-1".into(),
         ],
         services: vec![],
+        also_known_as: vec![],
         verification_methods: vec![
             ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.clone(),
             ALICE_VERIFICATION_METHOD_KEY_AGREEM_P256.clone(),
@@ -149,6 +150,7 @@ lazy_static! {
             "did:example:alice#key-p521-1".into(),
         ],
         services: vec![],
+        also_known_as: vec![],
         verification_methods: vec![
             ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519_NOT_IN_SECRET.clone(),
             ALICE_VERIFICATION_METHOD_KEY_AGREEM_X25519.clone(),
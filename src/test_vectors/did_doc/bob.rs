@@ -225,6 +225,7 @@ lazy_static! {
             "did:example:bob#key-p521-2".into(),
         ],
         services: vec![BOB_SERVICE.clone()],
+        also_known_as: vec![],
         verification_methods: vec![
             BOB_VERIFICATION_METHOD_KEY_AGREEM_X25519_1.clone(),
             BOB_VERIFICATION_METHOD_KEY_AGREEM_X25519_2.clone(),
@@ -256,6 +257,7 @@ lazy_static! {
             "did:example:bob#key-p521-not-secrets-1".into(),
         ],
         services: vec![BOB_SERVICE.clone()],
+        also_known_as: vec![],
         verification_methods: vec![
             BOB_VERIFICATION_METHOD_KEY_AGREEM_X25519_1.clone(),
             BOB_VERIFICATION_METHOD_KEY_AGREEM_X25519_2.clone(),
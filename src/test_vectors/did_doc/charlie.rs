@@ -54,6 +54,7 @@ lazy_static! {
         authentications: vec!["did:example:charlie#key-1".into()],
         key_agreements: vec!["did:example:charlie#key-x25519-1".into()],
         services: vec![CHARLIE_SERVICE.clone()],
+        also_known_as: vec![],
         verification_methods: vec![
             CHARLIE_VERIFICATION_METHOD_KEY_AGREEM_X25519.clone(),
             CHARLIE_AUTH_METHOD_25519.clone(),
@@ -75,6 +75,7 @@ lazy_static! {
             "did:example:mediator2#key-p521-1".into(),
         ],
         services: vec![],
+        also_known_as: vec![],
         verification_methods: vec![
             MEDIATOR2_VERIFICATION_METHOD_KEY_AGREEM_X25519_1.clone(),
             MEDIATOR2_VERIFICATION_METHOD_KEY_AGREEM_P256_1.clone(),
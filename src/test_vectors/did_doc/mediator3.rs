@@ -88,6 +88,7 @@ lazy_static! {
             "did:example:mediator3#key-p521-1".into(),
         ],
         services: vec![MEDIATOR3_SERVICE.clone()],
+        also_known_as: vec![],
         verification_methods: vec![
             MEDIATOR3_VERIFICATION_METHOD_KEY_AGREEM_X25519_1.clone(),
             MEDIATOR3_VERIFICATION_METHOD_KEY_AGREEM_P256_1.clone(),
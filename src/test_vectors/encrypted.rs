@@ -68,6 +68,37 @@ pub const ENCRYPTED_MSG_AUTH_X25519: &str = r#"
 }
 "#;
 
+// `ENCRYPTED_MSG_AUTH_X25519` anoncrypted (A256GCM, ECDH-ES+A256KW) to the same Bob keys
+// to hide the sender (protected sender). Produced independently of this library.
+pub const ENCRYPTED_MSG_AUTH_X25519_PROTECTED_SENDER: &str = r#"
+{
+    "ciphertext": "ZvFQG6Z6wenjv7JlBGG-nq1eoVFgHtqkOA8Nb6SROgPKMcQUqANlLZaptlOvIGhkZJuNibpn6ur4RydrSkqpagz3xbzqVUTsn-yCVh99s0YQ4SfVEE3pOL460zOutrYKAyStUkBuisQxyOvhsY_BoEg84-biH3t_paHmjh1BrHnr47fb5UD8Va_C3wn0Yrtsfi3eiWk_Fy7kWxn_AsKefyWjHMEoJZcxf_IOcMjdVZCKibnOgEs_I6g_SmWYROeYHLjxBRim3aHDV0TdP3lmuPpQNV_fbnKaLhFUDhdyAZU2YOv8GLJdTXOKTNqfHxcRakobecP6F51Ls59zJhyDQExdOfKbQVIBNevLfKX8HsL8vtoBqh1M69WlTMBoKiZ3PfmBonE9gJiLkA_pP8nyJFGX6J36IgbD2QgFIQSmNaXoGYNVGLBAMZOEy0ZwXti1zyvQZt4XFR4wFzLTAhhE34mVcbkucifc4QE1ML-FoBJgKjM1S-Y4JUWQbWsYP_O1WzN2AfFlI8LAmVoNs2VZ5PdINcSYvqNBxNYabEXDI9_UDvS7yPxpg4H1aBgUuEUGfXHdiohp0aDEeMK0iTBRQsx_7FIcicnFoc1mhdiLJsCMNaENRhzpT5_ouzBV5n0IRB2sZLNKEjICb7Fw2nDtVwR9hGZ8Sw7FlC5Osx8wa1SUTMi9gF1kEjEiaRkgfxnNGRlmar3W2VRROHtACh6YBe7GFEpSHpbaY18hF0ZlLlURFe6sq3ljwuIX8z9_PV7SLKtBvh5igpurfdPOfRYZI21-P4THIQM3ofglROrgrSZw13VAnPW8peBAv11-mrB4Vc9Jy1Rl-aIynWPIjW0IIEDeNbNf4Dw2-vBazANtFZqegG6ZsR-74hc2aerHWdXsEXLGuOsWT5rO10eo9bqwpDoSuHhLoY75aseT_rlKpXKkIqRg2ReimWQUmsDgpE8uo27f6kXJEoU1Xnu-YnN2YsGSbPb2WV9m_d77oXFt-akxxig2eLpE20fcJElJV3wfT17W9wmqxqpbC1P1X13tSWsRDeIB4DwZYf-OdKuY3sej9_J5ZM7c7BqU2qXB9bT4nMUCgYjrMOFYY0Yq4_gZ1lI5aVmGdvPTW9BMWNR1WUWauPTP_zyjNkbGjDuZ50GVSQc_dge0qNDz0fEiDdNk8WpnU6jYtN2kWvUPhRVVaHlcHX4CYMD2SR5iGzi7AWk52tNmN8qv-1tw_fucXg5Nj3jd1I1W3oEk-xAA6BiFVZtL2t-x1xtci_rCBMUSRI15COPfuMzBkhVdVgEslx_NJTSAkQnkEPHKjtUlSMU8lWIwcQdkb1hcacass2shb4DicBBSAUBp6nNQIgqUHlhoyo248TraLfsvU_4XP1xEQLgv-wjHywy7SBIqFSw-uN1iT77uoXNtSMQPFyrhbSV9lMMOeVrPhPsLLCIN4qsAXuwiHk3GjpBs8zL5jZ7gIGULzJFlqMLt7wpNGUCOfdqvFT6Uu9YbOpFALhOxKsDDGHZzJpYrlWPH8cdZDlmc-iTNu3NnMCZnJh-3c4XkjTWG2YTmbRBHZZZ-NB1MY9QQJPm8DchVctIv2mQAtXJMR4api12jcdyIHgCbMLIo3D6NpT491OmgyQGeFtfSh0WoYFIMkD0fmBwjgjKyh7qWKurkwPHO9xmt1vSvL5hRWUrFWItDOXJf0x9g47oNQTIr0DyCP1wygUC4_lF2jyhlhhrCjz5KRsvJIyi95ApbBrAGbQOmVkFp6DoFvS3h0E9W_x4H_dfhXvVaqSSZwOIvowEqGJ4HEWtPhLhlCOkYKaUy0cS1EHFCKmkgbOS2rl4RFsJxtEHJ_77syHSV6NzkvIKhD_BZVMIK9Fhk5xyDpkWNk_jsIGz5kmCvjhC5m00_WvCtXaB1bcU",
+    "protected": "eyJ0eXAiOiJhcHBsaWNhdGlvbi9kaWRjb21tLWVuY3J5cHRlZCtqc29uIiwiYWxnIjoiRUNESC1FUytBMjU2S1ciLCJlbmMiOiJBMjU2R0NNIiwiYXB2IjoiTmNzdUFuclJmUEs2OUEtcmtaMEw5WFdVRzRqTXZOQzNaZzc0QlB6NTNQQSIsImVwayI6eyJrdHkiOiJPS1AiLCJjcnYiOiJYMjU1MTkiLCJ4IjoieTJmZmlTOXU0ekpTZHlYeEo1Y3czbi16b2xDaTNhQkQ3d240a2dEWm5UdyJ9fQ",
+    "recipients": [
+        {
+            "encrypted_key": "ezuqnuyRoV-9nY75vvTihkpy2m5Fbl3TAGHorsVUwuUKn0W6dKoA1Q",
+            "header": {
+                "kid": "did:example:bob#key-x25519-1"
+            }
+        },
+        {
+            "encrypted_key": "MnyKqbRS9m7vL-tM0SGWvDYiEz3I_dtBPxql5iUMd9xFlyT36umNOA",
+            "header": {
+                "kid": "did:example:bob#key-x25519-2"
+            }
+        },
+        {
+            "encrypted_key": "xWPERPM-eCiuz8MYpQB5GDGaQZMFP22VYdiACeigp0nnPqCaVfPLrg",
+            "header": {
+                "kid": "did:example:bob#key-x25519-3"
+            }
+        }
+    ],
+    "tag": "02-XYxHmhrxxQA9wCDOGSA",
+    "iv": "1ekrhamz3LUWANKL"
+}
+"#;
+
 pub const ENCRYPTED_MSG_AUTH_P256: &str = r#"
 {
     "ciphertext": "WCufCs2lMZfkxQ0JCK92lPtLFgwWk_FtRWOMj52bQISa94nEbIYqHDUohIbvLMgbSjRcJVusZO04UthDuOpSSTcV5GBi3O0cMrjyI_PZnTb1yikLXpXma1bT10D2r5TPtzRMxXF3nFsr9y0JKV1TsMtn70Df2fERx2bAGxcflmd-A2sMlSTT8b7QqPtn17Yb-pA8gr4i0Bqb2WfDzwnbfewbukpRmPA2hsEs9oLKypbniAafSpoiQjfb19oDfsYaWWXqsdjTYMflqH__DqSmW52M-SUp6or0xU0ujbHmOkRkcdh9PsR5YsPuIWAqYa2hfjz_KIrGTxvCos0DMiZ4Lh_lPIYQqBufSdFH5AGChoekFbQ1vcyIyYMFugzOHOgZ2TwEzv94GCgokBHQR4_qaU_f4Mva64KPwqOYdm5f4KX16afTJa-IV7ar7__2L-A-LyxmC5KIHeGOedV9kzZBLC7TuzRAuE3vY7pkhLB1jPE6XpTeKXldljaeOSEVcbFUQtsHOSPz9JXuhqZ1fdAx8qV7hUnSAd_YMMDR3S6SXtem8ak2m98WPvKIxhCbcto7W2qoNYMT7MPvvid-QzUvTdKtyovCvLzhyYJzMjZxmn9-EnGhZ5ITPL_xFfLyKxhSSUVz3kSwK9xuOj3KpJnrrD7xrp5FKzEaJVIHWrUW90V_9QVLjriThZ36fA3ipvs8ZJ8QSTnGAmuIQ6Z2u_r4KsjL_mGAgn47qyqRm-OSLEUE4_2qB0Q9Z7EBKakCH8VPt09hTMDR62aYZYwtmpNs9ISu0VPvFjh8UmKbFcQsVrz90-x-r-Q1fTX9JaIFcDy7aqKcI-ai3tVF_HDR60Jaiw",
@@ -105,3 +136,29 @@ pub const INVALID_ENCRYPTED_MSG_ANON_P256_EPK_WRONG_POINT: &str = r#"
     "tag": "etLTQvKsTvF629fykLiUDg"
 }
 "#;
+
+// Anoncrypt (ECDH-ES+A256KW) message with `skid` header that is allowed only for authcrypt
+pub const INVALID_ENCRYPTED_MSG_ANON_XC20P_SKID: &str = r#"
+{
+    "ciphertext": "KWS7gJU7TbyJlcT9dPkCw-ohNigGaHSukR9MUqFM0THbCTCNkY-g5tahBFyszlKIKXs7qOtqzYyWbPou2q77XlAeYs93IhF6NvaIjyNqYklvj-OtJt9W2Pj5CLOMdsR0C30wchGoXd6wEQZY4ttbzpxYznqPmJ0b9KW6ZP-l4_DSRYe9B-1oSWMNmqMPwluKbtguC-riy356Xbu2C9ShfWmpmjz1HyJWQhZfczuwkWWlE63g26FMskIZZd_jGpEhPFHKUXCFwbuiw_Iy3R0BIzmXXdK_w7PZMMPbaxssl2UeJmLQgCAP8j8TukxV96EKa6rGgULvlo7qibjJqsS5j03bnbxkuxwbfyu3OxwgVzFWlyHbUH6p",
+    "protected": "eyJlcGsiOnsia3R5IjoiT0tQIiwiY3J2IjoiWDI1NTE5IiwieCI6IkpIanNtSVJaQWFCMHpSR193TlhMVjJyUGdnRjAwaGRIYlc1cmo4ZzBJMjQifSwiYXB2IjoiTmNzdUFuclJmUEs2OUEtcmtaMEw5WFdVRzRqTXZOQzNaZzc0QlB6NTNQQSIsInR5cCI6ImFwcGxpY2F0aW9uL2RpZGNvbW0tZW5jcnlwdGVkK2pzb24iLCJlbmMiOiJYQzIwUCIsImFsZyI6IkVDREgtRVMrQTI1NktXIiwic2tpZCI6ImRpZDpleGFtcGxlOmFsaWNlI2tleS14MjU1MTktMSJ9",
+    "recipients": [{
+            "encrypted_key": "3n1olyBR3nY7ZGAprOx-b7wYAKza6cvOYjNwVg3miTnbLwPP_FmE1A",
+            "header": {
+                "kid": "did:example:bob#key-x25519-1"
+            }
+        },{
+            "encrypted_key": "j5eSzn3kCrIkhQAWPnEwrFPMW6hG0zF_y37gUvvc5gvlzsuNX4hXrQ",
+            "header": {
+                "kid": "did:example:bob#key-x25519-2"
+            }
+        },{
+            "encrypted_key": "TEWlqlq-ao7Lbynf0oZYhxs7ZB39SUWBCK4qjqQqfeItfwmNyDm73A",
+            "header": {
+                "kid": "did:example:bob#key-x25519-3"
+            }
+        }],
+    "tag": "6ylC_iAs4JvDQzXeY6MuYQ",
+    "iv": "ESpmcyGiZpRjc5urDela21TOOTW8Wqd1"
+}
+"#;
@@ -0,0 +1,185 @@
+use serde_json::{Map, Value};
+
+/// Builds malformed variants of JWE and JWS test vectors. Each field of the envelope
+/// (protected header params, recipient and signature entries, base64url encoded values)
+/// can be changed, removed or corrupted independently, so malformed-case tests don't need
+/// to patch JSON strings by hand.
+///
+/// JWE protected header params are addressed with `protected` and `remove_protected` methods,
+/// JWS protected header params of each signature with `signature_protected` method.
+pub struct EnvelopeBuilder {
+    envelope: Map<String, Value>,
+}
+
+impl EnvelopeBuilder {
+    /// Starts from the given JWE or JWS in General JSON Serialization.
+    pub fn new(msg: &str) -> Self {
+        let envelope: Value = serde_json::from_str(msg).expect("Unable parse envelope");
+
+        EnvelopeBuilder {
+            envelope: envelope
+                .as_object()
+                .expect("Envelope is not an object")
+                .clone(),
+        }
+    }
+
+    /// Sets top-level field (for ex. `iv`, `tag`, `ciphertext` or `payload`).
+    pub fn field(mut self, name: &str, value: Value) -> Self {
+        self.envelope.insert(name.to_owned(), value);
+        self
+    }
+
+    /// Removes top-level field.
+    pub fn remove_field(mut self, name: &str) -> Self {
+        self.envelope.remove(name);
+        self
+    }
+
+    /// Flips bits of the last byte of base64url encoded top-level field (for ex. `tag`),
+    /// so the value is still decodable, but is wrong.
+    pub fn corrupt_field(mut self, name: &str) -> Self {
+        let value = self.envelope.get_mut(name).expect("Field not found");
+        _corrupt(value);
+        self
+    }
+
+    /// Sets JWE protected header param.
+    pub fn protected(mut self, name: &str, value: Value) -> Self {
+        _update_protected(&mut self.envelope, |protected| {
+            protected.insert(name.to_owned(), value);
+        });
+
+        self
+    }
+
+    /// Removes JWE protected header param.
+    pub fn remove_protected(mut self, name: &str) -> Self {
+        _update_protected(&mut self.envelope, |protected| {
+            protected.remove(name);
+        });
+
+        self
+    }
+
+    /// Sets field of JWE recipient entry with the given index.
+    /// `kid` is set in per-recipient header, other fields (for ex. `encrypted_key`) in the entry itself.
+    pub fn recipient(mut self, i: usize, name: &str, value: Value) -> Self {
+        let recipient = _entry(&mut self.envelope, "recipients", i);
+
+        match name {
+            "kid" => recipient["header"]["kid"] = value,
+            _ => recipient[name] = value,
+        }
+
+        self
+    }
+
+    /// Flips bits of `encrypted_key` of JWE recipient entry with the given index.
+    pub fn corrupt_recipient_key(mut self, i: usize) -> Self {
+        _corrupt(&mut _entry(&mut self.envelope, "recipients", i)["encrypted_key"]);
+        self
+    }
+
+    /// Removes JWE recipient entry with the given index.
+    pub fn remove_recipient(mut self, i: usize) -> Self {
+        _entries(&mut self.envelope, "recipients").remove(i);
+        self
+    }
+
+    /// Appends copy of JWE recipient entry with the given index.
+    pub fn duplicate_recipient(mut self, i: usize) -> Self {
+        let recipients = _entries(&mut self.envelope, "recipients");
+        let recipient = recipients.get(i).expect("Entry not found").clone();
+        recipients.push(recipient);
+        self
+    }
+
+    /// Sets JWS protected header param of the signature with the given index.
+    pub fn signature_protected(mut self, i: usize, name: &str, value: Value) -> Self {
+        let signature = _entry(&mut self.envelope, "signatures", i)
+            .as_object_mut()
+            .expect("Signature is not an object");
+
+        _update_protected(signature, |protected| {
+            protected.insert(name.to_owned(), value);
+        });
+
+        self
+    }
+
+    /// Sets field of JWS signature entry with the given index.
+    /// `kid` is set in unprotected header, other fields (for ex. `signature`) in the entry itself.
+    pub fn signature(mut self, i: usize, name: &str, value: Value) -> Self {
+        let signature = _entry(&mut self.envelope, "signatures", i);
+
+        match name {
+            "kid" => signature["header"]["kid"] = value,
+            _ => signature[name] = value,
+        }
+
+        self
+    }
+
+    /// Flips bits of `signature` of JWS signature entry with the given index.
+    pub fn corrupt_signature(mut self, i: usize) -> Self {
+        _corrupt(&mut _entry(&mut self.envelope, "signatures", i)["signature"]);
+        self
+    }
+
+    /// Returns the envelope as JSON string.
+    pub fn build(self) -> String {
+        serde_json::to_string(&self.envelope).expect("Unable serialize envelope")
+    }
+}
+
+fn _entries<'a>(envelope: &'a mut Map<String, Value>, name: &str) -> &'a mut Vec<Value> {
+    envelope
+        .get_mut(name)
+        .and_then(Value::as_array_mut)
+        .expect("Entries not found")
+}
+
+fn _entry<'a>(envelope: &'a mut Map<String, Value>, name: &str, i: usize) -> &'a mut Value {
+    _entries(envelope, name)
+        .get_mut(i)
+        .expect("Entry not found")
+}
+
+fn _update_protected(
+    object: &mut Map<String, Value>,
+    update: impl FnOnce(&mut Map<String, Value>),
+) {
+    let protected = object
+        .get("protected")
+        .and_then(Value::as_str)
+        .expect("Protected header not found");
+
+    let protected = base64::decode_config(protected, base64::URL_SAFE_NO_PAD)
+        .expect("Unable decode protected header");
+
+    let mut protected: Map<String, Value> =
+        serde_json::from_slice(&protected).expect("Unable parse protected header");
+
+    update(&mut protected);
+
+    let protected = serde_json::to_string(&protected).expect("Unable serialize protected header");
+
+    object.insert(
+        "protected".to_owned(),
+        base64::encode_config(protected, base64::URL_SAFE_NO_PAD).into(),
+    );
+}
+
+fn _corrupt(value: &mut Value) {
+    let mut decoded = base64::decode_config(
+        value.as_str().expect("Value is not a string"),
+        base64::URL_SAFE_NO_PAD,
+    )
+    .expect("Unable decode value");
+
+    let last = decoded.last_mut().expect("Value is empty");
+    *last ^= 0xff;
+
+    *value = base64::encode_config(decoded, base64::URL_SAFE_NO_PAD).into();
+}
@@ -0,0 +1,261 @@
+//! Interop vectors allowing to check that messages produced by other DIDComm implementations
+//! (for ex. didcomm-python) are unpacked the same way and that our packing matches them
+//! on deterministic fields.
+//!
+//! Built-in vectors are the ones shared with didcomm-python (`ENCRYPTED_MSG_*`, `SIGNED_MSG_*`,
+//! `PLAINTEXT_MSG_*`). Additional vectors can be provided with `DIDCOMM_INTEROP_VECTORS`
+//! environment variable pointing to JSON file (or directory of JSON files) containing
+//! an array of vectors in the following format:
+//!
+//! ```json
+//! [{
+//!     "name": "anoncrypt xc20p",
+//!     "message": { ...packed message... },
+//!     "recipient": "bob",
+//!     "expected_plaintext": { ...plaintext message... },
+//!     "expected_metadata": { "encrypted": true, "anonymous_sender": true }
+//! }]
+//! ```
+//!
+//! `recipient` is the party whose secrets are used for unpacking
+//! (`alice`, `bob`, `charlie`, `mediator1`, `mediator2` or `mediator3`).
+//! `expected_metadata` may contain any subset of `UnpackMetadata` fields,
+//! only provided fields are compared.
+
+use std::{fs, path::Path};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::didcomm::{did::DIDDoc, secrets::Secret};
+
+use super::{
+    ALICE_DID_DOC, ALICE_SECRETS, BOB_DID_DOC, BOB_SECRETS, CHARLIE_DID_DOC, CHARLIE_SECRETS,
+    ENCRYPTED_MSG_ANON_XC20P_1, ENCRYPTED_MSG_ANON_XC20P_2, ENCRYPTED_MSG_AUTH_P256,
+    ENCRYPTED_MSG_AUTH_P256_SIGNED, ENCRYPTED_MSG_AUTH_X25519, MEDIATOR1_DID_DOC,
+    MEDIATOR1_SECRETS, MEDIATOR2_DID_DOC, MEDIATOR2_SECRETS, MEDIATOR3_DID_DOC, MEDIATOR3_SECRETS,
+    PLAINTEXT_MSG_MINIMAL, PLAINTEXT_MSG_SIMPLE, SIGNED_MSG_ALICE_KEY_1, SIGNED_MSG_ALICE_KEY_2,
+    SIGNED_MSG_ALICE_KEY_3,
+};
+
+/// Environment variable pointing to JSON file or directory with additional interop vectors.
+pub const INTEROP_VECTORS_ENV: &str = "DIDCOMM_INTEROP_VECTORS";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct InteropVector {
+    /// Human readable vector name used in assertion messages.
+    pub name: String,
+
+    /// Packed message either as JSON object or as JSON string.
+    pub message: Value,
+
+    /// Party whose secrets are used for unpacking.
+    pub recipient: String,
+
+    /// Plaintext message the packed message must be unpacked to.
+    pub expected_plaintext: Value,
+
+    /// Subset of `UnpackMetadata` fields expected after unpacking.
+    pub expected_metadata: Value,
+}
+
+impl InteropVector {
+    /// Packed message as JSON string.
+    pub fn packed(&self) -> String {
+        match self.message {
+            Value::String(ref packed) => packed.clone(),
+            ref packed => packed.to_string(),
+        }
+    }
+}
+
+/// Built-in vectors and vectors loaded from `DIDCOMM_INTEROP_VECTORS` if set.
+pub fn interop_vectors() -> Vec<InteropVector> {
+    let mut vectors = builtin_interop_vectors();
+
+    if let Ok(path) = std::env::var(INTEROP_VECTORS_ENV) {
+        vectors.extend(load_interop_vectors(Path::new(&path)));
+    }
+
+    vectors
+}
+
+/// Loads vectors from JSON file or all `.json` files of the directory.
+/// Panics if vectors can't be read or parsed as this is a test utility.
+pub fn load_interop_vectors(path: &Path) -> Vec<InteropVector> {
+    if path.is_dir() {
+        let mut paths: Vec<_> = fs::read_dir(path)
+            .expect("Unable read interop vectors dir")
+            .map(|entry| entry.expect("Unable read interop vectors dir").path())
+            .filter(|path| path.extension().map(|ext| ext == "json").unwrap_or(false))
+            .collect();
+
+        paths.sort();
+        paths
+            .iter()
+            .flat_map(|path| load_interop_vectors(path))
+            .collect()
+    } else {
+        let vectors = fs::read_to_string(path).expect("Unable read interop vectors file");
+        serde_json::from_str(&vectors).expect("Unable parse interop vectors file")
+    }
+}
+
+/// DID Docs of all parties used by interop vectors.
+pub fn interop_did_docs() -> Vec<DIDDoc> {
+    vec![
+        ALICE_DID_DOC.clone(),
+        BOB_DID_DOC.clone(),
+        CHARLIE_DID_DOC.clone(),
+        MEDIATOR1_DID_DOC.clone(),
+        MEDIATOR2_DID_DOC.clone(),
+        MEDIATOR3_DID_DOC.clone(),
+    ]
+}
+
+/// Secrets of the given party. Panics for unknown party.
+pub fn interop_secrets(party: &str) -> Vec<Secret> {
+    match party {
+        "alice" => ALICE_SECRETS.clone(),
+        "bob" => BOB_SECRETS.clone(),
+        "charlie" => CHARLIE_SECRETS.clone(),
+        "mediator1" => MEDIATOR1_SECRETS.clone(),
+        "mediator2" => MEDIATOR2_SECRETS.clone(),
+        "mediator3" => MEDIATOR3_SECRETS.clone(),
+        _ => panic!("Unknown interop party {}", party),
+    }
+}
+
+fn builtin_interop_vectors() -> Vec<InteropVector> {
+    let simple: Value = serde_json::from_str(PLAINTEXT_MSG_SIMPLE).unwrap();
+    let minimal: Value = serde_json::from_str(PLAINTEXT_MSG_MINIMAL).unwrap();
+
+    let vector =
+        |name: &str, message: &str, expected_plaintext: &Value, metadata: Value| InteropVector {
+            name: name.into(),
+            message: serde_json::from_str(message).unwrap(),
+            recipient: "bob".into(),
+            expected_plaintext: expected_plaintext.clone(),
+            expected_metadata: metadata,
+        };
+
+    vec![
+        vector(
+            "plaintext simple",
+            PLAINTEXT_MSG_SIMPLE,
+            &simple,
+            json!({"encrypted": false, "authenticated": false, "non_repudiation": false}),
+        ),
+        vector(
+            "plaintext minimal",
+            PLAINTEXT_MSG_MINIMAL,
+            &minimal,
+            json!({"encrypted": false, "authenticated": false, "non_repudiation": false}),
+        ),
+        vector(
+            "signed ed25519",
+            SIGNED_MSG_ALICE_KEY_1,
+            &simple,
+            json!({
+                "encrypted": false,
+                "authenticated": true,
+                "non_repudiation": true,
+                "sign_from": "did:example:alice#key-1",
+                "sign_alg": "EdDSA",
+            }),
+        ),
+        vector(
+            "signed p256",
+            SIGNED_MSG_ALICE_KEY_2,
+            &simple,
+            json!({
+                "encrypted": false,
+                "authenticated": true,
+                "non_repudiation": true,
+                "sign_from": "did:example:alice#key-2",
+                "sign_alg": "ES256",
+            }),
+        ),
+        vector(
+            "signed secp256k1",
+            SIGNED_MSG_ALICE_KEY_3,
+            &simple,
+            json!({
+                "encrypted": false,
+                "authenticated": true,
+                "non_repudiation": true,
+                "sign_from": "did:example:alice#key-3",
+                "sign_alg": "ES256K",
+            }),
+        ),
+        vector(
+            "anoncrypt x25519 xc20p",
+            ENCRYPTED_MSG_ANON_XC20P_1,
+            &simple,
+            json!({
+                "encrypted": true,
+                "authenticated": false,
+                "anonymous_sender": true,
+                "enc_alg_anon": "Xc20pEcdhEsA256kw",
+                "encrypted_to_kids": [
+                    "did:example:bob#key-x25519-1",
+                    "did:example:bob#key-x25519-2",
+                    "did:example:bob#key-x25519-3",
+                ],
+            }),
+        ),
+        vector(
+            "anoncrypt p256 xc20p",
+            ENCRYPTED_MSG_ANON_XC20P_2,
+            &simple,
+            json!({
+                "encrypted": true,
+                "authenticated": false,
+                "anonymous_sender": true,
+                "enc_alg_anon": "Xc20pEcdhEsA256kw",
+                "encrypted_to_kids": [
+                    "did:example:bob#key-p256-1",
+                    "did:example:bob#key-p256-2",
+                ],
+            }),
+        ),
+        vector(
+            "authcrypt x25519",
+            ENCRYPTED_MSG_AUTH_X25519,
+            &simple,
+            json!({
+                "encrypted": true,
+                "authenticated": true,
+                "anonymous_sender": false,
+                "non_repudiation": false,
+                "enc_alg_auth": "A256cbcHs512Ecdh1puA256kw",
+                "encrypted_from_kid": "did:example:alice#key-x25519-1",
+                "encrypted_to_kids": [
+                    "did:example:bob#key-x25519-1",
+                    "did:example:bob#key-x25519-2",
+                    "did:example:bob#key-x25519-3",
+                ],
+            }),
+        ),
+        vector(
+            "authcrypt p256 signed",
+            ENCRYPTED_MSG_AUTH_P256,
+            &simple,
+            json!({
+                "encrypted": true,
+                "authenticated": true,
+                "anonymous_sender": false,
+                "non_repudiation": true,
+                "enc_alg_auth": "A256cbcHs512Ecdh1puA256kw",
+                "encrypted_from_kid": "did:example:alice#key-p256-1",
+                "encrypted_to_kids": [
+                    "did:example:bob#key-p256-1",
+                    "did:example:bob#key-p256-2",
+                ],
+                "sign_from": "did:example:alice#key-1",
+                "sign_alg": "EdDSA",
+                "signed_message": ENCRYPTED_MSG_AUTH_P256_SIGNED,
+            }),
+        ),
+    ]
+}
@@ -1,12 +1,15 @@
 mod common;
 mod did_doc;
 mod encrypted;
+mod envelope;
 mod from_prior;
 mod from_prior_jwt;
+mod interop;
 mod message;
 mod plaintext;
 mod secrets;
 mod signed;
+mod wire_format;
 
 pub use common::*;
 
@@ -14,13 +17,19 @@ pub use did_doc::*;
 
 pub use encrypted::*;
 
+pub use envelope::*;
+
 pub use from_prior::*;
 
 pub use from_prior_jwt::*;
 
+pub use interop::*;
+
 pub use message::*;
 pub use plaintext::*;
 
 pub use secrets::*;
 
 pub use signed::*;
+
+pub use wire_format::*;
@@ -282,3 +282,19 @@ pub const INVALID_PLAINTEXT_MSG_ATTACHMENTS_NULL_DATA: &str = r#"
     "attachments": [{"id": "1", "data": null}]
 }
 "#;
+
+pub const INVALID_PLAINTEXT_MSG_ATTACHMENTS_STRINGIFIED_JSON: &str = r#"
+{
+    "id": "1234567890",
+    "typ": "application/didcomm-plain+json",
+    "type": "http://example.com/protocols/lets_do_lunch/1.0/proposal",
+    "from": "did:example:alice",
+    "to": ["did:example:bob"],
+    "created_time": 1516269022,
+    "expires_time": 1516385931,
+    "body": {"messagespecificattribute": "and its value"},
+    "attachments": [
+        {"id": "23", "data": {"json": "{\"foo\": \"bar\", \"links\": [2, 3]}"}}
+    ]
+}
+"#;
@@ -36,3 +36,17 @@ pub const SIGNED_MSG_ALICE_KEY_3: &str = r#"
         }]
 }
 "#;
+
+// Signature is produced over JCS (RFC 8785) canonicalized payload instead of the transmitted one
+pub const SIGNED_MSG_ALICE_KEY_1_JCS: &str = r#"
+{
+    "payload": "eyJpZCI6IjEyMzQ1Njc4OTAiLCJ0eXAiOiJhcHBsaWNhdGlvbi9kaWRjb21tLXBsYWluK2pzb24iLCJ0eXBlIjoiaHR0cDovL2V4YW1wbGUuY29tL3Byb3RvY29scy9sZXRzX2RvX2x1bmNoLzEuMC9wcm9wb3NhbCIsImZyb20iOiJkaWQ6ZXhhbXBsZTphbGljZSIsInRvIjpbImRpZDpleGFtcGxlOmJvYiJdLCJjcmVhdGVkX3RpbWUiOjE1MTYyNjkwMjIsImV4cGlyZXNfdGltZSI6MTUxNjM4NTkzMSwiYm9keSI6eyJtZXNzYWdlc3BlY2lmaWNhdHRyaWJ1dGUiOiJhbmQgaXRzIHZhbHVlIn19",
+    "signatures": [{
+            "protected": "eyJ0eXAiOiJhcHBsaWNhdGlvbi9kaWRjb21tLXNpZ25lZCtqc29uIiwiYWxnIjoiRWREU0EifQ",
+            "signature": "E6rhAjZKDfREbhcPp_1De3ECVHSeZaQYHBJ_ZGRDjQbwKyN53XVqV3luaJS-OwZkLQxBVj0swgFZkGUoiUVfCg",
+            "header": {
+                "kid": "did:example:alice#key-1"
+            }
+        }]
+}
+"#;
@@ -0,0 +1,17 @@
+//! Frozen packed outputs of the current wire format (see `message::wire_format` tests).
+//!
+//! Unlike interop vectors, they are byte-exact outputs of this library for deterministic
+//! packing paths, so any change of field order, escaping or base64 encoding is caught.
+//! Vectors are versioned by name prefix (`WIRE_V1_`). Don't edit them in place: if wire format
+//! is changed intentionally, add vectors with the next version prefix, switch tests to them
+//! and keep the previous ones as long as they must be unpackable.
+
+pub const WIRE_V1_PLAINTEXT_SIMPLE: &str = r#"{"id":"1234567890","typ":"application/didcomm-plain+json","type":"http://example.com/protocols/lets_do_lunch/1.0/proposal","body":{"messagespecificattribute":"and its value"},"from":"did:example:alice","to":["did:example:bob"],"created_time":1516269022,"expires_time":1516385931}"#;
+
+pub const WIRE_V1_PLAINTEXT_MINIMAL: &str = r#"{"id":"1234567890","typ":"application/didcomm-plain+json","type":"http://example.com/protocols/lets_do_lunch/1.0/proposal","body":{}}"#;
+
+pub const WIRE_V1_PLAINTEXT_ATTACHMENT_MULTI: &str = r#"{"id":"1234567890","typ":"application/didcomm-plain+json","type":"http://example.com/protocols/lets_do_lunch/1.0/proposal","body":{"messagespecificattribute":"and its value"},"from":"did:example:alice","to":["did:example:bob"],"created_time":1516269022,"expires_time":1516385931,"attachments":[{"data":{"json":{"foo":"bar","links":[2,3]}},"id":"23"},{"data":{"base64":"qwerty"},"id":"24"},{"data":{"links":["1","2","3"],"hash":"qwerty"},"id":"25"}]}"#;
+
+pub const WIRE_V1_SIGNED_ED25519_SIMPLE: &str = r#"{"signatures":[{"header":{"kid":"did:example:alice#key-1"},"protected":"eyJ0eXAiOiJhcHBsaWNhdGlvbi9kaWRjb21tLXNpZ25lZCtqc29uIiwiYWxnIjoiRWREU0EifQ","signature":"T9v-aY20q0ixN5-o-DcsaSWIZXGx8t6UeNJqNn3yACQAbaa8WY70HpnXk6kXfQX0Wdu4eP4AYmtSHCk_TkTbAg"}],"payload":"eyJpZCI6IjEyMzQ1Njc4OTAiLCJ0eXAiOiJhcHBsaWNhdGlvbi9kaWRjb21tLXBsYWluK2pzb24iLCJ0eXBlIjoiaHR0cDovL2V4YW1wbGUuY29tL3Byb3RvY29scy9sZXRzX2RvX2x1bmNoLzEuMC9wcm9wb3NhbCIsImJvZHkiOnsibWVzc2FnZXNwZWNpZmljYXR0cmlidXRlIjoiYW5kIGl0cyB2YWx1ZSJ9LCJmcm9tIjoiZGlkOmV4YW1wbGU6YWxpY2UiLCJ0byI6WyJkaWQ6ZXhhbXBsZTpib2IiXSwiY3JlYXRlZF90aW1lIjoxNTE2MjY5MDIyLCJleHBpcmVzX3RpbWUiOjE1MTYzODU5MzF9"}"#;
+
+pub const WIRE_V1_SIGNED_ED25519_ATTACHMENT_MULTI: &str = r#"{"signatures":[{"header":{"kid":"did:example:alice#key-1"},"protected":"eyJ0eXAiOiJhcHBsaWNhdGlvbi9kaWRjb21tLXNpZ25lZCtqc29uIiwiYWxnIjoiRWREU0EifQ","signature":"S3d4X6IBu93gxG_cFr-t2G3D_sOw_noCwSk03s5vKyJhz0AA8AhzU5Pht1oooalLDGR7MNT5tUqQgAgnHA7lDA"}],"payload":"eyJpZCI6IjEyMzQ1Njc4OTAiLCJ0eXAiOiJhcHBsaWNhdGlvbi9kaWRjb21tLXBsYWluK2pzb24iLCJ0eXBlIjoiaHR0cDovL2V4YW1wbGUuY29tL3Byb3RvY29scy9sZXRzX2RvX2x1bmNoLzEuMC9wcm9wb3NhbCIsImJvZHkiOnsibWVzc2FnZXNwZWNpZmljYXR0cmlidXRlIjoiYW5kIGl0cyB2YWx1ZSJ9LCJmcm9tIjoiZGlkOmV4YW1wbGU6YWxpY2UiLCJ0byI6WyJkaWQ6ZXhhbXBsZTpib2IiXSwiY3JlYXRlZF90aW1lIjoxNTE2MjY5MDIyLCJleHBpcmVzX3RpbWUiOjE1MTYzODU5MzEsImF0dGFjaG1lbnRzIjpbeyJkYXRhIjp7Impzb24iOnsiZm9vIjoiYmFyIiwibGlua3MiOlsyLDNdfX0sImlkIjoiMjMifSx7ImRhdGEiOnsiYmFzZTY0IjoicXdlcnR5In0sImlkIjoiMjQifSx7ImRhdGEiOnsibGlua3MiOlsiMSIsIjIiLCIzIl0sImhhc2giOiJxd2VydHkifSwiaWQiOiIyNSJ9XX0"}"#;
@@ -9,13 +9,16 @@ use askar_crypto::{
     buffer::SecretBytes,
     encrypt::KeyAeadInPlace,
     kdf::{ecdh_1pu::Ecdh1PU, ecdh_es::EcdhEs, FromKeyDerivation, KeyExchange},
-    repr::{KeySecretBytes, ToSecretBytes},
+    repr::{KeySecretBytes, ToPublicBytes, ToSecretBytes},
 };
 
+use zeroize::Zeroize;
+
 use crate::error::{err_msg, ErrorKind, Result, ResultExt};
 
-/// Note this trait is compatible with KW algorithms only
-pub(crate) trait KeyWrap: KeyAeadInPlace {
+/// Note this trait is compatible with KW algorithms only.
+/// Key wrapping keys are derived secrets, so they must be zeroized on drop.
+pub(crate) trait KeyWrap: KeyAeadInPlace + Zeroize {
     fn wrap_key<K: KeyAeadInPlace + ToSecretBytes>(&self, key: &K) -> Result<SecretBytes> {
         let params = self.aead_params();
 
@@ -129,6 +132,20 @@ pub(crate) enum KnownKeyPair {
     K256(K256KeyPair),
 }
 
+impl KnownKeyPair {
+    pub(crate) fn public_bytes(&self) -> Result<Vec<u8>> {
+        let bytes = match self {
+            KnownKeyPair::Ed25519(k) => k.to_public_bytes(),
+            KnownKeyPair::X25519(k) => k.to_public_bytes(),
+            KnownKeyPair::P256(k) => k.to_public_bytes(),
+            KnownKeyPair::K256(k) => k.to_public_bytes(),
+        }
+        .kind(ErrorKind::InvalidState, "Unable get public key bytes")?;
+
+        Ok(bytes.to_vec())
+    }
+}
+
 pub(crate) trait AsKnownKeyPair {
     fn key_alg(&self) -> KnownKeyAlg;
     fn as_key_pair(&self) -> Result<KnownKeyPair>;
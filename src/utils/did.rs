@@ -4,19 +4,42 @@ use askar_crypto::alg::{
 use serde_json::json;
 use std::io::Cursor;
 use varint::{VarintRead, VarintWrite};
+use zeroize::Zeroizing;
 
 use crate::error::ToResult;
 use crate::{
-    did::{did_doc::VerificationMethodType, VerificationMaterial, VerificationMethod},
-    error::{err_msg, ErrorKind, Result, ResultExt},
+    did::{
+        did_doc::VerificationMethodType, parse_did_url, DIDDoc, VerificationMaterial,
+        VerificationMethod,
+    },
+    error::{err_msg, Error, ErrorKind, Result, ResultExt},
     jwk::FromJwkValue,
     secrets::{Secret, SecretMaterial, SecretType},
     utils::crypto::{AsKnownKeyPair, KnownKeyAlg, KnownKeyPair},
 };
 
+/// Whether the value is a valid DID or DID URL (see `parse_did_url`).
 pub(crate) fn is_did(did: &str) -> bool {
-    let parts: Vec<_> = did.split(':').collect();
-    return parts.len() >= 3 && parts.get(0).unwrap() == &"did";
+    parse_did_url(did).is_ok()
+}
+
+/// `DIDUrlNotFound` error for the DID that is resolved, but its DID Doc doesn't contain
+/// requested key agreements. Key agreements present in the DID Doc are listed for diagnostics,
+/// so it is distinguishable from `DIDNotResolved` error of the DID that isn't resolved at all.
+pub(crate) fn key_agreements_not_found(msg: &str, did_doc: &DIDDoc) -> Error {
+    let present = if did_doc.key_agreements.is_empty() {
+        "none".to_owned()
+    } else {
+        did_doc.key_agreements.join(", ")
+    };
+
+    err_msg(
+        ErrorKind::DIDUrlNotFound,
+        format!(
+            "{}. DID `{}` is resolved, present key agreements: {}",
+            msg, did_doc.did, present
+        ),
+    )
 }
 
 pub(crate) fn did_or_url(did_or_url: &str) -> (&str, Option<&str>) {
@@ -149,15 +172,7 @@ impl AsKnownKeyPair for VerificationMethod {
                 VerificationMethodType::X25519KeyAgreementKey2020,
                 VerificationMaterial::Multibase { ref value },
             ) => {
-                if !value.starts_with('z') {
-                    Err(err_msg(
-                        ErrorKind::IllegalArgument,
-                        "Multibase value must start with 'z'",
-                    ))?
-                };
-                let decoded_value = bs58::decode(&value[1..])
-                    .into_vec()
-                    .to_didcomm("Wrong multibase value in verification material")?;
+                let decoded_value = _decode_multibase(value, "verification material")?;
 
                 let (codec, decoded_value) = _from_multicodec(&decoded_value)?;
                 if codec != Codec::X25519Pub {
@@ -187,15 +202,7 @@ impl AsKnownKeyPair for VerificationMethod {
                 VerificationMethodType::Ed25519VerificationKey2020,
                 VerificationMaterial::Multibase { ref value },
             ) => {
-                if !value.starts_with('z') {
-                    Err(err_msg(
-                        ErrorKind::IllegalArgument,
-                        "Multibase must start with 'z'",
-                    ))?
-                }
-                let decoded_value = bs58::decode(&value[1..])
-                    .into_vec()
-                    .to_didcomm("Wrong multibase value in verification material")?;
+                let decoded_value = _decode_multibase(value, "verification material")?;
 
                 let (codec, decoded_value) = _from_multicodec(&decoded_value)?;
                 if codec != Codec::Ed25519Pub {
@@ -294,20 +301,23 @@ impl AsKnownKeyPair for Secret {
             }
 
             (SecretType::X25519KeyAgreementKey2019, SecretMaterial::Base58 { ref value }) => {
-                let decoded_value = bs58::decode(value)
-                    .into_vec()
-                    .to_didcomm("Wrong base58 value in secret material")?;
+                let decoded_value = Zeroizing::new(
+                    bs58::decode(value)
+                        .into_vec()
+                        .to_didcomm("Wrong base58 value in secret material")?,
+                );
 
                 let curve25519_point_size = 32;
                 let (d_value, x_value) = decoded_value.split_at(curve25519_point_size);
-                let base64_url_d_value = base64::encode_config(&d_value, base64::URL_SAFE_NO_PAD);
+                let base64_url_d_value =
+                    Zeroizing::new(base64::encode_config(&d_value, base64::URL_SAFE_NO_PAD));
                 let base64_url_x_value = base64::encode_config(&x_value, base64::URL_SAFE_NO_PAD);
 
                 let jwk = json!({
                     "kty": "OKP",
                     "crv": "X25519",
                     "x": base64_url_x_value,
-                    "d": base64_url_d_value
+                    "d": base64_url_d_value.as_str()
                 });
 
                 X25519KeyPair::from_jwk_value(&jwk)
@@ -316,19 +326,22 @@ impl AsKnownKeyPair for Secret {
             }
 
             (SecretType::Ed25519VerificationKey2018, SecretMaterial::Base58 { ref value }) => {
-                let decoded_value = bs58::decode(value)
-                    .into_vec()
-                    .to_didcomm("Wrong base58 value in secret material")?;
+                let decoded_value = Zeroizing::new(
+                    bs58::decode(value)
+                        .into_vec()
+                        .to_didcomm("Wrong base58 value in secret material")?,
+                );
 
                 let curve25519_point_size = 32;
                 let (d_value, x_value) = decoded_value.split_at(curve25519_point_size);
-                let base64_url_d_value = base64::encode_config(&d_value, base64::URL_SAFE_NO_PAD);
+                let base64_url_d_value =
+                    Zeroizing::new(base64::encode_config(&d_value, base64::URL_SAFE_NO_PAD));
                 let base64_url_x_value = base64::encode_config(&x_value, base64::URL_SAFE_NO_PAD);
 
                 let jwk = json!({"kty": "OKP",
                     "crv": "Ed25519",
                     "x": base64_url_x_value,
-                    "d": base64_url_d_value
+                    "d": base64_url_d_value.as_str()
                 });
 
                 Ed25519KeyPair::from_jwk_value(&jwk)
@@ -337,15 +350,8 @@ impl AsKnownKeyPair for Secret {
             }
 
             (SecretType::X25519KeyAgreementKey2020, SecretMaterial::Multibase { ref value }) => {
-                if !value.starts_with('z') {
-                    Err(err_msg(
-                        ErrorKind::IllegalArgument,
-                        "Multibase must start with 'z'",
-                    ))?
-                }
-                let decoded_multibase_value = bs58::decode(&value[1..])
-                    .into_vec()
-                    .to_didcomm("Wrong multibase value in secret material")?;
+                let decoded_multibase_value =
+                    Zeroizing::new(_decode_multibase(value, "secret material")?);
 
                 let (codec, decoded_value) = _from_multicodec(&decoded_multibase_value)?;
                 if codec != Codec::X25519Priv {
@@ -357,14 +363,15 @@ impl AsKnownKeyPair for Secret {
 
                 let curve25519_point_size = 32;
                 let (d_value, x_value) = decoded_value.split_at(curve25519_point_size);
-                let base64_url_d_value = base64::encode_config(&d_value, base64::URL_SAFE_NO_PAD);
+                let base64_url_d_value =
+                    Zeroizing::new(base64::encode_config(&d_value, base64::URL_SAFE_NO_PAD));
                 let base64_url_x_value = base64::encode_config(&x_value, base64::URL_SAFE_NO_PAD);
 
                 let jwk = json!({
                     "kty": "OKP",
                     "crv": "X25519",
                     "x": base64_url_x_value,
-                    "d": base64_url_d_value
+                    "d": base64_url_d_value.as_str()
                 });
 
                 X25519KeyPair::from_jwk_value(&jwk)
@@ -376,15 +383,8 @@ impl AsKnownKeyPair for Secret {
             }
 
             (SecretType::Ed25519VerificationKey2020, SecretMaterial::Multibase { ref value }) => {
-                if !value.starts_with('z') {
-                    Err(err_msg(
-                        ErrorKind::IllegalArgument,
-                        "Multibase must start with 'z'",
-                    ))?
-                }
-                let decoded_multibase_value = bs58::decode(&value[1..])
-                    .into_vec()
-                    .to_didcomm("Wrong multibase value in secret material")?;
+                let decoded_multibase_value =
+                    Zeroizing::new(_decode_multibase(value, "secret material")?);
 
                 let (codec, decoded_value) = _from_multicodec(&decoded_multibase_value)?;
                 if codec != Codec::Ed25519Priv {
@@ -396,14 +396,15 @@ impl AsKnownKeyPair for Secret {
 
                 let curve25519_point_size = 32;
                 let (d_value, x_value) = decoded_value.split_at(curve25519_point_size);
-                let base64_url_d_value = base64::encode_config(&d_value, base64::URL_SAFE_NO_PAD);
+                let base64_url_d_value =
+                    Zeroizing::new(base64::encode_config(&d_value, base64::URL_SAFE_NO_PAD));
                 let base64_url_x_value = base64::encode_config(&x_value, base64::URL_SAFE_NO_PAD);
 
                 let jwk = json!({
                     "kty": "OKP",
                     "crv": "Ed25519",
                     "x": base64_url_x_value,
-                    "d": base64_url_d_value
+                    "d": base64_url_d_value.as_str()
                 });
 
                 Ed25519KeyPair::from_jwk_value(&jwk)
@@ -422,6 +423,71 @@ impl AsKnownKeyPair for Secret {
     }
 }
 
+/// Decodes multibase (https://datatracker.ietf.org/doc/html/draft-multiformats-multibase) value.
+/// Only base58btc (`z` prefix) encoding is supported. Values in other known encodings
+/// produce `Unsupported` error naming the encoding.
+fn _decode_multibase(value: &str, material: &str) -> Result<Vec<u8>> {
+    let prefix = match value.chars().next() {
+        Some(prefix) => prefix,
+        None => Err(err_msg(
+            ErrorKind::Malformed,
+            format!("Empty multibase value in {}", material),
+        ))?,
+    };
+
+    if prefix != 'z' {
+        let err = match _multibase_encoding(prefix) {
+            Some(encoding) => err_msg(
+                ErrorKind::Unsupported,
+                format!(
+                    "Unsupported multibase encoding `{}` in {}, only base58btc is supported",
+                    encoding, material
+                ),
+            ),
+            None => err_msg(
+                ErrorKind::Malformed,
+                format!("Unknown multibase prefix `{}` in {}", prefix, material),
+            ),
+        };
+
+        Err(err)?
+    }
+
+    bs58::decode(&value[1..])
+        .into_vec()
+        .to_didcomm(format!("Wrong multibase value in {}", material))
+}
+
+fn _multibase_encoding(prefix: char) -> Option<&'static str> {
+    let encoding = match prefix {
+        '0' => "base2",
+        '7' => "base8",
+        '9' => "base10",
+        'f' => "base16",
+        'F' => "base16upper",
+        'v' => "base32hex",
+        'V' => "base32hexupper",
+        't' => "base32hexpad",
+        'T' => "base32hexpadupper",
+        'b' => "base32",
+        'B' => "base32upper",
+        'c' => "base32pad",
+        'C' => "base32padupper",
+        'h' => "base32z",
+        'k' => "base36",
+        'K' => "base36upper",
+        'Z' => "base58flickr",
+        'm' => "base64",
+        'M' => "base64pad",
+        'u' => "base64url",
+        'U' => "base64urlpad",
+        'p' => "proquint",
+        _ => return None,
+    };
+
+    Some(encoding)
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Codec {
     X25519Pub,
@@ -440,6 +506,25 @@ impl Codec {
             _ => Err(err_msg(ErrorKind::IllegalArgument, "Unsupported prefix")),
         };
     }
+
+    fn prefix(&self) -> u32 {
+        match self {
+            Codec::X25519Pub => 0xEC,
+            Codec::Ed25519Pub => 0xED,
+            Codec::X25519Priv => 0x1302,
+            Codec::Ed25519Priv => 0x1300,
+        }
+    }
+}
+
+pub(crate) fn _to_multicodec(codec: Codec, value: &[u8]) -> Result<Vec<u8>> {
+    let mut res: Cursor<Vec<u8>> = Cursor::new(Vec::new());
+    res.write_unsigned_varint_32(codec.prefix())
+        .kind(ErrorKind::InvalidState, "Cannot write varint")?;
+
+    let mut res = res.into_inner();
+    res.extend_from_slice(value);
+    Ok(res)
 }
 
 fn _from_multicodec(value: &Vec<u8>) -> Result<(Codec, &[u8])> {
@@ -459,11 +544,13 @@ fn _from_multicodec(value: &Vec<u8>) -> Result<(Codec, &[u8])> {
 
 #[cfg(test)]
 mod tests {
-    use crate::did::{VerificationMaterial, VerificationMethod, VerificationMethodType};
+    use crate::did::{DIDDoc, VerificationMaterial, VerificationMethod, VerificationMethodType};
+    use crate::error::ErrorKind;
     use crate::jwk::FromJwkValue;
     use crate::secrets::{Secret, SecretMaterial, SecretType};
+    use crate::test_vectors::ALICE_DID_DOC;
     use crate::utils::crypto::{AsKnownKeyPair, KnownKeyPair};
-    use crate::utils::did::{did_or_url, is_did};
+    use crate::utils::did::{did_or_url, is_did, key_agreements_not_found};
     use askar_crypto::alg::ed25519::Ed25519KeyPair;
     use askar_crypto::alg::x25519::X25519KeyPair;
     use serde_json::json;
@@ -644,6 +731,41 @@ mod tests {
         assert_eq!(format!("{:?}", actual_key), format!("{:?}", expected_key));
     }
 
+    #[test]
+    fn verification_method_as_key_pair_multibase_unsupported_encoding() {
+        let vm = VerificationMethod {
+            id: "did:example:eve#key-ed25519-1".to_string(),
+            type_: VerificationMethodType::Ed25519VerificationKey2020,
+            controller: "did:example:eve#key-ed25519-1".to_string(),
+            verification_material: (VerificationMaterial::Multibase {
+                // base64url encoding of the key from the test above
+                value: "u7QGjAGEJuS0OOR9Lo91B11PQPfKNKK1pLKw_diKYn9iCYA".to_string(),
+            }),
+        };
+
+        let err = vm.as_key_pair().expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+        assert_eq!(
+            format!("{}", err),
+            "Unsupported crypto or method: Unsupported multibase encoding `base64url` \
+            in verification material, only base58btc is supported"
+        );
+
+        let vm = VerificationMethod {
+            verification_material: (VerificationMaterial::Multibase {
+                value: "?6MkqRYqQiSgvZQdnBytw86Qbs2ZWUkGv22od935YF4s8M7V".to_string(),
+            }),
+            ..vm
+        };
+
+        let err = vm.as_key_pair().expect_err("res is ok");
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+        assert_eq!(
+            format!("{}", err),
+            "Malformed: Unknown multibase prefix `?` in verification material"
+        );
+    }
+
     #[test]
     fn did_or_url_works() {
         let res = did_or_url("did:example:alice");
@@ -663,8 +785,38 @@ mod tests {
     fn is_did_works() {
         assert_eq!(is_did(""), false);
         assert_eq!(is_did("did:example:alice"), true);
-        assert_eq!(is_did("did::"), true); //TODO is this ok?
+        assert_eq!(is_did("did::"), false);
+        assert_eq!(is_did("did:example:alice#key-1"), true);
+        assert_eq!(is_did("did:example:alice#"), false);
         assert_eq!(is_did("example:example:alice"), false);
         assert_eq!(is_did("example:alice"), false);
     }
+
+    #[test]
+    fn key_agreements_not_found_works() {
+        let err = key_agreements_not_found("Sender kid not found in did", &ALICE_DID_DOC);
+        assert_eq!(err.kind(), ErrorKind::DIDUrlNotFound);
+
+        assert_eq!(
+            format!("{}", err),
+            "DID URL not found: Sender kid not found in did. \
+            DID `did:example:alice` is resolved, present key agreements: \
+            did:example:alice#key-x25519-not-in-secrets-1, did:example:alice#key-x25519-1, \
+            did:example:alice#key-p256-1, did:example:alice#key-p521-1"
+        );
+
+        let did_doc = DIDDoc {
+            key_agreements: vec![],
+            ..ALICE_DID_DOC.clone()
+        };
+
+        let err = key_agreements_not_found("No recipient key agreements found", &did_doc);
+        assert_eq!(err.kind(), ErrorKind::DIDUrlNotFound);
+
+        assert_eq!(
+            format!("{}", err),
+            "DID URL not found: No recipient key agreements found. \
+            DID `did:example:alice` is resolved, present key agreements: none"
+        );
+    }
 }
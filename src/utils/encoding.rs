@@ -0,0 +1,5 @@
+/// Calculates size of data encoded as (optionally padded) base64 without decoding:
+/// each 4 base64 chars encode 3 bytes.
+pub(crate) fn base64_decoded_size(base64: &str) -> usize {
+    base64.trim_end_matches('=').len() * 3 / 4
+}
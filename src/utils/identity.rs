@@ -0,0 +1,31 @@
+/// Reference compared by identity (address of the referenced value) instead of by value.
+/// Options holding trait objects (nonce sources, algorithms, body validators) are compared
+/// this way as there is no other meaningful way to compare them. Only data pointers
+/// are compared, as vtable pointers of the same object may differ between codegen units.
+pub(crate) struct ByIdentity<'a, T: ?Sized>(pub(crate) &'a T);
+
+impl<'a, T: ?Sized> PartialEq for ByIdentity<'a, T> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(
+            self.0 as *const T as *const u8,
+            other.0 as *const T as *const u8,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fmt::Debug, sync::Arc};
+
+    use super::ByIdentity;
+
+    #[test]
+    fn by_identity_works() {
+        let value: Arc<dyn Debug> = Arc::new(1);
+        let same_value = value.clone();
+        let equal_value: Arc<dyn Debug> = Arc::new(1);
+
+        assert!(ByIdentity(&*value) == ByIdentity(&*same_value));
+        assert!(ByIdentity(&*value) != ByIdentity(&*equal_value));
+    }
+}
@@ -0,0 +1,125 @@
+use serde::Deserialize;
+
+use crate::error::{err_msg, ErrorKind, Result, ResultExt};
+
+/// Protected header parameters changing how JWS or JWE must be processed,
+/// so they can't be ignored as other unknown parameters are.
+#[derive(Deserialize)]
+struct ProcessingHeaders {
+    /// Extension parameters the recipient must understand (RFC 7515, section 4.1.11).
+    #[serde(default)]
+    crit: Option<Vec<String>>,
+
+    /// Whether JWS payload is base64url encoded (RFC 7797). True if absent.
+    #[serde(default)]
+    b64: Option<bool>,
+}
+
+/// Checks that serialized JWS or JWE protected header lists only `understood` parameters
+/// as critical and doesn't request unencoded payload (`b64` is false).
+///
+/// # Errors
+/// - `Malformed` `crit` or `b64` has invalid value or `crit` is empty.
+/// - `Unsupported` `crit` lists parameter that isn't understood or `b64` is false.
+pub(crate) fn check_processing_headers(protected: &[u8], understood: &[&str]) -> Result<()> {
+    let headers: ProcessingHeaders = serde_json::from_slice(protected)
+        .kind(ErrorKind::Malformed, "Unable parse protected header")?;
+
+    if let Some(crit) = headers.crit {
+        if crit.is_empty() {
+            Err(err_msg(
+                ErrorKind::Malformed,
+                "Critical header parameters list is empty",
+            ))?
+        }
+
+        if let Some(header) = crit.iter().find(|h| !understood.contains(&h.as_str())) {
+            Err(err_msg(
+                ErrorKind::Unsupported,
+                format!("Unsupported critical header parameter `{}`", header),
+            ))?
+        }
+    }
+
+    if headers.b64 == Some(false) {
+        Err(err_msg(
+            ErrorKind::Unsupported,
+            "Unencoded payload (`b64` is false) is unsupported",
+        ))?
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::error::ErrorKind;
+
+    use super::check_processing_headers;
+
+    #[test]
+    fn check_processing_headers_works() {
+        for protected in [
+            json!({"alg": "EdDSA"}),
+            json!({"alg": "EdDSA", "b64": true}),
+            json!({"alg": "EdDSA", "b64": true, "crit": ["b64"]}),
+        ] {
+            check_processing_headers(protected.to_string().as_bytes(), &["b64"])
+                .expect("check is ok.");
+        }
+    }
+
+    #[test]
+    fn check_processing_headers_works_unsupported() {
+        for (protected, understood, exp_err_kind, exp_err_msg) in [
+            (
+                json!({"alg": "EdDSA", "crit": ["exp"], "exp": 1}),
+                &["b64"][..],
+                ErrorKind::Unsupported,
+                "Unsupported crypto or method: Unsupported critical header parameter `exp`",
+            ),
+            (
+                json!({"alg": "EdDSA", "b64": true, "crit": ["b64"]}),
+                &[][..],
+                ErrorKind::Unsupported,
+                "Unsupported crypto or method: Unsupported critical header parameter `b64`",
+            ),
+            (
+                json!({"alg": "EdDSA", "b64": false}),
+                &["b64"][..],
+                ErrorKind::Unsupported,
+                "Unsupported crypto or method: Unencoded payload (`b64` is false) is unsupported",
+            ),
+            (
+                json!({"alg": "EdDSA", "b64": false, "crit": ["b64"]}),
+                &["b64"][..],
+                ErrorKind::Unsupported,
+                "Unsupported crypto or method: Unencoded payload (`b64` is false) is unsupported",
+            ),
+            (
+                json!({"alg": "EdDSA", "crit": []}),
+                &["b64"][..],
+                ErrorKind::Malformed,
+                "Malformed: Critical header parameters list is empty",
+            ),
+        ] {
+            let err = check_processing_headers(protected.to_string().as_bytes(), understood)
+                .expect_err("res is ok");
+
+            assert_eq!(err.kind(), exp_err_kind);
+            assert_eq!(format!("{}", err), exp_err_msg);
+        }
+
+        for protected in [
+            json!({"alg": "EdDSA", "crit": "exp"}),
+            json!({"alg": "EdDSA", "b64": "false"}),
+        ] {
+            let err = check_processing_headers(protected.to_string().as_bytes(), &["b64"])
+                .expect_err("res is ok");
+
+            assert_eq!(err.kind(), ErrorKind::Malformed);
+        }
+    }
+}
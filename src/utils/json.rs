@@ -0,0 +1,244 @@
+use serde_json::{Number, Value};
+
+use crate::error::{err_msg, ErrorKind, Result, ResultExt};
+
+/// Serializes JSON value according to JSON Canonicalization Scheme (JCS, RFC 8785):
+/// without whitespaces, object keys sorted by their UTF-16 code units and numbers
+/// serialized as ECMAScript (ES6) does for IEEE 754 double precision values.
+/// Output doesn't depend on the order keys were inserted or on serde_json features.
+pub(crate) fn to_canonical_string(value: &Value) -> Result<String> {
+    let mut res = String::new();
+    _write_canonical(value, &mut res)?;
+    Ok(res)
+}
+
+fn _write_canonical(value: &Value, res: &mut String) -> Result<()> {
+    match value {
+        Value::Array(values) => {
+            res.push('[');
+
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    res.push(',');
+                }
+
+                _write_canonical(value, res)?;
+            }
+
+            res.push(']');
+        }
+        Value::Object(values) => {
+            let mut keys: Vec<_> = values.keys().collect();
+            keys.sort_by(|a, b| a.encode_utf16().cmp(b.encode_utf16()));
+
+            res.push('{');
+
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    res.push(',');
+                }
+
+                // serde_json escapes strings the same way as JCS: only `"`, `\`
+                // and control characters, the last ones as `\b`, `\f`, `\n`, `\r`, `\t`
+                // or lowercase `\u00xx`
+                res.push_str(
+                    &serde_json::to_string(key)
+                        .kind(ErrorKind::InvalidState, "Unable serialize json key")?,
+                );
+
+                res.push(':');
+                _write_canonical(&values[key], res)?;
+            }
+
+            res.push('}');
+        }
+        Value::Number(number) => res.push_str(&_canonical_number(number)?),
+        value => res.push_str(
+            &serde_json::to_string(value)
+                .kind(ErrorKind::InvalidState, "Unable serialize json value")?,
+        ),
+    }
+
+    Ok(())
+}
+
+/// Serializes number as ECMAScript `Number.prototype.toString` does
+/// (https://262.ecma-international.org/6.0/#sec-tostring-applied-to-the-number-type).
+/// Integers not representable as IEEE 754 double are rounded as JCS requires.
+fn _canonical_number(number: &Number) -> Result<String> {
+    let value = number
+        .as_f64()
+        .filter(|value| value.is_finite())
+        .ok_or_else(|| err_msg(ErrorKind::Malformed, "Number isn't a finite double"))?;
+
+    if value == 0.0 {
+        // Also covers negative zero
+        return Ok("0".to_owned());
+    }
+
+    if value < 0.0 {
+        return Ok(format!("-{}", _canonical_positive_number(-value)));
+    }
+
+    Ok(_canonical_positive_number(value))
+}
+
+fn _canonical_positive_number(value: f64) -> String {
+    // Rust produces the shortest digits that round trip to the same double as ECMAScript
+    // does, but may resolve ties differently. Formatting the exact value with the same
+    // precision picks the closest digits and resolves ties to even as ECMAScript requires.
+    let shortest = format!("{:e}", value);
+    let precision = shortest.find('e').unwrap_or(0).saturating_sub(2);
+    let scientific = format!("{:.*e}", precision, value);
+    let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap_or(0));
+
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let exponent: i32 = exponent[1..].parse().unwrap_or(0);
+
+    // ECMAScript notation: value = digits * 10^(n - k)
+    let k = digits.len() as i32;
+    let n = exponent + 1;
+
+    if k <= n && n <= 21 {
+        format!("{}{}", digits, "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        format!("{}.{}", &digits[..n as usize], &digits[n as usize..])
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{}", "0".repeat(-n as usize), digits)
+    } else {
+        let sign = if n - 1 < 0 { '-' } else { '+' };
+
+        match k {
+            1 => format!("{}e{}{}", digits, sign, (n - 1).abs()),
+            _ => format!(
+                "{}.{}e{}{}",
+                &digits[..1],
+                &digits[1..],
+                sign,
+                (n - 1).abs()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn to_canonical_string_works() {
+        let value = json!({
+            "z": 1,
+            "a": [{"c": "x", "b": null}, true, 1.5],
+            "m": {"y": "\"q\"", "x": {}},
+        });
+
+        assert_eq!(
+            to_canonical_string(&value).expect("serialize is ok."),
+            r#"{"a":[{"b":null,"c":"x"},true,1.5],"m":{"x":{},"y":"\"q\""},"z":1}"#
+        );
+    }
+
+    // RFC 8785, section 3.2.2
+    #[test]
+    fn to_canonical_string_works_rfc8785_example() {
+        let value: Value = serde_json::from_str(
+            r#"{
+                "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+                "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+                "literals": [null, true, false]
+            }"#,
+        )
+        .expect("parse is ok.");
+
+        assert_eq!(
+            to_canonical_string(&value).expect("serialize is ok."),
+            r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+        );
+    }
+
+    // RFC 8785, section 3.2.3
+    #[test]
+    fn to_canonical_string_works_rfc8785_sorting() {
+        let value: Value = serde_json::from_str(
+            r#"{
+                "\u20ac": "Euro Sign",
+                "\r": "Carriage Return",
+                "\ufb33": "Hebrew Letter Dalet With Dagesh",
+                "1": "One",
+                "\ud83d\ude00": "Emoji: Grinning Face",
+                "\u0080": "Control",
+                "\u00f6": "Latin Small Letter O With Diaeresis"
+            }"#,
+        )
+        .expect("parse is ok.");
+
+        // Emoji is sorted before U+FB33 as its UTF-16 surrogate pair starts with 0xD83D
+        assert_eq!(
+            to_canonical_string(&value).expect("serialize is ok."),
+            "{\"\\r\":\"Carriage Return\",\
+             \"1\":\"One\",\
+             \"\u{80}\":\"Control\",\
+             \"\u{f6}\":\"Latin Small Letter O With Diaeresis\",\
+             \"\u{20ac}\":\"Euro Sign\",\
+             \"\u{1f600}\":\"Emoji: Grinning Face\",\
+             \"\u{fb33}\":\"Hebrew Letter Dalet With Dagesh\"}"
+        );
+    }
+
+    // RFC 8785, appendix B
+    #[test]
+    fn to_canonical_string_works_rfc8785_numbers() {
+        for (bits, expected) in [
+            (0x0000000000000000u64, "0"),
+            (0x8000000000000000, "0"),
+            (0x0000000000000001, "5e-324"),
+            (0x8000000000000001, "-5e-324"),
+            (0x7fefffffffffffff, "1.7976931348623157e+308"),
+            (0xffefffffffffffff, "-1.7976931348623157e+308"),
+            (0x4340000000000000, "9007199254740992"),
+            (0xc340000000000000, "-9007199254740992"),
+            (0x4430000000000000, "295147905179352830000"),
+            (0x44b52d02c7e14af5, "9.999999999999997e+22"),
+            (0x44b52d02c7e14af6, "1e+23"),
+            (0x44b52d02c7e14af7, "1.0000000000000001e+23"),
+            (0x444b1ae4d6e2ef4e, "999999999999999700000"),
+            (0x444b1ae4d6e2ef4f, "999999999999999900000"),
+            (0x444b1ae4d6e2ef50, "1e+21"),
+            (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+            (0x3eb0c6f7a0b5ed8d, "0.000001"),
+            (0x41b3de4355555553, "333333333.3333332"),
+            (0x41b3de4355555554, "333333333.33333325"),
+            (0x41b3de4355555555, "333333333.3333333"),
+            (0x41b3de4355555556, "333333333.3333334"),
+            (0x41b3de4355555557, "333333333.33333343"),
+            (0xbecbf647612f3696, "-0.0000033333333333333333"),
+            (0x43143ff3c1cb0959, "1424953923781206.2"),
+        ] {
+            let value = json!(f64::from_bits(bits));
+
+            assert_eq!(
+                to_canonical_string(&value).expect("serialize is ok."),
+                expected,
+                "bits {:x}",
+                bits
+            );
+        }
+    }
+
+    #[test]
+    fn to_canonical_string_works_large_integers() {
+        // Integers not representable as double are rounded as ECMAScript does
+        assert_eq!(
+            to_canonical_string(&json!(u64::MAX)).expect("serialize is ok."),
+            "18446744073709552000"
+        );
+
+        assert_eq!(
+            to_canonical_string(&json!(-9007199254740993i64)).expect("serialize is ok."),
+            "-9007199254740992"
+        );
+    }
+}
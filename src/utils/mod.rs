@@ -1,3 +1,7 @@
 pub(crate) mod crypto;
 pub(crate) mod did;
+pub(crate) mod encoding;
+pub(crate) mod identity;
+pub(crate) mod jose;
+pub(crate) mod json;
 pub(crate) mod serde;
@@ -1,3 +1,31 @@
+use std::time::Duration;
+
+use serde::{de::Error, Deserialize, Deserializer};
+use serde_json::Value;
+
 pub(crate) fn _true() -> bool {
     true
 }
+
+/// Deserializes optional duration from milliseconds.
+pub(crate) fn _duration_ms<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<u64>::deserialize(deserializer)?.map(Duration::from_millis))
+}
+
+/// Deserializes optional `u64` through `Value`. Unlike derived deserialization it also accepts
+/// numbers buffered for `#[serde(flatten)]` structs if `arbitrary-precision` feature is enabled.
+pub(crate) fn _optional_u64<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| D::Error::custom(format!("invalid value: {}, expected u64", value))),
+    }
+}
@@ -1,17 +1,54 @@
 use std::{
     cell::RefCell,
     cmp,
+    future::Future,
     sync::{Arc, Mutex},
 };
 
 use crate::UniffiCustomTypeWrapper;
 use didcomm_core::error::{err_msg, ErrorKind, Result, ResultExt, ResultExtNoContext, ToResult};
-use futures::{channel::oneshot, executor::ThreadPool};
+use futures::{
+    channel::oneshot,
+    executor::ThreadPool,
+    future::{abortable, AbortHandle},
+};
 use lazy_static::lazy_static;
 
+/// Stable integer codes of operation results passed to foreign callbacks.
+/// New codes are only appended, so existing values never change.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum ErrorCode {
     Success = 0,
     Error = 1,
+    DIDNotResolved = 2,
+    DIDUrlNotFound = 3,
+    SecretNotFound = 4,
+    Malformed = 5,
+    IoError = 6,
+    InvalidState = 7,
+    NoCompatibleCrypto = 8,
+    Unsupported = 9,
+    IllegalArgument = 10,
+    Untrusted = 11,
+    TooLarge = 12,
+}
+
+impl From<ErrorKind> for ErrorCode {
+    fn from(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::DIDNotResolved => ErrorCode::DIDNotResolved,
+            ErrorKind::DIDUrlNotFound => ErrorCode::DIDUrlNotFound,
+            ErrorKind::SecretNotFound => ErrorCode::SecretNotFound,
+            ErrorKind::Malformed => ErrorCode::Malformed,
+            ErrorKind::IoError => ErrorCode::IoError,
+            ErrorKind::InvalidState => ErrorCode::InvalidState,
+            ErrorKind::NoCompatibleCrypto => ErrorCode::NoCompatibleCrypto,
+            ErrorKind::Unsupported => ErrorCode::Unsupported,
+            ErrorKind::IllegalArgument => ErrorCode::IllegalArgument,
+            ErrorKind::Untrusted => ErrorCode::Untrusted,
+            ErrorKind::TooLarge => ErrorCode::TooLarge,
+        }
+    }
 }
 
 lazy_static! {
@@ -22,6 +59,48 @@ lazy_static! {
         .unwrap();
 }
 
+/// Handle of an operation spawned on the executor.
+/// Cancelled operation is aborted, so its callback is never invoked if it hasn't been invoked yet.
+/// Dropping the handle cancels the operation as well, so the handle must be kept
+/// until the callback is invoked.
+pub struct CancelHandle(AbortHandle);
+
+impl CancelHandle {
+    /// Cancels the operation. Does nothing if the operation is already completed.
+    pub fn cancel(&self) {
+        self.0.abort();
+    }
+}
+
+impl Drop for CancelHandle {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Spawns the operation on the executor. The operation can't be cancelled.
+pub(crate) fn spawn<F>(future: F) -> ErrorCode
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    EXECUTOR.spawn_ok(future);
+    ErrorCode::Success
+}
+
+/// Spawns the operation on the executor returning handle to cancel it.
+pub(crate) fn spawn_cancellable<F>(future: F) -> Arc<CancelHandle>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let (future, abort_handle) = abortable(future);
+
+    EXECUTOR.spawn_ok(async move {
+        let _ = future.await;
+    });
+
+    Arc::new(CancelHandle(abort_handle))
+}
+
 // We use `JsonValue` in our UDL. It moves to and from Uniffi bindings via a string.
 pub type JsonValue = serde_json::Value;
 
@@ -87,3 +166,37 @@ impl<T> OnResult<T> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use didcomm_core::error::ErrorKind;
+
+    use super::ErrorCode;
+
+    #[test]
+    fn error_code_from_error_kind_works() {
+        for (kind, code, value) in [
+            (ErrorKind::DIDNotResolved, ErrorCode::DIDNotResolved, 2),
+            (ErrorKind::DIDUrlNotFound, ErrorCode::DIDUrlNotFound, 3),
+            (ErrorKind::SecretNotFound, ErrorCode::SecretNotFound, 4),
+            (ErrorKind::Malformed, ErrorCode::Malformed, 5),
+            (ErrorKind::IoError, ErrorCode::IoError, 6),
+            (ErrorKind::InvalidState, ErrorCode::InvalidState, 7),
+            (
+                ErrorKind::NoCompatibleCrypto,
+                ErrorCode::NoCompatibleCrypto,
+                8,
+            ),
+            (ErrorKind::Unsupported, ErrorCode::Unsupported, 9),
+            (ErrorKind::IllegalArgument, ErrorCode::IllegalArgument, 10),
+            (ErrorKind::Untrusted, ErrorCode::Untrusted, 11),
+            (ErrorKind::TooLarge, ErrorCode::TooLarge, 12),
+        ] {
+            assert_eq!(ErrorCode::from(kind), code);
+            assert_eq!(code as i32, value);
+        }
+
+        assert_eq!(ErrorCode::Success as i32, 0);
+        assert_eq!(ErrorCode::Error as i32, 1);
+    }
+}
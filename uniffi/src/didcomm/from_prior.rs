@@ -1,19 +1,22 @@
+use std::future::Future;
+use std::sync::Arc;
+
 use didcomm_core::{error::ErrorKind, FromPrior};
 
 use crate::DIDComm;
 
-use crate::common::{ErrorCode, EXECUTOR};
+use crate::common::{spawn, spawn_cancellable, CancelHandle, ErrorCode};
 use crate::did_resolver_adapter::DIDResolverAdapter;
 use crate::secrets_resolver_adapter::SecretsResolverAdapter;
 
 pub trait OnFromPriorPackResult: Sync + Send {
     fn success(&self, from_prior_jwt: String, kid: String);
-    fn error(&self, err: ErrorKind, err_msg: String);
+    fn error(&self, err: ErrorKind, err_msg: String, code: ErrorCode);
 }
 
 pub trait OnFromPriorUnpackResult: Sync + Send {
     fn success(&self, from_prior: FromPrior, kid: String);
-    fn error(&self, err: ErrorKind, err_msg: String);
+    fn error(&self, err: ErrorKind, err_msg: String, code: ErrorCode);
 }
 
 impl DIDComm {
@@ -23,6 +26,44 @@ impl DIDComm {
         issuer_kid: Option<String>,
         cb: Box<dyn OnFromPriorPackResult>,
     ) -> ErrorCode {
+        spawn(self._pack_from_prior(msg, issuer_kid, cb))
+    }
+
+    /// Same as `pack_from_prior`, but returns handle cancelling the operation
+    /// when `cancel` is called or the handle is dropped.
+    pub fn pack_from_prior_cancellable(
+        &self,
+        msg: &FromPrior,
+        issuer_kid: Option<String>,
+        cb: Box<dyn OnFromPriorPackResult>,
+    ) -> Arc<CancelHandle> {
+        spawn_cancellable(self._pack_from_prior(msg, issuer_kid, cb))
+    }
+
+    pub fn unpack_from_prior(
+        &self,
+        from_prior_jwt: String,
+        cb: Box<dyn OnFromPriorUnpackResult>,
+    ) -> ErrorCode {
+        spawn(self._unpack_from_prior(from_prior_jwt, cb))
+    }
+
+    /// Same as `unpack_from_prior`, but returns handle cancelling the operation
+    /// when `cancel` is called or the handle is dropped.
+    pub fn unpack_from_prior_cancellable(
+        &self,
+        from_prior_jwt: String,
+        cb: Box<dyn OnFromPriorUnpackResult>,
+    ) -> Arc<CancelHandle> {
+        spawn_cancellable(self._unpack_from_prior(from_prior_jwt, cb))
+    }
+
+    fn _pack_from_prior(
+        &self,
+        msg: &FromPrior,
+        issuer_kid: Option<String>,
+        cb: Box<dyn OnFromPriorPackResult>,
+    ) -> impl Future<Output = ()> + Send + 'static {
         let msg = msg.clone();
         let did_resolver = DIDResolverAdapter::new(self.did_resolver.clone());
         let secret_resolver = SecretsResolverAdapter::new(self.secret_resolver.clone());
@@ -31,32 +72,28 @@ impl DIDComm {
             msg.pack(issuer_kid.as_deref(), &did_resolver, &secret_resolver)
                 .await
         };
-        EXECUTOR.spawn_ok(async move {
+        async move {
             match future.await {
                 Ok((from_prior_jwt, kid)) => cb.success(from_prior_jwt, kid),
-                Err(err) => cb.error(err.kind(), err.to_string()),
+                Err(err) => cb.error(err.kind(), err.to_string(), err.kind().into()),
             }
-        });
-
-        ErrorCode::Success
+        }
     }
 
-    pub fn unpack_from_prior(
+    fn _unpack_from_prior(
         &self,
         from_prior_jwt: String,
         cb: Box<dyn OnFromPriorUnpackResult>,
-    ) -> ErrorCode {
+    ) -> impl Future<Output = ()> + Send + 'static {
         let did_resolver = DIDResolverAdapter::new(self.did_resolver.clone());
 
         let future = async move { FromPrior::unpack(&from_prior_jwt, &did_resolver).await };
-        EXECUTOR.spawn_ok(async move {
+        async move {
             match future.await {
                 Ok((from_prior_jwt, kid)) => cb.success(from_prior_jwt, kid),
-                Err(err) => cb.error(err.kind(), err.to_string()),
+                Err(err) => cb.error(err.kind(), err.to_string(), err.kind().into()),
             }
-        });
-
-        ErrorCode::Success
+        }
     }
 }
 
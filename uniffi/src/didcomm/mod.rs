@@ -6,11 +6,11 @@ mod protocols;
 mod unpack;
 
 pub use from_prior::{OnFromPriorPackResult, OnFromPriorUnpackResult};
-pub use pack_encrypted::OnPackEncryptedResult;
+pub use pack_encrypted::{OnPackEncryptedResult, PackEncryptedOptions};
 pub use pack_plaintext::OnPackPlaintextResult;
 pub use pack_signed::OnPackSignedResult;
-pub use protocols::routing::OnWrapInForwardResult;
-pub use unpack::OnUnpackResult;
+pub use protocols::routing::{OnWrapInForwardResult, WrapInForwardOptions};
+pub use unpack::{OnUnpackResult, UnpackOptions};
 
 use std::sync::Arc;
 
@@ -1,28 +1,140 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use didcomm_core::algorithms::{AnonCryptAlg, AuthCryptAlg};
 use didcomm_core::error::ErrorKind;
-use didcomm_core::{Message, PackEncryptedMetadata, PackEncryptedOptions};
+use didcomm_core::{FromPriorSpec, Message, PackEncryptedMetadata};
 
-use crate::common::{ErrorCode, EXECUTOR};
+use crate::common::{spawn, spawn_cancellable, CancelHandle, ErrorCode};
 use crate::did_resolver_adapter::DIDResolverAdapter;
 use crate::secrets::secrets_resolver_adapter::SecretsResolverAdapter;
-use crate::DIDComm;
+use crate::{DIDComm, JsonValue};
+
+/// FFI counterpart of `didcomm_core::PackEncryptedOptions`.
+/// Custom crypto hooks (`nonce_source`, `session_key_provider`) can't cross FFI boundary,
+/// so they are omitted and defaults are used. See `didcomm_core::PackEncryptedOptions`
+/// for fields description.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PackEncryptedOptions {
+    pub protect_sender: bool,
+    pub forward: bool,
+    pub forward_headers: Option<HashMap<String, JsonValue>>,
+    pub forward_from: Option<String>,
+    pub messaging_service: Option<String>,
+    pub enc_alg_auth: AuthCryptAlg,
+    pub enc_alg_anon: AnonCryptAlg,
+    pub sign_outside: bool,
+    pub from_prior: Option<FromPriorSpec>,
+    pub resolver_timeout: Option<Duration>,
+    pub strict_did_urls: bool,
+    pub aad: Option<String>,
+    pub reject_key_reuse: bool,
+    pub preserve_recipient_kids_order: bool,
+    pub recipient_kid_prefix: Option<String>,
+    pub content_hash: bool,
+    pub flattened_jwe: bool,
+}
+
+impl Default for PackEncryptedOptions {
+    fn default() -> Self {
+        didcomm_core::PackEncryptedOptions::default().into()
+    }
+}
+
+impl From<didcomm_core::PackEncryptedOptions> for PackEncryptedOptions {
+    fn from(options: didcomm_core::PackEncryptedOptions) -> Self {
+        PackEncryptedOptions {
+            protect_sender: options.protect_sender,
+            forward: options.forward,
+            forward_headers: options.forward_headers,
+            forward_from: options.forward_from,
+            messaging_service: options.messaging_service,
+            enc_alg_auth: options.enc_alg_auth,
+            enc_alg_anon: options.enc_alg_anon,
+            sign_outside: options.sign_outside,
+            from_prior: options.from_prior,
+            resolver_timeout: options.resolver_timeout,
+            strict_did_urls: options.strict_did_urls,
+            aad: options.aad,
+            reject_key_reuse: options.reject_key_reuse,
+            preserve_recipient_kids_order: options.preserve_recipient_kids_order,
+            recipient_kid_prefix: options.recipient_kid_prefix,
+            content_hash: options.content_hash,
+            flattened_jwe: options.flattened_jwe,
+        }
+    }
+}
+
+impl From<PackEncryptedOptions> for didcomm_core::PackEncryptedOptions {
+    fn from(options: PackEncryptedOptions) -> Self {
+        didcomm_core::PackEncryptedOptions {
+            protect_sender: options.protect_sender,
+            forward: options.forward,
+            forward_headers: options.forward_headers,
+            forward_from: options.forward_from,
+            messaging_service: options.messaging_service,
+            enc_alg_auth: options.enc_alg_auth,
+            enc_alg_anon: options.enc_alg_anon,
+            sign_outside: options.sign_outside,
+            from_prior: options.from_prior,
+            resolver_timeout: options.resolver_timeout,
+            strict_did_urls: options.strict_did_urls,
+            aad: options.aad,
+            reject_key_reuse: options.reject_key_reuse,
+            preserve_recipient_kids_order: options.preserve_recipient_kids_order,
+            recipient_kid_prefix: options.recipient_kid_prefix,
+            content_hash: options.content_hash,
+            flattened_jwe: options.flattened_jwe,
+            ..didcomm_core::PackEncryptedOptions::default()
+        }
+    }
+}
 
 pub trait OnPackEncryptedResult: Sync + Send {
     fn success(&self, result: String, metadata: PackEncryptedMetadata);
-    fn error(&self, err: ErrorKind, err_msg: String);
+    fn error(&self, err: ErrorKind, err_msg: String, code: ErrorCode);
 }
 
 impl DIDComm {
-    pub fn pack_encrypted<'a, 'b>(
+    pub fn pack_encrypted(
         &self,
-        msg: &'a Message,
+        msg: &Message,
         to: String,
         from: Option<String>,
         sign_by: Option<String>,
-        options: &'b PackEncryptedOptions,
+        options: &PackEncryptedOptions,
         cb: Box<dyn OnPackEncryptedResult>,
     ) -> ErrorCode {
+        spawn(self._pack_encrypted(msg, to, from, sign_by, options, cb))
+    }
+
+    /// Same as `pack_encrypted`, but returns handle cancelling the operation
+    /// when `cancel` is called or the handle is dropped.
+    pub fn pack_encrypted_cancellable(
+        &self,
+        msg: &Message,
+        to: String,
+        from: Option<String>,
+        sign_by: Option<String>,
+        options: &PackEncryptedOptions,
+        cb: Box<dyn OnPackEncryptedResult>,
+    ) -> Arc<CancelHandle> {
+        spawn_cancellable(self._pack_encrypted(msg, to, from, sign_by, options, cb))
+    }
+
+    fn _pack_encrypted(
+        &self,
+        msg: &Message,
+        to: String,
+        from: Option<String>,
+        sign_by: Option<String>,
+        options: &PackEncryptedOptions,
+        cb: Box<dyn OnPackEncryptedResult>,
+    ) -> impl Future<Output = ()> + Send + 'static {
         let msg = msg.clone();
-        let options = options.clone();
+        let options: didcomm_core::PackEncryptedOptions = options.clone().into();
         let did_resolver = DIDResolverAdapter::new(self.did_resolver.clone());
         let secret_resolver = SecretsResolverAdapter::new(self.secret_resolver.clone());
 
@@ -37,14 +149,12 @@ impl DIDComm {
             )
             .await
         };
-        EXECUTOR.spawn_ok(async move {
+        async move {
             match future.await {
                 Ok((result, metadata)) => cb.success(result, metadata),
-                Err(err) => cb.error(err.kind(), err.to_string()),
+                Err(err) => cb.error(err.kind(), err.to_string(), err.kind().into()),
             }
-        });
-
-        ErrorCode::Success
+        }
     }
 }
 
@@ -53,10 +163,10 @@ mod tests {
     use crate::test_helper::{
         create_did_resolver, create_secrets_resolver, get_error, get_ok, PackResult,
     };
-    use crate::DIDComm;
+    use crate::{DIDComm, PackEncryptedOptions};
     use didcomm_core::error::ErrorKind;
     use didcomm_core::test_vectors::{ALICE_DID, BOB_DID, MESSAGE_SIMPLE};
-    use didcomm_core::{Message, PackEncryptedOptions};
+    use didcomm_core::Message;
     use serde_json::json;
 
     #[tokio::test]
@@ -1,30 +1,49 @@
+use std::future::Future;
+use std::sync::Arc;
+
 use didcomm_core::error::ErrorKind;
 use didcomm_core::Message;
 
-use crate::common::{ErrorCode, EXECUTOR};
+use crate::common::{spawn, spawn_cancellable, CancelHandle, ErrorCode};
 use crate::did_resolver_adapter::DIDResolverAdapter;
 use crate::DIDComm;
 
 pub trait OnPackPlaintextResult: Sync + Send {
     fn success(&self, result: String);
-    fn error(&self, err: ErrorKind, err_msg: String);
+    fn error(&self, err: ErrorKind, err_msg: String, code: ErrorCode);
 }
 
 impl DIDComm {
     pub fn pack_plaintext(&self, msg: &Message, cb: Box<dyn OnPackPlaintextResult>) -> ErrorCode {
+        spawn(self._pack_plaintext(msg, cb))
+    }
+
+    /// Same as `pack_plaintext`, but returns handle cancelling the operation
+    /// when `cancel` is called or the handle is dropped.
+    pub fn pack_plaintext_cancellable(
+        &self,
+        msg: &Message,
+        cb: Box<dyn OnPackPlaintextResult>,
+    ) -> Arc<CancelHandle> {
+        spawn_cancellable(self._pack_plaintext(msg, cb))
+    }
+
+    fn _pack_plaintext(
+        &self,
+        msg: &Message,
+        cb: Box<dyn OnPackPlaintextResult>,
+    ) -> impl Future<Output = ()> + Send + 'static {
         let msg = msg.clone();
         let did_resolver = DIDResolverAdapter::new(self.did_resolver.clone());
 
         let future = async move { msg.pack_plaintext(&did_resolver).await };
 
-        EXECUTOR.spawn_ok(async move {
+        async move {
             match future.await {
                 Ok(result) => cb.success(result),
-                Err(err) => cb.error(err.kind(), err.to_string()),
+                Err(err) => cb.error(err.kind(), err.to_string(), err.kind().into()),
             }
-        });
-
-        ErrorCode::Success
+        }
     }
 }
 
@@ -1,14 +1,17 @@
+use std::future::Future;
+use std::sync::Arc;
+
 use didcomm_core::Message;
 use didcomm_core::{error::ErrorKind, PackSignedMetadata};
 
-use crate::common::{ErrorCode, EXECUTOR};
+use crate::common::{spawn, spawn_cancellable, CancelHandle, ErrorCode};
 use crate::did_resolver_adapter::DIDResolverAdapter;
 use crate::secrets::secrets_resolver_adapter::SecretsResolverAdapter;
 use crate::DIDComm;
 
 pub trait OnPackSignedResult: Sync + Send {
     fn success(&self, result: String, metadata: PackSignedMetadata);
-    fn error(&self, err: ErrorKind, err_msg: String);
+    fn error(&self, err: ErrorKind, err_msg: String, code: ErrorCode);
 }
 
 impl DIDComm {
@@ -18,6 +21,26 @@ impl DIDComm {
         sign_by: String,
         cb: Box<dyn OnPackSignedResult>,
     ) -> ErrorCode {
+        spawn(self._pack_signed(msg, sign_by, cb))
+    }
+
+    /// Same as `pack_signed`, but returns handle cancelling the operation
+    /// when `cancel` is called or the handle is dropped.
+    pub fn pack_signed_cancellable(
+        &self,
+        msg: &Message,
+        sign_by: String,
+        cb: Box<dyn OnPackSignedResult>,
+    ) -> Arc<CancelHandle> {
+        spawn_cancellable(self._pack_signed(msg, sign_by, cb))
+    }
+
+    fn _pack_signed(
+        &self,
+        msg: &Message,
+        sign_by: String,
+        cb: Box<dyn OnPackSignedResult>,
+    ) -> impl Future<Output = ()> + Send + 'static {
         let msg = msg.clone();
         let did_resolver = DIDResolverAdapter::new(self.did_resolver.clone());
         let secret_resolver = SecretsResolverAdapter::new(self.secret_resolver.clone());
@@ -27,29 +50,31 @@ impl DIDComm {
                 .await
         };
 
-        EXECUTOR.spawn_ok(async move {
+        async move {
             match future.await {
                 Ok((result, metadata)) => cb.success(result, metadata),
-                Err(err) => cb.error(err.kind(), err.to_string()),
+                Err(err) => cb.error(err.kind(), err.to_string(), err.kind().into()),
             }
-        });
-
-        ErrorCode::Success
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Mutex};
+
     use didcomm_core::error::ErrorKind;
     use didcomm_core::Message;
+    use futures::channel::oneshot;
     use serde_json::json;
 
     use crate::test_helper::{
-        create_did_resolver, create_secrets_resolver, get_error, get_ok, PackResult,
+        create_did_resolver, create_secrets_resolver, get_error, get_ok, ErrorCodeResult,
+        PackResult,
     };
-    use crate::DIDComm;
+    use crate::{DIDComm, DIDResolver, ErrorCode, OnDIDResolverResult};
 
-    use didcomm_core::test_vectors::{ALICE_DID, MESSAGE_SIMPLE};
+    use didcomm_core::test_vectors::{ALICE_DID, ALICE_DID_DOC, MESSAGE_SIMPLE};
 
     #[tokio::test]
     async fn pack_signed_works() {
@@ -129,4 +154,88 @@ mod tests {
         let res = get_error(receiver).await;
         assert_eq!(res.kind(), ErrorKind::IllegalArgument);
     }
+
+    #[tokio::test]
+    async fn pack_signed_works_error_code() {
+        let didcomm = DIDComm::new(create_did_resolver(), create_secrets_resolver());
+
+        for (sign_by, code) in [
+            (ALICE_DID.to_owned(), ErrorCode::Success),
+            ("not-a-did".to_owned(), ErrorCode::IllegalArgument),
+            (
+                format!("{}#key-not-in-secrets-1", ALICE_DID),
+                ErrorCode::SecretNotFound,
+            ),
+            ("did:unknown:alice".to_owned(), ErrorCode::DIDNotResolved),
+        ] {
+            let (cb, receiver) = ErrorCodeResult::new();
+            didcomm.pack_signed(&MESSAGE_SIMPLE, sign_by, cb);
+            assert_eq!(get_ok(receiver).await, code);
+        }
+    }
+
+    #[tokio::test]
+    async fn pack_signed_works_cancelled() {
+        let (resolve_sender, resolve_receiver) = oneshot::channel();
+        let did_resolver = DeferredDIDResolver(Mutex::new(Some(resolve_sender)));
+
+        let (cb, receiver) = PackResult::new();
+
+        let handle = DIDComm::new(Box::new(did_resolver), create_secrets_resolver())
+            .pack_signed_cancellable(&MESSAGE_SIMPLE, String::from(ALICE_DID), cb);
+
+        // Operation is suspended on signer DID resolving
+        let resolve_cb = resolve_receiver.await.expect("Unable receive resolve");
+
+        handle.cancel();
+        let _ = resolve_cb.success(Some(ALICE_DID_DOC.clone()));
+
+        // Callback is dropped without invocation
+        assert!(receiver.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn pack_signed_works_cancelled_on_drop() {
+        let (resolve_sender, resolve_receiver) = oneshot::channel();
+        let did_resolver = DeferredDIDResolver(Mutex::new(Some(resolve_sender)));
+
+        let (cb, receiver) = PackResult::new();
+
+        let handle = DIDComm::new(Box::new(did_resolver), create_secrets_resolver())
+            .pack_signed_cancellable(&MESSAGE_SIMPLE, String::from(ALICE_DID), cb);
+
+        // Operation is suspended on signer DID resolving
+        let resolve_cb = resolve_receiver.await.expect("Unable receive resolve");
+
+        drop(handle);
+        let _ = resolve_cb.success(Some(ALICE_DID_DOC.clone()));
+
+        // Callback is dropped without invocation
+        assert!(receiver.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn pack_signed_works_cancellable() {
+        let (cb, receiver) = PackResult::new();
+
+        let handle = DIDComm::new(create_did_resolver(), create_secrets_resolver())
+            .pack_signed_cancellable(&MESSAGE_SIMPLE, String::from(ALICE_DID), cb);
+
+        let res = get_ok(receiver).await;
+        assert!(res.contains("payload"));
+
+        // Cancelling completed operation does nothing
+        handle.cancel();
+    }
+
+    struct DeferredDIDResolver(Mutex<Option<oneshot::Sender<Arc<OnDIDResolverResult>>>>);
+
+    impl DIDResolver for DeferredDIDResolver {
+        fn resolve(&self, _did: String, cb: Arc<OnDIDResolverResult>) -> ErrorCode {
+            match self.0.lock().expect("Unable lock").take() {
+                Some(sender) if sender.send(cb).is_ok() => ErrorCode::Success,
+                _ => ErrorCode::Error,
+            }
+        }
+    }
 }
@@ -1,16 +1,33 @@
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
 
 use didcomm_core::{
-    algorithms::AnonCryptAlg, error::ErrorKind, protocols::routing::wrap_in_forward,
+    algorithms::{AnonCryptAlg, AuthCryptAlg},
+    error::ErrorKind,
+    protocols::routing::{self, wrap_in_forward_with_options},
+    secrets::SecretsResolver,
 };
 use serde_json::Value;
 
-use crate::common::EXECUTOR;
-use crate::{did_resolver_adapter::DIDResolverAdapter, DIDComm, ErrorCode};
+use crate::common::{spawn, spawn_cancellable, CancelHandle, ErrorCode};
+use crate::secrets::secrets_resolver_adapter::SecretsResolverAdapter;
+use crate::{did_resolver_adapter::DIDResolverAdapter, DIDComm};
 
 pub trait OnWrapInForwardResult: Sync + Send {
     fn success(&self, result: String);
-    fn error(&self, err: ErrorKind, err_msg: String);
+    fn error(&self, err: ErrorKind, err_msg: String, code: ErrorCode);
+}
+
+/// Optional parameters of `DIDComm::wrap_in_forward_with_options`.
+/// Shadows `didcomm_core::protocols::routing::WrapInForwardOptions`
+/// as secrets resolver and algorithm references aren't representable in FFI.
+#[derive(Debug, Clone, Default)]
+pub struct WrapInForwardOptions {
+    /// If sender DID or key ID is provided, the outermost Forward addressed to the first mediator
+    /// is authcrypted (with `A256cbcHs512Ecdh1puA256kw`) by the sender using secrets
+    /// of this `DIDComm` instance. Otherwise all Forward messages are anonymous.
+    pub forward_from: Option<String>,
 }
 
 impl DIDComm {
@@ -23,31 +40,96 @@ impl DIDComm {
         enc_alg_anon: &AnonCryptAlg,
         cb: Box<dyn OnWrapInForwardResult>,
     ) -> ErrorCode {
+        spawn(self._wrap_in_forward(
+            msg,
+            headers,
+            to,
+            routing_keys,
+            enc_alg_anon,
+            &WrapInForwardOptions::default(),
+            cb,
+        ))
+    }
+
+    /// Same as `wrap_in_forward`, but allows to customize wrapping with `WrapInForwardOptions`.
+    pub fn wrap_in_forward_with_options(
+        &self,
+        msg: String,
+        headers: &HashMap<String, Value>,
+        to: String,
+        routing_keys: &Vec<String>,
+        enc_alg_anon: &AnonCryptAlg,
+        options: &WrapInForwardOptions,
+        cb: Box<dyn OnWrapInForwardResult>,
+    ) -> ErrorCode {
+        spawn(self._wrap_in_forward(msg, headers, to, routing_keys, enc_alg_anon, options, cb))
+    }
+
+    /// Same as `wrap_in_forward`, but returns handle cancelling the operation
+    /// when `cancel` is called or the handle is dropped.
+    pub fn wrap_in_forward_cancellable(
+        &self,
+        msg: String,
+        headers: &HashMap<String, Value>,
+        to: String,
+        routing_keys: &Vec<String>,
+        enc_alg_anon: &AnonCryptAlg,
+        cb: Box<dyn OnWrapInForwardResult>,
+    ) -> Arc<CancelHandle> {
+        spawn_cancellable(self._wrap_in_forward(
+            msg,
+            headers,
+            to,
+            routing_keys,
+            enc_alg_anon,
+            &WrapInForwardOptions::default(),
+            cb,
+        ))
+    }
+
+    fn _wrap_in_forward(
+        &self,
+        msg: String,
+        headers: &HashMap<String, Value>,
+        to: String,
+        routing_keys: &Vec<String>,
+        enc_alg_anon: &AnonCryptAlg,
+        options: &WrapInForwardOptions,
+        cb: Box<dyn OnWrapInForwardResult>,
+    ) -> impl Future<Output = ()> + Send + 'static {
         let did_resolver = DIDResolverAdapter::new(self.did_resolver.clone());
+        let secret_resolver = SecretsResolverAdapter::new(self.secret_resolver.clone());
         let headers = headers.clone();
         let routing_keys = routing_keys.clone();
         let enc_alg_anon = enc_alg_anon.clone();
+        let forward_from = options.forward_from.clone();
 
         let future = async move {
-            wrap_in_forward(
+            let enc_alg_auth = AuthCryptAlg::default();
+            let secret_resolver: &dyn SecretsResolver = &secret_resolver;
+
+            wrap_in_forward_with_options(
                 &msg,
                 Some(&headers),
                 &to,
                 &routing_keys,
                 &enc_alg_anon,
                 &did_resolver,
+                &routing::WrapInForwardOptions {
+                    forward_from: forward_from
+                        .as_deref()
+                        .map(|from| (from, &enc_alg_auth, secret_resolver)),
+                },
             )
             .await
         };
 
-        EXECUTOR.spawn_ok(async move {
+        async move {
             match future.await {
                 Ok(result) => cb.success(result),
-                Err(err) => cb.error(err.kind(), err.to_string()),
+                Err(err) => cb.error(err.kind(), err.to_string(), err.kind().into()),
             }
-        });
-
-        ErrorCode::Success
+        }
     }
 }
 
@@ -61,14 +143,14 @@ mod tests {
         create_did_resolver, create_secrets_resolver, get_ok, PackResult, UnpackResult,
         WrapInForwardResult,
     };
-    use crate::DIDComm;
+    use crate::{DIDComm, PackEncryptedOptions, UnpackOptions};
     use didcomm_core::algorithms::AnonCryptAlg;
     use didcomm_core::protocols::routing::try_parse_forward;
     use didcomm_core::test_vectors::{
         ALICE_DID, BOB_DID, CHARLIE_DID, MEDIATOR2_VERIFICATION_METHOD_KEY_AGREEM_X25519_1,
         MESSAGE_SIMPLE,
     };
-    use didcomm_core::{Message, PackEncryptedOptions, UnpackOptions};
+    use didcomm_core::Message;
     use serde_json::json;
 
     #[tokio::test]
@@ -95,6 +177,7 @@ mod tests {
             &UnpackOptions {
                 expect_decrypt_by_all_keys: true,
                 unwrap_re_wrapping_forward: false,
+                ..UnpackOptions::default()
             },
             cb,
         );
@@ -142,6 +225,7 @@ mod tests {
             &UnpackOptions {
                 expect_decrypt_by_all_keys: true,
                 unwrap_re_wrapping_forward: false,
+                ..UnpackOptions::default()
             },
             cb,
         );
@@ -158,6 +242,7 @@ mod tests {
             &UnpackOptions {
                 expect_decrypt_by_all_keys: true,
                 unwrap_re_wrapping_forward: false,
+                ..UnpackOptions::default()
             },
             cb,
         );
@@ -174,6 +259,7 @@ mod tests {
             &UnpackOptions {
                 expect_decrypt_by_all_keys: true,
                 unwrap_re_wrapping_forward: false,
+                ..UnpackOptions::default()
             },
             cb,
         );
@@ -215,6 +301,7 @@ mod tests {
             &UnpackOptions {
                 expect_decrypt_by_all_keys: true,
                 unwrap_re_wrapping_forward: false,
+                ..UnpackOptions::default()
             },
             cb,
         );
@@ -245,6 +332,7 @@ mod tests {
             &UnpackOptions {
                 expect_decrypt_by_all_keys: true,
                 unwrap_re_wrapping_forward: false,
+                ..UnpackOptions::default()
             },
             cb,
         );
@@ -1,48 +1,169 @@
-use didcomm_core::{error::ErrorKind, Message, UnpackMetadata, UnpackOptions};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::common::EXECUTOR;
+use didcomm_core::{error::ErrorKind, Message, UnpackMetadata};
+
+use crate::common::{spawn, spawn_cancellable, CancelHandle, ErrorCode};
 use crate::did_resolver_adapter::DIDResolverAdapter;
+use crate::secrets_resolver_adapter::SecretsResolverAdapter;
 use crate::DIDComm;
-use crate::{secrets_resolver_adapter::SecretsResolverAdapter, ErrorCode};
+
+/// FFI counterpart of `didcomm_core::UnpackOptions`.
+/// Registered algorithms and body validators (`algorithms`, `body_validators`)
+/// can't cross FFI boundary, so they are omitted and built-in ones are used. See `didcomm_core::UnpackOptions`
+/// for fields description.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct UnpackOptions {
+    pub expect_decrypt_by_all_keys: bool,
+    pub unwrap_re_wrapping_forward: bool,
+    pub accept_canonical_signatures: bool,
+    pub max_total_attachment_size: Option<u64>,
+    pub max_message_bytes: Option<u64>,
+    pub max_attachment_bytes: Option<u64>,
+    pub max_signatures: Option<u64>,
+    pub min_valid_signatures: Option<u64>,
+    pub lenient_base64: bool,
+    pub resolver_timeout: Option<Duration>,
+    pub retain_original: bool,
+    pub require_encrypted: bool,
+    pub require_signed: bool,
+    pub strict_did_urls: bool,
+    pub accepted_plaintext_typ: Option<Vec<String>>,
+    pub lenient_json_attachments: bool,
+    pub lenient_timestamps: bool,
+    pub allowed_senders: Option<Vec<String>>,
+    pub follow_also_known_as: bool,
+    pub skip_signature_verification: bool,
+    pub require_signer_matches_sender: bool,
+    pub expected_enc_typ: Option<String>,
+    pub verify_to_matches_recipient: bool,
+}
+
+impl Default for UnpackOptions {
+    fn default() -> Self {
+        didcomm_core::UnpackOptions::default().into()
+    }
+}
+
+impl From<didcomm_core::UnpackOptions> for UnpackOptions {
+    fn from(options: didcomm_core::UnpackOptions) -> Self {
+        UnpackOptions {
+            expect_decrypt_by_all_keys: options.expect_decrypt_by_all_keys,
+            unwrap_re_wrapping_forward: options.unwrap_re_wrapping_forward,
+            accept_canonical_signatures: options.accept_canonical_signatures,
+            max_total_attachment_size: options.max_total_attachment_size,
+            max_message_bytes: options.max_message_bytes,
+            max_attachment_bytes: options.max_attachment_bytes,
+            max_signatures: options.max_signatures,
+            min_valid_signatures: options.min_valid_signatures,
+            lenient_base64: options.lenient_base64,
+            resolver_timeout: options.resolver_timeout,
+            retain_original: options.retain_original,
+            require_encrypted: options.require_encrypted,
+            require_signed: options.require_signed,
+            strict_did_urls: options.strict_did_urls,
+            accepted_plaintext_typ: options.accepted_plaintext_typ,
+            lenient_json_attachments: options.lenient_json_attachments,
+            lenient_timestamps: options.lenient_timestamps,
+            allowed_senders: options.allowed_senders,
+            follow_also_known_as: options.follow_also_known_as,
+            skip_signature_verification: options.skip_signature_verification,
+            require_signer_matches_sender: options.require_signer_matches_sender,
+            expected_enc_typ: options.expected_enc_typ,
+            verify_to_matches_recipient: options.verify_to_matches_recipient,
+        }
+    }
+}
+
+impl From<UnpackOptions> for didcomm_core::UnpackOptions {
+    fn from(options: UnpackOptions) -> Self {
+        didcomm_core::UnpackOptions {
+            expect_decrypt_by_all_keys: options.expect_decrypt_by_all_keys,
+            unwrap_re_wrapping_forward: options.unwrap_re_wrapping_forward,
+            accept_canonical_signatures: options.accept_canonical_signatures,
+            max_total_attachment_size: options.max_total_attachment_size,
+            max_message_bytes: options.max_message_bytes,
+            max_attachment_bytes: options.max_attachment_bytes,
+            max_signatures: options.max_signatures,
+            min_valid_signatures: options.min_valid_signatures,
+            lenient_base64: options.lenient_base64,
+            resolver_timeout: options.resolver_timeout,
+            retain_original: options.retain_original,
+            require_encrypted: options.require_encrypted,
+            require_signed: options.require_signed,
+            strict_did_urls: options.strict_did_urls,
+            accepted_plaintext_typ: options.accepted_plaintext_typ,
+            lenient_json_attachments: options.lenient_json_attachments,
+            lenient_timestamps: options.lenient_timestamps,
+            allowed_senders: options.allowed_senders,
+            follow_also_known_as: options.follow_also_known_as,
+            skip_signature_verification: options.skip_signature_verification,
+            require_signer_matches_sender: options.require_signer_matches_sender,
+            expected_enc_typ: options.expected_enc_typ,
+            verify_to_matches_recipient: options.verify_to_matches_recipient,
+            ..didcomm_core::UnpackOptions::default()
+        }
+    }
+}
 
 pub trait OnUnpackResult: Sync + Send {
     fn success(&self, result: Message, metadata: UnpackMetadata);
-    fn error(&self, err: ErrorKind, err_msg: String);
+    fn error(&self, err: ErrorKind, err_msg: String, code: ErrorCode);
 }
 
 impl DIDComm {
-    pub fn unpack<'a>(
+    pub fn unpack(
         &self,
         msg: String,
-        options: &'a UnpackOptions,
+        options: &UnpackOptions,
         cb: Box<dyn OnUnpackResult>,
     ) -> ErrorCode {
+        spawn(self._unpack(msg, options, cb))
+    }
+
+    /// Same as `unpack`, but returns handle cancelling the operation
+    /// when `cancel` is called or the handle is dropped.
+    pub fn unpack_cancellable(
+        &self,
+        msg: String,
+        options: &UnpackOptions,
+        cb: Box<dyn OnUnpackResult>,
+    ) -> Arc<CancelHandle> {
+        spawn_cancellable(self._unpack(msg, options, cb))
+    }
+
+    fn _unpack(
+        &self,
+        msg: String,
+        options: &UnpackOptions,
+        cb: Box<dyn OnUnpackResult>,
+    ) -> impl Future<Output = ()> + Send + 'static {
         let msg = msg.clone();
-        let options = options.clone();
+        let options: didcomm_core::UnpackOptions = options.clone().into();
         let did_resolver = DIDResolverAdapter::new(self.did_resolver.clone());
         let secret_resolver = SecretsResolverAdapter::new(self.secret_resolver.clone());
 
         let future =
             async move { Message::unpack(&msg, &did_resolver, &secret_resolver, &options).await };
-        EXECUTOR.spawn_ok(async move {
+        async move {
             match future.await {
                 Ok((result, metadata)) => cb.success(result, metadata),
-                Err(err) => cb.error(err.kind(), err.to_string()),
+                Err(err) => cb.error(err.kind(), err.to_string(), err.kind().into()),
             }
-        });
-
-        ErrorCode::Success
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::test_helper::{
-        create_did_resolver, create_secrets_resolver, get_error, get_ok, PackResult, UnpackResult,
+        create_did_resolver, create_secrets_resolver, get_error, get_ok, ErrorCodeResult,
+        PackResult, UnpackResult, UnpackWithMetadataResult,
     };
-    use crate::DIDComm;
+    use crate::{DIDComm, ErrorCode, PackEncryptedOptions, UnpackOptions};
+    use didcomm_core::algorithms::SignAlg;
     use didcomm_core::error::ErrorKind;
-    use didcomm_core::{PackEncryptedOptions, UnpackOptions};
 
     use didcomm_core::test_vectors::{ALICE_DID, BOB_DID, MESSAGE_SIMPLE};
 
@@ -78,6 +199,28 @@ mod tests {
         assert_eq!(res, msg);
     }
 
+    #[tokio::test]
+    async fn unpack_works_signed_metadata() {
+        let msg = MESSAGE_SIMPLE.clone();
+        let didcomm = DIDComm::new(create_did_resolver(), create_secrets_resolver());
+
+        let (cb, receiver) = PackResult::new();
+        didcomm.pack_signed(&msg, String::from(ALICE_DID), cb);
+        let packed = get_ok(receiver).await;
+
+        let (cb, receiver) = UnpackWithMetadataResult::new();
+        didcomm.unpack(packed.clone(), &UnpackOptions::default(), cb);
+        let (res, metadata) = get_ok(receiver).await;
+
+        assert_eq!(res, msg);
+        assert!(metadata.non_repudiation);
+        assert!(metadata.authenticated);
+        assert!(!metadata.encrypted);
+        assert_eq!(metadata.sign_from, Some(format!("{}#key-1", ALICE_DID)));
+        assert_eq!(metadata.sign_alg, Some(SignAlg::EdDSA));
+        assert_eq!(metadata.signed_message, Some(packed));
+    }
+
     #[tokio::test]
     async fn unpack_works_encrypted() {
         let msg = MESSAGE_SIMPLE.clone();
@@ -116,4 +259,36 @@ mod tests {
 
         assert_eq!(res.kind(), ErrorKind::Malformed);
     }
+
+    #[tokio::test]
+    async fn unpack_works_error_code() {
+        let didcomm = DIDComm::new(create_did_resolver(), create_secrets_resolver());
+
+        let (cb, receiver) = ErrorCodeResult::new();
+        didcomm.unpack(
+            String::from("invalid message"),
+            &UnpackOptions::default(),
+            cb,
+        );
+        assert_eq!(get_ok(receiver).await, ErrorCode::Malformed);
+
+        let (cb, receiver) = PackResult::new();
+        didcomm.pack_plaintext(&MESSAGE_SIMPLE, cb);
+        let msg = get_ok(receiver).await;
+
+        let (cb, receiver) = ErrorCodeResult::new();
+        didcomm.unpack(
+            msg.clone(),
+            &UnpackOptions {
+                require_encrypted: true,
+                ..UnpackOptions::default()
+            },
+            cb,
+        );
+        assert_eq!(get_ok(receiver).await, ErrorCode::Untrusted);
+
+        let (cb, receiver) = ErrorCodeResult::new();
+        didcomm.unpack(msg, &UnpackOptions::default(), cb);
+        assert_eq!(get_ok(receiver).await, ErrorCode::Success);
+    }
 }
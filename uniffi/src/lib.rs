@@ -3,11 +3,13 @@ mod did;
 mod didcomm;
 mod secrets;
 
-pub use common::ErrorCode;
 pub use common::JsonValue;
+pub use common::{CancelHandle, ErrorCode};
 pub use did::resolvers::*;
 pub use did::*;
 pub use didcomm::*;
+// Shadow `didcomm_core` options that have fields not representable in FFI
+pub use didcomm::{PackEncryptedOptions, UnpackOptions, WrapInForwardOptions};
 pub use didcomm_core::algorithms::*;
 pub use didcomm_core::did::{
     DIDCommMessagingService, DIDDoc, Service, ServiceKind, VerificationMaterial,
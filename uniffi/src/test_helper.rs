@@ -7,7 +7,7 @@ use didcomm_core::{FromPrior, Message, PackEncryptedMetadata, PackSignedMetadata
 use futures::channel::oneshot::{self, Receiver};
 
 use crate::{
-    DIDResolver, ExampleDIDResolver, ExampleSecretsResolver, OnFromPriorPackResult,
+    DIDResolver, ErrorCode, ExampleDIDResolver, ExampleSecretsResolver, OnFromPriorPackResult,
     OnFromPriorUnpackResult, OnPackEncryptedResult, OnPackPlaintextResult, OnPackSignedResult,
     OnUnpackResult, OnWrapInForwardResult, SecretsResolver,
 };
@@ -88,7 +88,9 @@ where
             .expect("Unable send");
     }
 
-    fn _error(&self, err: ErrorKind, msg: String) {
+    fn _error(&self, err: ErrorKind, msg: String, code: ErrorCode) {
+        assert_eq!(code, ErrorCode::from(err));
+
         self.sender
             .lock()
             .expect("Unable lock")
@@ -106,8 +108,8 @@ impl OnPackPlaintextResult for PackResult {
         self._success(result);
     }
 
-    fn error(&self, err: ErrorKind, msg: String) {
-        self._error(err, msg);
+    fn error(&self, err: ErrorKind, msg: String, code: ErrorCode) {
+        self._error(err, msg, code);
     }
 }
 
@@ -116,8 +118,8 @@ impl OnPackSignedResult for PackResult {
         self._success(result);
     }
 
-    fn error(&self, err: ErrorKind, msg: String) {
-        self._error(err, msg);
+    fn error(&self, err: ErrorKind, msg: String, code: ErrorCode) {
+        self._error(err, msg, code);
     }
 }
 
@@ -126,8 +128,8 @@ impl OnPackEncryptedResult for PackResult {
         self._success(result);
     }
 
-    fn error(&self, err: ErrorKind, msg: String) {
-        self._error(err, msg);
+    fn error(&self, err: ErrorKind, msg: String, code: ErrorCode) {
+        self._error(err, msg, code);
     }
 }
 
@@ -138,8 +140,20 @@ impl OnUnpackResult for UnpackResult {
         self._success(result);
     }
 
-    fn error(&self, err: ErrorKind, msg: String) {
-        self._error(err, msg);
+    fn error(&self, err: ErrorKind, msg: String, code: ErrorCode) {
+        self._error(err, msg, code);
+    }
+}
+
+pub(crate) type UnpackWithMetadataResult = TestResult<(Message, UnpackMetadata)>;
+
+impl OnUnpackResult for UnpackWithMetadataResult {
+    fn success(&self, result: Message, metadata: UnpackMetadata) {
+        self._success((result, metadata));
+    }
+
+    fn error(&self, err: ErrorKind, msg: String, code: ErrorCode) {
+        self._error(err, msg, code);
     }
 }
 
@@ -150,8 +164,8 @@ impl OnFromPriorPackResult for FromPriorPackResult {
         self._success((from_prior_jwt, kid));
     }
 
-    fn error(&self, err: ErrorKind, msg: String) {
-        self._error(err, msg);
+    fn error(&self, err: ErrorKind, msg: String, code: ErrorCode) {
+        self._error(err, msg, code);
     }
 }
 
@@ -162,8 +176,8 @@ impl OnFromPriorUnpackResult for FromPriorUnpackResult {
         self._success((from_prior, kid));
     }
 
-    fn error(&self, err: ErrorKind, msg: String) {
-        self._error(err, msg);
+    fn error(&self, err: ErrorKind, msg: String, code: ErrorCode) {
+        self._error(err, msg, code);
     }
 }
 
@@ -174,7 +188,32 @@ impl OnWrapInForwardResult for WrapInForwardResult {
         self._success(result);
     }
 
-    fn error(&self, err: ErrorKind, msg: String) {
-        self._error(err, msg);
+    fn error(&self, err: ErrorKind, msg: String, code: ErrorCode) {
+        self._error(err, msg, code);
+    }
+}
+
+/// Receives code passed to the callback: `Success` on success and error code on error.
+pub(crate) type ErrorCodeResult = TestResult<ErrorCode>;
+
+impl OnPackSignedResult for ErrorCodeResult {
+    fn success(&self, _result: String, _metadata: PackSignedMetadata) {
+        self._success(ErrorCode::Success);
+    }
+
+    fn error(&self, err: ErrorKind, _msg: String, code: ErrorCode) {
+        assert_eq!(code, ErrorCode::from(err));
+        self._success(code);
+    }
+}
+
+impl OnUnpackResult for ErrorCodeResult {
+    fn success(&self, _result: Message, _metadata: UnpackMetadata) {
+        self._success(ErrorCode::Success);
+    }
+
+    fn error(&self, err: ErrorKind, _msg: String, code: ErrorCode) {
+        assert_eq!(code, ErrorCode::from(err));
+        self._success(code);
     }
 }
@@ -34,6 +34,12 @@ type DIDDoc = {
      * All services (https://www.w3.org/TR/did-core/#services)
      */
     services: Array<Service>,
+
+    /**
+     * Other identifiers (usually DIDs) of the DID subject
+     * (https://www.w3.org/TR/did-core/#also-known-as).
+     */
+    also_known_as?: Array<string>,
 }
 "#;
 
@@ -20,6 +20,8 @@ impl<T> JsResult<T> for _Result<T> {
                 _ErrorKind::Unsupported => "DIDCommUnsupported",
                 _ErrorKind::IllegalArgument => "DIDCommIllegalArgument",
                 _ErrorKind::SecretNotFound => "DIDCommSecretNotFound",
+                _ErrorKind::Untrusted => "DIDCommUntrusted",
+                _ErrorKind::TooLarge => "DIDCommTooLarge",
             };
 
             let e = JsError::new(&format!("{}", e));
@@ -56,6 +58,8 @@ impl<T> FromJsResult<T> for Result<T, JsValue> {
                     Some("DIDCommNoCompatibleCrypto") => _ErrorKind::NoCompatibleCrypto,
                     Some("DIDCommUnsupported") => _ErrorKind::Unsupported,
                     Some("DIDCommIllegalArgument") => _ErrorKind::IllegalArgument,
+                    Some("DIDCommUntrusted") => _ErrorKind::Untrusted,
+                    Some("DIDCommTooLarge") => _ErrorKind::TooLarge,
                     _ => _ErrorKind::InvalidState,
                 };
 
@@ -0,0 +1,115 @@
+use didcomm::error::{ErrorKind, ResultExt};
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::{error::JsResult, utils::set_panic_hook};
+
+#[wasm_bindgen]
+/// Allows building of message attachments.
+pub struct AttachmentBuilder(didcomm::AttachmentBuilder);
+
+#[wasm_bindgen]
+impl AttachmentBuilder {
+    /// Starts building of attachment with the given base64-encoded content.
+    pub fn base64(base64: String) -> AttachmentBuilder {
+        // TODO: Better place?
+        set_panic_hook();
+
+        AttachmentBuilder(didcomm::Attachment::base64(base64))
+    }
+
+    #[wasm_bindgen(skip_typescript)]
+    pub fn json(json: JsValue) -> Result<AttachmentBuilder, JsValue> {
+        // TODO: Better place?
+        set_panic_hook();
+
+        let json: Value = json
+            .into_serde()
+            .kind(ErrorKind::Malformed, "Json param is malformed")
+            .as_js()?;
+
+        Ok(AttachmentBuilder(didcomm::Attachment::json(json)))
+    }
+
+    #[wasm_bindgen(skip_typescript)]
+    pub fn links(links: JsValue, hash: String) -> Result<AttachmentBuilder, JsValue> {
+        // TODO: Better place?
+        set_panic_hook();
+
+        let links: Vec<String> = links
+            .into_serde()
+            .kind(ErrorKind::Malformed, "Links param is malformed")
+            .as_js()?;
+
+        Ok(AttachmentBuilder(didcomm::Attachment::links(links, hash)))
+    }
+
+    /// Sets attachment id. If not set, random UUID is generated.
+    pub fn id(self, id: String) -> AttachmentBuilder {
+        AttachmentBuilder(self.0.id(id))
+    }
+
+    /// Derives attachment id from SHA-256 hash of the attachment data
+    /// instead of generating random UUID. Ignored if id is set explicitly.
+    pub fn content_id(self) -> AttachmentBuilder {
+        AttachmentBuilder(self.0.content_id())
+    }
+
+    /// Sets human-readable description of the content.
+    pub fn description(self, description: String) -> AttachmentBuilder {
+        AttachmentBuilder(self.0.description(description))
+    }
+
+    /// Sets a hint about the name of the file the attachment can be persisted as.
+    pub fn filename(self, filename: String) -> AttachmentBuilder {
+        AttachmentBuilder(self.0.filename(filename))
+    }
+
+    /// Sets MIME type of the attached content.
+    pub fn media_type(self, media_type: String) -> AttachmentBuilder {
+        AttachmentBuilder(self.0.media_type(media_type))
+    }
+
+    /// Sets format of the attachment if the media type is not sufficient.
+    pub fn format(self, format: String) -> AttachmentBuilder {
+        AttachmentBuilder(self.0.format(format))
+    }
+
+    #[wasm_bindgen(skip_typescript)]
+    pub fn finalize(self) -> Result<JsValue, JsValue> {
+        let attachment = JsValue::from_serde(&self.0.finalize())
+            .kind(ErrorKind::Malformed, "Unable serialize Attachment")
+            .as_js()?;
+
+        Ok(attachment)
+    }
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const ATTACHMENT_BUILDER_TS: &'static str = r#"
+interface AttachmentBuilder {
+    /**
+     * @returns attachment as plain object that can be added to message `attachments`
+     */
+    finalize(): Attachment;
+}
+
+export namespace AttachmentBuilder {
+    /**
+     * Starts building of attachment with the given embedded JSON content.
+     *
+     * @throws DIDCommMalformed
+     */
+    function json(json: any): AttachmentBuilder;
+
+    /**
+     * Starts building of attachment with content available by the given links.
+     *
+     * @param `links` one or more locations at which the content may be fetched.
+     * @param `hash` the hash of the content encoded in multi-hash format.
+     *
+     * @throws DIDCommMalformed
+     */
+    function links(links: Array<string>, hash: string): AttachmentBuilder;
+}
+"#;
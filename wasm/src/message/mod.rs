@@ -1,14 +1,17 @@
+mod attachment;
 mod from_prior;
 mod pack_encrypted;
 mod pack_plaintext;
 mod pack_signed;
 mod unpack;
+mod wrap_in_forward;
 
 use didcomm::error::{ErrorKind, ResultExt};
 use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 
 use crate::{error::JsResult, utils::set_panic_hook};
+pub use attachment::AttachmentBuilder;
 pub use from_prior::FromPrior;
 
 #[wasm_bindgen]
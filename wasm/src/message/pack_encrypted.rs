@@ -155,10 +155,19 @@ type PackEncryptedOptions = {
      */
     forward_headers?: Array<[string, string]>,
 
+    /**
+     * If forward is enabled and this DID or key ID (DID URL) of the sender is present,
+     * the outermost `Forward` message is authcrypted (instead of anoncrypted) for the first mediator,
+     * so the mediator can authenticate the sender.
+     * If forward is disabled this property will be ignored.
+     */
+    forward_from?: string,
+
     /**
      * Identifier (DID URL) of messaging service (https://identity.foundation/didcomm-messaging/spec/#did-document-service-endpoint).
      * If DID contains multiple messaging services it allows specify what service to use.
-     * If not present first service will be used.
+     * If not present the first service accepting `didcomm/v2` profile will be used,
+     * services that don't declare `accept` are used only if there is no such service.
      */
     messaging_service?: string,
 
@@ -166,13 +175,83 @@ type PackEncryptedOptions = {
      *  Algorithm used for authenticated encryption.
      * Default "A256cbcHs512Ecdh1puA256kw"
      */
-    enc_alg_auth?: "A256cbcHs512Ecdh1puA256kw",
+    enc_alg_auth?: "A256cbcHs512Ecdh1puA256kw" | "A128cbcHs256Ecdh1puA256kw",
 
     /**
      * Algorithm used for anonymous encryption.
      * Default "Xc20pEcdhEsA256kw"
      */
-    enc_alg_anon?: "A256cbcHs512EcdhEsA256kw" | "Xc20pEcdhEsA256kw" | "A256gcmEcdhEsA256kw",
+    enc_alg_anon?: "A256cbcHs512EcdhEsA256kw" | "Xc20pEcdhEsA256kw" | "A256gcmEcdhEsA256kw" | "A128cbcHs256EcdhEsA256kw" | "A128gcmEcdhEsA256kw",
+
+    /**
+     * If true and `sign_by` is set, the encrypted message is signed (encrypt-then-sign)
+     * instead of the plaintext being signed before encryption (sign-then-encrypt).
+     * The outer signature discloses the signer to anyone including mediators,
+     * covers the ciphertext instead of the plaintext and can't be combined with `protect_sender`.
+     * Default false.
+     */
+    sign_outside?: boolean,
+
+    /**
+     * If set, signed `from_prior` header linking the sender prior DID (`iss`) to the message `from` DID
+     * is added to the message before signing and encryption. `issuer_kid` is the prior DID
+     * authentication key to sign `from_prior` with, the first key with known secret is used if not set.
+     * Message `from` must be set and message `from_prior` must not.
+     */
+    from_prior?: { iss: string, issuer_kid?: string },
+
+    /**
+     * Maximum duration of each DID resolver call in milliseconds. Calls that don't complete in time
+     * fail packing with `IOError`. No limit by default.
+     */
+    resolver_timeout?: number,
+
+    /**
+     * If `true`, DID Docs using relative DID URLs (for ex. `#key-1`) for verification methods
+     * are rejected with `DIDCommMalformed` error. Otherwise relative DID URLs are expanded
+     * against the DID. False by default.
+     */
+    strict_did_urls?: boolean,
+
+    /**
+     * Additional authenticated data (JWE `aad`) binding external context to the encrypted message.
+     * It isn't encrypted, but is covered by the authentication tag, so the message can't be
+     * decrypted if it is tampered. Not applied to `Forward` envelopes. None by default.
+     */
+    aad?: string,
+
+    /**
+     * If `true`, packing fails with `DIDCommIllegalArgument` error if `sign_by` and `from`
+     * resolve to the same key material (for ex. the same P-256 key declared for both
+     * authentication and key agreement). False by default.
+     */
+    reject_key_reuse?: boolean,
+
+    /**
+     * If `true`, recipient keys are used in the order of recipient DID Doc `keyAgreement`.
+     * Otherwise recipient keys are sorted by key ID. False by default.
+     */
+    preserve_recipient_kids_order?: boolean,
+
+    /**
+     * If set, the message is encrypted only for recipient `keyAgreement` keys which key IDs
+     * start with the given prefix instead of all recipient keys. Packing fails with
+     * `DIDCommDIDUrlNotFound` error if no key matches. None by default.
+     */
+    recipient_kid_prefix?: string,
+
+    /**
+     * If `true`, hex encoded SHA-256 hash of the produced message is returned
+     * in `content_hash` metadata field (for ex. to be used as a cache key). False by default.
+     */
+    content_hash?: boolean,
+
+    /**
+     * If `true`, the encrypted message for a single recipient key is produced in flattened
+     * JWE JSON serialization instead of general one. Messages for multiple recipient keys
+     * are always produced in general JWE JSON serialization. False by default.
+     */
+    flattened_jwe?: boolean,
 }
 "#;
 
@@ -189,6 +268,12 @@ type PackEncryptedMetadata = {
      */
     messaging_service?: MessagingServiceMetadata,
 
+    /**
+     * Whether the message is wrapped in Forward envelope(s) for mediator(s)
+     * instead of being packed for the recipient directly.
+     */
+    forwarded: boolean,
+
     /** 
      * Identifier (DID URL) of sender key used for message encryption.
      */
@@ -199,10 +284,25 @@ type PackEncryptedMetadata = {
      */
     sign_by_kid?: string,
 
+    /**
+     * Algorithm used for signing of inner message if `sign_by` was set.
+     */
+    sign_alg?: "EdDSA" | "ES256" | "ES256K",
+
+    /**
+     * Identifier (DID URL) of prior DID key used for `from_prior` signing if `from_prior` option was set.
+     */
+    from_prior_issuer_kid?: string,
+
     /**
      * Identifiers (DID URLs) of recipient keys used for message encryption.
      */
     to_kids: Array<string>,
+
+    /**
+     * Hex encoded SHA-256 hash of the produced message if `content_hash` option was set.
+     */
+    content_hash?: string,
 }
 "#;
 
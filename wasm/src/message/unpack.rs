@@ -63,7 +63,7 @@ export namespace Message {
      * This method supports all DID Comm message types (encrypted, signed, plaintext).
      *
      * If unpack options expect a particular property (for example that a message is encrypted)
-     * and the packed message doesn't meet the criteria (it's not encrypted), then a MessageUntrusted
+     * and the packed message doesn't meet the criteria (it's not encrypted), then a DIDCommUntrusted
      * error will be returned.
      *
      * @param `packed_msg` the message as JSON string to be unpacked
@@ -85,6 +85,8 @@ export namespace Message {
      * @throws DIDCommNoCompatibleCrypto
      * @throws DIDCommUnsupported
      * @throws DIDCommIllegalArgument
+     * @throws DIDCommUntrusted
+     * @throws DIDCommTooLarge
      */
     function unpack(
         msg: string,
@@ -114,6 +116,149 @@ type UnpackOptions = {
      * False by default.
      */
     unwrap_re_wrapping_forward?: boolean,
+
+    /**
+     * If `true`, a signature that doesn't match the base64url encoded JWS payload is additionally
+     * checked against the payload canonicalized according to JCS (RFC 8785).
+     * False by default.
+     */
+    accept_canonical_signatures?: boolean,
+
+    /**
+     * Maximum allowed sum of decoded sizes of base64 attachments in bytes.
+     * Message with bigger attachments is rejected with `DIDCommTooLarge` error. No limit by default.
+     * Checked independently of `max_attachment_bytes`: json attachments aren't counted here,
+     * and each attachment within this limit may still exceed `max_attachment_bytes`.
+     */
+    max_total_attachment_size?: number,
+
+    /**
+     * Maximum allowed size of the packed message in bytes. Bigger message is rejected
+     * with `DIDCommTooLarge` error before any parsing or decryption. No limit by default.
+     */
+    max_message_bytes?: number,
+
+    /**
+     * Maximum allowed size of each attachment data in bytes: decoded size of base64 data
+     * and serialized size of json data.
+     * Message with bigger attachment is rejected with `DIDCommTooLarge` error. No limit by default.
+     * Checked independently of `max_total_attachment_size`, so both limits must be met.
+     */
+    max_attachment_bytes?: number,
+
+    /**
+     * Maximum allowed amount of signatures in JWS. JWS with more signatures is considered
+     * malformed and rejected before any signature is verified. No limit by default.
+     */
+    max_signatures?: number,
+
+    /**
+     * If set, JWS with multiple signatures is accepted if at least this amount of signatures
+     * are valid, invalid signatures are ignored. Otherwise all signatures must be valid.
+     * None by default.
+     */
+    min_valid_signatures?: number,
+
+    /**
+     * If `true`, base64 values of encrypted message (JWE) fields are also accepted with padding
+     * and with characters of standard base64 alphabet. Some non-conformant senders produce such values.
+     * False by default, so only unpadded base64url values are accepted.
+     */
+    lenient_base64?: boolean,
+
+    /**
+     * Maximum duration of each DID resolver call in milliseconds. Calls that don't complete in time
+     * fail unpacking with `IOError`. No limit by default.
+     */
+    resolver_timeout?: number,
+
+    /**
+     * If `true`, the packed message is returned as received in `original_message` metadata field,
+     * so it can be persisted for audit or non-repudiation purposes. False by default.
+     */
+    retain_original?: boolean,
+
+    /**
+     * If `true`, unpacking fails with `DIDCommUntrusted` error if the message isn't encrypted.
+     * False by default.
+     */
+    require_encrypted?: boolean,
+
+    /**
+     * If `true`, unpacking fails with `DIDCommUntrusted` error if the message isn't signed
+     * (doesn't provide non-repudiation). False by default.
+     */
+    require_signed?: boolean,
+
+    /**
+     * If `true`, DID Docs using relative DID URLs (for ex. `#key-1`) for verification methods
+     * are rejected with `DIDCommMalformed` error. Otherwise relative DID URLs are expanded
+     * against the DID. False by default.
+     */
+    strict_did_urls?: boolean,
+
+    /**
+     * Plaintext `typ` values accepted in addition to the standard `application/didcomm-plain+json`
+     * (for ex. profile specific media types). Messages with other `typ` values are rejected
+     * with `DIDCommMalformed` error. None by default.
+     */
+    accepted_plaintext_typ?: Array<string>,
+
+    /**
+     * If `true`, `json` attachment data embedded as a string containing JSON object or array
+     * (stringified JSON) is parsed into the embedded JSON. Some non-conformant senders produce such data.
+     * False by default, so such attachments are rejected with `DIDCommMalformed` error
+     * as it is ambiguous whether the data is JSON or a string.
+     */
+    lenient_json_attachments?: boolean,
+
+    /**
+     * If `true`, `created_time` and `expires_time` plaintext headers are also accepted as strings
+     * containing UNIX timestamps (for ex. `"1516269022"`) and normalized to numbers.
+     * False by default, so only numeric timestamps are accepted as required by the spec.
+     */
+    lenient_timestamps?: boolean,
+
+    /**
+     * DIDs of senders allowed to send messages. If set, messages with an authenticated
+     * sender not in the list, as well as not authenticated messages, are rejected.
+     * Any sender is accepted by default.
+     */
+    allowed_senders?: Array<string>,
+
+    /**
+     * If `true`, a sender not in `allowed_senders` is also accepted if its DID is linked
+     * with an allowed DID by `alsoKnownAs` confirmed by both DID Docs.
+     * False by default.
+     */
+    follow_also_known_as?: boolean,
+
+    /**
+     * INSECURE! If `true`, signatures of signed messages are NOT cryptographically verified,
+     * `sign_from` is populated from JWS headers and must not be trusted.
+     * Use it only if the transport already guarantees authenticity. False by default.
+     */
+    skip_signature_verification?: boolean,
+
+    /**
+     * If `true`, messages both authcrypted and signed are rejected with `DIDCommUntrusted` error
+     * if a JWS signer DID differs from the authcrypt sender DID. False by default.
+     */
+    require_signer_matches_sender?: boolean,
+
+    /**
+     * If set, encrypted messages with protected `typ` header other than the given value
+     * (for ex. `application/didcomm-encrypted+json`) are rejected with `DIDCommMalformed` error.
+     * Not checked by default.
+     */
+    expected_enc_typ?: string,
+
+    /**
+     * If `true`, messages with plaintext `to` header not listing any DID (or key ID)
+     * the recipient holds key agreement secrets for are rejected with `DIDCommUntrusted` error.
+     * Messages without `to` aren't affected. False by default.
+     */
+    verify_to_matches_recipient?: boolean,
 }
 "#;
 
@@ -141,6 +286,8 @@ type UnpackMetadata = {
 
     /**
      * Whether the sender ID was hidden or protected.
+     * For protected sender (authcrypt wrapped in anoncrypt) both `anonymous_sender`
+     * and `authenticated` are true and `encrypted_from_kid` is the authenticated inner sender.
      */
     anonymous_sender: boolean,
 
@@ -149,6 +296,24 @@ type UnpackMetadata = {
      */
     re_wrapped_in_forward: boolean,
 
+    /**
+     * `next` of the re-wrapping forward message matched to the recipient keys
+     * if the plaintext was re-wrapped in forward.
+     */
+    re_wrapped_forward_next?: string,
+
+    /**
+     * Re-wrapping forward messages unwrapped for the recipient in order from the outermost one
+     * if the plaintext was re-wrapped in forward (possibly multiple times).
+     */
+    forward_chain?: Array<ForwardHopInfo>,
+
+    /**
+     * Whether the encrypted message has been signed (encrypt-then-sign)
+     * instead of the plaintext being signed before encryption.
+     */
+    signed_outside: boolean,
+
     /**
      * Key ID of the sender used for authentication encryption
      * if the plaintext has been authenticated and encrypted.
@@ -160,11 +325,23 @@ type UnpackMetadata = {
      */
     encrypted_to_kids?: Array<string>,
 
+    /**
+     * Key ID of the recipient secret that actually decrypted the message if the plaintext has been encrypted.
+     * If the sender is protected, the key decrypted anoncrypt envelope is used.
+     */
+    decrypted_with_kid?: string,
+
     /**
      * Key ID used for signature if the plaintext has been signed.
      */
     sign_from: string,
 
+    /**
+     * Key IDs of all valid signatures if the plaintext has been signed
+     * (multiple for multi-signature JWS). `sign_from` is the first of them.
+     */
+    sign_from_kids?: Array<string>,
+
     /**
      * Key ID used for from_prior header signature if from_prior header is present
      */
@@ -174,13 +351,13 @@ type UnpackMetadata = {
      * Algorithm used for authenticated encryption.
      * Default "A256cbcHs512Ecdh1puA256kw"
      */
-    enc_alg_auth?: "A256cbcHs512Ecdh1puA256kw",
+    enc_alg_auth?: "A256cbcHs512Ecdh1puA256kw" | "A128cbcHs256Ecdh1puA256kw",
  
     /**
      * Algorithm used for anonymous encryption.
      * Default "Xc20pEcdhEsA256kw"
      */
-    enc_alg_anon?: "A256cbcHs512EcdhEsA256kw" | "Xc20pEcdhEsA256kw" | "A256gcmEcdhEsA256kw",
+    enc_alg_anon?: "A256cbcHs512EcdhEsA256kw" | "Xc20pEcdhEsA256kw" | "A256gcmEcdhEsA256kw" | "A128cbcHs256EcdhEsA256kw" | "A128gcmEcdhEsA256kw",
 
     /**
      * Algorithm used for message signing.
@@ -196,5 +373,72 @@ type UnpackMetadata = {
      * If plaintext contains from_prior header, its unpacked value is returned
      */
     from_prior?: IFromPrior,
+
+    /**
+     * Ephemeral public key (as JWK) of the innermost JWE if the plaintext has been encrypted.
+     * Can be used to bind subsequent traffic to the key agreement of this message.
+     */
+    epk_jwk?: any,
+
+    /**
+     * The packed message exactly as received if `retain_original` option is set.
+     */
+    original_message?: string,
+
+    /**
+     * Additional authenticated data (JWE `aad`) of the encrypted message if present,
+     * see `aad` pack option.
+     */
+    aad?: string,
+
+    /**
+     * Whether the message is signed and authcrypted with the same key material.
+     * Such key reuse is discouraged, so it's a warning for the recipient.
+     */
+    signed_with_encryption_key: boolean,
+
+    /**
+     * Target key IDs of the encrypted message the recipient has no secrets for.
+     * It's a warning that some recipient keys aren't held by this agent.
+     */
+    missing_recipient_kids?: Array<string>,
+
+    /**
+     * Whether signatures of the signed message haven't been verified
+     * (see `skip_signature_verification` option), so `sign_from` isn't trusted.
+     */
+    signature_verification_skipped: boolean,
+
+    /**
+     * Protected `typ` header of the innermost JWE if the plaintext has been encrypted
+     * and the header is present.
+     */
+    enc_typ?: string,
+
+    /**
+     * Value of plaintext `return_route` header if present, so the transport can keep
+     * the inbound connection open for responses.
+     */
+    return_route?: "none" | "all" | "thread",
+}
+
+/**
+ * Re-wrapping forward message unwrapped by `unpack`.
+ */
+type ForwardHopInfo = {
+    /**
+     * `next` of the forward message (DID or key ID) matched to the recipient keys.
+     */
+    next: string,
+
+    /**
+     * Key ID of the recipient secret that decrypted the forward message.
+     */
+    decrypted_with_kid?: string,
+
+    /**
+     * Algorithm used for anonymous encryption of the forward message.
+     */
+    enc_alg_anon?: "A256cbcHs512EcdhEsA256kw" | "Xc20pEcdhEsA256kw" | "A256gcmEcdhEsA256kw" | "A128cbcHs256EcdhEsA256kw" | "A128gcmEcdhEsA256kw",
 }
 "#;
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use didcomm::{
+    algorithms::{AnonCryptAlg, AuthCryptAlg},
+    error::{err_msg, ErrorKind, ResultExt},
+    protocols::routing,
+    secrets::SecretsResolver as _SecretsResolver,
+};
+use js_sys::Promise;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::future_to_promise;
+
+use crate::{
+    error::JsResult, utils::set_panic_hook, DIDResolver, JsDIDResolver, JsSecretsResolver, Message,
+    SecretsResolver,
+};
+
+#[wasm_bindgen(skip_typescript)]
+impl Message {
+    #[wasm_bindgen(skip_typescript)]
+    pub fn wrap_in_forward(
+        msg: String,
+        headers: JsValue,
+        to: String,
+        routing_keys: JsValue,
+        enc_alg_anon: JsValue,
+        did_resolver: DIDResolver,
+        forward_from: Option<String>,
+        secrets_resolver: Option<SecretsResolver>,
+    ) -> Promise {
+        // TODO: Better place?
+        set_panic_hook();
+
+        let did_resolver = JsDIDResolver(did_resolver);
+        let secrets_resolver = secrets_resolver.map(JsSecretsResolver);
+
+        future_to_promise(async move {
+            let headers: Option<HashMap<String, Value>> = headers
+                .into_serde()
+                .kind(ErrorKind::Malformed, "Headers param is malformed")
+                .as_js()?;
+
+            let routing_keys: Vec<String> = routing_keys
+                .into_serde()
+                .kind(ErrorKind::Malformed, "Routing keys param is malformed")
+                .as_js()?;
+
+            let enc_alg_anon: Option<AnonCryptAlg> = enc_alg_anon
+                .into_serde()
+                .kind(ErrorKind::Malformed, "Enc alg anon param is malformed")
+                .as_js()?;
+
+            let enc_alg_auth = AuthCryptAlg::default();
+
+            let forward_from: Option<(&str, &AuthCryptAlg, &dyn _SecretsResolver)> =
+                match (forward_from.as_deref(), secrets_resolver.as_ref()) {
+                    (Some(from), Some(secrets_resolver)) => Some((
+                        from,
+                        &enc_alg_auth,
+                        secrets_resolver as &dyn _SecretsResolver,
+                    )),
+                    (Some(_), None) => Err(err_msg(
+                        ErrorKind::IllegalArgument,
+                        "Secrets resolver is required to authenticate forward",
+                    ))
+                    .as_js()?,
+                    (None, _) => None,
+                };
+
+            let msg = routing::wrap_in_forward_with_options(
+                &msg,
+                headers.as_ref(),
+                &to,
+                &routing_keys,
+                &enc_alg_anon.unwrap_or_default(),
+                &did_resolver,
+                &routing::WrapInForwardOptions { forward_from },
+            )
+            .await
+            .as_js()?;
+
+            Ok(msg.into())
+        })
+    }
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const MESSAGE_WRAP_IN_FORWARD_TS: &'static str = r#"
+export namespace Message {
+    /**
+     * Wraps the given packed DIDComm message in Forward messages for the given routing keys.
+     * https://identity.foundation/didcomm-messaging/spec/#messages
+     *
+     * Allows to build Forward envelopes explicitly when the message should be routed
+     * through mediators not listed in the recipient's DID Doc services.
+     *
+     * @param `msg` packed DIDComm message (JWE) to be wrapped.
+     * @param `headers` optional headers to be added to each Forward message.
+     * @param `to` DID or key ID (DID URL) of the final recipient of the message.
+     * @param `routing_keys` routing keys (each one is a DID or key ID) of mediators
+     *   the message is routed through. Forward message for the last routing key is the outermost one.
+     * @param `enc_alg_anon` optional algorithm used to encrypt Forward messages,
+     *   `Xc20pEcdhEsA256kw` by default.
+     * @param `did_resolver` instance of `DIDResolver` to resolve DIDs.
+     * @param `forward_from` optional sender DID or key ID (DID URL). If provided, the outermost
+     *   Forward message addressed to the first mediator is authcrypted (with `A256cbcHs512Ecdh1puA256kw`)
+     *   by the sender, so the mediator learns the authenticated sender. Otherwise all Forward
+     *   messages are anonymous. Not provided by default.
+     * @param `secrets_resolver` instance of `SecretsResolver` to resolve sender keys secrets.
+     *   Required if `forward_from` is provided.
+     *
+     * @returns the outermost Forward message as JWE JSON string.
+     *
+     * @throws DIDCommDIDNotResolved
+     * @throws DIDCommDIDUrlNotFound
+     * @throws DIDCommSecretNotFound
+     * @throws DIDCommMalformed
+     * @throws DIDCommIoError
+     * @throws DIDCommInvalidState
+     * @throws DIDCommIllegalArgument
+     * @throws DIDCommNoCompatibleCrypto
+     * @throws DIDCommUnsupported
+     */
+    function wrap_in_forward(
+        msg: string,
+        headers: Record<string, any> | null,
+        to: string,
+        routing_keys: Array<string>,
+        enc_alg_anon: "A256cbcHs512EcdhEsA256kw" | "Xc20pEcdhEsA256kw" | "A256gcmEcdhEsA256kw" | "A128cbcHs256EcdhEsA256kw" | "A128gcmEcdhEsA256kw" | null,
+        did_resolver: DIDResolver,
+        forward_from?: string | null,
+        secrets_resolver?: SecretsResolver | null,
+    ): Promise<string>;
+}
+"#;
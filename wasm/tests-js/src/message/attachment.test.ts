@@ -0,0 +1,62 @@
+import { AttachmentBuilder, Message, IMessage } from "didcomm-js";
+
+test("AttachmentBuilder.base64 works", () => {
+  const attachment = AttachmentBuilder.base64("ZXhhbXBsZQ==")
+    .id("attachment1")
+    .description("example attachment")
+    .media_type("text/plain")
+    .finalize();
+
+  expect(attachment).toStrictEqual({
+    data: {
+      base64: "ZXhhbXBsZQ==",
+    },
+    id: "attachment1",
+    description: "example attachment",
+    media_type: "text/plain",
+  });
+
+  const val: IMessage = {
+    id: "example-1",
+    typ: "application/didcomm-plain+json",
+    type: "example/v1",
+    body: "example-body",
+    attachments: [attachment],
+  };
+
+  const msg = new Message(val);
+  expect(msg.as_value()).toStrictEqual(val);
+});
+
+test("AttachmentBuilder.json works", () => {
+  const attachment = AttachmentBuilder.json({ foo: "bar" })
+    .id("attachment2")
+    .finalize();
+
+  expect(attachment).toStrictEqual({
+    data: {
+      json: { foo: "bar" },
+    },
+    id: "attachment2",
+  });
+});
+
+test("AttachmentBuilder.links works", () => {
+  const attachment = AttachmentBuilder.links(["1", "2"], "qwerty")
+    .id("attachment3")
+    .finalize();
+
+  expect(attachment).toStrictEqual({
+    data: {
+      links: ["1", "2"],
+      hash: "qwerty",
+    },
+    id: "attachment3",
+  });
+});
+
+test("AttachmentBuilder.links works for malformed links", () => {
+  expect(() => AttachmentBuilder.links("1" as any, "qwerty")).toThrowError(
+    "Malformed: Links param is malformed"
+  );
+});
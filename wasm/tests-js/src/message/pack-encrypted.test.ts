@@ -0,0 +1,94 @@
+import { Message } from "didcomm-js";
+import {
+  ALICE_DID,
+  ALICE_DID_DOC,
+  ALICE_SECRETS,
+  BOB_DID,
+  BOB_DID_DOC,
+  BOB_SECRETS,
+  ExampleDIDResolver,
+  ExampleSecretsResolver,
+  IMESSAGE_SIMPLE,
+  MESSAGE_SIMPLE,
+} from "../test-vectors";
+
+test.each([
+  {
+    case: "Anoncrypt",
+    from: null,
+    options: { forward: false },
+    expMetadata: {
+      from_kid: null,
+      sign_by_kid: null,
+      sign_alg: null,
+      from_prior_issuer_kid: null,
+      messaging_service: null,
+      forwarded: false,
+      content_hash: null,
+      to_kids: [
+        "did:example:bob#key-x25519-1",
+        "did:example:bob#key-x25519-2",
+        "did:example:bob#key-x25519-3",
+      ],
+    },
+    expUnpackMetadata: {
+      encrypted: true,
+      authenticated: false,
+      anonymous_sender: true,
+      enc_alg_anon: "Xc20pEcdhEsA256kw",
+      enc_alg_auth: null,
+    },
+  },
+  {
+    case: "Authcrypt",
+    from: ALICE_DID,
+    options: { forward: false, protect_sender: false },
+    expMetadata: {
+      from_kid: "did:example:alice#key-x25519-1",
+      sign_by_kid: null,
+      sign_alg: null,
+      from_prior_issuer_kid: null,
+      messaging_service: null,
+      forwarded: false,
+      content_hash: null,
+      to_kids: [
+        "did:example:bob#key-x25519-1",
+        "did:example:bob#key-x25519-2",
+        "did:example:bob#key-x25519-3",
+      ],
+    },
+    expUnpackMetadata: {
+      encrypted: true,
+      authenticated: true,
+      anonymous_sender: false,
+      enc_alg_anon: null,
+      enc_alg_auth: "A256cbcHs512Ecdh1puA256kw",
+    },
+  },
+])(
+  "Message.pack_encrypted works for $case",
+  async ({ from, options, expMetadata, expUnpackMetadata }) => {
+    const didResolver = new ExampleDIDResolver([ALICE_DID_DOC, BOB_DID_DOC]);
+
+    const [encrypted, metadata] = await MESSAGE_SIMPLE.pack_encrypted(
+      BOB_DID,
+      from,
+      null,
+      didResolver,
+      new ExampleSecretsResolver(ALICE_SECRETS),
+      options
+    );
+
+    expect(metadata).toStrictEqual(expMetadata);
+
+    const [unpacked, unpackMetadata] = await Message.unpack(
+      encrypted,
+      didResolver,
+      new ExampleSecretsResolver(BOB_SECRETS),
+      {}
+    );
+
+    expect(unpacked.as_value()).toStrictEqual(IMESSAGE_SIMPLE);
+    expect(unpackMetadata).toMatchObject(expUnpackMetadata);
+  }
+);
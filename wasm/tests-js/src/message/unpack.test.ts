@@ -1,6 +1,8 @@
 import { Message } from "didcomm-js";
 import {
+  ALICE_DID,
   ALICE_DID_DOC,
+  ALICE_SECRETS,
   BOB_DID_DOC,
   BOB_SECRETS,
   CHARLIE_DID_DOC,
@@ -9,6 +11,7 @@ import {
   IMESSAGE_FROM_PRIOR,
   IMESSAGE_MINIMAL,
   IMESSAGE_SIMPLE,
+  MESSAGE_SIMPLE,
   PLAINTEXT_FROM_PRIOR,
   PLAINTEXT_MSG_MINIMAL,
   PLAINTEXT_MSG_SIMPLE,
@@ -23,6 +26,7 @@ test.each([
     expMetadata: {
       anonymous_sender: false,
       authenticated: false,
+      decrypted_with_kid: null,
       enc_alg_anon: null,
       enc_alg_auth: null,
       encrypted: false,
@@ -32,9 +36,21 @@ test.each([
       from_prior_issuer_kid: null,
       non_repudiation: false,
       re_wrapped_in_forward: false,
+      signed_outside: false,
       sign_alg: null,
       sign_from: null,
+      sign_from_kids: null,
       signed_message: null,
+      epk_jwk: null,
+      original_message: null,
+      aad: null,
+      signed_with_encryption_key: false,
+      missing_recipient_kids: null,
+      signature_verification_skipped: false,
+      enc_typ: null,
+      re_wrapped_forward_next: null,
+      forward_chain: null,
+      return_route: null,
     },
   },
   {
@@ -45,6 +61,7 @@ test.each([
     expMetadata: {
       anonymous_sender: false,
       authenticated: false,
+      decrypted_with_kid: null,
       enc_alg_anon: null,
       enc_alg_auth: null,
       encrypted: false,
@@ -54,9 +71,21 @@ test.each([
       from_prior_issuer_kid: null,
       non_repudiation: false,
       re_wrapped_in_forward: false,
+      signed_outside: false,
       sign_alg: null,
       sign_from: null,
+      sign_from_kids: null,
       signed_message: null,
+      epk_jwk: null,
+      original_message: null,
+      aad: null,
+      signed_with_encryption_key: false,
+      missing_recipient_kids: null,
+      signature_verification_skipped: false,
+      enc_typ: null,
+      re_wrapped_forward_next: null,
+      forward_chain: null,
+      return_route: null,
     },
   },
   {
@@ -67,6 +96,7 @@ test.each([
     expMetadata: {
       anonymous_sender: false,
       authenticated: false,
+      decrypted_with_kid: null,
       enc_alg_anon: null,
       enc_alg_auth: null,
       encrypted: false,
@@ -84,9 +114,21 @@ test.each([
       from_prior_issuer_kid: "did:example:charlie#key-1",
       non_repudiation: false,
       re_wrapped_in_forward: false,
+      signed_outside: false,
       sign_alg: null,
       sign_from: null,
+      sign_from_kids: null,
       signed_message: null,
+      epk_jwk: null,
+      original_message: null,
+      aad: null,
+      signed_with_encryption_key: false,
+      missing_recipient_kids: null,
+      signature_verification_skipped: false,
+      enc_typ: null,
+      re_wrapped_forward_next: null,
+      forward_chain: null,
+      return_route: null,
     },
   },
 ])(
@@ -111,3 +153,37 @@ test.each([
     expect(metadata).toStrictEqual(expMetadata);
   }
 );
+
+test("Message.unpack works for signed round trip", async () => {
+  const didResolver = new ExampleDIDResolver([ALICE_DID_DOC, BOB_DID_DOC]);
+
+  const [signed, signMetadata] = await MESSAGE_SIMPLE.pack_signed(
+    ALICE_DID,
+    didResolver,
+    new ExampleSecretsResolver(ALICE_SECRETS)
+  );
+
+  const [unpacked, metadata] = await Message.unpack(
+    signed,
+    didResolver,
+    new ExampleSecretsResolver(BOB_SECRETS),
+    {}
+  );
+
+  expect(unpacked.as_value()).toStrictEqual(IMESSAGE_SIMPLE);
+
+  expect(metadata).toMatchObject({
+    anonymous_sender: false,
+    authenticated: true,
+    encrypted: false,
+    encrypted_from_kid: null,
+    encrypted_to_kids: null,
+    non_repudiation: true,
+    re_wrapped_in_forward: false,
+    signed_outside: false,
+    sign_alg: "EdDSA",
+    sign_from: signMetadata.sign_by_kid,
+    sign_from_kids: [signMetadata.sign_by_kid],
+    signed_message: signed,
+  });
+});
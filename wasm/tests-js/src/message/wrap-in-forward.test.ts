@@ -0,0 +1,170 @@
+import { Message } from "didcomm-js";
+import {
+  ALICE_DID,
+  ALICE_DID_DOC,
+  ALICE_SECRETS,
+  BOB_DID,
+  BOB_DID_DOC,
+  CHARLIE_DID_DOC,
+  CHARLIE_SECRETS,
+  ExampleDIDResolver,
+  ExampleSecretsResolver,
+  MESSAGE_SIMPLE,
+} from "../test-vectors";
+
+test.each([
+  {
+    case: "Default enc alg",
+    headers: null,
+    encAlgAnon: null,
+    expEncAlgAnon: "Xc20pEcdhEsA256kw",
+  },
+  {
+    case: "Headers and A256GCM",
+    headers: { example: "example-1" },
+    encAlgAnon: "A256gcmEcdhEsA256kw",
+    expEncAlgAnon: "A256gcmEcdhEsA256kw",
+  },
+])(
+  "Message.wrap_in_forward works for $case",
+  async ({ headers, encAlgAnon, expEncAlgAnon }) => {
+    const didResolver = new ExampleDIDResolver([
+      ALICE_DID_DOC,
+      BOB_DID_DOC,
+      CHARLIE_DID_DOC,
+    ]);
+
+    const [encrypted] = await MESSAGE_SIMPLE.pack_encrypted(
+      BOB_DID,
+      null,
+      null,
+      didResolver,
+      new ExampleSecretsResolver(ALICE_SECRETS),
+      { forward: false }
+    );
+
+    const forward = await Message.wrap_in_forward(
+      encrypted,
+      headers,
+      BOB_DID,
+      ["did:example:charlie#key-x25519-1"],
+      encAlgAnon as any,
+      didResolver
+    );
+
+    const jwe = JSON.parse(forward);
+    expect(jwe).toHaveProperty("ciphertext");
+    expect(jwe).toHaveProperty("iv");
+    expect(jwe).toHaveProperty("tag");
+    expect(jwe.recipients.map((r: any) => r.header.kid)).toStrictEqual([
+      "did:example:charlie#key-x25519-1",
+    ]);
+
+    const [unpacked, unpackMetadata] = await Message.unpack(
+      forward,
+      didResolver,
+      new ExampleSecretsResolver(CHARLIE_SECRETS),
+      {}
+    );
+
+    const forwardMsg = unpacked.as_value();
+    expect(forwardMsg.type).toStrictEqual(
+      "https://didcomm.org/routing/2.0/forward"
+    );
+    expect(forwardMsg.body).toStrictEqual({ next: BOB_DID });
+    expect(forwardMsg).toMatchObject(headers ?? {});
+    expect(unpackMetadata.encrypted).toBe(true);
+    expect(unpackMetadata.anonymous_sender).toBe(true);
+    expect(unpackMetadata.enc_alg_anon).toStrictEqual(expEncAlgAnon);
+  }
+);
+
+test.each([
+  {
+    case: "anonymous",
+    forwardFrom: null,
+  },
+  {
+    case: "authenticated",
+    forwardFrom: ALICE_DID,
+  },
+])(
+  "Message.wrap_in_forward works for $case forward",
+  async ({ forwardFrom }) => {
+    const didResolver = new ExampleDIDResolver([
+      ALICE_DID_DOC,
+      BOB_DID_DOC,
+      CHARLIE_DID_DOC,
+    ]);
+
+    const secretsResolver = new ExampleSecretsResolver(ALICE_SECRETS);
+
+    const [encrypted] = await MESSAGE_SIMPLE.pack_encrypted(
+      BOB_DID,
+      null,
+      null,
+      didResolver,
+      secretsResolver,
+      { forward: false }
+    );
+
+    const forward = await Message.wrap_in_forward(
+      encrypted,
+      null,
+      BOB_DID,
+      ["did:example:charlie#key-x25519-1"],
+      null,
+      didResolver,
+      forwardFrom,
+      secretsResolver
+    );
+
+    const [unpacked, unpackMetadata] = await Message.unpack(
+      forward,
+      didResolver,
+      new ExampleSecretsResolver(CHARLIE_SECRETS),
+      {}
+    );
+
+    expect(unpacked.as_value().body).toStrictEqual({ next: BOB_DID });
+    expect(unpackMetadata.authenticated).toBe(forwardFrom != null);
+    expect(unpackMetadata.anonymous_sender).toBe(forwardFrom == null);
+
+    if (forwardFrom) {
+      expect(unpackMetadata.encrypted_from_kid).toMatch(
+        new RegExp(`^${forwardFrom}#`)
+      );
+    }
+  }
+);
+
+test("Message.wrap_in_forward handles missing secrets resolver", async () => {
+  const didResolver = new ExampleDIDResolver([
+    ALICE_DID_DOC,
+    BOB_DID_DOC,
+    CHARLIE_DID_DOC,
+  ]);
+
+  const [encrypted] = await MESSAGE_SIMPLE.pack_encrypted(
+    BOB_DID,
+    null,
+    null,
+    didResolver,
+    new ExampleSecretsResolver(ALICE_SECRETS),
+    { forward: false }
+  );
+
+  const res = Message.wrap_in_forward(
+    encrypted,
+    null,
+    BOB_DID,
+    ["did:example:charlie#key-x25519-1"],
+    null,
+    didResolver,
+    ALICE_DID
+  );
+
+  await expect(res).rejects.toThrowError(
+    "Secrets resolver is required to authenticate forward"
+  );
+});
@@ -1039,6 +1039,14 @@ public protocol DIDCommProtocol {
     func packFromPrior(msg: FromPrior, issuerKid: String?, cb: OnFromPriorPackResult )  -> ErrorCode
     func unpackFromPrior(fromPriorJwt: String, cb: OnFromPriorUnpackResult )  -> ErrorCode
     func wrapInForward(msg: String, headers: [String: String], to: String, routingKeys: [String], encAlgAnon: AnonCryptAlg, cb: OnWrapInForwardResult )  -> ErrorCode
+
+    func packPlaintextCancellable(msg: Message, cb: OnPackPlaintextResult )  -> CancelHandle
+    func packSignedCancellable(msg: Message, signBy: String, cb: OnPackSignedResult )  -> CancelHandle
+    func packEncryptedCancellable(msg: Message, to: String, from: String?, signBy: String?, options: PackEncryptedOptions, cb: OnPackEncryptedResult )  -> CancelHandle
+    func unpackCancellable(msg: String, options: UnpackOptions, cb: OnUnpackResult )  -> CancelHandle
+    func packFromPriorCancellable(msg: FromPrior, issuerKid: String?, cb: OnFromPriorPackResult )  -> CancelHandle
+    func unpackFromPriorCancellable(fromPriorJwt: String, cb: OnFromPriorUnpackResult )  -> CancelHandle
+    func wrapInForwardCancellable(msg: String, headers: [String: String], to: String, routingKeys: [String], encAlgAnon: AnonCryptAlg, cb: OnWrapInForwardResult )  -> CancelHandle
     
 }
 
@@ -1131,6 +1139,69 @@ public class DidComm: DIDCommProtocol {
 }
         return try! ErrorCode.lift(_retval)
     }
+    public func packPlaintextCancellable(msg: Message, cb: OnPackPlaintextResult )  -> CancelHandle {
+        let _retval = try!
+    rustCall() {
+    
+    didcomm_f20e_DIDComm_pack_plaintext_cancellable(self.pointer, msg.lower(), ffiConverterCallbackInterfaceOnPackPlaintextResult.lower(cb) , $0
+    )
+}
+        return try! CancelHandle.lift(_retval)
+    }
+    public func packSignedCancellable(msg: Message, signBy: String, cb: OnPackSignedResult )  -> CancelHandle {
+        let _retval = try!
+    rustCall() {
+    
+    didcomm_f20e_DIDComm_pack_signed_cancellable(self.pointer, msg.lower(), signBy.lower(), ffiConverterCallbackInterfaceOnPackSignedResult.lower(cb) , $0
+    )
+}
+        return try! CancelHandle.lift(_retval)
+    }
+    public func packEncryptedCancellable(msg: Message, to: String, from: String?, signBy: String?, options: PackEncryptedOptions, cb: OnPackEncryptedResult )  -> CancelHandle {
+        let _retval = try!
+    rustCall() {
+    
+    didcomm_f20e_DIDComm_pack_encrypted_cancellable(self.pointer, msg.lower(), to.lower(), FfiConverterOptionString.lower(from), FfiConverterOptionString.lower(signBy), options.lower(), ffiConverterCallbackInterfaceOnPackEncryptedResult.lower(cb) , $0
+    )
+}
+        return try! CancelHandle.lift(_retval)
+    }
+    public func unpackCancellable(msg: String, options: UnpackOptions, cb: OnUnpackResult )  -> CancelHandle {
+        let _retval = try!
+    rustCall() {
+    
+    didcomm_f20e_DIDComm_unpack_cancellable(self.pointer, msg.lower(), options.lower(), ffiConverterCallbackInterfaceOnUnpackResult.lower(cb) , $0
+    )
+}
+        return try! CancelHandle.lift(_retval)
+    }
+    public func packFromPriorCancellable(msg: FromPrior, issuerKid: String?, cb: OnFromPriorPackResult )  -> CancelHandle {
+        let _retval = try!
+    rustCall() {
+    
+    didcomm_f20e_DIDComm_pack_from_prior_cancellable(self.pointer, msg.lower(), FfiConverterOptionString.lower(issuerKid), ffiConverterCallbackInterfaceOnFromPriorPackResult.lower(cb) , $0
+    )
+}
+        return try! CancelHandle.lift(_retval)
+    }
+    public func unpackFromPriorCancellable(fromPriorJwt: String, cb: OnFromPriorUnpackResult )  -> CancelHandle {
+        let _retval = try!
+    rustCall() {
+    
+    didcomm_f20e_DIDComm_unpack_from_prior_cancellable(self.pointer, fromPriorJwt.lower(), ffiConverterCallbackInterfaceOnFromPriorUnpackResult.lower(cb) , $0
+    )
+}
+        return try! CancelHandle.lift(_retval)
+    }
+    public func wrapInForwardCancellable(msg: String, headers: [String: String], to: String, routingKeys: [String], encAlgAnon: AnonCryptAlg, cb: OnWrapInForwardResult )  -> CancelHandle {
+        let _retval = try!
+    rustCall() {
+    
+    didcomm_f20e_DIDComm_wrap_in_forward_cancellable(self.pointer, msg.lower(), FfiConverterDictionaryJsonValue.lower(headers), to.lower(), FfiConverterSequenceString.lower(routingKeys), encAlgAnon.lower(), ffiConverterCallbackInterfaceOnWrapInForwardResult.lower(cb) , $0
+    )
+}
+        return try! CancelHandle.lift(_retval)
+    }
     
 }
 
@@ -1171,6 +1242,76 @@ fileprivate extension DidComm {
 extension DidComm : ViaFfi, Serializable {}
 
 
+public protocol CancelHandleProtocol {
+    func cancel() 
+    
+}
+
+public class CancelHandle: CancelHandleProtocol {
+    fileprivate let pointer: UnsafeMutableRawPointer
+
+    // TODO: We'd like this to be `private` but for Swifty reasons,
+    // we can't implement `ViaFfi` without making this `required` and we can't
+    // make it `required` without making it `public`.
+    required init(unsafeFromRawPointer pointer: UnsafeMutableRawPointer) {
+        self.pointer = pointer
+    }
+
+    deinit {
+        try! rustCall { ffi_didcomm_f20e_CancelHandle_object_free(pointer, $0) }
+    }
+
+    
+
+    
+    public func cancel()  {
+        try!
+    rustCall() {
+    
+    didcomm_f20e_CancelHandle_cancel(self.pointer , $0
+    )
+}
+    }
+    
+}
+
+
+fileprivate extension CancelHandle {
+    fileprivate typealias FfiType = UnsafeMutableRawPointer
+
+    fileprivate static func read(from buf: Reader) throws -> Self {
+        let v: UInt64 = try buf.readInt()
+        // The Rust code won't compile if a pointer won't fit in a UInt64.
+        // We have to go via `UInt` because that's the thing that's the size of a pointer.
+        let ptr = UnsafeMutableRawPointer(bitPattern: UInt(truncatingIfNeeded: v))
+        if (ptr == nil) {
+            throw UniffiInternalError.unexpectedNullPointer
+        }
+        return try self.lift(ptr!)
+    }
+
+    fileprivate func write(into buf: Writer) {
+        // This fiddling is because `Int` is the thing that's the same size as a pointer.
+        // The Rust code won't compile if a pointer won't fit in a `UInt64`.
+        buf.writeInt(UInt64(bitPattern: Int64(Int(bitPattern: self.lower()))))
+    }
+
+    fileprivate static func lift(_ pointer: UnsafeMutableRawPointer) throws -> Self {
+        return Self(unsafeFromRawPointer: pointer)
+    }
+
+    fileprivate func lower() -> UnsafeMutableRawPointer {
+        return self.pointer
+    }
+}
+
+// Ideally this would be `fileprivate`, but Swift says:
+// """
+// 'private' modifier cannot be used with extensions that declare protocol conformances
+// """
+extension CancelHandle : ViaFfi, Serializable {}
+
+
 public protocol OnDIDResolverResultProtocol {
     func success(result: DidDoc? ) throws
     func error(err: ErrorKind, msg: String ) throws
@@ -82,6 +82,42 @@ RustBuffer didcomm_f20e_DIDComm_wrap_in_forward(
       void*_Nonnull ptr,RustBuffer msg,RustBuffer headers,RustBuffer to,RustBuffer routing_keys,RustBuffer enc_alg_anon,uint64_t cb,
     RustCallStatus *_Nonnull out_status
     );
+void*_Nonnull didcomm_f20e_DIDComm_pack_plaintext_cancellable(
+      void*_Nonnull ptr,RustBuffer msg,uint64_t cb,
+    RustCallStatus *_Nonnull out_status
+    );
+void*_Nonnull didcomm_f20e_DIDComm_pack_signed_cancellable(
+      void*_Nonnull ptr,RustBuffer msg,RustBuffer sign_by,uint64_t cb,
+    RustCallStatus *_Nonnull out_status
+    );
+void*_Nonnull didcomm_f20e_DIDComm_pack_encrypted_cancellable(
+      void*_Nonnull ptr,RustBuffer msg,RustBuffer to,RustBuffer from,RustBuffer sign_by,RustBuffer options,uint64_t cb,
+    RustCallStatus *_Nonnull out_status
+    );
+void*_Nonnull didcomm_f20e_DIDComm_unpack_cancellable(
+      void*_Nonnull ptr,RustBuffer msg,RustBuffer options,uint64_t cb,
+    RustCallStatus *_Nonnull out_status
+    );
+void*_Nonnull didcomm_f20e_DIDComm_pack_from_prior_cancellable(
+      void*_Nonnull ptr,RustBuffer msg,RustBuffer issuer_kid,uint64_t cb,
+    RustCallStatus *_Nonnull out_status
+    );
+void*_Nonnull didcomm_f20e_DIDComm_unpack_from_prior_cancellable(
+      void*_Nonnull ptr,RustBuffer from_prior_jwt,uint64_t cb,
+    RustCallStatus *_Nonnull out_status
+    );
+void*_Nonnull didcomm_f20e_DIDComm_wrap_in_forward_cancellable(
+      void*_Nonnull ptr,RustBuffer msg,RustBuffer headers,RustBuffer to,RustBuffer routing_keys,RustBuffer enc_alg_anon,uint64_t cb,
+    RustCallStatus *_Nonnull out_status
+    );
+void ffi_didcomm_f20e_CancelHandle_object_free(
+      void*_Nonnull ptr,
+    RustCallStatus *_Nonnull out_status
+    );
+void didcomm_f20e_CancelHandle_cancel(
+      void*_Nonnull ptr,
+    RustCallStatus *_Nonnull out_status
+    );
 void ffi_didcomm_f20e_OnDIDResolverResult_object_free(
       void*_Nonnull ptr,
     RustCallStatus *_Nonnull out_status